@@ -17,7 +17,8 @@ create table if not exists accounts (
   currency     text not null,
   network      text,
   institution  text,
-  metadata     jsonb default '{}'::jsonb
+  metadata     jsonb default '{}'::jsonb,
+  unique (name, type)
 );
 
 create index if not exists accounts_type_idx on accounts(type);
@@ -0,0 +1,2 @@
+-- pgvector stores and indexes the embeddings used for semantic search.
+create extension if not exists vector;
@@ -0,0 +1,59 @@
+create type account_type as enum ('onchain', 'offchain');
+create type category_kind as enum ('income', 'expense', 'transfer');
+create type transaction_direction as enum ('income', 'expense', 'transfer');
+
+-- Names are unique per tenant (user_id); rows without one, written by a
+-- server with no TENANT_ID, count as a tenant of their own.
+create table accounts (
+  id           uuid primary key default gen_random_uuid(),
+  user_id      text,
+  name         text not null,
+  type         account_type not null,
+  currency     text not null,
+  network      text,
+  institution  text,
+  metadata     jsonb not null default '{}'::jsonb,
+  created_at   timestamptz not null default now(),
+  unique nulls not distinct (user_id, name, type)
+);
+
+create table categories (
+  id           uuid primary key default gen_random_uuid(),
+  user_id      text,
+  name         text not null,
+  kind         category_kind not null default 'expense',
+  description  text,
+  embedding    vector(1536),
+  created_at   timestamptz not null default now(),
+  unique nulls not distinct (user_id, name)
+);
+
+create table transactions (
+  id           uuid primary key default gen_random_uuid(),
+  user_id      text,
+  account_id   uuid not null references accounts(id) on delete cascade,
+  amount       numeric not null,
+  currency     text not null,
+  direction    transaction_direction not null,
+  occurred_at  timestamptz not null,
+  description  text,
+  raw_source   text,
+  category_id  uuid references categories(id),
+  metadata     jsonb not null default '{}'::jsonb,
+  embedding    vector(1536),
+  created_at   timestamptz not null default now()
+);
+
+create index accounts_user_idx on accounts(user_id);
+create index accounts_type_idx on accounts(type);
+create index categories_user_idx on categories(user_id);
+create index transactions_user_idx on transactions(user_id);
+create index transactions_account_idx on transactions(account_id, occurred_at desc);
+create index transactions_category_idx on transactions(category_id, occurred_at desc);
+
+-- HNSW builds incrementally, so unlike ivfflat it needs no training data and
+-- works on a fresh, empty table.
+create index categories_embedding_idx
+  on categories using hnsw (embedding vector_cosine_ops);
+create index transactions_embedding_idx
+  on transactions using hnsw (embedding vector_cosine_ops);
@@ -0,0 +1,69 @@
+-- Semantic search RPCs called by the Supabase gateway. `filter_user_id` is
+-- set when the server runs with TENANT_ID.
+create or replace function search_similar_transactions(
+  query_embedding vector(1536),
+  match_count int default 5,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  account_id uuid,
+  amount numeric,
+  currency text,
+  direction text,
+  occurred_at timestamptz,
+  description text,
+  raw_source text,
+  category_id uuid,
+  created_at timestamptz,
+  similarity float
+)
+language sql stable
+as $$
+  select
+    t.id,
+    t.account_id,
+    t.amount,
+    t.currency,
+    t.direction::text,
+    t.occurred_at,
+    t.description,
+    t.raw_source,
+    t.category_id,
+    t.created_at,
+    1 - (t.embedding <=> query_embedding) as similarity
+  from transactions t
+  where t.embedding is not null
+    and (filter_user_id is null or t.user_id = filter_user_id)
+  order by t.embedding <=> query_embedding
+  limit match_count;
+$$;
+
+create or replace function search_similar_categories(
+  query_embedding vector(1536),
+  match_count int default 5,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  name text,
+  kind text,
+  description text,
+  created_at timestamptz,
+  similarity float
+)
+language sql stable
+as $$
+  select
+    c.id,
+    c.name,
+    c.kind::text,
+    c.description,
+    c.created_at,
+    1 - (c.embedding <=> query_embedding) as similarity
+  from categories c
+  where c.embedding is not null
+    and (filter_user_id is null or c.user_id = filter_user_id)
+  order by c.embedding <=> query_embedding
+  limit match_count;
+$$;
@@ -0,0 +1,45 @@
+-- Spending totals computed in Postgres so reporting tools do not have to
+-- download whole transaction tables.
+create or replace function aggregate_spending(
+  group_by text,
+  period text default null,
+  from_date timestamptz default null,
+  to_date timestamptz default null,
+  filter_account_id uuid default null,
+  filter_category_id uuid default null,
+  filter_direction text default null,
+  filter_user_id text default null
+)
+returns table (
+  key text,
+  period_start text,
+  currency text,
+  total numeric,
+  count bigint
+)
+language sql stable
+as $$
+  select
+    case group_by
+      when 'category' then t.category_id::text
+      when 'account' then t.account_id::text
+      when 'direction' then t.direction::text
+    end as key,
+    -- Formatted like the SQLite and in-memory backends' period starts.
+    case when period is not null then
+      to_char(date_trunc(period, t.occurred_at at time zone 'UTC'),
+              'YYYY-MM-DD"T"HH24:MI:SS"Z"')
+    end as period_start,
+    t.currency,
+    sum(t.amount) as total,
+    count(*) as count
+  from transactions t
+  where (from_date is null or t.occurred_at >= from_date)
+    and (to_date is null or t.occurred_at < to_date)
+    and (filter_account_id is null or t.account_id = filter_account_id)
+    and (filter_category_id is null or t.category_id = filter_category_id)
+    and (filter_direction is null or t.direction::text = filter_direction)
+    and (filter_user_id is null or t.user_id = filter_user_id)
+  group by 1, 2, 3
+  order by 2 nulls first, 4 desc;
+$$;
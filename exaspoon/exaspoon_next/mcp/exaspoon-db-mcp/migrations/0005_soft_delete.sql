@@ -0,0 +1,129 @@
+-- Soft delete: rows with `deleted_at` set are hidden from every read and RPC
+-- until `purge_deleted` removes them for good.
+alter table accounts add column deleted_at timestamptz;
+alter table categories add column deleted_at timestamptz;
+alter table transactions add column deleted_at timestamptz;
+
+create index accounts_deleted_idx on accounts(deleted_at) where deleted_at is not null;
+create index categories_deleted_idx on categories(deleted_at) where deleted_at is not null;
+create index transactions_deleted_idx on transactions(deleted_at) where deleted_at is not null;
+
+-- Purging a category leaves its transactions uncategorized.
+alter table transactions drop constraint transactions_category_id_fkey;
+alter table transactions add constraint transactions_category_id_fkey
+  foreign key (category_id) references categories(id) on delete set null;
+
+create or replace function search_similar_transactions(
+  query_embedding vector(1536),
+  match_count int default 5,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  account_id uuid,
+  amount numeric,
+  currency text,
+  direction text,
+  occurred_at timestamptz,
+  description text,
+  raw_source text,
+  category_id uuid,
+  created_at timestamptz,
+  similarity float
+)
+language sql stable
+as $$
+  select
+    t.id,
+    t.account_id,
+    t.amount,
+    t.currency,
+    t.direction::text,
+    t.occurred_at,
+    t.description,
+    t.raw_source,
+    t.category_id,
+    t.created_at,
+    1 - (t.embedding <=> query_embedding) as similarity
+  from transactions t
+  where t.embedding is not null
+    and t.deleted_at is null
+    and (filter_user_id is null or t.user_id = filter_user_id)
+  order by t.embedding <=> query_embedding
+  limit match_count;
+$$;
+
+create or replace function search_similar_categories(
+  query_embedding vector(1536),
+  match_count int default 5,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  name text,
+  kind text,
+  description text,
+  created_at timestamptz,
+  similarity float
+)
+language sql stable
+as $$
+  select
+    c.id,
+    c.name,
+    c.kind::text,
+    c.description,
+    c.created_at,
+    1 - (c.embedding <=> query_embedding) as similarity
+  from categories c
+  where c.embedding is not null
+    and c.deleted_at is null
+    and (filter_user_id is null or c.user_id = filter_user_id)
+  order by c.embedding <=> query_embedding
+  limit match_count;
+$$;
+
+create or replace function aggregate_spending(
+  group_by text,
+  period text default null,
+  from_date timestamptz default null,
+  to_date timestamptz default null,
+  filter_account_id uuid default null,
+  filter_category_id uuid default null,
+  filter_direction text default null,
+  filter_user_id text default null
+)
+returns table (
+  key text,
+  period_start text,
+  currency text,
+  total numeric,
+  count bigint
+)
+language sql stable
+as $$
+  select
+    case group_by
+      when 'category' then t.category_id::text
+      when 'account' then t.account_id::text
+      when 'direction' then t.direction::text
+    end as key,
+    -- Formatted like the SQLite and in-memory backends' period starts.
+    case when period is not null then
+      to_char(date_trunc(period, t.occurred_at at time zone 'UTC'),
+              'YYYY-MM-DD"T"HH24:MI:SS"Z"')
+    end as period_start,
+    t.currency,
+    sum(t.amount) as total,
+    count(*) as count
+  from transactions t
+  where t.deleted_at is null
+    and (from_date is null or t.occurred_at >= from_date)
+    and (to_date is null or t.occurred_at < to_date)
+    and (filter_account_id is null or t.account_id = filter_account_id)
+    and (filter_category_id is null or t.category_id = filter_category_id)
+    and (filter_direction is null or t.direction::text = filter_direction)
+    and (filter_user_id is null or t.user_id = filter_user_id)
+  group by 1, 2, 3
+  order by 2 nulls first, 4 desc;
+$$;
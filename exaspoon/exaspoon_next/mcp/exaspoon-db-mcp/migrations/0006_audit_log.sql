@@ -0,0 +1,15 @@
+-- Change history: one row per mutation made through the server. Rows are only
+-- ever inserted.
+create table audit_log (
+  id           uuid primary key default gen_random_uuid(),
+  user_id      text,
+  tool         text not null,
+  input_hash   text not null,
+  record_id    text,
+  session_id   text,
+  occurred_at  timestamptz not null default now()
+);
+
+create index audit_log_user_idx on audit_log(user_id);
+create index audit_log_occurred_idx on audit_log(occurred_at desc);
+create index audit_log_record_idx on audit_log(record_id, occurred_at desc);
@@ -0,0 +1,18 @@
+-- Supabase Realtime only streams changes for tables in its publication. Plain
+-- Postgres has no such publication, so this is a no-op there, and so is a
+-- table that is already published: by the dashboard, by an earlier run or by
+-- a publication for all tables.
+do $$
+declare
+  publication oid;
+begin
+  select oid into publication from pg_publication
+  where pubname = 'supabase_realtime' and not puballtables;
+  if publication is not null and not exists (
+    select 1 from pg_publication_rel
+    where prpubid = publication and prrelid = 'transactions'::regclass
+  ) then
+    alter publication supabase_realtime add table transactions;
+  end if;
+end
+$$;
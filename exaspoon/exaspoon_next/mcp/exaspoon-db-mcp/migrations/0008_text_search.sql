@@ -0,0 +1,55 @@
+-- Keyword search over transaction descriptions: a full-text index for whole
+-- words and a trigram index for fragments such as invoice numbers.
+create extension if not exists pg_trgm;
+
+alter table transactions add column description_tsv tsvector
+  generated always as (
+    to_tsvector('simple', coalesce(description, '') || ' ' || coalesce(raw_source, ''))
+  ) stored;
+
+create index transactions_description_tsv_idx on transactions using gin(description_tsv);
+create index transactions_description_trgm_idx on transactions using gin(description gin_trgm_ops);
+
+create or replace function search_transactions_text(
+  search_query text,
+  match_count int default 20,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  account_id uuid,
+  amount numeric,
+  currency text,
+  direction text,
+  occurred_at timestamptz,
+  description text,
+  raw_source text,
+  category_id uuid,
+  created_at timestamptz,
+  rank real
+)
+language sql stable
+as $$
+  select
+    t.id,
+    t.account_id,
+    t.amount,
+    t.currency,
+    t.direction::text,
+    t.occurred_at,
+    t.description,
+    t.raw_source,
+    t.category_id,
+    t.created_at,
+    ts_rank(t.description_tsv, websearch_to_tsquery('simple', search_query))
+      + similarity(coalesce(t.description, ''), search_query) as rank
+  from transactions t
+  where t.deleted_at is null
+    and (filter_user_id is null or t.user_id = filter_user_id)
+    and (
+      t.description_tsv @@ websearch_to_tsquery('simple', search_query)
+      or t.description ilike '%' || search_query || '%'
+    )
+  order by rank desc, t.occurred_at desc
+  limit match_count;
+$$;
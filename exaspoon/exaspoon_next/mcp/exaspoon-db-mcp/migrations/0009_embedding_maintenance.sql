@@ -0,0 +1,58 @@
+-- Rows whose embedding needs attention, for the embedding_maintenance tool:
+--   missing   - a live row with text to embed but no embedding
+--   dimension - an embedding whose length is not expected_dimension
+--   orphaned  - an embedding on a deleted row or on a row with no text
+create or replace function embedding_issues(
+  target_table text,
+  expected_dimension int,
+  after_id uuid default null,
+  batch_size int default 100,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  issue text,
+  content text
+)
+language sql stable
+as $$
+  with candidates as (
+    select
+      t.id,
+      t.deleted_at,
+      nullif(btrim(t.description), '') as content,
+      t.embedding
+    from transactions t
+    where target_table = 'transactions'
+      and (filter_user_id is null or t.user_id = filter_user_id)
+    union all
+    select
+      c.id,
+      c.deleted_at,
+      coalesce(nullif(btrim(c.description), ''), c.name) as content,
+      c.embedding
+    from categories c
+    where target_table = 'categories'
+      and (filter_user_id is null or c.user_id = filter_user_id)
+  ),
+  classified as (
+    select
+      candidates.id,
+      candidates.content,
+      case
+        when embedding is not null and (deleted_at is not null or content is null)
+          then 'orphaned'
+        when embedding is not null and vector_dims(embedding) <> expected_dimension
+          then 'dimension'
+        when embedding is null and deleted_at is null and content is not null
+          then 'missing'
+      end as issue
+    from candidates
+  )
+  select classified.id, classified.issue, classified.content
+  from classified
+  where classified.issue is not null
+    and (after_id is null or classified.id > after_id)
+  order by classified.id
+  limit batch_size;
+$$;
@@ -0,0 +1,3 @@
+-- Tool calls recorded by the call log (CALL_LOG=audit_table) keep their
+-- redacted arguments and result here; mutation events leave it null.
+alter table audit_log add column details jsonb;
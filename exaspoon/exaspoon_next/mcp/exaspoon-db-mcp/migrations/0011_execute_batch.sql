@@ -0,0 +1,169 @@
+-- Files a live transaction under a live category, or under none with a null
+-- new_category_id, and returns it with the category it had before. Returns
+-- null when no live transaction has that id.
+create or replace function categorize_transaction(
+  transaction_id uuid,
+  new_category_id uuid default null,
+  filter_user_id text default null
+)
+returns jsonb
+language plpgsql
+as $$
+declare
+  previous uuid;
+  updated jsonb;
+begin
+  select t.category_id into previous
+  from transactions t
+  where t.id = transaction_id
+    and t.deleted_at is null
+    and (filter_user_id is null or t.user_id = filter_user_id)
+  for update;
+  if not found then
+    return null;
+  end if;
+  if new_category_id is not null and not exists (
+    select 1
+    from categories c
+    where c.id = new_category_id
+      and c.deleted_at is null
+      and (filter_user_id is null or c.user_id = filter_user_id)
+  ) then
+    raise exception 'no live category with id %', new_category_id
+      using errcode = 'P0002';
+  end if;
+
+  update transactions t
+  set category_id = new_category_id
+  where t.id = transaction_id
+  returning to_jsonb(t) - 'embedding' into updated;
+  return jsonb_build_object('transaction', updated, 'previous_category_id', previous);
+end;
+$$;
+
+-- The id a batch argument stands for: `$N` is the row written by step N and
+-- `$N.I` the Ith transaction step N created; anything else is an id already.
+create or replace function batch_reference(results jsonb, reference text)
+returns uuid
+language plpgsql
+immutable
+as $$
+declare
+  parts text[];
+  written jsonb;
+  id text;
+begin
+  if reference is null or left(reference, 1) <> '$' then
+    return reference::uuid;
+  end if;
+  parts := string_to_array(substr(reference, 2), '.');
+  written := results -> parts[1]::int;
+  id := case written ->> 'op'
+    when 'upsert_account' then written #>> '{account,id}'
+    when 'categorize' then written #>> '{transaction,id}'
+    when 'create_transactions' then
+      written #>> array['transactions', coalesce(parts[2], '0'), 'id']
+  end;
+  if id is null then
+    raise exception '% names no row written by an earlier step', reference
+      using errcode = '22023';
+  end if;
+  return id::uuid;
+end;
+$$;
+
+-- Applies a batch of upsert_account, create_transactions and categorize
+-- steps in order, in the caller's transaction. A failed step rolls back every
+-- write of the batch and is reported as {failed_step, code, message} rather
+-- than raised, so the caller learns which step it was; otherwise the result
+-- is {steps} with what each step wrote.
+create or replace function execute_batch(
+  steps jsonb,
+  filter_user_id text default null
+)
+returns jsonb
+language plpgsql
+as $$
+declare
+  results jsonb := '[]'::jsonb;
+  step_index int := 0;
+  step jsonb;
+  item jsonb;
+  written jsonb;
+  inserted jsonb;
+begin
+  begin
+    for step in select value from jsonb_array_elements(steps) loop
+      case step ->> 'op'
+        when 'upsert_account' then
+          insert into accounts as a (user_id, name, type, currency, network, institution)
+          values (
+            filter_user_id,
+            step ->> 'name',
+            (step ->> 'type')::account_type,
+            step ->> 'currency',
+            step ->> 'network',
+            step ->> 'institution'
+          )
+          on conflict (user_id, name, type) do update set
+            currency = excluded.currency,
+            network = excluded.network,
+            institution = excluded.institution,
+            deleted_at = null
+          returning to_jsonb(a) into written;
+          results := results || jsonb_build_array(
+            jsonb_build_object('op', 'upsert_account', 'account', written)
+          );
+        when 'create_transactions' then
+          inserted := '[]'::jsonb;
+          for item in select value from jsonb_array_elements(step -> 'transactions') loop
+            insert into transactions as t (
+              user_id, account_id, amount, currency, direction, occurred_at,
+              description, raw_source, embedding
+            )
+            values (
+              filter_user_id,
+              batch_reference(results, item ->> 'account_id'),
+              (item ->> 'amount')::numeric,
+              item ->> 'currency',
+              (item ->> 'direction')::transaction_direction,
+              (item ->> 'occurred_at')::timestamptz,
+              item ->> 'description',
+              item ->> 'raw_source',
+              (item ->> 'embedding')::vector
+            )
+            returning to_jsonb(t) - 'embedding' into written;
+            inserted := inserted || jsonb_build_array(written);
+          end loop;
+          results := results || jsonb_build_array(
+            jsonb_build_object('op', 'create_transactions', 'transactions', inserted)
+          );
+        when 'categorize' then
+          written := categorize_transaction(
+            batch_reference(results, step ->> 'transaction_id'),
+            batch_reference(results, step ->> 'category_id'),
+            filter_user_id
+          );
+          if written is null then
+            raise exception 'no live transaction with id %', step ->> 'transaction_id'
+              using errcode = 'P0002';
+          end if;
+          results := results || jsonb_build_array(
+            jsonb_build_object('op', 'categorize', 'transaction', written -> 'transaction')
+          );
+        else
+          raise exception 'unknown batch operation %', step ->> 'op'
+            using errcode = '22023';
+      end case;
+      step_index := step_index + 1;
+    end loop;
+  exception when others then
+    return jsonb_build_object(
+      'failed_step', step_index,
+      'code', sqlstate,
+      'message', sqlerrm
+    );
+  end;
+  return jsonb_build_object('steps', results);
+end;
+$$;
@@ -0,0 +1,15 @@
+-- The Plaid item each linked account syncs from, and where its last sync
+-- left off. access_token grants read access to the bank login, so keep this
+-- table away from anything but the server's service key.
+create table bank_links (
+  account_id        uuid primary key references accounts(id) on delete cascade,
+  user_id           text,
+  item_id           text not null,
+  access_token      text not null,
+  plaid_account_id  text,
+  cursor            text,
+  updated_at        timestamptz not null default now(),
+  unique nulls not distinct (user_id, account_id)
+);
+
+create index bank_links_user_idx on bank_links(user_id);
@@ -0,0 +1,2 @@
+-- Accounts held at a crypto exchange, synced by sync_exchange.
+alter type account_type add value if not exists 'exchange';
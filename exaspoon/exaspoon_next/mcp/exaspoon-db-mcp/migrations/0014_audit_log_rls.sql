@@ -0,0 +1,4 @@
+-- The server reads and writes the change history with the service key, which
+-- bypasses row level security. With it on and no policies, the anon key and
+-- end-user tokens can neither read the history nor rewrite it.
+alter table audit_log enable row level security;
@@ -0,0 +1,47 @@
+-- Keyword search requires every word of the query, each in the description or
+-- the raw source, as a fragment matched literally: `%`, `_` and `\` in a word
+-- are not wildcards. The full-text index now only ranks the matches.
+create or replace function search_transactions_text(
+  search_query text,
+  match_count int default 20,
+  filter_user_id text default null
+)
+returns table (
+  id uuid,
+  account_id uuid,
+  amount numeric,
+  currency text,
+  direction text,
+  occurred_at timestamptz,
+  description text,
+  raw_source text,
+  category_id uuid,
+  created_at timestamptz,
+  rank real
+)
+language sql stable
+as $$
+  select
+    t.id,
+    t.account_id,
+    t.amount,
+    t.currency,
+    t.direction::text,
+    t.occurred_at,
+    t.description,
+    t.raw_source,
+    t.category_id,
+    t.created_at,
+    ts_rank(t.description_tsv, websearch_to_tsquery('simple', search_query))
+      + similarity(coalesce(t.description, ''), search_query) as rank
+  from transactions t
+  where t.deleted_at is null
+    and (filter_user_id is null or t.user_id = filter_user_id)
+    and coalesce(t.description, '') || ' ' || coalesce(t.raw_source, '') ilike all (
+      select '%' || replace(replace(replace(term, '\', '\\'), '%', '\%'), '_', '\_') || '%'
+      from regexp_split_to_table(btrim(search_query), '\s+') as term
+      where term <> ''
+    )
+  order by rank desc, t.occurred_at desc
+  limit match_count;
+$$;
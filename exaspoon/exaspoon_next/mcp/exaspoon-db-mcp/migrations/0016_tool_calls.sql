@@ -0,0 +1,26 @@
+-- Tool calls recorded by the call log (CALL_LOG=table), kept apart from the
+-- change history so list_audit_events only shows mutations. Calls recorded in
+-- audit_log before this migration move here.
+create table tool_calls (
+  id           uuid primary key default gen_random_uuid(),
+  user_id      text,
+  tool         text not null,
+  input_hash   text not null,
+  record_id    text,
+  session_id   text,
+  occurred_at  timestamptz not null default now(),
+  details      jsonb
+);
+
+create index tool_calls_user_idx on tool_calls(user_id);
+create index tool_calls_occurred_idx on tool_calls(occurred_at desc);
+
+-- Written with the service key only, like audit_log.
+alter table tool_calls enable row level security;
+
+insert into tool_calls (user_id, tool, input_hash, record_id, session_id, occurred_at, details)
+select user_id, tool, input_hash, record_id, session_id, occurred_at, details
+from audit_log
+where details is not null;
+
+delete from audit_log where details is not null;
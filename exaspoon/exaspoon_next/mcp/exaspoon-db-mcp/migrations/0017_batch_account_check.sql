@@ -0,0 +1,110 @@
+-- create_transactions steps of execute_batch only write to a live account of
+-- the caller's tenant, rather than to any account id they are given.
+
+-- Applies a batch of upsert_account, create_transactions and categorize
+-- steps in order, in the caller's transaction. A failed step rolls back every
+-- write of the batch and is reported as {failed_step, code, message} rather
+-- than raised, so the caller learns which step it was; otherwise the result
+-- is {steps} with what each step wrote.
+create or replace function execute_batch(
+  steps jsonb,
+  filter_user_id text default null
+)
+returns jsonb
+language plpgsql
+as $$
+declare
+  results jsonb := '[]'::jsonb;
+  step_index int := 0;
+  step jsonb;
+  item jsonb;
+  written jsonb;
+  inserted jsonb;
+  account uuid;
+begin
+  begin
+    for step in select value from jsonb_array_elements(steps) loop
+      case step ->> 'op'
+        when 'upsert_account' then
+          insert into accounts as a (user_id, name, type, currency, network, institution)
+          values (
+            filter_user_id,
+            step ->> 'name',
+            (step ->> 'type')::account_type,
+            step ->> 'currency',
+            step ->> 'network',
+            step ->> 'institution'
+          )
+          on conflict (user_id, name, type) do update set
+            currency = excluded.currency,
+            network = excluded.network,
+            institution = excluded.institution,
+            deleted_at = null
+          returning to_jsonb(a) into written;
+          results := results || jsonb_build_array(
+            jsonb_build_object('op', 'upsert_account', 'account', written)
+          );
+        when 'create_transactions' then
+          inserted := '[]'::jsonb;
+          for item in select value from jsonb_array_elements(step -> 'transactions') loop
+            account := batch_reference(results, item ->> 'account_id');
+            if not exists (
+              select 1
+              from accounts a
+              where a.id = account
+                and a.deleted_at is null
+                and (filter_user_id is null or a.user_id = filter_user_id)
+            ) then
+              raise exception 'no live account with id %', item ->> 'account_id'
+                using errcode = 'P0002';
+            end if;
+            insert into transactions as t (
+              user_id, account_id, amount, currency, direction, occurred_at,
+              description, raw_source, embedding
+            )
+            values (
+              filter_user_id,
+              account,
+              (item ->> 'amount')::numeric,
+              item ->> 'currency',
+              (item ->> 'direction')::transaction_direction,
+              (item ->> 'occurred_at')::timestamptz,
+              item ->> 'description',
+              item ->> 'raw_source',
+              (item ->> 'embedding')::vector
+            )
+            returning to_jsonb(t) - 'embedding' into written;
+            inserted := inserted || jsonb_build_array(written);
+          end loop;
+          results := results || jsonb_build_array(
+            jsonb_build_object('op', 'create_transactions', 'transactions', inserted)
+          );
+        when 'categorize' then
+          written := categorize_transaction(
+            batch_reference(results, step ->> 'transaction_id'),
+            batch_reference(results, step ->> 'category_id'),
+            filter_user_id
+          );
+          if written is null then
+            raise exception 'no live transaction with id %', step ->> 'transaction_id'
+              using errcode = 'P0002';
+          end if;
+          results := results || jsonb_build_array(
+            jsonb_build_object('op', 'categorize', 'transaction', written -> 'transaction')
+          );
+        else
+          raise exception 'unknown batch operation %', step ->> 'op'
+            using errcode = '22023';
+      end case;
+      step_index := step_index + 1;
+    end loop;
+  exception when others then
+    return jsonb_build_object(
+      'failed_step', step_index,
+      'code', sqlstate,
+      'message', sqlerrm
+    );
+  end;
+  return jsonb_build_object('steps', results);
+end;
+$$;
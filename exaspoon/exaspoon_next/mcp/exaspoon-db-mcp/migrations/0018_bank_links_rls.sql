@@ -0,0 +1,5 @@
+-- bank_links holds the items the server syncs and their Plaid access tokens,
+-- which the server encrypts before storing. Like the audit log, it is only
+-- for the service key, which bypasses row level security: with it on and no
+-- policies, the anon key and end-user tokens can neither read nor change it.
+alter table bank_links enable row level security;
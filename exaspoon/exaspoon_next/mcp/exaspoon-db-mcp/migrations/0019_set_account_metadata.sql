@@ -0,0 +1,22 @@
+-- Sets one key of an account's metadata in place, so syncs that save their
+-- cursors under different keys at the same time do not overwrite each
+-- other's. Returns whether a live account of the caller's tenant was found.
+create or replace function set_account_metadata(
+  target_account uuid,
+  metadata_key text,
+  metadata_value jsonb,
+  filter_user_id text default null
+)
+returns boolean
+language plpgsql
+as $$
+begin
+  update accounts a
+  set metadata = coalesce(a.metadata, '{}'::jsonb)
+    || jsonb_build_object(metadata_key, metadata_value)
+  where a.id = target_account
+    and a.deleted_at is null
+    and (filter_user_id is null or a.user_id = filter_user_id);
+  return found;
+end;
+$$;
@@ -0,0 +1,51 @@
+use rmcp::model::Meta;
+use std::future::Future;
+
+/// `_meta` key a client uses to pass the end user's Supabase access token
+/// with a tool call.
+pub const ACCESS_TOKEN_META_KEY: &str = "supabase_access_token";
+
+tokio::task_local! {
+    static CURRENT: AuthContext;
+}
+
+/// Who a tool call runs as. The server scopes each call with the context taken
+/// from its request, and the Supabase gateway forwards the token so that Row
+/// Level Security applies to that user instead of the service role.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthContext {
+    pub access_token: Option<String>,
+}
+
+impl AuthContext {
+    pub fn new(access_token: impl Into<String>) -> Self {
+        Self {
+            access_token: Some(access_token.into()),
+        }
+    }
+
+    /// Reads the access token from a request's `_meta`, accepting an optional
+    /// `Bearer ` prefix.
+    pub fn from_meta(meta: &Meta) -> Self {
+        let access_token = meta
+            .0
+            .get(ACCESS_TOKEN_META_KEY)
+            .and_then(|value| value.as_str())
+            .map(|value| value.trim())
+            .map(|value| value.strip_prefix("Bearer ").unwrap_or(value).trim())
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
+        Self { access_token }
+    }
+
+    /// Runs `future` with this context as the current one.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// The context of the tool call being served, or an empty one outside of
+    /// any call.
+    pub fn current() -> Self {
+        CURRENT.try_with(Clone::clone).unwrap_or_default()
+    }
+}
@@ -0,0 +1,249 @@
+//! Batches of writes applied as one unit by `execute_batch`: accounts,
+//! transactions and categorizations that land together or not at all.
+//!
+//! Backends with transactions apply a batch inside one. Others fall back to
+//! [`execute_with_compensation`], which applies the steps one by one and, when
+//! one fails, undoes the ones before it: rows it created are soft-deleted,
+//! accounts it updated get their old values back and transactions it filed
+//! return to their old category.
+
+use crate::{
+    models::{
+        Account, BatchStepResult, CategorizeInput, CreateTransactionInput, ListAccountsInput,
+        RecordKind, UpsertAccountInput,
+    },
+    supabase::Database,
+};
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use tracing::{error, info, warn};
+
+/// Most operations `execute_batch` accepts in one call.
+pub const MAX_BATCH_OPERATIONS: usize = 50;
+
+/// An operation of a batch, ready to apply: transactions carry their
+/// embeddings, and ids may still be [`Reference`]s.
+#[derive(Debug, Clone)]
+pub enum BatchStep {
+    UpsertAccount(UpsertAccountInput),
+    CreateTransactions(Vec<(CreateTransactionInput, Option<Vec<f32>>)>),
+    Categorize(CategorizeInput),
+}
+
+/// A batch that stopped at `step`, with what went wrong there.
+#[derive(Debug)]
+pub struct BatchFailed {
+    /// Index of the failed step, counting from 0.
+    pub step: usize,
+    /// Whether every write of the earlier steps was undone.
+    pub rolled_back: bool,
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for BatchFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "batch step {} failed: {}", self.step, self.source)?;
+        if !self.rolled_back {
+            write!(f, "; earlier steps could not all be undone")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BatchFailed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// A step named a row that does not exist or is deleted.
+#[derive(Debug)]
+pub struct MissingRow(pub String);
+
+impl fmt::Display for MissingRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for MissingRow {}
+
+/// An id argument standing for a row an earlier step wrote: `$N` for the
+/// row of step `N`, or `$N.I` for the `I`th transaction it created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reference {
+    pub step: usize,
+    pub item: Option<usize>,
+}
+
+impl Reference {
+    /// The reference `value` holds, or `None` for a plain id.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let Some(reference) = value.trim().strip_prefix('$') else {
+            return Ok(None);
+        };
+        let (step, item) = match reference.split_once('.') {
+            Some((step, item)) => (step, Some(item)),
+            None => (reference, None),
+        };
+        let malformed = || anyhow!("{value} is not a reference such as $0 or $1.2");
+        let step = step.parse().map_err(|_| malformed())?;
+        let item = item.map(str::parse).transpose().map_err(|_| malformed())?;
+        Ok(Some(Self { step, item }))
+    }
+}
+
+/// The id `value` stands for once the steps in `done` have run.
+pub fn resolve(value: &str, done: &[BatchStepResult]) -> Result<String> {
+    let Some(reference) = Reference::parse(value)? else {
+        return Ok(value.to_string());
+    };
+    let Some(result) = done.get(reference.step) else {
+        bail!(
+            "{value} refers to step {}, which has not run yet",
+            reference.step
+        );
+    };
+    let id = match (result, reference.item) {
+        (BatchStepResult::UpsertAccount { account }, None) => Some(&account.id),
+        (BatchStepResult::Categorize { transaction }, None) => Some(&transaction.id),
+        (BatchStepResult::CreateTransactions { transactions }, item) => transactions
+            .get(item.unwrap_or(0))
+            .map(|transaction| &transaction.id),
+        _ => None,
+    };
+    id.cloned()
+        .ok_or_else(|| anyhow!("{value} names no row written by step {}", reference.step))
+}
+
+/// How to take back one write of a batch.
+enum Undo {
+    Delete(RecordKind, String),
+    Restore(Account),
+    Recategorize {
+        id: String,
+        category_id: Option<String>,
+    },
+}
+
+/// Applies `steps` one by one and, when one fails, undoes the writes made so
+/// far, reporting the failure as a [`BatchFailed`]. Unlike a transaction,
+/// other callers may see the writes before they are undone.
+pub async fn execute_with_compensation<D: Database + ?Sized>(
+    db: &D,
+    steps: &[BatchStep],
+) -> Result<Vec<BatchStepResult>> {
+    let mut done = Vec::with_capacity(steps.len());
+    let mut undo = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        match apply(db, step, &done, &mut undo).await {
+            Ok(result) => done.push(result),
+            Err(source) => {
+                warn!("Batch step {} failed; undoing {} writes", index, undo.len());
+                let rolled_back = compensate(db, undo).await;
+                return Err(BatchFailed {
+                    step: index,
+                    rolled_back,
+                    source,
+                }
+                .into());
+            }
+        }
+    }
+    info!("Applied batch of {} steps", done.len());
+    Ok(done)
+}
+
+async fn apply<D: Database + ?Sized>(
+    db: &D,
+    step: &BatchStep,
+    done: &[BatchStepResult],
+    undo: &mut Vec<Undo>,
+) -> Result<BatchStepResult> {
+    match step {
+        BatchStep::UpsertAccount(input) => {
+            let previous = existing_account(db, input).await?;
+            let account = db.upsert_account(input).await?;
+            undo.push(match previous {
+                Some(previous) => Undo::Restore(previous),
+                None => Undo::Delete(RecordKind::Account, account.id.clone()),
+            });
+            Ok(BatchStepResult::UpsertAccount { account })
+        }
+        BatchStep::CreateTransactions(rows) => {
+            let mut transactions = Vec::with_capacity(rows.len());
+            for (input, embedding) in rows {
+                let mut input = input.clone();
+                input.account_id = resolve(&input.account_id, done)?;
+                let transaction = db.insert_transaction(&input, embedding.clone()).await?;
+                undo.push(Undo::Delete(
+                    RecordKind::Transaction,
+                    transaction.id.clone(),
+                ));
+                transactions.push(transaction);
+            }
+            Ok(BatchStepResult::CreateTransactions { transactions })
+        }
+        BatchStep::Categorize(input) => {
+            let id = resolve(&input.transaction_id, done)?;
+            let category_id = resolve(&input.category_id, done)?;
+            let categorized = db
+                .categorize_transaction(&id, Some(&category_id))
+                .await?
+                .ok_or_else(|| MissingRow(format!("no live transaction with id {id}")))?;
+            undo.push(Undo::Recategorize {
+                id,
+                category_id: categorized.previous_category_id,
+            });
+            Ok(BatchStepResult::Categorize {
+                transaction: categorized.transaction,
+            })
+        }
+    }
+}
+
+/// The live account `input` would update rather than create.
+async fn existing_account<D: Database + ?Sized>(
+    db: &D,
+    input: &UpsertAccountInput,
+) -> Result<Option<Account>> {
+    let candidates = db
+        .list_accounts(&ListAccountsInput {
+            r#type: Some(input.r#type),
+            search: Some(input.name.clone()),
+            ..ListAccountsInput::default()
+        })
+        .await?;
+    Ok(candidates
+        .into_iter()
+        .find(|account| account.name == input.name))
+}
+
+/// Takes back `undo` newest first, returning whether all of it was.
+async fn compensate<D: Database + ?Sized>(db: &D, undo: Vec<Undo>) -> bool {
+    let mut complete = true;
+    for write in undo.into_iter().rev() {
+        let undone = match &write {
+            Undo::Delete(kind, id) => db.soft_delete(*kind, id).await.map(|_| ()),
+            Undo::Restore(account) => db
+                .upsert_account(&UpsertAccountInput {
+                    name: account.name.clone(),
+                    r#type: account.r#type,
+                    currency: account.currency.clone(),
+                    network: account.network.clone(),
+                    institution: account.institution.clone(),
+                })
+                .await
+                .map(|_| ()),
+            Undo::Recategorize { id, category_id } => db
+                .categorize_transaction(id, category_id.as_deref())
+                .await
+                .map(|_| ()),
+        };
+        if let Err(err) = undone {
+            error!("Failed to undo a batch write: {}", err);
+            complete = false;
+        }
+    }
+    complete
+}
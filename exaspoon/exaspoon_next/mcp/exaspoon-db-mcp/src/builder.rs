@@ -0,0 +1,560 @@
+//! Embedding the MCP server in another program: pick the database, embedder,
+//! tools and transport in code, or take them from an [`AppConfig`], then
+//! serve until a signal or until the host program says to stop.
+//!
+//! ```no_run
+//! # async fn run(
+//! #     database: std::sync::Arc<dyn exaspoon_db_mcp::supabase::Database>,
+//! #     embedder: std::sync::Arc<dyn exaspoon_db_mcp::embedding::Embedder>,
+//! # ) -> anyhow::Result<()> {
+//! use exaspoon_db_mcp::builder::ExaspoonServerBuilder;
+//! use exaspoon_db_mcp::config::Transport;
+//! use exaspoon_db_mcp::server::ToolGroup;
+//!
+//! ExaspoonServerBuilder::new(database, embedder)
+//!     .with_tool_groups(vec![ToolGroup::Core, ToolGroup::Analytics])
+//!     .with_read_only()
+//!     .with_transport(Transport::Http)
+//!     .with_bind_address("127.0.0.1:8931".parse()?)
+//!     .build()
+//!     .serve()
+//!     .await
+//! # }
+//! ```
+
+use crate::{
+    call_log::CallLog,
+    config::{AppConfig, Transport, DEFAULT_BIND_ADDRESS},
+    daemon,
+    embedding::{Embedder, EmbedderFactory},
+    exchange::{binance::BinanceClient, coinbase::CoinbaseClient},
+    fx::FxClient,
+    gocardless::GoCardlessClient,
+    models::Providers,
+    onchain::{bitcoin::BlockbookClient, ethereum::EtherscanClient, solana::SolanaClient},
+    origin::AllowedHosts,
+    plaid::PlaidClient,
+    prices::CoinGeckoClient,
+    receipt::ReceiptReader,
+    redaction::Redactor,
+    server::{ExaspoonDbServer, ToolGroup},
+    session::DEFAULT_SESSION_IDLE_TIMEOUT_SECS,
+    sheets::{google::GoogleSheetsClient, notion::NotionClient},
+    shutdown::{self, DEFAULT_SHUTDOWN_TIMEOUT_MS},
+    socket, stdio,
+    supabase::Database,
+    ynab::YnabClient,
+};
+use anyhow::{bail, Context, Result};
+use rmcp::ServiceExt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Assembles an [`ExaspoonServer`] from a database and an embedder, with
+/// every tool and the stdio transport unless told otherwise.
+#[derive(Clone)]
+pub struct ExaspoonServerBuilder {
+    database: Arc<dyn Database>,
+    server: ExaspoonDbServer,
+    transport: Transport,
+    options: ServeOptions,
+}
+
+/// A configured server, ready to serve its transport.
+#[derive(Clone)]
+pub struct ExaspoonServer {
+    server: ExaspoonDbServer,
+    transport: Transport,
+    options: ServeOptions,
+    created: Instant,
+}
+
+#[derive(Debug, Clone)]
+struct ServeOptions {
+    bind_address: SocketAddr,
+    allowed_hosts: AllowedHosts,
+    session_idle_timeout: Option<Duration>,
+    stdio_idle_timeout: Option<Duration>,
+    shutdown_timeout: Duration,
+    notify_service_manager: bool,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            bind_address: DEFAULT_BIND_ADDRESS.parse().unwrap(),
+            allowed_hosts: AllowedHosts::default(),
+            session_idle_timeout: Some(Duration::from_secs(DEFAULT_SESSION_IDLE_TIMEOUT_SECS)),
+            stdio_idle_timeout: None,
+            shutdown_timeout: Duration::from_millis(DEFAULT_SHUTDOWN_TIMEOUT_MS),
+            notify_service_manager: false,
+        }
+    }
+}
+
+/// Opens the database `DATABASE_BACKEND` names.
+pub fn open_database(config: &AppConfig) -> Result<Arc<dyn Database>> {
+    match config.database_backend.as_str() {
+        #[cfg(feature = "supabase")]
+        "supabase" => {
+            let host = &config.supabase_url;
+            info!(
+                "Supabase URL: {}",
+                &host[..host.find('.').unwrap_or(host.len())]
+            );
+            Ok(Arc::new(crate::supabase::SupabaseGateway::new(config)?))
+        }
+        #[cfg(not(feature = "supabase"))]
+        "supabase" => {
+            bail!("DATABASE_BACKEND=supabase requires building with the `supabase` feature")
+        }
+        #[cfg(feature = "memory-backend")]
+        "memory" => Ok(Arc::new(crate::memory::MemoryDatabase::new())),
+        #[cfg(not(feature = "memory-backend"))]
+        "memory" => {
+            bail!("DATABASE_BACKEND=memory requires building with the `memory-backend` feature")
+        }
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            info!("SQLite path: {}", config.sqlite_path);
+            Ok(Arc::new(crate::sqlite::SqliteDatabase::open(
+                &config.sqlite_path,
+            )?))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => bail!("DATABASE_BACKEND=sqlite requires building with the `sqlite` feature"),
+        other => bail!("unknown DATABASE_BACKEND {other:?} (expected supabase, sqlite or memory)"),
+    }
+}
+
+impl ExaspoonServerBuilder {
+    pub fn new(database: Arc<dyn Database>, embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            server: ExaspoonDbServer::new(database.clone(), embedder),
+            database,
+            transport: Transport::Stdio,
+            options: ServeOptions::default(),
+        }
+    }
+
+    /// Opens the database and embedding provider `config` names and applies
+    /// the rest of it, as the `exaspoon-db-mcp` binary does.
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        let database = open_database(config)?;
+        let embedder = EmbedderFactory::default().build(config)?;
+        Self::new(database, embedder).with_config(config)
+    }
+
+    /// Applies the tool, limit and transport settings of `config`.
+    pub fn with_config(mut self, config: &AppConfig) -> Result<Self> {
+        let mut server = self
+            .server
+            .with_rate_limit(config.rate_limit)
+            .with_timeouts(config.tool_timeouts.clone())
+            .with_concurrency_limits(config.concurrency)
+            .with_max_sessions(config.max_sessions)
+            .with_identity(config.identity.clone())
+            .with_locale(config.locale)
+            .with_providers(Providers {
+                database_backend: config.database_backend.clone(),
+                embedding_provider: config.embedding_provider.clone(),
+                embedding_model: config.embedding_model.clone(),
+            });
+        if config.admin_tools {
+            info!("Admin tools enabled");
+            server = server.with_admin_tools();
+        }
+        if config.demo_seed {
+            info!("seed_demo_data enabled");
+            server = server.with_demo_seed();
+        }
+        if config.dry_run {
+            info!("Dry run enabled; mutating tools will not write");
+            server = server.with_dry_run();
+        }
+        if let Some(window) = config.confirmation_window {
+            info!("Destructive tools need confirmation within {:?}", window);
+            server = server.with_confirmation(window);
+        }
+        if !config.rpc_allowlist.is_empty() {
+            info!("call_rpc allowed for: {:?}", config.rpc_allowlist);
+            server = server.with_rpc_allowlist(config.rpc_allowlist.clone());
+        }
+        if let Some(plaid) = &config.plaid {
+            info!("Bank sync through Plaid enabled");
+            server = server.with_plaid(PlaidClient::new(plaid, &config.http)?);
+        }
+        if let Some(gocardless) = &config.gocardless {
+            info!("Open banking sync through GoCardless enabled");
+            server = server.with_gocardless(GoCardlessClient::new(gocardless, &config.http)?);
+        }
+        if let Some(etherscan) = &config.etherscan {
+            info!("Ethereum address sync through Etherscan enabled");
+            server = server.with_etherscan(EtherscanClient::new(etherscan, &config.http)?);
+        }
+        if let Some(blockbook) = &config.blockbook {
+            info!("Bitcoin address sync through Blockbook enabled");
+            server = server.with_blockbook(BlockbookClient::new(blockbook, &config.http)?);
+        }
+        if let Some(solana) = &config.solana {
+            info!("Solana wallet sync through Solana RPC enabled");
+            server = server.with_solana(SolanaClient::new(solana, &config.http)?);
+        }
+        if let Some(binance) = &config.binance {
+            info!("Exchange sync from Binance enabled");
+            server = server.with_binance(BinanceClient::new(binance, &config.http)?);
+        }
+        if let Some(coinbase) = &config.coinbase {
+            info!("Exchange sync from Coinbase enabled");
+            server = server.with_coinbase(CoinbaseClient::new(coinbase, &config.http)?);
+        }
+        if let Some(fx) = &config.fx {
+            info!(
+                "Exchange rates from {} enabled, base currency {}",
+                fx.provider, fx.base_currency
+            );
+            server = server.with_fx(FxClient::new(fx, &config.http)?);
+        }
+        if let Some(prices) = &config.prices {
+            info!("Asset prices from CoinGecko enabled");
+            server = server.with_prices(CoinGeckoClient::new(prices, &config.http)?);
+        }
+        if let Some(receipt) = &config.receipt {
+            info!("Receipt reading with {} enabled", receipt.model);
+            server = server.with_receipts(ReceiptReader::new(receipt, &config.http)?);
+        }
+        if let Some(google_sheets) = &config.google_sheets {
+            info!("Google Sheets export enabled");
+            server =
+                server.with_google_sheets(GoogleSheetsClient::new(google_sheets, &config.http)?);
+        }
+        if let Some(notion) = &config.notion {
+            info!("Notion export enabled");
+            server = server.with_notion(NotionClient::new(notion, &config.http)?);
+        }
+        if let Some(ynab) = &config.ynab {
+            info!("YNAB import and export enabled");
+            server = server.with_ynab(YnabClient::new(ynab, &config.http)?);
+        }
+        if config.read_only {
+            info!("Read-only mode; tools that write are hidden");
+            server = server.with_read_only();
+        }
+        if config.tool_groups != ToolGroup::ALL {
+            info!("Tool groups mounted: {:?}", config.tool_groups);
+            server = server.with_tool_groups(config.tool_groups.clone());
+        }
+        if !config.tool_names.is_identity() {
+            info!("Tools renamed: {:?}", config.tool_names);
+            server = server.with_tool_names(config.tool_names.clone());
+        }
+        if !config.disabled_tools.is_empty() {
+            info!("Tools disabled: {:?}", config.disabled_tools);
+            server = server.with_disabled_tools(config.disabled_tools.clone());
+        }
+        if let Some(target) = &config.call_log {
+            info!("Recording tool calls to {:?}", target);
+            let redactor = Redactor::new(&config.pii_redaction_patterns)?;
+            let redacted_fields = config.call_log_redacted_fields.clone();
+            let database = self.database.clone();
+            let call_log = CallLog::open(target, database, redacted_fields, redactor)?;
+            server = server.with_call_log(call_log);
+        }
+        self.server = server;
+        self.transport = config.transport.clone();
+        self.options = ServeOptions {
+            bind_address: config.bind_address,
+            allowed_hosts: AllowedHosts::new(config.allowed_hosts.clone()),
+            session_idle_timeout: config.session_idle_timeout,
+            stdio_idle_timeout: config.stdio_idle_timeout,
+            shutdown_timeout: config.shutdown_timeout,
+            ..self.options
+        };
+        Ok(self)
+    }
+
+    /// Mounts only the tools of `groups`.
+    pub fn with_tool_groups(self, groups: Vec<ToolGroup>) -> Self {
+        self.with_server(|server| server.with_tool_groups(groups))
+    }
+
+    /// Hides tools matching any of `patterns`; see
+    /// [`ExaspoonDbServer::with_disabled_tools`].
+    pub fn with_disabled_tools(self, patterns: Vec<String>) -> Self {
+        self.with_server(|server| server.with_disabled_tools(patterns))
+    }
+
+    /// Exposes only the tools that cannot write.
+    pub fn with_read_only(self) -> Self {
+        self.with_server(ExaspoonDbServer::with_read_only)
+    }
+
+    /// Lists the admin tools as well.
+    pub fn with_admin_tools(self) -> Self {
+        self.with_server(ExaspoonDbServer::with_admin_tools)
+    }
+
+    /// Applies any other [`ExaspoonDbServer`] setting, such as a call log or
+    /// rate limits.
+    pub fn with_server(
+        mut self,
+        configure: impl FnOnce(ExaspoonDbServer) -> ExaspoonDbServer,
+    ) -> Self {
+        self.server = configure(self.server);
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Where the HTTP and WebSocket transports listen.
+    pub fn with_bind_address(mut self, address: SocketAddr) -> Self {
+        self.options.bind_address = address;
+        self
+    }
+
+    /// Host names the HTTP and WebSocket transports answer to; see
+    /// [`AllowedHosts`].
+    pub fn with_allowed_hosts(mut self, hosts: AllowedHosts) -> Self {
+        self.options.allowed_hosts = hosts;
+        self
+    }
+
+    /// How long an HTTP session may go without a request, if limited.
+    pub fn with_session_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.session_idle_timeout = timeout;
+        self
+    }
+
+    /// How long a stdio client may send nothing before the server stops, if
+    /// limited.
+    pub fn with_stdio_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.stdio_idle_timeout = timeout;
+        self
+    }
+
+    /// How long stopping waits for running tool calls.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.options.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Reports readiness and stopping to a systemd `NOTIFY_SOCKET`, for a
+    /// process whose service is this server.
+    pub fn with_service_notifications(mut self) -> Self {
+        self.options.notify_service_manager = true;
+        self
+    }
+
+    pub fn build(self) -> ExaspoonServer {
+        ExaspoonServer {
+            server: self.server,
+            transport: self.transport,
+            options: self.options,
+            created: Instant::now(),
+        }
+    }
+}
+
+impl ExaspoonServer {
+    /// The MCP handler, for calling tools directly or serving it over a
+    /// transport of the host program's own.
+    pub fn handler(&self) -> &ExaspoonDbServer {
+        &self.server
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    /// Serves the transport until SIGTERM or SIGINT, or until the stdio
+    /// client goes away.
+    pub async fn serve(self) -> Result<()> {
+        let shutdown = CancellationToken::new();
+        let signalled = shutdown.clone();
+        tokio::spawn(async move {
+            let signal = shutdown::signal().await;
+            info!("Received {}", signal);
+            signalled.cancel();
+        });
+        self.serve_until(shutdown).await
+    }
+
+    /// Serves the transport until `shutdown` is cancelled, or until the stdio
+    /// client goes away. Running tool calls are let finish, up to the
+    /// shutdown timeout, before the transport closes.
+    pub async fn serve_until(self, shutdown: CancellationToken) -> Result<()> {
+        let address = self.options.bind_address;
+        match self.transport.clone() {
+            Transport::Stdio => self.serve_stdio(shutdown).await,
+            Transport::Http => {
+                let listener = bind_tcp(&address.to_string()).await?;
+                self.serve_http(listener, shutdown).await
+            }
+            Transport::WebSocket => {
+                let listener = bind_tcp(&address.to_string()).await?;
+                self.serve_websocket(listener, shutdown).await
+            }
+            Transport::Tcp(address) => {
+                let listener = bind_tcp(&address).await?;
+                let stop = self.stop_on(shutdown);
+                socket::serve_tcp(self.server, listener, stop).await
+            }
+            Transport::Unix(path) => self.serve_unix(&path, shutdown).await,
+        }
+    }
+
+    async fn serve_stdio(self, shutdown: CancellationToken) -> Result<()> {
+        let running = self.server.clone();
+        let (input, output, client) = stdio::monitor(tokio::io::stdin(), tokio::io::stdout());
+
+        // On shutdown, when the client closes stdio or after it has been
+        // silent for the idle timeout, let running calls finish before
+        // closing the transport, so their results still reach the client.
+        let stop = CancellationToken::new();
+        let stopping = stop.clone();
+        let draining = running.clone();
+        let options = self.options.clone();
+        tokio::spawn(async move {
+            let idle_timeout = options.stdio_idle_timeout;
+            let reason = tokio::select! {
+                () = shutdown.cancelled() => "Shutdown requested".to_string(),
+                closed = client.closed() => format!("Client {closed}"),
+                () = client.idle(idle_timeout) => {
+                    format!("Client idle for {:?}", idle_timeout.unwrap_or_default())
+                }
+            };
+            info!("{}, shutting down", reason);
+            if options.notify_service_manager {
+                daemon::notify_stopping();
+            }
+            // Requests read just before the client went away may not have
+            // reached a handler yet, so wait for their answers as well as
+            // for the calls already running.
+            let deadline = Instant::now() + options.shutdown_timeout;
+            let _ = tokio::time::timeout_at(deadline.into(), client.answered()).await;
+            draining
+                .shut_down(deadline.saturating_duration_since(Instant::now()))
+                .await;
+            stopping.cancel();
+        });
+
+        let service = match self
+            .server
+            .clone()
+            .serve_with_ct((input, output), stop.clone())
+            .await
+        {
+            Ok(service) => service,
+            Err(err) if stop.is_cancelled() => {
+                info!("Stopped before the client initialized: {}", err);
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        self.started();
+
+        info!("Waiting for MCP connections");
+        let reason = service.waiting().await?;
+        info!("MCP service stopped: {:?}", reason);
+
+        // The transport may have failed with calls still running.
+        running.shut_down(self.options.shutdown_timeout).await;
+        Ok(())
+    }
+
+    #[cfg(feature = "http")]
+    async fn serve_http(self, listener: TcpListener, shutdown: CancellationToken) -> Result<()> {
+        let stop = self.stop_on(shutdown);
+        let options = self.options;
+        crate::http::serve(
+            self.server,
+            listener,
+            options.session_idle_timeout,
+            options.allowed_hosts,
+            stop,
+        )
+        .await
+    }
+
+    #[cfg(not(feature = "http"))]
+    async fn serve_http(self, _listener: TcpListener, _shutdown: CancellationToken) -> Result<()> {
+        bail!("TRANSPORT=http requires building with the `http` feature")
+    }
+
+    #[cfg(feature = "websocket")]
+    async fn serve_websocket(
+        self,
+        listener: TcpListener,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        let stop = self.stop_on(shutdown);
+        let allowed_hosts = self.options.allowed_hosts;
+        crate::websocket::serve(self.server, listener, allowed_hosts, stop).await
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    async fn serve_websocket(
+        self,
+        _listener: TcpListener,
+        _shutdown: CancellationToken,
+    ) -> Result<()> {
+        bail!("TRANSPORT=websocket requires building with the `websocket` feature")
+    }
+
+    #[cfg(unix)]
+    async fn serve_unix(self, path: &Path, shutdown: CancellationToken) -> Result<()> {
+        let listener = socket::bind_unix(path)?;
+        let stop = self.stop_on(shutdown);
+        socket::serve_unix(self.server, listener, stop).await
+    }
+
+    #[cfg(not(unix))]
+    async fn serve_unix(self, _path: &Path, _shutdown: CancellationToken) -> Result<()> {
+        bail!("TRANSPORT=unix: is only available on Unix")
+    }
+
+    /// Returns a token cancelled once `shutdown` is and running calls have
+    /// finished, telling a transport serving many clients to close their
+    /// connections.
+    fn stop_on(&self, shutdown: CancellationToken) -> CancellationToken {
+        self.started();
+        let stop = CancellationToken::new();
+        let stopping = stop.clone();
+        let draining = self.server.clone();
+        let options = self.options.clone();
+        tokio::spawn(async move {
+            shutdown.cancelled().await;
+            info!("Shutting down");
+            if options.notify_service_manager {
+                daemon::notify_stopping();
+            }
+            draining.shut_down(options.shutdown_timeout).await;
+            stopping.cancel();
+        });
+        stop
+    }
+
+    fn started(&self) {
+        info!(
+            "Server started successfully in {:?}",
+            self.created.elapsed()
+        );
+        if self.options.notify_service_manager {
+            daemon::notify_ready();
+        }
+    }
+}
+
+async fn bind_tcp(address: &str) -> Result<TcpListener> {
+    TcpListener::bind(address)
+        .await
+        .with_context(|| format!("failed to listen on {address}"))
+}
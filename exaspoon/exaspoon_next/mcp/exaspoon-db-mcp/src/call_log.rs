@@ -0,0 +1,216 @@
+//! Optional record of every tool call, with its arguments and result
+//! redacted, for compliance and for debugging what an agent did.
+
+use crate::{models::AuditEvent, redaction::Redactor, server::input_hash, supabase::Database};
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use rmcp::{model::CallToolResult, ErrorData as McpError};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+use tracing::{debug, error};
+
+pub const DEFAULT_CALL_LOG_PATH: &str = "tool_calls.jsonl";
+
+/// Replaces the value of every redacted field.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Fields redacted whether listed or not, as their values are credentials.
+pub const CREDENTIAL_FIELDS: &[&str] = &["access_token", "public_token"];
+
+/// Where tool calls are recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallLogTarget {
+    /// One JSON object per line, appended to a local file.
+    Jsonl(PathBuf),
+    /// Rows of the `tool_calls` table, with the call under `details`. The
+    /// audit log of mutations is left alone.
+    Table,
+}
+
+/// One tool call as written to the log, after redaction.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    /// SHA-256 of the unredacted arguments, as in mutation audit events.
+    pub input_hash: String,
+    pub session_id: String,
+    pub occurred_at: String,
+    pub duration_ms: u64,
+    pub arguments: Value,
+    /// The structured result, or the content blocks of a tool without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// `code`, `message` and `data` of a failed call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+}
+
+enum Sink {
+    Jsonl(Mutex<File>),
+    Table(Arc<dyn Database>),
+}
+
+/// Redacts tool calls and writes them to a [`CallLogTarget`]. Field names
+/// listed as redacted, and [`CREDENTIAL_FIELDS`], have their whole value
+/// replaced, at any depth; every other string goes through the PII
+/// [`Redactor`].
+pub struct CallLog {
+    sink: Sink,
+    redacted_fields: Vec<String>,
+    redactor: Redactor,
+}
+
+impl CallLog {
+    /// Opens `target`, creating a JSONL file if needed. `database` is where
+    /// the `tool_calls` table lives.
+    pub fn open(
+        target: &CallLogTarget,
+        database: Arc<dyn Database>,
+        redacted_fields: Vec<String>,
+        redactor: Redactor,
+    ) -> Result<Self> {
+        let sink = match target {
+            CallLogTarget::Jsonl(path) => Sink::Jsonl(Mutex::new(open_append(path)?)),
+            CallLogTarget::Table => Sink::Table(database),
+        };
+        Ok(Self {
+            sink,
+            redacted_fields,
+            redactor,
+        })
+    }
+
+    /// `value` with redacted fields replaced and PII masked in the rest.
+    pub fn redact(&self, value: Value) -> Value {
+        match value {
+            Value::String(text) => Value::String(self.redactor.redact(&text).into_owned()),
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|item| self.redact(item)).collect())
+            }
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = if self.is_redacted(&key) {
+                            Value::from(REDACTED)
+                        } else {
+                            self.redact(value)
+                        };
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    fn is_redacted(&self, field: &str) -> bool {
+        self.redacted_fields
+            .iter()
+            .map(String::as_str)
+            .chain(CREDENTIAL_FIELDS.iter().copied())
+            .any(|redacted| redacted.eq_ignore_ascii_case(field))
+    }
+
+    /// Redacts and writes a call of `tool` that took `duration` and ended
+    /// with `outcome`. Failures are logged rather than returned, so a broken
+    /// log never fails the call.
+    pub async fn record(
+        &self,
+        tool: &str,
+        session_id: &str,
+        arguments: Value,
+        outcome: &Result<CallToolResult, McpError>,
+        duration: Duration,
+    ) {
+        let (result, error) = match outcome {
+            Ok(result) => {
+                let value = match &result.structured_content {
+                    Some(structured) => structured.clone(),
+                    None => serde_json::to_value(&result.content).unwrap_or_default(),
+                };
+                (Some(self.redact(value)), None)
+            }
+            Err(err) => (None, Some(self.redact(json!(err)))),
+        };
+        let record = ToolCallRecord {
+            tool: tool.to_string(),
+            input_hash: input_hash(&arguments),
+            session_id: session_id.to_string(),
+            occurred_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            duration_ms: duration.as_millis() as u64,
+            arguments: self.redact(arguments),
+            result,
+            error,
+        };
+        if let Err(err) = self.write(record).await {
+            error!("Failed to record call of {}: {}", tool, err);
+        } else {
+            debug!("Recorded call of {} in {:?}", tool, duration);
+        }
+    }
+
+    async fn write(&self, record: ToolCallRecord) -> Result<()> {
+        match &self.sink {
+            Sink::Jsonl(file) => {
+                let mut line = serde_json::to_vec(&record)?;
+                line.push(b'\n');
+                // tokio hands the write to a blocking thread; flushing waits
+                // for it, so a record is in the file once its call returns.
+                let mut file = file.lock().await;
+                file.write_all(&line)
+                    .await
+                    .context("failed to append to the call log")?;
+                file.flush()
+                    .await
+                    .context("failed to append to the call log")
+            }
+            Sink::Table(database) => {
+                let event = AuditEvent {
+                    tool: record.tool,
+                    input_hash: record.input_hash,
+                    record_id: None,
+                    occurred_at: record.occurred_at,
+                    session_id: Some(record.session_id),
+                    details: Some(json!({
+                        "duration_ms": record.duration_ms,
+                        "arguments": record.arguments,
+                        "result": record.result,
+                        "error": record.error,
+                    })),
+                };
+                database.record_tool_calls(&[event]).await
+            }
+        }
+    }
+
+    /// Makes sure recorded calls are on disk, before the server exits.
+    pub async fn flush(&self) -> Result<()> {
+        match &self.sink {
+            Sink::Jsonl(file) => file
+                .lock()
+                .await
+                .sync_data()
+                .await
+                .context("failed to flush the call log"),
+            // Each tool call is written before its call returns.
+            Sink::Table(_) => Ok(()),
+        }
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map(File::from_std)
+        .with_context(|| format!("failed to open call log {}", path.display()))
+}
@@ -0,0 +1,25 @@
+//! MCP request cancellation, visible to the loops a tool call runs.
+//!
+//! rmcp keeps polling a handler after the client sends
+//! `notifications/cancelled` and only trips the request's token, so long
+//! loops check [`is_cancelled`] between items and wrap up with what they have.
+
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+
+tokio::task_local! {
+    static CURRENT: CancellationToken;
+}
+
+/// Runs `future` with `token` as the cancellation of the current call.
+pub async fn scope<F: Future>(token: CancellationToken, future: F) -> F::Output {
+    CURRENT.scope(token, future).await
+}
+
+/// Whether the client has cancelled the tool call being served. Always
+/// `false` outside of a call.
+pub fn is_cancelled() -> bool {
+    CURRENT
+        .try_with(CancellationToken::is_cancelled)
+        .unwrap_or(false)
+}
@@ -0,0 +1,173 @@
+//! Circuit breaker that makes calls to a dependency that keeps failing fail
+//! fast instead of waiting out every timeout.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_OPEN_DURATION_MS: u64 = 30_000;
+
+/// When the circuit opens and how long it stays open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit. Zero disables the breaker.
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects calls before letting a probe through.
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration: Duration::from_millis(DEFAULT_OPEN_DURATION_MS),
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// One probe call is in flight; everyone else keeps failing fast. A probe
+    /// that never reports back is replaced after another `open_duration`.
+    HalfOpen {
+        probe_started: Instant,
+    },
+}
+
+/// Returned instead of calling a dependency whose circuit is open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitOpen {
+    pub dependency: String,
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is unavailable after repeated failures; failing fast for another {}s",
+            self.dependency,
+            self.retry_in.as_secs_f32().ceil()
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// Tracks consecutive failures of one dependency. Closed, it lets every call
+/// through; after `failure_threshold` failures in a row it opens and rejects
+/// calls for `open_duration`; then it half-opens and lets a single probe
+/// decide whether to close again or stay open.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    dependency: String,
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(dependency: impl Into<String>, config: CircuitBreakerConfig) -> Self {
+        Self {
+            dependency: dependency.into(),
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go out now.
+    pub fn allow(&self) -> Result<(), CircuitOpen> {
+        if self.config.failure_threshold == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(self.open_error(until - now)),
+            State::HalfOpen { probe_started }
+                if now < probe_started + self.config.open_duration =>
+            {
+                Err(self.open_error(Duration::ZERO))
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                info!("{} circuit half-open, sending a probe", self.dependency);
+                *state = State::HalfOpen { probe_started: now };
+                Ok(())
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, State::Closed { .. }) {
+            info!("{} recovered, closing circuit", self.dependency);
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            State::Open { .. } | State::HalfOpen { .. } => self.config.failure_threshold,
+        };
+        *state = if failures >= self.config.failure_threshold {
+            warn!(
+                "{} failed {} times in a row, opening circuit for {:?}",
+                self.dependency, failures, self.config.open_duration
+            );
+            State::Open {
+                until: Instant::now() + self.config.open_duration,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.state.lock().unwrap(), State::Closed { .. })
+    }
+
+    /// Runs `call` unless the circuit is open, counting any error as a
+    /// failure of the dependency.
+    pub async fn call<T, Fut>(&self, call: Fut) -> anyhow::Result<T>
+    where
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.allow()?;
+        let result = call.await;
+        match &result {
+            Ok(_) => self.record_success(),
+            Err(_) => self.record_failure(),
+        }
+        result
+    }
+
+    fn open_error(&self, retry_in: Duration) -> CircuitOpen {
+        CircuitOpen {
+            dependency: self.dependency.clone(),
+            retry_in,
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! The `exaspoon-db-mcp` command line. `serve` runs the MCP server; the other
+//! subcommands do one operational task with the same configuration and exit,
+//! so seeding, importing or re-embedding doesn't need an MCP client. They call
+//! the server's tools over a session of their own, so the calls are
+//! validated, rate limited, confirmed and logged like any client's.
+
+use crate::config::{validate_schema, AppConfig, DEFAULT_SUPABASE_SCHEMA};
+use crate::confirmation::CONFIRMATION_TOKEN_FIELD;
+use crate::export::{self, ExportFormat};
+use crate::models::{
+    CreateTransactionInput, CreateTransactionsInput, DryRun, EmbeddingMaintenanceAction,
+    EmbeddingMaintenanceInput, EmbeddingMaintenanceOutput, HealthCheckOutput, HealthStatus,
+    Outcome, RecordKind, SeedDemoDataInput, SeedDemoDataOutput, TableMaintenance,
+    TransactionFilters, TransactionsOutput,
+};
+use crate::server::{ExaspoonDbServer, MAX_BATCH_TRANSACTIONS};
+use crate::supabase::Database;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use rmcp::model::{CallToolRequestParam, CallToolResult, ClientInfo, ProtocolVersion};
+use rmcp::service::{RoleClient, RunningService, ServiceError};
+use rmcp::{ErrorData as McpError, ServiceExt};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "exaspoon-db-mcp",
+    version,
+    about = "Supabase-backed MCP server for ExaSpoon"
+)]
+pub struct Cli {
+    /// Configuration file, TOML or YAML. Defaults to `$EXASPOON_CONFIG`, then
+    /// `exaspoon.toml`, `config.toml` or `exaspoon.yaml` when present.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Same as `check`, kept for existing deploy scripts.
+    #[arg(long, hide = true)]
+    check: bool,
+    /// Go ahead with tool calls that need confirmation instead of stopping
+    /// at their preview.
+    #[arg(long, global = true)]
+    pub yes: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    /// The subcommand to run, `serve` when none is given.
+    pub fn command(&self) -> Command {
+        match self.command.clone() {
+            Some(command) => command,
+            None if self.check => Command::Check,
+            None => Command::Serve { daemon: false },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum Command {
+    /// Serve MCP clients over the configured transport (the default).
+    Serve {
+        /// Run as a system service: refuse the stdio transport and ignore
+        /// SIGHUP from a closed terminal.
+        #[arg(long)]
+        daemon: bool,
+    },
+    /// Probe the tables, search RPCs and embedding provider once, failing
+    /// when any probe does.
+    Check,
+    /// Write a throwaway account, category and transaction, find them
+    /// through the search RPCs and delete them again, failing when any step
+    /// does.
+    Verify,
+    /// Apply the embedded schema migrations to `SUPABASE_DB_URL`.
+    Migrate {
+        /// Print the migrations as one SQL script instead of applying them.
+        #[arg(long)]
+        print: bool,
+    },
+    /// Populate demo accounts, categories and transactions.
+    Seed {
+        /// Calendar months of transactions, ending with the current one.
+        #[arg(long)]
+        months: Option<u32>,
+        /// Show what would be written without writing it.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create the transactions in a JSON Lines file, `-` for stdin.
+    Import {
+        file: PathBuf,
+        /// Validate and embed the transactions without inserting them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write transactions as JSON Lines or Parquet, newest first.
+    Export {
+        /// File to write to instead of stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Told from the output's extension when left out: Parquet for
+        /// `.parquet`, JSON Lines otherwise.
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        /// Inclusive lower bound on `occurred_at` (RFC 3339).
+        #[arg(long)]
+        from: Option<String>,
+        /// Exclusive upper bound on `occurred_at` (RFC 3339).
+        #[arg(long)]
+        to: Option<String>,
+        #[arg(long)]
+        account_id: Option<String>,
+        #[arg(long)]
+        category_id: Option<String>,
+    },
+    /// Embed every row whose embedding is missing or has the wrong size, e.g.
+    /// after switching embedding models.
+    Reembed {
+        /// Only this table; transactions and categories when omitted.
+        #[arg(long, value_enum)]
+        kind: Option<EmbeddedKind>,
+        /// Rows embedded per round.
+        #[arg(long)]
+        batch_size: Option<u32>,
+    },
+}
+
+/// The kinds of rows that carry embeddings.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum EmbeddedKind {
+    Transaction,
+    Category,
+}
+
+impl From<EmbeddedKind> for RecordKind {
+    fn from(kind: EmbeddedKind) -> Self {
+        match kind {
+            EmbeddedKind::Transaction => RecordKind::Transaction,
+            EmbeddedKind::Category => RecordKind::Category,
+        }
+    }
+}
+
+/// `migrate`: applies the embedded schema migrations, or prints them as one
+/// SQL script.
+pub async fn migrate(print: bool) -> Result<()> {
+    let schema = std::env::var("SUPABASE_SCHEMA")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_SUPABASE_SCHEMA.to_string());
+    let schema = validate_schema(&schema)?;
+
+    if print {
+        print!("{}", crate::migrations::script(schema));
+        return Ok(());
+    }
+    #[cfg(feature = "migrate")]
+    {
+        let database_url = AppConfig::secret("SUPABASE_DB_URL")?
+            .context("Missing required env var SUPABASE_DB_URL (or SUPABASE_DB_URL_FILE)")?;
+        info!("Migrating schema {}", schema);
+        let applied = crate::migrations::run(&database_url, schema).await?;
+        info!("Migrations applied: {:?}", applied);
+        Ok(())
+    }
+    #[cfg(not(feature = "migrate"))]
+    bail!("`migrate` requires building with the `migrate` feature; use `migrate --print` to get the SQL")
+}
+
+/// `check`: probes the tables, search RPCs and embedding provider once,
+/// prints a report and fails when any probe does.
+pub async fn check(config: &AppConfig, server: &ExaspoonDbServer) -> Result<()> {
+    print_backend(config);
+    let report = server.startup_check().await;
+    print_report(&report, "startup check", "probes")
+}
+
+/// `verify`: runs [`ExaspoonDbServer::verify_deployment`], prints a report
+/// and fails when any step does.
+pub async fn verify(config: &AppConfig, server: &ExaspoonDbServer) -> Result<()> {
+    print_backend(config);
+    let report = server.verify_deployment().await;
+    print_report(&report, "verification", "steps")
+}
+
+fn print_backend(config: &AppConfig) {
+    println!(
+        "Database backend: {}, embedding provider: {} ({})",
+        config.database_backend, config.embedding_provider, config.embedding_model
+    );
+}
+
+fn print_report(report: &HealthCheckOutput, what: &str, steps: &str) -> Result<()> {
+    for check in &report.checks {
+        let status = if check.ok { "ok" } else { "FAILED" };
+        let line = format!("{:<6} {:<28} {:>6}ms", status, check.name, check.latency_ms);
+        match &check.error {
+            Some(error) => println!("{line}  {error}"),
+            None => println!("{line}"),
+        }
+    }
+    let failed = report.checks.iter().filter(|check| !check.ok).count();
+    if report.status != HealthStatus::Ok {
+        bail!("{what} failed: {failed} of {} {steps}", report.checks.len());
+    }
+    println!("All {} {steps} passed", report.checks.len());
+    Ok(())
+}
+
+/// `seed`: runs `seed_demo_data`, whether or not the server lists it.
+pub async fn seed(
+    server: &ExaspoonDbServer,
+    months: Option<u32>,
+    dry_run: bool,
+) -> Result<Outcome<SeedDemoDataOutput>> {
+    let client = LocalClient::connect(server.clone().with_demo_seed(), false).await?;
+    let input = DryRun {
+        input: SeedDemoDataInput { months },
+        dry_run: Some(dry_run),
+    };
+    client.call("seed_demo_data", input).await
+}
+
+/// `import`: creates one transaction per line of `lines`, in the shape
+/// `create_transaction` takes or `export` writes. Every line is parsed
+/// before anything is written; transactions are then inserted in batches
+/// of [`MAX_BATCH_TRANSACTIONS`], so a failure leaves the earlier batches.
+/// Returns how many transactions were written, or would have been.
+pub async fn import(
+    server: &ExaspoonDbServer,
+    lines: impl BufRead,
+    dry_run: bool,
+) -> Result<usize> {
+    let mut transactions = Vec::new();
+    for (index, line) in lines.lines().enumerate() {
+        let line = line.context("failed to read transactions")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let transaction: CreateTransactionInput = serde_json::from_str(&line)
+            .with_context(|| format!("line {}: not a transaction", index + 1))?;
+        transactions.push(transaction);
+    }
+    info!("Importing {} transactions", transactions.len());
+
+    let client = LocalClient::connect(server.clone(), false).await?;
+    let mut imported = 0;
+    for batch in transactions.chunks(MAX_BATCH_TRANSACTIONS) {
+        let input = DryRun {
+            input: CreateTransactionsInput {
+                transactions: batch.to_vec(),
+            },
+            dry_run: Some(dry_run),
+        };
+        let written: Outcome<TransactionsOutput> = client
+            .call("create_transactions", input)
+            .await
+            .with_context(|| format!("imported {imported} transactions before failing"))?;
+        imported += written.output.transactions.len();
+    }
+    Ok(imported)
+}
+
+/// `export`: writes the transactions matching `filters` to `out` in
+/// `format`, a page at a time. Returns how many were written.
+pub async fn export(
+    database: &dyn Database,
+    filters: &TransactionFilters,
+    format: ExportFormat,
+    out: impl Write + Send,
+) -> Result<u64> {
+    export::export(database, filters, format, out).await
+}
+
+/// `reembed`: runs `embedding_maintenance` with `backfill`, whether or not
+/// the server lists the admin tools, until no table has rows left to embed.
+/// Each round needs `confirmed` when the server asks for confirmation.
+/// Returns how many rows of each table were embedded.
+pub async fn reembed(
+    server: &ExaspoonDbServer,
+    kind: Option<EmbeddedKind>,
+    batch_size: Option<u32>,
+    confirmed: bool,
+) -> Result<BTreeMap<String, u64>> {
+    let client = LocalClient::connect(server.clone().with_admin_tools(), confirmed).await?;
+    let mut embedded = BTreeMap::new();
+    loop {
+        let input = EmbeddingMaintenanceInput {
+            kind: kind.map(RecordKind::from),
+            action: EmbeddingMaintenanceAction::Backfill,
+            batch_size,
+            dimension: None,
+        };
+        let round: Outcome<EmbeddingMaintenanceOutput> = client
+            .call("embedding_maintenance", DryRun::from(input))
+            .await?;
+        let mut complete = true;
+        let mut updated = 0;
+        for (table, summary) in round.output.tables {
+            if let TableMaintenance::Repair(repair) = summary {
+                *embedded.entry(table).or_default() += u64::from(repair.updated);
+                complete &= repair.complete;
+                updated += repair.updated;
+            }
+        }
+        // Rows the provider can't embed would otherwise be retried forever.
+        if complete || updated == 0 {
+            return Ok(embedded);
+        }
+        info!("Embedded {} rows; continuing", updated);
+    }
+}
+
+/// A client session with a server in this process.
+struct LocalClient {
+    server: ExaspoonDbServer,
+    service: RunningService<RoleClient, ClientInfo>,
+    /// Whether calls that need confirmation go ahead.
+    confirmed: bool,
+}
+
+impl LocalClient {
+    async fn connect(server: ExaspoonDbServer, confirmed: bool) -> Result<Self> {
+        let (client_end, server_end) = tokio::io::duplex(64 * 1024);
+        let serving = server.clone();
+        tokio::spawn(async move {
+            match serving.serve(server_end).await {
+                Ok(running) => {
+                    let _ = running.waiting().await;
+                }
+                Err(err) => warn!("Local session failed to start: {}", err),
+            }
+        });
+        // Structured results are only returned from this revision on.
+        let client = ClientInfo {
+            protocol_version: ProtocolVersion::V_2025_06_18,
+            ..ClientInfo::default()
+        };
+        let service = client
+            .serve(client_end)
+            .await
+            .context("failed to start a session with the server")?;
+        Ok(Self {
+            server,
+            service,
+            confirmed,
+        })
+    }
+
+    /// Calls `tool` with `arguments`. A preview that asks for confirmation
+    /// is confirmed when the client may, and fails otherwise.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        tool: &str,
+        arguments: impl Serialize,
+    ) -> Result<Outcome<T>> {
+        let Value::Object(mut arguments) = serde_json::to_value(arguments)? else {
+            bail!("{tool} arguments are not an object");
+        };
+        let mut outcome: Outcome<T> = self.call_once(tool, arguments.clone()).await?;
+        let Some(confirmation) = outcome.confirmation.take() else {
+            return Ok(outcome);
+        };
+        if !self.confirmed {
+            bail!("{tool} needs confirmation; run again with --yes to go ahead");
+        }
+        info!("Confirming {} call", tool);
+        arguments.insert(
+            CONFIRMATION_TOKEN_FIELD.to_string(),
+            Value::String(confirmation.token),
+        );
+        self.call_once(tool, arguments).await
+    }
+
+    async fn call_once<T: DeserializeOwned>(
+        &self,
+        tool: &str,
+        arguments: serde_json::Map<String, Value>,
+    ) -> Result<T> {
+        let request = CallToolRequestParam {
+            name: self.server.tool_name(tool).into(),
+            arguments: Some(arguments),
+        };
+        let result = self
+            .service
+            .call_tool(request)
+            .await
+            .map_err(|err| match err {
+                ServiceError::McpError(err) => err,
+                err => McpError::internal_error(err.to_string(), None),
+            });
+        outcome(result)
+    }
+}
+
+/// The structured result of a tool call, or its error with the details a
+/// client would find in its data.
+fn outcome<T: DeserializeOwned>(result: Result<CallToolResult, McpError>) -> Result<T> {
+    let result = result.map_err(|err| {
+        let details = err.data.as_ref().and_then(|data| data["details"].as_str());
+        match details {
+            Some(details) => anyhow!("{}: {}", err.message, details),
+            None => anyhow!("{}", err.message),
+        }
+    })?;
+    let content = result
+        .structured_content
+        .ok_or_else(|| anyhow!("the tool returned no structured result"))?;
+    Ok(serde_json::from_value(content)?)
+}
@@ -0,0 +1,83 @@
+//! Caps on how many tool calls run at once, so a burst from a client queues
+//! instead of exhausting database connections or provider rate limits.
+
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+pub const DEFAULT_MAX_CONCURRENT_CALLS: usize = 16;
+pub const DEFAULT_MAX_CONCURRENT_WRITES: usize = 4;
+
+/// How many tool calls may run at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyLimits {
+    /// Calls of any tool. Zero means no limit.
+    pub calls: usize,
+    /// Calls of tools that can write, which also count as calls. Zero means
+    /// no limit.
+    pub writes: usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            calls: DEFAULT_MAX_CONCURRENT_CALLS,
+            writes: DEFAULT_MAX_CONCURRENT_WRITES,
+        }
+    }
+}
+
+/// Hands out permits to run tool calls, making callers wait while the limits
+/// are reached.
+#[derive(Debug)]
+pub struct CallSlots {
+    calls: Option<Arc<Semaphore>>,
+    writes: Option<Arc<Semaphore>>,
+}
+
+/// Held while a tool call runs; dropping it frees its slots.
+#[derive(Debug)]
+pub struct CallPermit {
+    _write: Option<OwnedSemaphorePermit>,
+    _call: Option<OwnedSemaphorePermit>,
+}
+
+impl CallSlots {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        let semaphore = |permits: usize| (permits > 0).then(|| Arc::new(Semaphore::new(permits)));
+        Self {
+            calls: semaphore(limits.calls),
+            writes: semaphore(limits.writes),
+        }
+    }
+
+    /// Waits for a slot to run a call, `writes` saying whether the tool can
+    /// write. A write takes its write slot first, so writes queued behind
+    /// each other do not hold up reads.
+    pub async fn acquire(&self, writes: bool) -> CallPermit {
+        let write = match &self.writes {
+            Some(semaphore) if writes => Some(wait(semaphore, "write").await),
+            _ => None,
+        };
+        let call = match &self.calls {
+            Some(semaphore) => Some(wait(semaphore, "call").await),
+            None => None,
+        };
+        CallPermit {
+            _write: write,
+            _call: call,
+        }
+    }
+}
+
+async fn wait(semaphore: &Arc<Semaphore>, slot: &str) -> OwnedSemaphorePermit {
+    if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+        return permit;
+    }
+    debug!("Waiting for a free {} slot", slot);
+    semaphore
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("tool call semaphores are never closed")
+}
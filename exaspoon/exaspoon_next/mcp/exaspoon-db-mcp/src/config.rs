@@ -1,13 +1,249 @@
-use anyhow::{Context, Result};
+use crate::{
+    call_log::{CallLogTarget, DEFAULT_CALL_LOG_PATH},
+    circuit::{CircuitBreakerConfig, DEFAULT_FAILURE_THRESHOLD, DEFAULT_OPEN_DURATION_MS},
+    concurrency::{
+        ConcurrencyLimits, DEFAULT_MAX_CONCURRENT_CALLS, DEFAULT_MAX_CONCURRENT_WRITES,
+    },
+    confirmation::DEFAULT_CONFIRMATION_WINDOW_SECS,
+    embedding::{default_model, EmbeddingEncoding, DEFAULT_EMBEDDING_PROVIDER},
+    exchange::{binance::BINANCE_BASE_URL, coinbase::COINBASE_BASE_URL},
+    fx::{
+        DEFAULT_BASE_CURRENCY, DEFAULT_FX_CACHE_TTL_SECS, EXCHANGERATE_HOST_BASE_URL,
+        FRANKFURTER_BASE_URL,
+    },
+    gocardless::GOCARDLESS_BASE_URL,
+    i18n::Locale,
+    models::FxProvider,
+    onchain::ethereum::ETHERSCAN_BASE_URL,
+    plaid::{PLAID_PRODUCTION_URL, PLAID_SANDBOX_URL},
+    prices::{COINGECKO_BASE_URL, COINGECKO_PRO_BASE_URL, DEFAULT_PRICE_CACHE_TTL_SECS},
+    rate_limit::{RateLimitConfig, DEFAULT_CALLS_PER_MINUTE, DEFAULT_EMBEDDING_CALLS_PER_MINUTE},
+    receipt::OPENAI_BASE_URL,
+    retry::{RetryPolicy, DEFAULT_MAX_RETRIES, DEFAULT_RETRY_BASE_DELAY_MS},
+    server::ToolGroup,
+    session::{DEFAULT_MAX_SESSIONS, DEFAULT_SESSION_IDLE_TIMEOUT_SECS},
+    sheets::{
+        google::{GOOGLE_SHEETS_BASE_URL, GOOGLE_TOKEN_URL},
+        notion::NOTION_BASE_URL,
+    },
+    shutdown::DEFAULT_SHUTDOWN_TIMEOUT_MS,
+    timeout::{ToolTimeouts, DEFAULT_TOOL_TIMEOUT_MS},
+    tool_names::ToolNames,
+    ynab::YNAB_BASE_URL,
+};
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 use tracing::Level;
 
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+pub const DEFAULT_DATABASE_BACKEND: &str = "supabase";
+pub const DEFAULT_SQLITE_PATH: &str = "exaspoon.db";
+pub const DEFAULT_SUPABASE_SCHEMA: &str = "public";
+pub const DEFAULT_SERVER_INSTRUCTIONS: &str =
+    "Tools for managing accounts, transactions, and semantic search over Supabase data.";
+pub const DEFAULT_HTTP_CONNECT_TIMEOUT_MS: u64 = 10_000;
+pub const DEFAULT_HTTP_REQUEST_TIMEOUT_MS: u64 = 30_000;
+pub const DEFAULT_HTTP_POOL_IDLE_TIMEOUT_MS: u64 = 90_000;
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:8080";
+pub const DEFAULT_HEALTH_PROBE_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_LOG_FILE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_LOG_FILE_RETAINED: usize = 5;
+/// Names the OS keyring service secrets are looked up under when neither
+/// their variable nor its `_FILE` variant is set.
+pub const KEYRING_SERVICE_VAR: &str = "KEYRING_SERVICE";
+
+/// Checks that a Postgres schema name is a plain identifier, since it is
+/// interpolated into profile headers and migration SQL.
+pub fn validate_schema(schema: &str) -> Result<&str> {
+    let schema = schema.trim();
+    if schema.is_empty()
+        || !schema
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        bail!("invalid SUPABASE_SCHEMA {schema:?}: expected a plain Postgres schema name");
+    }
+    Ok(schema)
+}
+
+/// How clients reach the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transport {
+    /// One client, which spawned the server, over stdin and stdout.
+    Stdio,
+    /// Any number of clients over streamable HTTP.
+    Http,
+    /// Any number of clients over WebSocket connections.
+    WebSocket,
+    /// Any number of clients over TCP connections to `host:port`, speaking
+    /// newline-delimited JSON-RPC as over stdio.
+    Tcp(String),
+    /// Like [`Self::Tcp`], over connections to a Unix domain socket.
+    Unix(PathBuf),
+}
+
+impl FromStr for Transport {
+    type Err = anyhow::Error;
+
+    /// Accepts `stdio`, `http`, `websocket`, `tcp:host:port` or
+    /// `unix:/path.sock`.
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Some(address) = value.strip_prefix("tcp:") {
+            if !address.contains(':') {
+                bail!("TRANSPORT {value:?} must name a port, as in tcp:127.0.0.1:9000");
+            }
+            return Ok(Self::Tcp(address.to_string()));
+        }
+        if let Some(path) = value.strip_prefix("unix:") {
+            if path.is_empty() {
+                bail!("TRANSPORT {value:?} must name a socket path, as in unix:/run/exaspoon.sock");
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        match value.to_ascii_lowercase().as_str() {
+            "stdio" => Ok(Self::Stdio),
+            "http" => Ok(Self::Http),
+            "websocket" => Ok(Self::WebSocket),
+            _ => bail!(
+                "unknown TRANSPORT {value:?} (expected stdio, http, websocket, tcp:host:port \
+                 or unix:/path.sock)"
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppConfig {
+    pub transport: Transport,
+    /// Where the `http` and `websocket` transports listen.
+    pub bind_address: SocketAddr,
+    /// Host names the `http` and `websocket` transports answer to and
+    /// browser origins they accept; empty for loopback only.
+    pub allowed_hosts: Vec<String>,
+    /// Client sessions a network transport holds open at once; zero means no
+    /// limit.
+    pub max_sessions: usize,
+    /// How long an HTTP session may go without a request before it is
+    /// closed, if at all.
+    pub session_idle_timeout: Option<Duration>,
+    /// How long the stdio client may write nothing before the server exits,
+    /// if at all.
+    pub stdio_idle_timeout: Option<Duration>,
+    /// Where `/healthz` and `/readyz` are served, if anywhere.
+    pub health_address: Option<SocketAddr>,
+    /// How often the health endpoint probes the dependencies.
+    pub health_probe_interval: Duration,
+    pub database_backend: String,
+    pub sqlite_path: String,
     pub supabase_url: String,
+    /// PostgREST endpoint of a read replica that serves lists, counts,
+    /// searches and aggregates. Writes always go to `supabase_url`.
+    pub supabase_read_url: Option<String>,
     pub supabase_service_key: String,
+    pub supabase_schema: String,
+    pub supabase_access_token: Option<String>,
+    pub supabase_require_user_auth: bool,
+    pub supabase_retry: RetryPolicy,
+    /// Forwards Supabase Realtime changes to the transactions table as MCP
+    /// resource update notifications.
+    pub supabase_realtime: bool,
+    pub tenant_id: Option<String>,
+    /// Exposes destructive maintenance tools such as `purge_deleted`.
+    pub admin_tools: bool,
+    /// Exposes `seed_demo_data`, which writes demo rows into the database.
+    pub demo_seed: bool,
+    /// Turns every call of a mutating tool into a dry run that writes nothing.
+    pub dry_run: bool,
+    /// How long the token from a destructive tool's preview stays valid, when
+    /// those tools need one to run at all.
+    pub confirmation_window: Option<Duration>,
+    /// Hides every tool that can write, for analysis-only agents.
+    pub read_only: bool,
+    /// Postgres functions the `call_rpc` tool may invoke. Empty hides the tool.
+    pub rpc_allowlist: Vec<String>,
+    /// Tools hidden from clients, by name or by a prefix ending in `*`.
+    pub disabled_tools: Vec<String>,
+    /// Groups of tools mounted at all; every group by default.
+    pub tool_groups: Vec<ToolGroup>,
+    /// Names clients see for the tools, when they differ from the originals.
+    pub tool_names: ToolNames,
+    /// Tool calls allowed per minute, overall and for embedding-heavy tools.
+    pub rate_limit: RateLimitConfig,
+    /// Tool calls that may run at once, overall and for tools that write.
+    pub concurrency: ConcurrencyLimits,
+    /// Time limits on tool calls, by default and per tool.
+    pub tool_timeouts: ToolTimeouts,
+    /// How long a shutdown waits for running tool calls to finish.
+    pub shutdown_timeout: Duration,
+    /// File the process id is written to while the server runs.
+    pub pid_file: Option<PathBuf>,
+    /// Where every tool call is recorded, if anywhere.
+    pub call_log: Option<CallLogTarget>,
+    /// Fields whose values the call log replaces, at any depth.
+    pub call_log_redacted_fields: Vec<String>,
+    pub http: HttpClientConfig,
+    /// How the server introduces itself to clients.
+    pub identity: ServerIdentity,
+    /// Language of user-facing messages for clients that ask for none.
+    pub locale: Locale,
+    /// Shared by the Supabase gateway and the embedding provider; each gets
+    /// its own breaker.
+    pub circuit_breaker: CircuitBreakerConfig,
     pub openai_api_key: String,
     pub openai_base_url: Option<String>,
+    pub azure_openai: Option<AzureOpenAiConfig>,
+    /// Credentials for syncing linked bank accounts; the bank tools are
+    /// hidden without them.
+    pub plaid: Option<PlaidConfig>,
+    /// Credentials for syncing accounts from European banks; the open
+    /// banking tools are hidden without them.
+    pub gocardless: Option<GoCardlessConfig>,
+    /// Etherscan access for syncing Ethereum addresses; `sync_onchain` is
+    /// hidden without it.
+    pub etherscan: Option<EtherscanConfig>,
+    /// The Blockbook explorer Bitcoin addresses are synced through.
+    pub blockbook: Option<BlockbookConfig>,
+    /// The RPC node Solana wallets are synced through.
+    pub solana: Option<SolanaConfig>,
+    /// API key of a Binance account; `sync_exchange` is hidden without
+    /// this or `coinbase`.
+    pub binance: Option<BinanceConfig>,
+    /// API key of a Coinbase user.
+    pub coinbase: Option<CoinbaseConfig>,
+    /// Where exchange rates come from; `convert_currency` is hidden and the
+    /// reporting tools give no base currency totals without it.
+    pub fx: Option<FxConfig>,
+    /// Where crypto asset prices come from; `get_asset_price` is hidden
+    /// without it.
+    pub prices: Option<PriceConfig>,
+    /// The vision model receipts are read with; `parse_receipt` is hidden
+    /// without it.
+    pub receipt: Option<ReceiptConfig>,
+    /// OAuth credentials of a Google user; `export_to_sheet` is hidden
+    /// without this or `notion`.
+    pub google_sheets: Option<GoogleSheetsConfig>,
+    /// Token of a Notion integration.
+    pub notion: Option<NotionConfig>,
+    /// Personal access token of a YNAB user; `export_ynab` is hidden, and
+    /// `import_ynab` only reads exports, without it.
+    pub ynab: Option<YnabConfig>,
+    pub ollama_base_url: Option<String>,
+    pub cohere_api_key: Option<String>,
+    pub voyage_api_key: Option<String>,
+    pub embedding_provider: String,
     pub embedding_model: String,
+    pub embedding_dimensions: Option<usize>,
+    pub embedding_encoding: EmbeddingEncoding,
+    pub embedding_query_prompt: Option<String>,
+    pub embedding_document_prompt: Option<String>,
+    pub pii_redaction: bool,
+    pub pii_redaction_patterns: Vec<String>,
     pub log_level: Level,
 }
 
@@ -17,18 +253,186 @@ impl AppConfig {
             .unwrap_or_else(|_| "info".to_string())
             .parse::<Level>()
             .unwrap_or(Level::INFO);
+        let azure_openai = AzureOpenAiConfig::from_env()?;
+        // Setting only the Azure endpoint keeps selecting Azure, as before
+        // EMBEDDING_PROVIDER existed.
+        let embedding_provider = Self::optional("EMBEDDING_PROVIDER")
+            .map(|value| value.to_ascii_lowercase())
+            .unwrap_or_else(|| {
+                if azure_openai.is_some() {
+                    "azure".to_string()
+                } else {
+                    DEFAULT_EMBEDDING_PROVIDER.to_string()
+                }
+            });
+        // The OpenAI key is only mandatory when talking to OpenAI directly.
+        let openai_api_key = if embedding_provider == "openai" {
+            Self::require_secret("OPENAI_API_KEY")?
+        } else {
+            Self::secret("OPENAI_API_KEY")?.unwrap_or_default()
+        };
+        
+        let database_backend = Self::optional("DATABASE_BACKEND")
+            .map(|value| value.to_ascii_lowercase())
+            .unwrap_or_else(|| DEFAULT_DATABASE_BACKEND.to_string());
+        // Supabase credentials are only mandatory for the Supabase backend.
+        let (supabase_url, supabase_service_key) = if database_backend == "supabase" {
+            (
+                Self::require("SUPABASE_URL")?,
+                Self::require_secret("SUPABASE_SERVICE_KEY")?,
+            )
+        } else {
+            (
+                Self::optional("SUPABASE_URL").unwrap_or_default(),
+                Self::secret("SUPABASE_SERVICE_KEY")?.unwrap_or_default(),
+            )
+        };
         
         Ok(Self {
-            supabase_url: Self::require("SUPABASE_URL")?,
-            supabase_service_key: Self::require("SUPABASE_SERVICE_KEY")?,
-            openai_api_key: Self::require("OPENAI_API_KEY")?,
+            transport: Self::optional("TRANSPORT")
+                .map(|value| value.parse())
+                .transpose()?
+                .unwrap_or(Transport::Stdio),
+            bind_address: Self::parsed("BIND_ADDRESS", "a socket address such as 127.0.0.1:8080")?
+                .unwrap_or_else(|| DEFAULT_BIND_ADDRESS.parse().unwrap()),
+            allowed_hosts: Self::json_list("ALLOWED_HOSTS")?,
+            max_sessions: Self::parsed("MAX_SESSIONS", "a non-negative integer")?
+                .unwrap_or(DEFAULT_MAX_SESSIONS),
+            session_idle_timeout: Some(
+                Self::parsed("SESSION_IDLE_TIMEOUT_SECS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_SESSION_IDLE_TIMEOUT_SECS),
+            )
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs),
+            stdio_idle_timeout: Self::parsed("STDIO_IDLE_TIMEOUT_SECS", "a number of seconds")?
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            health_address: Self::parsed(
+                "HEALTH_ADDRESS",
+                "a socket address such as 0.0.0.0:8081",
+            )?,
+            health_probe_interval: match Self::parsed(
+                "HEALTH_PROBE_INTERVAL_SECS",
+                "a number of seconds",
+            )? {
+                Some(0) => bail!("HEALTH_PROBE_INTERVAL_SECS must be above zero"),
+                secs => Duration::from_secs(secs.unwrap_or(DEFAULT_HEALTH_PROBE_INTERVAL_SECS)),
+            },
+            database_backend,
+            sqlite_path: Self::optional("SQLITE_PATH")
+                .unwrap_or_else(|| DEFAULT_SQLITE_PATH.to_string()),
+            supabase_url,
+            supabase_read_url: Self::optional("SUPABASE_READ_URL"),
+            supabase_service_key,
+            supabase_schema: Self::optional("SUPABASE_SCHEMA")
+                .unwrap_or_else(|| DEFAULT_SUPABASE_SCHEMA.to_string()),
+            supabase_access_token: Self::secret("SUPABASE_ACCESS_TOKEN")?,
+            supabase_require_user_auth: Self::flag("SUPABASE_REQUIRE_USER_AUTH"),
+            supabase_realtime: Self::flag("SUPABASE_REALTIME"),
+            tenant_id: Self::optional("TENANT_ID"),
+            admin_tools: Self::flag("ENABLE_ADMIN_TOOLS"),
+            demo_seed: Self::flag("ENABLE_DEMO_SEED"),
+            dry_run: Self::flag("DRY_RUN"),
+            confirmation_window: Self::confirmation_window()?,
+            read_only: Self::flag("READ_ONLY"),
+            rpc_allowlist: Self::json_list("RPC_ALLOWLIST")?,
+            disabled_tools: Self::json_list("DISABLED_TOOLS")?,
+            tool_groups: Self::tool_groups()?,
+            tool_names: ToolNames::new(
+                Self::optional("TOOL_NAME_PREFIX").unwrap_or_default(),
+                Self::json_map::<String>("TOOL_ALIASES", "tool names")?,
+            )?,
+            rate_limit: RateLimitConfig::new(
+                Self::parsed("RATE_LIMIT_CALLS_PER_MINUTE", "a non-negative integer")?
+                    .unwrap_or(DEFAULT_CALLS_PER_MINUTE),
+                Self::parsed("RATE_LIMIT_EMBEDDING_CALLS_PER_MINUTE", "a non-negative integer")?
+                    .unwrap_or(DEFAULT_EMBEDDING_CALLS_PER_MINUTE),
+            ),
+            concurrency: ConcurrencyLimits {
+                calls: Self::parsed("MAX_CONCURRENT_TOOL_CALLS", "a non-negative integer")?
+                    .unwrap_or(DEFAULT_MAX_CONCURRENT_CALLS),
+                writes: Self::parsed("MAX_CONCURRENT_WRITES", "a non-negative integer")?
+                    .unwrap_or(DEFAULT_MAX_CONCURRENT_WRITES),
+            },
+            tool_timeouts: ToolTimeouts::new(
+                Duration::from_millis(
+                    Self::parsed("TOOL_TIMEOUT_MS", "a number of milliseconds")?
+                        .unwrap_or(DEFAULT_TOOL_TIMEOUT_MS),
+                ),
+                Self::json_map::<u64>("TOOL_TIMEOUTS_MS", "numbers of milliseconds")?
+                    .into_iter()
+                    .map(|(tool, ms)| (tool, Duration::from_millis(ms)))
+                    .collect(),
+            ),
+            shutdown_timeout: Duration::from_millis(
+                Self::parsed("SHUTDOWN_TIMEOUT_MS", "a number of milliseconds")?
+                    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_MS),
+            ),
+            pid_file: Self::optional("PID_FILE").map(PathBuf::from),
+            call_log: Self::call_log()?,
+            call_log_redacted_fields: Self::json_list("CALL_LOG_REDACTED_FIELDS")?,
+            supabase_retry: RetryPolicy::new(
+                Self::parsed("SUPABASE_MAX_RETRIES", "a non-negative integer")?
+                    .unwrap_or(DEFAULT_MAX_RETRIES),
+                Duration::from_millis(
+                    Self::parsed("SUPABASE_RETRY_BASE_DELAY_MS", "a number of milliseconds")?
+                        .unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS),
+                ),
+            ),
+            http: HttpClientConfig::from_env()?,
+            identity: ServerIdentity::from_env(),
+            locale: Self::optional("LOCALE")
+                .map(|value| value.parse())
+                .transpose()
+                .context("LOCALE must be en or ru")?
+                .unwrap_or_default(),
+            circuit_breaker: CircuitBreakerConfig::new(
+                Self::parsed("CIRCUIT_BREAKER_FAILURE_THRESHOLD", "a non-negative integer")?
+                    .unwrap_or(DEFAULT_FAILURE_THRESHOLD),
+                Duration::from_millis(
+                    Self::parsed("CIRCUIT_BREAKER_OPEN_MS", "a number of milliseconds")?
+                        .unwrap_or(DEFAULT_OPEN_DURATION_MS),
+                ),
+            ),
+            openai_api_key,
             openai_base_url: std::env::var("OPENAI_BASE_URL")
                 .ok()
                 .filter(|value| !value.is_empty()),
+            azure_openai,
+            plaid: PlaidConfig::from_env()?,
+            gocardless: GoCardlessConfig::from_env()?,
+            etherscan: EtherscanConfig::from_env()?,
+            blockbook: Self::optional("BLOCKBOOK_URL")
+                .map(|base_url| BlockbookConfig { base_url }),
+            solana: Self::secret("SOLANA_RPC_URL")?.map(|rpc_url| SolanaConfig { rpc_url }),
+            binance: BinanceConfig::from_env()?,
+            coinbase: CoinbaseConfig::from_env()?,
+            fx: FxConfig::from_env()?,
+            prices: PriceConfig::from_env()?,
+            receipt: ReceiptConfig::from_env()?,
+            google_sheets: GoogleSheetsConfig::from_env()?,
+            notion: NotionConfig::from_env()?,
+            ynab: YnabConfig::from_env()?,
+            ollama_base_url: Self::optional("OLLAMA_BASE_URL"),
+            cohere_api_key: Self::secret("COHERE_API_KEY")?,
+            voyage_api_key: Self::secret("VOYAGE_API_KEY")?,
             embedding_model: std::env::var("EMBEDDING_MODEL")
                 .ok()
                 .filter(|value| !value.is_empty())
-                .unwrap_or_else(|| "text-embedding-3-large".to_string()),
+                .unwrap_or_else(|| default_model(&embedding_provider).to_string()),
+            embedding_provider,
+            embedding_dimensions: Self::optional("EMBEDDING_DIMENSIONS")
+                .map(|value| value.parse())
+                .transpose()
+                .context("EMBEDDING_DIMENSIONS must be a positive integer")?,
+            embedding_encoding: Self::optional("EMBEDDING_ENCODING_FORMAT")
+                .map(|value| value.parse())
+                .transpose()?
+                .unwrap_or_default(),
+            embedding_query_prompt: Self::optional("EMBEDDING_QUERY_PROMPT"),
+            embedding_document_prompt: Self::optional("EMBEDDING_DOCUMENT_PROMPT"),
+            pii_redaction: Self::flag("PII_REDACTION"),
+            pii_redaction_patterns: Self::json_list("PII_REDACTION_PATTERNS")?,
             log_level,
         })
     }
@@ -36,4 +440,727 @@ impl AppConfig {
     fn require(key: &str) -> Result<String> {
         std::env::var(key).with_context(|| format!("Missing required env var {key}"))
     }
+
+    /// A secret from `key`, from the file `key_FILE` names (as Docker and
+    /// Kubernetes mount secrets), or from the OS keyring service named by
+    /// `KEYRING_SERVICE`, with `key` as the account, in that order.
+    pub fn secret(key: &str) -> Result<Option<String>> {
+        let file_key = format!("{key}_FILE");
+        match (Self::optional(key), Self::optional(&file_key)) {
+            (Some(_), Some(_)) => bail!("set either {key} or {file_key}, not both"),
+            (Some(value), None) => Ok(Some(value)),
+            (None, Some(path)) => {
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {file_key} {path}"))?;
+                let value = contents.trim_end_matches(['\r', '\n']);
+                if value.is_empty() {
+                    bail!("{file_key} {path} is empty");
+                }
+                Ok(Some(value.to_string()))
+            }
+            (None, None) => match Self::optional(KEYRING_SERVICE_VAR) {
+                Some(service) => keyring_secret(&service, key),
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn require_secret(key: &str) -> Result<String> {
+        Self::secret(key)?
+            .with_context(|| format!("Missing required env var {key} (or {key}_FILE)"))
+    }
+
+    fn optional(key: &str) -> Option<String> {
+        std::env::var(key).ok().filter(|value| !value.is_empty())
+    }
+
+    fn parsed<T: std::str::FromStr>(key: &str, expected: &str) -> Result<Option<T>>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        Self::optional(key)
+            .map(|value| value.parse())
+            .transpose()
+            .with_context(|| format!("{key} must be {expected}"))
+    }
+
+    fn call_log() -> Result<Option<CallLogTarget>> {
+        match Self::optional("CALL_LOG").as_deref() {
+            None => Ok(None),
+            Some("jsonl") => {
+                let path = Self::optional("CALL_LOG_PATH")
+                    .unwrap_or_else(|| DEFAULT_CALL_LOG_PATH.to_string());
+                Ok(Some(CallLogTarget::Jsonl(path.into())))
+            }
+            Some("table") => Ok(Some(CallLogTarget::Table)),
+            Some(other) => bail!("CALL_LOG must be jsonl or table, got {other:?}"),
+        }
+    }
+
+    fn tool_groups() -> Result<Vec<ToolGroup>> {
+        if Self::optional("TOOL_GROUPS").is_none() {
+            return Ok(ToolGroup::ALL.to_vec());
+        }
+        Self::json_list("TOOL_GROUPS")?
+            .iter()
+            .map(|group| group.parse())
+            .collect::<Result<_>>()
+            .context("TOOL_GROUPS must list core, analytics, maintenance or monitoring")
+    }
+
+    fn confirmation_window() -> Result<Option<Duration>> {
+        if !Self::flag("REQUIRE_CONFIRMATION") {
+            return Ok(None);
+        }
+        let secs = Self::parsed("CONFIRMATION_WINDOW_SECS", "a number of seconds")?
+            .unwrap_or(DEFAULT_CONFIRMATION_WINDOW_SECS);
+        Ok(Some(Duration::from_secs(secs)))
+    }
+
+    fn flag(key: &str) -> bool {
+        std::env::var(key)
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false)
+    }
+
+    fn json_list(key: &str) -> Result<Vec<String>> {
+        match Self::optional(key) {
+            Some(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("{key} must be a JSON array of strings")),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn json_map<T: serde::de::DeserializeOwned>(
+        key: &str,
+        values: &str,
+    ) -> Result<HashMap<String, T>> {
+        match Self::optional(key) {
+            Some(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("{key} must be a JSON object of {values}")),
+            None => Ok(HashMap::new()),
+        }
+    }
+}
+
+/// Reads `key` from the keyring `service`, on a thread of its own because
+/// the Secret Service client blocks on D-Bus calls of its own runtime.
+#[cfg(feature = "keyring")]
+fn keyring_secret(service: &str, key: &str) -> Result<Option<String>> {
+    let lookup = || keyring::Entry::new(service, key)?.get_password();
+    let found = std::thread::scope(|scope| scope.spawn(lookup).join())
+        .map_err(|_| anyhow::anyhow!("keyring lookup of {key} panicked"))?;
+    match found {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => {
+            Err(err).with_context(|| format!("failed to read {key} from keyring service {service}"))
+        }
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_secret(_service: &str, _key: &str) -> Result<Option<String>> {
+    bail!("KEYRING_SERVICE requires building with the `keyring` feature")
+}
+
+/// Azure OpenAI endpoint settings. Enabled when `AZURE_OPENAI_ENDPOINT` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AzureOpenAiConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    pub deployment: String,
+    pub api_version: String,
+}
+
+impl AzureOpenAiConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(endpoint) = AppConfig::optional("AZURE_OPENAI_ENDPOINT") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            endpoint,
+            api_key: AppConfig::require_secret("AZURE_OPENAI_API_KEY")?,
+            deployment: AppConfig::require("AZURE_OPENAI_DEPLOYMENT")?,
+            api_version: AppConfig::optional("AZURE_OPENAI_API_VERSION")
+                .unwrap_or_else(|| DEFAULT_AZURE_API_VERSION.to_string()),
+        }))
+    }
+}
+
+/// Plaid API settings. Enabled when `PLAID_CLIENT_ID` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaidConfig {
+    pub client_id: String,
+    pub secret: String,
+    /// API host of the environment `PLAID_ENV` names.
+    pub base_url: String,
+    /// AES-256 key access tokens are encrypted with before they are stored,
+    /// from `PLAID_TOKEN_KEY` in base64.
+    pub token_key: [u8; 32],
+}
+
+impl PlaidConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(client_id) = AppConfig::optional("PLAID_CLIENT_ID") else {
+            return Ok(None);
+        };
+        let base_url = match AppConfig::optional("PLAID_ENV").as_deref() {
+            None | Some("sandbox") => PLAID_SANDBOX_URL,
+            Some("production") => PLAID_PRODUCTION_URL,
+            Some(other) => bail!("PLAID_ENV must be sandbox or production, not {other:?}"),
+        };
+
+        let token_key = AppConfig::require_secret("PLAID_TOKEN_KEY")?;
+        let token_key = STANDARD
+            .decode(token_key.trim())
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .context(
+                "PLAID_TOKEN_KEY must be 32 bytes in base64, e.g. from `openssl rand -base64 32`",
+            )?;
+
+        Ok(Some(Self {
+            client_id,
+            secret: AppConfig::require_secret("PLAID_SECRET")?,
+            base_url: base_url.to_string(),
+            token_key,
+        }))
+    }
+}
+
+/// GoCardless Bank Account Data settings. Enabled when
+/// `GOCARDLESS_SECRET_ID` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoCardlessConfig {
+    pub secret_id: String,
+    pub secret_key: String,
+    pub base_url: String,
+}
+
+impl GoCardlessConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(secret_id) = AppConfig::optional("GOCARDLESS_SECRET_ID") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            secret_id,
+            secret_key: AppConfig::require_secret("GOCARDLESS_SECRET_KEY")?,
+            base_url: AppConfig::optional("GOCARDLESS_BASE_URL")
+                .unwrap_or_else(|| GOCARDLESS_BASE_URL.to_string()),
+        }))
+    }
+}
+
+/// Etherscan settings for Ethereum addresses. Enabled when
+/// `ETHERSCAN_API_KEY` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EtherscanConfig {
+    pub api_key: String,
+    pub base_url: String,
+}
+
+impl EtherscanConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(api_key) = AppConfig::secret("ETHERSCAN_API_KEY")? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            api_key,
+            base_url: AppConfig::optional("ETHERSCAN_BASE_URL")
+                .unwrap_or_else(|| ETHERSCAN_BASE_URL.to_string()),
+        }))
+    }
+}
+
+/// Blockbook settings for Bitcoin addresses, read from `BLOCKBOOK_URL`,
+/// e.g. `https://btc1.trezor.io`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockbookConfig {
+    pub base_url: String,
+}
+
+/// Solana RPC settings for Solana wallets, read from `SOLANA_RPC_URL`,
+/// e.g. `https://api.mainnet-beta.solana.com`. Read as a secret, as
+/// indexers such as Helius take their API key in the URL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolanaConfig {
+    pub rpc_url: String,
+}
+
+/// Binance settings for exchange accounts. Enabled when `BINANCE_API_KEY`
+/// is set; the key needs read permission only.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinanceConfig {
+    pub api_key: String,
+    pub api_secret: String,
+    pub base_url: String,
+}
+
+impl BinanceConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(api_key) = AppConfig::secret("BINANCE_API_KEY")? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            api_key,
+            api_secret: AppConfig::require_secret("BINANCE_API_SECRET")?,
+            base_url: AppConfig::optional("BINANCE_BASE_URL")
+                .unwrap_or_else(|| BINANCE_BASE_URL.to_string()),
+        }))
+    }
+}
+
+/// Coinbase settings for exchange accounts. Enabled when
+/// `COINBASE_API_KEY` is set; the key needs the `wallet:accounts:read` and
+/// `wallet:transactions:read` permissions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinbaseConfig {
+    pub api_key: String,
+    pub api_secret: String,
+    pub base_url: String,
+}
+
+impl CoinbaseConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(api_key) = AppConfig::secret("COINBASE_API_KEY")? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            api_key,
+            api_secret: AppConfig::require_secret("COINBASE_API_SECRET")?,
+            base_url: AppConfig::optional("COINBASE_BASE_URL")
+                .unwrap_or_else(|| COINBASE_BASE_URL.to_string()),
+        }))
+    }
+}
+
+/// Exchange rate settings. Enabled when `FX_PROVIDER` is `ecb` or
+/// `exchangerate_host`; the latter needs `FX_API_KEY`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FxConfig {
+    pub provider: FxProvider,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    /// Currency the reporting tools' totals are converted into.
+    pub base_currency: String,
+    /// How long the latest rates are kept; a past day's are kept for good.
+    pub cache_ttl: Duration,
+}
+
+impl FxConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let provider = match AppConfig::optional("FX_PROVIDER").as_deref() {
+            None => return Ok(None),
+            Some("ecb") => FxProvider::Ecb,
+            Some("exchangerate_host") => FxProvider::ExchangerateHost,
+            Some(other) => bail!("FX_PROVIDER must be ecb or exchangerate_host, not {other:?}"),
+        };
+        let (default_url, api_key) = match provider {
+            FxProvider::Ecb => (FRANKFURTER_BASE_URL, None),
+            FxProvider::ExchangerateHost => (
+                EXCHANGERATE_HOST_BASE_URL,
+                Some(AppConfig::require_secret("FX_API_KEY")?),
+            ),
+        };
+
+        Ok(Some(Self {
+            provider,
+            base_url: AppConfig::optional("FX_BASE_URL").unwrap_or_else(|| default_url.to_string()),
+            api_key,
+            base_currency: base_currency()?,
+            cache_ttl: Duration::from_secs(
+                AppConfig::parsed("FX_CACHE_TTL_SECS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_FX_CACHE_TTL_SECS),
+            ),
+        }))
+    }
+}
+
+/// Crypto asset price settings. Enabled when `PRICE_PROVIDER` is
+/// `coingecko`; `PRICE_API_KEY` is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceConfig {
+    /// The Pro API's URL on the `pro` plan, else the public API's.
+    pub base_url: String,
+    /// A CoinGecko key of the `api_plan`.
+    pub api_key: Option<String>,
+    /// Which CoinGecko plan `api_key` is of, `PRICE_API_PLAN`.
+    pub api_plan: CoinGeckoPlan,
+    /// Currency `get_asset_price` prices in unless asked for another.
+    pub base_currency: String,
+    /// How long a latest price is kept; a past day's is kept for good.
+    pub cache_ttl: Duration,
+}
+
+impl PriceConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        match AppConfig::optional("PRICE_PROVIDER").as_deref() {
+            None => return Ok(None),
+            Some("coingecko") => {}
+            Some(other) => bail!("PRICE_PROVIDER must be coingecko, not {other:?}"),
+        }
+
+        let api_plan: CoinGeckoPlan = AppConfig::optional("PRICE_API_PLAN")
+            .map(|value| value.parse())
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Some(Self {
+            base_url: AppConfig::optional("PRICE_BASE_URL").unwrap_or_else(|| {
+                match api_plan {
+                    CoinGeckoPlan::Demo => COINGECKO_BASE_URL,
+                    CoinGeckoPlan::Pro => COINGECKO_PRO_BASE_URL,
+                }
+                .to_string()
+            }),
+            api_key: AppConfig::secret("PRICE_API_KEY")?,
+            api_plan,
+            base_currency: base_currency()?,
+            cache_ttl: Duration::from_secs(
+                AppConfig::parsed("PRICE_CACHE_TTL_SECS", "a number of seconds")?
+                    .unwrap_or(DEFAULT_PRICE_CACHE_TTL_SECS),
+            ),
+        }))
+    }
+}
+
+/// The CoinGecko plan of a price API key, which decides the header it is
+/// sent in: demo keys only work on the public API, Pro keys on the Pro one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoinGeckoPlan {
+    /// A free demo key, sent as `x-cg-demo-api-key`.
+    #[default]
+    Demo,
+    /// A paid key, sent as `x-cg-pro-api-key`.
+    Pro,
+}
+
+impl FromStr for CoinGeckoPlan {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "demo" => Ok(Self::Demo),
+            "pro" => Ok(Self::Pro),
+            _ => bail!("unknown PRICE_API_PLAN {value:?} (expected demo or pro)"),
+        }
+    }
+}
+
+/// Receipt reading settings. Enabled when `RECEIPT_MODEL` names a
+/// vision-capable model behind an OpenAI-compatible chat completions API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptConfig {
+    pub model: String,
+    pub base_url: String,
+    /// `RECEIPT_API_KEY`, else `OPENAI_API_KEY`; a local model may need
+    /// none.
+    pub api_key: Option<String>,
+}
+
+impl ReceiptConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(model) = AppConfig::optional("RECEIPT_MODEL") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            model,
+            base_url: AppConfig::optional("RECEIPT_BASE_URL")
+                .or_else(|| AppConfig::optional("OPENAI_BASE_URL"))
+                .unwrap_or_else(|| OPENAI_BASE_URL.to_string()),
+            api_key: match AppConfig::secret("RECEIPT_API_KEY")? {
+                Some(api_key) => Some(api_key),
+                None => AppConfig::secret("OPENAI_API_KEY")?,
+            },
+        }))
+    }
+}
+
+/// Google Sheets settings for `export_to_sheet`. Enabled when
+/// `GOOGLE_SHEETS_CLIENT_ID` is set, with the OAuth client's secret and a
+/// refresh token of a user who can edit the spreadsheets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoogleSheetsConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    /// The spreadsheet exports go to unless the call names another.
+    pub spreadsheet_id: Option<String>,
+    pub base_url: String,
+    pub token_url: String,
+}
+
+impl GoogleSheetsConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(client_id) = AppConfig::optional("GOOGLE_SHEETS_CLIENT_ID") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            client_id,
+            client_secret: AppConfig::require_secret("GOOGLE_SHEETS_CLIENT_SECRET")?,
+            refresh_token: AppConfig::require_secret("GOOGLE_SHEETS_REFRESH_TOKEN")?,
+            spreadsheet_id: AppConfig::optional("GOOGLE_SHEETS_SPREADSHEET_ID"),
+            base_url: AppConfig::optional("GOOGLE_SHEETS_BASE_URL")
+                .unwrap_or_else(|| GOOGLE_SHEETS_BASE_URL.to_string()),
+            token_url: AppConfig::optional("GOOGLE_TOKEN_URL")
+                .unwrap_or_else(|| GOOGLE_TOKEN_URL.to_string()),
+        }))
+    }
+}
+
+/// Notion settings for `export_to_sheet`. Enabled when `NOTION_TOKEN` holds
+/// the token of an integration the databases are shared with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotionConfig {
+    pub token: String,
+    /// The database exports go to unless the call names another.
+    pub database_id: Option<String>,
+    pub base_url: String,
+}
+
+impl NotionConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(token) = AppConfig::secret("NOTION_TOKEN")? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            token,
+            database_id: AppConfig::optional("NOTION_DATABASE_ID"),
+            base_url: AppConfig::optional("NOTION_BASE_URL")
+                .unwrap_or_else(|| NOTION_BASE_URL.to_string()),
+        }))
+    }
+}
+
+/// YNAB settings for `import_ynab` and `export_ynab`. Enabled when
+/// `YNAB_TOKEN` holds a personal access token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct YnabConfig {
+    pub token: String,
+    /// The budget read and written unless the call names another; the one
+    /// opened last when unset.
+    pub budget_id: Option<String>,
+    pub base_url: String,
+}
+
+impl YnabConfig {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(token) = AppConfig::secret("YNAB_TOKEN")? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            token,
+            budget_id: AppConfig::optional("YNAB_BUDGET_ID"),
+            base_url: AppConfig::optional("YNAB_BASE_URL")
+                .unwrap_or_else(|| YNAB_BASE_URL.to_string()),
+        }))
+    }
+}
+
+/// `BASE_CURRENCY`, uppercased, which reports and prices are given in.
+fn base_currency() -> Result<String> {
+    let base_currency = AppConfig::optional("BASE_CURRENCY")
+        .unwrap_or_else(|| DEFAULT_BASE_CURRENCY.to_string())
+        .to_ascii_uppercase();
+    if base_currency.len() != 3 || !base_currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        bail!("BASE_CURRENCY must be an ISO 4217 code such as USD, not {base_currency:?}");
+    }
+    Ok(base_currency)
+}
+
+/// The name, version and instructions the server reports when a client
+/// connects. The instructions reach the client's model, so a deployment can
+/// describe its data there, e.g. "Family budget, EUR base currency".
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerIdentity {
+    pub name: String,
+    /// Display name for clients that show one.
+    pub title: Option<String>,
+    pub version: String,
+    pub instructions: String,
+}
+
+impl Default for ServerIdentity {
+    fn default() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            title: None,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            instructions: DEFAULT_SERVER_INSTRUCTIONS.to_string(),
+        }
+    }
+}
+
+impl ServerIdentity {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            name: AppConfig::optional("SERVER_NAME").unwrap_or(default.name),
+            title: AppConfig::optional("SERVER_TITLE"),
+            version: AppConfig::optional("SERVER_VERSION").unwrap_or(default.version),
+            instructions: AppConfig::optional("SERVER_INSTRUCTIONS")
+                .unwrap_or(default.instructions),
+        }
+    }
+}
+
+/// Timeouts and connection pool limits for outgoing HTTP clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpClientConfig {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    pub pool_idle_timeout: Duration,
+    /// Idle connections kept per host; `None` keeps reqwest's unbounded default.
+    pub pool_max_idle_per_host: Option<usize>,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_millis(DEFAULT_HTTP_CONNECT_TIMEOUT_MS),
+            request_timeout: Duration::from_millis(DEFAULT_HTTP_REQUEST_TIMEOUT_MS),
+            pool_idle_timeout: Duration::from_millis(DEFAULT_HTTP_POOL_IDLE_TIMEOUT_MS),
+            pool_max_idle_per_host: None,
+        }
+    }
+}
+
+impl HttpClientConfig {
+    pub fn from_env() -> Result<Self> {
+        let millis = |key: &str, default: u64| -> Result<Duration> {
+            Ok(Duration::from_millis(
+                AppConfig::parsed(key, "a number of milliseconds")?.unwrap_or(default),
+            ))
+        };
+
+        Ok(Self {
+            connect_timeout: millis("HTTP_CONNECT_TIMEOUT_MS", DEFAULT_HTTP_CONNECT_TIMEOUT_MS)?,
+            request_timeout: millis("HTTP_REQUEST_TIMEOUT_MS", DEFAULT_HTTP_REQUEST_TIMEOUT_MS)?,
+            pool_idle_timeout: millis(
+                "HTTP_POOL_IDLE_TIMEOUT_MS",
+                DEFAULT_HTTP_POOL_IDLE_TIMEOUT_MS,
+            )?,
+            pool_max_idle_per_host: AppConfig::parsed(
+                "HTTP_POOL_MAX_IDLE_PER_HOST",
+                "a non-negative integer",
+            )?,
+        })
+    }
+
+    /// Applies these settings to a reqwest client builder.
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        let builder = builder
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+            .pool_idle_timeout(self.pool_idle_timeout);
+        match self.pool_max_idle_per_host {
+            Some(max) => builder.pool_max_idle_per_host(max),
+            None => builder,
+        }
+    }
+}
+
+/// How the server logs, and where spans are exported over OTLP, read from
+/// the standard OpenTelemetry variables. Read before logging starts, apart
+/// from the rest of the config.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TracingConfig {
+    pub log_format: LogFormat,
+    /// A file log lines are also written to, if any.
+    pub log_file: Option<LogFileConfig>,
+    /// Full URL spans are posted to; export is off when unset.
+    pub otlp_endpoint: Option<String>,
+    /// Headers sent with every export, such as an API key.
+    pub otlp_headers: HashMap<String, String>,
+    /// `service.name` of the exported spans.
+    pub service_name: String,
+}
+
+impl TracingConfig {
+    pub fn from_env() -> Result<Self> {
+        let otlp_endpoint = AppConfig::optional("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").or_else(|| {
+            AppConfig::optional("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .map(|base| format!("{}/v1/traces", base.trim_end_matches('/')))
+        });
+        Ok(Self {
+            log_format: AppConfig::optional("LOG_FORMAT")
+                .map(|value| value.parse())
+                .transpose()?
+                .unwrap_or_default(),
+            log_file: AppConfig::optional("LOG_FILE")
+                .map(|path| -> Result<_> {
+                    Ok(LogFileConfig {
+                        path: path.into(),
+                        max_bytes: AppConfig::parsed("LOG_FILE_MAX_BYTES", "a number of bytes")?
+                            .unwrap_or(DEFAULT_LOG_FILE_MAX_BYTES),
+                        retained: AppConfig::parsed("LOG_FILE_RETAINED", "a non-negative integer")?
+                            .unwrap_or(DEFAULT_LOG_FILE_RETAINED),
+                    })
+                })
+                .transpose()?,
+            otlp_endpoint,
+            otlp_headers: AppConfig::optional("OTEL_EXPORTER_OTLP_HEADERS")
+                .map(|headers| parse_otlp_headers(&headers))
+                .transpose()?
+                .unwrap_or_default(),
+            service_name: AppConfig::optional("OTEL_SERVICE_NAME")
+                .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string()),
+        })
+    }
+}
+
+/// A log file rotated by size; see [`crate::log_file::RotatingFile`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFileConfig {
+    pub path: PathBuf,
+    /// Size past which the file is rotated.
+    pub max_bytes: u64,
+    /// Rotated files kept, as `path.1` (newest) to `path.N`.
+    pub retained: usize,
+}
+
+/// Shape of the log lines written to stderr and the log file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, see [`crate::log_format::JsonFormat`].
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown LOG_FORMAT {value:?} (expected text or json)"),
+        }
+    }
+}
+
+/// Parses `OTEL_EXPORTER_OTLP_HEADERS`: comma-separated `key=value` pairs.
+pub fn parse_otlp_headers(value: &str) -> Result<HashMap<String, String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => bail!("OTEL_EXPORTER_OTLP_HEADERS entries must be key=value, got {pair:?}"),
+        })
+        .collect()
 }
@@ -0,0 +1,289 @@
+//! `exaspoon.toml` or `exaspoon.yaml`: the environment variables of
+//! [`AppConfig`](crate::config::AppConfig) grouped into sections. A file is
+//! applied like `.env`, filling in the variables the environment leaves
+//! unset, so an environment variable always overrides the file.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the configuration file to load.
+pub const CONFIG_PATH_VAR: &str = "EXASPOON_CONFIG";
+
+/// Files looked for in the working directory, in order, when no path is
+/// given. `exaspoon/config.toml` and `exaspoon/config.yaml` in the user's
+/// configuration directory are tried after these.
+pub const DEFAULT_CONFIG_PATHS: [&str; 4] = [
+    "exaspoon.toml",
+    "config.toml",
+    "exaspoon.yaml",
+    "exaspoon.yml",
+];
+
+/// Keys a configuration file may set, by section, and the environment
+/// variables they stand for.
+pub const SETTINGS: &[(&str, &str)] = &[
+    ("server.name", "SERVER_NAME"),
+    ("server.title", "SERVER_TITLE"),
+    ("server.version", "SERVER_VERSION"),
+    ("server.instructions", "SERVER_INSTRUCTIONS"),
+    ("server.locale", "LOCALE"),
+    ("server.shutdown_timeout_ms", "SHUTDOWN_TIMEOUT_MS"),
+    ("server.pid_file", "PID_FILE"),
+    ("database.backend", "DATABASE_BACKEND"),
+    ("database.sqlite_path", "SQLITE_PATH"),
+    ("database.tenant_id", "TENANT_ID"),
+    ("supabase.url", "SUPABASE_URL"),
+    ("supabase.service_key", "SUPABASE_SERVICE_KEY"),
+    ("supabase.access_token", "SUPABASE_ACCESS_TOKEN"),
+    ("supabase.require_user_auth", "SUPABASE_REQUIRE_USER_AUTH"),
+    ("supabase.read_url", "SUPABASE_READ_URL"),
+    ("supabase.db_url", "SUPABASE_DB_URL"),
+    ("supabase.schema", "SUPABASE_SCHEMA"),
+    ("supabase.realtime", "SUPABASE_REALTIME"),
+    ("supabase.max_retries", "SUPABASE_MAX_RETRIES"),
+    (
+        "supabase.retry_base_delay_ms",
+        "SUPABASE_RETRY_BASE_DELAY_MS",
+    ),
+    ("plaid.client_id", "PLAID_CLIENT_ID"),
+    ("plaid.secret", "PLAID_SECRET"),
+    ("plaid.env", "PLAID_ENV"),
+    ("plaid.token_key", "PLAID_TOKEN_KEY"),
+    ("gocardless.secret_id", "GOCARDLESS_SECRET_ID"),
+    ("gocardless.secret_key", "GOCARDLESS_SECRET_KEY"),
+    ("gocardless.base_url", "GOCARDLESS_BASE_URL"),
+    ("etherscan.api_key", "ETHERSCAN_API_KEY"),
+    ("etherscan.base_url", "ETHERSCAN_BASE_URL"),
+    ("blockbook.url", "BLOCKBOOK_URL"),
+    ("solana.rpc_url", "SOLANA_RPC_URL"),
+    ("binance.api_key", "BINANCE_API_KEY"),
+    ("binance.api_secret", "BINANCE_API_SECRET"),
+    ("binance.base_url", "BINANCE_BASE_URL"),
+    ("coinbase.api_key", "COINBASE_API_KEY"),
+    ("coinbase.api_secret", "COINBASE_API_SECRET"),
+    ("coinbase.base_url", "COINBASE_BASE_URL"),
+    ("fx.provider", "FX_PROVIDER"),
+    ("fx.api_key", "FX_API_KEY"),
+    ("fx.base_url", "FX_BASE_URL"),
+    ("fx.base_currency", "BASE_CURRENCY"),
+    ("fx.cache_ttl_secs", "FX_CACHE_TTL_SECS"),
+    ("prices.provider", "PRICE_PROVIDER"),
+    ("prices.api_key", "PRICE_API_KEY"),
+    ("prices.api_plan", "PRICE_API_PLAN"),
+    ("prices.base_url", "PRICE_BASE_URL"),
+    ("prices.cache_ttl_secs", "PRICE_CACHE_TTL_SECS"),
+    ("receipt.model", "RECEIPT_MODEL"),
+    ("receipt.base_url", "RECEIPT_BASE_URL"),
+    ("receipt.api_key", "RECEIPT_API_KEY"),
+    ("google_sheets.client_id", "GOOGLE_SHEETS_CLIENT_ID"),
+    ("google_sheets.client_secret", "GOOGLE_SHEETS_CLIENT_SECRET"),
+    ("google_sheets.refresh_token", "GOOGLE_SHEETS_REFRESH_TOKEN"),
+    ("google_sheets.spreadsheet_id", "GOOGLE_SHEETS_SPREADSHEET_ID"),
+    ("google_sheets.base_url", "GOOGLE_SHEETS_BASE_URL"),
+    ("google_sheets.token_url", "GOOGLE_TOKEN_URL"),
+    ("notion.token", "NOTION_TOKEN"),
+    ("notion.database_id", "NOTION_DATABASE_ID"),
+    ("notion.base_url", "NOTION_BASE_URL"),
+    ("ynab.token", "YNAB_TOKEN"),
+    ("ynab.budget_id", "YNAB_BUDGET_ID"),
+    ("ynab.base_url", "YNAB_BASE_URL"),
+    ("embedding.provider", "EMBEDDING_PROVIDER"),
+    ("embedding.model", "EMBEDDING_MODEL"),
+    ("embedding.dimensions", "EMBEDDING_DIMENSIONS"),
+    ("embedding.encoding_format", "EMBEDDING_ENCODING_FORMAT"),
+    ("embedding.document_prompt", "EMBEDDING_DOCUMENT_PROMPT"),
+    ("embedding.query_prompt", "EMBEDDING_QUERY_PROMPT"),
+    (
+        "embedding.calls_per_minute",
+        "RATE_LIMIT_EMBEDDING_CALLS_PER_MINUTE",
+    ),
+    ("embedding.openai.api_key", "OPENAI_API_KEY"),
+    ("embedding.openai.base_url", "OPENAI_BASE_URL"),
+    ("embedding.azure.endpoint", "AZURE_OPENAI_ENDPOINT"),
+    ("embedding.azure.api_key", "AZURE_OPENAI_API_KEY"),
+    ("embedding.azure.deployment", "AZURE_OPENAI_DEPLOYMENT"),
+    ("embedding.azure.api_version", "AZURE_OPENAI_API_VERSION"),
+    ("embedding.cohere.api_key", "COHERE_API_KEY"),
+    ("embedding.voyage.api_key", "VOYAGE_API_KEY"),
+    ("embedding.ollama.base_url", "OLLAMA_BASE_URL"),
+    ("transport.mode", "TRANSPORT"),
+    ("transport.bind_address", "BIND_ADDRESS"),
+    ("transport.allowed_hosts", "ALLOWED_HOSTS"),
+    ("transport.max_sessions", "MAX_SESSIONS"),
+    (
+        "transport.session_idle_timeout_secs",
+        "SESSION_IDLE_TIMEOUT_SECS",
+    ),
+    (
+        "transport.stdio_idle_timeout_secs",
+        "STDIO_IDLE_TIMEOUT_SECS",
+    ),
+    ("transport.health_address", "HEALTH_ADDRESS"),
+    (
+        "transport.health_probe_interval_secs",
+        "HEALTH_PROBE_INTERVAL_SECS",
+    ),
+    ("tools.admin", "ENABLE_ADMIN_TOOLS"),
+    ("tools.demo_seed", "ENABLE_DEMO_SEED"),
+    ("tools.read_only", "READ_ONLY"),
+    ("tools.dry_run", "DRY_RUN"),
+    ("tools.require_confirmation", "REQUIRE_CONFIRMATION"),
+    ("tools.confirmation_window_secs", "CONFIRMATION_WINDOW_SECS"),
+    ("tools.groups", "TOOL_GROUPS"),
+    ("tools.disabled", "DISABLED_TOOLS"),
+    ("tools.name_prefix", "TOOL_NAME_PREFIX"),
+    ("tools.aliases", "TOOL_ALIASES"),
+    ("tools.rpc_allowlist", "RPC_ALLOWLIST"),
+    ("tools.timeout_ms", "TOOL_TIMEOUT_MS"),
+    ("tools.timeouts_ms", "TOOL_TIMEOUTS_MS"),
+    ("tools.calls_per_minute", "RATE_LIMIT_CALLS_PER_MINUTE"),
+    ("tools.max_concurrent_calls", "MAX_CONCURRENT_TOOL_CALLS"),
+    ("tools.max_concurrent_writes", "MAX_CONCURRENT_WRITES"),
+    (
+        "circuit_breaker.failure_threshold",
+        "CIRCUIT_BREAKER_FAILURE_THRESHOLD",
+    ),
+    ("circuit_breaker.open_ms", "CIRCUIT_BREAKER_OPEN_MS"),
+    ("http_client.connect_timeout_ms", "HTTP_CONNECT_TIMEOUT_MS"),
+    ("http_client.request_timeout_ms", "HTTP_REQUEST_TIMEOUT_MS"),
+    (
+        "http_client.pool_idle_timeout_ms",
+        "HTTP_POOL_IDLE_TIMEOUT_MS",
+    ),
+    (
+        "http_client.pool_max_idle_per_host",
+        "HTTP_POOL_MAX_IDLE_PER_HOST",
+    ),
+    ("call_log.target", "CALL_LOG"),
+    ("call_log.path", "CALL_LOG_PATH"),
+    ("call_log.redacted_fields", "CALL_LOG_REDACTED_FIELDS"),
+    ("pii_redaction.enabled", "PII_REDACTION"),
+    ("pii_redaction.patterns", "PII_REDACTION_PATTERNS"),
+    ("log.level", "LOG_LEVEL"),
+    ("log.format", "LOG_FORMAT"),
+    ("log.file", "LOG_FILE"),
+    ("log.file_max_bytes", "LOG_FILE_MAX_BYTES"),
+    ("log.file_retained", "LOG_FILE_RETAINED"),
+    ("otel.endpoint", "OTEL_EXPORTER_OTLP_ENDPOINT"),
+    ("otel.traces_endpoint", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"),
+    ("otel.headers", "OTEL_EXPORTER_OTLP_HEADERS"),
+    ("otel.service_name", "OTEL_SERVICE_NAME"),
+];
+
+/// A configuration file, read into the environment variables it sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub settings: BTreeMap<String, String>,
+    /// Keys this version does not know, such as those of a newer one, which
+    /// are ignored.
+    pub unknown: Vec<String>,
+}
+
+impl ConfigFile {
+    /// Loads `path`, or the file named by `EXASPOON_CONFIG`, both of which
+    /// must exist; without either, the first of the default locations that
+    /// exists, or `None`.
+    pub fn find(path: Option<&Path>) -> Result<Option<Self>> {
+        let named = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(CONFIG_PATH_VAR).map(PathBuf::from));
+        if let Some(path) = named {
+            return Self::load(&path).map(Some);
+        }
+        let user_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|dir| dir.join("exaspoon"));
+        let candidates = DEFAULT_CONFIG_PATHS.iter().map(PathBuf::from).chain(
+            user_dir
+                .iter()
+                .flat_map(|dir| [dir.join("config.toml"), dir.join("config.yaml")]),
+        );
+        for candidate in candidates {
+            if candidate.is_file() {
+                return Self::load(&candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads a TOML or YAML file, telling them apart by extension.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        Self::parse(path, &text).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// The environment variables set by `text`, in the format the extension
+    /// of `path` names.
+    pub fn parse(path: &Path, text: &str) -> Result<Self> {
+        let document: Value = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(text)?,
+            Some("yaml" | "yml") => serde_yaml::from_str(text)?,
+            _ => bail!("expected a .toml, .yaml or .yml file"),
+        };
+        let mut file = Self {
+            path: path.to_path_buf(),
+            settings: BTreeMap::new(),
+            unknown: Vec::new(),
+        };
+        match document {
+            Value::Object(sections) => file.flatten("", sections),
+            // An empty YAML file.
+            Value::Null => {}
+            _ => bail!("expected sections of settings at the top level"),
+        }
+        Ok(file)
+    }
+
+    /// Sets every variable of the file the environment leaves unset, and
+    /// returns those the environment overrides. Setting variables races with
+    /// other threads reading them, so call this before starting any.
+    pub fn apply(&self) -> Vec<&str> {
+        let mut overridden = Vec::new();
+        for (key, value) in &self.settings {
+            if std::env::var_os(key).is_some() {
+                overridden.push(key.as_str());
+            } else {
+                std::env::set_var(key, value);
+            }
+        }
+        overridden
+    }
+
+    fn flatten(&mut self, prefix: &str, table: serde_json::Map<String, Value>) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() {
+                key
+            } else {
+                format!("{prefix}.{key}")
+            };
+            let setting = SETTINGS.iter().find(|(name, _)| *name == path);
+            match (setting, value) {
+                (_, Value::Null) => {}
+                (Some((_, var)), value) => {
+                    self.settings.insert(var.to_string(), env_value(var, value));
+                }
+                (None, Value::Object(table)) => self.flatten(&path, table),
+                (None, _) => self.unknown.push(path),
+            }
+        }
+    }
+}
+
+/// `value` as its environment variable spells it: scalars as text, lists and
+/// maps as JSON, and OTLP headers as `key=value` pairs.
+fn env_value(var: &str, value: Value) -> String {
+    match value {
+        Value::String(text) => text,
+        Value::Object(headers) if var == "OTEL_EXPORTER_OTLP_HEADERS" => headers
+            .into_iter()
+            .map(|(name, value)| format!("{name}={}", env_value(&name, value)))
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
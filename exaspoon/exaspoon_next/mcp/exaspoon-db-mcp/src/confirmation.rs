@@ -0,0 +1,98 @@
+//! Two-phase confirmation of destructive tool calls. A call without a token
+//! only previews what it would do and gets a single-use token for exactly
+//! those arguments; running it takes a second call carrying the token before
+//! it expires, so one bad call cannot delete data on its own.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CONFIRMATION_WINDOW_SECS: u64 = 300;
+
+/// Argument a destructive call passes its token in.
+pub const CONFIRMATION_TOKEN_FIELD: &str = "confirmation_token";
+
+/// Why a token does not confirm a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refused {
+    /// The token was never issued or has been used.
+    Unknown,
+    /// The token's window has passed.
+    Expired,
+    /// The token was issued for another tool or other arguments.
+    Mismatch,
+}
+
+impl Refused {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Expired => "expired",
+            Self::Mismatch => "mismatch",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    tool: String,
+    fingerprint: String,
+    expires_at: Instant,
+}
+
+/// Tokens issued by previews and not yet used.
+#[derive(Debug)]
+pub struct Confirmations {
+    window: Duration,
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl Confirmations {
+    /// Tokens stay valid for `window` after their preview.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: Mutex::default(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// A token confirming a call of `tool` with arguments hashing to
+    /// `fingerprint`.
+    pub fn issue(&self, tool: &str, fingerprint: &str) -> String {
+        let now = Instant::now();
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, issued| issued.expires_at > now);
+        pending.insert(
+            token.clone(),
+            Pending {
+                tool: tool.to_string(),
+                fingerprint: fingerprint.to_string(),
+                expires_at: now + self.window,
+            },
+        );
+        token
+    }
+
+    /// Uses up `token`, succeeding when it was issued for this call and has
+    /// not expired. A refused token is used up all the same.
+    pub fn redeem(&self, token: &str, tool: &str, fingerprint: &str) -> Result<(), Refused> {
+        let issued = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(token.trim())
+            .ok_or(Refused::Unknown)?;
+        if issued.expires_at <= Instant::now() {
+            return Err(Refused::Expired);
+        }
+        if issued.tool != tool || issued.fingerprint != fingerprint {
+            return Err(Refused::Mismatch);
+        }
+        Ok(())
+    }
+}
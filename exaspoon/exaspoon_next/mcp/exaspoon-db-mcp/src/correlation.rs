@@ -0,0 +1,47 @@
+//! Correlation ids tying a tool call together across the server's logs, the
+//! requests it sends to Supabase and the embedding provider, and the error
+//! its client receives.
+//!
+//! Each call gets a fresh id. Every tracing span opened while serving it is
+//! nested in the call's span, which carries the id, and outgoing requests send
+//! it as [`CORRELATION_HEADER`], so a failure reported by a client can be
+//! matched with the server's logs and the providers' dashboards.
+
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::future::Future;
+
+/// Header carrying the id on requests to Supabase and embedding providers.
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+/// Key of the error data holding the id of the failed call.
+pub const CORRELATION_ID_FIELD: &str = "correlation_id";
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// A new id for a tool call.
+pub fn generate() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Runs `future` with `id` as the current call's correlation id.
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    CURRENT.scope(id, future).await
+}
+
+/// The id of the tool call being served, if any.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(Clone::clone).ok()
+}
+
+/// [`CORRELATION_HEADER`] with the current id, or no headers outside of a
+/// call.
+pub fn headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let value = current().and_then(|id| HeaderValue::from_str(&id).ok());
+    if let Some(value) = value {
+        headers.insert(CORRELATION_HEADER, value);
+    }
+    headers
+}
@@ -0,0 +1,123 @@
+//! Running as a long-lived system service: readiness and stopping
+//! notifications for systemd `Type=notify` units, a PID file for init systems
+//! that track the process by one, and ignoring the hangup of a closed
+//! terminal.
+
+use anyhow::{bail, Context, Result};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Sends `state` (such as `READY=1`) to the service manager at
+/// `NOTIFY_SOCKET`. Returns whether one was listening there; without the
+/// variable, as outside systemd, this does nothing.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    send(Path::new(&socket), state)?;
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn send(socket: &Path, state: &str) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let sender = UnixDatagram::unbound()?;
+    // `@name` is a Linux abstract socket.
+    match socket.as_os_str().as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            sender.send_to_addr(state.as_bytes(), &address)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return Err(io::Error::other("abstract sockets need Linux")),
+        None => {
+            sender.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &Path, _state: &str) -> io::Result<()> {
+    Err(io::Error::other("NOTIFY_SOCKET is only supported on Unix"))
+}
+
+/// Tells the service manager the server accepts connections.
+pub fn notify_ready() {
+    match notify("READY=1\nSTATUS=Accepting connections") {
+        Ok(true) => info!("Notified the service manager of readiness"),
+        Ok(false) => {}
+        Err(err) => warn!("Failed to notify readiness: {}", err),
+    }
+}
+
+/// Tells the service manager the server is shutting down.
+pub fn notify_stopping() {
+    if let Err(err) = notify("STOPPING=1\nSTATUS=Draining tool calls") {
+        warn!("Failed to notify stopping: {}", err);
+    }
+}
+
+/// The process id written to a file for as long as the server runs. The file
+/// is removed when this drops.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Writes the process id to `path`, replacing a file left by a process
+    /// that is gone, but failing while the process it names still runs.
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Ok(contents) = std::fs::read_to_string(path) {
+            if let Ok(pid) = contents.trim().parse::<u32>() {
+                if pid != std::process::id() && is_running(pid) {
+                    bail!("{} names running process {pid}", path.display());
+                }
+            }
+        }
+        // Written aside and renamed, so a reader never sees a partial id.
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".tmp");
+        std::fs::write(&partial, format!("{}\n", std::process::id()))
+            .and_then(|()| std::fs::rename(&partial, path))
+            .with_context(|| format!("failed to write PID file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether process `pid` exists, judged from `/proc` where there is one.
+/// Elsewhere a leftover file is always taken to be stale.
+fn is_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    proc.is_dir() && proc.join(pid.to_string()).exists()
+}
+
+/// Keeps SIGHUP, sent when the terminal a server was started from closes,
+/// from terminating it.
+pub fn ignore_hangup() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = signal(SignalKind::hangup()).context("failed to handle SIGHUP")?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("Received SIGHUP; ignoring it");
+            }
+        });
+    }
+    Ok(())
+}
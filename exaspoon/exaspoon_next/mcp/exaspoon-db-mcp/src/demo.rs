@@ -0,0 +1,312 @@
+//! Demo dataset for `seed_demo_data`: a household with a checking account, a
+//! credit card, savings and a crypto wallet, the categories it spends in, and
+//! a few months of believable transactions.
+//!
+//! Everything is deterministic, so seeding twice with the same date produces
+//! the same rows.
+
+use crate::models::{
+    AccountType, CategoryKind, CreateTransactionInput, TransactionDirection, UpsertAccountInput,
+    UpsertCategoryInput,
+};
+use chrono::{Datelike, Months, NaiveDate};
+
+/// Months of history seeded when the caller does not say.
+pub const DEFAULT_DEMO_MONTHS: u32 = 3;
+/// Most months of history one call may seed.
+pub const MAX_DEMO_MONTHS: u32 = 12;
+
+pub const CHECKING: &str = "Demo Checking";
+pub const CREDIT_CARD: &str = "Demo Credit Card";
+pub const SAVINGS: &str = "Demo Savings";
+pub const WALLET: &str = "Demo ETH Wallet";
+
+/// A demo transaction and the name of the demo account it belongs to. The
+/// input's `account_id` is left empty until the account exists.
+#[derive(Debug, Clone)]
+pub struct DemoTransaction {
+    pub account: &'static str,
+    pub transaction: CreateTransactionInput,
+}
+
+pub fn demo_accounts() -> Vec<UpsertAccountInput> {
+    let offchain = |name: &str, institution: &str| UpsertAccountInput {
+        name: name.to_string(),
+        r#type: AccountType::Offchain,
+        currency: "USD".to_string(),
+        network: None,
+        institution: Some(institution.to_string()),
+    };
+    vec![
+        offchain(CHECKING, "Demo Bank"),
+        offchain(CREDIT_CARD, "Demo Bank"),
+        offchain(SAVINGS, "Demo Credit Union"),
+        UpsertAccountInput {
+            name: WALLET.to_string(),
+            r#type: AccountType::Onchain,
+            currency: "ETH".to_string(),
+            network: Some("ethereum".to_string()),
+            institution: None,
+        },
+    ]
+}
+
+pub fn demo_categories() -> Vec<UpsertCategoryInput> {
+    let category = |name: &str, kind: CategoryKind, description: &str| UpsertCategoryInput {
+        name: name.to_string(),
+        kind: Some(kind),
+        description: Some(description.to_string()),
+    };
+    vec![
+        category(
+            "Salary",
+            CategoryKind::Income,
+            "Paychecks and payroll deposits from an employer",
+        ),
+        category(
+            "Staking",
+            CategoryKind::Income,
+            "Crypto staking and validator rewards",
+        ),
+        category(
+            "Rent",
+            CategoryKind::Expense,
+            "Monthly rent for the apartment",
+        ),
+        category(
+            "Utilities",
+            CategoryKind::Expense,
+            "Electricity, water, gas and internet bills",
+        ),
+        category(
+            "Groceries",
+            CategoryKind::Expense,
+            "Supermarket and grocery store purchases",
+        ),
+        category(
+            "Dining Out",
+            CategoryKind::Expense,
+            "Restaurants, cafes, coffee shops and takeout",
+        ),
+        category(
+            "Transport",
+            CategoryKind::Expense,
+            "Fuel, rideshare, parking and public transit",
+        ),
+        category(
+            "Subscriptions",
+            CategoryKind::Expense,
+            "Streaming services, apps and memberships",
+        ),
+        category(
+            "Savings",
+            CategoryKind::Transfer,
+            "Transfers between own accounts into savings",
+        ),
+    ]
+}
+
+/// Transactions for the `months` calendar months up to and including the one
+/// holding `today`, oldest first. Nothing is dated after `today`.
+pub fn demo_transactions(today: NaiveDate, months: u32) -> Vec<DemoTransaction> {
+    let this_month = today.with_day(1).unwrap_or(today);
+    let mut transactions = Vec::new();
+    for back in (0..months.clamp(1, MAX_DEMO_MONTHS)).rev() {
+        let Some(month) = this_month.checked_sub_months(Months::new(back)) else {
+            continue;
+        };
+        let seed = month.year() as u32 * 12 + month.month();
+        for entry in MONTHLY {
+            let Some(date) = month.with_day(entry.day) else {
+                continue;
+            };
+            if date > today {
+                continue;
+            }
+            transactions.push(DemoTransaction {
+                account: entry.account,
+                transaction: CreateTransactionInput {
+                    account_id: String::new(),
+                    amount: vary(entry.amount, entry.jitter, seed + entry.day),
+                    currency: entry.currency.to_string(),
+                    direction: entry.direction,
+                    occurred_at: format!("{}T12:00:00Z", date.format("%Y-%m-%d")),
+                    description: Some(entry.description.to_string()),
+                    raw_source: Some("seed_demo_data".to_string()),
+                },
+            });
+        }
+    }
+    transactions
+}
+
+struct MonthlyEntry {
+    day: u32,
+    account: &'static str,
+    direction: TransactionDirection,
+    amount: f64,
+    /// Largest relative change from `amount` between months, e.g. 0.2 for ±20%.
+    jitter: f64,
+    currency: &'static str,
+    description: &'static str,
+}
+
+const fn entry(
+    day: u32,
+    account: &'static str,
+    direction: TransactionDirection,
+    amount: f64,
+    jitter: f64,
+    description: &'static str,
+) -> MonthlyEntry {
+    MonthlyEntry {
+        day,
+        account,
+        direction,
+        amount,
+        jitter,
+        currency: "USD",
+        description,
+    }
+}
+
+const MONTHLY: &[MonthlyEntry] = {
+    use TransactionDirection::{Expense, Income, Transfer};
+    &[
+        entry(
+            1,
+            CHECKING,
+            Income,
+            4200.0,
+            0.0,
+            "ACME Corp payroll deposit",
+        ),
+        entry(
+            2,
+            CHECKING,
+            Transfer,
+            500.0,
+            0.0,
+            "Transfer to Demo Savings",
+        ),
+        entry(
+            2,
+            SAVINGS,
+            Transfer,
+            500.0,
+            0.0,
+            "Transfer from Demo Checking",
+        ),
+        entry(
+            3,
+            CHECKING,
+            Expense,
+            1650.0,
+            0.0,
+            "Rent - Maple Street Apartments",
+        ),
+        entry(
+            5,
+            CREDIT_CARD,
+            Expense,
+            96.4,
+            0.25,
+            "Whole Foods Market groceries",
+        ),
+        entry(
+            7,
+            CREDIT_CARD,
+            Expense,
+            4.75,
+            0.1,
+            "Blue Bottle Coffee latte",
+        ),
+        entry(8, CREDIT_CARD, Expense, 42.3, 0.3, "Shell gas station fuel"),
+        entry(
+            10,
+            CHECKING,
+            Expense,
+            84.0,
+            0.3,
+            "City Power & Light electricity bill",
+        ),
+        entry(
+            12,
+            CREDIT_CARD,
+            Expense,
+            71.15,
+            0.25,
+            "Trader Joe's groceries",
+        ),
+        entry(
+            14,
+            CREDIT_CARD,
+            Expense,
+            58.0,
+            0.35,
+            "Sushi Zen dinner for two",
+        ),
+        entry(
+            15,
+            CHECKING,
+            Expense,
+            59.99,
+            0.0,
+            "Fiber Internet monthly plan",
+        ),
+        entry(18, CREDIT_CARD, Expense, 15.49, 0.0, "Netflix subscription"),
+        entry(
+            19,
+            CREDIT_CARD,
+            Expense,
+            104.8,
+            0.25,
+            "Costco bulk groceries",
+        ),
+        entry(21, CREDIT_CARD, Expense, 18.6, 0.4, "Uber ride downtown"),
+        entry(
+            23,
+            CREDIT_CARD,
+            Expense,
+            10.99,
+            0.0,
+            "Spotify Premium subscription",
+        ),
+        entry(25, CHECKING, Expense, 32.0, 0.2, "City Water utility bill"),
+        entry(26, CREDIT_CARD, Expense, 63.25, 0.25, "Safeway groceries"),
+        entry(
+            27,
+            CHECKING,
+            Expense,
+            1200.0,
+            0.1,
+            "Demo Credit Card payment",
+        ),
+        entry(
+            28,
+            CREDIT_CARD,
+            Expense,
+            27.5,
+            0.3,
+            "Chipotle takeout lunch",
+        ),
+        MonthlyEntry {
+            day: 28,
+            account: WALLET,
+            direction: Income,
+            amount: 0.012,
+            jitter: 0.15,
+            currency: "ETH",
+            description: "Ethereum staking reward",
+        },
+    ]
+};
+
+/// `amount` moved by up to `jitter` in either direction, picked from `seed`
+/// and rounded to cents (or to six decimals below one unit).
+fn vary(amount: f64, jitter: f64, seed: u32) -> f64 {
+    let step = f64::from(seed.wrapping_mul(2_654_435_761) % 201) / 100.0 - 1.0;
+    let value = amount * (1.0 + jitter * step);
+    let scale = if amount < 1.0 { 1_000_000.0 } else { 100.0 };
+    (value * scale).round() / scale
+}
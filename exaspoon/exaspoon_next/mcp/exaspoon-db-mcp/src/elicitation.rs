@@ -0,0 +1,95 @@
+//! Requests a tool call sends back to its client: elicitation, to ask the
+//! user for input the call is missing, and sampling, to have the client's
+//! model write text.
+
+use rmcp::model::{
+    Content, CreateElicitationRequestParam, CreateMessageRequestParam, ElicitationAction,
+    ElicitationSchema, Role, SamplingMessage,
+};
+use rmcp::{Peer, RoleServer};
+use serde_json::Value;
+use std::future::Future;
+use tracing::{debug, info, warn};
+
+tokio::task_local! {
+    static CLIENT: Peer<RoleServer>;
+}
+
+/// Runs `future` with `peer` as the client the tool call can send requests to.
+pub async fn scope<F: Future>(peer: Peer<RoleServer>, future: F) -> F::Output {
+    CLIENT.scope(peer, future).await
+}
+
+/// Asks the user of the current tool call's client to fill in `schema`.
+/// Returns `None`, so the caller can fail as it would have without asking,
+/// when there is no client, it does not support elicitation, the request
+/// fails, or the user declines or cancels.
+pub async fn ask(message: String, schema: ElicitationSchema) -> Option<Value> {
+    let peer = CLIENT.try_with(Clone::clone).ok()?;
+    if !peer.supports_elicitation() {
+        debug!("Client does not support elicitation");
+        return None;
+    }
+    let request = CreateElicitationRequestParam {
+        message,
+        requested_schema: schema,
+    };
+    match peer.create_elicitation(request).await {
+        Ok(result) if result.action == ElicitationAction::Accept => result.content,
+        Ok(result) => {
+            info!("User answered elicitation with {:?}", result.action);
+            None
+        }
+        Err(err) => {
+            warn!("Elicitation failed: {}", err);
+            None
+        }
+    }
+}
+
+/// Text the client's model wrote for `prompt`, and the model's name.
+pub struct Sampled {
+    pub text: String,
+    pub model: String,
+}
+
+/// Has the current tool call's client sample its model for `prompt`, guided
+/// by `system_prompt`. Returns `None` when there is no client, it does not
+/// support sampling, the request fails (the user may refuse it), or the
+/// reply holds no text.
+pub async fn sample(system_prompt: &str, prompt: String, max_tokens: u32) -> Option<Sampled> {
+    let peer = CLIENT.try_with(Clone::clone).ok()?;
+    let supported = peer
+        .peer_info()
+        .is_some_and(|client| client.capabilities.sampling.is_some());
+    if !supported {
+        debug!("Client does not support sampling");
+        return None;
+    }
+    let request = CreateMessageRequestParam {
+        messages: vec![SamplingMessage {
+            role: Role::User,
+            content: Content::text(prompt),
+        }],
+        model_preferences: None,
+        system_prompt: Some(system_prompt.to_string()),
+        include_context: None,
+        temperature: None,
+        max_tokens,
+        stop_sequences: None,
+        metadata: None,
+    };
+    match peer.create_message(request).await {
+        Ok(result) => {
+            let text = result.message.content.as_text()?.text.clone();
+            Some(Sampled {
+                text,
+                model: result.model,
+            })
+        }
+        Err(err) => {
+            warn!("Sampling failed: {}", err);
+            None
+        }
+    }
+}
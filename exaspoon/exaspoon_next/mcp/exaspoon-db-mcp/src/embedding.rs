@@ -1,97 +1,250 @@
 use anyhow::{anyhow, Context, Result};
-use async_openai::{config::OpenAIConfig, types::embeddings::CreateEmbeddingRequestArgs, Client};
+use crate::{circuit::CircuitBreaker, metrics::Metrics, redaction::Redactor};
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::borrow::Cow;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Instant;
-use tracing::{debug, error, info, instrument, warn};
+
+mod cohere;
+mod factory;
+mod local;
+#[cfg(feature = "openai")]
+mod openai;
+mod voyage;
+
+pub use cohere::CohereEmbedder;
+pub use factory::{default_model, EmbedderFactory, DEFAULT_EMBEDDING_PROVIDER};
+pub use local::{DeterministicEmbedder, HashingEmbedder};
+#[cfg(feature = "openai")]
+pub use openai::EmbeddingService;
+pub use voyage::VoyageEmbedder;
+
+/// Placeholder replaced with the input text inside a prompt template.
+pub const TEXT_PLACEHOLDER: &str = "{text}";
 
 #[async_trait]
 pub trait Embedder: Send + Sync {
+    /// Embeds text that will be stored alongside a row (document side).
     async fn embed(&self, text: &str) -> Result<Vec<f32>>;
-    async fn maybe_embed(&self, text: Option<&str>) -> Result<Option<Vec<f32>>>;
+
+    /// Embeds optional text, skipping missing or blank values.
+    async fn maybe_embed(&self, text: Option<&str>) -> Result<Option<Vec<f32>>> {
+        match text {
+            Some(value) if !value.trim().is_empty() => Ok(Some(self.embed(value).await?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Embeds a search query. Defaults to the document embedding for
+    /// providers that do not distinguish between the two.
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(text).await
+    }
 }
 
-#[derive(Clone)]
-pub struct EmbeddingService {
-    client: Client<OpenAIConfig>,
-    model: String,
+/// Which side of a retrieval pair a text belongs to, for providers that
+/// take an explicit input type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InputKind {
+    Query,
+    Document,
 }
 
-impl EmbeddingService {
-    #[instrument(fields(model = %model, has_base_url = base_url.is_some()))]
-    pub fn new(api_key: &str, base_url: Option<&str>, model: &str) -> Result<Self> {
-        info!("Initializing embedding service");
-        debug!("Using model: {}", model);
-        
-        let mut config = OpenAIConfig::new().with_api_key(api_key);
-        if let Some(base) = base_url {
-            debug!("Using custom base URL: {}", base);
-            config = config.with_api_base(base);
+/// Instruction templates applied before text is sent to the embedding model.
+///
+/// Some models (e5, bge, nomic) expect prefixes such as `query: ` or
+/// `passage: `. A template may contain `{text}`; otherwise it is used as a
+/// plain prefix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingPrompts {
+    pub query: Option<String>,
+    pub document: Option<String>,
+}
+
+impl EmbeddingPrompts {
+    pub fn render_query<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        render_template(self.query.as_deref(), text)
+    }
+
+    pub fn render_document<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        render_template(self.document.as_deref(), text)
+    }
+}
+
+fn render_template<'a>(template: Option<&str>, text: &'a str) -> Cow<'a, str> {
+    match template {
+        Some(template) if template.contains(TEXT_PLACEHOLDER) => {
+            Cow::Owned(template.replace(TEXT_PLACEHOLDER, text))
         }
-        
-        let client = Client::with_config(config);
-        
-        info!("Embedding service initialized successfully");
-        Ok(Self {
-            client,
-            model: model.to_string(),
-        })
+        Some(prefix) => Cow::Owned(format!("{prefix}{text}")),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Wire format requested from the embeddings endpoint.
+///
+/// `Base64` packs little-endian f32 values and is roughly a quarter of the
+/// size of the JSON float array, which matters for large batch jobs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmbeddingEncoding {
+    Float,
+    #[default]
+    Base64,
+}
+
+impl EmbeddingEncoding {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Float => "float",
+            Self::Base64 => "base64",
+        }
+    }
+}
+
+impl FromStr for EmbeddingEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "float" => Ok(Self::Float),
+            "base64" => Ok(Self::Base64),
+            other => Err(anyhow!("unsupported embedding encoding format: {other}")),
+        }
+    }
+}
+
+/// Decodes a base64 embedding payload (little-endian f32 values).
+pub fn decode_base64_embedding(encoded: &str) -> Result<Vec<f32>> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .context("embedding payload is not valid base64")?;
+    if bytes.len() % 4 != 0 {
+        return Err(anyhow!(
+            "embedding payload length {} is not a multiple of 4 bytes",
+            bytes.len()
+        ));
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+/// Cosine similarity of two embeddings. Returns `None` when the vectors cannot
+/// be compared, e.g. rows embedded with a model of different dimensionality.
+pub fn cosine_similarity(left: &[f32], right: &[f32]) -> Option<f64> {
+    if left.len() != right.len() || left.is_empty() {
+        return None;
+    }
+
+    let (mut dot, mut left_norm, mut right_norm) = (0.0f64, 0.0f64, 0.0f64);
+    for (a, b) in left.iter().zip(right) {
+        let (a, b) = (f64::from(*a), f64::from(*b));
+        dot += a * b;
+        left_norm += a * a;
+        right_norm += b * b;
+    }
+    if left_norm == 0.0 || right_norm == 0.0 {
+        return None;
+    }
+    Some(dot / (left_norm.sqrt() * right_norm.sqrt()))
+}
+
+/// Decorates another [`Embedder`] so personal data is masked before text is
+/// sent to the provider. Callers still store the original text.
+pub struct RedactingEmbedder {
+    inner: Arc<dyn Embedder>,
+    redactor: Redactor,
+}
+
+impl RedactingEmbedder {
+    pub fn new(inner: Arc<dyn Embedder>, redactor: Redactor) -> Self {
+        Self { inner, redactor }
     }
 }
 
 #[async_trait]
-impl Embedder for EmbeddingService {
-    #[instrument(skip(self), fields(text_len = %text.len(), model = %self.model))]
+impl Embedder for RedactingEmbedder {
     async fn embed(&self, text: &str) -> Result<Vec<f32>> {
-        let start_time = Instant::now();
-        debug!("Creating embedding for text (length: {})", text.len());
-        
-        let request = CreateEmbeddingRequestArgs::default()
-            .model(self.model.clone())
-            .input(text)
-            .build()
-            .context("failed to build embedding request")?;
-
-        let response = self
-            .client
-            .embeddings()
-            .create(request)
-            .await
-            .map_err(|err| {
-                error!("Embedding request failed: {}", err);
-                anyhow!("embedding request failed")
-            })?;
-
-        let result = response
-            .data
-            .into_iter()
-            .next()
-            .map(|item| item.embedding)
-            .ok_or_else(|| {
-                error!("OpenAI did not return embedding data");
-                anyhow!("OpenAI did not return embedding data")
-            })?;
-        
-        let duration = start_time.elapsed();
-        info!("Embedding created successfully in {:?} (dimensions: {})", duration, result.len());
-        
-        Ok(result)
-    }
-
-    #[instrument(skip(self), fields(has_text = text.is_some()))]
+        self.inner.embed(&self.redactor.redact(text)).await
+    }
+
     async fn maybe_embed(&self, text: Option<&str>) -> Result<Option<Vec<f32>>> {
         match text {
-            Some(value) if !value.trim().is_empty() => {
-                debug!("Text provided, creating embedding");
-                Ok(Some(self.embed(value).await?))
-            }
-            Some(_value) => {
-                warn!("Empty text provided, skipping embedding");
-                Ok(None)
-            }
-            None => {
-                debug!("No text provided, skipping embedding");
-                Ok(None)
-            }
+            Some(value) => self.inner.maybe_embed(Some(&self.redactor.redact(value))).await,
+            None => self.inner.maybe_embed(None).await,
         }
     }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.inner.embed_query(&self.redactor.redact(text)).await
+    }
+}
+
+/// Decorates another [`Embedder`] to record each call's latency and outcome
+/// in [`Metrics`].
+pub struct MeteredEmbedder {
+    inner: Arc<dyn Embedder>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredEmbedder {
+    pub fn new(inner: Arc<dyn Embedder>, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn measure<T>(
+        &self,
+        operation: &str,
+        call: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T> {
+        let start_time = Instant::now();
+        let result = call.await;
+        self.metrics.record(operation, start_time.elapsed(), result.is_ok());
+        result
+    }
+}
+
+#[async_trait]
+impl Embedder for MeteredEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.measure("embed", self.inner.embed(text)).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.measure("embed_query", self.inner.embed_query(text)).await
+    }
+}
+
+/// Decorates another [`Embedder`] with a circuit breaker, so that while the
+/// provider keeps failing, tool calls fail at once instead of each waiting
+/// for the request to time out.
+pub struct CircuitBreakingEmbedder {
+    inner: Arc<dyn Embedder>,
+    breaker: CircuitBreaker,
+}
+
+impl CircuitBreakingEmbedder {
+    pub fn new(inner: Arc<dyn Embedder>, breaker: CircuitBreaker) -> Self {
+        Self { inner, breaker }
+    }
+}
+
+#[async_trait]
+impl Embedder for CircuitBreakingEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.breaker.call(self.inner.embed(text)).await
+    }
+
+    async fn maybe_embed(&self, text: Option<&str>) -> Result<Option<Vec<f32>>> {
+        self.breaker.call(self.inner.maybe_embed(text)).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.breaker.call(self.inner.embed_query(text)).await
+    }
 }
@@ -0,0 +1,102 @@
+use super::{Embedder, InputKind};
+use crate::correlation;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Instant;
+use tracing::{debug, error, info, instrument};
+
+pub const COHERE_API_BASE: &str = "https://api.cohere.com";
+
+/// Embedder backed by the Cohere v2 `/embed` endpoint, which distinguishes
+/// between `search_query` and `search_document` inputs natively.
+#[derive(Clone)]
+pub struct CohereEmbedder {
+    http: Client,
+    api_base: String,
+    api_key: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: EmbeddingsByType,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsByType {
+    #[serde(default)]
+    float: Vec<Vec<f32>>,
+}
+
+impl CohereEmbedder {
+    pub fn new(api_key: &str, api_base: Option<&str>, model: &str) -> Result<Self> {
+        info!("Initializing Cohere embedding service");
+        Ok(Self {
+            http: Client::builder()
+                .build()
+                .context("failed to build HTTP client for Cohere")?,
+            api_base: api_base.unwrap_or(COHERE_API_BASE).trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        })
+    }
+
+    #[instrument(skip(self, text), fields(text_len = %text.len(), model = %self.model, kind = ?kind))]
+    async fn create_embedding(&self, text: &str, kind: InputKind) -> Result<Vec<f32>> {
+        let start_time = Instant::now();
+        debug!("Creating Cohere embedding for text (length: {})", text.len());
+
+        let input_type = match kind {
+            InputKind::Query => "search_query",
+            InputKind::Document => "search_document",
+        };
+        let response = self
+            .http
+            .post(format!("{}/v2/embed", self.api_base))
+            .bearer_auth(&self.api_key)
+            .headers(correlation::headers())
+            .json(&json!({
+                "model": &self.model,
+                "texts": [text],
+                "input_type": input_type,
+                "embedding_types": ["float"],
+            }))
+            .send()
+            .await
+            .context("Cohere embedding request failed")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            error!("Cohere embedding request failed ({}): {}", status, body);
+            return Err(anyhow!("Cohere embedding request failed ({status})"));
+        }
+
+        let result = response
+            .json::<EmbedResponse>()
+            .await
+            .context("failed to parse Cohere embedding response")?
+            .embeddings
+            .float
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Cohere did not return embedding data"))?;
+
+        info!("Embedding created successfully in {:?} (dimensions: {})", start_time.elapsed(), result.len());
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Embedder for CohereEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.create_embedding(text, InputKind::Document).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.create_embedding(text, InputKind::Query).await
+    }
+}
@@ -0,0 +1,196 @@
+use super::{
+    CircuitBreakingEmbedder, CohereEmbedder, DeterministicEmbedder, Embedder, HashingEmbedder,
+    RedactingEmbedder, VoyageEmbedder,
+};
+#[cfg(feature = "openai")]
+use super::{EmbeddingEncoding, EmbeddingPrompts, EmbeddingService};
+use crate::{circuit::CircuitBreaker, config::AppConfig, redaction::Redactor};
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::info;
+
+pub const DEFAULT_EMBEDDING_PROVIDER: &str = "openai";
+#[cfg(feature = "openai")]
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+/// Matches `text-embedding-3-large` so offline vectors fit the same column.
+pub const DEFAULT_LOCAL_DIMENSIONS: usize = 3072;
+/// Providers that compute vectors in process and so never need a breaker.
+const LOCAL_PROVIDERS: &[&str] = &["local", "mock"];
+
+type EmbedderBuilder = Box<dyn Fn(&AppConfig) -> Result<Arc<dyn Embedder>> + Send + Sync>;
+
+/// Default model name for a provider when `EMBEDDING_MODEL` is not set.
+pub fn default_model(provider: &str) -> &'static str {
+    match provider {
+        "ollama" => "nomic-embed-text",
+        "cohere" => "embed-english-v3.0",
+        "voyage" => "voyage-3",
+        "local" | "mock" => "hashing",
+        _ => "text-embedding-3-large",
+    }
+}
+
+/// Registry of embedding providers keyed by the `EMBEDDING_PROVIDER` name.
+///
+/// Builders receive the full [`AppConfig`]; the factory applies cross-cutting
+/// wrappers such as PII redaction and the circuit breaker to whatever the
+/// builder returns.
+pub struct EmbedderFactory {
+    builders: BTreeMap<String, EmbedderBuilder>,
+}
+
+impl Default for EmbedderFactory {
+    fn default() -> Self {
+        let mut factory = Self::empty();
+        factory.register("openai", build_openai);
+        factory.register("azure", build_azure);
+        factory.register("ollama", build_ollama);
+        factory.register("cohere", build_cohere);
+        factory.register("voyage", build_voyage);
+        factory.register("local", |config| {
+            Ok(Arc::new(HashingEmbedder::new(local_dimensions(config))))
+        });
+        factory.register("mock", |config| {
+            Ok(Arc::new(DeterministicEmbedder::new(local_dimensions(config))))
+        });
+        factory
+    }
+}
+
+impl EmbedderFactory {
+    pub fn empty() -> Self {
+        Self {
+            builders: BTreeMap::new(),
+        }
+    }
+
+    /// Registers (or replaces) the builder for a provider name.
+    pub fn register<F>(&mut self, name: &str, builder: F)
+    where
+        F: Fn(&AppConfig) -> Result<Arc<dyn Embedder>> + Send + Sync + 'static,
+    {
+        self.builders
+            .insert(name.to_ascii_lowercase(), Box::new(builder));
+    }
+
+    pub fn providers(&self) -> Vec<&str> {
+        self.builders.keys().map(String::as_str).collect()
+    }
+
+    pub fn build(&self, config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+        let provider = config.embedding_provider.to_ascii_lowercase();
+        let builder = self.builders.get(&provider).ok_or_else(|| {
+            anyhow!(
+                "unknown embedding provider {provider:?} (available: {})",
+                self.providers().join(", ")
+            )
+        })?;
+
+        info!("Building embedder for provider: {}", provider);
+        let mut embedder = builder(config)?;
+        if config.pii_redaction {
+            info!(
+                "PII redaction enabled ({} custom patterns)",
+                config.pii_redaction_patterns.len()
+            );
+            let redactor = Redactor::new(&config.pii_redaction_patterns)?;
+            embedder = Arc::new(RedactingEmbedder::new(embedder, redactor));
+        }
+        if !LOCAL_PROVIDERS.contains(&provider.as_str()) {
+            let breaker = CircuitBreaker::new(
+                format!("{provider} embedding provider"),
+                config.circuit_breaker,
+            );
+            embedder = Arc::new(CircuitBreakingEmbedder::new(embedder, breaker));
+        }
+        Ok(embedder)
+    }
+}
+
+#[cfg(feature = "openai")]
+fn prompts(config: &AppConfig) -> EmbeddingPrompts {
+    EmbeddingPrompts {
+        query: config.embedding_query_prompt.clone(),
+        document: config.embedding_document_prompt.clone(),
+    }
+}
+
+fn local_dimensions(config: &AppConfig) -> usize {
+    config.embedding_dimensions.unwrap_or(DEFAULT_LOCAL_DIMENSIONS)
+}
+
+#[cfg(feature = "openai")]
+fn build_openai(config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    let service = EmbeddingService::new(
+        &config.openai_api_key,
+        config.openai_base_url.as_deref(),
+        &config.embedding_model,
+    )?;
+    Ok(Arc::new(
+        service
+            .with_encoding(config.embedding_encoding)
+            .with_prompts(prompts(config)),
+    ))
+}
+
+#[cfg(feature = "openai")]
+fn build_azure(config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    let azure = config
+        .azure_openai
+        .as_ref()
+        .ok_or_else(|| anyhow!("azure embedding provider requires AZURE_OPENAI_ENDPOINT"))?;
+    let service = EmbeddingService::azure(azure, &config.embedding_model)?;
+    Ok(Arc::new(
+        service
+            .with_encoding(config.embedding_encoding)
+            .with_prompts(prompts(config)),
+    ))
+}
+
+#[cfg(feature = "openai")]
+fn build_ollama(config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    // Ollama exposes an OpenAI-compatible endpoint that ignores the API key
+    // and only returns float arrays.
+    let base_url = config
+        .ollama_base_url
+        .as_deref()
+        .unwrap_or(DEFAULT_OLLAMA_BASE_URL);
+    let service = EmbeddingService::new("ollama", Some(base_url), &config.embedding_model)?;
+    Ok(Arc::new(
+        service
+            .with_encoding(EmbeddingEncoding::Float)
+            .with_prompts(prompts(config)),
+    ))
+}
+
+#[cfg(not(feature = "openai"))]
+fn build_openai(_config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    anyhow::bail!("EMBEDDING_PROVIDER=openai requires building with the `openai` feature")
+}
+
+#[cfg(not(feature = "openai"))]
+fn build_azure(_config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    anyhow::bail!("EMBEDDING_PROVIDER=azure requires building with the `openai` feature")
+}
+
+#[cfg(not(feature = "openai"))]
+fn build_ollama(_config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    anyhow::bail!("EMBEDDING_PROVIDER=ollama requires building with the `openai` feature")
+}
+
+fn build_cohere(config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    let api_key = config
+        .cohere_api_key
+        .as_deref()
+        .ok_or_else(|| anyhow!("cohere embedding provider requires COHERE_API_KEY"))?;
+    Ok(Arc::new(CohereEmbedder::new(api_key, None, &config.embedding_model)?))
+}
+
+fn build_voyage(config: &AppConfig) -> Result<Arc<dyn Embedder>> {
+    let api_key = config
+        .voyage_api_key
+        .as_deref()
+        .ok_or_else(|| anyhow!("voyage embedding provider requires VOYAGE_API_KEY"))?;
+    Ok(Arc::new(VoyageEmbedder::new(api_key, None, &config.embedding_model)?))
+}
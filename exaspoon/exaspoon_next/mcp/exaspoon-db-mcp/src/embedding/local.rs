@@ -0,0 +1,100 @@
+use super::Embedder;
+use anyhow::Result;
+use async_trait::async_trait;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Offline embedder using the hashing trick over word unigrams and bigrams.
+///
+/// It captures lexical overlap only, but needs no network access or model
+/// files, which makes it suitable for local-only deployments. Hashes are
+/// stable across builds so stored vectors stay comparable.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    pub fn vectorize(&self, text: &str) -> Vec<f32> {
+        let tokens: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        let mut vector = vec![0.0f32; self.dimensions];
+        let bigrams = tokens.windows(2).map(|pair| format!("{} {}", pair[0], pair[1]));
+        for feature in tokens.iter().cloned().chain(bigrams) {
+            let hash = fnv1a(feature.as_bytes());
+            let index = (hash % self.dimensions as u64) as usize;
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign;
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+#[async_trait]
+impl Embedder for HashingEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.vectorize(text))
+    }
+}
+
+/// Deterministic pseudo-random embedder for demos and agent development.
+/// Identical texts map to identical unit vectors; nothing else is preserved.
+#[derive(Debug, Clone)]
+pub struct DeterministicEmbedder {
+    dimensions: usize,
+}
+
+impl DeterministicEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    pub fn vectorize(&self, text: &str) -> Vec<f32> {
+        let mut state = fnv1a(text.as_bytes()) | 1;
+        let mut vector: Vec<f32> = (0..self.dimensions)
+            .map(|_| {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state as f64 / u64::MAX as f64 * 2.0 - 1.0) as f32
+            })
+            .collect();
+        normalize(&mut vector);
+        vector
+    }
+}
+
+#[async_trait]
+impl Embedder for DeterministicEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.vectorize(text))
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+}
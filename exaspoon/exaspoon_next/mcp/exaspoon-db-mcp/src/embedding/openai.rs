@@ -0,0 +1,168 @@
+use super::{decode_base64_embedding, Embedder, EmbeddingEncoding, EmbeddingPrompts};
+use crate::{config::AzureOpenAiConfig, correlation};
+use anyhow::{anyhow, Context, Result};
+use async_openai::{
+    config::{AzureConfig, Config, OpenAIConfig},
+    traits::RequestOptionsBuilder,
+    types::embeddings::{CreateEmbeddingRequestArgs, EncodingFormat},
+    Client,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, error, info, instrument, warn};
+
+#[derive(Clone)]
+pub struct EmbeddingService {
+    client: Client<Arc<dyn Config>>,
+    model: String,
+    prompts: EmbeddingPrompts,
+    encoding: EmbeddingEncoding,
+}
+
+impl EmbeddingService {
+    #[instrument(fields(model = %model, has_base_url = base_url.is_some()))]
+    pub fn new(api_key: &str, base_url: Option<&str>, model: &str) -> Result<Self> {
+        info!("Initializing embedding service");
+        debug!("Using model: {}", model);
+        
+        let mut config = OpenAIConfig::new().with_api_key(api_key);
+        if let Some(base) = base_url {
+            debug!("Using custom base URL: {}", base);
+            config = config.with_api_base(base);
+        }
+        
+        info!("Embedding service initialized successfully");
+        Ok(Self::with_config(Arc::new(config), model))
+    }
+
+    /// Builds a service that talks to an Azure OpenAI deployment. Requests are
+    /// routed by deployment name and authenticated with the `api-key` header.
+    #[instrument(skip(azure), fields(model = %model, deployment = %azure.deployment))]
+    pub fn azure(azure: &AzureOpenAiConfig, model: &str) -> Result<Self> {
+        info!("Initializing Azure OpenAI embedding service");
+        debug!("Using Azure endpoint: {}", azure.endpoint);
+        
+        let config = AzureConfig::new()
+            .with_api_base(azure.endpoint.trim_end_matches('/'))
+            .with_api_key(azure.api_key.as_str())
+            .with_deployment_id(azure.deployment.as_str())
+            .with_api_version(azure.api_version.as_str());
+        
+        info!("Azure OpenAI embedding service initialized successfully");
+        Ok(Self::with_config(Arc::new(config), model))
+    }
+
+    fn with_config(config: Arc<dyn Config>, model: &str) -> Self {
+        Self {
+            client: Client::with_config(config),
+            model: model.to_string(),
+            prompts: EmbeddingPrompts::default(),
+            encoding: EmbeddingEncoding::default(),
+        }
+    }
+
+    pub fn with_prompts(mut self, prompts: EmbeddingPrompts) -> Self {
+        debug!(
+            "Using embedding prompts (query: {}, document: {})",
+            prompts.query.is_some(),
+            prompts.document.is_some()
+        );
+        self.prompts = prompts;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: EmbeddingEncoding) -> Self {
+        debug!("Using embedding encoding format: {}", encoding.as_ref());
+        self.encoding = encoding;
+        self
+    }
+
+    #[instrument(skip(self, text), fields(text_len = %text.len(), model = %self.model))]
+    async fn create_embedding(&self, text: &str) -> Result<Vec<f32>> {
+        let start_time = Instant::now();
+        debug!("Creating embedding for text (length: {})", text.len());
+        
+        let mut builder = CreateEmbeddingRequestArgs::default();
+        builder.model(self.model.clone()).input(text);
+        if self.encoding == EmbeddingEncoding::Base64 {
+            builder.encoding_format(EncodingFormat::Base64);
+        }
+        let request = builder
+            .build()
+            .context("failed to build embedding request")?;
+
+        let embedding = match self.encoding {
+            EmbeddingEncoding::Float => self
+                .client
+                .embeddings()
+                .headers(correlation::headers())
+                .create(request)
+                .await
+                .map_err(|err| {
+                    error!("Embedding request failed: {}", err);
+                    anyhow!("embedding request failed")
+                })?
+                .data
+                .into_iter()
+                .next()
+                .map(|item| item.embedding),
+            EmbeddingEncoding::Base64 => self
+                .client
+                .embeddings()
+                .headers(correlation::headers())
+                .create_base64(request)
+                .await
+                .map_err(|err| {
+                    error!("Embedding request failed: {}", err);
+                    anyhow!("embedding request failed")
+                })?
+                .data
+                .into_iter()
+                .next()
+                .map(|item| decode_base64_embedding(&item.embedding.0))
+                .transpose()?,
+        };
+
+        let result = embedding.ok_or_else(|| {
+            error!("OpenAI did not return embedding data");
+            anyhow!("OpenAI did not return embedding data")
+        })?;
+        
+        let duration = start_time.elapsed();
+        info!("Embedding created successfully in {:?} (dimensions: {})", duration, result.len());
+        
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Embedder for EmbeddingService {
+    #[instrument(skip(self), fields(text_len = %text.len(), model = %self.model))]
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.create_embedding(&self.prompts.render_document(text)).await
+    }
+
+    #[instrument(skip(self), fields(text_len = %text.len(), model = %self.model))]
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.create_embedding(&self.prompts.render_query(text)).await
+    }
+
+    #[instrument(skip(self), fields(has_text = text.is_some()))]
+    async fn maybe_embed(&self, text: Option<&str>) -> Result<Option<Vec<f32>>> {
+        match text {
+            Some(value) if !value.trim().is_empty() => {
+                debug!("Text provided, creating embedding");
+                Ok(Some(self.embed(value).await?))
+            }
+            Some(_value) => {
+                warn!("Empty text provided, skipping embedding");
+                Ok(None)
+            }
+            None => {
+                debug!("No text provided, skipping embedding");
+                Ok(None)
+            }
+        }
+    }
+}
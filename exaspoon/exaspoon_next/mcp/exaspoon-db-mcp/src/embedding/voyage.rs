@@ -0,0 +1,99 @@
+use super::{Embedder, InputKind};
+use crate::correlation;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Instant;
+use tracing::{debug, error, info, instrument};
+
+pub const VOYAGE_API_BASE: &str = "https://api.voyageai.com/v1";
+
+/// Embedder backed by the Voyage AI `/embeddings` endpoint.
+#[derive(Clone)]
+pub struct VoyageEmbedder {
+    http: Client,
+    api_base: String,
+    api_key: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingItem {
+    embedding: Vec<f32>,
+}
+
+impl VoyageEmbedder {
+    pub fn new(api_key: &str, api_base: Option<&str>, model: &str) -> Result<Self> {
+        info!("Initializing Voyage embedding service");
+        Ok(Self {
+            http: Client::builder()
+                .build()
+                .context("failed to build HTTP client for Voyage")?,
+            api_base: api_base.unwrap_or(VOYAGE_API_BASE).trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        })
+    }
+
+    #[instrument(skip(self, text), fields(text_len = %text.len(), model = %self.model, kind = ?kind))]
+    async fn create_embedding(&self, text: &str, kind: InputKind) -> Result<Vec<f32>> {
+        let start_time = Instant::now();
+        debug!("Creating Voyage embedding for text (length: {})", text.len());
+
+        let input_type = match kind {
+            InputKind::Query => "query",
+            InputKind::Document => "document",
+        };
+        let response = self
+            .http
+            .post(format!("{}/embeddings", self.api_base))
+            .bearer_auth(&self.api_key)
+            .headers(correlation::headers())
+            .json(&json!({
+                "model": &self.model,
+                "input": [text],
+                "input_type": input_type,
+            }))
+            .send()
+            .await
+            .context("Voyage embedding request failed")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            error!("Voyage embedding request failed ({}): {}", status, body);
+            return Err(anyhow!("Voyage embedding request failed ({status})"));
+        }
+
+        let result = response
+            .json::<EmbeddingsResponse>()
+            .await
+            .context("failed to parse Voyage embedding response")?
+            .data
+            .into_iter()
+            .next()
+            .map(|item| item.embedding)
+            .ok_or_else(|| anyhow!("Voyage did not return embedding data"))?;
+
+        info!("Embedding created successfully in {:?} (dimensions: {})", start_time.elapsed(), result.len());
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Embedder for VoyageEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.create_embedding(text, InputKind::Document).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.create_embedding(text, InputKind::Query).await
+    }
+}
@@ -0,0 +1,138 @@
+//! Deposits, withdrawals and trades of exchange accounts, read from the
+//! exchanges' APIs with one API key each. An account is tied to one
+//! exchange, kept in its `metadata` with where each of the exchange's lists
+//! was read through, and each sync goes on from there. A movement is stored
+//! with its exchange and the exchange's id for it, so one read twice is
+//! recorded once. A trade is recorded as two movements in their own
+//! currencies, what it sold as an expense and what it bought as income,
+//! with its fee as another expense.
+
+use crate::models::{CreateTransactionInput, Exchange, TransactionDirection};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+pub mod binance;
+pub mod coinbase;
+
+/// Key of the exchange in an account's `metadata`.
+pub const EXCHANGE_METADATA_KEY: &str = "exchange";
+
+/// Days of history the first sync of an account reads where the exchange
+/// lists by time.
+pub const EXCHANGE_HISTORY_DAYS: i64 = 365;
+
+/// Days a movement still in progress holds a sync's cursor back. One pending
+/// for longer is passed over, so a deposit stuck at the exchange does not
+/// keep every later sync reading from it.
+pub const PENDING_HOLD_DAYS: i64 = 7;
+
+/// A movement of money to or from an exchange account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movement {
+    pub exchange: Exchange,
+    /// The exchange's id of the deposit, withdrawal or trade, followed for a
+    /// trade's sides by `:sold` or `:bought` and for a fee by `:fee`.
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub direction: TransactionDirection,
+    /// In whole units of `currency`.
+    pub amount: f64,
+    /// The asset's symbol at the exchange, e.g. `BTC` or `USDT`.
+    pub currency: String,
+    /// What moved and how, e.g. `BTC withdrawal to bc1q…`.
+    pub description: String,
+    /// Not completed yet, so a later sync reads the movement again.
+    pub pending: bool,
+    /// Cancelled or rejected by the exchange, so nothing moved.
+    pub failed: bool,
+    /// The record as the exchange listed it.
+    pub raw: Value,
+}
+
+/// The movements one sync read.
+#[derive(Debug, Clone, Default)]
+pub struct Movements {
+    pub movements: Vec<Movement>,
+    /// Where each list was read through, for the next sync to go on from.
+    pub cursors: BTreeMap<String, String>,
+}
+
+impl Movement {
+    /// When the movement happened, as an RFC 3339 timestamp.
+    pub fn occurred_at(&self) -> String {
+        self.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    /// The movement as `create_transaction` takes it, with its exchange, id
+    /// and the exchange's record as its raw source so that [`entry_id`]
+    /// finds it again.
+    pub fn to_input(&self, account_id: &str) -> CreateTransactionInput {
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount,
+            currency: self.currency.clone(),
+            direction: self.direction,
+            occurred_at: self.occurred_at(),
+            description: Some(self.description.clone()),
+            raw_source: Some(
+                json!({
+                    "exchange": self.exchange,
+                    "movement": self.id,
+                    "source": self.raw,
+                })
+                .to_string(),
+            ),
+        }
+    }
+
+    /// What tells this movement apart in later syncs; see [`entry_id`].
+    pub fn entry_id(&self) -> String {
+        format!("{}:{}", self.exchange, self.id)
+    }
+}
+
+/// What identifies a transaction recorded by an exchange sync, read back
+/// from its raw source: its exchange and movement id, e.g.
+/// `binance:deposit:8f2c…`.
+pub fn entry_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    let exchange = raw.get("exchange")?.as_str()?;
+    let movement = raw.get("movement")?.as_str()?;
+    Some(format!("{exchange}:{movement}"))
+}
+
+/// The exchange an account's `institution` names, e.g. `Binance`.
+pub fn exchange_of_institution(institution: &str) -> Option<Exchange> {
+    let institution = institution.trim().to_ascii_lowercase();
+    if institution.starts_with("binance") {
+        Some(Exchange::Binance)
+    } else if institution.starts_with("coinbase") {
+        Some(Exchange::Coinbase)
+    } else {
+        None
+    }
+}
+
+/// A decimal amount as the exchanges write them, e.g. `"0.00120000"`.
+pub fn decimal(value: &str) -> Result<f64> {
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("{value:?} is not an amount"))
+}
+
+/// The hex HMAC-SHA256 of `message` under `secret`, which both exchanges
+/// sign their requests with.
+fn signature(secret: &str, message: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .context("the API secret cannot key an HMAC")?;
+    mac.update(message.as_bytes());
+    Ok(format!("{:x}", mac.finalize().into_bytes()))
+}
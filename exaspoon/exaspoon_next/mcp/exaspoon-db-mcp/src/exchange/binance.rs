@@ -0,0 +1,501 @@
+//! Deposits, withdrawals and spot trades of a Binance account, read through
+//! the Binance API with an API key allowed to read. Requests are signed with
+//! the key's secret over their query string. Deposits and withdrawals are
+//! listed by time, at most 90 days per request, so a sync reads on from the
+//! time the previous one stopped at, or from its earliest movement still in
+//! progress within the past week; trades are listed by trading pair from a
+//! trade id on.
+
+use super::{decimal, signature, Movement, Movements, EXCHANGE_HISTORY_DAYS, PENDING_HOLD_DAYS};
+use crate::config::{BinanceConfig, HttpClientConfig};
+use crate::correlation;
+use crate::models::{Exchange, TransactionDirection};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+pub const BINANCE_BASE_URL: &str = "https://api.binance.com";
+
+/// Rows asked of Binance per request, the most any of its lists returns.
+const BINANCE_PAGE_SIZE: usize = 1000;
+
+/// The longest span the deposit and withdrawal lists take at once.
+const HISTORY_WINDOW_DAYS: i64 = 90;
+
+/// Milliseconds a signed request stays valid for after its timestamp.
+const RECV_WINDOW_MS: u64 = 10_000;
+
+/// Deposit statuses: credited, and credited but locked until confirmed.
+const DEPOSIT_DONE: &[i64] = &[1, 6];
+/// Deposit statuses once rejected, or sent to a wrong address or memo.
+const DEPOSIT_REJECTED: &[i64] = &[2, 7];
+/// Withdrawal status once completed.
+const WITHDRAWAL_DONE: i64 = 6;
+/// Withdrawal statuses once cancelled, rejected or failed.
+const WITHDRAWAL_FAILED: &[i64] = &[1, 3, 5];
+
+/// Reads one Binance account with one API key.
+#[derive(Clone)]
+pub struct BinanceClient {
+    http: Client,
+    base_url: String,
+    api_key: String,
+    api_secret: String,
+}
+
+/// An error response of Binance, e.g. code `-2015` for a key without read
+/// permission or from an IP it does not allow.
+#[derive(Debug, Clone)]
+pub struct BinanceError {
+    pub status_code: u16,
+    pub code: i64,
+    pub msg: String,
+}
+
+impl fmt::Display for BinanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Binance {} ({}): {}",
+            self.status_code, self.code, self.msg
+        )
+    }
+}
+
+impl std::error::Error for BinanceError {}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: i64,
+    msg: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Deposit {
+    id: String,
+    amount: String,
+    coin: String,
+    #[serde(default)]
+    network: String,
+    status: i64,
+    /// Milliseconds since the epoch.
+    insert_time: i64,
+    #[serde(skip)]
+    raw: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Withdrawal {
+    id: String,
+    amount: String,
+    #[serde(default)]
+    transaction_fee: Option<String>,
+    coin: String,
+    #[serde(default)]
+    address: String,
+    status: i64,
+    /// UTC, as `2024-03-01 12:00:00`.
+    apply_time: String,
+    #[serde(skip)]
+    raw: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Trade {
+    symbol: String,
+    id: u64,
+    /// Of the base asset.
+    qty: String,
+    /// Of the quote asset.
+    quote_qty: String,
+    commission: String,
+    commission_asset: String,
+    /// Milliseconds since the epoch.
+    time: i64,
+    is_buyer: bool,
+    #[serde(skip)]
+    raw: Value,
+}
+
+#[derive(Deserialize)]
+struct ExchangeInfo {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInfo {
+    base_asset: String,
+    quote_asset: String,
+}
+
+impl BinanceClient {
+    pub fn new(config: &BinanceConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Binance client for {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Binance")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            api_secret: config.api_secret.clone(),
+        })
+    }
+
+    /// The deposits, withdrawals and trades in `symbols` since `cursors`,
+    /// with where the next sync goes on from.
+    #[instrument(skip(self, cursors))]
+    pub async fn movements(
+        &self,
+        symbols: &[String],
+        cursors: &BTreeMap<String, String>,
+    ) -> Result<Movements> {
+        let start_time = Instant::now();
+        let now = Utc::now();
+        let since = |list: &str| -> Result<DateTime<Utc>> {
+            match cursors.get(list) {
+                Some(millis) => millis_time(
+                    millis
+                        .parse()
+                        .with_context(|| format!("{millis:?} is not a {list} cursor"))?,
+                ),
+                None => Ok(now - Duration::days(EXCHANGE_HISTORY_DAYS)),
+            }
+        };
+        let hold_since = now - Duration::days(PENDING_HOLD_DAYS);
+        let mut movements = Vec::new();
+        let mut next = cursors.clone();
+
+        let deposits: Vec<Deposit> = self
+            .history("/sapi/v1/capital/deposit/hisrec", since("deposits")?, now)
+            .await?;
+        let mut resume = now;
+        for deposit in deposits {
+            let movement = deposit.movement()?;
+            if movement.pending && movement.timestamp > hold_since {
+                resume = resume.min(movement.timestamp);
+            }
+            movements.push(movement);
+        }
+        next.insert(
+            "deposits".to_string(),
+            resume.timestamp_millis().to_string(),
+        );
+
+        let withdrawals: Vec<Withdrawal> = self
+            .history(
+                "/sapi/v1/capital/withdraw/history",
+                since("withdrawals")?,
+                now,
+            )
+            .await?;
+        let mut resume = now;
+        for withdrawal in withdrawals {
+            let moved = withdrawal.movements()?;
+            if moved[0].pending && moved[0].timestamp > hold_since {
+                resume = resume.min(moved[0].timestamp);
+            }
+            movements.extend(moved);
+        }
+        next.insert(
+            "withdrawals".to_string(),
+            resume.timestamp_millis().to_string(),
+        );
+
+        for symbol in symbols {
+            let key = format!("trades:{symbol}");
+            let from_id = match cursors.get(&key) {
+                Some(id) => id
+                    .parse()
+                    .with_context(|| format!("{id:?} is not a trade id"))?,
+                None => 0,
+            };
+            let (base, quote) = self.assets(symbol).await?;
+            let trades = self.trades(symbol, from_id).await?;
+            if let Some(last) = trades.last() {
+                next.insert(key, (last.id + 1).to_string());
+            }
+            for trade in trades {
+                movements.extend(trade.movements(&base, &quote)?);
+            }
+        }
+
+        movements.sort_by_key(|movement| movement.timestamp);
+        info!(
+            "Read {} Binance movements in {:?}",
+            movements.len(),
+            start_time.elapsed()
+        );
+        Ok(Movements {
+            movements,
+            cursors: next,
+        })
+    }
+
+    /// Every row of a list Binance keeps by time, from `since` to `until`,
+    /// read 90 days and a page at a time.
+    async fn history<T: Raw>(
+        &self,
+        path: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<T>> {
+        let mut rows = Vec::new();
+        let mut start = since;
+        while start < until {
+            let end = until.min(start + Duration::days(HISTORY_WINDOW_DAYS));
+            let mut offset = 0;
+            loop {
+                let page: Vec<Value> = self
+                    .signed(
+                        path,
+                        &[
+                            ("startTime", start.timestamp_millis().to_string()),
+                            ("endTime", end.timestamp_millis().to_string()),
+                            ("offset", offset.to_string()),
+                            ("limit", BINANCE_PAGE_SIZE.to_string()),
+                        ],
+                    )
+                    .await?;
+                let full = page.len() >= BINANCE_PAGE_SIZE;
+                offset += page.len();
+                for raw in page {
+                    rows.push(T::parse(raw, path)?);
+                }
+                if !full {
+                    break;
+                }
+            }
+            start = end;
+        }
+        Ok(rows)
+    }
+
+    /// The trades of `symbol` from trade `from_id` on, oldest first.
+    async fn trades(&self, symbol: &str, from_id: u64) -> Result<Vec<Trade>> {
+        let mut trades = Vec::new();
+        let mut from_id = from_id;
+        loop {
+            let page: Vec<Value> = self
+                .signed(
+                    "/api/v3/myTrades",
+                    &[
+                        ("symbol", symbol.to_string()),
+                        ("fromId", from_id.to_string()),
+                        ("limit", BINANCE_PAGE_SIZE.to_string()),
+                    ],
+                )
+                .await?;
+            let full = page.len() >= BINANCE_PAGE_SIZE;
+            for raw in page {
+                let trade = Trade::parse(raw, "/api/v3/myTrades")?;
+                from_id = trade.id + 1;
+                trades.push(trade);
+            }
+            if !full {
+                return Ok(trades);
+            }
+        }
+    }
+
+    /// The base and quote assets of the trading pair `symbol`.
+    async fn assets(&self, symbol: &str) -> Result<(String, String)> {
+        let path = "/api/v3/exchangeInfo";
+        let url = self.url(path, &[("symbol", symbol.to_string())])?;
+        let info: ExchangeInfo = self.get(path, url, false).await?;
+        let info = info
+            .symbols
+            .into_iter()
+            .next()
+            .with_context(|| format!("Binance has no trading pair {symbol}"))?;
+        Ok((info.base_asset, info.quote_asset))
+    }
+
+    /// A GET signed with the API secret over its query string, as sent.
+    async fn signed<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T> {
+        let mut url = self.url(path, params)?;
+        url.query_pairs_mut()
+            .append_pair("recvWindow", &RECV_WINDOW_MS.to_string())
+            .append_pair("timestamp", &Utc::now().timestamp_millis().to_string());
+        let signature = signature(&self.api_secret, url.query().unwrap_or_default())?;
+        url.query_pairs_mut().append_pair("signature", &signature);
+        self.get(path, url, true).await
+    }
+
+    /// The URL of `path` with `params` URL-encoded as its query.
+    fn url(&self, path: &str, params: &[(&str, String)]) -> Result<Url> {
+        let mut url = Url::parse(&format!("{}{}", self.base_url, path))
+            .context("invalid Binance base URL")?;
+        url.query_pairs_mut().extend_pairs(params);
+        Ok(url)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, url: Url, signed: bool) -> Result<T> {
+        let mut request = self.http.get(url).headers(correlation::headers());
+        if signed {
+            request = request.header("X-MBX-APIKEY", &self.api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Binance request to {path} failed"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Binance request to {} failed ({}): {}", path, status, body);
+            let (code, msg) = match serde_json::from_str::<ErrorBody>(&body) {
+                Ok(rejected) => (rejected.code, rejected.msg),
+                Err(_) => (0, format!("request to {path} failed")),
+            };
+            return Err(anyhow!(BinanceError {
+                status_code: status.as_u16(),
+                code,
+                msg,
+            }));
+        }
+        response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Binance response from {path}"))
+    }
+}
+
+/// A row kept with the JSON it was read from.
+trait Raw: Sized {
+    fn parse(raw: Value, path: &str) -> Result<Self>;
+}
+
+macro_rules! raw {
+    ($($row:ty),*) => {$(
+        impl Raw for $row {
+            fn parse(raw: Value, path: &str) -> Result<Self> {
+                let mut row: Self = serde_json::from_value(raw.clone())
+                    .with_context(|| format!("unexpected Binance row shape from {path}"))?;
+                row.raw = raw;
+                Ok(row)
+            }
+        }
+    )*};
+}
+
+raw!(Deposit, Withdrawal, Trade);
+
+impl Deposit {
+    fn movement(&self) -> Result<Movement> {
+        let description = if self.network.is_empty() {
+            format!("{} deposit", self.coin)
+        } else {
+            format!("{} deposit over {}", self.coin, self.network)
+        };
+        Ok(Movement {
+            exchange: Exchange::Binance,
+            id: format!("deposit:{}", self.id),
+            timestamp: millis_time(self.insert_time)?,
+            direction: TransactionDirection::Income,
+            amount: decimal(&self.amount)?,
+            currency: self.coin.clone(),
+            description,
+            pending: !DEPOSIT_DONE.contains(&self.status)
+                && !DEPOSIT_REJECTED.contains(&self.status),
+            failed: DEPOSIT_REJECTED.contains(&self.status),
+            raw: self.raw.clone(),
+        })
+    }
+}
+
+impl Withdrawal {
+    /// The withdrawal, followed by its fee when it has one.
+    fn movements(&self) -> Result<Vec<Movement>> {
+        let applied = NaiveDateTime::parse_from_str(&self.apply_time, "%Y-%m-%d %H:%M:%S")
+            .with_context(|| format!("{:?} is not a withdrawal time", self.apply_time))?;
+        let withdrawal = Movement {
+            exchange: Exchange::Binance,
+            id: format!("withdrawal:{}", self.id),
+            timestamp: Utc.from_utc_datetime(&applied),
+            direction: TransactionDirection::Expense,
+            amount: decimal(&self.amount)?,
+            currency: self.coin.clone(),
+            description: format!("{} withdrawal to {}", self.coin, self.address),
+            pending: self.status != WITHDRAWAL_DONE && !WITHDRAWAL_FAILED.contains(&self.status),
+            failed: WITHDRAWAL_FAILED.contains(&self.status),
+            raw: self.raw.clone(),
+        };
+        let fee = decimal(self.transaction_fee.as_deref().unwrap_or("0"))?;
+        if fee == 0.0 {
+            return Ok(vec![withdrawal]);
+        }
+        let fee = Movement {
+            id: format!("withdrawal:{}:fee", self.id),
+            amount: fee,
+            description: format!("{} withdrawal fee", self.coin),
+            ..withdrawal.clone()
+        };
+        Ok(vec![withdrawal, fee])
+    }
+}
+
+impl Trade {
+    /// What the trade sold and bought, and its commission when it had one.
+    fn movements(&self, base: &str, quote: &str) -> Result<Vec<Movement>> {
+        let base_qty = decimal(&self.qty)?;
+        let quote_qty = decimal(&self.quote_qty)?;
+        let ((sold, sold_qty), (bought, bought_qty)) = if self.is_buyer {
+            ((quote, quote_qty), (base, base_qty))
+        } else {
+            ((base, base_qty), (quote, quote_qty))
+        };
+        let id = format!("trade:{}:{}", self.symbol, self.id);
+        let sold = Movement {
+            exchange: Exchange::Binance,
+            id: format!("{id}:sold"),
+            timestamp: millis_time(self.time)?,
+            direction: TransactionDirection::Expense,
+            amount: sold_qty,
+            currency: sold.to_string(),
+            description: format!("{sold} sold for {bought}"),
+            pending: false,
+            failed: false,
+            raw: self.raw.clone(),
+        };
+        let bought = Movement {
+            id: format!("{id}:bought"),
+            direction: TransactionDirection::Income,
+            amount: bought_qty,
+            currency: bought.to_string(),
+            description: format!("{bought} bought with {}", sold.currency),
+            ..sold.clone()
+        };
+        let commission = decimal(&self.commission)?;
+        if commission == 0.0 {
+            return Ok(vec![sold, bought]);
+        }
+        let fee = Movement {
+            id: format!("{id}:fee"),
+            amount: commission,
+            currency: self.commission_asset.clone(),
+            description: format!("{} trading fee", self.commission_asset),
+            ..sold.clone()
+        };
+        Ok(vec![sold, bought, fee])
+    }
+}
+
+fn millis_time(millis: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis)
+        .with_context(|| format!("{millis} is out of range for a timestamp"))
+}
@@ -0,0 +1,393 @@
+//! Transactions of every wallet of a Coinbase user, read through the
+//! Coinbase API with a CDP API key allowed to read. Each request carries a
+//! JWT naming its method and path, signed with ES256 by the key's EC private
+//! key. Each wallet's transactions are read oldest first from the one its
+//! previous sync stopped after, which is the last before its earliest
+//! transaction still pending.
+
+use super::{decimal, Movement, Movements, PENDING_HOLD_DAYS};
+use crate::config::{CoinbaseConfig, HttpClientConfig};
+use crate::correlation;
+use crate::models::{Exchange, TransactionDirection};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+pub const COINBASE_BASE_URL: &str = "https://api.coinbase.com";
+
+/// Rows asked of Coinbase per request, the most it returns.
+const COINBASE_PAGE_SIZE: usize = 100;
+
+/// API version the requests are made against.
+const COINBASE_VERSION: &str = "2024-01-01";
+
+/// Seconds a request's JWT is valid for, the most Coinbase accepts.
+const JWT_LIFETIME_SECS: i64 = 120;
+
+/// DER of the algorithm of a P-256 key in PKCS#8: `id-ecPublicKey` on
+/// `prime256v1`.
+const P256_ALGORITHM: &[u8] = &[
+    0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48,
+    0xce, 0x3d, 0x03, 0x01, 0x07,
+];
+
+/// Transaction statuses of ones that moved nothing.
+const FAILED_STATUSES: &[&str] = &["failed", "canceled", "expired"];
+
+/// Reads one Coinbase user's wallets with one API key.
+#[derive(Clone)]
+pub struct CoinbaseClient {
+    http: Client,
+    base_url: String,
+    /// The key's name, `organizations/{org}/apiKeys/{key}`.
+    api_key: String,
+    signing_key: Arc<EcdsaKeyPair>,
+    rng: SystemRandom,
+}
+
+/// An error response of Coinbase, e.g. `authentication_error` for an
+/// unknown key.
+#[derive(Debug, Clone)]
+pub struct CoinbaseError {
+    pub status_code: u16,
+    pub id: String,
+    pub message: String,
+}
+
+impl fmt::Display for CoinbaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Coinbase {} ({}): {}",
+            self.status_code, self.id, self.message
+        )
+    }
+}
+
+impl std::error::Error for CoinbaseError {}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    errors: Vec<ErrorEntry>,
+}
+
+#[derive(Deserialize)]
+struct ErrorEntry {
+    id: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    #[serde(default)]
+    pagination: Option<Pagination>,
+    data: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct Pagination {
+    next_uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Wallet {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CoinbaseTransaction {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    status: String,
+    amount: Money,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    details: Option<Details>,
+    #[serde(default)]
+    network: Option<Network>,
+}
+
+#[derive(Deserialize)]
+struct Money {
+    amount: String,
+    currency: String,
+}
+
+#[derive(Deserialize)]
+struct Details {
+    title: Option<String>,
+    subtitle: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Network {
+    transaction_fee: Option<Money>,
+}
+
+impl CoinbaseClient {
+    pub fn new(config: &CoinbaseConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Coinbase client for {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Coinbase")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            signing_key: Arc::new(signing_key(&config.api_secret)?),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// The transactions of every wallet since `cursors`, with where the next
+    /// sync goes on from.
+    #[instrument(skip(self, cursors))]
+    pub async fn movements(&self, cursors: &BTreeMap<String, String>) -> Result<Movements> {
+        let start_time = Instant::now();
+        let mut movements = Vec::new();
+        let mut next = cursors.clone();
+        let hold_since = Utc::now() - Duration::days(PENDING_HOLD_DAYS);
+        let wallets = self
+            .list(format!("/v2/accounts?limit={COINBASE_PAGE_SIZE}"))
+            .await?;
+        for wallet in wallets {
+            let wallet: Wallet =
+                serde_json::from_value(wallet).context("unexpected Coinbase wallet shape")?;
+            let mut path = format!(
+                "/v2/accounts/{}/transactions?limit={COINBASE_PAGE_SIZE}&order=asc",
+                wallet.id
+            );
+            if let Some(after) = cursors.get(&wallet.id) {
+                path.push_str(&format!("&starting_after={after}"));
+            }
+            let mut pending = false;
+            for raw in self.list(path).await? {
+                let transaction: CoinbaseTransaction = serde_json::from_value(raw.clone())
+                    .context("unexpected Coinbase transaction shape")?;
+                let moved = transaction.movements(raw)?;
+                pending |= moved[0].pending && moved[0].timestamp > hold_since;
+                if !pending {
+                    next.insert(wallet.id.clone(), transaction.id.clone());
+                }
+                movements.extend(moved);
+            }
+        }
+        movements.sort_by_key(|movement| movement.timestamp);
+        info!(
+            "Read {} Coinbase movements in {:?}",
+            movements.len(),
+            start_time.elapsed()
+        );
+        Ok(Movements {
+            movements,
+            cursors: next,
+        })
+    }
+
+    /// Every row of a list, following its pages.
+    async fn list(&self, path: String) -> Result<Vec<Value>> {
+        let mut rows = Vec::new();
+        let mut path = Some(path);
+        while let Some(current) = path {
+            let page: Page = self.get(&current).await?;
+            rows.extend(page.data);
+            path = page.pagination.and_then(|pagination| pagination.next_uri);
+        }
+        Ok(rows)
+    }
+
+    /// A GET carrying a JWT for its method and path.
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let token = self.jwt(path)?;
+        let response = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .headers(correlation::headers())
+            .bearer_auth(token)
+            .header("CB-VERSION", COINBASE_VERSION)
+            .send()
+            .await
+            .with_context(|| format!("Coinbase request to {path} failed"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Coinbase request to {} failed ({}): {}", path, status, body);
+            let (id, message) = match serde_json::from_str::<ErrorBody>(&body)
+                .ok()
+                .and_then(|rejected| rejected.errors.into_iter().next())
+            {
+                Some(entry) => (entry.id, entry.message),
+                None => ("unknown".to_string(), format!("request to {path} failed")),
+            };
+            return Err(anyhow!(CoinbaseError {
+                status_code: status.as_u16(),
+                id,
+                message,
+            }));
+        }
+        response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Coinbase response from {path}"))
+    }
+
+    /// The JWT of a GET of `path`: issued by `cdp` for the key, valid for two
+    /// minutes, with the method, host and path without its query as `uri`.
+    fn jwt(&self, path: &str) -> Result<String> {
+        let mut nonce = [0u8; 16];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("failed to generate a JWT nonce"))?;
+        let nonce: String = nonce.iter().map(|b| format!("{b:02x}")).collect();
+        let host = self
+            .base_url
+            .split_once("://")
+            .map_or(self.base_url.as_str(), |(_, host)| host);
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        let now = Utc::now().timestamp();
+        let header = json!({
+            "alg": "ES256",
+            "kid": self.api_key,
+            "nonce": nonce,
+            "typ": "JWT",
+        });
+        let claims = json!({
+            "sub": self.api_key,
+            "iss": "cdp",
+            "nbf": now,
+            "exp": now + JWT_LIFETIME_SECS,
+            "uri": format!("GET {host}{path}"),
+        });
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = self
+            .signing_key
+            .sign(&self.rng, message.as_bytes())
+            .map_err(|_| anyhow!("failed to sign the Coinbase JWT"))?;
+        Ok(format!(
+            "{message}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        ))
+    }
+}
+
+/// The P-256 key of a CDP API secret: a PEM `EC PRIVATE KEY`, as Coinbase
+/// hands it out, or a PKCS#8 `PRIVATE KEY`. Newlines escaped as `\n`, as in
+/// the key file's JSON, are read as newlines.
+fn signing_key(secret: &str) -> Result<EcdsaKeyPair> {
+    let secret = secret.replace("\\n", "\n");
+    let label = secret
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("-----"))
+        .context("COINBASE_API_SECRET is not a PEM private key")?;
+    let body: String = secret
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+        .collect();
+    let der = STANDARD
+        .decode(body)
+        .context("COINBASE_API_SECRET is not valid PEM")?;
+    let pkcs8 = match label {
+        "EC PRIVATE KEY" => der_value(
+            0x30,
+            &[
+                &[0x02, 0x01, 0x00][..],
+                P256_ALGORITHM,
+                &der_value(0x04, &der),
+            ]
+            .concat(),
+        ),
+        "PRIVATE KEY" => der,
+        _ => bail!("COINBASE_API_SECRET is a {label}, not an EC private key"),
+    };
+    EcdsaKeyPair::from_pkcs8(
+        &ECDSA_P256_SHA256_FIXED_SIGNING,
+        &pkcs8,
+        &SystemRandom::new(),
+    )
+    .map_err(|err| anyhow!("COINBASE_API_SECRET is not a P-256 private key: {err}"))
+}
+
+/// The DER encoding of `content` under `tag`.
+fn der_value(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => encoded.push(len as u8),
+        len @ 0x80..=0xff => encoded.extend([0x81, len as u8]),
+        len => encoded.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+impl CoinbaseTransaction {
+    /// The transaction, followed by its network fee when it sent crypto
+    /// out and paid one.
+    fn movements(&self, raw: Value) -> Result<Vec<Movement>> {
+        let amount = decimal(&self.amount.amount)?;
+        let direction = if amount < 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        };
+        let description = match &self.details {
+            Some(Details {
+                title: Some(title),
+                subtitle,
+            }) => match subtitle {
+                Some(subtitle) if !subtitle.is_empty() => format!("{title} {subtitle}"),
+                _ => title.clone(),
+            },
+            _ => self.kind.replace('_', " "),
+        };
+        let status = self.status.to_ascii_lowercase();
+        let movement = Movement {
+            exchange: Exchange::Coinbase,
+            id: self.id.clone(),
+            timestamp: self.created_at,
+            direction,
+            amount: amount.abs(),
+            currency: self.amount.currency.clone(),
+            description,
+            pending: status == "pending",
+            failed: FAILED_STATUSES.contains(&status.as_str()),
+            raw,
+        };
+        let fee = match &self.network {
+            Some(Network {
+                transaction_fee: Some(fee),
+            }) if direction == TransactionDirection::Expense => fee,
+            _ => return Ok(vec![movement]),
+        };
+        let fee_amount = decimal(&fee.amount)?;
+        if fee_amount == 0.0 {
+            return Ok(vec![movement]);
+        }
+        let fee = Movement {
+            id: format!("{}:fee", self.id),
+            amount: fee_amount,
+            currency: fee.currency.clone(),
+            description: format!("{} network fee", fee.currency),
+            ..movement.clone()
+        };
+        Ok(vec![movement, fee])
+    }
+}
@@ -0,0 +1,121 @@
+//! Transactions written out for analytics tools such as DuckDB or pandas:
+//! JSON Lines, one object per line, or, with the `parquet` feature, a Parquet
+//! file with a typed column per field. Rows come from
+//! [`Database::stream_transactions`] and are written as their pages arrive,
+//! so an export holds a page or two, plus one Parquet row group, in memory
+//! however large the table is.
+
+use crate::models::{Transaction, TransactionFilters};
+use crate::supabase::Database;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::io::Write;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+#[cfg(feature = "parquet")]
+mod parquet;
+
+/// Transactions an export reads per page.
+pub const DEFAULT_EXPORT_PAGE_SIZE: u32 = 500;
+
+/// Rows per Parquet row group, the unit readers skip or read in parallel.
+pub const PARQUET_ROW_GROUP_ROWS: usize = 10_000;
+
+/// What an export writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One JSON object per line, as `import` reads it back.
+    Jsonl,
+    /// Columnar Parquet, compressed with Snappy.
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Parquet for a path ending in `.parquet`, JSON Lines otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        let parquet = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("parquet"));
+        if parquet {
+            Self::Parquet
+        } else {
+            Self::Jsonl
+        }
+    }
+}
+
+/// Writes the transactions matching `filters` to `out` in `format`, newest
+/// first, reading them a page at a time. Returns how many were written.
+pub async fn export(
+    database: &dyn Database,
+    filters: &TransactionFilters,
+    format: ExportFormat,
+    out: impl Write + Send,
+) -> Result<u64> {
+    let mut writer = Writer::new(format, out)?;
+    let (pages, mut received) = mpsc::channel(2);
+    let write = async move {
+        let mut written = 0u64;
+        while let Some(page) = received.recv().await {
+            for transaction in page {
+                writer.write(transaction)?;
+                written += 1;
+            }
+        }
+        writer.finish()?;
+        anyhow::Ok(written)
+    };
+    // A failed write drops the receiver, which stops the stream.
+    let (streamed, written) = tokio::join!(
+        database.stream_transactions(filters, DEFAULT_EXPORT_PAGE_SIZE, pages),
+        write
+    );
+    let written = written.context("failed to write transactions")?;
+    streamed.context("failed to read transactions")?;
+    Ok(written)
+}
+
+enum Writer<W: Write + Send> {
+    Jsonl(W),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<parquet::TransactionWriter<W>>),
+}
+
+impl<W: Write + Send> Writer<W> {
+    fn new(format: ExportFormat, out: W) -> Result<Self> {
+        match format {
+            ExportFormat::Jsonl => Ok(Self::Jsonl(out)),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => {
+                let writer = parquet::TransactionWriter::new(out)?;
+                Ok(Self::Parquet(Box::new(writer)))
+            }
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => {
+                anyhow::bail!("Parquet export requires building with the `parquet` feature")
+            }
+        }
+    }
+
+    fn write(&mut self, transaction: Transaction) -> Result<()> {
+        match self {
+            Self::Jsonl(out) => {
+                serde_json::to_writer(&mut *out, &transaction)?;
+                out.write_all(b"\n")?;
+            }
+            #[cfg(feature = "parquet")]
+            Self::Parquet(writer) => writer.write(transaction)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Jsonl(mut out) => out.flush()?,
+            #[cfg(feature = "parquet")]
+            Self::Parquet(writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Parquet output: a column per transaction field, text as UTF-8 strings and
+//! times as UTC timestamps in microseconds, written a row group at a time.
+
+use super::PARQUET_ROW_GROUP_ROWS;
+use crate::models::Transaction;
+use ::parquet::basic::Compression;
+use ::parquet::data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type};
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use ::parquet::schema::parser::parse_message_type;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime};
+use std::io::Write;
+use std::sync::Arc;
+
+/// The columns, in the order [`TransactionWriter`] writes them.
+const SCHEMA: &str = "
+message transaction {
+    REQUIRED BYTE_ARRAY id (STRING);
+    REQUIRED BYTE_ARRAY account_id (STRING);
+    REQUIRED DOUBLE amount;
+    REQUIRED BYTE_ARRAY currency (STRING);
+    REQUIRED BYTE_ARRAY direction (STRING);
+    REQUIRED INT64 occurred_at (TIMESTAMP(MICROS, true));
+    OPTIONAL BYTE_ARRAY description (STRING);
+    OPTIONAL BYTE_ARRAY raw_source (STRING);
+    OPTIONAL BYTE_ARRAY category_id (STRING);
+    OPTIONAL INT64 created_at (TIMESTAMP(MICROS, true));
+}
+";
+
+/// Buffers transactions into row groups of [`PARQUET_ROW_GROUP_ROWS`].
+pub(super) struct TransactionWriter<W: Write + Send> {
+    file: SerializedFileWriter<W>,
+    rows: Vec<Transaction>,
+}
+
+impl<W: Write + Send> TransactionWriter<W> {
+    pub(super) fn new(out: W) -> Result<Self> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_created_by(concat!("exaspoon-db-mcp ", env!("CARGO_PKG_VERSION")).to_string())
+            .build();
+        Ok(Self {
+            file: SerializedFileWriter::new(out, schema, Arc::new(properties))?,
+            rows: Vec::with_capacity(PARQUET_ROW_GROUP_ROWS),
+        })
+    }
+
+    pub(super) fn write(&mut self, transaction: Transaction) -> Result<()> {
+        self.rows.push(transaction);
+        if self.rows.len() >= PARQUET_ROW_GROUP_ROWS {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last row group and the footer, and flushes the output.
+    pub(super) fn finish(mut self) -> Result<()> {
+        if !self.rows.is_empty() {
+            self.write_row_group()?;
+        }
+        self.file.into_inner()?.flush()?;
+        Ok(())
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        let rows = std::mem::take(&mut self.rows);
+        let mut group = self.file.next_row_group()?;
+        let text = |value: &str| Some(ByteArray::from(value));
+        let optional_text = |value: &Option<String>| value.as_deref().map(ByteArray::from);
+
+        column::<ByteArrayType, _>(&mut group, false, rows.iter().map(|row| text(&row.id)))?;
+        column::<ByteArrayType, _>(
+            &mut group,
+            false,
+            rows.iter().map(|row| text(&row.account_id)),
+        )?;
+        column::<DoubleType, _>(&mut group, false, rows.iter().map(|row| Some(row.amount)))?;
+        column::<ByteArrayType, _>(
+            &mut group,
+            false,
+            rows.iter().map(|row| text(&row.currency)),
+        )?;
+        column::<ByteArrayType, _>(
+            &mut group,
+            false,
+            rows.iter().map(|row| text(row.direction.as_ref())),
+        )?;
+        let occurred_at = rows
+            .iter()
+            .map(|row| {
+                timestamp_micros(&row.occurred_at)
+                    .with_context(|| format!("transaction {}", row.id))
+                    .map(Some)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        column::<Int64Type, _>(&mut group, false, occurred_at)?;
+        column::<ByteArrayType, _>(
+            &mut group,
+            true,
+            rows.iter().map(|row| optional_text(&row.description)),
+        )?;
+        column::<ByteArrayType, _>(
+            &mut group,
+            true,
+            rows.iter().map(|row| optional_text(&row.raw_source)),
+        )?;
+        column::<ByteArrayType, _>(
+            &mut group,
+            true,
+            rows.iter().map(|row| optional_text(&row.category_id)),
+        )?;
+        let created_at = rows
+            .iter()
+            .map(|row| {
+                row.created_at
+                    .as_deref()
+                    .map(timestamp_micros)
+                    .transpose()
+                    .with_context(|| format!("transaction {}", row.id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        column::<Int64Type, _>(&mut group, true, created_at)?;
+
+        group.close()?;
+        Ok(())
+    }
+}
+
+/// Writes the next column of `group`. An `optional` column stores a
+/// definition level per row, so `None` becomes null.
+fn column<T: DataType, W: Write + Send>(
+    group: &mut SerializedRowGroupWriter<'_, W>,
+    optional: bool,
+    values: impl IntoIterator<Item = Option<T::T>>,
+) -> Result<()> {
+    let mut column = group
+        .next_column()?
+        .context("the Parquet schema has fewer columns than a transaction")?;
+    let mut levels = Vec::new();
+    let mut present = Vec::new();
+    for value in values {
+        levels.push(i16::from(value.is_some()));
+        present.extend(value);
+    }
+    let levels = optional.then_some(levels.as_slice());
+    column.typed::<T>().write_batch(&present, levels, None)?;
+    column.close()?;
+    Ok(())
+}
+
+/// Microseconds since the epoch of an RFC 3339 timestamp, or of one without
+/// an offset, such as Postgres `timestamp` text, taken as UTC.
+fn timestamp_micros(value: &str) -> Result<i64> {
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+        return Ok(parsed.timestamp_micros());
+    }
+    let parsed = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f"))
+        .with_context(|| format!("{value:?} is not a timestamp"))?;
+    Ok(parsed.and_utc().timestamp_micros())
+}
@@ -0,0 +1,531 @@
+//! Exchange rates between fiat currencies, for `convert_currency` and for
+//! the reporting tools' totals in the base currency. Rates come from the
+//! European Central Bank's daily reference rates through the Frankfurter
+//! API, or from exchangerate.host. A day's rates never change once
+//! published, so they are kept until [`MAX_CACHED_RATES`] days push the
+//! oldest out; the latest ones are kept for `FX_CACHE_TTL_SECS`. A report
+//! over many days reads their rates as one time series.
+
+use crate::config::{FxConfig, HttpClientConfig};
+use crate::correlation;
+use crate::models::FxProvider;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument};
+
+pub const FRANKFURTER_BASE_URL: &str = "https://api.frankfurter.app";
+pub const EXCHANGERATE_HOST_BASE_URL: &str = "https://api.exchangerate.host";
+
+/// Seconds the latest rates are kept before they are fetched again.
+pub const DEFAULT_FX_CACHE_TTL_SECS: u64 = 3600;
+
+/// Base currency of the reporting tools' totals unless `BASE_CURRENCY` names
+/// another.
+pub const DEFAULT_BASE_CURRENCY: &str = "USD";
+
+/// Rates kept per currency and day, beyond which the longest kept go.
+pub const MAX_CACHED_RATES: usize = 4096;
+
+/// Most days of rates asked for in one time series request, the most
+/// exchangerate.host returns.
+const MAX_SERIES_DAYS: i64 = 365;
+
+/// The currency codes of ISO 4217, fiat and precious metals, which are
+/// only ever converted at exchange rates, never priced as crypto assets.
+pub const ISO_4217_CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP",
+    "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP",
+    "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS",
+    "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD",
+    "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD", "NGN",
+    "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR",
+    "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SLL",
+    "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VED", "VES",
+    "VND", "VUV", "WST", "XAF", "XAG", "XAU", "XCD", "XCG", "XDR", "XOF", "XPD", "XPF", "XPT",
+    "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+/// exchangerate.host's error code for a currency it does not know.
+const INVALID_SOURCE_CURRENCY: i64 = 201;
+
+/// Fetches and caches exchange rates from one provider.
+pub struct FxClient {
+    http: Client,
+    provider: FxProvider,
+    base_url: String,
+    api_key: Option<String>,
+    base_currency: String,
+    cache_ttl: Duration,
+    /// Rates per unit of a currency, by that currency and day; `None` for
+    /// the latest.
+    cache: Mutex<HashMap<(String, Option<NaiveDate>), Cached>>,
+}
+
+struct Cached {
+    fetched_at: Instant,
+    rates: Arc<Rates>,
+}
+
+/// How much of every other currency one unit of `base` buys.
+#[derive(Debug)]
+struct Rates {
+    date: NaiveDate,
+    rates: HashMap<String, f64>,
+}
+
+/// A rate between two currencies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    /// Units of the target currency per unit of the source one.
+    pub rate: f64,
+    /// The day the rate is for.
+    pub date: NaiveDate,
+}
+
+/// A currency the provider has no rates for.
+#[derive(Debug, Clone)]
+pub struct UnknownCurrency(pub String);
+
+impl fmt::Display for UnknownCurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no exchange rates for {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownCurrency {}
+
+/// Whether `code` is an ISO 4217 currency, however it is cased.
+pub fn is_iso_currency(code: &str) -> bool {
+    ISO_4217_CURRENCIES
+        .binary_search(&code.to_ascii_uppercase().as_str())
+        .is_ok()
+}
+
+/// An error response of the provider: exchangerate.host's error code and
+/// info, or the Frankfurter API's status code and message.
+#[derive(Debug, Clone)]
+pub struct FxError {
+    pub provider: FxProvider,
+    pub code: i64,
+    pub message: String,
+}
+
+impl fmt::Display for FxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error {}: {}", self.provider, self.code, self.message)
+    }
+}
+
+impl std::error::Error for FxError {}
+
+#[derive(Deserialize)]
+struct FrankfurterRates {
+    date: NaiveDate,
+    rates: HashMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+struct FrankfurterSeries {
+    rates: BTreeMap<NaiveDate, HashMap<String, f64>>,
+}
+
+#[derive(Deserialize)]
+struct FrankfurterError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct QuotesResponse {
+    success: bool,
+    #[serde(default)]
+    date: Option<NaiveDate>,
+    #[serde(default)]
+    timestamp: Option<i64>,
+    #[serde(default)]
+    quotes: HashMap<String, f64>,
+    #[serde(default)]
+    error: Option<QuotesError>,
+}
+
+#[derive(Deserialize)]
+struct TimeframeResponse {
+    success: bool,
+    #[serde(default)]
+    quotes: BTreeMap<NaiveDate, HashMap<String, f64>>,
+    #[serde(default)]
+    error: Option<QuotesError>,
+}
+
+#[derive(Deserialize)]
+struct QuotesError {
+    code: i64,
+    #[serde(default)]
+    info: Option<String>,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+}
+
+impl FxClient {
+    pub fn new(config: &FxConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!(
+            "Initializing {} exchange rates from {}",
+            config.provider, config.base_url
+        );
+        if config.provider == FxProvider::ExchangerateHost && config.api_key.is_none() {
+            bail!("exchangerate.host needs an access key");
+        }
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for exchange rates")?,
+            provider: config.provider,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            base_currency: config.base_currency.to_ascii_uppercase(),
+            cache_ttl: config.cache_ttl,
+            cache: Mutex::default(),
+        })
+    }
+
+    pub fn provider(&self) -> FxProvider {
+        self.provider
+    }
+
+    /// The currency the reporting tools' totals are converted into.
+    pub fn base_currency(&self) -> &str {
+        &self.base_currency
+    }
+
+    /// The rate from `from` to `to` on `date`, or the latest one. Fails with
+    /// [`UnknownCurrency`] for a currency the provider has no rates for.
+    #[instrument(skip(self))]
+    pub async fn rate(&self, from: &str, to: &str, date: Option<NaiveDate>) -> Result<Rate> {
+        let from = from.to_ascii_uppercase();
+        let to = to.to_ascii_uppercase();
+        if from == to {
+            return Ok(Rate {
+                rate: 1.0,
+                date: date.unwrap_or_else(|| Utc::now().date_naive()),
+            });
+        }
+        let rates = self.rates(&to, date).await?;
+        // Rates are per unit of `to`, so the rate from `from` is their inverse.
+        match rates.rates.get(&from) {
+            Some(&per_unit) if per_unit > 0.0 => Ok(Rate {
+                rate: 1.0 / per_unit,
+                date: rates.date,
+            }),
+            _ => Err(anyhow!(UnknownCurrency(from))),
+        }
+    }
+
+    /// The rates per unit of `base`, from the cache while they are fresh.
+    async fn rates(&self, base: &str, date: Option<NaiveDate>) -> Result<Arc<Rates>> {
+        let key = (base.to_string(), date);
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            if date.is_some() || cached.fetched_at.elapsed() < self.cache_ttl {
+                debug!("Exchange rates for {} from the cache", base);
+                return Ok(cached.rates.clone());
+            }
+        }
+
+        let start_time = Instant::now();
+        let rates = Arc::new(match self.provider {
+            FxProvider::Ecb => self.frankfurter(base, date).await?,
+            FxProvider::ExchangerateHost => self.exchangerate_host(base, date).await?,
+        });
+        info!(
+            "Fetched {} exchange rates for {} on {} in {:?}",
+            rates.rates.len(),
+            base,
+            rates.date,
+            start_time.elapsed()
+        );
+        self.remember(key, rates.clone());
+        Ok(rates)
+    }
+
+    /// Fetches the rates per unit of `base` of every day from `start` to
+    /// `end` as a time series, a year per request, so that [`Self::rate`]
+    /// finds them in the cache. A day without rates of its own, such as a
+    /// weekend, takes those of the last day before it that has some.
+    #[instrument(skip(self))]
+    pub async fn load_series(&self, base: &str, start: NaiveDate, end: NaiveDate) -> Result<()> {
+        let base = base.to_ascii_uppercase();
+        let start_time = Instant::now();
+        let mut from = start;
+        while from <= end {
+            let to = end.min(from + chrono::Duration::days(MAX_SERIES_DAYS - 1));
+            let series = match self.provider {
+                FxProvider::Ecb => self.frankfurter_series(&base, from, to).await?,
+                FxProvider::ExchangerateHost => self.timeframe(&base, from, to).await?,
+            };
+            let mut last = None;
+            let mut days = series.into_iter().peekable();
+            for day in from.iter_days().take_while(|day| *day <= to) {
+                while let Some((date, _)) = days.peek() {
+                    if *date > day {
+                        break;
+                    }
+                    let (date, rates) = days.next().unwrap();
+                    last = Some(Arc::new(Rates { date, rates }));
+                }
+                if let Some(rates) = &last {
+                    self.remember((base.clone(), Some(day)), rates.clone());
+                }
+            }
+            from = to + chrono::Duration::days(1);
+        }
+        info!(
+            "Fetched {} exchange rates from {} to {} in {:?}",
+            base,
+            start,
+            end,
+            start_time.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Keeps `rates` under `key`, first letting the longest kept go once
+    /// [`MAX_CACHED_RATES`] are.
+    fn remember(&self, key: (String, Option<NaiveDate>), rates: Arc<Rates>) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_RATES && !cache.contains_key(&key) {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            key,
+            Cached {
+                fetched_at: Instant::now(),
+                rates,
+            },
+        );
+    }
+
+    async fn frankfurter(&self, base: &str, date: Option<NaiveDate>) -> Result<Rates> {
+        let day = date.map_or("latest".to_string(), |date| date.to_string());
+        let response = self
+            .http
+            .get(format!("{}/{}", self.base_url, day))
+            .query(&[("base", base)])
+            .headers(correlation::headers())
+            .send()
+            .await
+            .context("Frankfurter request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Frankfurter request failed ({}): {}", status, body);
+            return Err(self.frankfurter_error(base, status, &body).await);
+        }
+        let rates: FrankfurterRates = response
+            .json()
+            .await
+            .context("failed to parse Frankfurter rates")?;
+        Ok(Rates {
+            date: rates.date,
+            rates: rates.rates,
+        })
+    }
+
+    async fn frankfurter_series(
+        &self,
+        base: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, HashMap<String, f64>>> {
+        let response = self
+            .http
+            .get(format!("{}/{start}..{end}", self.base_url))
+            .query(&[("base", base)])
+            .headers(correlation::headers())
+            .send()
+            .await
+            .context("Frankfurter request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Frankfurter request failed ({}): {}", status, body);
+            return Err(self.frankfurter_error(base, status, &body).await);
+        }
+        let series: FrankfurterSeries = response
+            .json()
+            .await
+            .context("failed to parse Frankfurter rates")?;
+        Ok(series.rates)
+    }
+
+    /// The error of a failed Frankfurter request for rates per unit of
+    /// `base`. Frankfurter answers 404 or 422 both for a currency and for a
+    /// day it has no rates for, so only one missing from its list of
+    /// currencies is an [`UnknownCurrency`].
+    async fn frankfurter_error(
+        &self,
+        base: &str,
+        status: reqwest::StatusCode,
+        body: &str,
+    ) -> anyhow::Error {
+        let message = serde_json::from_str::<FrankfurterError>(body)
+            .map_or_else(|_| status.to_string(), |rejected| rejected.message);
+        if matches!(status.as_u16(), 404 | 422) {
+            if let Ok(currencies) = self.frankfurter_currencies().await {
+                if !currencies.contains_key(base) {
+                    return anyhow!(UnknownCurrency(base.to_string()));
+                }
+            }
+        }
+        anyhow!(FxError {
+            provider: self.provider,
+            code: status.as_u16().into(),
+            message,
+        })
+    }
+
+    /// The currencies Frankfurter has rates for, by code.
+    async fn frankfurter_currencies(&self) -> Result<HashMap<String, String>> {
+        let response = self
+            .http
+            .get(format!("{}/currencies", self.base_url))
+            .headers(correlation::headers())
+            .send()
+            .await
+            .context("Frankfurter request failed")?
+            .error_for_status()
+            .context("Frankfurter did not list its currencies")?;
+        response
+            .json()
+            .await
+            .context("failed to parse Frankfurter currencies")
+    }
+
+    async fn exchangerate_host(&self, base: &str, date: Option<NaiveDate>) -> Result<Rates> {
+        let api_key = self.api_key.as_deref().unwrap_or_default();
+        let mut request = match date {
+            Some(date) => self
+                .http
+                .get(format!("{}/historical", self.base_url))
+                .query(&[("date", date.to_string())]),
+            None => self.http.get(format!("{}/live", self.base_url)),
+        };
+        request = request
+            .query(&[("access_key", api_key), ("source", base)])
+            .headers(correlation::headers());
+        let response = request
+            .send()
+            .await
+            .context("exchangerate.host request failed")?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let quotes: QuotesResponse = serde_json::from_str(&body).map_err(|_| {
+            error!("exchangerate.host request failed ({}): {}", status, body);
+            anyhow!(FxError {
+                provider: self.provider,
+                code: status.as_u16().into(),
+                message: format!("unexpected response ({status})"),
+            })
+        })?;
+        if !quotes.success {
+            error!("exchangerate.host rejected the request: {}", body);
+            return Err(self.quotes_error(base, quotes.error));
+        }
+        let date = match (quotes.date, quotes.timestamp) {
+            (Some(date), _) => date,
+            (None, Some(timestamp)) => DateTime::from_timestamp(timestamp, 0)
+                .context("exchangerate.host sent a timestamp out of range")?
+                .date_naive(),
+            (None, None) => Utc::now().date_naive(),
+        };
+        Ok(Rates {
+            date,
+            rates: unpaired(base, quotes.quotes),
+        })
+    }
+
+    async fn timeframe(
+        &self,
+        base: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, HashMap<String, f64>>> {
+        let api_key = self.api_key.as_deref().unwrap_or_default();
+        let response = self
+            .http
+            .get(format!("{}/timeframe", self.base_url))
+            .query(&[
+                ("access_key", api_key),
+                ("source", base),
+                ("start_date", &start.to_string()),
+                ("end_date", &end.to_string()),
+            ])
+            .headers(correlation::headers())
+            .send()
+            .await
+            .context("exchangerate.host request failed")?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let series: TimeframeResponse = serde_json::from_str(&body).map_err(|_| {
+            error!("exchangerate.host request failed ({}): {}", status, body);
+            anyhow!(FxError {
+                provider: self.provider,
+                code: status.as_u16().into(),
+                message: format!("unexpected response ({status})"),
+            })
+        })?;
+        if !series.success {
+            error!("exchangerate.host rejected the request: {}", body);
+            return Err(self.quotes_error(base, series.error));
+        }
+        Ok(series
+            .quotes
+            .into_iter()
+            .map(|(date, quotes)| (date, unpaired(base, quotes)))
+            .collect())
+    }
+
+    /// The error of a request exchangerate.host rejected, asking for rates
+    /// per unit of `base`.
+    fn quotes_error(&self, base: &str, error: Option<QuotesError>) -> anyhow::Error {
+        let rejected = error.unwrap_or(QuotesError {
+            code: 0,
+            info: None,
+            kind: None,
+        });
+        if rejected.code == INVALID_SOURCE_CURRENCY {
+            return anyhow!(UnknownCurrency(base.to_string()));
+        }
+        anyhow!(FxError {
+            provider: self.provider,
+            code: rejected.code,
+            message: rejected
+                .info
+                .or(rejected.kind)
+                .unwrap_or_else(|| "request failed".to_string()),
+        })
+    }
+}
+
+/// exchangerate.host's quotes, keyed by the pair such as `USDEUR`, keyed by
+/// the currency quoted instead.
+fn unpaired(base: &str, quotes: HashMap<String, f64>) -> HashMap<String, f64> {
+    quotes
+        .into_iter()
+        .filter_map(|(pair, rate)| Some((pair.strip_prefix(base)?.to_string(), rate)))
+        .collect()
+}
@@ -0,0 +1,558 @@
+//! GnuCash books, for `import_gnucash`: the XML file GnuCash saves, or its
+//! SQLite database. A book is a tree of accounts and transactions of two or
+//! more splits, each moving a value between the transaction's currency and
+//! one account.
+//!
+//! Asset, bank, cash, credit card and liability accounts become accounts,
+//! and income and expense accounts categories, named by their place below
+//! the top level (`Auto:Fuel` for `Expenses:Auto:Fuel`). Equity, trading and
+//! security accounts have no counterpart, so what an account's split moves
+//! to or from them, or to another account, is recorded as a transfer.
+
+use crate::models::{CategoryKind, CreateTransactionInput, TransactionDirection};
+use crate::xml::Element;
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// Largest part of an account's split left once its categories are taken
+/// out that is rounding rather than a transfer.
+const REMAINDER_TOLERANCE: f64 = 1e-6;
+
+/// First bytes of a SQLite database.
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// First bytes of a gzip stream, which GnuCash compresses XML books with
+/// unless told not to.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// The accounts and transactions of a book.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    /// In book order, parents before their children.
+    pub accounts: Vec<BookAccount>,
+    pub transactions: Vec<BookTransaction>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BookAccount {
+    pub id: String,
+    pub name: String,
+    /// GnuCash's account type, e.g. `BANK` or `EXPENSE`.
+    pub r#type: String,
+    /// `None` for the root account.
+    pub parent: Option<String>,
+    /// Code of the currency or security the account is held in, e.g. `EUR`.
+    pub commodity: Option<String>,
+    pub description: Option<String>,
+    /// Whether the account only groups others and holds no splits itself.
+    pub placeholder: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BookTransaction {
+    pub id: String,
+    /// RFC 3339 in UTC.
+    pub posted_at: String,
+    pub description: Option<String>,
+    pub splits: Vec<Split>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Split {
+    pub id: String,
+    pub account: String,
+    /// In the transaction's currency, negative for a credit.
+    pub value: f64,
+    /// In the account's commodity, negative for a credit.
+    pub quantity: f64,
+    pub memo: Option<String>,
+}
+
+/// What a GnuCash account is imported as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Account,
+    Category(CategoryKind),
+    /// Equity, trading and security accounts, and the root.
+    Other,
+}
+
+/// One transaction to record: the part of an account's split filed under
+/// one category, or what is left of the split once its categories are taken
+/// out, as a transfer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The split the entry stands for, the category's where there is one.
+    pub split: String,
+    pub transaction: String,
+    pub account: String,
+    pub category: Option<String>,
+    pub direction: TransactionDirection,
+    /// In the account's commodity, negative for money leaving the account.
+    pub amount: f64,
+    pub posted_at: String,
+    pub description: Option<String>,
+}
+
+impl Entry {
+    /// The entry as `create_transaction` takes it, with its split and
+    /// transaction ids and signed amount as its raw source.
+    pub fn to_input(&self, account_id: &str, currency: &str) -> CreateTransactionInput {
+        let raw_source = json!({
+            "gnucash_split": self.split,
+            "gnucash_transaction": self.transaction,
+            "gnucash_amount": self.amount,
+        });
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount.abs(),
+            currency: currency.to_string(),
+            direction: self.direction,
+            occurred_at: self.posted_at.clone(),
+            description: self.description.clone(),
+            raw_source: Some(raw_source.to_string()),
+        }
+    }
+}
+
+/// Whether a transaction recorded by an import brought money into its
+/// account, read back from its raw source.
+pub fn inflow(raw_source: &str) -> Option<bool> {
+    split_id(raw_source)?;
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("gnucash_amount")?
+        .as_f64()
+        .map(|amount| amount > 0.0)
+}
+
+/// The GnuCash split id of a transaction recorded by an import, read back
+/// from its raw source.
+pub fn split_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("gnucash_split")?
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+/// The book in `book`: uncompressed XML, or base64 of the XML or of a
+/// SQLite book.
+pub fn parse(book: &str) -> Result<Book> {
+    let book = book.trim_start();
+    if book.starts_with('<') {
+        return parse_xml(book);
+    }
+    let encoded = book
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let bytes = STANDARD
+        .decode(encoded)
+        .context("the book is neither GnuCash XML nor base64")?;
+    if bytes.starts_with(GZIP_MAGIC) {
+        bail!(
+            "the book is compressed; save it with \"Compress files\" turned off in GnuCash's preferences, or as SQLite"
+        );
+    }
+    if bytes.starts_with(SQLITE_MAGIC) {
+        return parse_sqlite(&bytes);
+    }
+    match std::str::from_utf8(&bytes) {
+        Ok(text) if text.trim_start().starts_with('<') => parse_xml(text.trim_start()),
+        _ => bail!("not a GnuCash book: neither XML nor SQLite"),
+    }
+}
+
+impl Book {
+    pub fn account(&self, id: &str) -> Option<&BookAccount> {
+        self.accounts.iter().find(|account| account.id == id)
+    }
+
+    pub fn role(&self, account: &BookAccount) -> Role {
+        match account.r#type.as_str() {
+            "INCOME" => Role::Category(CategoryKind::Income),
+            "EXPENSE" => Role::Category(CategoryKind::Expense),
+            "ROOT" | "EQUITY" | "TRADING" | "STOCK" | "MUTUAL" => Role::Other,
+            _ => Role::Account,
+        }
+    }
+
+    /// Names of the accounts from the top level down to `id`, without the
+    /// root.
+    fn path(&self, id: &str) -> Vec<&str> {
+        let mut path = Vec::new();
+        let mut next = self.account(id);
+        while let Some(account) = next {
+            if account.r#type == "ROOT" || path.len() > self.accounts.len() {
+                break;
+            }
+            path.push(account.name.as_str());
+            next = account
+                .parent
+                .as_deref()
+                .and_then(|parent| self.account(parent));
+        }
+        path.reverse();
+        path
+    }
+
+    /// The account's name in GnuCash, e.g. `Expenses:Auto:Fuel`.
+    pub fn full_name(&self, id: &str) -> String {
+        self.path(id).join(":")
+    }
+
+    /// The name the account is imported under: its place below the top
+    /// level, e.g. `Auto:Fuel`, or its own name at the top level.
+    pub fn imported_name(&self, id: &str) -> String {
+        let path = self.path(id);
+        match path.split_first() {
+            Some((top, [])) => top.to_string(),
+            Some((_, below)) => below.join(":"),
+            None => String::new(),
+        }
+    }
+
+    /// The accounts that become accounts or categories, in book order:
+    /// those holding splits, and those that are not placeholders.
+    pub fn imported_accounts(&self) -> Vec<&BookAccount> {
+        let used = self
+            .transactions
+            .iter()
+            .flat_map(|transaction| &transaction.splits)
+            .map(|split| split.account.as_str())
+            .collect::<HashSet<_>>();
+        self.accounts
+            .iter()
+            .filter(|account| self.role(account) != Role::Other)
+            .filter(|account| !account.placeholder || used.contains(account.id.as_str()))
+            .collect()
+    }
+
+    /// The transactions to record, in book order.
+    ///
+    /// Each category split is drawn on the account split of the opposite
+    /// sign that moves the most, or on the first account split when none is
+    /// of the opposite sign, e.g. a purchase split across groceries and
+    /// household on the account that paid, converted into its commodity.
+    /// Whatever is left of an account's split, all of it where no category
+    /// takes part, moved to another account or to equity, trading or
+    /// security accounts, is a transfer, so the account's balance follows
+    /// the book's.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for transaction in &self.transactions {
+            let role = |split: &Split| {
+                self.account(&split.account)
+                    .map_or(Role::Other, |account| self.role(account))
+            };
+            let accounts = transaction
+                .splits
+                .iter()
+                .filter(|split| role(split) == Role::Account)
+                .collect::<Vec<_>>();
+            let mut drawn = vec![Vec::new(); accounts.len()];
+            for category in transaction
+                .splits
+                .iter()
+                .filter(|split| matches!(role(split), Role::Category(_)))
+            {
+                let opposite = accounts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, account)| account.value * category.value < 0.0)
+                    .max_by(|(_, left), (_, right)| left.value.abs().total_cmp(&right.value.abs()))
+                    .map(|(index, _)| index);
+                if let Some(categories) = drawn.get_mut(opposite.unwrap_or(0)) {
+                    categories.push(category);
+                }
+            }
+            let entry = |split: &Split, memo: Option<&str>, category, direction, amount| Entry {
+                split: split.id.clone(),
+                transaction: transaction.id.clone(),
+                account: String::new(),
+                category,
+                direction,
+                amount,
+                posted_at: transaction.posted_at.clone(),
+                description: description(transaction.description.as_deref(), memo),
+            };
+            for (account, categories) in accounts.into_iter().zip(drawn) {
+                let rate = if account.value == 0.0 {
+                    1.0
+                } else {
+                    account.quantity / account.value
+                };
+                let mut remainder = account.quantity;
+                for category in categories {
+                    let amount = -category.value * rate;
+                    remainder -= amount;
+                    let memo = category.memo.as_deref().or(account.memo.as_deref());
+                    entries.push(Entry {
+                        account: account.account.clone(),
+                        ..entry(
+                            category,
+                            memo,
+                            Some(category.account.clone()),
+                            signed(amount),
+                            amount,
+                        )
+                    });
+                }
+                if remainder.abs() > REMAINDER_TOLERANCE {
+                    entries.push(Entry {
+                        account: account.account.clone(),
+                        ..entry(
+                            account,
+                            account.memo.as_deref(),
+                            None,
+                            TransactionDirection::Transfer,
+                            remainder,
+                        )
+                    });
+                }
+            }
+        }
+        entries
+    }
+}
+
+fn signed(amount: f64) -> TransactionDirection {
+    if amount > 0.0 {
+        TransactionDirection::Income
+    } else {
+        TransactionDirection::Expense
+    }
+}
+
+/// The transaction's description and the split's memo, as `Fuel - card`.
+fn description(description: Option<&str>, memo: Option<&str>) -> Option<String> {
+    let description = description.map(str::trim).filter(|text| !text.is_empty());
+    let memo = memo.map(str::trim).filter(|text| !text.is_empty());
+    match (description, memo) {
+        (Some(description), Some(memo)) if description != memo => {
+            Some(format!("{description} - {memo}"))
+        }
+        (Some(text), _) | (None, Some(text)) => Some(text.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// A GnuCash amount, a fraction such as `-1250/100`.
+fn fraction(value: &str) -> Result<f64> {
+    let invalid = || format!("amount {value:?} is not a fraction");
+    let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+    let numerator = numerator.trim().parse::<i64>().with_context(invalid)?;
+    let denominator = denominator.trim().parse::<i64>().with_context(invalid)?;
+    ratio(numerator, denominator)
+}
+
+fn ratio(numerator: i64, denominator: i64) -> Result<f64> {
+    if denominator == 0 {
+        bail!("amount {numerator}/{denominator} has a denominator of zero");
+    }
+    Ok(numerator as f64 / denominator as f64)
+}
+
+/// A GnuCash date as RFC 3339 in UTC: `2024-01-15 10:59:00 +0100` in XML,
+/// `2024-01-15 10:59:00` or `20240115105900` in UTC in SQLite.
+fn posted_at(value: &str) -> Result<String> {
+    let value = value.trim();
+    let parsed = match DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S %z") {
+        Ok(parsed) => parsed.with_timezone(&Utc),
+        Err(_) => NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S"))
+            .with_context(|| format!("{value:?} is not a GnuCash date"))?
+            .and_utc(),
+    };
+    Ok(parsed.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn parse_xml(document: &str) -> Result<Book> {
+    let root = Element::parse(document)?;
+    let Some(book) = root
+        .child("book")
+        .or_else(|| root.descendants("book").next())
+    else {
+        bail!("not a GnuCash book: no <gnc:book> element");
+    };
+    let mut parsed = Book::default();
+    // Only the book's own accounts and transactions: those of scheduled
+    // transactions sit further down, under <gnc:template-transactions>.
+    for account in book.children("account") {
+        let id = account.text_at(&["id"]).context("an account has no id")?;
+        let placeholder = account.descendants("slot").any(|slot| {
+            slot.text_at(&["key"]).as_deref() == Some("placeholder")
+                && slot.text_at(&["value"]).as_deref() == Some("true")
+        });
+        parsed.accounts.push(BookAccount {
+            name: account.text_at(&["name"]).unwrap_or_default(),
+            r#type: account.text_at(&["type"]).unwrap_or_default(),
+            parent: account.text_at(&["parent"]),
+            commodity: account.text_at(&["commodity", "id"]),
+            description: account.text_at(&["description"]),
+            placeholder,
+            id,
+        });
+    }
+    for transaction in book.children("transaction") {
+        let id = transaction
+            .text_at(&["id"])
+            .context("a transaction has no id")?;
+        let parsed_transaction =
+            parse_transaction(transaction, &id).with_context(|| format!("transaction {id}"))?;
+        parsed.transactions.push(parsed_transaction);
+    }
+    Ok(parsed)
+}
+
+fn parse_transaction(transaction: &Element, id: &str) -> Result<BookTransaction> {
+    let date = transaction
+        .text_at(&["date-posted", "date"])
+        .context("no posting date")?;
+    let mut splits = Vec::new();
+    for split in transaction
+        .child("splits")
+        .into_iter()
+        .flat_map(|splits| splits.children("split"))
+    {
+        let value = split.text_at(&["value"]).context("a split has no value")?;
+        let quantity = split
+            .text_at(&["quantity"])
+            .unwrap_or_else(|| value.clone());
+        splits.push(Split {
+            id: split.text_at(&["id"]).context("a split has no id")?,
+            account: split
+                .text_at(&["account"])
+                .context("a split has no account")?,
+            value: fraction(&value)?,
+            quantity: fraction(&quantity)?,
+            memo: split.text_at(&["memo"]),
+        });
+    }
+    Ok(BookTransaction {
+        id: id.to_string(),
+        posted_at: posted_at(&date)?,
+        description: transaction.text_at(&["description"]),
+        splits,
+    })
+}
+
+/// Reads the book from memory, so it never touches the disk.
+#[cfg(feature = "sqlite")]
+fn parse_sqlite(bytes: &[u8]) -> Result<Book> {
+    use rusqlite::Connection;
+
+    let mut connection = Connection::open_in_memory().context("could not open the SQLite book")?;
+    connection
+        .deserialize_read_exact("main", bytes, bytes.len(), true)
+        .context("could not open the SQLite book")?;
+    read_sqlite(&connection)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn parse_sqlite(_bytes: &[u8]) -> Result<Book> {
+    bail!("this server was built without SQLite support; save the book as uncompressed XML")
+}
+
+#[cfg(feature = "sqlite")]
+fn read_sqlite(connection: &rusqlite::Connection) -> Result<Book> {
+    let root: String = connection
+        .query_row("select root_account_guid from books", [], |row| row.get(0))
+        .context("not a GnuCash book: no books table")?;
+
+    let mut statement = connection.prepare(
+        "select a.guid, a.name, a.account_type, a.parent_guid, c.mnemonic,
+                a.description, a.placeholder
+         from accounts a left join commodities c on c.guid = a.commodity_guid",
+    )?;
+    let accounts = statement
+        .query_map([], |row| {
+            Ok(BookAccount {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                r#type: row.get(2)?,
+                parent: row.get(3)?,
+                commodity: row.get(4)?,
+                description: row
+                    .get::<_, Option<String>>(5)?
+                    .filter(|text| !text.is_empty()),
+                placeholder: row.get::<_, Option<i64>>(6)?.unwrap_or(0) != 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    // Only the book's own accounts: those of scheduled transactions hang
+    // from a root of their own.
+    let mut parsed = Book::default();
+    let by_id = accounts
+        .iter()
+        .map(|account| (account.id.as_str(), account))
+        .collect::<std::collections::HashMap<_, _>>();
+    for account in &accounts {
+        let mut next = Some(account);
+        let mut steps = 0;
+        while let Some(current) = next.filter(|current| current.id != root) {
+            steps += 1;
+            next = current
+                .parent
+                .as_deref()
+                .and_then(|parent| by_id.get(parent).copied())
+                .filter(|_| steps <= accounts.len());
+        }
+        if next.is_some() {
+            parsed.accounts.push(account.clone());
+        }
+    }
+
+    let mut statement = connection.prepare(
+        "select t.guid, t.post_date, t.description, s.guid, s.account_guid, s.memo,
+                s.value_num, s.value_denom, s.quantity_num, s.quantity_denom
+         from transactions t join splits s on s.tx_guid = t.guid
+         order by t.post_date, t.guid",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let id: String = row.get(0)?;
+        let split = Split {
+            id: row.get(3)?,
+            account: row.get(4)?,
+            memo: row
+                .get::<_, Option<String>>(5)?
+                .filter(|text| !text.is_empty()),
+            value: ratio(row.get(6)?, row.get(7)?)?,
+            quantity: ratio(row.get(8)?, row.get(9)?)?,
+        };
+        if parsed.transactions.last().map(|last| &last.id) != Some(&id) {
+            let date: String = row.get(1)?;
+            parsed.transactions.push(BookTransaction {
+                posted_at: posted_at(&date).with_context(|| format!("transaction {id}"))?,
+                description: row
+                    .get::<_, Option<String>>(2)?
+                    .filter(|text| !text.is_empty()),
+                splits: Vec::new(),
+                id,
+            });
+        }
+        if let Some(transaction) = parsed.transactions.last_mut() {
+            transaction.splits.push(split);
+        }
+    }
+    // Scheduled transactions' templates only touch their own accounts.
+    let book_accounts = parsed
+        .accounts
+        .iter()
+        .map(|account| account.id.clone())
+        .collect::<HashSet<_>>();
+    parsed.transactions.retain(|transaction| {
+        transaction
+            .splits
+            .iter()
+            .all(|split| book_accounts.contains(&split.account))
+    });
+    Ok(parsed)
+}
@@ -0,0 +1,396 @@
+//! Bank transactions from GoCardless Bank Account Data, formerly Nordigen,
+//! which reaches European banks through PSD2. The bank login is authorized
+//! through a requisition, which lists the bank accounts it grants access to,
+//! and each account's transactions are read by booking date. GoCardless
+//! reports no cursor, so a sync starts a few days before the last booking
+//! date it saw and skips the transactions recorded before.
+
+use crate::config::{GoCardlessConfig, HttpClientConfig};
+use crate::correlation;
+use crate::models::{CreateTransactionInput, TransactionDirection};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument};
+
+pub const GOCARDLESS_BASE_URL: &str = "https://bankaccountdata.gocardless.com/api/v2";
+
+/// Requisition status once the user has authorized access at the bank.
+pub const REQUISITION_LINKED: &str = "LN";
+
+/// Key of the link in an account's `metadata`.
+pub const OPENBANKING_METADATA_KEY: &str = "openbanking";
+
+/// Seconds before an access token expires at which a new one is fetched.
+const TOKEN_EXPIRY_MARGIN_SECS: u64 = 60;
+
+/// Talks to GoCardless with the secret id and key of one user secret.
+#[derive(Clone)]
+pub struct GoCardlessClient {
+    http: Client,
+    base_url: String,
+    secret_id: String,
+    secret_key: String,
+    /// The access token and when to fetch another.
+    token: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+/// A requisition, as `/requisitions/{id}/` returns it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Requisition {
+    pub id: String,
+    /// `LN` once linked; `CR`, `GA` or `UA` while the user is still at the
+    /// bank, `EX` or `RJ` once it can no longer be used.
+    pub status: String,
+    pub institution_id: String,
+    /// GoCardless ids of the bank accounts the requisition grants access to.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+}
+
+/// The amount of a transaction, in the bank's notation.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransactionAmount {
+    /// A decimal string, negative for money leaving the account.
+    pub amount: String,
+    pub currency: String,
+}
+
+/// A booked transaction of `/accounts/{id}/transactions/`, in the Berlin
+/// Group field names GoCardless passes on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookedTransaction {
+    #[serde(default)]
+    pub transaction_id: Option<String>,
+    /// GoCardless's own id, present where the bank sends none.
+    #[serde(default)]
+    pub internal_transaction_id: Option<String>,
+    #[serde(default)]
+    pub booking_date: Option<String>,
+    #[serde(default)]
+    pub booking_date_time: Option<String>,
+    #[serde(default)]
+    pub value_date: Option<String>,
+    pub transaction_amount: TransactionAmount,
+    #[serde(default)]
+    pub creditor_name: Option<String>,
+    #[serde(default)]
+    pub debtor_name: Option<String>,
+    #[serde(default)]
+    pub remittance_information_unstructured: Option<String>,
+    #[serde(default)]
+    pub remittance_information_unstructured_array: Vec<String>,
+    /// The transaction as GoCardless sent it.
+    #[serde(skip)]
+    pub raw: Value,
+}
+
+/// The transactions of an account: booked ones, and pending ones counted
+/// but not read as their ids and amounts may still change.
+#[derive(Debug, Clone, Default)]
+pub struct AccountTransactions {
+    pub booked: Vec<BookedTransaction>,
+    pub pending: usize,
+}
+
+/// An error response of GoCardless.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoCardlessError {
+    pub status_code: u16,
+    #[serde(default)]
+    pub summary: String,
+    #[serde(default)]
+    pub detail: Value,
+}
+
+impl fmt::Display for GoCardlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GoCardless {}: {}", self.status_code, self.summary)?;
+        match &self.detail {
+            Value::String(detail) if !detail.is_empty() => write!(f, " ({detail})"),
+            Value::Null => Ok(()),
+            detail => write!(f, " ({detail})"),
+        }
+    }
+}
+
+impl std::error::Error for GoCardlessError {}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access: String,
+    /// Seconds the access token is valid for.
+    access_expires: u64,
+}
+
+#[derive(Deserialize)]
+struct TransactionsResponse {
+    transactions: TransactionLists,
+}
+
+#[derive(Deserialize)]
+struct TransactionLists {
+    #[serde(default)]
+    booked: Vec<Value>,
+    #[serde(default)]
+    pending: Vec<Value>,
+}
+
+impl GoCardlessClient {
+    pub fn new(config: &GoCardlessConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing GoCardless client for {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for GoCardless")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            secret_id: config.secret_id.clone(),
+            secret_key: config.secret_key.clone(),
+            token: Arc::default(),
+        })
+    }
+
+    /// The requisition `requisition_id`, with the accounts it links.
+    #[instrument(skip(self))]
+    pub async fn requisition(&self, requisition_id: &str) -> Result<Requisition> {
+        let path = format!("/requisitions/{requisition_id}/");
+        let request = self.http.get(self.url(&path));
+        self.send(&path, request).await
+    }
+
+    /// The transactions of the GoCardless account `account_id` booked from
+    /// `date_from`, or as far back as the bank allows without one.
+    #[instrument(skip(self))]
+    pub async fn transactions(
+        &self,
+        account_id: &str,
+        date_from: Option<NaiveDate>,
+    ) -> Result<AccountTransactions> {
+        let start_time = Instant::now();
+        let path = format!("/accounts/{account_id}/transactions/");
+        let mut request = self.http.get(self.url(&path));
+        if let Some(date_from) = date_from {
+            request = request.query(&[("date_from", date_from.format("%Y-%m-%d").to_string())]);
+        }
+        let response: TransactionsResponse = self.send(&path, request).await?;
+        let booked = response
+            .transactions
+            .booked
+            .into_iter()
+            .map(|raw| {
+                let mut transaction: BookedTransaction = serde_json::from_value(raw.clone())
+                    .context("unexpected GoCardless transaction shape")?;
+                transaction.raw = raw;
+                Ok(transaction)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        info!(
+            "Read {} booked and {} pending GoCardless transactions in {:?}",
+            booked.len(),
+            response.transactions.pending.len(),
+            start_time.elapsed()
+        );
+        Ok(AccountTransactions {
+            booked,
+            pending: response.transactions.pending.len(),
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// An access token, fetched with the secret when there is none or it is
+    /// about to expire.
+    async fn access_token(&self) -> Result<String> {
+        let cached = self.token.lock().unwrap().clone();
+        if let Some((token, refresh_at)) = cached {
+            if Instant::now() < refresh_at {
+                return Ok(token);
+            }
+        }
+        debug!("Fetching a GoCardless access token");
+        let path = "/token/new/";
+        let response = self
+            .http
+            .post(self.url(path))
+            .headers(correlation::headers())
+            .json(&json!({
+                "secret_id": self.secret_id,
+                "secret_key": self.secret_key,
+            }))
+            .send()
+            .await
+            .with_context(|| format!("GoCardless request to {path} failed"))?;
+        let token: TokenResponse = parse(path, response).await?;
+        let valid_for = Duration::from_secs(
+            token
+                .access_expires
+                .saturating_sub(TOKEN_EXPIRY_MARGIN_SECS),
+        );
+        *self.token.lock().unwrap() = Some((token.access.clone(), Instant::now() + valid_for));
+        Ok(token.access)
+    }
+
+    /// Sends `request` with an access token and parses the response.
+    async fn send<T: DeserializeOwned>(&self, path: &str, request: RequestBuilder) -> Result<T> {
+        let token = self.access_token().await?;
+        let response = request
+            .bearer_auth(token)
+            .headers(correlation::headers())
+            .send()
+            .await
+            .with_context(|| format!("GoCardless request to {path} failed"))?;
+        parse(path, response).await
+    }
+}
+
+async fn parse<T: DeserializeOwned>(path: &str, response: reqwest::Response) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        error!(
+            "GoCardless request to {} failed ({}): {}",
+            path, status, body
+        );
+        return Err(match serde_json::from_str::<GoCardlessError>(&body) {
+            Ok(err) => anyhow!(err),
+            Err(_) => anyhow!(GoCardlessError {
+                status_code: status.as_u16(),
+                summary: format!("request to {path} failed"),
+                detail: Value::Null,
+            }),
+        });
+    }
+    response
+        .json()
+        .await
+        .with_context(|| format!("failed to parse GoCardless response from {path}"))
+}
+
+impl BookedTransaction {
+    /// The amount, negative for money leaving the account.
+    pub fn amount(&self) -> Result<f64> {
+        let amount = &self.transaction_amount.amount;
+        amount
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|amount| amount.is_finite())
+            .with_context(|| format!("{amount:?} is not an amount"))
+    }
+
+    /// The day the bank booked the transaction, or else its value date.
+    pub fn booked_on(&self) -> Result<NaiveDate> {
+        let Some(date) = self.booking_date.as_deref().or(self.value_date.as_deref()) else {
+            bail!("the transaction has neither a booking nor a value date");
+        };
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .with_context(|| format!("{date:?} is not a date"))
+    }
+
+    /// When the transaction happened as an RFC 3339 timestamp in UTC: its
+    /// booking time if the bank sends one, otherwise midnight of its date.
+    pub fn occurred_at(&self) -> Result<String> {
+        if let Some(time) = self.booking_date_time.as_deref() {
+            let parsed = DateTime::parse_from_rfc3339(time)
+                .with_context(|| format!("{time:?} is not an RFC 3339 timestamp"))?;
+            return Ok(parsed
+                .with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+        Ok(self
+            .booked_on()?
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+            .and_utc()
+            .to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    /// The other party, the creditor of money paid and the debtor of money
+    /// received, or else the remittance text.
+    pub fn description(&self, direction: TransactionDirection) -> Option<String> {
+        let party = match direction {
+            TransactionDirection::Income => &self.debtor_name,
+            _ => &self.creditor_name,
+        };
+        party
+            .clone()
+            .into_iter()
+            .chain(self.remittance_information_unstructured.clone())
+            .chain(Some(
+                self.remittance_information_unstructured_array.join(" "),
+            ))
+            .map(|text| text.trim().to_string())
+            .find(|text| !text.is_empty())
+    }
+
+    /// The transaction as `create_transaction` takes it, with the JSON
+    /// GoCardless sent as its raw source so that [`entry_id`] finds it again.
+    pub fn to_input(&self, account_id: &str) -> Result<CreateTransactionInput> {
+        let context = || format!("GoCardless transaction {}", self.id());
+        let amount = self.amount().with_context(context)?;
+        let direction = if amount < 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        };
+        Ok(CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: amount.abs(),
+            currency: self.transaction_amount.currency.clone(),
+            direction,
+            occurred_at: self.occurred_at().with_context(context)?,
+            description: self.description(direction),
+            raw_source: Some(self.raw.to_string()),
+        })
+    }
+
+    /// The id a result lists the transaction by: the bank's or GoCardless's,
+    /// or else its booking date and amount.
+    pub fn id(&self) -> String {
+        self.transaction_id
+            .iter()
+            .chain(&self.internal_transaction_id)
+            .find(|id| !id.is_empty())
+            .cloned()
+            .unwrap_or_else(|| {
+                format!(
+                    "{} {} {}",
+                    self.booking_date.as_deref().unwrap_or_default(),
+                    self.transaction_amount.amount,
+                    self.transaction_amount.currency
+                )
+            })
+    }
+
+    /// What tells this transaction apart in later syncs; see [`entry_id`].
+    pub fn entry_id(&self) -> String {
+        entry_id(&self.raw.to_string()).unwrap_or_default()
+    }
+}
+
+/// What identifies a transaction recorded by an open-banking sync, read back
+/// from its raw source: the bank's `transactionId`, else GoCardless's
+/// `internalTransactionId`, else the whole entry, which some banks repeat
+/// unchanged in every response.
+pub fn entry_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("transactionAmount")?;
+    ["transactionId", "internalTransactionId"]
+        .into_iter()
+        .find_map(|key| raw.get(key)?.as_str().filter(|id| !id.is_empty()))
+        .map(str::to_string)
+        .or_else(|| Some(raw_source.to_string()))
+}
@@ -0,0 +1,102 @@
+//! Health endpoints for container orchestration, served on a port of their
+//! own next to any transport: `/healthz` and `/readyz` report the outcome of
+//! periodic dependency probes, so Docker or Kubernetes can restart a server
+//! that lost its database or embedding provider.
+
+use crate::models::{HealthCheckOutput, HealthStatus};
+use crate::server::ExaspoonDbServer;
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tracing::info;
+
+/// Fails while the latest probe found a dependency down.
+pub const HEALTHZ_PATH: &str = "/healthz";
+/// Fails until a probe has passed, whenever one fails, and while shutting
+/// down.
+pub const READYZ_PATH: &str = "/readyz";
+
+#[derive(Clone)]
+struct Probes {
+    server: ExaspoonDbServer,
+    latest: watch::Receiver<Option<HealthCheckOutput>>,
+}
+
+/// Probes `server`'s dependencies every `interval` and answers health
+/// requests on `listener` with the latest outcome. Requests never probe
+/// themselves, so frequent polling costs no embedding requests.
+pub async fn serve(
+    server: ExaspoonDbServer,
+    listener: TcpListener,
+    interval: Duration,
+) -> Result<()> {
+    let (report, latest) = watch::channel(None);
+    let probing = server.clone();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            let health = probing.probe_health().await;
+            if report.send(Some(health)).is_err() {
+                return;
+            }
+        }
+    });
+
+    let app = Router::new()
+        .route(HEALTHZ_PATH, get(healthz))
+        .route(READYZ_PATH, get(readyz))
+        .with_state(Probes { server, latest });
+    info!("Serving health checks at http://{}", listener.local_addr()?);
+    axum::serve(listener, app)
+        .await
+        .context("health endpoint failed")
+}
+
+async fn healthz(State(probes): State<Probes>) -> Response {
+    match &*probes.latest.borrow() {
+        None => respond(StatusCode::OK, json!({ "status": "starting" })),
+        Some(health) => respond(status_code(health), json!(health)),
+    }
+}
+
+async fn readyz(State(probes): State<Probes>) -> Response {
+    if probes.server.is_draining() {
+        return respond(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "status": "draining" }),
+        );
+    }
+    match &*probes.latest.borrow() {
+        None => respond(
+            StatusCode::SERVICE_UNAVAILABLE,
+            json!({ "status": "starting" }),
+        ),
+        Some(health) => respond(status_code(health), json!(health)),
+    }
+}
+
+fn status_code(health: &HealthCheckOutput) -> StatusCode {
+    match health.status {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::Degraded => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+fn respond(status: StatusCode, body: Value) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
@@ -0,0 +1,139 @@
+//! The streamable HTTP transport: one long-lived server that any number of
+//! MCP clients connect to, each in a session of its own, instead of a server
+//! spawned per client over stdio.
+
+use crate::{origin::AllowedHosts, server::ExaspoonDbServer};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rmcp::transport::streamable_http_server::{
+    session::local::{LocalSessionManager, SessionConfig},
+    StreamableHttpServerConfig, StreamableHttpService,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Path clients send their MCP requests to.
+pub const MCP_PATH: &str = "/mcp";
+
+/// Seconds a client refused a session is told to wait before trying again.
+pub const SESSION_RETRY_AFTER_SECS: u64 = 5;
+
+/// Header naming the session a request belongs to.
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Serves `server` over streamable HTTP on `listener` until `stop` is
+/// cancelled, starting a [`ExaspoonDbServer::new_session`] for each client
+/// that initializes. A session that sees no request for `idle_timeout` is
+/// closed, since HTTP clients may go away without ending theirs. Requests
+/// whose `Host` or `Origin` is not among `allowed_hosts` are refused with 403,
+/// and new sessions beyond the server's limit with 503.
+pub async fn serve(
+    server: ExaspoonDbServer,
+    listener: TcpListener,
+    idle_timeout: Option<Duration>,
+    allowed_hosts: AllowedHosts,
+    stop: CancellationToken,
+) -> Result<()> {
+    let address = listener.local_addr()?;
+    let sessions = LocalSessionManager {
+        sessions: Default::default(),
+        session_config: SessionConfig {
+            keep_alive: idle_timeout,
+            ..SessionConfig::default()
+        },
+    };
+    let limited = server.clone();
+    let service = StreamableHttpService::new(
+        move || {
+            let session = server
+                .new_session()
+                .ok_or_else(|| std::io::Error::other("too many client sessions"))?;
+            info!("Client session started");
+            Ok(session)
+        },
+        Arc::new(sessions),
+        StreamableHttpServerConfig::default(),
+    );
+    let app = axum::Router::new()
+        .nest_service(MCP_PATH, service)
+        .layer(axum::middleware::from_fn_with_state(
+            limited,
+            check_sessions,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(allowed_hosts),
+            check_host,
+        ));
+    info!("Serving MCP at http://{}{}", address, MCP_PATH);
+
+    // Clients keep their event streams open for as long as they like, so
+    // once running calls have drained the connections are dropped rather
+    // than waited for.
+    tokio::select! {
+        served = axum::serve(listener, app) => served.context("HTTP transport failed"),
+        _ = stop.cancelled() => {
+            info!("Stopped serving MCP at http://{}{}", address, MCP_PATH);
+            Ok(())
+        }
+    }
+}
+
+/// Refuses requests addressed to a host, or sent from a browser origin, that
+/// `allowed_hosts` does not admit.
+async fn check_host(
+    State(allowed_hosts): State<Arc<AllowedHosts>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !admits(&allowed_hosts, &request) {
+        return (StatusCode::FORBIDDEN, "host or origin not allowed").into_response();
+    }
+    next.run(request).await
+}
+
+fn admits(allowed_hosts: &AllowedHosts, request: &Request) -> bool {
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let (host, origin) = (header(header::HOST), header(header::ORIGIN));
+    let admitted = allowed_hosts.admits(host, origin);
+    if !admitted {
+        warn!(
+            "Refused request for host {:?} from origin {:?}",
+            host, origin
+        );
+    }
+    admitted
+}
+
+/// Refuses a request that would open a session while the server holds as
+/// many as it allows, asking the client to retry later instead of failing
+/// the session outright.
+async fn check_sessions(
+    State(server): State<ExaspoonDbServer>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let opens_session =
+        request.method() == Method::POST && !request.headers().contains_key(SESSION_HEADER);
+    if opens_session && server.sessions_full() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, SESSION_RETRY_AFTER_SECS.to_string())],
+            "too many client sessions",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
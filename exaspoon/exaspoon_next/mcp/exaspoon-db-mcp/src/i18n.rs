@@ -0,0 +1,323 @@
+//! Language of the messages a person reads: tool call errors, elicitation
+//! questions and narrated reports, and the way amounts are written in text.
+//! Error data, logs and tool output stay in English so agents and operators
+//! can rely on them.
+//!
+//! The locale of a call is the one its client asks for under the
+//! `i18n.locale` experimental capability, else the server's configured one.
+
+use crate::confirmation::Refused;
+use crate::rate_limit::RateLimited;
+use crate::validation::Rule;
+use anyhow::{anyhow, Result};
+use rmcp::model::InitializeRequestParam;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+tokio::task_local! {
+    static CURRENT: Locale;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Locale {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Ru => "ru",
+        }
+    }
+
+    /// The locale `client` asked for, if it asked for one this server has.
+    pub fn requested_by(client: &InitializeRequestParam) -> Option<Self> {
+        let tag = client
+            .capabilities
+            .experimental
+            .as_ref()?
+            .get("i18n")?
+            .get("locale")?
+            .as_str()?;
+        tag.parse().ok()
+    }
+
+    /// Name of the language, for instructing the client's model.
+    pub fn language(&self) -> &'static str {
+        match self {
+            Self::En => "English",
+            Self::Ru => "Russian",
+        }
+    }
+
+    /// `amount` of `currency` as this locale writes it, with the currency's
+    /// symbol, grouped thousands and the locale's decimal separator, e.g.
+    /// `-$1,234.50` or `-1 234,50 $`. Currencies without a known symbol, such
+    /// as crypto assets, keep their code and up to 8 decimals.
+    pub fn format_amount(&self, amount: f64, currency: &str) -> String {
+        let known = currency_format(currency);
+        let decimals = match known {
+            Some((_, decimals)) => decimals,
+            None => decimals_needed(amount),
+        };
+        let digits = format!("{:.*}", decimals, amount.abs());
+        let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+        let (separator, point) = match self {
+            Self::En => (',', '.'),
+            Self::Ru => ('\u{a0}', ','),
+        };
+        let mut number = group_thousands(whole, separator);
+        if !fraction.is_empty() {
+            number.push(point);
+            number.push_str(fraction);
+        }
+        // No minus on an amount that rounds to zero.
+        let negative = amount < 0.0 && digits.bytes().any(|b| b.is_ascii_digit() && b != b'0');
+        let sign = if negative { "-" } else { "" };
+        match (self, known) {
+            (Self::En, Some((symbol, _))) => format!("{sign}{symbol}{number}"),
+            (Self::En, None) => format!("{sign}{number} {currency}"),
+            (Self::Ru, known) => {
+                let unit = known.map_or(currency, |(symbol, _)| symbol);
+                format!("{sign}{number}\u{a0}{unit}")
+            }
+        }
+    }
+}
+
+/// Symbol and decimals of the currencies written with a symbol.
+fn currency_format(code: &str) -> Option<(&'static str, usize)> {
+    match code {
+        "USD" => Some(("$", 2)),
+        "EUR" => Some(("€", 2)),
+        "GBP" => Some(("£", 2)),
+        "RUB" => Some(("₽", 2)),
+        "UAH" => Some(("₴", 2)),
+        "KZT" => Some(("₸", 2)),
+        "INR" => Some(("₹", 2)),
+        "CNY" => Some(("¥", 2)),
+        "JPY" => Some(("¥", 0)),
+        _ => None,
+    }
+}
+
+/// Decimals `amount` needs, at least 2 and at most 8.
+fn decimals_needed(amount: f64) -> usize {
+    let digits = format!("{:.8}", amount.abs());
+    let fraction = digits.split_once('.').map_or("", |(_, fraction)| fraction);
+    fraction.trim_end_matches('0').len().max(2)
+}
+
+fn group_thousands(digits: &str, separator: char) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    /// Accepts a BCP 47 tag such as `ru-RU`, or a POSIX one such as
+    /// `ru_RU.UTF-8`, by its language.
+    fn from_str(value: &str) -> Result<Self> {
+        let language = value
+            .trim()
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Ok(Self::En),
+            "ru" => Ok(Self::Ru),
+            _ => Err(anyhow!("unsupported locale: {value}")),
+        }
+    }
+}
+
+/// Runs `future` with `locale` as the language of the current call.
+pub async fn scope<F: Future>(locale: Locale, future: F) -> F::Output {
+    CURRENT.scope(locale, future).await
+}
+
+/// The language of the tool call being served; English outside of a call.
+pub fn current() -> Locale {
+    CURRENT.try_with(|locale| *locale).unwrap_or_default()
+}
+
+/// `amount` of `currency` written the current call's way.
+pub fn format_amount(amount: f64, currency: &str) -> String {
+    current().format_amount(amount, currency)
+}
+
+/// A message shown to the user, rendered in the current call's language.
+#[derive(Debug, Clone, Copy)]
+pub enum Message<'a> {
+    /// A database or embedding request failed; `action` is e.g. `insert
+    /// transaction`.
+    Failed {
+        action: &'a str,
+    },
+    /// A required field was left empty.
+    Required {
+        field: &'a str,
+    },
+    /// An argument broke the validation `rule` of its field.
+    Invalid {
+        field: &'a str,
+        rule: Rule,
+    },
+    RateLimited(&'a RateLimited),
+    TimedOut {
+        tool: &'a str,
+        limit: Duration,
+    },
+    /// The server is draining and refuses new calls.
+    ShuttingDown,
+    /// A destructive call's confirmation token was refused.
+    Unconfirmed(Refused),
+    /// Asks the user for the fields a transaction is missing.
+    WhichFields {
+        fields: &'a [&'a str],
+        direction: &'a str,
+        amount: &'a str,
+        occurred_at: &'a str,
+    },
+}
+
+impl Message<'_> {
+    /// The message in the current call's language.
+    pub fn text(&self) -> String {
+        self.localize(current())
+    }
+
+    pub fn localize(&self, locale: Locale) -> String {
+        match (self, locale) {
+            (Self::Failed { action }, Locale::En) => format!("Failed to {action}"),
+            (Self::Failed { action }, Locale::Ru) => {
+                format!("Не удалось выполнить операцию «{action}»")
+            }
+            (Self::Required { field }, Locale::En) => format!("{field} is required"),
+            (Self::Required { field }, Locale::Ru) => format!("Поле {field} обязательно"),
+            (Self::Invalid { field, rule }, Locale::En) => match rule {
+                Rule::Rfc3339 => {
+                    format!("{field} must be an RFC 3339 timestamp, e.g. 2024-01-31T12:00:00Z")
+                }
+                Rule::Positive => format!("{field} must be a positive number"),
+                Rule::Currency => format!(
+                    "{field} must be an uppercase currency or asset code such as USD or USDT, \
+                     or a token contract or mint"
+                ),
+                Rule::MaxChars(max) => format!("{field} must be at most {max} characters"),
+                Rule::Range(min, max) => format!("{field} must be between {min} and {max}"),
+                Rule::Timezone => format!(
+                    "{field} must be a UTC offset such as +03:00 or a timezone such as \
+                     Europe/Berlin"
+                ),
+            },
+            (Self::Invalid { field, rule }, Locale::Ru) => match rule {
+                Rule::Rfc3339 => format!(
+                    "Поле {field} должно содержать время в формате RFC 3339, \
+                     например 2024-01-31T12:00:00Z"
+                ),
+                Rule::Positive => format!("Поле {field} должно быть положительным числом"),
+                Rule::Currency => format!(
+                    "Поле {field} должно содержать код валюты или актива заглавными буквами, \
+                     например USD или USDT, либо адрес контракта или минта токена"
+                ),
+                Rule::MaxChars(max) => {
+                    format!("Поле {field} должно быть не длиннее {max} символов")
+                }
+                Rule::Range(min, max) => format!("Поле {field} должно быть от {min} до {max}"),
+                Rule::Timezone => format!(
+                    "Поле {field} должно содержать смещение от UTC, например +03:00, \
+                     или часовой пояс, например Europe/Berlin"
+                ),
+            },
+            (Self::RateLimited(limited), Locale::En) => limited.to_string(),
+            (Self::RateLimited(limited), Locale::Ru) => {
+                let what = match limited.budget {
+                    "embedding_calls" => "вызовов инструментов с эмбеддингами",
+                    _ => "вызовов инструментов",
+                };
+                format!(
+                    "достигнут лимит в {} {} за {} с; повторите через {} с",
+                    limited.limit,
+                    what,
+                    limited.window.as_secs(),
+                    limited.retry_in.as_secs_f32().ceil()
+                )
+            }
+            (Self::TimedOut { tool, limit }, Locale::En) => {
+                format!("{tool} did not finish within {}ms", limit.as_millis())
+            }
+            (Self::TimedOut { tool, limit }, Locale::Ru) => {
+                format!("{tool} не завершился за {} мс", limit.as_millis())
+            }
+            (Self::ShuttingDown, Locale::En) => {
+                "The server is shutting down; retry once it is back".to_string()
+            }
+            (Self::ShuttingDown, Locale::Ru) => {
+                "Сервер завершает работу; повторите вызов после его перезапуска".to_string()
+            }
+            (Self::Unconfirmed(refused), Locale::En) => {
+                let why = match refused {
+                    Refused::Unknown => "is unknown or already used",
+                    Refused::Expired => "has expired",
+                    Refused::Mismatch => "was issued for a different call",
+                };
+                format!("confirmation_token {why}; call without it to preview and get a new one")
+            }
+            (Self::Unconfirmed(refused), Locale::Ru) => {
+                let why = match refused {
+                    Refused::Unknown => "неизвестен или уже использован",
+                    Refused::Expired => "просрочен",
+                    Refused::Mismatch => "выдан для другого вызова",
+                };
+                format!(
+                    "confirmation_token {why}; вызовите инструмент без него, чтобы увидеть \
+                     предпросмотр и получить новый"
+                )
+            }
+            (
+                Self::WhichFields {
+                    fields,
+                    direction,
+                    amount,
+                    occurred_at,
+                },
+                Locale::En,
+            ) => format!(
+                "Which {} should the {direction} of {amount} on {occurred_at} use?",
+                fields.join(" and ")
+            ),
+            (
+                Self::WhichFields {
+                    fields,
+                    direction,
+                    amount,
+                    occurred_at,
+                },
+                Locale::Ru,
+            ) => {
+                let direction = match *direction {
+                    "income" => "дохода",
+                    "expense" => "расхода",
+                    "transfer" => "перевода",
+                    other => other,
+                };
+                format!(
+                    "Укажите {} для {direction} на {amount} от {occurred_at}.",
+                    fields.join(" и ")
+                )
+            }
+        }
+    }
+}
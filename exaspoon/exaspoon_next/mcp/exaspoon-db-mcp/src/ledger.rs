@@ -0,0 +1,273 @@
+//! Plain-text accounting journals, for `export_ledger`: beancount, or the
+//! ledger format hledger reads as well. Each transaction becomes a balanced
+//! entry of two postings, the account's `Assets:` account against the
+//! `Expenses:` or `Income:` account named after its category, so the books
+//! can be checked with `bean-check` or `hledger check` and reported on with
+//! the tools around them.
+//!
+//! A transfer records only one side of the movement, so it is booked
+//! against `Equity:Transfers`, or the `Equity:Transfers:` account of its
+//! category. Its amount is stored unsigned, so whether it came in or left is
+//! read back from the raw source of the import that recorded it; a transfer
+//! entered by hand is booked as leaving.
+
+use crate::models::{
+    Account, Category, CategoryKind, LedgerFormat, Transaction, TransactionDirection,
+};
+use crate::{gnucash, ofx, plaid, qif, ynab};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+/// Most transactions one export writes, the newest.
+pub const MAX_LEDGER_TRANSACTIONS: usize = 5000;
+
+/// Longest commodity beancount takes.
+const MAX_COMMODITY_CHARS: usize = 24;
+
+/// Opening date of accounts nothing dates.
+const EPOCH: &str = "1970-01-01";
+
+/// The journal of `transactions`, which are written in the order given, with
+/// an opening or declaration of every account of `accounts` and `categories`
+/// and of any account the transactions reach that they lack.
+pub fn write(
+    format: LedgerFormat,
+    accounts: &[Account],
+    categories: &[Category],
+    transactions: &[Transaction],
+) -> String {
+    let mut names = Names::default();
+    let mut opened = BTreeMap::new();
+    for account in accounts {
+        let name = names.account(account);
+        open(
+            &mut opened,
+            &name,
+            created_on(account.created_at.as_deref()),
+        );
+    }
+    for category in categories {
+        let name = names.category(category);
+        open(
+            &mut opened,
+            &name,
+            created_on(category.created_at.as_deref()),
+        );
+    }
+
+    let mut entries = String::new();
+    for transaction in transactions {
+        let date = transaction
+            .occurred_at
+            .get(..10)
+            .unwrap_or(&transaction.occurred_at);
+        let account = names.account_id(&transaction.account_id);
+        let other = names.counterpart(transaction.direction, transaction.category_id.as_deref());
+        open(&mut opened, &account, date);
+        open(&mut opened, &other, date);
+        let (debit, credit) = match transaction.direction {
+            TransactionDirection::Income => (account, other),
+            TransactionDirection::Transfer if inflow(transaction) => (account, other),
+            TransactionDirection::Expense | TransactionDirection::Transfer => (other, account),
+        };
+        let description = transaction
+            .description
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let commodity = commodity(format, &transaction.currency);
+        let amount = transaction.amount;
+
+        entries.push('\n');
+        match format {
+            LedgerFormat::Beancount => {
+                let _ = writeln!(entries, "{date} * \"{}\"", quoted(&description));
+                let _ = writeln!(entries, "  id: \"{}\"", quoted(&transaction.id));
+                let _ = writeln!(entries, "  {debit}  {} {commodity}", number(amount));
+                let _ = writeln!(entries, "  {credit}  {} {commodity}", number(-amount));
+            }
+            LedgerFormat::Ledger => {
+                let payee = if description.is_empty() {
+                    "(no description)"
+                } else {
+                    &description
+                };
+                let _ = writeln!(entries, "{date} * {payee}");
+                let _ = writeln!(entries, "    ; id: {}", transaction.id);
+                let _ = writeln!(entries, "    {debit}  {} {commodity}", number(amount));
+                let _ = writeln!(entries, "    {credit}  {} {commodity}", number(-amount));
+            }
+        }
+    }
+
+    let mut opened = opened.into_iter().collect::<Vec<_>>();
+    opened.sort_by(|left, right| left.1.cmp(&right.1).then_with(|| left.0.cmp(&right.0)));
+    let mut journal = String::new();
+    for (name, date) in opened {
+        let _ = match format {
+            LedgerFormat::Beancount => writeln!(journal, "{date} open {name}"),
+            LedgerFormat::Ledger => writeln!(journal, "account {name}"),
+        };
+    }
+    journal.push_str(&entries);
+    journal
+}
+
+/// Account names given out so far, by the id of what they stand for, kept
+/// unique by suffixing a later namesake with the start of its id.
+#[derive(Default)]
+struct Names {
+    by_id: HashMap<String, String>,
+    taken: HashSet<String>,
+}
+
+impl Names {
+    fn account(&mut self, account: &Account) -> String {
+        self.name(&account.id, "Assets", &account.name)
+    }
+
+    fn category(&mut self, category: &Category) -> String {
+        let root = match category.kind {
+            CategoryKind::Income => "Income",
+            CategoryKind::Expense => "Expenses",
+            CategoryKind::Transfer => "Equity:Transfers",
+        };
+        self.name(&category.id, root, &category.name)
+    }
+
+    /// The account `account_id`, named after its id when it is not among
+    /// those listed, e.g. once deleted.
+    fn account_id(&mut self, account_id: &str) -> String {
+        self.name(account_id, "Assets", account_id)
+    }
+
+    /// What a transaction of `direction` filed under `category_id` is booked
+    /// against.
+    fn counterpart(
+        &mut self,
+        direction: TransactionDirection,
+        category_id: Option<&str>,
+    ) -> String {
+        let root = match direction {
+            TransactionDirection::Income => "Income",
+            TransactionDirection::Expense => "Expenses",
+            TransactionDirection::Transfer => "Equity:Transfers",
+        };
+        match category_id {
+            Some(category_id) => self.name(category_id, root, category_id),
+            None if direction == TransactionDirection::Transfer => root.to_string(),
+            None => format!("{root}:Uncategorized"),
+        }
+    }
+
+    fn name(&mut self, id: &str, root: &str, name: &str) -> String {
+        if let Some(known) = self.by_id.get(id) {
+            return known.clone();
+        }
+        let mut full = format!("{root}:{}", component(name));
+        if !self.taken.insert(full.clone()) {
+            full = format!("{full}-{}", component(id.get(..8).unwrap_or(id)));
+            self.taken.insert(full.clone());
+        }
+        self.by_id.insert(id.to_string(), full.clone());
+        full
+    }
+}
+
+/// `name` as one component of an account name, which both formats restrict
+/// to words: each word capitalized, joined by dashes.
+fn component(name: &str) -> String {
+    let words = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        "Unnamed".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+/// Records that `name` is used on `date`, keeping the earliest date, which
+/// beancount opens the account on.
+fn open(opened: &mut BTreeMap<String, String>, name: &str, date: &str) {
+    match opened.get_mut(name) {
+        Some(earliest) if date < earliest.as_str() => *earliest = date.to_string(),
+        Some(_) => {}
+        None => {
+            opened.insert(name.to_string(), date.to_string());
+        }
+    }
+}
+
+/// Whether the import that recorded `transaction` saw its money come in.
+fn inflow(transaction: &Transaction) -> bool {
+    let Some(raw_source) = transaction.raw_source.as_deref() else {
+        return false;
+    };
+    ofx::inflow(raw_source)
+        .or_else(|| qif::inflow(raw_source))
+        .or_else(|| plaid::inflow(raw_source))
+        .or_else(|| ynab::inflow(raw_source))
+        .or_else(|| gnucash::inflow(raw_source))
+        .unwrap_or(false)
+}
+
+fn created_on(created_at: Option<&str>) -> &str {
+    created_at
+        .and_then(|created_at| created_at.get(..10))
+        .unwrap_or(EPOCH)
+}
+
+/// `currency` as a commodity beancount takes, matching
+/// `[A-Z][A-Z0-9'._-]{0,22}[A-Z0-9]`: uppercased, without other characters,
+/// with an `X` before a code that does not start with a letter or is one
+/// character long, and cut to 24 characters, e.g. a Solana mint address.
+/// Quoted for ledger unless it is all letters.
+fn commodity(format: LedgerFormat, currency: &str) -> String {
+    let mut code = currency
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "'._-".contains(*c))
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if !code.starts_with(|c: char| c.is_ascii_alphabetic()) || code.len() == 1 {
+        code.insert(0, 'X');
+    }
+    code.truncate(MAX_COMMODITY_CHARS);
+    let code = code.trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+    match format {
+        _ if code.len() < 2 => "UNKNOWN".to_string(),
+        LedgerFormat::Ledger if !code.chars().all(|c| c.is_ascii_alphabetic()) => {
+            format!("\"{code}\"")
+        }
+        _ => code.to_string(),
+    }
+}
+
+/// `value` with two decimals, or up to eight for crypto amounts.
+fn number(value: f64) -> String {
+    let precise = format!("{value:.8}");
+    let trimmed = precise.trim_end_matches('0');
+    let decimals = trimmed
+        .split_once('.')
+        .map_or(0, |(_, decimals)| decimals.len());
+    if decimals < 2 {
+        format!("{value:.2}")
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// `text` escaped for a beancount string.
+fn quoted(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
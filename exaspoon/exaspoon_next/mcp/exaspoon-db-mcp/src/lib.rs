@@ -1,7 +1,70 @@
 //! ExaSpoon MCP server library.
 
+pub mod auth;
+pub mod batch;
+pub mod builder;
+pub mod call_log;
+pub mod cancellation;
+pub mod circuit;
+pub mod cli;
+pub mod concurrency;
 pub mod config;
+pub mod config_file;
+pub mod confirmation;
+pub mod correlation;
+pub mod daemon;
+pub mod demo;
+pub mod elicitation;
 pub mod embedding;
+pub mod exchange;
+pub mod export;
+pub mod fx;
+pub mod gnucash;
+pub mod gocardless;
+#[cfg(feature = "http")]
+pub mod health;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod i18n;
+pub mod ledger;
+pub mod log_file;
+pub mod log_format;
+#[cfg(feature = "memory-backend")]
+pub mod memory;
+pub mod metrics;
+pub mod migrations;
 pub mod models;
+pub mod ofx;
+pub mod onchain;
+pub mod origin;
+pub mod plaid;
+#[cfg(feature = "supabase")]
+pub mod postgrest;
+pub mod prices;
+pub mod progress;
+pub mod qif;
+pub mod rate_limit;
+#[cfg(feature = "realtime")]
+pub mod realtime;
+pub mod receipt;
+pub mod redaction;
+pub mod retry;
 pub mod server;
+pub mod session;
+pub mod sheets;
+pub mod shutdown;
+pub mod socket;
+pub mod stdio;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod statement;
 pub mod supabase;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod timeout;
+pub mod tool_names;
+pub mod validation;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+mod xml;
+pub mod ynab;
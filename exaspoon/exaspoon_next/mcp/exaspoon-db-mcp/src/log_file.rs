@@ -0,0 +1,121 @@
+//! A log file that rotates by size, for deployments where the client that
+//! spawned the server over stdio discards its stderr.
+
+use crate::config::LogFileConfig;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Appends to `path` until the next write would take it past `max_bytes`,
+/// then renames it to `path.1`, shifting older files up to `path.N` for the
+/// `retained` most recent, and starts a new one.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    retained: usize,
+    current: Mutex<Current>,
+}
+
+#[derive(Debug)]
+struct Current {
+    file: File,
+    written: u64,
+}
+
+/// Writes one formatted event to a [`RotatingFile`].
+#[derive(Debug)]
+pub struct RotatingFileWriter<'a> {
+    log: &'a RotatingFile,
+}
+
+impl RotatingFile {
+    /// Opens the log file of `config`, appending to what an earlier run left.
+    pub fn open(config: &LogFileConfig) -> Result<Self> {
+        if let Some(dir) = config
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create log directory {}", dir.display()))?;
+        }
+        let file = append(&config.path)?;
+        let written = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(Self {
+            path: config.path.clone(),
+            max_bytes: config.max_bytes,
+            retained: config.retained,
+            current: Mutex::new(Current { file, written }),
+        })
+    }
+
+    fn write_event(&self, buf: &[u8]) -> io::Result<usize> {
+        let mut current = self.current.lock().unwrap_or_else(|err| err.into_inner());
+        if current.written > 0 && current.written + buf.len() as u64 > self.max_bytes {
+            current.file.flush()?;
+            self.rotate()?;
+            current.file = append(&self.path).map_err(io::Error::other)?;
+            current.written = 0;
+        }
+        current.file.write_all(buf)?;
+        current.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    /// Shifts `path.N-1` to `path.N` down to `path` to `path.1`, dropping the
+    /// oldest, or removes `path` when no files are retained.
+    fn rotate(&self) -> io::Result<()> {
+        if self.retained == 0 {
+            return fs::remove_file(&self.path);
+        }
+        let _ = fs::remove_file(self.rotated(self.retained));
+        for index in (1..self.retained).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        name.into()
+    }
+}
+
+fn append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open log file {}", path.display()))
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = RotatingFileWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RotatingFileWriter { log: self }
+    }
+}
+
+impl Write for RotatingFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.log.write_event(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut current = self
+            .log
+            .current
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        current.file.flush()
+    }
+}
@@ -1,76 +1,264 @@
-mod config;
-mod embedding;
-mod models;
-mod server;
-mod supabase;
-
-use crate::{
-    config::AppConfig,
-    embedding::{Embedder, EmbeddingService},
+use exaspoon_db_mcp::{
+    builder::{self, ExaspoonServerBuilder},
+    cli::{self, Cli, Command},
+    config::{AppConfig, TracingConfig, Transport},
+    config_file::ConfigFile,
+    daemon::{self, PidFile},
+    embedding::{Embedder, EmbedderFactory},
+    export::ExportFormat,
+    log_file::RotatingFile,
+    log_format,
+    models::TransactionFilters,
     server::ExaspoonDbServer,
-    supabase::{Database, SupabaseGateway},
+    supabase::Database,
 };
-use anyhow::Result;
-use rmcp::{transport::stdio, ServiceExt};
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
-use std::time::Instant;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let start_time = Instant::now();
-    
-    // Load environment variables
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // Load environment variables, then fill in those still unset from the
+    // configuration file. Setting variables is only sound while no other
+    // thread runs, so this happens before the runtime starts its workers.
     dotenvy::dotenv().ok();
+    let config_file = ConfigFile::find(cli.config.as_deref())?;
+    let overridden = config_file.as_ref().map(ConfigFile::apply).unwrap_or_default();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("failed to start the runtime")?
+        .block_on(start(&cli, config_file.as_ref(), overridden))
+}
+
+async fn start(cli: &Cli, config_file: Option<&ConfigFile>, overridden: Vec<&str>) -> Result<()> {
+    let command = cli.command();
     
     // Initialize basic logging first
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("exaspoon_db_mcp=info"));
     
-    tracing_subscriber::registry()
+    let tracing_config = TracingConfig::from_env()?;
+    let format = tracing_config.log_format;
+    let log_file = tracing_config.log_file.as_ref().map(RotatingFile::open).transpose()?;
+    let registry = tracing_subscriber::registry()
         .with(env_filter)
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_writer(std::io::stderr)
-                .with_ansi(false)
-        )
-        .init();
+        .with(log_format::layer(format, std::io::stderr))
+        .with(log_file.map(|file| log_format::layer(format, file)));
+
+    // Spans go to an OpenTelemetry collector as well when one is configured.
+    // The exporter flushes what is still queued when it drops at exit.
+    #[cfg(feature = "otel")]
+    let _otlp = {
+        let otlp = exaspoon_db_mcp::telemetry::OtlpExporter::from_config(&tracing_config)?;
+        registry.with(otlp.as_ref().map(|otlp| otlp.layer())).init();
+        if let Some(endpoint) = &tracing_config.otlp_endpoint {
+            info!("Exporting spans to {}", endpoint);
+        }
+        otlp
+    };
+    #[cfg(not(feature = "otel"))]
+    {
+        if tracing_config.otlp_endpoint.is_some() {
+            bail!("OTEL_EXPORTER_OTLP_ENDPOINT requires building with the `otel` feature");
+        }
+        registry.init();
+    }
+    if let Some(file) = config_file {
+        info!("Configuration file: {}", file.path.display());
+        if !overridden.is_empty() {
+            info!("Overridden by the environment: {:?}", overridden);
+        }
+        for key in &file.unknown {
+            warn!("Ignored unknown setting `{}` in {}", key, file.path.display());
+        }
+    }
+    
+    if let Command::Migrate { print } = command {
+        return cli::migrate(print).await;
+    }
     
     // Load and validate configuration
     info!("Loading configuration");
     let config = AppConfig::from_env()?;
     info!("Configuration loaded successfully");
-    info!("Supabase URL: {}", &config.supabase_url[..config.supabase_url.find('.').unwrap_or(config.supabase_url.len())]);
+    info!("Database backend: {}", config.database_backend);
+    info!("Embedding provider: {}", config.embedding_provider);
     info!("Embedding model: {}", config.embedding_model);
     info!("Log level: {}", config.log_level);
     
     info!("Starting Exaspoon DB MCP Server");
     
     // Initialize services
-    info!("Initializing Supabase gateway");
-    let supabase: Arc<dyn Database> = Arc::new(SupabaseGateway::new(&config)?);
-    info!("Supabase gateway initialized");
+    info!("Initializing {} database", config.database_backend);
+    let database = builder::open_database(&config)?;
+    info!("Database initialized");
     
     info!("Initializing embedding service");
-    let embedder: Arc<dyn Embedder> = Arc::new(EmbeddingService::new(
-        &config.openai_api_key,
-        config.openai_base_url.as_deref(),
-        &config.embedding_model,
-    )?);
+    let embedder: Arc<dyn Embedder> = EmbedderFactory::default().build(&config)?;
     info!("Embedding service initialized");
     
+    if command == Command::Check {
+        return cli::check(&config, &ExaspoonDbServer::new(database, embedder)).await;
+    }
+    if command == Command::Verify {
+        return cli::verify(&config, &ExaspoonDbServer::new(database, embedder)).await;
+    }
+    
     // Start the MCP server
     info!("Starting MCP server");
-    let service = ExaspoonDbServer::new(supabase, embedder)
-        .serve(stdio())
-        .await?;
-    
-    let startup_time = start_time.elapsed();
-    info!("Server started successfully in {:?}", startup_time);
-    
-    info!("Waiting for MCP connections");
-    service.waiting().await?;
-    
+    let server = ExaspoonServerBuilder::new(database.clone(), embedder)
+        .with_config(&config)?
+        .with_service_notifications()
+        .build();
+    let Command::Serve { daemon } = command else {
+        return run(command, server.handler(), database.as_ref(), cli.yes).await;
+    };
+    if daemon {
+        if config.transport == Transport::Stdio {
+            bail!("serve --daemon needs a network transport; set TRANSPORT");
+        }
+        daemon::ignore_hangup()?;
+    }
+    let _pid_file = config.pid_file.as_deref().map(PidFile::create).transpose()?;
+    if let Some(path) = &config.pid_file {
+        info!("PID file: {}", path.display());
+    }
+    if config.supabase_realtime {
+        forward_realtime_changes(&config, server.handler().clone())?;
+    }
+    if let Some(address) = config.health_address {
+        serve_health(&config, address, server.handler().clone()).await?;
+    }
+    server.serve().await
+}
+
+/// Serves `/healthz` and `/readyz` at `HEALTH_ADDRESS` alongside the
+/// transport, for as long as the process runs.
+#[cfg(feature = "http")]
+async fn serve_health(
+    config: &AppConfig,
+    address: SocketAddr,
+    server: ExaspoonDbServer,
+) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .with_context(|| format!("failed to listen on {address}"))?;
+    let interval = config.health_probe_interval;
+    tokio::spawn(async move {
+        if let Err(err) = exaspoon_db_mcp::health::serve(server, listener, interval).await {
+            tracing::error!("{:#}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+async fn serve_health(
+    _config: &AppConfig,
+    _address: SocketAddr,
+    _server: ExaspoonDbServer,
+) -> Result<()> {
+    bail!("HEALTH_ADDRESS requires building with the `http` feature")
+}
+
+/// Runs one of the subcommands that do a task and exit.
+async fn run(
+    command: Command,
+    server: &ExaspoonDbServer,
+    database: &dyn Database,
+    confirmed: bool,
+) -> Result<()> {
+    match command {
+        Command::Seed { months, dry_run } => {
+            let outcome = cli::seed(server, months, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&outcome)?);
+        }
+        Command::Import { file, dry_run } => {
+            let imported = if file == Path::new("-") {
+                cli::import(server, io::stdin().lock(), dry_run).await?
+            } else {
+                let lines = File::open(&file)
+                    .with_context(|| format!("failed to open {}", file.display()))?;
+                cli::import(server, BufReader::new(lines), dry_run).await?
+            };
+            let verb = if dry_run { "Would import" } else { "Imported" };
+            println!("{verb} {imported} transactions");
+        }
+        Command::Export {
+            output,
+            format,
+            from,
+            to,
+            account_id,
+            category_id,
+        } => {
+            let filters = TransactionFilters {
+                from,
+                to,
+                account_id,
+                category_id,
+                direction: None,
+            };
+            let format = format.unwrap_or_else(|| {
+                output
+                    .as_deref()
+                    .map_or(ExportFormat::Jsonl, ExportFormat::for_path)
+            });
+            let exported = match output {
+                Some(path) => {
+                    let out = File::create(&path)
+                        .with_context(|| format!("failed to create {}", path.display()))?;
+                    cli::export(database, &filters, format, BufWriter::new(out)).await?
+                }
+                None => {
+                    let out = BufWriter::new(io::stdout());
+                    cli::export(database, &filters, format, out).await?
+                }
+            };
+            info!("Exported {} transactions", exported);
+        }
+        Command::Reembed { kind, batch_size } => {
+            for (table, embedded) in cli::reembed(server, kind, batch_size, confirmed).await? {
+                println!("{table}: {embedded} embedded");
+            }
+        }
+        Command::Serve { .. } | Command::Check | Command::Verify | Command::Migrate { .. } => {
+            unreachable!("{command:?} is run by main")
+        }
+    }
     Ok(())
 }
+
+/// Notifies subscribers of the transactions resource whenever Supabase
+/// Realtime reports a change to the transactions table.
+#[cfg(feature = "realtime")]
+fn forward_realtime_changes(config: &AppConfig, server: ExaspoonDbServer) -> Result<()> {
+    use exaspoon_db_mcp::{realtime::RealtimeListener, server::TRANSACTIONS_RESOURCE_URI};
+
+    if config.database_backend != "supabase" {
+        bail!("SUPABASE_REALTIME requires DATABASE_BACKEND=supabase");
+    }
+    info!("Forwarding Supabase Realtime changes to {}", TRANSACTIONS_RESOURCE_URI);
+    let mut changes = RealtimeListener::from_config(config).spawn();
+    tokio::spawn(async move {
+        while let Some(change) = changes.recv().await {
+            info!("Transaction {:?} {:?}", change.record_id, change.kind);
+            server.notify_resource_updated(TRANSACTIONS_RESOURCE_URI).await;
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "realtime"))]
+fn forward_realtime_changes(_config: &AppConfig, _server: ExaspoonDbServer) -> Result<()> {
+    bail!("SUPABASE_REALTIME requires building with the `realtime` feature")
+}
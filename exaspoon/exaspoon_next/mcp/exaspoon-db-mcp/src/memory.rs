@@ -0,0 +1,782 @@
+use crate::{
+    batch::MissingRow,
+    embedding::cosine_similarity,
+    models::{
+        Account, AggregateSpendingInput, AuditEvent, BankLink, Categorized, Category, CategoryKind,
+        CategoryMatch, CreateTransactionInput, EmbeddingIssue, ListAccountsInput, ListAuditEventsInput,
+        ListTransactionsInput, RecordKind,
+        SpendingBucket, SpendingGroupBy, SpendingPeriod, Transaction, TransactionFilters,
+        TransactionMatch, TransactionTextMatch, UpsertAccountInput, UpsertCategoryInput,
+    },
+    supabase::{
+        embedded_table, embedding_issue, page_limit, resolve_limit, search_terms, Database,
+        DEFAULT_AUDIT_PAGE, DEFAULT_TEXT_SEARCH_LIMIT, DEFAULT_TRANSACTION_PAGE,
+    },
+};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tracing::{debug, info, instrument};
+
+/// A process-local backend that needs no credentials. Everything is lost on
+/// exit, which makes it suitable for demos and for developing agents against
+/// the tool surface.
+#[derive(Default)]
+pub struct MemoryDatabase {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    next_id: u64,
+    transactions: Vec<(Transaction, Option<Vec<f32>>)>,
+    categories: Vec<(Category, Option<Vec<f32>>)>,
+    accounts: Vec<Account>,
+    /// When each soft-deleted row was deleted, by id. Ids are unique across
+    /// kinds.
+    deleted: HashMap<String, DateTime<Utc>>,
+    audit_log: Vec<AuditEvent>,
+    tool_calls: Vec<AuditEvent>,
+    /// By account id.
+    bank_links: HashMap<String, BankLink>,
+    /// By account id.
+    account_metadata: HashMap<String, Map<String, Value>>,
+}
+
+impl MemoryState {
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}-{}", self.next_id)
+    }
+
+    fn is_live(&self, id: &str) -> bool {
+        !self.deleted.contains_key(id)
+    }
+
+    fn contains(&self, kind: RecordKind, id: &str) -> bool {
+        match kind {
+            RecordKind::Transaction => self.transactions.iter().any(|(row, _)| row.id == id),
+            RecordKind::Category => self.categories.iter().any(|(row, _)| row.id == id),
+            RecordKind::Account => self.accounts.iter().any(|row| row.id == id),
+        }
+    }
+
+    /// Drops the rows of `kind` with these ids, whether or not they are
+    /// deleted.
+    fn remove(&mut self, kind: RecordKind, ids: &[String]) {
+        for id in ids {
+            self.deleted.remove(id);
+        }
+        match kind {
+            RecordKind::Transaction => self
+                .transactions
+                .retain(|(transaction, _)| !ids.contains(&transaction.id)),
+            RecordKind::Category => {
+                self.categories
+                    .retain(|(category, _)| !ids.contains(&category.id));
+                // Removing a category leaves its transactions uncategorized.
+                for (transaction, _) in &mut self.transactions {
+                    if transaction
+                        .category_id
+                        .as_ref()
+                        .is_some_and(|id| ids.contains(id))
+                    {
+                        transaction.category_id = None;
+                    }
+                }
+            }
+            RecordKind::Account => {
+                self.accounts.retain(|account| !ids.contains(&account.id));
+                // Like the Postgres foreign key, an account takes its
+                // transactions with it.
+                self.transactions
+                    .retain(|(transaction, _)| !ids.contains(&transaction.account_id));
+            }
+        }
+    }
+}
+
+impl MemoryDatabase {
+    pub fn new() -> Self {
+        info!("Initializing in-memory database");
+        Self::default()
+    }
+
+    /// Tool calls recorded by the call log, oldest first.
+    pub fn tool_calls(&self) -> Result<Vec<AuditEvent>> {
+        Ok(self.state()?.tool_calls.clone())
+    }
+
+    fn state(&self) -> Result<std::sync::MutexGuard<'_, MemoryState>> {
+        self.state
+            .lock()
+            .map_err(|_| anyhow!("in-memory database lock poisoned"))
+    }
+}
+
+#[async_trait]
+impl Database for MemoryDatabase {
+    #[instrument(skip(self, input), fields(account_id = %input.account_id, amount = %input.amount))]
+    async fn insert_transaction(
+        &self,
+        input: &CreateTransactionInput,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<Transaction> {
+        let mut state = self.state()?;
+        if !state.accounts.iter().any(|account| account.id == input.account_id) {
+            return Err(anyhow!("accounts record {} was not found", input.account_id));
+        }
+
+        let transaction = Transaction {
+            id: state.next_id("txn"),
+            account_id: input.account_id.clone(),
+            amount: input.amount,
+            currency: input.currency.clone(),
+            direction: input.direction,
+            occurred_at: input.occurred_at.clone(),
+            description: input.description.clone(),
+            raw_source: input.raw_source.clone(),
+            category_id: None,
+            created_at: None,
+        };
+        state.transactions.push((transaction.clone(), embedding));
+        debug!("Stored transaction {}", transaction.id);
+
+        Ok(transaction)
+    }
+
+    #[instrument(skip(self, input), fields(category_name = %input.name, kind = ?input.kind))]
+    async fn upsert_category(
+        &self,
+        input: &UpsertCategoryInput,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<Category> {
+        let mut state = self.state()?;
+        let kind = input.kind.unwrap_or(CategoryKind::Expense);
+        let description = input
+            .description
+            .clone()
+            .unwrap_or_else(|| input.name.clone());
+
+        if let Some((category, stored)) = state
+            .categories
+            .iter_mut()
+            .find(|(category, _)| category.name == input.name)
+        {
+            category.kind = kind;
+            category.description = Some(description);
+            *stored = embedding;
+            let category = category.clone();
+            state.deleted.remove(&category.id);
+            return Ok(category);
+        }
+
+        let category = Category {
+            id: state.next_id("cat"),
+            name: input.name.clone(),
+            kind,
+            description: Some(description),
+            created_at: None,
+        };
+        state.categories.push((category.clone(), embedding));
+        debug!("Stored category {}", category.id);
+
+        Ok(category)
+    }
+
+    #[instrument(skip(self, input), fields(account_name = %input.name, account_type = %input.r#type))]
+    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Account> {
+        let mut state = self.state()?;
+
+        if let Some(account) = state
+            .accounts
+            .iter_mut()
+            .find(|account| account.name == input.name && account.r#type == input.r#type)
+        {
+            account.currency = input.currency.clone();
+            account.network = input.network.clone();
+            account.institution = input.institution.clone();
+            let account = account.clone();
+            state.deleted.remove(&account.id);
+            return Ok(account);
+        }
+
+        let account = Account {
+            id: state.next_id("acct"),
+            name: input.name.clone(),
+            r#type: input.r#type,
+            currency: input.currency.clone(),
+            network: input.network.clone(),
+            institution: input.institution.clone(),
+            created_at: None,
+        };
+        state.accounts.push(account.clone());
+        debug!("Stored account {}", account.id);
+
+        Ok(account)
+    }
+
+    #[instrument(skip(self), fields(id = %id, category_id = ?category_id))]
+    async fn categorize_transaction(
+        &self,
+        id: &str,
+        category_id: Option<&str>,
+    ) -> Result<Option<Categorized>> {
+        let mut state = self.state()?;
+        if let Some(category_id) = category_id {
+            if !state.is_live(category_id) || !state.contains(RecordKind::Category, category_id) {
+                return Err(MissingRow(format!("no live category with id {category_id}")).into());
+            }
+        }
+        if !state.is_live(id) {
+            return Ok(None);
+        }
+        let Some((transaction, _)) = state.transactions.iter_mut().find(|(row, _)| row.id == id)
+        else {
+            return Ok(None);
+        };
+        let previous_category_id =
+            std::mem::replace(&mut transaction.category_id, category_id.map(str::to_string));
+        debug!("Filed transaction {} under {:?}", id, category_id);
+
+        Ok(Some(Categorized {
+            transaction: transaction.clone(),
+            previous_category_id,
+        }))
+    }
+
+    #[instrument(skip(self, params), fields(account_type = ?params.r#type, search = ?params.search))]
+    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Account>> {
+        let state = self.state()?;
+        let mut result = matching_accounts(&state, params);
+        result.sort_by(|left, right| left.name.cmp(&right.name));
+
+        let offset = params.offset.unwrap_or(0) as usize;
+        let limit = page_limit(params.limit, None).map_or(usize::MAX, |limit| limit as usize);
+        Ok(result.into_iter().skip(offset).take(limit).collect())
+    }
+
+    #[instrument(skip(self, params), fields(account_type = ?params.r#type, search = ?params.search))]
+    async fn count_accounts(&self, params: &ListAccountsInput) -> Result<u64> {
+        let state = self.state()?;
+        Ok(matching_accounts(&state, params).len() as u64)
+    }
+
+    #[instrument(skip(self, input), fields(limit = ?input.limit, offset = ?input.offset))]
+    async fn list_transactions(&self, input: &ListTransactionsInput) -> Result<Vec<Transaction>> {
+        let state = self.state()?;
+        let mut result = matching_transactions(&state, &input.filters)?
+            .into_iter()
+            .map(|(transaction, _)| transaction.clone())
+            .collect::<Vec<_>>();
+        result.sort_by(|left, right| {
+            right
+                .occurred_at
+                .cmp(&left.occurred_at)
+                .then_with(|| right.id.cmp(&left.id))
+        });
+
+        let offset = input.offset.unwrap_or(0) as usize;
+        let limit = page_limit(input.limit, Some(DEFAULT_TRANSACTION_PAGE))
+            .map_or(usize::MAX, |limit| limit as usize);
+        Ok(result.into_iter().skip(offset).take(limit).collect())
+    }
+
+    #[instrument(skip(self, filters))]
+    async fn largest_transactions(
+        &self,
+        filters: &TransactionFilters,
+        limit: u32,
+    ) -> Result<Vec<Transaction>> {
+        let state = self.state()?;
+        let mut result = matching_transactions(&state, filters)?
+            .into_iter()
+            .map(|(transaction, _)| transaction.clone())
+            .collect::<Vec<_>>();
+        result.sort_by(|left, right| {
+            right
+                .amount
+                .total_cmp(&left.amount)
+                .then_with(|| right.occurred_at.cmp(&left.occurred_at))
+                .then_with(|| right.id.cmp(&left.id))
+        });
+        result.truncate(limit as usize);
+        Ok(result)
+    }
+
+    #[instrument(skip(self, filters))]
+    async fn count_transactions(&self, filters: &TransactionFilters) -> Result<u64> {
+        let state = self.state()?;
+        Ok(matching_transactions(&state, filters)?.len() as u64)
+    }
+
+    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
+    async fn search_similar_transactions(
+        &self,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionMatch>> {
+        let state = self.state()?;
+        Ok(rank(
+            &state.transactions,
+            |row| state.is_live(&row.id),
+            &embedding,
+            limit,
+        )
+            .into_iter()
+            .map(|(transaction, similarity)| TransactionMatch {
+                transaction,
+                similarity: Some(similarity),
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
+    async fn search_similar_categories(
+        &self,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<CategoryMatch>> {
+        let state = self.state()?;
+        Ok(rank(
+            &state.categories,
+            |row| state.is_live(&row.id),
+            &embedding,
+            limit,
+        )
+            .into_iter()
+            .map(|(category, similarity)| CategoryMatch {
+                category,
+                similarity: Some(similarity),
+            })
+            .collect())
+    }
+
+    async fn list_category_names(&self, search: &str, limit: u32) -> Result<Vec<String>> {
+        let state = self.state()?;
+        let needle = search.trim().to_lowercase();
+        let mut names = state
+            .categories
+            .iter()
+            .map(|(category, _)| category)
+            .filter(|category| state.is_live(&category.id))
+            .filter(|category| category.name.to_lowercase().contains(&needle))
+            .map(|category| category.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.truncate(limit as usize);
+        Ok(names)
+    }
+
+    async fn list_categories(&self) -> Result<Vec<Category>> {
+        let state = self.state()?;
+        let mut categories = state
+            .categories
+            .iter()
+            .map(|(category, _)| category)
+            .filter(|category| state.is_live(&category.id))
+            .cloned()
+            .collect::<Vec<_>>();
+        categories.sort_by(|left, right| left.name.cmp(&right.name));
+        Ok(categories)
+    }
+
+    async fn search_transactions_text(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionTextMatch>> {
+        let state = self.state()?;
+        let terms = search_terms(query);
+        let mut result = state
+            .transactions
+            .iter()
+            .map(|(transaction, _)| transaction)
+            .filter(|transaction| state.is_live(&transaction.id))
+            .filter(|transaction| {
+                let text = format!(
+                    "{} {}",
+                    transaction.description.as_deref().unwrap_or_default(),
+                    transaction.raw_source.as_deref().unwrap_or_default()
+                )
+                .to_lowercase();
+                terms.iter().all(|term| text.contains(term.as_str()))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        result.sort_by(|left, right| {
+            right
+                .occurred_at
+                .cmp(&left.occurred_at)
+                .then_with(|| right.id.cmp(&left.id))
+        });
+
+        let limit = page_limit(limit, Some(DEFAULT_TEXT_SEARCH_LIMIT))
+            .map_or(usize::MAX, |limit| limit as usize);
+        Ok(result
+            .into_iter()
+            .take(limit)
+            .map(|transaction| TransactionTextMatch {
+                transaction,
+                rank: None,
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self), fields(group_by = ?input.group_by, period = ?input.period))]
+    async fn aggregate_spending(
+        &self,
+        input: &AggregateSpendingInput,
+    ) -> Result<Vec<SpendingBucket>> {
+        let state = self.state()?;
+        let mut groups: BTreeMap<(Option<String>, Option<String>, String), (f64, u64)> =
+            BTreeMap::new();
+        for (transaction, occurred_at) in matching_transactions(&state, &input.filters)? {
+            let key = match input.group_by {
+                SpendingGroupBy::Category => transaction.category_id.clone(),
+                SpendingGroupBy::Account => Some(transaction.account_id.clone()),
+                SpendingGroupBy::Direction => Some(transaction.direction.as_ref().to_string()),
+            };
+            let period_start = input
+                .period
+                .map(|period| period_start(period, occurred_at.naive_utc().date()));
+            let entry = groups
+                .entry((key, period_start, transaction.currency.clone()))
+                .or_default();
+            entry.0 += transaction.amount;
+            entry.1 += 1;
+        }
+
+        let mut buckets = groups
+            .into_iter()
+            .map(|((key, period_start, currency), (total, count))| SpendingBucket {
+                key,
+                period_start,
+                currency,
+                total,
+                count,
+            })
+            .collect::<Vec<_>>();
+        buckets.sort_by(|left, right| {
+            left.period_start
+                .cmp(&right.period_start)
+                .then(right.total.total_cmp(&left.total))
+        });
+        debug!("Aggregated spending into {} buckets", buckets.len());
+        Ok(buckets)
+    }
+
+    #[instrument(skip(self, events), fields(count = events.len()))]
+    async fn record_audit_events(&self, events: &[AuditEvent]) -> Result<()> {
+        self.state()?.audit_log.extend_from_slice(events);
+        Ok(())
+    }
+
+    #[instrument(skip(self, calls), fields(count = calls.len()))]
+    async fn record_tool_calls(&self, calls: &[AuditEvent]) -> Result<()> {
+        self.state()?.tool_calls.extend_from_slice(calls);
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(tool = ?input.tool, record_id = ?input.record_id))]
+    async fn list_audit_events(&self, input: &ListAuditEventsInput) -> Result<Vec<AuditEvent>> {
+        let state = self.state()?;
+        // Newest first; events recorded in the same instant keep their
+        // reverse insertion order.
+        let mut result = state
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|event| input.tool.as_ref().is_none_or(|tool| *tool == event.tool))
+            .filter(|event| {
+                input
+                    .record_id
+                    .as_ref()
+                    .is_none_or(|id| Some(id) == event.record_id.as_ref())
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        result.sort_by(|left, right| right.occurred_at.cmp(&left.occurred_at));
+
+        let offset = input.offset.unwrap_or(0) as usize;
+        let limit = page_limit(input.limit, Some(DEFAULT_AUDIT_PAGE))
+            .map_or(usize::MAX, |limit| limit as usize);
+        Ok(result.into_iter().skip(offset).take(limit).collect())
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn soft_delete(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let mut state = self.state()?;
+        if !state.contains(kind, id) || !state.is_live(id) {
+            return Ok(false);
+        }
+        state.deleted.insert(id.to_string(), Utc::now());
+        debug!("Soft-deleted {} record {}", kind.table(), id);
+        Ok(true)
+    }
+
+    async fn record_exists(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let state = self.state()?;
+        Ok(state.contains(kind, id) && state.is_live(id))
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn delete_record(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let mut state = self.state()?;
+        if !state.contains(kind, id) {
+            return Ok(false);
+        }
+        state.remove(kind, &[id.to_string()]);
+        debug!("Deleted {} record {}", kind.table(), id);
+        Ok(true)
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn purge_deleted(&self, kind: RecordKind, deleted_before: Option<&str>) -> Result<u64> {
+        let deleted_before = deleted_before.map(parse_timestamp).transpose()?;
+        let mut state = self.state()?;
+        let purged = state
+            .deleted
+            .iter()
+            .filter(|(id, deleted_at)| {
+                state.contains(kind, id)
+                    && deleted_before.is_none_or(|before| **deleted_at < before)
+            })
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+
+        state.remove(kind, &purged);
+        debug!("Purged {} {} rows", purged.len(), kind.table());
+        Ok(purged.len() as u64)
+    }
+
+    async fn embedding_issues(
+        &self,
+        kind: RecordKind,
+        dimension: usize,
+        after: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<EmbeddingIssue>> {
+        embedded_table(kind)?;
+        let state = self.state()?;
+        let rows: Vec<(&String, Option<&str>, Option<&Vec<f32>>)> = match kind {
+            RecordKind::Transaction => state
+                .transactions
+                .iter()
+                .map(|(row, embedding)| (&row.id, row.description.as_deref(), embedding.as_ref()))
+                .collect(),
+            _ => state
+                .categories
+                .iter()
+                .map(|(row, embedding)| {
+                    let content = row
+                        .description
+                        .as_deref()
+                        .filter(|text| !text.trim().is_empty())
+                        .unwrap_or(&row.name);
+                    (&row.id, Some(content), embedding.as_ref())
+                })
+                .collect(),
+        };
+        let mut issues = rows
+            .into_iter()
+            .filter(|(id, _, _)| after.is_none_or(|after| id.as_str() > after))
+            .filter_map(|(id, content, embedding)| {
+                let issue = embedding_issue(
+                    !state.is_live(id),
+                    content,
+                    embedding.map(Vec::len),
+                    dimension,
+                )?;
+                Some(EmbeddingIssue {
+                    id: id.clone(),
+                    issue,
+                    content: content.map(str::to_string),
+                })
+            })
+            .collect::<Vec<_>>();
+        issues.sort_by(|left, right| left.id.cmp(&right.id));
+        issues.truncate(limit as usize);
+        Ok(issues)
+    }
+
+    async fn set_embedding(
+        &self,
+        kind: RecordKind,
+        id: &str,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<()> {
+        let table = embedded_table(kind)?;
+        let mut state = self.state()?;
+        let stored = match kind {
+            RecordKind::Transaction => state
+                .transactions
+                .iter_mut()
+                .find(|(row, _)| row.id == id)
+                .map(|(_, stored)| stored),
+            _ => state
+                .categories
+                .iter_mut()
+                .find(|(row, _)| row.id == id)
+                .map(|(_, stored)| stored),
+        };
+        *stored.ok_or_else(|| anyhow!("no {table} row with id {id}"))? = embedding;
+        Ok(())
+    }
+
+    async fn bank_link(&self, account_id: &str) -> Result<Option<BankLink>> {
+        Ok(self.state()?.bank_links.get(account_id).cloned())
+    }
+
+    async fn account_metadata(&self, account_id: &str) -> Result<Map<String, Value>> {
+        let state = self.state()?;
+        if !state.contains(RecordKind::Account, account_id) || !state.is_live(account_id) {
+            return Err(anyhow!("accounts record {account_id} was not found"));
+        }
+        Ok(state
+            .account_metadata
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    #[instrument(skip(self, value))]
+    async fn set_account_metadata(&self, account_id: &str, key: &str, value: Value) -> Result<()> {
+        let mut state = self.state()?;
+        if !state.contains(RecordKind::Account, account_id) || !state.is_live(account_id) {
+            return Err(anyhow!("accounts record {account_id} was not found"));
+        }
+        state
+            .account_metadata
+            .entry(account_id.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn set_account_network(&self, account_id: &str, network: &str) -> Result<()> {
+        let mut state = self.state()?;
+        if !state.is_live(account_id) {
+            return Err(anyhow!("accounts record {account_id} was not found"));
+        }
+        let account = state
+            .accounts
+            .iter_mut()
+            .find(|account| account.id == account_id)
+            .ok_or_else(|| anyhow!("accounts record {account_id} was not found"))?;
+        account.network = Some(network.to_string());
+        Ok(())
+    }
+
+    #[instrument(skip(self, link), fields(account_id = %link.account_id))]
+    async fn save_bank_link(&self, link: &BankLink) -> Result<BankLink> {
+        let mut state = self.state()?;
+        if !state.accounts.iter().any(|account| account.id == link.account_id) {
+            return Err(anyhow!("accounts record {} was not found", link.account_id));
+        }
+        let link = BankLink {
+            updated_at: Some(Utc::now().to_rfc3339()),
+            ..link.clone()
+        };
+        state
+            .bank_links
+            .insert(link.account_id.clone(), link.clone());
+        Ok(link)
+    }
+}
+
+fn matching_accounts(state: &MemoryState, params: &ListAccountsInput) -> Vec<Account> {
+    let needle = params
+        .search
+        .as_ref()
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty());
+
+    state
+        .accounts
+        .iter()
+        .filter(|account| state.is_live(&account.id))
+        .filter(|account| params.r#type.is_none_or(|kind| account.r#type == kind))
+        .filter(|account| {
+            needle
+                .as_ref()
+                .is_none_or(|needle| account.name.to_lowercase().contains(needle))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the stored transactions matching `filters`, each paired with its
+/// parsed `occurred_at`.
+fn matching_transactions<'a>(
+    state: &'a MemoryState,
+    filters: &TransactionFilters,
+) -> Result<Vec<(&'a Transaction, DateTime<FixedOffset>)>> {
+    let from = filters.from.as_deref().map(parse_timestamp).transpose()?;
+    let to = filters.to.as_deref().map(parse_timestamp).transpose()?;
+
+    let mut result = Vec::new();
+    for (transaction, _) in &state.transactions {
+        if !state.is_live(&transaction.id) {
+            continue;
+        }
+        let occurred_at = parse_timestamp(&transaction.occurred_at)?;
+        if from.is_some_and(|from| occurred_at < from)
+            || to.is_some_and(|to| occurred_at >= to)
+            || filters
+                .account_id
+                .as_ref()
+                .is_some_and(|id| *id != transaction.account_id)
+            || filters
+                .category_id
+                .as_ref()
+                .is_some_and(|id| Some(id) != transaction.category_id.as_ref())
+            || filters
+                .direction
+                .is_some_and(|direction| direction != transaction.direction)
+        {
+            continue;
+        }
+        result.push((transaction, occurred_at));
+    }
+    Ok(result)
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value).with_context(|| format!("invalid timestamp {value:?}"))
+}
+
+/// Formats the first day of the bucket containing the UTC `date` as a
+/// timestamp.
+fn period_start(period: SpendingPeriod, date: NaiveDate) -> String {
+    let start = match period {
+        SpendingPeriod::Day => date,
+        SpendingPeriod::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+        SpendingPeriod::Month => date.with_day(1).unwrap_or(date),
+        SpendingPeriod::Year => date.with_ordinal(1).unwrap_or(date),
+    };
+    format!("{}T00:00:00Z", start.format("%Y-%m-%d"))
+}
+
+fn rank<T: Clone>(
+    rows: &[(T, Option<Vec<f32>>)],
+    is_live: impl Fn(&T) -> bool,
+    embedding: &[f32],
+    limit: Option<u32>,
+) -> Vec<(T, f64)> {
+    let mut scored = rows
+        .iter()
+        .filter(|(row, _)| is_live(row))
+        .filter_map(|(row, stored)| {
+            let similarity = cosine_similarity(embedding, stored.as_deref()?)?;
+            Some((row.clone(), similarity))
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|left, right| right.1.total_cmp(&left.1));
+    scored.truncate(resolve_limit(limit) as usize);
+    scored
+}
@@ -0,0 +1,131 @@
+//! Request counters and latency histograms kept in process, so slow tools,
+//! tables, RPCs and embedding calls show up in the `server_metrics` tool
+//! without parsing logs.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in milliseconds, of the latency histogram buckets. Slower
+/// requests land in a final unbounded bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 10] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+#[derive(Debug, Default, Clone)]
+struct OperationStats {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+    /// One count per entry of [`LATENCY_BUCKETS_MS`] plus the overflow bucket.
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+/// Collects one entry per operation label, e.g. `query accounts`,
+/// `RPC search_similar_transactions` or a tool name.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    operations: Mutex<BTreeMap<String, OperationStats>>,
+}
+
+/// Snapshot of one operation's requests.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct OperationMetrics {
+    pub operation: String,
+    pub calls: u64,
+    pub errors: u64,
+    /// `errors` as a share of `calls`, from 0 to 1.
+    pub error_rate: f64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    /// Upper bounds of the histogram buckets holding the 50th, 95th and 99th
+    /// percentiles; `None` when one falls in the unbounded bucket.
+    pub p50_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+    pub histogram: Vec<LatencyBucket>,
+}
+
+/// Requests that took at most `le_ms` milliseconds and more than the
+/// previous bucket's bound. The last bucket has no bound.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct LatencyBucket {
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one request, including any retries it took, and whether it
+    /// ended in an error.
+    pub fn record(&self, operation: &str, elapsed: Duration, succeeded: bool) {
+        let mut operations = self.operations.lock().unwrap();
+        let stats = operations.entry(operation.to_string()).or_default();
+        stats.calls += 1;
+        if !succeeded {
+            stats.errors += 1;
+        }
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| elapsed <= Duration::from_millis(*bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        stats.buckets[bucket] += 1;
+    }
+
+    /// Every operation seen so far, the one with the most total time first.
+    pub fn snapshot(&self) -> Vec<OperationMetrics> {
+        let operations = self.operations.lock().unwrap();
+        let mut snapshot = operations
+            .iter()
+            .map(|(operation, stats)| stats.summarize(operation))
+            .collect::<Vec<_>>();
+        snapshot.sort_by(|left, right| right.total_ms.total_cmp(&left.total_ms));
+        snapshot
+    }
+}
+
+impl OperationStats {
+    fn summarize(&self, operation: &str) -> OperationMetrics {
+        let bounds = LATENCY_BUCKETS_MS.iter().copied().map(Some).chain([None]);
+        let histogram = bounds
+            .zip(self.buckets)
+            .map(|(le_ms, count)| LatencyBucket { le_ms, count })
+            .collect::<Vec<_>>();
+        let percentile = |percent: u64| {
+            let rank = (self.calls * percent).div_ceil(100);
+            let mut seen = 0;
+            histogram
+                .iter()
+                .find(|bucket| {
+                    seen += bucket.count;
+                    seen >= rank
+                })
+                .and_then(|bucket| bucket.le_ms)
+        };
+
+        OperationMetrics {
+            operation: operation.to_string(),
+            calls: self.calls,
+            errors: self.errors,
+            error_rate: self.errors as f64 / self.calls.max(1) as f64,
+            total_ms: millis(self.total),
+            mean_ms: millis(self.total) / self.calls.max(1) as f64,
+            max_ms: millis(self.max),
+            p50_ms: percentile(50),
+            p95_ms: percentile(95),
+            p99_ms: percentile(99),
+            histogram,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
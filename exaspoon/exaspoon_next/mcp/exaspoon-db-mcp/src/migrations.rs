@@ -0,0 +1,220 @@
+//! The Postgres schema the Supabase backend expects, shipped as ordered SQL
+//! migrations embedded in the binary.
+
+/// A single schema migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// All migrations, in the order they must be applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "extensions",
+        sql: include_str!("../migrations/0001_extensions.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "core_schema",
+        sql: include_str!("../migrations/0002_core_schema.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "search_functions",
+        sql: include_str!("../migrations/0003_search_functions.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "aggregate_spending",
+        sql: include_str!("../migrations/0004_aggregate_spending.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "soft_delete",
+        sql: include_str!("../migrations/0005_soft_delete.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "audit_log",
+        sql: include_str!("../migrations/0006_audit_log.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "realtime",
+        sql: include_str!("../migrations/0007_realtime.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "text_search",
+        sql: include_str!("../migrations/0008_text_search.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "embedding_maintenance",
+        sql: include_str!("../migrations/0009_embedding_maintenance.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "audit_log_details",
+        sql: include_str!("../migrations/0010_audit_log_details.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "execute_batch",
+        sql: include_str!("../migrations/0011_execute_batch.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "bank_links",
+        sql: include_str!("../migrations/0012_bank_links.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "exchange_accounts",
+        sql: include_str!("../migrations/0013_exchange_accounts.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "audit_log_rls",
+        sql: include_str!("../migrations/0014_audit_log_rls.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "text_search_terms",
+        sql: include_str!("../migrations/0015_text_search_terms.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "tool_calls",
+        sql: include_str!("../migrations/0016_tool_calls.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "batch_account_check",
+        sql: include_str!("../migrations/0017_batch_account_check.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "bank_links_rls",
+        sql: include_str!("../migrations/0018_bank_links_rls.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "set_account_metadata",
+        sql: include_str!("../migrations/0019_set_account_metadata.sql"),
+    },
+];
+
+/// Table in the target schema that records which migrations have run.
+pub const MIGRATIONS_TABLE: &str = "exaspoon_migrations";
+
+/// Renders every migration as one script for pasting into the Supabase SQL
+/// editor. It does not record versions, so only run it on a fresh project.
+pub fn script(schema: &str) -> String {
+    let mut script = format!(
+        "create schema if not exists {schema};\nset search_path to {schema}, public, extensions;\n"
+    );
+    for migration in MIGRATIONS {
+        script.push_str(&format!(
+            "\n-- {:04}_{}\n{}",
+            migration.version, migration.name, migration.sql
+        ));
+    }
+    script
+}
+
+#[cfg(feature = "migrate")]
+pub use runner::run;
+
+#[cfg(feature = "migrate")]
+mod runner {
+    use super::{Migration, MIGRATIONS, MIGRATIONS_TABLE};
+    use anyhow::{Context, Result};
+    use postgres_native_tls::MakeTlsConnector;
+    use std::collections::HashSet;
+    use std::time::Instant;
+    use tracing::{error, info};
+
+    /// Applies every migration not yet recorded in `schema`, each in its own
+    /// transaction, and returns the versions that were applied.
+    pub async fn run(database_url: &str, schema: &str) -> Result<Vec<u32>> {
+        let start_time = Instant::now();
+        let tls = native_tls::TlsConnector::new().context("failed to build TLS connector")?;
+        let (mut client, connection) =
+            tokio_postgres::connect(database_url, MakeTlsConnector::new(tls))
+                .await
+                .context("failed to connect to Postgres")?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                error!("Postgres connection error: {}", err);
+            }
+        });
+
+        client
+            .batch_execute(&format!(
+                "create schema if not exists {schema};
+                 set search_path to {schema}, public, extensions;
+                 create table if not exists {MIGRATIONS_TABLE} (
+                   version     integer primary key,
+                   name        text not null,
+                   applied_at  timestamptz not null default now()
+                 );"
+            ))
+            .await
+            .context("failed to prepare migrations table")?;
+
+        let applied: HashSet<u32> = client
+            .query(&format!("select version from {MIGRATIONS_TABLE}"), &[])
+            .await
+            .context("failed to read applied migrations")?
+            .iter()
+            .map(|row| row.get::<_, i32>(0) as u32)
+            .collect();
+
+        let pending: Vec<&Migration> = MIGRATIONS
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .collect();
+        if pending.is_empty() {
+            info!("Schema {} is up to date", schema);
+            return Ok(Vec::new());
+        }
+
+        let mut versions = Vec::with_capacity(pending.len());
+        for migration in pending {
+            info!(
+                "Applying migration {:04}_{}",
+                migration.version, migration.name
+            );
+            let transaction = client.transaction().await?;
+            transaction
+                .batch_execute(migration.sql)
+                .await
+                .with_context(|| {
+                    format!(
+                        "migration {:04}_{} failed",
+                        migration.version, migration.name
+                    )
+                })?;
+            transaction
+                .execute(
+                    &format!("insert into {MIGRATIONS_TABLE} (version, name) values ($1, $2)"),
+                    &[&(migration.version as i32), &migration.name],
+                )
+                .await?;
+            transaction.commit().await?;
+            versions.push(migration.version);
+        }
+
+        info!(
+            "Applied {} migration(s) to schema {} in {:?}",
+            versions.len(),
+            schema,
+            start_time.elapsed()
+        );
+        Ok(versions)
+    }
+}
@@ -1,5 +1,7 @@
+use crate::metrics::OperationMetrics;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
@@ -43,6 +45,8 @@ impl CategoryKind {
 pub enum AccountType {
     Onchain,
     Offchain,
+    /// Held at a crypto exchange, such as Binance or Coinbase.
+    Exchange,
 }
 
 impl AccountType {
@@ -50,6 +54,7 @@ impl AccountType {
         match self {
             Self::Onchain => "onchain",
             Self::Offchain => "offchain",
+            Self::Exchange => "exchange",
         }
     }
 }
@@ -62,8 +67,14 @@ impl fmt::Display for AccountType {
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CreateTransactionInput {
+    /// Left empty, `create_transaction` asks the user to pick an account
+    /// when the client supports elicitation.
+    #[serde(default)]
     pub account_id: String,
     pub amount: f64,
+    /// ISO 4217 code. Left empty, `create_transaction` asks the user for it
+    /// when the client supports elicitation.
+    #[serde(default)]
     pub currency: String,
     pub direction: TransactionDirection,
     pub occurred_at: String,
@@ -73,6 +84,130 @@ pub struct CreateTransactionInput {
     pub raw_source: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CreateTransactionsInput {
+    pub transactions: Vec<CreateTransactionInput>,
+}
+
+/// Input of `import_ofx`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportOfxInput {
+    /// Account the statement belongs to. Left empty, the session's default
+    /// account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// Contents of the OFX or QFX file.
+    pub ofx: String,
+    /// ISO 4217 code for statements that declare no `CURDEF`. Left empty,
+    /// the session's default currency is used.
+    #[serde(default)]
+    pub currency: String,
+}
+
+/// Input of `import_qif`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportQifInput {
+    /// Account the file belongs to. Left empty, the session's default
+    /// account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// Contents of the QIF file.
+    pub qif: String,
+    /// ISO 4217 code of the amounts, which QIF files do not state. Left
+    /// empty, the session's default currency is used.
+    #[serde(default)]
+    pub currency: String,
+    /// Read dates such as `05/01/2024` day first. Dates separated by dots
+    /// are always read day first.
+    #[serde(default)]
+    pub day_first: bool,
+    /// Lowest similarity at which a QIF category without an exact name
+    /// match is mapped to an existing one. Defaults to 0.5.
+    #[serde(default)]
+    pub min_similarity: Option<f64>,
+}
+
+/// Formats `import_bank_statement` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementFormat {
+    /// SWIFT MT940, text with `:61:` statement lines.
+    Mt940,
+    /// ISO 20022 CAMT.053 XML.
+    #[serde(rename = "camt053")]
+    Camt053,
+}
+
+impl StatementFormat {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Mt940 => "mt940",
+            Self::Camt053 => "camt053",
+        }
+    }
+}
+
+/// Input of `import_bank_statement`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportBankStatementInput {
+    /// Account the statement belongs to. Left empty, the session's default
+    /// account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// Contents of the MT940 or CAMT.053 file.
+    pub statement: String,
+    /// Told from the contents when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<StatementFormat>,
+    /// ISO 4217 code for statements that state none. Left empty, the
+    /// session's default currency is used.
+    #[serde(default)]
+    pub currency: String,
+}
+
+/// Input of `import_gnucash`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportGnuCashInput {
+    /// The book: the XML file GnuCash saves with "Compress files" turned
+    /// off, or a SQLite book encoded as base64.
+    pub book: String,
+}
+
+/// Input of `import_ynab`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ImportYnabInput {
+    /// The register CSV of YNAB's "Export budget". Left out, the budget is
+    /// read through the YNAB API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,
+    /// The plan CSV of the same export, whose categories are imported even
+    /// when no transaction is filed under them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    /// Budget to read through the API; the configured one when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_id: Option<String>,
+    /// ISO 4217 code of the register's amounts. Left empty, the session's
+    /// default currency is used; the API gives the budget's own.
+    #[serde(default)]
+    pub currency: String,
+    /// Read register dates such as `05/01/2024` day first. Dates separated
+    /// by dots are always read day first.
+    #[serde(default)]
+    pub day_first: bool,
+}
+
+/// Input of `import_json`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportJsonInput {
+    /// Transactions as `create_transaction` takes them. Each is checked
+    /// against that schema on its own, so one that fails is reported instead
+    /// of failing the rest. Those without `account_id` or `currency` take the
+    /// session's defaults. At most 5000.
+    #[schemars(with = "Vec<CreateTransactionInput>")]
+    pub records: Vec<serde_json::Value>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SearchSimilarInput {
     pub query: String,
@@ -80,6 +215,24 @@ pub struct SearchSimilarInput {
     pub limit: Option<u32>,
 }
 
+/// Keyword search over transaction descriptions and raw source text.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SearchTextInput {
+    /// Words or fragments to look for, e.g. `invoice 4812`. Every word must
+    /// appear.
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SeedDemoDataInput {
+    /// Calendar months of transactions to create, ending with the current
+    /// one. Defaults to 3, at most 12.
+    #[serde(default)]
+    pub months: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UpsertCategoryInput {
     pub name: String,
@@ -95,6 +248,11 @@ pub struct ListAccountsInput {
     pub r#type: Option<AccountType>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub search: Option<String>,
+    /// Page size; all matching accounts are returned when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
 }
 
 impl Default for ListAccountsInput {
@@ -102,18 +260,1695 @@ impl Default for ListAccountsInput {
         Self {
             r#type: None,
             search: None,
+            limit: None,
+            offset: None,
         }
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ListTransactionsInput {
+    #[serde(default)]
+    pub filters: TransactionFilters,
+    /// Page size, 100 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UpsertAccountInput {
     pub name: String,
     #[serde(rename = "type")]
     pub r#type: AccountType,
+    /// ISO 4217 code. Left empty, the session's default currency is used.
+    #[serde(default)]
     pub currency: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub institution: Option<String>,
 }
+
+/// A transaction row as stored in Supabase.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct Transaction {
+    pub id: String,
+    pub account_id: String,
+    pub amount: f64,
+    pub currency: String,
+    pub direction: TransactionDirection,
+    pub occurred_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// An account row as stored in Supabase.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub r#type: AccountType,
+    pub currency: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub institution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// A category row as stored in Supabase.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+    pub kind: CategoryKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// A transaction returned by semantic search, with its similarity score.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct TransactionMatch {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+}
+
+/// A transaction returned by keyword search. Only the Supabase backend ranks
+/// matches; the offline backends list them newest first without a rank.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransactionTextMatch {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f64>,
+}
+
+/// A category returned by semantic search, with its similarity score.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct CategoryMatch {
+    #[serde(flatten)]
+    pub category: Category,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+}
+
+/// The kinds of rows that can be soft-deleted and purged.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Transaction,
+    Category,
+    Account,
+}
+
+impl RecordKind {
+    /// Every kind, in an order where purging never trips a foreign key.
+    pub const ALL: [RecordKind; 3] = [Self::Transaction, Self::Category, Self::Account];
+
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Transaction => "transaction",
+            Self::Category => "category",
+            Self::Account => "account",
+        }
+    }
+
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::Transaction => "transactions",
+            Self::Category => "categories",
+            Self::Account => "accounts",
+        }
+    }
+}
+
+/// What is wrong with a row's embedding.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingIssueKind {
+    /// A live row with text to embed but no embedding.
+    Missing,
+    /// An embedding whose length differs from the current model's.
+    Dimension,
+    /// An embedding on a deleted row or on a row with no text to embed.
+    Orphaned,
+}
+
+/// A row whose embedding needs attention.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct EmbeddingIssue {
+    pub id: String,
+    pub issue: EmbeddingIssueKind,
+    /// Text the row's embedding is generated from, if it has any.
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingMaintenanceAction {
+    /// Count the rows with each issue without changing anything.
+    #[default]
+    Report,
+    /// Embed rows that are missing an embedding or have one of the wrong size.
+    Backfill,
+    /// Remove embeddings of the wrong size and orphaned embeddings.
+    Clear,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddingMaintenanceInput {
+    /// `transaction` or `category`; both when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<RecordKind>,
+    #[serde(default)]
+    pub action: EmbeddingMaintenanceAction,
+    /// Rows `backfill` or `clear` change per call, and rows `report` reads
+    /// per page. Defaults to 100, at most 500.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<u32>,
+    /// Expected embedding length. Defaults to the length the configured
+    /// embedding provider returns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<usize>,
+}
+
+/// Input of a mutating tool. A dry run validates it and computes embeddings
+/// as usual, then returns what would be written instead of writing it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DryRun<T> {
+    #[serde(flatten)]
+    pub input: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<bool>,
+}
+
+impl<T> From<T> for DryRun<T> {
+    fn from(input: T) -> Self {
+        Self {
+            input,
+            dry_run: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteRecordInput {
+    pub id: String,
+}
+
+/// Files a transaction under a category.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CategorizeInput {
+    pub transaction_id: String,
+    pub category_id: String,
+}
+
+/// One operation of `execute_batch`, named by `op`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    UpsertAccount(UpsertAccountInput),
+    CreateTransactions(CreateTransactionsInput),
+    Categorize(CategorizeInput),
+}
+
+impl BatchOperation {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::UpsertAccount(_) => "upsert_account",
+            Self::CreateTransactions(_) => "create_transactions",
+            Self::Categorize(_) => "categorize",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExecuteBatchInput {
+    /// Applied in order, all or none. Where an operation takes an id, `$N`
+    /// stands for the row written by operation `N`, counting from 0, and
+    /// `$N.I` for the `I`th transaction created by operation `N`.
+    pub operations: Vec<BatchOperation>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PurgeDeletedInput {
+    /// Purge only this kind of row; every kind when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<RecordKind>,
+    /// Purge only rows deleted before this RFC 3339 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_before: Option<String>,
+}
+
+/// One mutation performed through the server, as kept in `audit_log`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct AuditEvent {
+    /// The tool that made the change.
+    pub tool: String,
+    /// SHA-256 of the tool input's JSON, so repeated calls can be matched
+    /// without storing financial details twice.
+    pub input_hash: String,
+    /// The row the change affected, when there is a single one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_id: Option<String>,
+    pub occurred_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// The redacted arguments and result of a call recorded by the call log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ListAuditEventsInput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_id: Option<String>,
+    /// Page size, 100 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+/// What `aggregate_spending` groups transactions by, besides currency.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpendingGroupBy {
+    Category,
+    Account,
+    Direction,
+}
+
+impl SpendingGroupBy {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Category => "category",
+            Self::Account => "account",
+            Self::Direction => "direction",
+        }
+    }
+}
+
+/// Calendar bucket for `aggregate_spending`. Weeks start on Monday.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpendingPeriod {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl SpendingPeriod {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        }
+    }
+}
+
+/// Restricts which transactions a list, count or aggregation includes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct TransactionFilters {
+    /// Inclusive lower bound on `occurred_at` (RFC 3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Exclusive upper bound on `occurred_at` (RFC 3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<TransactionDirection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AggregateSpendingInput {
+    pub group_by: SpendingGroupBy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<SpendingPeriod>,
+    #[serde(default)]
+    pub filters: TransactionFilters,
+}
+
+/// Input of `summarize_period`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SummarizePeriodInput {
+    /// Month to summarize as `YYYY-MM`; the current month when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month: Option<String>,
+}
+
+/// One group of an `aggregate_spending` result. Amounts in different
+/// currencies are never summed together.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct SpendingBucket {
+    /// The category id, account id or direction, depending on `group_by`.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Start of the calendar bucket when a period was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period_start: Option<String>,
+    pub currency: String,
+    pub total: f64,
+    pub count: u64,
+}
+
+/// Buckets of several currencies converted into the base currency and summed
+/// per key and period, once an FX provider is configured.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct BaseTotals {
+    /// The base currency every bucket is in, `BASE_CURRENCY`.
+    pub currency: String,
+    /// Each converted at the rate of its period's first day, or without a
+    /// period at the rate of the day reported on, else the latest rate.
+    pub buckets: Vec<SpendingBucket>,
+    /// Currencies the FX provider has no rate for, such as most crypto,
+    /// whose buckets are left out of `buckets`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unconverted: Vec<String>,
+}
+
+/// Where `convert_currency` and the base currency totals take their rates
+/// from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FxProvider {
+    /// The European Central Bank's daily reference rates, through the
+    /// Frankfurter API; no key needed.
+    Ecb,
+    /// exchangerate.host, with an access key.
+    ExchangerateHost,
+}
+
+impl FxProvider {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Ecb => "ecb",
+            Self::ExchangerateHost => "exchangerate_host",
+        }
+    }
+}
+
+impl fmt::Display for FxProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Input of `convert_currency`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConvertCurrencyInput {
+    pub amount: f64,
+    /// ISO 4217 code of the currency `amount` is in, e.g. `EUR`.
+    pub from: String,
+    /// ISO 4217 code to convert into. Left empty, the base currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Day whose rate to use, as `YYYY-MM-DD`; the latest rate when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+/// Result of `convert_currency`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConvertCurrencyOutput {
+    pub amount: f64,
+    pub from: String,
+    pub to: String,
+    /// `amount` in `to`.
+    pub converted: f64,
+    /// Units of `to` per unit of `from`.
+    pub rate: f64,
+    /// The day the rate is for, which for a weekend or holiday may be the
+    /// business day before.
+    pub date: String,
+    pub provider: FxProvider,
+}
+
+/// Input of `get_net_worth`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct GetNetWorthInput {
+    /// Day to value the accounts at the end of, as `YYYY-MM-DD`, at that
+    /// day's rates; today at the latest rates when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+/// Result of `get_net_worth`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NetWorthOutput {
+    /// The day valued, when one was asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Income less expenses per account id and currency. Transfers are left
+    /// out, as they move money between the user's own accounts.
+    pub balances: Vec<SpendingBucket>,
+    /// `balances` converted into the base currency per account, with an FX
+    /// provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_totals: Option<BaseTotals>,
+    /// The sum of `base_totals`, leaving out its `unconverted` currencies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub net_worth: Option<f64>,
+}
+
+/// Input of `get_asset_price`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GetAssetPriceInput {
+    /// Ticker symbol such as `BTC` or `SOL`, or a CoinGecko coin id such as
+    /// `bitcoin`.
+    pub asset: String,
+    /// ISO 4217 code to price the asset in. Left empty, the base currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vs_currency: Option<String>,
+    /// Day whose closing price to use, as `YYYY-MM-DD`; the latest price when
+    /// omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Units held, to value them as well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+}
+
+/// Result of `get_asset_price`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AssetPriceOutput {
+    /// The asset as asked for, uppercased.
+    pub asset: String,
+    /// The CoinGecko coin the asset was taken for.
+    pub coin_id: String,
+    pub vs_currency: String,
+    /// Units of `vs_currency` per unit of the asset.
+    pub price: f64,
+    /// When the price is from: the day asked for, or the time CoinGecko last
+    /// updated it as RFC 3339.
+    pub as_of: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+    /// `amount` in `vs_currency`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+}
+
+/// A receipt as the vision model read it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct Receipt {
+    #[serde(default)]
+    pub merchant: Option<String>,
+    /// Day of the purchase, as `YYYY-MM-DD`.
+    #[serde(default)]
+    pub date: Option<String>,
+    /// ISO 4217 code of the amounts, when the receipt shows one.
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// The amount paid, tax included.
+    #[serde(default)]
+    pub total: Option<f64>,
+    #[serde(default)]
+    pub tax: Option<f64>,
+    #[serde(default)]
+    pub items: Vec<ReceiptItem>,
+}
+
+/// A line of a receipt.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ReceiptItem {
+    pub description: String,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    /// The line's total, negative for a discount.
+    pub amount: f64,
+}
+
+/// Input of `parse_receipt`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ParseReceiptInput {
+    /// The receipt photo or scan: base64, a `data:` URL, or an `https://` URL
+    /// the model can fetch.
+    pub image: String,
+    /// MIME type of a base64 `image`; `image/jpeg` when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Account to record the purchase in. Left empty, the receipt is only
+    /// read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    /// Record one expense per line item, plus one for tax and other charges,
+    /// instead of one for the total. The expenses share the receipt's id in
+    /// their raw source.
+    #[serde(default)]
+    pub split: bool,
+}
+
+/// Result of `parse_receipt`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ParseReceiptOutput {
+    pub receipt: Receipt,
+    /// The model that read the receipt.
+    pub model: String,
+    /// The expenses recorded when an `account_id` was given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    /// The purchase was recorded in the account before, from an earlier
+    /// read of the same receipt, so nothing was recorded again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub already_recorded: bool,
+}
+
+/// Where `export_to_sheet` writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SheetDestination {
+    /// A tab of a Google Sheet, replaced by the export.
+    GoogleSheets,
+    /// A Notion database, which gets a page per row.
+    Notion,
+}
+
+impl SheetDestination {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::GoogleSheets => "google_sheets",
+            Self::Notion => "notion",
+        }
+    }
+}
+
+impl fmt::Display for SheetDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// What `export_to_sheet` writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SheetReport {
+    /// A row per transaction, newest first.
+    #[default]
+    Transactions,
+    /// A row per month, direction, category and currency with its total.
+    MonthlySummary,
+}
+
+impl SheetReport {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Transactions => "transactions",
+            Self::MonthlySummary => "monthly_summary",
+        }
+    }
+}
+
+/// Input of `export_to_sheet`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ExportToSheetInput {
+    /// Where to write; the only one configured when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<SheetDestination>,
+    #[serde(default)]
+    pub report: SheetReport,
+    /// Which transactions to export or summarize.
+    #[serde(default)]
+    pub filters: TransactionFilters,
+    /// Spreadsheet id, or Notion database id; the configured one when
+    /// omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Google Sheets tab to replace, added if missing; `Transactions` or
+    /// `Monthly summary` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sheet: Option<String>,
+}
+
+/// Result of `export_to_sheet`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportToSheetOutput {
+    pub destination: SheetDestination,
+    pub report: SheetReport,
+    /// The spreadsheet or Notion database written to.
+    pub target: String,
+    /// The Google Sheets tab written to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sheet: Option<String>,
+    /// Rows written, or on a dry run the rows that would be.
+    pub rows: usize,
+    /// More transactions matched than one export writes; the newest were
+    /// kept.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Link to what was written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Plain-text accounting format `export_ledger` writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerFormat {
+    /// Beancount, checked with `bean-check` and browsed with Fava.
+    #[default]
+    Beancount,
+    /// Ledger's journal format, which hledger reads as well.
+    Ledger,
+}
+
+impl LedgerFormat {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Beancount => "beancount",
+            Self::Ledger => "ledger",
+        }
+    }
+}
+
+/// Input of `export_ledger`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ExportLedgerInput {
+    #[serde(default)]
+    pub format: LedgerFormat,
+    /// Which transactions to write; every account and category is declared
+    /// either way.
+    #[serde(default)]
+    pub filters: TransactionFilters,
+}
+
+/// Result of `export_ledger`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportLedgerOutput {
+    pub format: LedgerFormat,
+    /// Transactions in the journal.
+    pub transactions: usize,
+    /// More transactions matched than one export writes; the newest were
+    /// written.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// The journal, oldest transaction first.
+    pub journal: String,
+}
+
+/// Input of `export_ynab`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ExportYnabInput {
+    /// Budget to write to; the configured one when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_id: Option<String>,
+    /// Category group the missing categories are added to, and added itself
+    /// unless the budget has it; `Exaspoon` when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// Whether `export_ynab` found an expense category in the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum YnabCategoryStatus {
+    /// The budget has a category of that name.
+    Existing,
+    /// Added to the budget, or on a dry run would be.
+    Added,
+}
+
+/// One expense category `export_ynab` looked up in the budget.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct YnabCategoryExport {
+    pub category: String,
+    /// The YNAB category of that name; none on a dry run for one to add.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ynab_category_id: Option<String>,
+    pub status: YnabCategoryStatus,
+}
+
+/// Result of `export_ynab`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExportYnabOutput {
+    pub budget_id: String,
+    /// Category group the missing categories were added to.
+    pub group: String,
+    /// By name.
+    pub categories: Vec<YnabCategoryExport>,
+}
+
+/// Calls an allowlisted Postgres function through PostgREST.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CallRpcInput {
+    /// Function name; must appear in the server's `RPC_ALLOWLIST`.
+    pub function: String,
+    /// Named arguments, passed to the function as a JSON object.
+    #[serde(default)]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Arguments of the `categorize_transaction` prompt. Prompt arguments arrive
+/// as strings.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CategorizeTransactionPromptArgs {
+    /// The bank statement line to categorize.
+    pub line: String,
+}
+
+/// Arguments of the `monthly_budget_review` prompt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct MonthlyBudgetReviewPromptArgs {
+    /// Month to review as `YYYY-MM`; the current month when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month: Option<String>,
+}
+
+/// Arguments of the `find_unusual_spending` prompt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct FindUnusualSpendingPromptArgs {
+    /// How many recent days to check, 30 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<String>,
+}
+
+/// The outcome of probing one dependency in `health_check`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct DependencyHealth {
+    pub name: String,
+    pub ok: bool,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A tool's structured result with the flags a writing tool may set.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Outcome<T> {
+    #[serde(flatten)]
+    pub output: T,
+    /// Nothing was written; the result shows what would have been.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// The client cancelled the call part way; the result holds only the
+    /// work done before it stopped.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+    /// Set on the preview of a call that needs confirmation: the token to
+    /// run it with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation: Option<Confirmation>,
+}
+
+/// Runs a previewed destructive call when passed back as
+/// `confirmation_token` with the same arguments.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Confirmation {
+    pub token: String,
+    pub expires_in_secs: u64,
+}
+
+/// A row a dry run would have written. Its embedding is summarized by its
+/// dimension rather than returned in full.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Planned<T> {
+    #[serde(flatten)]
+    pub row: T,
+    pub embedding_dimension: Option<usize>,
+}
+
+/// A written row, or under a dry run the row that would have been written.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Written<R, P> {
+    Row(R),
+    Planned(Planned<P>),
+}
+
+/// Result of `create_transaction`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransactionOutput {
+    pub transaction: Written<Transaction, CreateTransactionInput>,
+}
+
+/// Result of `create_transactions`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransactionsOutput {
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+}
+
+/// Why a statement import, bank, onchain or exchange sync left a transaction
+/// out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The transaction is already recorded: for OFX one with the same FITID,
+    /// or one that came earlier in the file; for MT940 and CAMT.053 one with
+    /// the same source text; for Plaid one with the same `transaction_id`;
+    /// for GoCardless one with the same transaction id, or the same entry;
+    /// onchain one with the same transaction hash and place in it; for an
+    /// exchange one with the same id at the exchange; for GnuCash one from
+    /// the same split; for YNAB one with the same YNAB id.
+    Duplicate,
+    /// The amount is zero, e.g. a balance note or a contract call moving no
+    /// ether.
+    ZeroAmount,
+    /// The bank has not booked the entry yet, the onchain transaction is
+    /// not in a block yet, or the exchange has not completed the deposit or
+    /// withdrawal yet.
+    Pending,
+    /// The onchain transaction was reverted, so only its fee moved, or the
+    /// exchange cancelled or rejected the deposit or withdrawal.
+    Failed,
+    /// The onchain transfer is of a token posing as another, such as a USDT
+    /// from a contract other than Tether's, or named like no real token.
+    Spam,
+}
+
+/// A statement transaction `import_ofx` left out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedTransaction {
+    pub fitid: String,
+    pub reason: SkipReason,
+}
+
+/// Result of `import_ofx`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportOfxOutput {
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedTransaction>,
+}
+
+/// How `import_qif` found the category for a QIF category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+    /// A category has the same name, or the same name as its last part,
+    /// ignoring case.
+    Name,
+    /// The most similar category is similar enough.
+    Similarity,
+}
+
+/// The existing category the transactions of one QIF category were filed
+/// under, if any.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QifCategoryMapping {
+    /// The category as the file names it, e.g. `Food:Groceries`.
+    pub qif_category: String,
+    /// Transactions in the file with this category.
+    pub transactions: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<Category>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_by: Option<MatchedBy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+}
+
+/// Result of `import_qif`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportQifOutput {
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub categories: Vec<QifCategoryMapping>,
+    /// Records of zero amount, which were left out.
+    pub skipped_zero_amount: usize,
+}
+
+/// A statement entry `import_bank_statement` left out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub booked_at: String,
+    /// Negative for money leaving the account.
+    pub amount: f64,
+    pub reason: SkipReason,
+}
+
+/// Result of `import_bank_statement`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportBankStatementOutput {
+    pub format: StatementFormat,
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// What `import_gnucash` imported a GnuCash account as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GnuCashImportedAs {
+    /// An asset, bank, cash, credit card or liability account.
+    Account,
+    /// An income or expense account.
+    Category,
+}
+
+/// The account or category one GnuCash account was imported as.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GnuCashAccountMapping {
+    /// The account's full name in the book, e.g. `Expenses:Auto:Fuel`.
+    pub gnucash_account: String,
+    pub imported_as: GnuCashImportedAs,
+    /// Name of the account or category, e.g. `Auto:Fuel`.
+    pub name: String,
+    /// Absent in a dry run for one that does not exist yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Whether it is new, rather than one of the same name that existed
+    /// before.
+    pub created: bool,
+    /// Transactions imported into the account or filed under the category.
+    pub transactions: usize,
+}
+
+/// Result of `import_gnucash`. Skipped splits are referred to by their
+/// GnuCash id, and in a dry run the transactions of an account not created
+/// yet by the GnuCash id of the account.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportGnuCashOutput {
+    pub accounts: Vec<GnuCashAccountMapping>,
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// Where `import_ynab` read a budget from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum YnabSource {
+    /// The CSV files of an export.
+    Export,
+    /// The YNAB API.
+    Api,
+}
+
+/// The account or category one YNAB account or category was imported as,
+/// under the same name.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct YnabMapping {
+    pub name: String,
+    /// Absent in a dry run for one that does not exist yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Whether it is new, rather than one of the same name that existed
+    /// before.
+    pub created: bool,
+    /// Transactions imported into the account or filed under the category.
+    pub transactions: usize,
+}
+
+/// Result of `import_ynab`. Skipped transactions are referred to by their
+/// YNAB id, and in a dry run the transactions of an account not created yet
+/// by the YNAB name of the account.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportYnabOutput {
+    pub source: YnabSource,
+    /// The budget read through the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_id: Option<String>,
+    pub accounts: Vec<YnabMapping>,
+    pub categories: Vec<YnabMapping>,
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// A record `import_json` left out, as it does not fit the transaction
+/// schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RejectedRecord {
+    /// Position of the record in `records`, counting from 0.
+    pub index: usize,
+    /// Path of the offending value, e.g. `records[2].amount`.
+    pub field: String,
+    pub reason: String,
+}
+
+/// Result of `import_json`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportJsonOutput {
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub rejected: Vec<RejectedRecord>,
+}
+
+/// The Plaid item an account's transactions are synced from, as kept in
+/// `bank_links`. One per account.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct BankLink {
+    pub account_id: String,
+    /// Plaid's id for the login at the bank.
+    pub item_id: String,
+    /// Grants access to the item; never returned by a tool.
+    pub access_token: String,
+    /// The account within the item to sync; every account of the item when
+    /// unset.
+    #[serde(default)]
+    pub plaid_account_id: Option<String>,
+    /// Where the last `/transactions/sync` left off; unset before the first
+    /// sync.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+impl fmt::Debug for BankLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BankLink")
+            .field("account_id", &self.account_id)
+            .field("item_id", &self.item_id)
+            .field("access_token", &"<redacted>")
+            .field("plaid_account_id", &self.plaid_account_id)
+            .field("cursor", &self.cursor)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
+/// Input of `link_bank_account`. Exactly one of `public_token` and
+/// `access_token` is given.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LinkBankAccountInput {
+    /// Account the bank's transactions are booked to. Left empty, the
+    /// session's default account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// The `public_token` Plaid Link returned, exchanged here for an access
+    /// token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_token: Option<String>,
+    /// The access token of an item exchanged elsewhere.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// Plaid's id of the account within the item to sync, for items that
+    /// hold several accounts. Every account of the item is synced when
+    /// omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plaid_account_id: Option<String>,
+}
+
+/// Result of `link_bank_account`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LinkBankAccountOutput {
+    pub account_id: String,
+    /// Unknown on a dry run given a public token, which is not exchanged as
+    /// it can be only once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plaid_account_id: Option<String>,
+    /// Whether an earlier link of the account was replaced, which restarts
+    /// syncing from the item's full history.
+    pub replaced: bool,
+}
+
+/// Input of `sync_bank_account`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncBankAccountInput {
+    /// Account whose linked bank account is synced. Left empty, the
+    /// session's default account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// ISO 4217 code for transactions Plaid reports no currency for. Left
+    /// empty, the session's default currency is used.
+    #[serde(default)]
+    pub currency: String,
+}
+
+/// A bank transaction `sync_bank_account` or `sync_openbanking` left out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedBankTransaction {
+    /// The bank's id of the transaction: Plaid's `transaction_id`, or the
+    /// `transactionId` or `internalTransactionId` GoCardless reports, or for
+    /// an entry with neither its booking date and amount.
+    pub transaction_id: String,
+    pub reason: SkipReason,
+}
+
+/// Result of `sync_bank_account`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncBankAccountOutput {
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedBankTransaction>,
+    /// Plaid ids of transactions the bank changed since they were synced,
+    /// e.g. once a pending one posted with another amount. They are left as
+    /// recorded.
+    pub modified: Vec<String>,
+    /// Plaid ids of transactions the bank withdrew since they were synced.
+    /// They are left as recorded.
+    pub removed: Vec<String>,
+}
+
+/// The GoCardless bank account an account's transactions are synced from,
+/// kept under `openbanking` in the account's `metadata`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct OpenBankingLink {
+    /// The requisition that authorized access to the bank.
+    pub requisition_id: String,
+    /// GoCardless's id of the bank, e.g. `REVOLUT_REVOGB21`.
+    pub institution_id: String,
+    /// GoCardless's id of the bank account.
+    pub openbanking_account_id: String,
+    /// The latest booking date a sync has read, as `YYYY-MM-DD`; unset
+    /// before the first sync.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_through: Option<String>,
+}
+
+/// Input of `link_openbanking_account`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LinkOpenBankingAccountInput {
+    /// Account the bank's transactions are booked to. Left empty, the
+    /// session's default account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// The GoCardless requisition the user authorized at the bank.
+    pub requisition_id: String,
+    /// GoCardless's id of the bank account to sync, for requisitions that
+    /// grant access to several. May be omitted when there is only one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openbanking_account_id: Option<String>,
+}
+
+/// Result of `link_openbanking_account`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LinkOpenBankingAccountOutput {
+    pub account_id: String,
+    pub link: OpenBankingLink,
+    /// Whether an earlier link of the account was replaced, which restarts
+    /// syncing from as far back as the bank allows.
+    pub replaced: bool,
+}
+
+/// Input of `sync_openbanking`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncOpenBankingInput {
+    /// Account whose linked bank account is synced. Left empty, the
+    /// session's default account is used.
+    #[serde(default)]
+    pub account_id: String,
+}
+
+/// Result of `sync_openbanking`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncOpenBankingOutput {
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedBankTransaction>,
+    /// Transactions the bank has not booked yet, read by a later sync.
+    pub pending: usize,
+    /// The latest booking date read, where the next sync picks up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_through: Option<String>,
+}
+
+/// Blockchains `sync_onchain` reads transfers from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Chain {
+    /// Ethereum mainnet, with ether and ERC-20 tokens.
+    Ethereum,
+    /// Bitcoin, by address or extended public key.
+    Bitcoin,
+    /// Solana mainnet, with SOL and SPL tokens.
+    Solana,
+}
+
+impl Chain {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Ethereum => "ethereum",
+            Self::Bitcoin => "bitcoin",
+            Self::Solana => "solana",
+        }
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// The address an onchain account's transfers are synced from, kept under
+/// `onchain` in the account's `metadata`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct OnchainLink {
+    pub chain: Chain,
+    /// The address, in lower case for Ethereum, or for Bitcoin an extended
+    /// public key (`xpub`, `ypub` or `zpub`) standing for a wallet's
+    /// addresses.
+    pub address: String,
+    /// The block, or on Solana the slot, the latest sync read through,
+    /// where the next one starts; unset before the first sync.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_block: Option<u64>,
+    /// On Solana, the newest transaction the latest sync read, which the
+    /// next one lists back to rather than to its slot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_signature: Option<String>,
+}
+
+/// Input of `sync_onchain`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncOnchainInput {
+    /// Onchain account the address's transfers are booked to. Left empty,
+    /// the session's default account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// The address to sync. Needed on an account's first sync and kept for
+    /// later ones; another address replaces the kept one and is synced from
+    /// its first transfer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+/// A transfer `sync_onchain` left out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedTransfer {
+    /// The transaction hash or id, followed for a token or internal transfer
+    /// by its place in the transaction, e.g. `0x5c50…:token:12`, and for the
+    /// network fee by `:fee`.
+    pub transfer_id: String,
+    pub reason: SkipReason,
+}
+
+/// Result of `sync_onchain`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncOnchainOutput {
+    pub account_id: String,
+    /// The address synced, with the block the next sync starts from.
+    pub link: OnchainLink,
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedTransfer>,
+    /// False when the address has more transfers than one sync reads;
+    /// syncing again continues after the last one read.
+    pub complete: bool,
+}
+
+/// Exchanges `sync_exchange` reads an account's movements from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Exchange {
+    /// Binance, with spot trades read by trading pair.
+    Binance,
+    /// Coinbase, with every wallet of the API key's user.
+    Coinbase,
+}
+
+impl Exchange {
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Binance => "binance",
+            Self::Coinbase => "coinbase",
+        }
+    }
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// The exchange an exchange account's movements are synced from, kept under
+/// `exchange` in the account's `metadata`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ExchangeLink {
+    pub exchange: Exchange,
+    /// Binance trading pairs whose trades are read, e.g. `BTCUSDT`, as
+    /// Binance lists trades by pair only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<String>,
+    /// Where the latest sync read each of the exchange's lists through, and
+    /// the next one goes on from: on Binance a time in milliseconds for
+    /// `deposits` and `withdrawals` and a trade id for `trades:` and the
+    /// pair, on Coinbase a transaction id per wallet.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cursors: BTreeMap<String, String>,
+}
+
+/// Input of `sync_exchange`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncExchangeInput {
+    /// Exchange account the movements are booked to. Left empty, the
+    /// session's default account is used.
+    #[serde(default)]
+    pub account_id: String,
+    /// The exchange to sync from. Needed on an account's first sync unless
+    /// the account's `institution` names it, and kept for later ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange: Option<Exchange>,
+    /// Binance trading pairs whose trades to read, e.g. `["BTCUSDT"]`,
+    /// replacing the kept ones. Coinbase lists trades with each wallet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbols: Option<Vec<String>>,
+}
+
+/// A movement `sync_exchange` left out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedMovement {
+    /// The exchange's id of the deposit, withdrawal or trade, e.g.
+    /// `withdrawal:7213fea8…`, followed for a trade's sides by `:sold` or
+    /// `:bought` and for a fee by `:fee`.
+    pub movement_id: String,
+    pub reason: SkipReason,
+}
+
+/// Result of `sync_exchange`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SyncExchangeOutput {
+    pub account_id: String,
+    /// The exchange synced, with where the next sync goes on from.
+    pub link: ExchangeLink,
+    pub transactions: Vec<Written<Transaction, CreateTransactionInput>>,
+    pub skipped: Vec<SkippedMovement>,
+}
+
+/// Result of `search_similar_transactions`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransactionMatchesOutput {
+    pub matches: Vec<TransactionMatch>,
+}
+
+/// Result of `search_transactions_text`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TextMatchesOutput {
+    pub matches: Vec<TransactionTextMatch>,
+}
+
+/// Result of `upsert_category`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CategoryOutput {
+    pub category: Written<Category, UpsertCategoryInput>,
+}
+
+/// Result of `search_similar_categories`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CategoryMatchesOutput {
+    pub matches: Vec<CategoryMatch>,
+}
+
+/// Result of `list_accounts`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AccountsOutput {
+    pub accounts: Vec<Account>,
+    /// Accounts matching the filters across all pages.
+    pub total: u64,
+}
+
+/// Result of `list_transactions`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TransactionPageOutput {
+    pub transactions: Vec<Transaction>,
+    /// Transactions matching the filters across all pages.
+    pub total: u64,
+    pub limit: Option<u32>,
+    pub offset: u32,
+}
+
+/// Result of `upsert_account`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AccountOutput {
+    pub account: Written<Account, UpsertAccountInput>,
+}
+
+/// What one `execute_batch` operation wrote, named by `op`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchStepResult {
+    UpsertAccount { account: Account },
+    CreateTransactions { transactions: Vec<Transaction> },
+    Categorize { transaction: Transaction },
+}
+
+/// Result of `execute_batch`: one step per operation, in order.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExecuteBatchOutput {
+    pub steps: Vec<Written<BatchStepResult, BatchOperation>>,
+}
+
+/// A transaction just filed under a category, and the category it had
+/// before.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Categorized {
+    pub transaction: Transaction,
+    pub previous_category_id: Option<String>,
+}
+
+/// Result of the `delete_*` tools.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeletedOutput {
+    pub deleted: DeletedRecord,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeletedRecord {
+    pub kind: RecordKind,
+    pub id: String,
+}
+
+/// Result of `purge_deleted`: rows removed per table, or under a dry run the
+/// tables that would be purged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PurgeOutput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged: Option<BTreeMap<String, u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_before: Option<String>,
+}
+
+/// Result of `embedding_maintenance`, per table.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddingMaintenanceOutput {
+    pub action: EmbeddingMaintenanceAction,
+    pub dimension: usize,
+    pub tables: BTreeMap<String, TableMaintenance>,
+}
+
+/// What `embedding_maintenance` found or changed in one table.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum TableMaintenance {
+    Report(EmbeddingReport),
+    Repair(EmbeddingRepair),
+}
+
+/// Embedding issues of one table, counted by `report`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct EmbeddingReport {
+    pub missing: u64,
+    pub dimension: u64,
+    pub orphaned: u64,
+    /// A few ids of the rows with each issue.
+    pub sample_ids: EmbeddingIssueIds,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct EmbeddingIssueIds {
+    pub missing: Vec<String>,
+    pub dimension: Vec<String>,
+    pub orphaned: Vec<String>,
+}
+
+impl EmbeddingReport {
+    /// Counts `issue`, keeping its id while fewer than `samples` are kept.
+    pub fn record(&mut self, issue: &EmbeddingIssue, samples: usize) {
+        let (count, ids) = match issue.issue {
+            EmbeddingIssueKind::Missing => (&mut self.missing, &mut self.sample_ids.missing),
+            EmbeddingIssueKind::Dimension => (&mut self.dimension, &mut self.sample_ids.dimension),
+            EmbeddingIssueKind::Orphaned => (&mut self.orphaned, &mut self.sample_ids.orphaned),
+        };
+        *count += 1;
+        if ids.len() < samples {
+            ids.push(issue.id.clone());
+        }
+    }
+}
+
+/// Embeddings of one table re-embedded by `backfill` or removed by `clear`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct EmbeddingRepair {
+    pub updated: u32,
+    /// No rows with the repaired issues are left.
+    pub complete: bool,
+}
+
+/// Result of `list_audit_events`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEventsOutput {
+    pub events: Vec<AuditEvent>,
+}
+
+/// Result of `aggregate_spending`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SpendingOutput {
+    pub buckets: Vec<SpendingBucket>,
+    /// `buckets` converted into the base currency, with an FX provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_totals: Option<BaseTotals>,
+}
+
+/// Result of `summarize_period`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PeriodSummaryOutput {
+    /// The month summarized, as `YYYY-MM`.
+    pub month: String,
+    /// Income, expense and transfer totals per currency.
+    pub totals: Vec<SpendingBucket>,
+    pub expenses_by_category: Vec<SpendingBucket>,
+    /// `totals` converted into the base currency at the rates of the
+    /// month's first day, with an FX provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_totals: Option<BaseTotals>,
+    /// `expenses_by_category` converted into the base currency like
+    /// `base_totals`, with an FX provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_expenses_by_category: Option<BaseTotals>,
+    /// The month's largest expenses, largest first.
+    pub notable_transactions: Vec<Transaction>,
+    /// The summary written by the client's model; `None` when the client
+    /// could not or would not sample.
+    pub narrative: Option<String>,
+    /// The model that wrote `narrative`.
+    pub model: Option<String>,
+}
+
+/// Result of `seed_demo_data`: rows written, or under a dry run rows that
+/// would be.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SeedDemoDataOutput {
+    pub accounts: usize,
+    pub categories: usize,
+    pub transactions: usize,
+    /// The demo accounts already had transactions, so none were added.
+    pub skipped_transactions: bool,
+}
+
+/// Result of `call_rpc`: the function's result, or under a dry run the
+/// parameters it would have been called with.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CallRpcOutput {
+    pub function: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Result of `ping`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PingOutput {
+    pub uptime_secs: u64,
+    pub server_name: String,
+    pub server_version: String,
+    /// The newest MCP revision the server speaks.
+    pub protocol_version: String,
+    /// What the server was configured with; absent when it was built without
+    /// a configuration, as in tests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub providers: Option<Providers>,
+}
+
+/// The backends a server was started with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Providers {
+    /// `supabase`, `sqlite` or `memory`.
+    pub database_backend: String,
+    pub embedding_provider: String,
+    pub embedding_model: String,
+}
+
+/// Result of `health_check`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HealthCheckOutput {
+    pub status: HealthStatus,
+    pub checks: Vec<DependencyHealth>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// Every dependency answered.
+    Ok,
+    /// At least one dependency failed its probe.
+    Degraded,
+}
+
+/// What tools fall back to for the rest of the session when a call leaves a
+/// field out, set with `set_session_defaults`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionDefaults {
+    /// Account of new transactions that name none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    /// ISO 4217 code of new transactions and accounts that name none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// UTC offset such as `+03:00` or `UTC`, or IANA timezone such as
+    /// `Europe/Berlin` with its daylight saving time, applied to dates and
+    /// times given without an offset, e.g. `2024-01-31` or
+    /// `2024-01-31T09:30:00`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+/// Result of `set_session_defaults`: the defaults now in effect.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SessionDefaultsOutput {
+    pub defaults: SessionDefaults,
+}
+
+/// Result of `describe_capabilities`, one entry per mounted tool by name.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CapabilitiesOutput {
+    pub tools: Vec<ToolCapability>,
+}
+
+/// What a tool is for and how to call it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ToolCapability {
+    pub name: String,
+    /// The tool's description.
+    pub purpose: String,
+    /// The tool group it is mounted with, e.g. `core`.
+    pub group: String,
+    /// The tool can write; it accepts `dry_run` to preview the write.
+    pub writes: bool,
+    /// Tables, RPCs and settings the tool relies on, e.g. `rpc
+    /// aggregate_spending (migration 0004_aggregate_spending)`.
+    pub prerequisites: Vec<String>,
+    /// Arguments of a call, using ids from the database where it has rows.
+    /// Examples of destructive tools are dry runs.
+    pub example: serde_json::Value,
+}
+
+/// Result of `server_metrics`. Each list has the operation with the most
+/// total time first.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ServerMetricsOutput {
+    pub uptime_secs: u64,
+    /// Client sessions open on a network transport; zero over stdio.
+    pub sessions: usize,
+    /// Calls per tool, timed from the request to the response.
+    pub tools: Vec<OperationMetrics>,
+    /// Calls to the embedding provider, `embed` for stored text and
+    /// `embed_query` for searches.
+    pub embeddings: Vec<OperationMetrics>,
+    /// Database requests per table and RPC.
+    pub operations: Vec<OperationMetrics>,
+}
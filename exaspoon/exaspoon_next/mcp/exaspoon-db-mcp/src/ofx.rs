@@ -0,0 +1,248 @@
+//! Bank and credit card statements in OFX, the format US and Canadian banks
+//! offer for download, and QFX, Quicken's variant of it. Both the SGML of
+//! OFX 1.x, whose value elements have no closing tags, and the XML of OFX
+//! 2.x are read. Only the statement transactions are kept; each carries the
+//! bank's FITID, which stays the same across downloads and so tells which
+//! transactions were imported before.
+
+use crate::models::{CreateTransactionInput, TransactionDirection};
+use anyhow::{bail, Context, Result};
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+
+/// One `<STMTTRN>` of a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementTransaction {
+    /// The bank's id for the transaction, unique within the account.
+    pub fitid: String,
+    /// `TRNTYPE`, e.g. `DEBIT`, `CREDIT` or `XFER`.
+    pub kind: String,
+    /// `DTPOSTED` as an RFC 3339 timestamp in UTC.
+    pub posted_at: String,
+    /// `TRNAMT`, negative for money leaving the account.
+    pub amount: f64,
+    pub name: Option<String>,
+    pub memo: Option<String>,
+    /// `CURDEF` of the statement the transaction is listed in, which its
+    /// amount is in.
+    pub currency: Option<String>,
+    /// The `<STMTTRN>` element as it appeared in the file, on one line.
+    pub raw: String,
+}
+
+impl StatementTransaction {
+    /// The transaction as `create_transaction` takes it, with `raw` as its
+    /// raw source so that [`fitid`] finds it again. `currency` is used when
+    /// the statement declares none.
+    pub fn to_input(&self, account_id: &str, currency: &str) -> CreateTransactionInput {
+        let direction = if self.kind.eq_ignore_ascii_case("XFER") {
+            TransactionDirection::Transfer
+        } else if self.amount < 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        };
+        let description = match (&self.name, &self.memo) {
+            (Some(name), Some(memo)) if name != memo => Some(format!("{name} - {memo}")),
+            (Some(name), _) => Some(name.clone()),
+            (None, memo) => memo.clone(),
+        };
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount.abs(),
+            currency: self
+                .currency
+                .clone()
+                .unwrap_or_else(|| currency.to_string()),
+            direction,
+            occurred_at: self.posted_at.clone(),
+            description,
+            raw_source: Some(self.raw.clone()),
+        }
+    }
+}
+
+/// The statement transactions of an OFX or QFX document, in file order.
+pub fn parse(document: &str) -> Result<Vec<StatementTransaction>> {
+    let Some(start) = find_tag(document, "OFX") else {
+        bail!("not an OFX document: no <OFX> element");
+    };
+    let mut transactions = Vec::new();
+    let mut currency = None;
+    let mut current: Option<(usize, Vec<(String, String)>)> = None;
+    for tag in tags(&document[start..]) {
+        let offset = start + tag.offset;
+        match current.as_mut() {
+            None if tag.name == "STMTTRN" && !tag.closing => current = Some((offset, Vec::new())),
+            None if tag.name == "CURDEF" && !tag.closing => {
+                currency = Some(tag.value.to_uppercase()).filter(|code| !code.is_empty());
+            }
+            Some(_) if tag.name == "STMTTRN" && tag.closing => {
+                let (begin, fields) = current.take().unwrap_or_default();
+                let raw = one_line(&document[begin..offset + tag.length]);
+                let number = transactions.len() + 1;
+                let transaction = transaction(fields, currency.clone(), raw)
+                    .with_context(|| format!("statement transaction {number}"))?;
+                transactions.push(transaction);
+            }
+            Some((_, fields)) if !tag.closing => fields.push((tag.name, tag.value)),
+            _ => {}
+        }
+    }
+    if current.is_some() {
+        bail!(
+            "statement transaction {} is not closed",
+            transactions.len() + 1
+        );
+    }
+    Ok(transactions)
+}
+
+/// The FITID of a transaction imported from a statement, read back from its
+/// raw source. `None` for transactions that came from anywhere else.
+pub fn fitid(raw_source: &str) -> Option<String> {
+    let opening = raw_source.get(.."<STMTTRN>".len());
+    if !opening.is_some_and(|opening| opening.eq_ignore_ascii_case("<STMTTRN>")) {
+        return None;
+    }
+    tags(raw_source)
+        .find(|tag| tag.name == "FITID" && !tag.closing)
+        .map(|tag| tag.value)
+        .filter(|fitid| !fitid.is_empty())
+}
+
+/// Whether a transaction imported from a statement brought money into its
+/// account, read back from the TRNAMT of its raw source. `None` for
+/// transactions that came from anywhere else.
+pub fn inflow(raw_source: &str) -> Option<bool> {
+    fitid(raw_source)?;
+    tags(raw_source)
+        .find(|tag| tag.name == "TRNAMT" && !tag.closing)
+        .and_then(|tag| tag.value.replace(',', ".").parse::<f64>().ok())
+        .map(|amount| amount > 0.0)
+}
+
+fn transaction(
+    fields: Vec<(String, String)>,
+    currency: Option<String>,
+    raw: String,
+) -> Result<StatementTransaction> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, value)| field == name && !value.is_empty())
+            .map(|(_, value)| value.clone())
+    };
+    let required = |name: &str| field(name).with_context(|| format!("no {name}"));
+    let fitid = required("FITID")?;
+    let amount = required("TRNAMT")?;
+    let amount = amount
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|amount| amount.is_finite())
+        .with_context(|| format!("TRNAMT {amount:?} is not a number"))?;
+    Ok(StatementTransaction {
+        kind: field("TRNTYPE").unwrap_or_else(|| "OTHER".to_string()),
+        posted_at: parse_date(&required("DTPOSTED")?)?,
+        amount,
+        name: field("NAME"),
+        memo: field("MEMO"),
+        currency,
+        raw,
+        fitid,
+    })
+}
+
+/// An OFX date, `YYYYMMDD[HHMMSS[.XXX]][[offset:TZ]]`, as RFC 3339 in UTC.
+/// Dates without an offset are in UTC.
+pub fn parse_date(value: &str) -> Result<String> {
+    let invalid = || format!("{value:?} is not an OFX date");
+    let (local, zone) = match value.split_once('[') {
+        Some((local, zone)) => (local, Some(zone.trim_end_matches(']'))),
+        None => (value, None),
+    };
+    let digits = local.split('.').next().unwrap_or_default();
+    let local = match digits.len() {
+        8 => NaiveDate::parse_from_str(digits, "%Y%m%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0)),
+        12 => NaiveDateTime::parse_from_str(&format!("{digits}00"), "%Y%m%d%H%M%S").ok(),
+        14 => NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M%S").ok(),
+        _ => None,
+    }
+    .with_context(invalid)?;
+    let hours = match zone {
+        Some(zone) => {
+            let hours = zone.split(':').next().unwrap_or_default();
+            hours.parse::<f64>().ok().with_context(invalid)?
+        }
+        None => 0.0,
+    };
+    let offset = FixedOffset::east_opt((hours * 3600.0).round() as i32).with_context(invalid)?;
+    let posted = offset
+        .from_local_datetime(&local)
+        .single()
+        .with_context(invalid)?;
+    Ok(posted
+        .with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+struct Tag {
+    offset: usize,
+    length: usize,
+    name: String,
+    closing: bool,
+    /// Text up to the next tag, unescaped and trimmed.
+    value: String,
+}
+
+/// The tags of `document` in order, skipping processing instructions and
+/// comments.
+fn tags(document: &str) -> impl Iterator<Item = Tag> + '_ {
+    let mut rest = 0;
+    std::iter::from_fn(move || loop {
+        let open = rest + document[rest..].find('<')?;
+        let close = open + document[open..].find('>')?;
+        let text_end = document[close..]
+            .find('<')
+            .map_or(document.len(), |next| close + next);
+        rest = close + 1;
+        let inner = &document[open + 1..close];
+        if inner.starts_with('?') || inner.starts_with('!') {
+            continue;
+        }
+        let (closing, name) = match inner.strip_prefix('/') {
+            Some(name) => (true, name),
+            None => (false, inner),
+        };
+        let name = name.split_whitespace().next().unwrap_or_default();
+        return Some(Tag {
+            offset: open,
+            length: close + 1 - open,
+            name: name.trim_end_matches('/').to_ascii_uppercase(),
+            closing,
+            value: unescape(document[close + 1..text_end].trim()),
+        });
+    })
+}
+
+fn find_tag(document: &str, name: &str) -> Option<usize> {
+    tags(document)
+        .find(|tag| tag.name == name && !tag.closing)
+        .map(|tag| tag.offset)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// `text` with its lines trimmed and joined, the way an `<STMTTRN>` is kept.
+fn one_line(text: &str) -> String {
+    text.lines().map(str::trim).collect()
+}
@@ -0,0 +1,159 @@
+//! Transfers of onchain accounts, read from public blockchains. An account
+//! is tied to one address on one chain, kept in its `metadata`, and each
+//! sync reads the address's transfers from the block the previous one
+//! stopped at. A transfer is stored with its chain and what identifies it
+//! on the chain, its transaction hash and place in the transaction, so a
+//! transfer read twice is recorded once.
+
+use crate::models::{Chain, CreateTransactionInput, TransactionDirection};
+use anyhow::{bail, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+pub mod bitcoin;
+pub mod ethereum;
+pub mod solana;
+
+/// Key of the address in an account's `metadata`.
+pub const ONCHAIN_METADATA_KEY: &str = "onchain";
+
+/// A movement of value to or from a synced address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub chain: Chain,
+    /// The transaction hash, followed by `:token:`, `:internal:` and the
+    /// transfer's place, or on Solana the token's mint, for the transfers a
+    /// transaction makes besides its own value, or by `:fee` for the fee it
+    /// paid.
+    pub id: String,
+    pub block: u64,
+    pub timestamp: DateTime<Utc>,
+    pub direction: TransactionDirection,
+    /// In whole units of `currency`, e.g. ether rather than wei and bitcoin
+    /// rather than satoshi.
+    pub amount: f64,
+    /// `ETH`, `BTC`, `SOL`, or the token's symbol.
+    pub currency: String,
+    /// What moved and where to, e.g. `USDC to 0x…`.
+    pub description: String,
+    /// The transaction was reverted, so the transfer did not happen.
+    pub failed: bool,
+    /// Not in a block yet, so a later sync reads the transfer again.
+    pub pending: bool,
+    /// Of a token posing as another or named like no real token, as
+    /// airdrop and address-poisoning spam sends.
+    pub spam: bool,
+    /// The transfer as the explorer listed it.
+    pub raw: Value,
+}
+
+/// The transfers one sync read.
+#[derive(Debug, Clone, Default)]
+pub struct Transfers {
+    pub transfers: Vec<Transfer>,
+    /// The block the transfers were read through, unset when none were found.
+    pub synced_block: Option<u64>,
+    /// False when the explorer stopped at a page's end before the latest
+    /// block.
+    pub complete: bool,
+    /// On Solana, the newest transaction read, unset when none were found.
+    pub synced_signature: Option<String>,
+}
+
+impl Transfer {
+    /// When the transfer's block was mined, as an RFC 3339 timestamp.
+    pub fn occurred_at(&self) -> String {
+        self.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    /// The transfer as `create_transaction` takes it, with its chain, id and
+    /// the explorer's record as its raw source so that [`entry_id`] finds it
+    /// again.
+    pub fn to_input(&self, account_id: &str) -> CreateTransactionInput {
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount,
+            currency: self.currency.clone(),
+            direction: self.direction,
+            occurred_at: self.occurred_at(),
+            description: Some(self.description.clone()),
+            raw_source: Some(
+                json!({
+                    "chain": self.chain,
+                    "transfer": self.id,
+                    "source": self.raw,
+                })
+                .to_string(),
+            ),
+        }
+    }
+
+    /// What tells this transfer apart in later syncs; see [`entry_id`].
+    pub fn entry_id(&self) -> String {
+        format!("{}:{}", self.chain, self.id)
+    }
+}
+
+/// What identifies a transaction recorded by an onchain sync, read back
+/// from its raw source: its chain and transfer id, e.g.
+/// `ethereum:0x5c50…:fee`.
+pub fn entry_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    let chain = raw.get("chain")?.as_str()?;
+    let transfer = raw.get("transfer")?.as_str()?;
+    Some(format!("{chain}:{transfer}"))
+}
+
+/// The chain an account's `network` names, e.g. `ethereum` or `btc`.
+pub fn chain_of_network(network: &str) -> Option<Chain> {
+    match network.trim().to_ascii_lowercase().as_str() {
+        "ethereum" | "eth" | "mainnet" | "ethereum mainnet" => Some(Chain::Ethereum),
+        "bitcoin" | "btc" => Some(Chain::Bitcoin),
+        "solana" | "sol" => Some(Chain::Solana),
+        _ => None,
+    }
+}
+
+/// The chain an address is written for, for accounts with no `network`.
+pub fn chain_of_address(address: &str) -> Option<Chain> {
+    if ethereum::parse_address(address).is_ok() {
+        Some(Chain::Ethereum)
+    } else if bitcoin::parse_address(address).is_ok() {
+        Some(Chain::Bitcoin)
+    } else if solana::parse_address(address).is_ok() {
+        Some(Chain::Solana)
+    } else {
+        None
+    }
+}
+
+/// `address` in the form it is kept and compared in on `chain`.
+pub fn parse_address(chain: Chain, address: &str) -> Result<String> {
+    match chain {
+        Chain::Ethereum => ethereum::parse_address(address),
+        Chain::Bitcoin => bitcoin::parse_address(address),
+        Chain::Solana => solana::parse_address(address),
+    }
+}
+
+/// `value` base units, such as wei, in whole units of a currency with
+/// `decimals` decimal places.
+pub fn units(value: &str, decimals: u32) -> Result<f64> {
+    let value = value.trim();
+    let Some(scale) = 10u128.checked_pow(decimals) else {
+        bail!("{decimals} decimal places are more than an amount can have");
+    };
+    if let Ok(base) = value.parse::<u128>() {
+        return Ok((base / scale) as f64 + (base % scale) as f64 / scale as f64);
+    }
+    // Past u128 only worthless tokens go, where precision no longer matters.
+    match value.parse::<f64>() {
+        Ok(base) if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+            Ok(base / scale as f64)
+        }
+        _ => bail!("{value:?} is not an amount of base units"),
+    }
+}
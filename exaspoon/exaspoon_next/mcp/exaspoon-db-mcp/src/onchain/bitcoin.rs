@@ -0,0 +1,378 @@
+//! Bitcoin transactions of an address or an extended public key, read
+//! through a Blockbook explorer such as the ones Trezor runs. Blockbook
+//! derives an xpub's addresses itself and marks the inputs and outputs
+//! that belong to it, so a transaction's effect on the wallet is what its
+//! own outputs receive less what its own inputs spend, fee included.
+
+use super::{units, Transfer, Transfers};
+use crate::config::{BlockbookConfig, HttpClientConfig};
+use crate::correlation;
+use crate::models::{Chain, TransactionDirection};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+/// Transactions asked of Blockbook per page, the most it returns.
+pub const BLOCKBOOK_PAGE_SIZE: usize = 1000;
+
+/// Decimal places of bitcoin, counted in satoshi.
+const BITCOIN_DECIMALS: u32 = 8;
+
+/// Prefixes of the extended public keys Blockbook reads, for legacy,
+/// wrapped and native SegWit wallets on mainnet and testnet.
+const XPUB_PREFIXES: &[&str] = &["xpub", "ypub", "zpub", "tpub", "upub", "vpub"];
+
+/// Reads Bitcoin through one Blockbook instance.
+#[derive(Clone)]
+pub struct BlockbookClient {
+    http: Client,
+    base_url: String,
+}
+
+/// An error response of Blockbook, e.g. for an address it cannot parse.
+#[derive(Debug, Clone)]
+pub struct BlockbookError {
+    pub status_code: u16,
+    pub error: String,
+}
+
+impl fmt::Display for BlockbookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Blockbook {}: {}", self.status_code, self.error)
+    }
+}
+
+impl std::error::Error for BlockbookError {}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page {
+    #[serde(default = "first_page")]
+    page: u32,
+    #[serde(default = "first_page")]
+    total_pages: u32,
+    #[serde(default)]
+    transactions: Vec<Value>,
+}
+
+fn first_page() -> u32 {
+    1
+}
+
+/// A block as Blockbook details it, for the time it was mined.
+#[derive(Deserialize)]
+struct Block {
+    #[serde(default)]
+    time: Option<i64>,
+}
+
+/// A transaction as Blockbook details it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockbookTransaction {
+    txid: String,
+    #[serde(default)]
+    vin: Vec<Side>,
+    #[serde(default)]
+    vout: Vec<Side>,
+    /// Zero or negative while the transaction is unconfirmed.
+    #[serde(default)]
+    block_height: i64,
+    /// Missing or zero for unconfirmed transactions, and from some
+    /// instances for confirmed ones too.
+    #[serde(default)]
+    block_time: Option<i64>,
+    #[serde(default)]
+    fees: Option<String>,
+    /// The transaction as Blockbook sent it.
+    #[serde(skip)]
+    raw: Value,
+}
+
+/// An input or output of a transaction.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Side {
+    #[serde(default)]
+    value: Option<String>,
+    #[serde(default)]
+    addresses: Vec<String>,
+    /// Belongs to the address or xpub asked about.
+    #[serde(default)]
+    is_own: bool,
+}
+
+impl BlockbookClient {
+    pub fn new(config: &BlockbookConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Blockbook client for {}", config.base_url);
+        Ok(Self {
+            // Public Blockbook instances turn away requests without one.
+            http: http
+                .apply(Client::builder())
+                .user_agent(concat!(
+                    env!("CARGO_PKG_NAME"),
+                    "/",
+                    env!("CARGO_PKG_VERSION")
+                ))
+                .build()
+                .context("failed to build HTTP client for Blockbook")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// The transactions of `address`, an address or xpub, from block
+    /// `start_block` on, oldest first and the unconfirmed ones last.
+    #[instrument(skip(self))]
+    pub async fn transfers(&self, address: &str, start_block: u64) -> Result<Transfers> {
+        let start_time = Instant::now();
+        let address = parse_address(address)?;
+        let kind = if is_xpub(&address) { "xpub" } else { "address" };
+        let path = format!("/api/v2/{kind}/{address}");
+        let mut transfers = Vec::new();
+        let mut block_times = HashMap::new();
+        let mut page = 1;
+        loop {
+            let listed = self.page(&path, start_block, page).await?;
+            for raw in listed.transactions {
+                let mut transaction: BlockbookTransaction = serde_json::from_value(raw.clone())
+                    .context("unexpected Blockbook transaction shape")?;
+                transaction.raw = raw;
+                if transaction.block_height > 0 && transaction.block_time.is_none_or(|t| t <= 0) {
+                    let height = transaction.block_height;
+                    let time = match block_times.get(&height) {
+                        Some(time) => *time,
+                        None => {
+                            let time = self.block_time(height).await?;
+                            block_times.insert(height, time);
+                            time
+                        }
+                    };
+                    transaction.block_time = Some(time);
+                }
+                transfers.extend(
+                    transaction
+                        .transfers(&address)
+                        .with_context(|| format!("Blockbook transaction {}", transaction.txid))?,
+                );
+            }
+            if listed.page >= listed.total_pages {
+                break;
+            }
+            page = listed.page + 1;
+        }
+        transfers.sort_by_key(|transfer| (transfer.pending, transfer.block, transfer.timestamp));
+        let synced_block = transfers
+            .iter()
+            .filter(|transfer| !transfer.pending)
+            .map(|transfer| transfer.block)
+            .max();
+        info!(
+            "Read {} Bitcoin transfers of {} in {:?}",
+            transfers.len(),
+            address,
+            start_time.elapsed()
+        );
+        Ok(Transfers {
+            transfers,
+            synced_block,
+            complete: true,
+            synced_signature: None,
+        })
+    }
+
+    /// When the block at `height` was mined, for transactions listed
+    /// without it.
+    async fn block_time(&self, height: i64) -> Result<i64> {
+        let path = format!("/api/v2/block/{height}");
+        let response = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .headers(correlation::headers())
+            .send()
+            .await
+            .with_context(|| format!("Blockbook request to {path} failed"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!(
+                "Blockbook request to {} failed ({}): {}",
+                path, status, body
+            );
+            return Err(anyhow!(BlockbookError {
+                status_code: status.as_u16(),
+                error: format!("request to {path} failed"),
+            }));
+        }
+        let block: Block = response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Blockbook response from {path}"))?;
+        block
+            .time
+            .filter(|time| *time > 0)
+            .with_context(|| format!("Blockbook has no time for block {height}"))
+    }
+
+    async fn page(&self, path: &str, start_block: u64, page: u32) -> Result<Page> {
+        let response = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .headers(correlation::headers())
+            .query(&[
+                ("details", "txs".to_string()),
+                ("from", start_block.to_string()),
+                ("page", page.to_string()),
+                ("pageSize", BLOCKBOOK_PAGE_SIZE.to_string()),
+            ])
+            .send()
+            .await
+            .with_context(|| format!("Blockbook request to {path} failed"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!(
+                "Blockbook request to {} failed ({}): {}",
+                path, status, body
+            );
+            let error = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|body| body.get("error")?.as_str().map(str::to_string))
+                .unwrap_or_else(|| format!("request to {path} failed"));
+            return Err(anyhow!(BlockbookError {
+                status_code: status.as_u16(),
+                error,
+            }));
+        }
+        response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Blockbook response from {path}"))
+    }
+}
+
+impl BlockbookTransaction {
+    /// What the transaction moved for `address`: bitcoin received, sent to
+    /// others or moved between the wallet's own addresses, and for one the
+    /// wallet spent from, the fee it paid.
+    fn transfers(&self, address: &str) -> Result<Vec<Transfer>> {
+        let own = |side: &Side| side.is_own || side.addresses.iter().any(|a| a == address);
+        let satoshi = |side: &Side| -> Result<u64> {
+            let value = side.value.as_deref().unwrap_or("0");
+            value
+                .parse()
+                .with_context(|| format!("{value:?} is not an amount of satoshi"))
+        };
+        let total = |sides: &[Side], owned: bool| -> Result<u64> {
+            sides
+                .iter()
+                .filter(|side| own(side) == owned)
+                .map(satoshi)
+                .sum()
+        };
+        let spent = total(&self.vin, true)?;
+        let received = total(&self.vout, true)?;
+        let paid_out = total(&self.vout, false)?;
+        let other = |sides: &[Side]| {
+            sides
+                .iter()
+                .filter(|side| !own(side))
+                .find_map(|side| side.addresses.first().cloned())
+                .unwrap_or_else(|| "another wallet".to_string())
+        };
+
+        let pending = self.block_height <= 0;
+        let block = u64::try_from(self.block_height).unwrap_or_default();
+        // An unconfirmed transaction is skipped, so when it was first seen
+        // only shows in what the sync reports.
+        let timestamp = match self.block_time.filter(|time| *time > 0) {
+            Some(time) => DateTime::from_timestamp(time, 0)
+                .with_context(|| format!("{time} is out of range for a timestamp"))?,
+            None if pending => Utc::now(),
+            None => bail!("block {} has no time", self.block_height),
+        };
+        let (direction, amount, description) = if spent == 0 {
+            (
+                TransactionDirection::Income,
+                received,
+                format!("BTC from {}", other(&self.vin)),
+            )
+        } else if paid_out > 0 {
+            (
+                TransactionDirection::Expense,
+                paid_out,
+                format!("BTC to {}", other(&self.vout)),
+            )
+        } else {
+            (
+                TransactionDirection::Transfer,
+                received,
+                "BTC between own addresses".to_string(),
+            )
+        };
+        let value = Transfer {
+            chain: Chain::Bitcoin,
+            id: self.txid.clone(),
+            block,
+            timestamp,
+            direction,
+            amount: units(&amount.to_string(), BITCOIN_DECIMALS)?,
+            currency: "BTC".to_string(),
+            description,
+            failed: false,
+            pending,
+            spam: false,
+            raw: self.raw.clone(),
+        };
+        if spent == 0 {
+            return Ok(vec![value]);
+        }
+        let fee = Transfer {
+            id: format!("{}:fee", self.txid),
+            direction: TransactionDirection::Expense,
+            amount: units(self.fees.as_deref().unwrap_or("0"), BITCOIN_DECIMALS)?,
+            description: "BTC network fee".to_string(),
+            ..value.clone()
+        };
+        Ok(vec![value, fee])
+    }
+}
+
+/// Whether `address` is an extended public key rather than an address.
+pub fn is_xpub(address: &str) -> bool {
+    XPUB_PREFIXES
+        .iter()
+        .any(|prefix| address.starts_with(prefix))
+}
+
+/// `address` as Blockbook takes it, once it looks like a Bitcoin address or
+/// extended public key: bech32 addresses in lower case, and base58 ones and
+/// xpubs as written, as their case matters.
+pub fn parse_address(address: &str) -> Result<String> {
+    let address = address.trim();
+    let base58 = |text: &str| {
+        text.bytes()
+            .all(|b| b.is_ascii_alphanumeric() && !matches!(b, b'0' | b'O' | b'I' | b'l'))
+    };
+    let lower = address.to_ascii_lowercase();
+    let bech32 = ["bc1", "tb1"].iter().any(|hrp| lower.starts_with(hrp))
+        && (address == lower || address == address.to_ascii_uppercase());
+    if bech32
+        && (14..=74).contains(&address.len())
+        && lower.bytes().all(|b| b.is_ascii_alphanumeric())
+    {
+        return Ok(lower);
+    }
+    let legacy =
+        address.starts_with(['1', '3', 'm', 'n', '2']) && (26..=35).contains(&address.len());
+    let xpub = is_xpub(address) && address.len() == 111;
+    if (legacy || xpub) && base58(address) {
+        return Ok(address.to_string());
+    }
+    bail!("{address:?} is not a Bitcoin address or extended public key");
+}
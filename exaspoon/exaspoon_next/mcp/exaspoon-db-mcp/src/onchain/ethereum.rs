@@ -0,0 +1,448 @@
+//! Ether and ERC-20 token transfers of an Ethereum address, read through
+//! the Etherscan API: the transactions the address sent or received, the
+//! ether contracts sent it within other transactions, and its token
+//! transfers. Etherscan answers errors with HTTP 200 too, telling them by a
+//! `status` of `0` and a message in place of the list.
+
+use super::{units, Transfer, Transfers};
+use crate::config::{EtherscanConfig, HttpClientConfig};
+use crate::correlation;
+use crate::models::{Chain, TransactionDirection};
+use crate::validation;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::DateTime;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+pub const ETHERSCAN_BASE_URL: &str = "https://api.etherscan.io/v2/api";
+
+/// Etherscan's id of Ethereum mainnet.
+const MAINNET_CHAIN_ID: &str = "1";
+
+/// Rows asked of Etherscan per list; a full page means more are left.
+pub const ETHERSCAN_PAGE_SIZE: usize = 1000;
+
+/// Pages Etherscan lets a list be read through, as it serves no row past
+/// the 10000th.
+const ETHERSCAN_MAX_PAGES: usize = 10;
+
+/// Contracts of well-known ERC-20 tokens. A token with one of these symbols
+/// from any other contract is a counterfeit, as address-poisoning spam
+/// sends; a token called ETH is always one.
+const KNOWN_TOKENS: &[(&str, &str)] = &[
+    ("USDT", "0xdac17f958d2ee523a2206206994597c13d831ec7"),
+    ("USDC", "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"),
+    ("DAI", "0x6b175474e89094c44da98b954eedeac495271d0f"),
+    ("WETH", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"),
+    ("WBTC", "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599"),
+    ("PYUSD", "0x6c3ea9036406852006290770bedfcaba0e23a0e8"),
+    ("STETH", "0xae7ab96520de3a18e5e111b5eaab095312d7fe84"),
+    ("LINK", "0x514910771af9ca656af840dff83e8264ecf986ca"),
+    ("UNI", "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984"),
+    ("SHIB", "0x95ad61b0a150d79219dcf64e1e6cc01f0b64c4ce"),
+];
+
+/// The longest symbol a token is taken to be real with.
+const MAX_SYMBOL_LEN: usize = 11;
+
+/// Decimal places of ether, counted in wei.
+const ETHER_DECIMALS: u32 = 18;
+
+/// Reads Ethereum mainnet through Etherscan with one API key.
+#[derive(Clone)]
+pub struct EtherscanClient {
+    http: Client,
+    base_url: String,
+    api_key: String,
+}
+
+/// An error response of Etherscan, e.g. `NOTOK` with `Invalid API Key`.
+#[derive(Debug, Clone)]
+pub struct EtherscanError {
+    pub message: String,
+    pub result: String,
+}
+
+impl fmt::Display for EtherscanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Etherscan {}: {}", self.message, self.result)
+    }
+}
+
+impl std::error::Error for EtherscanError {}
+
+#[derive(Deserialize)]
+struct Response {
+    status: String,
+    #[serde(default)]
+    message: String,
+    result: Value,
+}
+
+/// The lists Etherscan keeps of an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum List {
+    /// Transactions the address sent or received.
+    Normal,
+    /// Ether contracts moved within other transactions.
+    Internal,
+    /// ERC-20 token transfers.
+    Token,
+}
+
+impl List {
+    fn action(self) -> &'static str {
+        match self {
+            Self::Normal => "txlist",
+            Self::Internal => "txlistinternal",
+            Self::Token => "tokentx",
+        }
+    }
+}
+
+/// A row of any of the lists, which share most of their fields.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Row {
+    block_number: String,
+    time_stamp: String,
+    hash: String,
+    from: String,
+    /// Empty for a transaction creating a contract.
+    #[serde(default)]
+    to: String,
+    value: String,
+    #[serde(default)]
+    contract_address: String,
+    #[serde(default)]
+    gas_used: Option<String>,
+    #[serde(default)]
+    gas_price: Option<String>,
+    /// `1` for a reverted transaction.
+    #[serde(default)]
+    is_error: Option<String>,
+    #[serde(default)]
+    token_symbol: Option<String>,
+    #[serde(default)]
+    token_decimal: Option<String>,
+    #[serde(default)]
+    log_index: Option<String>,
+    #[serde(default)]
+    trace_id: Option<String>,
+    #[serde(skip)]
+    raw: Value,
+}
+
+impl EtherscanClient {
+    pub fn new(config: &EtherscanConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Etherscan client for {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Etherscan")?,
+            base_url: config.base_url.clone(),
+            api_key: config.api_key.clone(),
+        })
+    }
+
+    /// The transfers of `address` from `start_block` on, oldest first: a
+    /// page of each list, cut at the earliest block a full page ended at
+    /// so that no list leaves a gap the next sync would not read.
+    #[instrument(skip(self))]
+    pub async fn transfers(&self, address: &str, start_block: u64) -> Result<Transfers> {
+        let start_time = Instant::now();
+        let address = parse_address(address)?;
+        let mut rows = Vec::new();
+        let mut cut: Option<u64> = None;
+        for list in [List::Normal, List::Internal, List::Token] {
+            let (page, full) = self.list(list, &address, start_block).await?;
+            if full {
+                if let Some(last) = page.last() {
+                    let last = last.block()?;
+                    cut = Some(cut.map_or(last, |cut| cut.min(last)));
+                }
+            }
+            rows.extend(page.into_iter().map(|row| (list, row)));
+        }
+
+        let mut latest = None;
+        let mut places: HashMap<(List, String), usize> = HashMap::new();
+        let mut transfers = Vec::new();
+        for (list, row) in rows {
+            let block = row.block()?;
+            if cut.is_some_and(|cut| block > cut) {
+                continue;
+            }
+            latest = latest.max(Some(block));
+            let place = places.entry((list, row.hash.clone())).or_default();
+            transfers.extend(
+                row.transfers(list, &address, *place)
+                    .with_context(|| format!("Etherscan {} row of {}", list.action(), row.hash))?,
+            );
+            *place += 1;
+        }
+        transfers.sort_by_key(|transfer| (transfer.block, transfer.timestamp));
+        info!(
+            "Read {} Ethereum transfers of {} in {:?}",
+            transfers.len(),
+            address,
+            start_time.elapsed()
+        );
+        Ok(Transfers {
+            transfers,
+            synced_block: cut.or(latest),
+            complete: cut.is_none(),
+            synced_signature: None,
+        })
+    }
+
+    /// The rows of `list` from `start_block` on, and whether the last page
+    /// read was full. Pages are read on while every row so far is in one
+    /// block, as cutting at that block would leave the next sync where this
+    /// one started.
+    async fn list(&self, list: List, address: &str, start_block: u64) -> Result<(Vec<Row>, bool)> {
+        let mut rows: Vec<Row> = Vec::new();
+        for page in 1..=ETHERSCAN_MAX_PAGES {
+            let listed = self.page(list, address, start_block, page).await?;
+            let full = listed.len() >= ETHERSCAN_PAGE_SIZE;
+            rows.extend(listed);
+            let one_block = match (rows.first(), rows.last()) {
+                (Some(first), Some(last)) => first.block()? == last.block()?,
+                _ => false,
+            };
+            if !full || !one_block {
+                return Ok((rows, full));
+            }
+        }
+        bail!(
+            "more than {} Etherscan {} rows of {address} are in block {}, more than Etherscan lists",
+            ETHERSCAN_MAX_PAGES * ETHERSCAN_PAGE_SIZE,
+            list.action(),
+            rows.first().map(Row::block).transpose()?.unwrap_or(start_block)
+        )
+    }
+
+    async fn page(
+        &self,
+        list: List,
+        address: &str,
+        start_block: u64,
+        page: usize,
+    ) -> Result<Vec<Row>> {
+        let action = list.action();
+        let start_block = start_block.to_string();
+        let page = page.to_string();
+        let page_size = ETHERSCAN_PAGE_SIZE.to_string();
+        let response = self
+            .http
+            .get(&self.base_url)
+            .headers(correlation::headers())
+            .query(&[
+                ("chainid", MAINNET_CHAIN_ID),
+                ("module", "account"),
+                ("action", action),
+                ("address", address),
+                ("startblock", start_block.as_str()),
+                ("page", page.as_str()),
+                ("offset", page_size.as_str()),
+                ("sort", "asc"),
+                ("apikey", self.api_key.as_str()),
+            ])
+            .send()
+            .await
+            .with_context(|| format!("Etherscan request for {action} failed"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!(
+                "Etherscan request for {} failed ({}): {}",
+                action, status, body
+            );
+            return Err(anyhow!(EtherscanError {
+                message: status.to_string(),
+                result: format!("request for {action} failed"),
+            }));
+        }
+        let response: Response = response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Etherscan response for {action}"))?;
+        // An empty list comes with status 0 and "No transactions found".
+        let Value::Array(rows) = response.result else {
+            let result = match response.result {
+                Value::String(result) => result,
+                other => other.to_string(),
+            };
+            error!(
+                "Etherscan refused {}: {} ({})",
+                action, response.message, result
+            );
+            return Err(anyhow!(EtherscanError {
+                message: response.message,
+                result,
+            }));
+        };
+        if response.status != "1" && !rows.is_empty() {
+            bail!(
+                "Etherscan answered {action} with status {}",
+                response.status
+            );
+        }
+        rows.into_iter()
+            .map(|raw| {
+                let mut row: Row = serde_json::from_value(raw.clone())
+                    .with_context(|| format!("unexpected Etherscan {action} row shape"))?;
+                row.raw = raw;
+                Ok(row)
+            })
+            .collect()
+    }
+}
+
+impl Row {
+    fn block(&self) -> Result<u64> {
+        self.block_number
+            .parse()
+            .with_context(|| format!("{:?} is not a block number", self.block_number))
+    }
+
+    /// The transfers this row makes to or from `address`: its value, and
+    /// for a transaction `address` sent the fee it paid, even when it was
+    /// reverted.
+    fn transfers(&self, list: List, address: &str, place: usize) -> Result<Vec<Transfer>> {
+        let seconds: i64 = self
+            .time_stamp
+            .parse()
+            .with_context(|| format!("{:?} is not a timestamp", self.time_stamp))?;
+        let timestamp = DateTime::from_timestamp(seconds, 0)
+            .with_context(|| format!("{seconds} is out of range for a timestamp"))?;
+        let failed = self.is_error.as_deref() == Some("1");
+        let from = self.from.to_ascii_lowercase();
+        let to = if self.to.is_empty() {
+            self.contract_address.to_ascii_lowercase()
+        } else {
+            self.to.to_ascii_lowercase()
+        };
+        let (id, currency, decimals, spam) = match list {
+            List::Normal => (self.hash.clone(), "ETH".to_string(), ETHER_DECIMALS, false),
+            List::Internal => (
+                format!(
+                    "{}:internal:{}",
+                    self.hash,
+                    self.trace_id.clone().unwrap_or_else(|| place.to_string())
+                ),
+                "ETH".to_string(),
+                ETHER_DECIMALS,
+                false,
+            ),
+            List::Token => {
+                let symbol = self
+                    .token_symbol
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|symbol| !symbol.is_empty())
+                    .unwrap_or(&self.contract_address)
+                    .to_string();
+                let decimals = self
+                    .token_decimal
+                    .as_deref()
+                    .and_then(|decimals| decimals.parse().ok())
+                    .unwrap_or(0);
+                let place = self.log_index.clone().unwrap_or_else(|| place.to_string());
+                let spam = is_spam(&symbol, &self.contract_address);
+                (
+                    format!("{}:token:{place}", self.hash),
+                    // Kept the way other tools take currencies, e.g. STETH.
+                    validation::currency_code(&symbol).unwrap_or(symbol),
+                    decimals,
+                    spam,
+                )
+            }
+        };
+        let (direction, description) = match (from == address, to == address) {
+            (true, true) => (
+                TransactionDirection::Transfer,
+                format!("{currency} to itself"),
+            ),
+            (true, false) => (TransactionDirection::Expense, format!("{currency} to {to}")),
+            (false, _) => (
+                TransactionDirection::Income,
+                format!("{currency} from {from}"),
+            ),
+        };
+        let value = Transfer {
+            chain: Chain::Ethereum,
+            id,
+            block: self.block()?,
+            timestamp,
+            direction,
+            amount: units(&self.value, decimals)?,
+            currency,
+            description,
+            failed,
+            pending: false,
+            spam,
+            raw: self.raw.clone(),
+        };
+        if list != List::Normal || from != address {
+            return Ok(vec![value]);
+        }
+        let gas = |amount: &Option<String>| {
+            amount
+                .as_deref()
+                .unwrap_or("0")
+                .parse::<u128>()
+                .with_context(|| format!("{amount:?} is not an amount of gas"))
+        };
+        let fee = gas(&self.gas_used)?
+            .checked_mul(gas(&self.gas_price)?)
+            .context("the fee is out of range")?;
+        let fee = Transfer {
+            id: format!("{}:fee", self.hash),
+            direction: TransactionDirection::Expense,
+            amount: units(&fee.to_string(), ETHER_DECIMALS)?,
+            currency: "ETH".to_string(),
+            description: "ETH network fee".to_string(),
+            failed: false,
+            ..value.clone()
+        };
+        Ok(vec![value, fee])
+    }
+}
+
+/// Whether a token called `symbol` from `contract` is spam: one posing as a
+/// well-known token or as ether, or named with something no ticker has,
+/// such as the link of a phishing site.
+fn is_spam(symbol: &str, contract: &str) -> bool {
+    let ticker = symbol.to_ascii_uppercase();
+    let contract = contract.to_ascii_lowercase();
+    let counterfeit = ticker == "ETH"
+        || KNOWN_TOKENS
+            .iter()
+            .any(|(known, address)| *known == ticker && *address != contract);
+    let lure = symbol.len() > MAX_SYMBOL_LEN
+        || !symbol
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'));
+    counterfeit || lure
+}
+
+/// `address` in lower case, once it is checked to be `0x` and 40 hex
+/// digits.
+pub fn parse_address(address: &str) -> Result<String> {
+    let address = address.trim();
+    let digits = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .unwrap_or_default();
+    if digits.len() != 40 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("{address:?} is not an Ethereum address, 0x and 40 hex digits");
+    }
+    Ok(address.to_ascii_lowercase())
+}
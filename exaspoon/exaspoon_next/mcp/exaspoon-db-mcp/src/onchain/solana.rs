@@ -0,0 +1,534 @@
+//! SOL and SPL token transfers of a Solana wallet, read through a JSON-RPC
+//! node, either the public mainnet one or an indexer's such as Helius.
+//! What a transaction did to the wallet is read from the balances it
+//! changed: the lamports of the wallet's own account, less the fee when the
+//! wallet paid it, and the tokens of the token accounts the wallet owns.
+//! Only finalized transactions are read, so none are pending.
+
+use super::{units, Transfer, Transfers};
+use crate::config::{HttpClientConfig, SolanaConfig};
+use crate::correlation;
+use crate::models::{Chain, TransactionDirection};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::DateTime;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+/// Transactions read per sync, each one an RPC call; a wallet with more is
+/// read over several syncs.
+pub const SOLANA_PAGE_SIZE: usize = 100;
+
+/// Signatures asked of `getSignaturesForAddress` per call, the most it
+/// returns.
+const SIGNATURE_PAGE_SIZE: usize = 1000;
+
+/// Decimal places of SOL, counted in lamports.
+const SOL_DECIMALS: u32 = 9;
+
+/// Symbols of well-known SPL token mints. RPC nodes know tokens only by
+/// their mint, which stands for the currency of any other token.
+const KNOWN_MINTS: &[(&str, &str)] = &[
+    ("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "USDC"),
+    ("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BEW7NYb", "USDT"),
+    ("So11111111111111111111111111111111111111112", "WSOL"),
+    ("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So", "MSOL"),
+    ("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn", "JITOSOL"),
+    ("JUPyiwrYJFskUPiHa7hkeR8VUtAeFoSYbKedZNsDvCN", "JUP"),
+    ("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263", "BONK"),
+    ("HZ1JovNiVvGrGNiiYvEozEVgZ58xaU3RKwX8eACQBCt3", "PYTH"),
+];
+
+/// Reads Solana mainnet through one RPC endpoint.
+#[derive(Clone)]
+pub struct SolanaClient {
+    http: Client,
+    rpc_url: String,
+}
+
+/// An error the RPC node answered with, e.g. `-32005` when a rate limit
+/// is hit, or the HTTP status of a request it turned away.
+#[derive(Debug, Clone)]
+pub struct SolanaRpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl fmt::Display for SolanaRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Solana RPC {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for SolanaRpcError {}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// A transaction `getSignaturesForAddress` lists.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Signature {
+    signature: String,
+    slot: u64,
+    #[serde(default)]
+    block_time: Option<i64>,
+}
+
+/// A transaction as `getTransaction` details it in `jsonParsed` encoding.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SolanaTransaction {
+    #[serde(default)]
+    block_time: Option<i64>,
+    meta: Meta,
+    transaction: Envelope,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    /// Set for a transaction that failed, which still paid its fee.
+    #[serde(default)]
+    err: Option<Value>,
+    fee: u64,
+    pre_balances: Vec<u64>,
+    post_balances: Vec<u64>,
+    #[serde(default)]
+    pre_token_balances: Vec<TokenBalance>,
+    #[serde(default)]
+    post_token_balances: Vec<TokenBalance>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Envelope {
+    message: Message,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    /// The accounts the balances are listed in the order of, the fee payer
+    /// first.
+    account_keys: Vec<AccountKey>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct AccountKey {
+    pubkey: String,
+}
+
+/// The balance of a token account before or after a transaction.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenBalance {
+    mint: String,
+    /// The wallet the token account belongs to.
+    #[serde(default)]
+    owner: Option<String>,
+    ui_token_amount: TokenAmount,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TokenAmount {
+    /// In the token's base units.
+    amount: String,
+    decimals: u32,
+}
+
+impl SolanaClient {
+    pub fn new(config: &SolanaConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Solana RPC client");
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Solana RPC")?,
+            rpc_url: config.rpc_url.clone(),
+        })
+    }
+
+    /// The transfers of the wallet `address` from slot `start_block` on, or
+    /// after transaction `until` when the previous sync names it, oldest
+    /// first: up to [`SOLANA_PAGE_SIZE`] transactions, and the rest of the
+    /// slot the last of them is in, so that the next sync leaves no gap.
+    #[instrument(skip(self))]
+    pub async fn transfers(
+        &self,
+        address: &str,
+        start_block: u64,
+        until: Option<&str>,
+    ) -> Result<Transfers> {
+        let start_time = Instant::now();
+        let address = parse_address(address)?;
+        let mut signatures = self.signatures(&address, start_block, until).await?;
+        let cut = signatures
+            .get(SOLANA_PAGE_SIZE)
+            .map(|_| signatures[SOLANA_PAGE_SIZE - 1].slot);
+        if let Some(cut) = cut {
+            signatures.retain(|signature| signature.slot <= cut);
+        }
+
+        let mut transfers = Vec::new();
+        for signature in &signatures {
+            let raw = self
+                .call(
+                    "getTransaction",
+                    json!([
+                        signature.signature,
+                        {
+                            "encoding": "jsonParsed",
+                            "commitment": "finalized",
+                            "maxSupportedTransactionVersion": 0,
+                        },
+                    ]),
+                )
+                .await?;
+            if raw.is_null() {
+                bail!(
+                    "the RPC node no longer has transaction {}; use one that keeps the full history",
+                    signature.signature
+                );
+            }
+            let transaction: SolanaTransaction = serde_json::from_value(raw.clone())
+                .context("unexpected Solana transaction shape")?;
+            // Nodes answer 0 or null for slots whose time they lack.
+            let block_time = match transaction
+                .block_time
+                .or(signature.block_time)
+                .filter(|time| *time > 0)
+            {
+                Some(time) => time,
+                None => self.block_time(signature.slot).await?,
+            };
+            transfers.extend(
+                transaction
+                    .transfers(signature, block_time, &address, &raw)
+                    .with_context(|| format!("Solana transaction {}", signature.signature))?,
+            );
+        }
+        info!(
+            "Read {} Solana transfers of {} in {:?}",
+            transfers.len(),
+            address,
+            start_time.elapsed()
+        );
+        let newest = signatures.last();
+        Ok(Transfers {
+            transfers,
+            synced_block: cut.or(newest.map(|signature| signature.slot)),
+            complete: cut.is_none(),
+            synced_signature: newest.map(|signature| signature.signature.clone()),
+        })
+    }
+
+    /// The finalized transactions of `address` from slot `start_block` on
+    /// and newer than `until`, oldest first. The node lists them newest
+    /// first, so every page back to there is read; `until` has the node stop
+    /// there itself.
+    async fn signatures(
+        &self,
+        address: &str,
+        start_block: u64,
+        until: Option<&str>,
+    ) -> Result<Vec<Signature>> {
+        let mut signatures = Vec::new();
+        let mut before: Option<String> = None;
+        loop {
+            let mut options = json!({
+                "limit": SIGNATURE_PAGE_SIZE,
+                "commitment": "finalized",
+            });
+            if let Some(before) = &before {
+                options["before"] = json!(before);
+            }
+            if let Some(until) = until {
+                options["until"] = json!(until);
+            }
+            let page: Vec<Signature> = serde_json::from_value(
+                self.call("getSignaturesForAddress", json!([address, options]))
+                    .await?,
+            )
+            .context("unexpected Solana signature list shape")?;
+            let full = page.len() >= SIGNATURE_PAGE_SIZE;
+            let reached = page
+                .last()
+                .is_some_and(|signature| signature.slot < start_block);
+            before = page.last().map(|signature| signature.signature.clone());
+            signatures.extend(
+                page.into_iter()
+                    .take_while(|signature| signature.slot >= start_block),
+            );
+            if !full || reached {
+                break;
+            }
+        }
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    /// When slot `slot` was produced, for transactions listed without it.
+    async fn block_time(&self, slot: u64) -> Result<i64> {
+        self.call("getBlockTime", json!([slot]))
+            .await?
+            .as_i64()
+            .filter(|time| *time > 0)
+            .with_context(|| format!("the RPC node has no time for slot {slot}"))
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response = self
+            .http
+            .post(&self.rpc_url)
+            .headers(correlation::headers())
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .with_context(|| format!("Solana RPC request for {method} failed"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!(
+                "Solana RPC request for {} failed ({}): {}",
+                method, status, body
+            );
+            return Err(anyhow!(SolanaRpcError {
+                code: i64::from(status.as_u16()),
+                message: format!("request for {method} failed"),
+            }));
+        }
+        let response: RpcResponse = response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Solana RPC response for {method}"))?;
+        if let Some(rejected) = response.error {
+            error!(
+                "Solana RPC refused {}: {} ({})",
+                method, rejected.message, rejected.code
+            );
+            return Err(anyhow!(SolanaRpcError {
+                code: rejected.code,
+                message: rejected.message,
+            }));
+        }
+        Ok(response.result)
+    }
+}
+
+impl SolanaTransaction {
+    /// What the transaction moved for `address`: its change in SOL besides
+    /// the fee, its change in each token, and the fee when it paid it.
+    fn transfers(
+        &self,
+        signature: &Signature,
+        block_time: i64,
+        address: &str,
+        raw: &Value,
+    ) -> Result<Vec<Transfer>> {
+        let meta = &self.meta;
+        let keys: Vec<&str> = self
+            .transaction
+            .message
+            .account_keys
+            .iter()
+            .map(|key| key.pubkey.as_str())
+            .collect();
+        let timestamp = DateTime::from_timestamp(block_time, 0)
+            .with_context(|| format!("{block_time} is out of range for a timestamp"))?;
+        let failed = meta.err.is_some();
+        // The balances the transfers are read from, rather than the whole
+        // transaction with its instructions and logs.
+        let balances: serde_json::Map<String, Value> = [
+            "err",
+            "fee",
+            "preBalances",
+            "postBalances",
+            "preTokenBalances",
+            "postTokenBalances",
+        ]
+        .into_iter()
+        .filter_map(|key| Some((key.to_string(), raw["meta"].get(key)?.clone())))
+        .collect();
+        let raw = json!({
+            "signature": signature.signature,
+            "slot": signature.slot,
+            "blockTime": block_time,
+            "accountKeys": keys,
+            "meta": balances,
+        });
+        let base = Transfer {
+            chain: Chain::Solana,
+            id: signature.signature.clone(),
+            block: signature.slot,
+            timestamp,
+            direction: TransactionDirection::Expense,
+            amount: 0.0,
+            currency: "SOL".to_string(),
+            description: String::new(),
+            failed,
+            pending: false,
+            spam: false,
+            raw,
+        };
+        // A change of the wallet's balance as a transfer from or to `other`.
+        let moved = |change: i128, currency: String, decimals: u32, other: Option<&str>| {
+            let (direction, description) = match (change > 0, other) {
+                (true, Some(other)) => (
+                    TransactionDirection::Income,
+                    format!("{currency} from {other}"),
+                ),
+                (true, None) => (TransactionDirection::Income, format!("{currency} received")),
+                (false, Some(other)) => (
+                    TransactionDirection::Expense,
+                    format!("{currency} to {other}"),
+                ),
+                (false, None) => (TransactionDirection::Expense, format!("{currency} sent")),
+            };
+            Ok::<_, anyhow::Error>(Transfer {
+                direction,
+                amount: units(&change.unsigned_abs().to_string(), decimals)?,
+                currency,
+                description,
+                ..base.clone()
+            })
+        };
+        let mut transfers = Vec::new();
+
+        // The fee payer signs first, and its balance falls by the fee too.
+        let own = keys.iter().position(|key| *key == address);
+        let fee = if own == Some(0) { meta.fee } else { 0 };
+        let lamports: Vec<i128> = (0..keys.len())
+            .map(|index| {
+                let pre = meta.pre_balances.get(index).copied().unwrap_or_default();
+                let post = meta.post_balances.get(index).copied().unwrap_or_default();
+                i128::from(post) - i128::from(pre)
+            })
+            .collect();
+        if let Some(own) = own {
+            let change = lamports[own] + i128::from(fee);
+            if change != 0 {
+                let other = counterparty(
+                    change,
+                    keys.iter()
+                        .zip(&lamports)
+                        .enumerate()
+                        .filter_map(|(index, (key, change))| {
+                            // The fee leaves the payer without going to anyone.
+                            let change = if index == 0 {
+                                change + i128::from(meta.fee)
+                            } else {
+                                *change
+                            };
+                            (index != own).then_some((*key, change))
+                        }),
+                );
+                transfers.push(moved(change, "SOL".to_string(), SOL_DECIMALS, other)?);
+            }
+        }
+
+        // Each wallet's change in each token, over all its token accounts.
+        let mut tokens: BTreeMap<&str, (u32, BTreeMap<&str, i128>)> = BTreeMap::new();
+        for (balances, sign) in [
+            (&meta.pre_token_balances, -1),
+            (&meta.post_token_balances, 1),
+        ] {
+            for balance in balances {
+                let amount: i128 = balance.ui_token_amount.amount.parse().with_context(|| {
+                    format!(
+                        "{:?} is not an amount of a token",
+                        balance.ui_token_amount.amount
+                    )
+                })?;
+                let (decimals, owners) = tokens
+                    .entry(balance.mint.as_str())
+                    .or_insert((balance.ui_token_amount.decimals, BTreeMap::new()));
+                *decimals = balance.ui_token_amount.decimals;
+                *owners
+                    .entry(balance.owner.as_deref().unwrap_or_default())
+                    .or_default() += sign * amount;
+            }
+        }
+        for (mint, (decimals, owners)) in &tokens {
+            let Some(&change) = owners.get(address).filter(|change| **change != 0) else {
+                continue;
+            };
+            let other = counterparty(
+                change,
+                owners
+                    .iter()
+                    .filter(|(owner, _)| **owner != address && !owner.is_empty())
+                    .map(|(owner, change)| (*owner, *change)),
+            );
+            transfers.push(Transfer {
+                id: format!("{}:token:{mint}", signature.signature),
+                ..moved(change, symbol(mint), *decimals, other)?
+            });
+        }
+
+        if fee > 0 {
+            transfers.push(Transfer {
+                id: format!("{}:fee", signature.signature),
+                amount: units(&fee.to_string(), SOL_DECIMALS)?,
+                description: "SOL network fee".to_string(),
+                failed: false,
+                ..base.clone()
+            });
+        }
+        Ok(transfers)
+    }
+}
+
+/// Of the accounts a transaction changed, the one that moved most the other
+/// way from the wallet's `change`, as the transfer's sender or recipient.
+fn counterparty<'a>(
+    change: i128,
+    others: impl Iterator<Item = (&'a str, i128)>,
+) -> Option<&'a str> {
+    others
+        .filter(|(_, other)| other.signum() == -change.signum())
+        .max_by_key(|(_, other)| other.unsigned_abs())
+        .map(|(key, _)| key)
+}
+
+/// The currency of the tokens of `mint`: its symbol when it is well known,
+/// or else the mint itself.
+pub fn symbol(mint: &str) -> String {
+    KNOWN_MINTS
+        .iter()
+        .find(|(known, _)| *known == mint)
+        .map_or(mint, |(_, symbol)| symbol)
+        .to_string()
+}
+
+/// `address` as written, once it looks like a Solana account address: 32
+/// to 44 base58 characters.
+pub fn parse_address(address: &str) -> Result<String> {
+    let address = address.trim();
+    let base58 = address
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() && !matches!(b, b'0' | b'O' | b'I' | b'l'));
+    if !base58 || !(32..=44).contains(&address.len()) {
+        bail!("{address:?} is not a Solana address, 32 to 44 base58 characters");
+    }
+    Ok(address.to_string())
+}
@@ -0,0 +1,77 @@
+//! Which host names the HTTP and WebSocket transports answer to. Any web
+//! page the user opens can make the browser connect to a server on their
+//! machine, through a name it controls that resolves to it (DNS rebinding)
+//! or straight to `localhost` (cross-site WebSocket hijacking). Checking the
+//! `Host` and, for browsers, the `Origin` of each request against the names
+//! the server is meant to be reached by stops both.
+
+/// Names allowed when none are configured: the loopback interface only.
+pub const DEFAULT_ALLOWED_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// Allows any host; for servers behind a proxy that checks them itself.
+pub const ANY_HOST: &str = "*";
+
+/// Host names requests may be addressed to and browser origins they may come
+/// from, compared without their port and ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedHosts {
+    hosts: Vec<String>,
+}
+
+impl Default for AllowedHosts {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl AllowedHosts {
+    /// `hosts`, or the [`DEFAULT_ALLOWED_HOSTS`] when empty.
+    pub fn new(hosts: Vec<String>) -> Self {
+        let hosts = if hosts.is_empty() {
+            DEFAULT_ALLOWED_HOSTS
+                .iter()
+                .map(|host| host.to_string())
+                .collect()
+        } else {
+            hosts
+                .iter()
+                .map(|host| host.trim().to_ascii_lowercase())
+                .collect()
+        };
+        Self { hosts }
+    }
+
+    /// Whether a request with these `Host` and `Origin` headers may be
+    /// served. A request must name its host; one without an origin does not
+    /// come from a browser and is let through.
+    pub fn admits(&self, host: Option<&str>, origin: Option<&str>) -> bool {
+        let Some(host) = host else {
+            return false;
+        };
+        self.allows(host)
+            && origin.is_none_or(|origin| {
+                origin
+                    .split_once("://")
+                    .is_some_and(|(_, authority)| self.allows(authority))
+            })
+    }
+
+    fn allows(&self, authority: &str) -> bool {
+        let name = strip_port(authority.trim().trim_end_matches('/')).to_ascii_lowercase();
+        self.hosts
+            .iter()
+            .any(|host| host == ANY_HOST || *host == name)
+    }
+}
+
+/// `authority` without a trailing `:port`, keeping IPv6 brackets.
+fn strip_port(authority: &str) -> &str {
+    if authority.starts_with('[') {
+        return authority
+            .find(']')
+            .map_or(authority, |end| &authority[..=end]);
+    }
+    authority
+        .rsplit_once(':')
+        .map_or(authority, |(name, _)| name)
+}
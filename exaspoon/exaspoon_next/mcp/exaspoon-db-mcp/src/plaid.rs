@@ -0,0 +1,421 @@
+//! Bank transactions from Plaid. A login at a bank, an item in Plaid's
+//! terms, is reached with an access token, and `/transactions/sync` returns
+//! what changed on it since a cursor, so each sync only fetches what is new.
+//! Every transaction carries Plaid's `transaction_id`, which stays the same
+//! across syncs and so tells which transactions were recorded before.
+
+use crate::config::{HttpClientConfig, PlaidConfig};
+use crate::correlation;
+use crate::models::{CreateTransactionInput, TransactionDirection};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use reqwest::Client;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::Instant;
+use tracing::{debug, error, info, instrument, warn};
+
+pub const PLAID_SANDBOX_URL: &str = "https://sandbox.plaid.com";
+pub const PLAID_PRODUCTION_URL: &str = "https://production.plaid.com";
+
+/// Transactions per `/transactions/sync` page, the most Plaid allows.
+pub const PLAID_SYNC_PAGE_SIZE: u32 = 500;
+
+/// Times a sync starts over after the item changed while it was paging.
+const MAX_SYNC_RESTARTS: usize = 3;
+
+/// Error code Plaid returns when an item changed mid-pagination; the sync
+/// must start again from the cursor it began with.
+const MUTATION_DURING_PAGINATION: &str = "TRANSACTIONS_SYNC_MUTATION_DURING_PAGINATION";
+
+/// Marks an access token encrypted by [`PlaidClient::seal`]; a stored token
+/// without it was saved before tokens were encrypted.
+const SEALED_PREFIX: &str = "sealed:v1:";
+
+/// Talks to the Plaid API with the client id and secret of one environment.
+#[derive(Clone)]
+pub struct PlaidClient {
+    http: Client,
+    base_url: String,
+    client_id: String,
+    secret: String,
+    token_key: [u8; 32],
+}
+
+/// An item, as `/item/public_token/exchange` returns it.
+#[derive(Clone, Deserialize)]
+pub struct Item {
+    pub item_id: String,
+    pub access_token: String,
+}
+
+/// One transaction of `/transactions/sync`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaidTransaction {
+    pub transaction_id: String,
+    /// Plaid's id of the account within the item.
+    pub account_id: String,
+    /// Positive for money leaving the account.
+    pub amount: f64,
+    #[serde(default)]
+    pub iso_currency_code: Option<String>,
+    /// Set instead of `iso_currency_code` for currencies such as crypto.
+    #[serde(default)]
+    pub unofficial_currency_code: Option<String>,
+    /// Posting date, or the date of a pending transaction, as `YYYY-MM-DD`.
+    pub date: String,
+    #[serde(default)]
+    pub datetime: Option<String>,
+    #[serde(default)]
+    pub authorized_datetime: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub merchant_name: Option<String>,
+    #[serde(default)]
+    pub pending: bool,
+    #[serde(default)]
+    pub personal_finance_category: Option<PersonalFinanceCategory>,
+    /// The transaction as Plaid sent it.
+    #[serde(skip)]
+    pub raw: Value,
+}
+
+/// Plaid's own categorization of a transaction.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PersonalFinanceCategory {
+    /// E.g. `FOOD_AND_DRINK` or `TRANSFER_OUT`.
+    pub primary: String,
+}
+
+/// What changed on an item since a cursor.
+#[derive(Debug, Clone, Default)]
+pub struct SyncChanges {
+    pub added: Vec<PlaidTransaction>,
+    pub modified: Vec<PlaidTransaction>,
+    /// `transaction_id`s of withdrawn transactions, with their account.
+    pub removed: Vec<RemovedTransaction>,
+    /// Where the next sync starts.
+    pub next_cursor: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemovedTransaction {
+    pub transaction_id: String,
+    #[serde(default)]
+    pub account_id: Option<String>,
+}
+
+/// An error response of the Plaid API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaidError {
+    pub error_type: String,
+    pub error_code: String,
+    pub error_message: String,
+}
+
+impl fmt::Display for PlaidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Plaid {} {}: {}",
+            self.error_type, self.error_code, self.error_message
+        )
+    }
+}
+
+impl std::error::Error for PlaidError {}
+
+#[derive(Deserialize)]
+struct SyncPage {
+    added: Vec<Value>,
+    modified: Vec<Value>,
+    removed: Vec<RemovedTransaction>,
+    next_cursor: String,
+    has_more: bool,
+}
+
+#[derive(Deserialize)]
+struct ItemResponse {
+    item: ItemId,
+}
+
+#[derive(Deserialize)]
+struct ItemId {
+    item_id: String,
+}
+
+impl PlaidClient {
+    pub fn new(config: &PlaidConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Plaid client for {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Plaid")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            client_id: config.client_id.clone(),
+            secret: config.secret.clone(),
+            token_key: config.token_key,
+        })
+    }
+
+    /// `access_token` encrypted with the token key for storage, bound to
+    /// `account_id` so that it only opens for that account's link.
+    pub fn seal(&self, account_id: &str, access_token: &str) -> Result<String> {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("failed to generate a nonce"))?;
+        let mut sealed = access_token.as_bytes().to_vec();
+        self.token_cipher()?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(account_id),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("failed to encrypt access token"))?;
+        sealed.splice(0..0, nonce);
+        Ok(format!("{SEALED_PREFIX}{}", STANDARD.encode(sealed)))
+    }
+
+    /// The access token `stored` holds for `account_id`'s link, decrypting
+    /// it unless it was stored before tokens were encrypted.
+    pub fn open(&self, account_id: &str, stored: &str) -> Result<String> {
+        let Some(sealed) = stored.strip_prefix(SEALED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let mut sealed = STANDARD
+            .decode(sealed)
+            .context("stored access token is not base64")?;
+        if sealed.len() < NONCE_LEN {
+            bail!("stored access token is truncated");
+        }
+        let mut ciphertext = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed)
+            .map_err(|_| anyhow!("stored access token has no nonce"))?;
+        let token = self
+            .token_cipher()?
+            .open_in_place(nonce, Aad::from(account_id), &mut ciphertext)
+            .map_err(|_| anyhow!("failed to decrypt access token; was PLAID_TOKEN_KEY changed?"))?;
+        String::from_utf8(token.to_vec()).context("decrypted access token is not UTF-8")
+    }
+
+    fn token_cipher(&self) -> Result<LessSafeKey> {
+        let key = UnboundKey::new(&AES_256_GCM, &self.token_key)
+            .map_err(|_| anyhow!("PLAID_TOKEN_KEY is not an AES-256 key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    /// Exchanges the `public_token` Plaid Link returned for an item's access
+    /// token. A public token can be exchanged only once.
+    #[instrument(skip_all)]
+    pub async fn exchange_public_token(&self, public_token: &str) -> Result<Item> {
+        self.post(
+            "/item/public_token/exchange",
+            json!({ "public_token": public_token }),
+        )
+        .await
+    }
+
+    /// The id of the item `access_token` grants access to, which also proves
+    /// the token valid.
+    #[instrument(skip_all)]
+    pub async fn item_id(&self, access_token: &str) -> Result<String> {
+        let response: ItemResponse = self
+            .post("/item/get", json!({ "access_token": access_token }))
+            .await?;
+        Ok(response.item.item_id)
+    }
+
+    /// Every change on the item since `cursor`, or its whole history without
+    /// one, read a page at a time.
+    #[instrument(skip_all, fields(resumed = cursor.is_some()))]
+    pub async fn sync(&self, access_token: &str, cursor: Option<&str>) -> Result<SyncChanges> {
+        let start_time = Instant::now();
+        let mut restarts = 0;
+        loop {
+            match self.sync_pages(access_token, cursor).await {
+                Err(err) if is_mutation_during_pagination(&err) && restarts < MAX_SYNC_RESTARTS => {
+                    restarts += 1;
+                    warn!("Plaid item changed during the sync; starting over");
+                }
+                Ok(changes) => {
+                    info!(
+                        "Synced {} added, {} modified and {} removed Plaid transactions in {:?}",
+                        changes.added.len(),
+                        changes.modified.len(),
+                        changes.removed.len(),
+                        start_time.elapsed()
+                    );
+                    return Ok(changes);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn sync_pages(&self, access_token: &str, cursor: Option<&str>) -> Result<SyncChanges> {
+        let mut changes = SyncChanges {
+            next_cursor: cursor.unwrap_or_default().to_string(),
+            ..Default::default()
+        };
+        loop {
+            let mut body = json!({
+                "access_token": access_token,
+                "count": PLAID_SYNC_PAGE_SIZE,
+            });
+            if !changes.next_cursor.is_empty() {
+                body["cursor"] = json!(changes.next_cursor);
+            }
+            let page: SyncPage = self.post("/transactions/sync", body).await?;
+            debug!(
+                "Plaid sync page: {} added, {} modified, {} removed",
+                page.added.len(),
+                page.modified.len(),
+                page.removed.len()
+            );
+            changes.added.extend(transactions(page.added)?);
+            changes.modified.extend(transactions(page.modified)?);
+            changes.removed.extend(page.removed);
+            changes.next_cursor = page.next_cursor;
+            if !page.has_more {
+                return Ok(changes);
+            }
+        }
+    }
+
+    /// POSTs `body`, with the client id and secret added, to `path`.
+    async fn post<T: DeserializeOwned>(&self, path: &str, mut body: Value) -> Result<T> {
+        body["client_id"] = json!(self.client_id);
+        body["secret"] = json!(self.secret);
+        let response = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .headers(correlation::headers())
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("Plaid request to {path} failed"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Plaid request to {} failed ({}): {}", path, status, body);
+            return Err(match serde_json::from_str::<PlaidError>(&body) {
+                Ok(err) => anyhow!(err),
+                Err(_) => anyhow!("Plaid request to {path} failed ({status})"),
+            });
+        }
+        response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Plaid response from {path}"))
+    }
+}
+
+fn transactions(values: Vec<Value>) -> Result<Vec<PlaidTransaction>> {
+    values
+        .into_iter()
+        .map(|raw| {
+            let mut transaction: PlaidTransaction = serde_json::from_value(raw.clone())
+                .context("unexpected Plaid transaction shape")?;
+            transaction.raw = raw;
+            Ok(transaction)
+        })
+        .collect()
+}
+
+fn is_mutation_during_pagination(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<PlaidError>()
+        .is_some_and(|err| err.error_code == MUTATION_DURING_PAGINATION)
+}
+
+impl PlaidTransaction {
+    /// The currency Plaid reports, official or not.
+    pub fn currency(&self) -> Option<&str> {
+        self.iso_currency_code
+            .as_deref()
+            .or(self.unofficial_currency_code.as_deref())
+            .filter(|code| !code.is_empty())
+    }
+
+    /// When the transaction happened as an RFC 3339 timestamp in UTC: its
+    /// time if Plaid knows one, otherwise midnight of its date.
+    pub fn occurred_at(&self) -> Result<String> {
+        let time = self
+            .datetime
+            .as_deref()
+            .or(self.authorized_datetime.as_deref());
+        if let Some(time) = time {
+            let parsed = DateTime::parse_from_rfc3339(time)
+                .with_context(|| format!("{time:?} is not an RFC 3339 timestamp"))?;
+            return Ok(parsed
+                .with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+        let date = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+            .with_context(|| format!("{:?} is not a date", self.date))?;
+        Ok(date
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+            .and_utc()
+            .to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    /// The transaction as `create_transaction` takes it, with the JSON Plaid
+    /// sent as its raw source so that [`transaction_id`] finds it again.
+    /// `currency` is used when Plaid reports none.
+    pub fn to_input(&self, account_id: &str, currency: &str) -> Result<CreateTransactionInput> {
+        let transfer = self
+            .personal_finance_category
+            .as_ref()
+            .is_some_and(|category| category.primary.starts_with("TRANSFER_"));
+        let direction = if transfer {
+            TransactionDirection::Transfer
+        } else if self.amount > 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        };
+        let occurred_at = self
+            .occurred_at()
+            .with_context(|| format!("Plaid transaction {}", self.transaction_id))?;
+        Ok(CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount.abs(),
+            currency: self.currency().unwrap_or(currency).to_string(),
+            direction,
+            occurred_at,
+            description: self.merchant_name.clone().or_else(|| self.name.clone()),
+            raw_source: Some(self.raw.to_string()),
+        })
+    }
+}
+
+/// Whether a transaction recorded by a sync brought money into its account,
+/// read back from its raw source, where Plaid gives money leaving the
+/// account a positive amount.
+pub fn inflow(raw_source: &str) -> Option<bool> {
+    transaction_id(raw_source)?;
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("amount")?.as_f64().map(|amount| amount < 0.0)
+}
+
+/// The Plaid `transaction_id` of a transaction recorded by a sync, read back
+/// from its raw source.
+pub fn transaction_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("transaction_id")?
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
@@ -0,0 +1,112 @@
+//! Errors returned by PostgREST, kept structured so tools can tell a
+//! duplicate row or a dangling reference apart from an outage.
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Postgres SQLSTATE for a unique constraint violation.
+pub const UNIQUE_VIOLATION: &str = "23505";
+/// Postgres SQLSTATE for a foreign key violation.
+pub const FOREIGN_KEY_VIOLATION: &str = "23503";
+/// Postgres SQLSTATE for a null in a `not null` column.
+pub const NOT_NULL_VIOLATION: &str = "23502";
+/// Postgres SQLSTATE for a failed `check` constraint.
+pub const CHECK_VIOLATION: &str = "23514";
+/// Postgres SQLSTATE a function raises for a row it did not find.
+pub const NO_DATA_FOUND: &str = "P0002";
+
+/// How a failed request should be reported to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostgrestErrorKind {
+    /// The row clashes with an existing one.
+    Conflict,
+    /// The request refers to rows that do not exist or breaks a constraint.
+    InvalidInput,
+    /// Anything else: outages, permissions, malformed queries.
+    Other,
+}
+
+/// A non-success PostgREST response. PostgREST reports Postgres errors as
+/// `{"code", "message", "details", "hint"}`; bodies in any other shape end up
+/// whole in `message`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PostgrestError {
+    #[serde(skip)]
+    pub operation: String,
+    #[serde(serialize_with = "serialize_status")]
+    pub status: StatusCode,
+    pub code: Option<String>,
+    pub message: String,
+    pub details: Option<String>,
+    pub hint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: Option<String>,
+    message: Option<String>,
+    details: Option<String>,
+    hint: Option<String>,
+}
+
+impl PostgrestError {
+    pub fn parse(operation: &str, status: StatusCode, body: &str) -> Self {
+        let parsed = serde_json::from_str::<ErrorBody>(body)
+            .ok()
+            .filter(|parsed| parsed.code.is_some() || parsed.message.is_some());
+        let (code, message, details, hint) = match parsed {
+            Some(parsed) => (
+                parsed.code,
+                parsed.message.unwrap_or_default(),
+                parsed.details,
+                parsed.hint,
+            ),
+            None => (None, body.trim().to_string(), None, None),
+        };
+        Self {
+            operation: operation.to_string(),
+            status,
+            code,
+            message,
+            details,
+            hint,
+        }
+    }
+
+    pub fn kind(&self) -> PostgrestErrorKind {
+        match self.code.as_deref() {
+            Some(UNIQUE_VIOLATION) => PostgrestErrorKind::Conflict,
+            Some(FOREIGN_KEY_VIOLATION | NOT_NULL_VIOLATION | CHECK_VIOLATION) => {
+                PostgrestErrorKind::InvalidInput
+            }
+            _ => PostgrestErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for PostgrestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed ({})", self.operation, self.status)?;
+        if let Some(code) = &self.code {
+            write!(f, " [{code}]")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(details) = &self.details {
+            write!(f, "; {details}")?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "; hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PostgrestError {}
+
+fn serialize_status<S: serde::Serializer>(
+    status: &StatusCode,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(status.as_u16())
+}
@@ -0,0 +1,367 @@
+//! Prices of crypto assets in fiat currencies from CoinGecko, for
+//! `get_asset_price` and for valuing the crypto held in onchain and exchange
+//! accounts in the reports' base currency totals. Assets are named by ticker
+//! symbol: the common ones are known, the rest are searched for and the
+//! ranked coin with exactly that symbol and the largest market cap taken. A
+//! day's price never changes once the day is over, so it is kept until
+//! [`MAX_CACHED_PRICES`] push the oldest out; the latest one is kept for
+//! `PRICE_CACHE_TTL_SECS`.
+
+use crate::config::{CoinGeckoPlan, HttpClientConfig, PriceConfig};
+use crate::correlation;
+use crate::fx::UnknownCurrency;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument};
+
+pub const COINGECKO_BASE_URL: &str = "https://api.coingecko.com/api/v3";
+pub const COINGECKO_PRO_BASE_URL: &str = "https://pro-api.coingecko.com/api/v3";
+
+/// Seconds a latest price is kept before it is fetched again.
+pub const DEFAULT_PRICE_CACHE_TTL_SECS: u64 = 300;
+
+/// Prices kept per coin, currency and day, beyond which the longest kept go.
+pub const MAX_CACHED_PRICES: usize = 4096;
+
+/// Symbols whose coin is kept, beyond which the longest kept go.
+pub const MAX_CACHED_COINS: usize = 1024;
+
+/// CoinGecko coins of the symbols synced accounts hold most, which a search
+/// could otherwise match to a lookalike token.
+const KNOWN_COINS: &[(&str, &str)] = &[
+    ("BTC", "bitcoin"),
+    ("ETH", "ethereum"),
+    ("SOL", "solana"),
+    ("USDT", "tether"),
+    ("USDC", "usd-coin"),
+    ("DAI", "dai"),
+    ("BNB", "binancecoin"),
+    ("XRP", "ripple"),
+    ("ADA", "cardano"),
+    ("DOGE", "dogecoin"),
+];
+
+/// Fetches and caches asset prices from CoinGecko.
+pub struct CoinGeckoClient {
+    http: Client,
+    base_url: String,
+    api_key: Option<String>,
+    api_plan: CoinGeckoPlan,
+    base_currency: String,
+    cache_ttl: Duration,
+    /// Coin ids of the symbols searched for, with when they were found.
+    coins: Mutex<HashMap<String, (Instant, String)>>,
+    /// Cached prices by coin id, currency and day.
+    cache: Mutex<HashMap<PriceKey, Cached>>,
+}
+
+/// Coin id, currency and day of a cached price; no day for the latest.
+type PriceKey = (String, String, Option<NaiveDate>);
+
+struct Cached {
+    fetched_at: Instant,
+    price: Price,
+}
+
+/// The price of an asset in a currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    /// The CoinGecko coin the asset was taken for, e.g. `bitcoin`.
+    pub coin_id: String,
+    /// Units of the currency per unit of the asset.
+    pub price: f64,
+    /// The day asked for, or the time CoinGecko last updated the price.
+    pub as_of: String,
+}
+
+/// An asset CoinGecko has no coin for.
+#[derive(Debug, Clone)]
+pub struct UnknownAsset(pub String);
+
+impl fmt::Display for UnknownAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no CoinGecko coin for {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownAsset {}
+
+/// An error response of CoinGecko, with its own code when it sent one, e.g.
+/// `10002` for a missing API key.
+#[derive(Debug, Clone)]
+pub struct CoinGeckoError {
+    pub status_code: u16,
+    pub error_code: Option<i64>,
+    pub message: String,
+}
+
+impl fmt::Display for CoinGeckoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CoinGecko error {}: {}", self.status_code, self.message)
+    }
+}
+
+impl std::error::Error for CoinGeckoError {}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    coins: Vec<SearchCoin>,
+}
+
+#[derive(Deserialize)]
+struct SearchCoin {
+    id: String,
+    symbol: String,
+    /// Missing for the coins CoinGecko does not rank, which is most spam.
+    #[serde(default)]
+    market_cap_rank: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct History {
+    #[serde(default)]
+    market_data: Option<MarketData>,
+}
+
+#[derive(Deserialize)]
+struct MarketData {
+    #[serde(default)]
+    current_price: HashMap<String, f64>,
+}
+
+/// Either shape CoinGecko rejects a request with.
+#[derive(Deserialize)]
+struct Rejected {
+    #[serde(default)]
+    status: Option<RejectedStatus>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RejectedStatus {
+    error_code: i64,
+    error_message: String,
+}
+
+impl CoinGeckoClient {
+    pub fn new(config: &PriceConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing CoinGecko prices from {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for CoinGecko")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            api_plan: config.api_plan,
+            base_currency: config.base_currency.to_ascii_uppercase(),
+            cache_ttl: config.cache_ttl,
+            coins: Mutex::default(),
+            cache: Mutex::default(),
+        })
+    }
+
+    /// The currency `get_asset_price` prices in unless asked for another.
+    pub fn base_currency(&self) -> &str {
+        &self.base_currency
+    }
+
+    /// The price of `asset` in `currency` on `date`, or the latest one. Fails
+    /// with [`UnknownAsset`] for an asset CoinGecko has no coin for, and with
+    /// [`UnknownCurrency`] for a currency it has no prices in.
+    #[instrument(skip(self))]
+    pub async fn price(
+        &self,
+        asset: &str,
+        currency: &str,
+        date: Option<NaiveDate>,
+    ) -> Result<Price> {
+        let coin_id = self.coin_id(asset).await?;
+        let currency = currency.to_ascii_lowercase();
+        let key = (coin_id.clone(), currency.clone(), date);
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            if date.is_some() || cached.fetched_at.elapsed() < self.cache_ttl {
+                debug!("Price of {} from the cache", coin_id);
+                return Ok(cached.price.clone());
+            }
+        }
+
+        let start_time = Instant::now();
+        let price = match date {
+            Some(date) => self.history(&coin_id, &currency, date).await?,
+            None => self.latest(&coin_id, &currency).await?,
+        };
+        info!(
+            "Fetched the price of {} in {} in {:?}",
+            coin_id,
+            currency,
+            start_time.elapsed()
+        );
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_PRICES && !cache.contains_key(&key) {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            key,
+            Cached {
+                fetched_at: Instant::now(),
+                price: price.clone(),
+            },
+        );
+        Ok(price)
+    }
+
+    /// The coin `asset` names: a known symbol, the coin with the best market
+    /// cap rank of those with exactly the symbol, or a coin id. Unranked
+    /// coins are never taken for a symbol, as lookalike and spam tokens
+    /// share the symbols of real ones.
+    async fn coin_id(&self, asset: &str) -> Result<String> {
+        let symbol = asset.trim().to_ascii_uppercase();
+        if let Some((_, id)) = KNOWN_COINS.iter().find(|(known, _)| *known == symbol) {
+            return Ok(id.to_string());
+        }
+        if let Some((_, id)) = self.coins.lock().unwrap().get(&symbol) {
+            return Ok(id.clone());
+        }
+
+        let found: SearchResponse = self
+            .get("/search", &[("query", asset.trim().to_string())])
+            .await?;
+        let id = found
+            .coins
+            .iter()
+            .filter(|coin| coin.symbol.eq_ignore_ascii_case(&symbol))
+            .filter_map(|coin| Some((coin.market_cap_rank?, coin)))
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, coin)| coin)
+            .or_else(|| {
+                found
+                    .coins
+                    .iter()
+                    .find(|coin| coin.id.eq_ignore_ascii_case(asset.trim()))
+            })
+            .map(|coin| coin.id.clone())
+            .ok_or_else(|| anyhow!(UnknownAsset(symbol.clone())))?;
+        debug!("Taking CoinGecko coin {} for {}", id, symbol);
+        let mut coins = self.coins.lock().unwrap();
+        if coins.len() >= MAX_CACHED_COINS && !coins.contains_key(&symbol) {
+            let oldest = coins
+                .iter()
+                .min_by_key(|(_, (found_at, _))| *found_at)
+                .map(|(symbol, _)| symbol.clone());
+            if let Some(oldest) = oldest {
+                coins.remove(&oldest);
+            }
+        }
+        coins.insert(symbol, (Instant::now(), id.clone()));
+        Ok(id)
+    }
+
+    async fn latest(&self, coin_id: &str, currency: &str) -> Result<Price> {
+        let prices: HashMap<String, HashMap<String, f64>> = self
+            .get(
+                "/simple/price",
+                &[
+                    ("ids", coin_id.to_string()),
+                    ("vs_currencies", currency.to_string()),
+                    ("include_last_updated_at", "true".to_string()),
+                ],
+            )
+            .await?;
+        let coin = prices
+            .get(coin_id)
+            .ok_or_else(|| anyhow!(UnknownAsset(coin_id.to_string())))?;
+        let price = *coin
+            .get(currency)
+            .ok_or_else(|| anyhow!(UnknownCurrency(currency.to_ascii_uppercase())))?;
+        let updated_at = coin
+            .get("last_updated_at")
+            .and_then(|&timestamp| DateTime::from_timestamp(timestamp as i64, 0))
+            .unwrap_or_else(Utc::now);
+        Ok(Price {
+            coin_id: coin_id.to_string(),
+            price,
+            as_of: updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        })
+    }
+
+    async fn history(&self, coin_id: &str, currency: &str, date: NaiveDate) -> Result<Price> {
+        let history: History = self
+            .get(
+                &format!("/coins/{coin_id}/history"),
+                &[
+                    ("date", date.format("%d-%m-%Y").to_string()),
+                    ("localization", "false".to_string()),
+                ],
+            )
+            .await?;
+        let prices = history
+            .market_data
+            .with_context(|| format!("CoinGecko has no price of {coin_id} on {date}"))?;
+        let price = *prices
+            .current_price
+            .get(currency)
+            .ok_or_else(|| anyhow!(UnknownCurrency(currency.to_ascii_uppercase())))?;
+        Ok(Price {
+            coin_id: coin_id.to_string(),
+            price,
+            as_of: date.to_string(),
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .query(query)
+            .headers(correlation::headers());
+        if let Some(api_key) = &self.api_key {
+            let header = match self.api_plan {
+                CoinGeckoPlan::Demo => "x-cg-demo-api-key",
+                CoinGeckoPlan::Pro => "x-cg-pro-api-key",
+            };
+            request = request.header(header, api_key);
+        }
+        let response = request.send().await.context("CoinGecko request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("CoinGecko request failed ({}): {}", status, body);
+            let rejected = serde_json::from_str::<Rejected>(&body).ok();
+            let (error_code, message) = match rejected {
+                Some(Rejected {
+                    status: Some(rejected),
+                    ..
+                }) => (Some(rejected.error_code), rejected.error_message),
+                Some(Rejected {
+                    error: Some(error), ..
+                }) => (None, error),
+                _ => (None, status.to_string()),
+            };
+            return Err(anyhow!(CoinGeckoError {
+                status_code: status.as_u16(),
+                error_code,
+                message,
+            }));
+        }
+        response
+            .json()
+            .await
+            .context("failed to parse CoinGecko response")
+    }
+}
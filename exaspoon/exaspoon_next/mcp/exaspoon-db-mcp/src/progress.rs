@@ -0,0 +1,53 @@
+//! MCP progress notifications for tools that work through many rows.
+
+use rmcp::model::{Meta, ProgressNotificationParam, ProgressToken};
+use rmcp::{Peer, RoleServer};
+use std::future::Future;
+use tracing::debug;
+
+tokio::task_local! {
+    static CURRENT: Progress;
+}
+
+/// Where a tool call reports its progress. A client opts in by sending a
+/// `progressToken` in the call's `_meta`; without one, or outside of any call,
+/// reporting does nothing.
+#[derive(Clone, Default)]
+pub struct Progress {
+    target: Option<(Peer<RoleServer>, ProgressToken)>,
+}
+
+impl Progress {
+    pub fn new(peer: Peer<RoleServer>, meta: &Meta) -> Self {
+        Self {
+            target: meta.get_progress_token().map(|token| (peer, token)),
+        }
+    }
+
+    /// Runs `future` with this as the current progress target.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// The progress target of the tool call being served.
+    pub fn current() -> Self {
+        CURRENT.try_with(Clone::clone).unwrap_or_default()
+    }
+
+    /// Sends `done` of `total` items. A failed send is only logged, since the
+    /// work itself carries on either way.
+    pub async fn report(&self, done: usize, total: usize, message: &str) {
+        let Some((peer, token)) = &self.target else {
+            return;
+        };
+        let notification = ProgressNotificationParam {
+            progress_token: token.clone(),
+            progress: done as f64,
+            total: Some(total as f64),
+            message: Some(message.to_string()),
+        };
+        if let Err(err) = peer.notify_progress(notification).await {
+            debug!("Failed to send progress notification: {}", err);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Statements in QIF, the Quicken Interchange Format that older personal
+//! finance programs still export. A file lists records of one line fields,
+//! each starting with a letter, under a `!Type:` header and ending with `^`.
+//! Only the records of bank, cash, credit card and other asset or liability
+//! accounts are read; category and class lists and memorized transactions
+//! are skipped.
+
+use crate::models::{CreateTransactionInput, TransactionDirection};
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, SecondsFormat};
+
+/// `!Type:` headers whose records are transactions.
+const TRANSACTION_TYPES: &[&str] = &["bank", "cash", "ccard", "oth a", "oth l"];
+
+/// One record of a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QifTransaction {
+    /// `D` as an RFC 3339 timestamp at midnight UTC.
+    pub occurred_at: String,
+    /// `T`, negative for money leaving the account.
+    pub amount: f64,
+    pub payee: Option<String>,
+    pub memo: Option<String>,
+    /// `L` without its class, e.g. `Food:Groceries`, or for a split
+    /// transaction without one the category of its first split. `None` for
+    /// transfers, whose `L` names the other account in brackets.
+    pub category: Option<String>,
+    /// Whether `L` names another account, e.g. `[Savings]`.
+    pub transfer: bool,
+    /// The record's lines as they appeared in the file.
+    pub raw: String,
+}
+
+impl QifTransaction {
+    /// The transaction as `create_transaction` takes it, with the record as
+    /// its raw source.
+    pub fn to_input(&self, account_id: &str, currency: &str) -> CreateTransactionInput {
+        let direction = if self.transfer {
+            TransactionDirection::Transfer
+        } else if self.amount < 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        };
+        let description = match (&self.payee, &self.memo) {
+            (Some(payee), Some(memo)) if payee != memo => Some(format!("{payee} - {memo}")),
+            (Some(payee), _) => Some(payee.clone()),
+            (None, memo) => memo.clone(),
+        };
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount.abs(),
+            currency: currency.to_string(),
+            direction,
+            occurred_at: self.occurred_at.clone(),
+            description,
+            raw_source: Some(self.raw.clone()),
+        }
+    }
+}
+
+/// The transactions of a QIF document, in file order. Dates are read month
+/// first, as Quicken writes them in the US, unless `day_first` is set or
+/// they are separated by dots, and amounts as those of a currency with
+/// `decimals` decimals.
+pub fn parse(document: &str, day_first: bool, decimals: usize) -> Result<Vec<QifTransaction>> {
+    let mut transactions = Vec::new();
+    let mut section = None;
+    let mut typed = false;
+    let mut record = Vec::new();
+    for line in document.lines().map(str::trim_end) {
+        if let Some(header) = line.strip_prefix('!') {
+            if !record.is_empty() {
+                bail!("record {} is not closed by ^", transactions.len() + 1);
+            }
+            section = header
+                .split_once(':')
+                .filter(|(kind, _)| kind.trim().eq_ignore_ascii_case("type"))
+                .map(|(_, name)| name.trim().to_ascii_lowercase());
+            typed |= section.is_some();
+            if section.as_deref() == Some("invst") {
+                bail!("investment accounts (!Type:Invst) are not supported");
+            }
+            continue;
+        }
+        let reading = section
+            .as_deref()
+            .is_some_and(|name| TRANSACTION_TYPES.contains(&name));
+        if !reading || line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('^') {
+            let number = transactions.len() + 1;
+            let transaction = transaction(&record, day_first, decimals)
+                .with_context(|| format!("record {number}"))?;
+            transactions.push(transaction);
+            record.clear();
+        } else {
+            record.push(line);
+        }
+    }
+    if !record.is_empty() {
+        bail!("record {} is not closed by ^", transactions.len() + 1);
+    }
+    if !typed {
+        bail!("not a QIF document: no !Type: header");
+    }
+    Ok(transactions)
+}
+
+/// Whether a record imported from a statement brought money into its
+/// account, read back from the `T` or `U` line of its raw source. `None` for
+/// transactions that came from anywhere else.
+pub fn inflow(raw_source: &str) -> Option<bool> {
+    let lines = raw_source.lines().collect::<Vec<_>>();
+    let record = lines.len() > 1
+        && lines
+            .iter()
+            .all(|line| line.starts_with(|c: char| c.is_ascii_uppercase()))
+        && lines.iter().any(|line| line.starts_with('D'));
+    if !record {
+        return None;
+    }
+    let amount = lines
+        .iter()
+        .find(|line| line.starts_with('T'))
+        .or_else(|| lines.iter().find(|line| line.starts_with('U')))?;
+    // Only the sign is read, which the decimals do not change.
+    parse_amount(&amount[1..], 2)
+        .ok()
+        .flatten()
+        .map(|amount| amount > 0.0)
+}
+
+fn transaction(lines: &[&str], day_first: bool, decimals: usize) -> Result<QifTransaction> {
+    let field = |code: char| {
+        lines
+            .iter()
+            .find(|line| line.starts_with(code))
+            .map(|line| line[1..].trim())
+            .filter(|value| !value.is_empty())
+    };
+    let date = field('D').context("no date (D)")?;
+    let amount = field('T').or_else(|| field('U')).context("no amount (T)")?;
+    let amount = parse_amount(amount, decimals)?
+        .filter(|amount| amount.is_finite())
+        .with_context(|| format!("amount {amount:?} is not a number"))?;
+    let category = field('L')
+        .filter(|category| !category.eq_ignore_ascii_case("--split--"))
+        .or_else(|| field('S'));
+    let (category, transfer) = match category.map(split_class) {
+        Some(account) if account.starts_with('[') => (None, true),
+        Some(category) if !category.is_empty() => (Some(category.to_string()), false),
+        _ => (None, false),
+    };
+    Ok(QifTransaction {
+        occurred_at: parse_date(date, day_first)?,
+        amount,
+        payee: field('P').map(str::to_string),
+        memo: field('M').map(str::to_string),
+        category,
+        transfer,
+        raw: lines.join("\n"),
+    })
+}
+
+/// `category` without the class Quicken appends after a slash.
+fn split_class(category: &str) -> &str {
+    category
+        .split_once('/')
+        .map_or(category, |(category, _)| category)
+        .trim()
+}
+
+/// An amount in the user's number format, e.g. `-1,234.56`, `1.234,56`,
+/// `12,50` or `(45.00) €`, or `None` when it has no digits. The separator
+/// written last is the decimal one, unless it is the only kind written and is
+/// followed by three digits, which group thousands unless `decimals` is 3, as
+/// for KWD.
+pub fn parse_amount(value: &str, decimals: usize) -> Result<Option<f64>> {
+    let negative = value.contains('-') || value.starts_with('(');
+    let digits = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect::<String>();
+    if digits.is_empty() {
+        return Ok(None);
+    }
+    let decimal = match (digits.rfind('.'), digits.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (None, Some(separator)) | (Some(separator), None)
+            if decimals == 3 || digits.len() - separator - 1 != 3 =>
+        {
+            Some(separator)
+        }
+        _ => None,
+    };
+    let normalized = digits
+        .char_indices()
+        .filter_map(|(index, c)| match c {
+            '.' | ',' if Some(index) == decimal => Some('.'),
+            '.' | ',' => None,
+            c => Some(c),
+        })
+        .collect::<String>();
+    let amount: f64 = normalized
+        .parse()
+        .with_context(|| format!("{value:?} is not an amount"))?;
+    Ok(Some(if negative { -amount } else { amount }))
+}
+
+/// A QIF date, e.g. `01/05/2024`, `1/5'24`, `05.01.2024` or `2024-01-05`,
+/// as RFC 3339 at midnight UTC. Two-digit years before 50 are in this
+/// century.
+pub fn parse_date(value: &str, day_first: bool) -> Result<String> {
+    let invalid = || format!("{value:?} is not a QIF date");
+    let parts = value
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<u32>().ok().map(|number| (number, part.len())))
+        .collect::<Option<Vec<_>>>()
+        .with_context(invalid)?;
+    let &[(first, first_digits), (second, _), (third, third_digits)] = parts.as_slice() else {
+        bail!(invalid());
+    };
+    let (year, month, day) = if first_digits == 4 {
+        (first as i32, second, third)
+    } else {
+        let year = match third_digits {
+            1 | 2 if third < 50 => 2000 + third as i32,
+            1 | 2 => 1900 + third as i32,
+            _ => third as i32,
+        };
+        if day_first || value.contains('.') {
+            (year, second, first)
+        } else {
+            (year, first, second)
+        }
+    };
+    let date = NaiveDate::from_ymd_opt(year, month, day).with_context(invalid)?;
+    Ok(date
+        .and_hms_opt(0, 0, 0)
+        .with_context(invalid)?
+        .and_utc()
+        .to_rfc3339_opts(SecondsFormat::Secs, true))
+}
@@ -0,0 +1,149 @@
+//! Per-session tool call budgets, so a runaway agent loop cannot hammer the
+//! database and the embedding provider.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CALLS_PER_MINUTE: u32 = 120;
+pub const DEFAULT_EMBEDDING_CALLS_PER_MINUTE: u32 = 30;
+
+/// How many tool calls a session may make per window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// Calls of any tool. Zero disables the budget.
+    pub calls: u32,
+    /// Calls of tools that embed text, which also count as calls. Zero
+    /// disables the budget.
+    pub embedding_calls: u32,
+    /// The sliding window both budgets apply to; a minute outside of tests.
+    pub window: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_CALLS_PER_MINUTE, DEFAULT_EMBEDDING_CALLS_PER_MINUTE)
+    }
+}
+
+impl RateLimitConfig {
+    /// Budgets per minute.
+    pub fn new(calls: u32, embedding_calls: u32) -> Self {
+        Self {
+            calls,
+            embedding_calls,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Returned instead of running a call that is over budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    /// `calls` or `embedding_calls`.
+    pub budget: &'static str,
+    pub limit: u32,
+    pub window: Duration,
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.budget {
+            "embedding_calls" => "embedding tool calls",
+            _ => "tool calls",
+        };
+        write!(
+            f,
+            "rate limit of {} {} per {}s reached; retry in {}s",
+            self.limit,
+            what,
+            self.window.as_secs(),
+            self.retry_in.as_secs_f32().ceil()
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+#[derive(Debug, Default)]
+struct Windows {
+    calls: VecDeque<Instant>,
+    embedding_calls: VecDeque<Instant>,
+}
+
+/// Remembers when recent calls started and refuses calls that would go over
+/// a budget within the sliding window. Refused calls use no budget.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    windows: Mutex<Windows>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            windows: Mutex::default(),
+        }
+    }
+
+    pub fn config(&self) -> RateLimitConfig {
+        self.config
+    }
+
+    /// Counts a call against the budgets, `embeds` saying whether it is an
+    /// embedding-heavy one, or says how long to wait if it is over one.
+    pub fn acquire(&self, embeds: bool) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let Windows {
+            calls,
+            embedding_calls,
+        } = &mut *windows;
+        self.admit(now, "calls", self.config.calls, calls)?;
+        if embeds {
+            self.admit(
+                now,
+                "embedding_calls",
+                self.config.embedding_calls,
+                embedding_calls,
+            )?;
+            if self.config.embedding_calls > 0 {
+                embedding_calls.push_back(now);
+            }
+        }
+        if self.config.calls > 0 {
+            calls.push_back(now);
+        }
+        Ok(())
+    }
+
+    fn admit(
+        &self,
+        now: Instant,
+        budget: &'static str,
+        limit: u32,
+        window: &mut VecDeque<Instant>,
+    ) -> Result<(), RateLimited> {
+        if limit == 0 {
+            return Ok(());
+        }
+        while window
+            .front()
+            .is_some_and(|started| now.duration_since(*started) >= self.config.window)
+        {
+            window.pop_front();
+        }
+        match window.front() {
+            Some(oldest) if window.len() >= limit as usize => Err(RateLimited {
+                budget,
+                limit,
+                window: self.config.window,
+                retry_in: self.config.window - now.duration_since(*oldest),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
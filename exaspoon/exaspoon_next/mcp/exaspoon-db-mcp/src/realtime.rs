@@ -0,0 +1,233 @@
+//! Supabase Realtime listener for changes to the transactions table.
+//!
+//! Realtime speaks the Phoenix channel protocol over a websocket: the client
+//! joins a topic with a `postgres_changes` filter, sends a heartbeat at least
+//! every 30 seconds, and then receives one `postgres_changes` message per
+//! committed row.
+
+use crate::{config::AppConfig, retry::RetryPolicy, supabase::TENANT_COLUMN};
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, instrument, warn};
+
+/// Table whose changes are forwarded to MCP clients.
+pub const TRANSACTIONS_TABLE: &str = "transactions";
+/// Channel topic the listener joins.
+pub const TRANSACTIONS_TOPIC: &str = "realtime:exaspoon-transactions";
+/// Protocol version sent in the websocket URL.
+const PROTOCOL_VERSION: &str = "1.0.0";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+/// A connection that stayed up this long resets the reconnect backoff.
+const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+/// Changes buffered between the websocket and the MCP notifier.
+const CHANGE_BUFFER: usize = 64;
+
+/// What happened to a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// One committed change to the transactions table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionChange {
+    pub kind: ChangeKind,
+    pub record_id: Option<String>,
+}
+
+/// Connection settings for the Realtime websocket.
+#[derive(Debug, Clone)]
+pub struct RealtimeListener {
+    supabase_url: String,
+    api_key: String,
+    access_token: String,
+    schema: String,
+    tenant_id: Option<String>,
+    reconnect: RetryPolicy,
+}
+
+impl RealtimeListener {
+    /// Listens as the configured access token, or the service key when none is
+    /// set, so Realtime applies the same row level security as the gateway.
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            supabase_url: config.supabase_url.clone(),
+            api_key: config.supabase_service_key.clone(),
+            access_token: config
+                .supabase_access_token
+                .clone()
+                .unwrap_or_else(|| config.supabase_service_key.clone()),
+            schema: config.supabase_schema.clone(),
+            tenant_id: config.tenant_id.clone(),
+            reconnect: config.supabase_retry,
+        }
+    }
+
+    /// `wss://<project>/realtime/v1/websocket?apikey=...&vsn=1.0.0`.
+    pub fn websocket_url(&self) -> Result<String> {
+        let mut url = reqwest::Url::parse(&self.supabase_url)
+            .with_context(|| format!("invalid SUPABASE_URL {:?}", self.supabase_url))?
+            .join("realtime/v1/websocket")?;
+        let scheme = match url.scheme() {
+            "https" => "wss",
+            "http" => "ws",
+            other => bail!("unsupported SUPABASE_URL scheme {other:?}"),
+        };
+        url.set_scheme(scheme)
+            .map_err(|_| anyhow::anyhow!("cannot switch {url} to {scheme}"))?;
+        url.query_pairs_mut()
+            .append_pair("apikey", &self.api_key)
+            .append_pair("vsn", PROTOCOL_VERSION);
+        Ok(url.into())
+    }
+
+    /// Joins [`TRANSACTIONS_TOPIC`] for every change to the transactions
+    /// table, limited to the configured tenant's rows.
+    pub fn join_message(&self) -> Value {
+        let mut changes = json!({
+            "event": "*",
+            "schema": self.schema,
+            "table": TRANSACTIONS_TABLE,
+        });
+        if let Some(tenant) = &self.tenant_id {
+            changes["filter"] = json!(format!("{TENANT_COLUMN}=eq.{tenant}"));
+        }
+        json!({
+            "topic": TRANSACTIONS_TOPIC,
+            "event": "phx_join",
+            "ref": "1",
+            "join_ref": "1",
+            "payload": {
+                "config": {
+                    "broadcast": { "self": false },
+                    "presence": { "key": "" },
+                    "postgres_changes": [changes],
+                },
+                "access_token": self.access_token,
+            },
+        })
+    }
+
+    /// Starts listening in the background. The listener reconnects until the
+    /// returned receiver is dropped.
+    pub fn spawn(self) -> mpsc::Receiver<TransactionChange> {
+        let (sender, receiver) = mpsc::channel(CHANGE_BUFFER);
+        tokio::spawn(self.run(sender));
+        receiver
+    }
+
+    async fn run(self, changes: mpsc::Sender<TransactionChange>) {
+        let mut failures = 0;
+        loop {
+            let connected_at = Instant::now();
+            match self.session(&changes).await {
+                Ok(()) => {
+                    debug!("Realtime listener stopped: no one is listening for changes");
+                    return;
+                }
+                Err(err) => {
+                    if connected_at.elapsed() >= STABLE_CONNECTION {
+                        failures = 0;
+                    }
+                    let delay = self.reconnect.delay(failures);
+                    failures = failures.saturating_add(1);
+                    warn!(
+                        "Supabase Realtime connection failed: {:#}; reconnecting in {:?}",
+                        err, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// One websocket connection. Returns `Ok` only once `changes` is closed.
+    #[instrument(skip_all)]
+    async fn session(&self, changes: &mpsc::Sender<TransactionChange>) -> Result<()> {
+        let start_time = Instant::now();
+        let (mut socket, _) = connect_async(self.websocket_url()?)
+            .await
+            .context("failed to connect to Supabase Realtime")?;
+        socket
+            .send(Message::text(self.join_message().to_string()))
+            .await?;
+        info!(
+            "Subscribed to {} changes in {:?}",
+            TRANSACTIONS_TABLE,
+            start_time.elapsed()
+        );
+
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        heartbeat.tick().await;
+        let mut message_ref = 1u64;
+        loop {
+            tokio::select! {
+                _ = heartbeat.tick() => {
+                    message_ref += 1;
+                    socket.send(Message::text(heartbeat_message(message_ref).to_string())).await?;
+                }
+                _ = changes.closed() => return Ok(()),
+                frame = socket.next() => match frame {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Some(change) = parse_message(text.as_str())? {
+                            debug!("Transaction change: {:?}", change);
+                            if changes.send(change).await.is_err() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(frame))) => bail!("Supabase Realtime closed the connection: {frame:?}"),
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err.into()),
+                    None => bail!("Supabase Realtime closed the connection"),
+                },
+            }
+        }
+    }
+}
+
+/// Keeps the connection alive; Realtime drops sockets that stay silent.
+pub fn heartbeat_message(message_ref: u64) -> Value {
+    json!({
+        "topic": "phoenix",
+        "event": "heartbeat",
+        "payload": {},
+        "ref": message_ref.to_string(),
+    })
+}
+
+/// Extracts the row change from a Realtime message. Replies and presence
+/// traffic yield `None`; a rejected join is an error.
+pub fn parse_message(text: &str) -> Result<Option<TransactionChange>> {
+    let message: Value =
+        serde_json::from_str(text).context("Supabase Realtime sent invalid JSON")?;
+    let payload = &message["payload"];
+    match message["event"].as_str() {
+        Some("phx_reply") | Some("system") if payload["status"] == "error" => {
+            bail!("Supabase Realtime rejected the subscription: {}", payload)
+        }
+        Some("postgres_changes") => {}
+        _ => return Ok(None),
+    }
+
+    let data = &payload["data"];
+    let kind = match data["type"].as_str() {
+        Some("INSERT") => ChangeKind::Insert,
+        Some("UPDATE") => ChangeKind::Update,
+        Some("DELETE") => ChangeKind::Delete,
+        _ => return Ok(None),
+    };
+    let record_id = [&data["record"], &data["old_record"]]
+        .into_iter()
+        .find_map(|record| record["id"].as_str())
+        .map(str::to_string);
+    Ok(Some(TransactionChange { kind, record_id }))
+}
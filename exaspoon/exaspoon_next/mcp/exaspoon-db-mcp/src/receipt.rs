@@ -0,0 +1,400 @@
+//! Receipts read by a vision-capable model through an OpenAI-compatible chat
+//! completions API, for `parse_receipt`. The model is asked for the
+//! merchant, date, currency, total, tax and line items as JSON; what it
+//! answers is checked rather than trusted, so an unreadable date or currency
+//! is dropped instead of recorded. A read receipt becomes one expense for its
+//! total, or split into one per line item, all carrying the receipt's id so
+//! the lines of one purchase stay together and it is never recorded twice.
+
+use crate::config::{HttpClientConfig, ReceiptConfig};
+use crate::correlation;
+use crate::models::{CreateTransactionInput, Receipt, TransactionDirection};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::Instant;
+use tracing::{debug, error, info, instrument};
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Largest receipt image accepted, in bytes once decoded.
+pub const MAX_RECEIPT_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// MIME type of a base64 image sent without one.
+const DEFAULT_IMAGE_TYPE: &str = "image/jpeg";
+
+/// Differences smaller than this are rounding, not a missing charge.
+const CENT: f64 = 0.005;
+
+const RECEIPT_PROMPT: &str = "You read shop receipts. Answer with one JSON object with the keys \
+    merchant (string), date (YYYY-MM-DD), currency (ISO 4217 code), total (number, the amount \
+    paid), tax (number) and items (array of objects with description, quantity and amount, the \
+    line's total, negative for discounts). Use null for anything the receipt does not show. \
+    Do not guess.";
+
+/// Reads receipts with one vision model.
+pub struct ReceiptReader {
+    http: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+/// An error response of the model's API, with its code when it sent one,
+/// e.g. `model_not_found`.
+#[derive(Debug, Clone)]
+pub struct VisionError {
+    pub status_code: u16,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for VisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vision model error {}: {}",
+            self.status_code, self.message
+        )
+    }
+}
+
+impl std::error::Error for VisionError {}
+
+#[derive(Deserialize)]
+struct Completion {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ChoiceMessage,
+}
+
+#[derive(Deserialize)]
+struct ChoiceMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Rejected {
+    error: RejectedError,
+}
+
+#[derive(Deserialize)]
+struct RejectedError {
+    message: String,
+    #[serde(default)]
+    code: Option<serde_json::Value>,
+}
+
+impl ReceiptReader {
+    pub fn new(config: &ReceiptConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!(
+            "Initializing receipt reading with {} at {}",
+            config.model, config.base_url
+        );
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for the vision model")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            model: config.model.clone(),
+        })
+    }
+
+    /// The model receipts are read with.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// The receipt in the image at `image_url`, a `data:` or `https://` URL.
+    #[instrument(skip(self, image_url))]
+    pub async fn read(&self, image_url: &str) -> Result<Receipt> {
+        let start_time = Instant::now();
+        let body = json!({
+            "model": self.model,
+            "temperature": 0,
+            "response_format": { "type": "json_object" },
+            "messages": [
+                { "role": "system", "content": RECEIPT_PROMPT },
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": "Read this receipt." },
+                        { "type": "image_url", "image_url": { "url": image_url } },
+                    ],
+                },
+            ],
+        });
+        let mut request = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .headers(correlation::headers())
+            .json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .context("vision model request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Vision model request failed ({}): {}", status, body);
+            let (code, message) = match serde_json::from_str::<Rejected>(&body) {
+                Ok(rejected) => (
+                    rejected.error.code.map(|code| match code {
+                        serde_json::Value::String(code) => code,
+                        other => other.to_string(),
+                    }),
+                    rejected.error.message,
+                ),
+                Err(_) => (None, status.to_string()),
+            };
+            return Err(anyhow!(VisionError {
+                status_code: status.as_u16(),
+                code,
+                message,
+            }));
+        }
+        let completion: Completion = response
+            .json()
+            .await
+            .context("failed to parse vision model response")?;
+        let content = completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .context("the vision model answered nothing")?;
+        debug!("Vision model answered: {}", content);
+        let receipt = parse_receipt(&content)?;
+        info!(
+            "Read a receipt with {} items in {:?}",
+            receipt.items.len(),
+            start_time.elapsed()
+        );
+        Ok(receipt)
+    }
+}
+
+/// The receipt in the model's answer, with a date or currency it could not
+/// have meant dropped and a missing total taken from the items.
+pub fn parse_receipt(answer: &str) -> Result<Receipt> {
+    // Some models wrap JSON in a Markdown code fence whatever they are told.
+    let json = answer
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    let mut receipt: Receipt =
+        serde_json::from_str(json).context("the vision model did not answer with a receipt")?;
+    receipt.merchant = receipt
+        .merchant
+        .map(|merchant| merchant.trim().to_string())
+        .filter(|merchant| !merchant.is_empty());
+    receipt.date = receipt
+        .date
+        .filter(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok());
+    receipt.currency = receipt
+        .currency
+        .map(|currency| currency.trim().to_ascii_uppercase())
+        .filter(|currency| currency.len() == 3 && currency.bytes().all(|b| b.is_ascii_uppercase()));
+    receipt
+        .items
+        .retain(|item| item.amount.is_finite() && !item.description.trim().is_empty());
+    if receipt.total.is_none() && !receipt.items.is_empty() {
+        receipt.total = Some(round(receipt.items.iter().map(|item| item.amount).sum()));
+    }
+    Ok(receipt)
+}
+
+/// `image` as a URL the model can read: an `https://` URL as given, or
+/// base64 as a `data:` URL of `mime_type`. Plain `http://` is refused, so the
+/// image cannot be swapped on its way to the model's API.
+pub fn image_url(image: &str, mime_type: Option<&str>) -> Result<String> {
+    let image = image.trim();
+    if image.starts_with("https://") {
+        return Ok(image.to_string());
+    }
+    if image.starts_with("http://") {
+        bail!("a receipt image URL must be https://");
+    }
+    let (mime_type, data) = match image.strip_prefix("data:") {
+        Some(url) => {
+            let (header, data) = url
+                .split_once(',')
+                .context("a data: URL needs a comma before its data")?;
+            let mime_type = header
+                .strip_suffix(";base64")
+                .context("a data: URL image must be base64")?;
+            (mime_type.to_string(), data)
+        }
+        None => (
+            mime_type
+                .unwrap_or(DEFAULT_IMAGE_TYPE)
+                .trim()
+                .to_ascii_lowercase(),
+            image,
+        ),
+    };
+    if !mime_type.starts_with("image/") {
+        bail!("the receipt must be an image, not {mime_type}");
+    }
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    let decoded = STANDARD
+        .decode(&data)
+        .context("the image is not valid base64")?;
+    if decoded.len() > MAX_RECEIPT_IMAGE_BYTES {
+        bail!(
+            "the image is {} bytes; receipts of up to {MAX_RECEIPT_IMAGE_BYTES} bytes are read",
+            decoded.len()
+        );
+    }
+    Ok(format!("data:{mime_type};base64,{data}"))
+}
+
+/// The receipt id in the raw source of an expense recorded from a receipt,
+/// shared by all the expenses of one purchase.
+pub fn receipt_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("receipt")?
+        .get("id")?
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+/// The id of the purchase on `receipt`, in `currency` unless it shows its
+/// own: a hash of its merchant, date, currency and total, which a second
+/// read of the same receipt comes to again.
+pub fn purchase_id(receipt: &Receipt, currency: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        receipt
+            .merchant
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase(),
+        receipt.date.clone().unwrap_or_default(),
+        receipt
+            .currency
+            .as_deref()
+            .unwrap_or(currency)
+            .to_ascii_uppercase(),
+        format!("{:.2}", receipt.total.unwrap_or_default()),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    let digest = hasher.finalize();
+    let hash = digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("receipt-{hash}")
+}
+
+/// The expenses to record for `receipt` in `account_id`, in `currency` unless
+/// the receipt shows its own: one for the total, or with `split` one per
+/// line item and one for what the items leave of the total, such as tax.
+/// Items that come to more than the total, as after a discount, are recorded
+/// as the total alone. Each keeps the [`purchase_id`] and the number of
+/// `lines` the purchase was recorded as in its raw source.
+pub fn transactions(
+    receipt: &Receipt,
+    account_id: &str,
+    currency: &str,
+    split: bool,
+) -> Result<Vec<CreateTransactionInput>> {
+    let total = receipt
+        .total
+        .filter(|total| *total > 0.0)
+        .context("the receipt shows no total to record")?;
+    let merchant = receipt.merchant.as_deref().unwrap_or("Receipt");
+    let occurred_at = receipt
+        .date
+        .as_deref()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .map_or_else(Utc::now, |noon| noon.and_utc())
+        .to_rfc3339_opts(SecondsFormat::Secs, true);
+    let id = purchase_id(receipt, currency);
+    let itemized: f64 = receipt.items.iter().map(|item| item.amount).sum();
+    let whole = !split
+        || receipt.items.is_empty()
+        || receipt.items.iter().any(|item| item.amount <= 0.0)
+        || itemized > total + CENT;
+    let rest = total - itemized;
+    let lines = if whole {
+        1
+    } else {
+        receipt.items.len() + usize::from(rest > CENT)
+    };
+    let expense = |amount: f64, description: String, line: Option<usize>| CreateTransactionInput {
+        account_id: account_id.to_string(),
+        amount: round(amount),
+        currency: receipt.currency.as_deref().unwrap_or(currency).to_string(),
+        direction: TransactionDirection::Expense,
+        occurred_at: occurred_at.clone(),
+        description: Some(description),
+        raw_source: Some(
+            json!({
+                "receipt": {
+                    "id": id,
+                    "merchant": receipt.merchant,
+                    "date": receipt.date,
+                    "total": total,
+                    "lines": lines,
+                },
+                "line": line,
+            })
+            .to_string(),
+        ),
+    };
+
+    if whole {
+        if split {
+            debug!("Recording the receipt whole; its items do not split the total");
+        }
+        return Ok(vec![expense(total, merchant.to_string(), None)]);
+    }
+    let mut expenses: Vec<_> = receipt
+        .items
+        .iter()
+        .enumerate()
+        .map(|(line, item)| {
+            expense(
+                item.amount,
+                format!("{merchant}: {}", item.description.trim()),
+                Some(line),
+            )
+        })
+        .collect();
+    if rest > CENT {
+        expenses.push(expense(
+            rest,
+            format!("{merchant}: tax and other charges"),
+            None,
+        ));
+    }
+    Ok(expenses)
+}
+
+fn round(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
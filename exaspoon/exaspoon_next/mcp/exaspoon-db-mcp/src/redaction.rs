@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use tracing::debug;
+
+const EMAIL_PATTERN: &str = r"(?i)\b[A-Z0-9._%+-]+@[A-Z0-9.-]+\.[A-Z]{2,}\b";
+const IBAN_PATTERN: &str = r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,4})?\b";
+const CARD_PATTERN: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+/// A phone number needs its shape: a `+` and country code, an area code in
+/// parentheses, or separators between the groups. Bare runs of digits are
+/// more often account numbers, order ids or amounts.
+const PHONE_PATTERN: &str = concat!(
+    r"(?:\+\d{1,3}[\s.-]?(?:\(\d{1,4}\)[\s.-]?)?\d{2,4}(?:[\s.-]?\d{2,4}){2,3}",
+    r"|\(\d{3}\)[\s.-]?\d{3}[\s.-]\d{4}",
+    r"|\b\d{3}[\s.-]\d{3}[\s.-]\d{4})\b"
+);
+
+/// Masks personal data (IBANs, card numbers, emails, phone numbers and any
+/// configured patterns) in text that leaves the process, e.g. before it is
+/// sent to an embedding provider.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Regex,
+    replacement: &'static str,
+    luhn_only: bool,
+}
+
+impl Redactor {
+    /// Builds a redactor with the built-in rules followed by `custom_patterns`.
+    pub fn new(custom_patterns: &[String]) -> Result<Self> {
+        // Order matters: IBANs and card numbers would otherwise be partially
+        // consumed by the looser phone number rule.
+        let mut rules = vec![
+            Rule::builtin(EMAIL_PATTERN, "[EMAIL]", false),
+            Rule::builtin(IBAN_PATTERN, "[IBAN]", false),
+            Rule::builtin(CARD_PATTERN, "[CARD]", true),
+            Rule::builtin(PHONE_PATTERN, "[PHONE]", false),
+        ];
+        for pattern in custom_patterns {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("invalid redaction pattern {pattern:?}"))?;
+            rules.push(Rule {
+                pattern: regex,
+                replacement: "[REDACTED]",
+                luhn_only: false,
+            });
+        }
+
+        debug!("Redactor initialized with {} rules", rules.len());
+        Ok(Self { rules })
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(text);
+        for rule in &self.rules {
+            if !rule.pattern.is_match(&result) {
+                continue;
+            }
+            let replaced = rule
+                .pattern
+                .replace_all(&result, |caps: &Captures| {
+                    let matched = &caps[0];
+                    if rule.luhn_only && !passes_luhn(matched) {
+                        matched.to_string()
+                    } else {
+                        rule.replacement.to_string()
+                    }
+                })
+                .into_owned();
+            result = Cow::Owned(replaced);
+        }
+        result
+    }
+}
+
+impl Rule {
+    fn builtin(pattern: &str, replacement: &'static str, luhn_only: bool) -> Self {
+        Self {
+            pattern: Regex::new(pattern).expect("built-in redaction pattern must compile"),
+            replacement,
+            luhn_only,
+        }
+    }
+}
+
+/// Card numbers are only masked when they pass the Luhn checksum, so that
+/// invoice and reference numbers keep their search value.
+fn passes_luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() < 13 {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                *digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,171 @@
+use crate::cancellation;
+use anyhow::Error;
+use reqwest::StatusCode;
+use std::{future::Future, io, time::Duration};
+use tracing::{debug, error, warn};
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 200;
+
+/// Longest single backoff, so a misconfigured base delay cannot stall a tool
+/// call for minutes.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Statuses worth retrying: the server was overloaded, rate limited the
+/// request or could not reach its upstream in time.
+const TRANSIENT_STATUSES: &[StatusCode] = &[
+    StatusCode::REQUEST_TIMEOUT,
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// I/O failures worth retrying: the peer was slow or the connection dropped.
+const TRANSIENT_IO_KINDS: &[io::ErrorKind] = &[
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::ConnectionRefused,
+    io::ErrorKind::ConnectionReset,
+    io::ErrorKind::ConnectionAborted,
+    io::ErrorKind::BrokenPipe,
+    io::ErrorKind::UnexpectedEof,
+];
+
+/// Exponential backoff for idempotent requests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+/// Outcome of a single failed attempt.
+#[derive(Debug)]
+pub enum Failure {
+    /// Worth retrying: gateway errors, rate limits, timeouts, dropped connections.
+    Transient(Error),
+    /// Retrying would give the same answer.
+    Permanent(Error),
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+        }
+    }
+
+    /// Delay before retry number `retry` (zero-based): base, 2×base, 4×base, ...
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1u32 << retry.min(16))
+            .min(MAX_DELAY)
+    }
+
+    /// Runs `call` until it succeeds, fails permanently, the retry budget is
+    /// spent or the tool call it serves is cancelled.
+    pub async fn run<T, F, Fut>(&self, operation: &str, mut call: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Failure>>,
+    {
+        let mut retry = 0;
+        loop {
+            match call().await {
+                Ok(value) => {
+                    if retry > 0 {
+                        debug!(
+                            retries = retry,
+                            "{} succeeded after {} retries", operation, retry
+                        );
+                    }
+                    return Ok(value);
+                }
+                Err(Failure::Transient(err))
+                    if retry < self.max_retries && !cancellation::is_cancelled() =>
+                {
+                    let delay = self.delay(retry);
+                    retry += 1;
+                    warn!(
+                        retry,
+                        max_retries = self.max_retries,
+                        "{} failed, retrying in {:?}: {}",
+                        operation,
+                        delay,
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(Failure::Transient(err) | Failure::Permanent(err)) => {
+                    error!(retries = retry, "{} failed: {}", operation, err);
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+impl Failure {
+    /// Classifies an error from a library that reports failures as text: by
+    /// the `reqwest` or I/O error in its chain, or else by an HTTP status such
+    /// as `503 Service Unavailable` quoted in `message`.
+    pub fn from_message(err: Error, message: &str) -> Self {
+        if is_transient_error(&err) || is_transient_message(message) {
+            Self::Transient(err)
+        } else {
+            Self::Permanent(err)
+        }
+    }
+
+    pub fn from_reqwest(err: Error, source: &reqwest::Error) -> Self {
+        if is_transient_reqwest(source) {
+            Self::Transient(err)
+        } else {
+            Self::Permanent(err)
+        }
+    }
+
+    pub fn from_status(err: Error, status: StatusCode) -> Self {
+        if is_transient_status(status) {
+            Self::Transient(err)
+        } else {
+            Self::Permanent(err)
+        }
+    }
+}
+
+pub fn is_transient_status(status: StatusCode) -> bool {
+    TRANSIENT_STATUSES.contains(&status)
+}
+
+fn is_transient_reqwest(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.status().is_some_and(is_transient_status)
+}
+
+fn is_transient_error(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return is_transient_reqwest(err);
+        }
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| TRANSIENT_IO_KINDS.contains(&err.kind()))
+    })
+}
+
+/// Whether `message` quotes a transient status with its reason phrase, so
+/// that ids or amounts that happen to read `503` do not count.
+fn is_transient_message(message: &str) -> bool {
+    TRANSIENT_STATUSES
+        .iter()
+        .any(|status| message.contains(&status.to_string()))
+}
@@ -1,45 +1,777 @@
 use crate::{
-    embedding::Embedder,
+    auth::AuthContext,
+    call_log::CallLog,
+    cancellation,
+    concurrency::{CallSlots, ConcurrencyLimits},
+    config::ServerIdentity,
+    confirmation::Confirmations,
+    correlation::{self, CORRELATION_ID_FIELD},
+    demo::{self, DEFAULT_DEMO_MONTHS, MAX_DEMO_MONTHS},
+    elicitation,
+    embedding::{Embedder, MeteredEmbedder},
+    fx::{FxClient, FxError, UnknownCurrency},
+    gnucash::{self, Role},
+    gocardless::{
+        self, BookedTransaction, GoCardlessClient, GoCardlessError, OPENBANKING_METADATA_KEY,
+        REQUISITION_LINKED,
+    },
+    i18n::{self, Locale, Message},
+    ledger::{self, MAX_LEDGER_TRANSACTIONS},
+    metrics::Metrics,
     models::{
-        CreateTransactionInput, ListAccountsInput, SearchSimilarInput, UpsertAccountInput,
-        UpsertCategoryInput,
+        AccountOutput, AccountType, AccountsOutput, AggregateSpendingInput, AuditEvent, AuditEventsOutput,
+        AssetPriceOutput, CallRpcInput, CallRpcOutput, CapabilitiesOutput, ConvertCurrencyInput, ConvertCurrencyOutput, GetAssetPriceInput, GetNetWorthInput, NetWorthOutput, CategoryKind, CategoryMatchesOutput, CategoryOutput,
+        CreateTransactionInput, CreateTransactionsInput, DeleteRecordInput, DeletedOutput, DeletedRecord, DependencyHealth,
+        DryRun, EmbeddingIssueKind, EmbeddingMaintenanceAction, EmbeddingMaintenanceInput,
+        BankLink, ExecuteBatchInput, ExecuteBatchOutput, ImportBankStatementInput,
+        GnuCashAccountMapping, GnuCashImportedAs, ImportGnuCashInput, ImportGnuCashOutput,
+        ImportBankStatementOutput, ImportJsonInput, ImportJsonOutput, ImportOfxInput,
+        ImportOfxOutput, ImportQifInput, ImportQifOutput, LinkBankAccountInput,
+        LinkBankAccountOutput, MatchedBy, QifCategoryMapping, RejectedRecord,
+        SkippedBankTransaction, SyncBankAccountInput, SyncBankAccountOutput,
+        LinkOpenBankingAccountInput, LinkOpenBankingAccountOutput, OpenBankingLink,
+        SyncOpenBankingInput, SyncOpenBankingOutput, ParseReceiptInput, ParseReceiptOutput,
+        ExportToSheetInput, ExportToSheetOutput, SheetDestination, SheetReport,
+        Account, Chain, OnchainLink, SkippedTransfer, SyncOnchainInput, SyncOnchainOutput,
+        Exchange, ExchangeLink, SkippedMovement, SyncExchangeInput, SyncExchangeOutput,
+        EmbeddingMaintenanceOutput, EmbeddingRepair, EmbeddingReport, HealthCheckOutput,
+        HealthStatus, ListAccountsInput, ListAuditEventsInput, ListTransactionsInput, Outcome,
+        PeriodSummaryOutput, PingOutput, Planned, Providers, PurgeDeletedInput, PurgeOutput,
+        RecordKind, SearchSimilarInput, SearchTextInput, SeedDemoDataInput, SeedDemoDataOutput,
+        ServerMetricsOutput, SessionDefaults, SessionDefaultsOutput, SkipReason, SkippedEntry, SkippedTransaction, SpendingOutput, SummarizePeriodInput, TableMaintenance,
+        TextMatchesOutput, TransactionFilters, TransactionMatchesOutput, TransactionOutput,
+        Category, ExportLedgerInput, ExportLedgerOutput,
+        ExportYnabInput, ExportYnabOutput, ImportYnabInput, ImportYnabOutput, YnabCategoryExport,
+        YnabCategoryStatus, YnabMapping, YnabSource, SpendingBucket, SpendingGroupBy,
+        Transaction, TransactionDirection, TransactionPageOutput, TransactionsOutput, UpsertAccountInput, UpsertCategoryInput, Written,
+    },
+    exchange::{
+        self,
+        binance::{BinanceClient, BinanceError},
+        coinbase::{CoinbaseClient, CoinbaseError},
+        Movement, EXCHANGE_METADATA_KEY,
+    },
+    ofx::{self, StatementTransaction},
+    onchain::{
+        self,
+        bitcoin::{BlockbookClient, BlockbookError},
+        ethereum::{EtherscanClient, EtherscanError},
+        solana::{SolanaClient, SolanaRpcError},
+        Transfer, ONCHAIN_METADATA_KEY,
+    },
+    plaid::{self, Item, PlaidClient, PlaidError, PlaidTransaction},
+    prices::{CoinGeckoClient, CoinGeckoError, UnknownAsset},
+    progress::Progress,
+    qif,
+    rate_limit::{RateLimitConfig, RateLimiter},
+    receipt::{self, ReceiptReader, VisionError},
+    session::{SessionSlot, Sessions},
+    sheets::{
+        google::{GoogleError, GoogleSheetsClient},
+        notion::{Interrupted, NotionClient, NotionError},
+        MAX_SHEET_ROWS,
     },
-    supabase::Database,
+    shutdown::Drain,
+    statement::{self, StatementEntry},
+    supabase::{
+        page_limit, Database, AUDIT_LOG_TABLE, DEFAULT_TRANSACTION_PAGE, MAX_PAGE_SIZE,
+        MAX_SIMILAR_LIMIT,
+    },
+    timeout::ToolTimeouts,
+    tool_names::ToolNames,
+    validation::{self, Invalid},
+    ynab::{self, YnabClient, YnabError},
 };
 use rmcp::{
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{CallToolResult, Implementation, ProtocolVersion, ServerCapabilities, ServerInfo},
-    tool, tool_handler, tool_router, ErrorData as McpError, ServerHandler,
+    handler::server::{
+        prompt::PromptContext,
+        router::{prompt::PromptRouter, tool::ToolRouter},
+        tool::{cached_schema_for_type, ToolCallContext},
+        wrapper::Parameters,
+    },
+    model::{
+        AnnotateAble, CallToolRequestParam, CallToolResult, CompleteRequestParam, CompleteResult,
+        Content, ErrorCode, GetPromptRequestParam, GetPromptResult, Implementation, JsonObject,
+        ListPromptsResult, ListResourcesResult, ListToolsResult, LoggingLevel,
+        PaginatedRequestParam, RawResource, ReadResourceRequestParam, ReadResourceResult,
+        ResourceContents, ResourceUpdatedNotificationParam, ServerCapabilities, ServerInfo,
+        SetLevelRequestParam, SubscribeRequestParam, Tool, UnsubscribeRequestParam,
+    },
+    service::{NotificationContext, RequestContext},
+    tool, tool_router, ErrorData as McpError, Peer, RoleServer, ServerHandler,
 };
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
 use serde_json::{json, Value};
-use std::sync::Arc;
-use std::time::Instant;
-use tracing::{debug, error, info, instrument, warn};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
+
+mod batch;
+mod capabilities;
+mod completion;
+mod confirmation;
+mod currency;
+mod defaults;
+mod errors;
+mod logging;
+mod missing_fields;
+mod prompts;
+mod protocol;
+mod render;
+mod sheets;
+mod summary;
+
+use logging::ClientLog;
+use missing_fields::{missing_field, missing_fields};
+use render::Render;
+pub use errors::{ErrorKind, ToolError, ERROR_CODE_FIELD};
+pub use logging::DEFAULT_CLIENT_LOG_LEVEL;
+pub use prompts::{DEFAULT_UNUSUAL_SPENDING_DAYS, MAX_UNUSUAL_SPENDING_DAYS};
+pub use protocol::PROTOCOL_VERSION;
+
+/// Most transactions `create_transactions` accepts in one call.
+pub const MAX_BATCH_TRANSACTIONS: usize = 500;
+
+/// Most records `import_json` accepts in one call.
+pub const MAX_JSON_RECORDS: usize = 5000;
+
+/// Error code for writes that clash with an existing row. MCP has no code for
+/// this, so it comes from the JSON-RPC range reserved for servers.
+pub const CONFLICT: ErrorCode = ErrorCode(-32009);
+
+/// Error code for calls over a session's rate limit, from the same range as
+/// [`CONFLICT`].
+pub const RATE_LIMITED: ErrorCode = ErrorCode(-32029);
+
+/// Error code for calls that ran past their tool's time limit.
+pub const TIMED_OUT: ErrorCode = ErrorCode(-32008);
+
+/// Error code for calls whose database or embedding provider is unreachable
+/// or failing fast behind an open circuit.
+pub const UPSTREAM_UNAVAILABLE: ErrorCode = ErrorCode(-32010);
+
+/// Days either side of an OFX statement's dates searched for transactions
+/// already imported from it.
+pub const OFX_DUPLICATE_WINDOW_DAYS: i64 = 7;
+
+/// Days either side of a bank sync's dates searched for transactions
+/// recorded by an earlier sync.
+pub const PLAID_DUPLICATE_WINDOW_DAYS: i64 = 7;
+
+/// Days before the last booking date an open-banking sync read that the next
+/// one reads again, as banks book some transactions days late.
+pub const OPENBANKING_OVERLAP_DAYS: i64 = 3;
+
+/// Days either side of an open-banking sync's dates searched for
+/// transactions recorded by an earlier sync.
+pub const OPENBANKING_DUPLICATE_WINDOW_DAYS: i64 = 7;
+
+/// Days either side of an onchain sync's transfers searched for transfers
+/// recorded by an earlier sync, which read the same block again.
+pub const ONCHAIN_DUPLICATE_WINDOW_DAYS: i64 = 1;
+
+/// Days either side of an exchange sync's movements searched for movements
+/// recorded by an earlier sync, which read the same stretch again.
+pub const EXCHANGE_DUPLICATE_WINDOW_DAYS: i64 = 1;
+
+/// Days either side of a GnuCash book's transactions searched for
+/// transactions recorded by an earlier import of it.
+pub const GNUCASH_DUPLICATE_WINDOW_DAYS: i64 = 1;
+
+/// Days either side of a YNAB budget's transactions searched for
+/// transactions recorded by an earlier import of it.
+pub const YNAB_DUPLICATE_WINDOW_DAYS: i64 = 1;
+
+/// Days either side of a receipt's date searched for an earlier recording
+/// of the same purchase.
+pub const RECEIPT_DUPLICATE_WINDOW_DAYS: i64 = 1;
+
+/// Similarity at which `import_qif` maps a QIF category to an existing one
+/// whose name differs, unless the call asks for another.
+pub const QIF_CATEGORY_MIN_SIMILARITY: f64 = 0.5;
+
+/// Tools that embed text on every call, which also count against the
+/// embedding budget of the rate limit.
+pub const EMBEDDING_TOOLS: &[&str] = &[
+    "create_transaction",
+    "create_transactions",
+    "import_ofx",
+    "import_qif",
+    "import_bank_statement",
+    "import_gnucash",
+    "import_ynab",
+    "import_json",
+    "sync_bank_account",
+    "sync_openbanking",
+    "sync_onchain",
+    "sync_exchange",
+    "execute_batch",
+    "search_similar_transactions",
+    "search_similar_categories",
+    "upsert_category",
+    "upsert_account",
+    "embedding_maintenance",
+    "seed_demo_data",
+    "health_check",
+];
+
+
+/// Tools only listed once [`ExaspoonDbServer::with_plaid`] is called.
+pub const BANK_TOOLS: &[&str] = &["link_bank_account", "sync_bank_account"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_gocardless`] is called.
+pub const OPENBANKING_TOOLS: &[&str] = &["link_openbanking_account", "sync_openbanking"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_etherscan`],
+/// [`ExaspoonDbServer::with_blockbook`] or [`ExaspoonDbServer::with_solana`]
+/// is called.
+pub const ONCHAIN_TOOLS: &[&str] = &["sync_onchain"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_binance`] or
+/// [`ExaspoonDbServer::with_coinbase`] is called.
+pub const EXCHANGE_TOOLS: &[&str] = &["sync_exchange"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_fx`] is called.
+pub const FX_TOOLS: &[&str] = &["convert_currency"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_prices`] is called.
+pub const PRICE_TOOLS: &[&str] = &["get_asset_price"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_receipts`] is called.
+pub const RECEIPT_TOOLS: &[&str] = &["parse_receipt"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_google_sheets`] or
+/// [`ExaspoonDbServer::with_notion`] is called.
+pub const SHEET_TOOLS: &[&str] = &["export_to_sheet"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_ynab`] is called.
+pub const YNAB_TOOLS: &[&str] = &["export_ynab"];
+
+/// Tools only listed once [`ExaspoonDbServer::with_admin_tools`] is called.
+pub const ADMIN_TOOLS: &[&str] = &["purge_deleted", "embedding_maintenance"];
+
+/// A set of tools mounted, or left out, as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolGroup {
+    /// Writing, listing, searching and deleting accounts, categories and
+    /// transactions, the [`RECEIPT_TOOLS`], the [`YNAB_TOOLS`], and
+    /// `set_session_defaults`.
+    Core,
+    /// `aggregate_spending`, `summarize_period`, `get_net_worth`,
+    /// `list_audit_events`, the [`FX_TOOLS`], the [`PRICE_TOOLS`], the
+    /// [`SHEET_TOOLS`] and `export_ledger`.
+    Analytics,
+    /// The [`ADMIN_TOOLS`], `seed_demo_data` and `call_rpc`, which stay behind
+    /// their own settings.
+    Maintenance,
+    /// `ping`, `health_check`, `server_metrics` and `describe_capabilities`.
+    Monitoring,
+}
+
+impl ToolGroup {
+    pub const ALL: [Self; 4] = [Self::Core, Self::Analytics, Self::Maintenance, Self::Monitoring];
+
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Core => "core",
+            Self::Analytics => "analytics",
+            Self::Maintenance => "maintenance",
+            Self::Monitoring => "monitoring",
+        }
+    }
+
+    fn router(self) -> ToolRouter<ExaspoonDbServer> {
+        match self {
+            Self::Core => ExaspoonDbServer::core_tools(),
+            Self::Analytics => ExaspoonDbServer::analytics_tools(),
+            Self::Maintenance => ExaspoonDbServer::maintenance_tools(),
+            Self::Monitoring => ExaspoonDbServer::monitoring_tools(),
+        }
+    }
+}
+
+impl FromStr for ToolGroup {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|group| group.as_ref().eq_ignore_ascii_case(value.trim()))
+            .ok_or_else(|| anyhow::anyhow!("unknown tool group: {value}"))
+    }
+}
+
+/// Rows `embedding_maintenance` changes per call unless asked otherwise.
+pub const DEFAULT_MAINTENANCE_BATCH: u32 = 100;
+/// Most rows one `embedding_maintenance` call may change.
+pub const MAX_MAINTENANCE_BATCH: u32 = 500;
+/// Ids listed per issue in an `embedding_maintenance` report.
+const MAINTENANCE_SAMPLE_IDS: usize = 10;
+
+/// Resource holding the latest transactions. Clients that subscribe to it are
+/// notified whenever the transactions table changes.
+pub const TRANSACTIONS_RESOURCE_URI: &str = "exaspoon://transactions";
+
+/// Clients subscribed to a resource, by session id.
+type Subscribers = HashMap<String, Peer<RoleServer>>;
+
+/// A session of a server serving many clients, which ends when dropped.
+struct OpenSession {
+    id: String,
+    subscriptions: Arc<Mutex<HashMap<String, Subscribers>>>,
+    _slot: SessionSlot,
+}
+
+impl Drop for OpenSession {
+    fn drop(&mut self) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.retain(|_, peers| {
+            peers.remove(&self.id);
+            !peers.is_empty()
+        });
+        debug!("Closed session {}", self.id);
+    }
+}
 
 #[derive(Clone)]
 pub struct ExaspoonDbServer {
     supabase: Arc<dyn Database>,
     embedder: Arc<dyn Embedder>,
+    /// Identifies this server instance's connection in the audit log.
+    session_id: String,
+    /// Clients to notify per subscribed resource URI, by session id. Shared
+    /// by every session of an HTTP server.
+    subscriptions: Arc<Mutex<HashMap<String, Subscribers>>>,
+    /// Sessions open on this server, shared by all of them.
+    sessions: Arc<Sessions>,
+    /// Ends this session once every clone of a [`Self::new_session`] is gone.
+    open_session: Option<Arc<OpenSession>>,
+    /// Client that receives log notifications, and at what level.
+    client_log: Arc<Mutex<ClientLog>>,
+    /// What calls of this session fall back to for fields they leave out.
+    session_defaults: Arc<Mutex<SessionDefaults>>,
+    rate_limiter: Arc<RateLimiter>,
+    identity: Arc<ServerIdentity>,
+    /// The configured backends, reported by `ping`.
+    providers: Option<Arc<Providers>>,
+    started_at: Instant,
+    /// Latency and outcome of each tool call.
+    tool_metrics: Arc<Metrics>,
+    /// Latency and outcome of each embedding request.
+    embedding_metrics: Arc<Metrics>,
+    timeouts: Arc<ToolTimeouts>,
+    slots: Arc<CallSlots>,
+    /// Admits tool calls until the server starts shutting down.
+    drain: Arc<Drain>,
+    /// Records every tool call, redacted, when configured.
+    call_log: Option<Arc<CallLog>>,
+    admin_tools: bool,
+    demo_seed: bool,
+    /// Makes every mutating call a dry run, whatever its `dry_run` says.
+    dry_run: bool,
+    /// Tokens of previewed calls of [`needs_confirmation`] tools, when they
+    /// need one.
+    confirmations: Option<Arc<Confirmations>>,
+    /// Hides every tool not annotated as read-only.
+    read_only: bool,
+    /// Postgres functions `call_rpc` may invoke.
+    rpc_allowlist: Arc<[String]>,
+    /// Where linked bank accounts are synced from.
+    plaid: Option<Arc<PlaidClient>>,
+    /// Where accounts at European banks are synced from.
+    gocardless: Option<Arc<GoCardlessClient>>,
+    /// Where the transfers of Ethereum addresses are read from.
+    etherscan: Option<Arc<EtherscanClient>>,
+    /// Where the transactions of Bitcoin addresses are read from.
+    blockbook: Option<Arc<BlockbookClient>>,
+    /// Where the transfers of Solana wallets are read from.
+    solana: Option<Arc<SolanaClient>>,
+    /// Where the movements of Binance accounts are read from.
+    binance: Option<Arc<BinanceClient>>,
+    /// Where the transactions of Coinbase wallets are read from.
+    coinbase: Option<Arc<CoinbaseClient>>,
+    /// Where exchange rates into the base currency come from.
+    fx: Option<Arc<FxClient>>,
+    /// Where the prices of crypto assets come from.
+    prices: Option<Arc<CoinGeckoClient>>,
+    /// The vision model receipts are read with.
+    receipts: Option<Arc<ReceiptReader>>,
+    /// Where `export_to_sheet` writes Google Sheets.
+    google_sheets: Option<Arc<GoogleSheetsClient>>,
+    /// Where `export_to_sheet` writes Notion databases.
+    notion: Option<Arc<NotionClient>>,
+    /// Where `import_ynab` reads budgets from and `export_ynab` writes them.
+    ynab: Option<Arc<YnabClient>>,
+    /// Tool names, or `*`-terminated prefixes, hidden from clients.
+    disabled_tools: Arc<[String]>,
+    /// Groups whose tools are mounted at all.
+    tool_groups: Arc<[ToolGroup]>,
+    /// Names clients list and call the tools by.
+    tool_names: Arc<ToolNames>,
+    /// Language of user-facing messages for clients that ask for none.
+    locale: Locale,
     tool_router: ToolRouter<Self>,
+    prompt_router: PromptRouter<Self>,
 }
 
-#[tool_router]
+#[tool_router(router = core_tools)]
 impl ExaspoonDbServer {
     pub fn new(supabase: Arc<dyn Database>, embedder: Arc<dyn Embedder>) -> Self {
-        Self {
+        let embedding_metrics = Arc::new(Metrics::new());
+        let mut server = Self {
             supabase,
-            embedder,
-            tool_router: Self::tool_router(),
+            embedder: Arc::new(MeteredEmbedder::new(embedder, embedding_metrics.clone())),
+            session_id: uuid::Uuid::new_v4().to_string(),
+            subscriptions: Arc::default(),
+            sessions: Arc::default(),
+            open_session: None,
+            client_log: Arc::default(),
+            session_defaults: Arc::default(),
+            rate_limiter: Arc::new(RateLimiter::new(RateLimitConfig::default())),
+            identity: Arc::default(),
+            providers: None,
+            started_at: Instant::now(),
+            tool_metrics: Arc::default(),
+            embedding_metrics,
+            timeouts: Arc::default(),
+            slots: Arc::new(CallSlots::new(ConcurrencyLimits::default())),
+            drain: Arc::default(),
+            call_log: None,
+            admin_tools: false,
+            demo_seed: false,
+            dry_run: false,
+            confirmations: None,
+            read_only: false,
+            rpc_allowlist: Arc::from([]),
+            plaid: None,
+            gocardless: None,
+            etherscan: None,
+            blockbook: None,
+            solana: None,
+            binance: None,
+            coinbase: None,
+            fx: None,
+            prices: None,
+            receipts: None,
+            google_sheets: None,
+            notion: None,
+            ynab: None,
+            disabled_tools: Arc::from([]),
+            tool_groups: Arc::from(ToolGroup::ALL),
+            tool_names: Arc::default(),
+            locale: Locale::default(),
+            tool_router: ToolRouter::new(),
+            prompt_router: Self::prompt_router(),
+        };
+        server.tool_router = server.routes();
+        server
+    }
+
+    /// A server for another client session: it shares the backends, metrics,
+    /// limits on concurrent calls, call log and resource subscriptions with
+    /// this one, but starts with its own session id, defaults, log level,
+    /// rate limit budgets and confirmation tokens. `None` while the server
+    /// already holds as many sessions as [`Self::with_max_sessions`] allows.
+    ///
+    /// The session ends when the last clone of it is dropped, which also
+    /// drops its resource subscriptions.
+    pub fn new_session(&self) -> Option<Self> {
+        let Some(slot) = self.sessions.open() else {
+            warn!(
+                "Refused a client session: {} sessions already open",
+                self.sessions.active()
+            );
+            return None;
+        };
+        let mut session = self.clone();
+        session.session_id = uuid::Uuid::new_v4().to_string();
+        session.open_session = Some(Arc::new(OpenSession {
+            id: session.session_id.clone(),
+            subscriptions: self.subscriptions.clone(),
+            _slot: slot,
+        }));
+        session.client_log = Arc::default();
+        session.session_defaults = Arc::default();
+        session.rate_limiter = Arc::new(RateLimiter::new(self.rate_limiter.config()));
+        session.confirmations = self
+            .confirmations
+            .as_ref()
+            .map(|confirmations| Arc::new(Confirmations::new(confirmations.window())));
+        debug!(
+            "Opened session {} ({} open)",
+            session.session_id,
+            self.sessions.active()
+        );
+        Some(session)
+    }
+
+    /// Whether [`Self::new_session`] would be refused right now.
+    pub fn sessions_full(&self) -> bool {
+        self.sessions.is_full()
+    }
+
+    /// Caps the client sessions open at once; zero means no limit.
+    pub fn with_max_sessions(mut self, limit: usize) -> Self {
+        self.sessions = Arc::new(Sessions::new(limit));
+        self
+    }
+
+    /// Also exposes the destructive [`ADMIN_TOOLS`].
+    pub fn with_admin_tools(mut self) -> Self {
+        self.admin_tools = true;
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes `seed_demo_data`.
+    pub fn with_demo_seed(mut self) -> Self {
+        self.demo_seed = true;
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Replaces the default per-session tool call budgets.
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(config));
+        self
+    }
+
+    /// Replaces the default time limits on tool calls.
+    pub fn with_timeouts(mut self, timeouts: ToolTimeouts) -> Self {
+        self.timeouts = Arc::new(timeouts);
+        self
+    }
+
+    /// Replaces the name, version and instructions reported to clients.
+    pub fn with_identity(mut self, identity: ServerIdentity) -> Self {
+        self.identity = Arc::new(identity);
+        self
+    }
+
+    /// Names the backends `ping` reports.
+    pub fn with_providers(mut self, providers: Providers) -> Self {
+        self.providers = Some(Arc::new(providers));
+        self
+    }
+
+    /// Replaces the default caps on concurrent tool calls.
+    pub fn with_concurrency_limits(mut self, limits: ConcurrencyLimits) -> Self {
+        self.slots = Arc::new(CallSlots::new(limits));
+        self
+    }
+
+    /// Records every tool call to `call_log`.
+    pub fn with_call_log(mut self, call_log: CallLog) -> Self {
+        self.call_log = Some(Arc::new(call_log));
+        self
+    }
+
+    /// Turns every call of a mutating tool into a dry run.
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Makes the tools annotated as destructive preview their effect and
+    /// return a token, and only run when called again with it within
+    /// `window`. See [`needs_confirmation`].
+    pub fn with_confirmation(mut self, window: Duration) -> Self {
+        self.confirmations = Some(Arc::new(Confirmations::new(window)));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Hides every tool that can write, leaving only those annotated as
+    /// read-only, whatever the other switches enable.
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes `call_rpc` for the given Postgres functions.
+    pub fn with_rpc_allowlist(mut self, functions: Vec<String>) -> Self {
+        self.rpc_allowlist = functions.into();
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`BANK_TOOLS`], which link accounts to bank logins
+    /// through `plaid` and sync their transactions.
+    pub fn with_plaid(mut self, plaid: PlaidClient) -> Self {
+        self.plaid = Some(Arc::new(plaid));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`OPENBANKING_TOOLS`], which link accounts to bank
+    /// accounts at European banks through `gocardless` and sync them.
+    pub fn with_gocardless(mut self, gocardless: GoCardlessClient) -> Self {
+        self.gocardless = Some(Arc::new(gocardless));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`ONCHAIN_TOOLS`], which sync the transfers of onchain
+    /// accounts' Ethereum addresses through `etherscan`.
+    pub fn with_etherscan(mut self, etherscan: EtherscanClient) -> Self {
+        self.etherscan = Some(Arc::new(etherscan));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`ONCHAIN_TOOLS`], which sync the transactions of onchain
+    /// accounts' Bitcoin addresses and xpubs through `blockbook`.
+    pub fn with_blockbook(mut self, blockbook: BlockbookClient) -> Self {
+        self.blockbook = Some(Arc::new(blockbook));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`ONCHAIN_TOOLS`], which sync the SOL and SPL token
+    /// transfers of onchain accounts' Solana wallets through `solana`.
+    pub fn with_solana(mut self, solana: SolanaClient) -> Self {
+        self.solana = Some(Arc::new(solana));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`EXCHANGE_TOOLS`], which sync the deposits, withdrawals
+    /// and trades of exchange accounts at Binance through `binance`.
+    pub fn with_binance(mut self, binance: BinanceClient) -> Self {
+        self.binance = Some(Arc::new(binance));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`EXCHANGE_TOOLS`], which sync the transactions of
+    /// exchange accounts at Coinbase through `coinbase`.
+    pub fn with_coinbase(mut self, coinbase: CoinbaseClient) -> Self {
+        self.coinbase = Some(Arc::new(coinbase));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`FX_TOOLS`] and adds totals in the base currency to the
+    /// reports of `aggregate_spending` and `summarize_period`, at the rates
+    /// of `fx`.
+    pub fn with_fx(mut self, fx: FxClient) -> Self {
+        self.fx = Some(Arc::new(fx));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`PRICE_TOOLS`] and, with [`Self::with_fx`], values the
+    /// crypto assets the exchange rates leave out of the base currency totals
+    /// at the prices of `prices`.
+    pub fn with_prices(mut self, prices: CoinGeckoClient) -> Self {
+        self.prices = Some(Arc::new(prices));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`RECEIPT_TOOLS`], which read receipt images with the
+    /// vision model of `receipts`.
+    pub fn with_receipts(mut self, receipts: ReceiptReader) -> Self {
+        self.receipts = Some(Arc::new(receipts));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`SHEET_TOOLS`], which can then export to Google Sheets
+    /// through `google_sheets`.
+    pub fn with_google_sheets(mut self, google_sheets: GoogleSheetsClient) -> Self {
+        self.google_sheets = Some(Arc::new(google_sheets));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`SHEET_TOOLS`], which can then export to Notion through
+    /// `notion`.
+    pub fn with_notion(mut self, notion: NotionClient) -> Self {
+        self.notion = Some(Arc::new(notion));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Exposes the [`YNAB_TOOLS`], and lets `import_ynab` read budgets
+    /// through the API of `ynab` rather than only from exports.
+    pub fn with_ynab(mut self, ynab: YnabClient) -> Self {
+        self.ynab = Some(Arc::new(ynab));
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// Hides the tools matching `patterns`, each a tool name or a prefix
+    /// ending in `*` such as `delete_*`, whatever the other switches enable.
+    pub fn with_disabled_tools(mut self, patterns: Vec<String>) -> Self {
+        let tools = ToolGroup::ALL
+            .into_iter()
+            .flat_map(|group| group.router().list_all())
+            .collect::<Vec<_>>();
+        for pattern in &patterns {
+            if !tools.iter().any(|tool| tool_matches(pattern, &tool.name)) {
+                warn!("Disabled tool pattern {:?} matches no tool", pattern);
+            }
+        }
+        self.disabled_tools = patterns.into();
+        self.tool_router = self.routes();
+        self
+    }
+
+    /// The name clients call `tool` by.
+    pub fn tool_name(&self, tool: &str) -> String {
+        self.tool_names.exposed(tool)
+    }
+
+    /// Lists and calls the tools by `names` instead of their own.
+    pub fn with_tool_names(mut self, names: ToolNames) -> Self {
+        let tools = ToolGroup::ALL
+            .into_iter()
+            .flat_map(|group| group.router().list_all())
+            .map(|tool| tool.name.to_string())
+            .collect::<Vec<_>>();
+        for aliased in names.aliased() {
+            if !tools.iter().any(|tool| tool == aliased) {
+                warn!("Tool alias given for {:?}, which is no tool", aliased);
+            }
         }
+        for tool in &tools {
+            if names.original(&names.exposed(tool)).as_deref() != Some(tool.as_str()) {
+                warn!("{} cannot be called: {} is another tool's alias", tool, names.exposed(tool));
+            }
+        }
+        self.tool_names = Arc::new(names);
+        self
+    }
+
+    /// Writes user-facing messages in `locale` unless the client asks for
+    /// another.
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
     }
 
-    #[tool(description = "Insert a transaction row, automatically embedding the description.")]
+    /// Mounts only the tools of `groups`; every group is mounted by default.
+    pub fn with_tool_groups(mut self, groups: Vec<ToolGroup>) -> Self {
+        self.tool_groups = groups.into();
+        self.tool_router = self.routes();
+        self
+    }
+
+    #[tool(description = "Insert a transaction row, automatically embedding the description.", annotations(destructive_hint = false), output_schema = cached_schema_for_type::<Outcome<TransactionOutput>>())]
     #[instrument(skip(self), fields(account_id = %input.account_id, amount = %input.amount, currency = %input.currency))]
     pub async fn create_transaction(
         &self,
-        Parameters(input): Parameters<CreateTransactionInput>,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<CreateTransactionInput>>,
     ) -> Result<CallToolResult, McpError> {
         let start_time = Instant::now();
+        let input = self.fill_missing_fields(input).await?;
         info!("Creating transaction for account: {}", input.account_id);
         
         let embedding = self
@@ -48,8 +780,14 @@ impl ExaspoonDbServer {
             .await
             .map_err(|err| {
                 error!("Failed to generate transaction embedding: {}", err);
-                internal_error("generate transaction embedding", err)
+                ToolError::failed("generate transaction embedding", err)
             })?;
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; transaction not inserted");
+            return Ok(dry_run_result(TransactionOutput {
+                transaction: Written::Planned(planned(input, embedding.as_deref())),
+            }));
+        }
 
         let record = self
             .supabase
@@ -57,331 +795,7033 @@ impl ExaspoonDbServer {
             .await
             .map_err(|err| {
                 error!("Failed to insert transaction: {}", err);
-                internal_error("insert transaction", err)
+                ToolError::failed("insert transaction", err)
             })?;
 
         let duration = start_time.elapsed();
         info!("Transaction created successfully in {:?}", duration);
         debug!("Transaction record: {:?}", record);
         
-        Ok(success(json!({ "transaction": record })))
+        self.audit("create_transaction", input_hash(&input), [Some(record.id.as_str())])
+            .await;
+
+        Ok(success(TransactionOutput {
+            transaction: Written::Row(record),
+        }))
     }
 
-    #[tool(description = "Semantic nearest-neighbor search over historical transactions.")]
-    #[instrument(skip(self), fields(query = %input.query, limit = ?input.limit))]
-    pub async fn search_similar_transactions(
+    #[tool(description = "Insert up to 500 transactions in one request, embedding each description.", annotations(destructive_hint = false), output_schema = cached_schema_for_type::<Outcome<TransactionsOutput>>())]
+    #[instrument(skip(self, input), fields(count = input.transactions.len()))]
+    pub async fn create_transactions(
         &self,
-        Parameters(input): Parameters<SearchSimilarInput>,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<CreateTransactionsInput>>,
     ) -> Result<CallToolResult, McpError> {
         let start_time = Instant::now();
-        info!("Searching for similar transactions with query: {}", input.query);
-        
-        if input.query.trim().is_empty() {
-            warn!("Empty query provided for transaction search");
-            return Err(McpError::invalid_params(
-                "query must not be empty",
-                Some(json!({ "field": "query" })),
-            ));
+        info!("Creating {} transactions", input.transactions.len());
+
+        if input.transactions.is_empty() || input.transactions.len() > MAX_BATCH_TRANSACTIONS {
+            warn!("Rejected batch of {} transactions", input.transactions.len());
+            return Err(ToolError::invalid(
+                format!("transactions must contain between 1 and {MAX_BATCH_TRANSACTIONS} items"),
+                "transactions",
+            )
+            .into());
         }
 
-        let embedding = self
-            .embedder
-            .embed(input.query.trim())
-            .await
-            .map_err(|err| {
-                error!("Failed to embed query text: {}", err);
-                internal_error("embed query text", err)
-            })?;
+        for (index, transaction) in input.transactions.iter().enumerate() {
+            if let Some(field) = missing_fields(transaction).first() {
+                warn!("Rejected batch: transaction {} has no {}", index, field);
+                return Err(missing_field(&format!("transactions[{index}].{field}")));
+            }
+        }
 
-        let matches = self
+        let hash = input_hash(&input);
+        let total = input.transactions.len();
+        let rows = self.embed_transactions(input.transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} of {} transactions; none inserted",
+                rows.len(),
+                total
+            );
+            self.log_to_client(
+                LoggingLevel::Warning,
+                "Import cancelled; no transactions inserted",
+                json!({ "embedded": rows.len(), "requested": total }),
+            )
+            .await;
+            return Ok(batch_result(TransactionsOutput {
+                transactions: Vec::new(),
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(TransactionsOutput { transactions }));
+        }
+
+        let records = self
             .supabase
-            .search_similar_transactions(embedding, input.limit)
+            .insert_transactions(&rows)
             .await
             .map_err(|err| {
-                error!("Failed to search similar transactions: {}", err);
-                internal_error("search similar transactions", err)
+                error!("Failed to insert transactions: {}", err);
+                ToolError::failed("insert transactions", err)
             })?;
 
         let duration = start_time.elapsed();
-        info!("Found {} similar transactions in {:?}", matches.len(), duration);
-        debug!("Transaction matches: {:?}", matches);
+        info!("Created {} transactions in {:?}", records.len(), duration);
 
-        Ok(success(json!({ "matches": matches })))
+        self.audit(
+            "create_transactions",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(TransactionsOutput {
+            transactions: records.into_iter().map(Written::Row).collect(),
+        }))
     }
 
-    #[tool(description = "Create or update a category with embeddings for semantic search.")]
-    #[instrument(skip(self), fields(category_name = %input.name, kind = ?input.kind))]
-    pub async fn upsert_category(
+    #[tool(description = "Import a bank or credit card statement downloaded as OFX or QFX into an account, embedding each description. Transactions whose FITID (the bank's transaction id) is already recorded for the account are skipped, so overlapping downloads can be imported safely.", annotations(destructive_hint = false, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<ImportOfxOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn import_ofx(
         &self,
-        Parameters(input): Parameters<UpsertCategoryInput>,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ImportOfxInput>>,
     ) -> Result<CallToolResult, McpError> {
         let start_time = Instant::now();
-        info!("Upserting category: {}", input.name);
-        
-        let description_source = input.description.as_deref().unwrap_or(input.name.as_str());
-        let embedding = self
-            .embedder
-            .embed(description_source)
-            .await
-            .map_err(|err| {
-                error!("Failed to generate category embedding: {}", err);
-                internal_error("generate category embedding", err)
-            })?;
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let statement = ofx::parse(&input.ofx).map_err(|err| {
+            warn!("Rejected OFX statement: {:#}", err);
+            McpError::from(ToolError::invalid(format!("{err:#}"), "ofx"))
+        })?;
+        info!("Importing {} statement transactions", statement.len());
 
-        let category = self
-            .supabase
-            .upsert_category(&input, Some(embedding))
-            .await
-            .map_err(|err| {
-                error!("Failed to upsert category: {}", err);
-                internal_error("upsert category", err)
-            })?;
+        let mut seen = self.recorded_fitids(&input.account_id, &statement).await?;
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        for line in &statement {
+            let reason = if line.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if !seen.insert(line.fitid.clone()) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedTransaction {
+                    fitid: line.fitid.clone(),
+                    reason,
+                }),
+                None => transactions.push(line.to_input(&input.account_id, &input.currency)),
+            }
+        }
+        if transactions.iter().any(|transaction| transaction.currency.is_empty()) {
+            return Err(missing_field("currency"));
+        }
+        info!(
+            "{} new statement transactions, {} skipped",
+            transactions.len(),
+            skipped.len()
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} statement transactions; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(ImportOfxOutput {
+                transactions: Vec::new(),
+                skipped,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} statement transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(ImportOfxOutput {
+                transactions,
+                skipped,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert statement transactions: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
 
         let duration = start_time.elapsed();
-        info!("Category upserted successfully in {:?}", duration);
-        debug!("Category record: {:?}", category);
+        info!(
+            "Imported {} statement transactions in {:?}",
+            records.len(),
+            duration
+        );
+
+        self.audit(
+            "import_ofx",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
 
-        Ok(success(json!({ "category": category })))
+        Ok(success(ImportOfxOutput {
+            transactions: records.into_iter().map(Written::Row).collect(),
+            skipped,
+        }))
     }
 
-    #[tool(description = "Semantic search across categories by embedding query.")]
-    #[instrument(skip(self), fields(query = %input.query, limit = ?input.limit))]
-    pub async fn search_similar_categories(
+    #[tool(description = "Import a QIF file exported by Quicken or another personal finance program into an account, embedding each description. Each QIF category is mapped to an existing category with the same name or, failing that, the most similar one at or above min_similarity, and its transactions are filed under it. Returns the transactions and how each QIF category was mapped.", annotations(destructive_hint = false), output_schema = cached_schema_for_type::<Outcome<ImportQifOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn import_qif(
         &self,
-        Parameters(input): Parameters<SearchSimilarInput>,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ImportQifInput>>,
     ) -> Result<CallToolResult, McpError> {
         let start_time = Instant::now();
-        info!("Searching for similar categories with query: {}", input.query);
-        
-        if input.query.trim().is_empty() {
-            warn!("Empty query provided for category search");
-            return Err(McpError::invalid_params(
-                "query must not be empty",
-                Some(json!({ "field": "query" })),
-            ));
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        if input.currency.trim().is_empty() {
+            return Err(missing_field("currency"));
         }
+        let min_similarity = input.min_similarity.unwrap_or(QIF_CATEGORY_MIN_SIMILARITY);
+        if !(0.0..=1.0).contains(&min_similarity) {
+            return Err(ToolError::invalid(
+                "min_similarity must be between 0 and 1",
+                "min_similarity",
+            )
+            .into());
+        }
+        let decimals = validation::currency_decimals(&input.currency);
+        let records = qif::parse(&input.qif, input.day_first, decimals).map_err(|err| {
+            warn!("Rejected QIF file: {:#}", err);
+            McpError::from(ToolError::invalid(format!("{err:#}"), "qif"))
+        })?;
+        let (records, zero): (Vec<_>, Vec<_>) =
+            records.into_iter().partition(|record| record.amount != 0.0);
+        info!(
+            "Importing {} QIF transactions, {} of zero amount skipped",
+            records.len(),
+            zero.len()
+        );
 
-        let embedding = self
-            .embedder
-            .embed(input.query.trim())
-            .await
-            .map_err(|err| {
-                error!("Failed to embed query text: {}", err);
-                internal_error("embed query text", err)
-            })?;
+        let categories = self.map_qif_categories(&records, min_similarity).await?;
+        let filed = records
+            .iter()
+            .map(|record| {
+                let mapping = categories
+                    .iter()
+                    .find(|mapping| Some(&mapping.qif_category) == record.category.as_ref());
+                mapping
+                    .and_then(|mapping| mapping.category.as_ref())
+                    .map(|category| category.id.clone())
+            })
+            .collect::<Vec<_>>();
+        let transactions = records
+            .iter()
+            .map(|record| record.to_input(&input.account_id, &input.currency))
+            .collect();
 
-        let matches = self
-            .supabase
-            .search_similar_categories(embedding, input.limit)
-            .await
-            .map_err(|err| {
-                error!("Failed to search similar categories: {}", err);
-                internal_error("search similar categories", err)
-            })?;
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        let result = |transactions, categories| ImportQifOutput {
+            transactions,
+            categories,
+            skipped_zero_amount: zero.len(),
+        };
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} QIF transactions; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(result(Vec::new(), categories)));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} QIF transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(result(transactions, categories)));
+        }
+
+        let transactions = self.insert_filed(&rows, &filed).await?;
 
         let duration = start_time.elapsed();
-        info!("Found {} similar categories in {:?}", matches.len(), duration);
-        debug!("Category matches: {:?}", matches);
+        info!(
+            "Imported {} QIF transactions in {:?}",
+            transactions.len(),
+            duration
+        );
 
-        Ok(success(json!({ "matches": matches })))
+        self.audit(
+            "import_qif",
+            hash,
+            transactions.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(result(
+            transactions.into_iter().map(Written::Row).collect(),
+            categories,
+        )))
     }
 
-    #[tool(description = "List accounts with optional filters by type or name substring.")]
-    #[instrument(skip(self), fields(account_type = ?input.r#type, search = ?input.search))]
-    pub async fn list_accounts(
+    #[tool(description = "Import a bank statement exported as MT940 or CAMT.053 XML, the formats European banks offer, into an account, embedding each description. Entries already imported from an earlier statement, pending entries and entries of zero amount are skipped, so overlapping statements can be imported safely.", annotations(destructive_hint = false, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<ImportBankStatementOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id, format = ?input.format))]
+    pub async fn import_bank_statement(
         &self,
-        Parameters(input): Parameters<ListAccountsInput>,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ImportBankStatementInput>>,
     ) -> Result<CallToolResult, McpError> {
         let start_time = Instant::now();
-        info!("Listing accounts with filters: type={:?}, search={:?}", input.r#type, input.search);
-        
-        let accounts = self
-            .supabase
-            .list_accounts(&input)
-            .await
-            .map_err(|err| {
-                error!("Failed to list accounts: {}", err);
-                internal_error("list accounts", err)
-            })?;
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let parsed = match input.format {
+            Some(format) => Ok(format),
+            None => statement::detect(&input.statement),
+        }
+        .and_then(|format| Ok((format, statement::parse(&input.statement, format)?)));
+        let (format, entries) = parsed.map_err(|err| {
+            warn!("Rejected bank statement: {:#}", err);
+            McpError::from(ToolError::invalid(format!("{err:#}"), "statement"))
+        })?;
+        info!(
+            "Importing {} {} statement entries",
+            entries.len(),
+            format.as_ref()
+        );
+
+        let mut recorded = self.recorded_entries(&input.account_id, &entries).await?;
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        for entry in &entries {
+            let reason = if entry.pending {
+                Some(SkipReason::Pending)
+            } else if entry.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else {
+                match recorded.get_mut(&entry.raw) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        Some(SkipReason::Duplicate)
+                    }
+                    _ => None,
+                }
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedEntry {
+                    reference: entry.reference.clone(),
+                    booked_at: entry.booked_at.clone(),
+                    amount: entry.amount,
+                    reason,
+                }),
+                None => transactions.push(entry.to_input(&input.account_id, &input.currency)),
+            }
+        }
+        if transactions.iter().any(|transaction| transaction.currency.is_empty()) {
+            return Err(missing_field("currency"));
+        }
+        info!(
+            "{} new statement entries, {} skipped",
+            transactions.len(),
+            skipped.len()
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} statement entries; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(ImportBankStatementOutput {
+                format,
+                transactions: Vec::new(),
+                skipped,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} statement entries not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(ImportBankStatementOutput {
+                format,
+                transactions,
+                skipped,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert statement entries: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
 
         let duration = start_time.elapsed();
-        info!("Found {} accounts in {:?}", accounts.len(), duration);
-        debug!("Account list: {:?}", accounts);
+        info!(
+            "Imported {} statement entries in {:?}",
+            records.len(),
+            duration
+        );
+
+        self.audit(
+            "import_bank_statement",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
 
-        Ok(success(json!({ "accounts": accounts })))
+        Ok(success(ImportBankStatementOutput {
+            format,
+            transactions: records.into_iter().map(Written::Row).collect(),
+            skipped,
+        }))
     }
 
-    #[tool(description = "Create or update an account keyed by name+type.")]
-    #[instrument(skip(self), fields(account_name = %input.name, account_type = %input.r#type, currency = %input.currency))]
-    pub async fn upsert_account(
+    #[tool(description = "Migrate a GnuCash book, saved as uncompressed XML or as SQLite, embedding each description. Its asset, bank, cash, credit card and liability accounts become accounts and its income and expense accounts categories, reusing those of the same name and currency; category splits become transactions of the account they draw on, filed under their categories, and what is left of an account's split, e.g. what moved to savings or bought shares, a transfer. Splits imported before and splits of zero amount are skipped, so a book can be imported again after more work in GnuCash.", annotations(destructive_hint = false, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<ImportGnuCashOutput>>())]
+    #[instrument(skip(self, input), fields(bytes = input.book.len()))]
+    pub async fn import_gnucash(
         &self,
-        Parameters(input): Parameters<UpsertAccountInput>,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ImportGnuCashInput>>,
     ) -> Result<CallToolResult, McpError> {
         let start_time = Instant::now();
-        info!("Upserting account: {} ({})", input.name, input.r#type);
-        
-        let _embedding = self
-            .embedder
-            .embed(&input.name)
+        if input.book.trim().is_empty() {
+            return Err(missing_field("book"));
+        }
+        let book = gnucash::parse(&input.book).map_err(|err| {
+            warn!("Rejected GnuCash book: {:#}", err);
+            McpError::from(ToolError::invalid(format!("{err:#}"), "book"))
+        })?;
+        let entries = book.entries();
+        info!(
+            "Importing {} GnuCash accounts and {} transactions",
+            book.accounts.len(),
+            entries.len()
+        );
+        let dry_run = self.is_dry_run(dry_run);
+
+        let existing_accounts = self.all_accounts().await?;
+        let existing_categories = self.supabase.list_categories().await.unwrap_or_else(|err| {
+            warn!("Upserting every GnuCash category; categories could not be listed: {:#}", err);
+            Vec::new()
+        });
+        // By GnuCash id: the account to book to (the GnuCash id itself for
+        // one a dry run has not created), its currency and whether it existed
+        // before; and the category to file under.
+        let mut booked_to: HashMap<String, (String, String, bool)> = HashMap::new();
+        let mut filed_under: HashMap<String, String> = HashMap::new();
+        let mut mappings = Vec::new();
+        let mut mapped = Vec::new();
+        for account in book.imported_accounts() {
+            let name = book.imported_name(&account.id);
+            let (imported_as, id, created) = match book.role(account) {
+                Role::Account => {
+                    let commodity = account.commodity.as_deref().unwrap_or_default().trim();
+                    if commodity.is_empty() {
+                        return Err(ToolError::invalid(
+                            format!("account {} has no currency", book.full_name(&account.id)),
+                            "book",
+                        )
+                        .into());
+                    }
+                    let Some(currency) = validation::currency_code(commodity) else {
+                        return Err(ToolError::invalid(
+                            format!(
+                                "account {} is held in {commodity:?}, which is not a currency or asset code",
+                                book.full_name(&account.id)
+                            ),
+                            "book",
+                        )
+                        .into());
+                    };
+                    let found = existing_accounts.iter().find(|existing| {
+                        existing.name == name && existing.r#type == AccountType::Offchain
+                    });
+                    // Upserting the namesake would change its currency under
+                    // the transactions it holds.
+                    if let Some(found) =
+                        found.filter(|found| !found.currency.eq_ignore_ascii_case(&currency))
+                    {
+                        return Err(ToolError::invalid(
+                            format!(
+                                "account {} is held in {currency}, but the account {name} here is held in {}; rename one of them",
+                                book.full_name(&account.id),
+                                found.currency
+                            ),
+                            "book",
+                        )
+                        .into());
+                    }
+                    let id = match found {
+                        Some(found) => Some(found.id.clone()),
+                        None if dry_run => None,
+                        None => {
+                            let upsert = UpsertAccountInput {
+                                name: name.clone(),
+                                r#type: AccountType::Offchain,
+                                currency: currency.clone(),
+                                network: None,
+                                institution: None,
+                            };
+                            let record =
+                                self.supabase.upsert_account(&upsert).await.map_err(|err| {
+                                    error!("Failed to upsert GnuCash account {}: {}", name, err);
+                                    ToolError::failed("upsert account", err)
+                                })?;
+                            Some(record.id)
+                        }
+                    };
+                    let booked = id.clone().unwrap_or_else(|| account.id.clone());
+                    booked_to.insert(account.id.clone(), (booked, currency, found.is_some()));
+                    (GnuCashImportedAs::Account, id, found.is_none())
+                }
+                Role::Category(kind) => {
+                    let found = existing_categories
+                        .iter()
+                        .find(|existing| existing.name == name);
+                    let id = match found {
+                        Some(found) => Some(found.id.clone()),
+                        None if dry_run => None,
+                        None => {
+                            let upsert = UpsertCategoryInput {
+                                name: name.clone(),
+                                kind: Some(kind),
+                                description: account.description.clone(),
+                            };
+                            let source = upsert.description.as_deref().unwrap_or(&upsert.name);
+                            let embedding = self.embedder.embed(source).await.map_err(|err| {
+                                error!("Failed to generate category embedding: {}", err);
+                                ToolError::failed("generate category embedding", err)
+                            })?;
+                            let record = self
+                                .supabase
+                                .upsert_category(&upsert, Some(embedding))
+                                .await
+                                .map_err(|err| {
+                                    error!("Failed to upsert GnuCash category {}: {}", name, err);
+                                    ToolError::failed("upsert category", err)
+                                })?;
+                            Some(record.id)
+                        }
+                    };
+                    if let Some(id) = &id {
+                        filed_under.insert(account.id.clone(), id.clone());
+                    }
+                    (GnuCashImportedAs::Category, id, found.is_none())
+                }
+                Role::Other => continue,
+            };
+            mapped.push(account.id.as_str());
+            mappings.push(GnuCashAccountMapping {
+                gnucash_account: book.full_name(&account.id),
+                imported_as,
+                name,
+                id,
+                created,
+                transactions: 0,
+            });
+        }
+
+        let mut recorded = HashSet::new();
+        for (gnucash_id, (account_id, _, existed)) in &booked_to {
+            if *existed {
+                recorded.extend(self.recorded_split_ids(account_id, gnucash_id, &entries).await?);
+            }
+        }
+
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        let mut filed = Vec::new();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for entry in &entries {
+            let reason = if entry.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if recorded.contains(&entry.split) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                skipped.push(SkippedEntry {
+                    reference: Some(entry.split.clone()),
+                    booked_at: entry.posted_at.clone(),
+                    amount: entry.amount,
+                    reason,
+                });
+                continue;
+            }
+            let Some((account_id, currency, _)) = booked_to.get(&entry.account) else {
+                continue;
+            };
+            transactions.push(entry.to_input(account_id, currency));
+            *counts.entry(entry.account.as_str()).or_default() += 1;
+            let category = entry.category.as_ref().and_then(|category| {
+                *counts.entry(category.as_str()).or_default() += 1;
+                filed_under.get(category).cloned()
+            });
+            filed.push(category);
+        }
+        for (gnucash_id, mapping) in mapped.into_iter().zip(&mut mappings) {
+            mapping.transactions = counts.get(gnucash_id).copied().unwrap_or(0);
+        }
+        info!(
+            "{} new GnuCash transactions, {} skipped",
+            transactions.len(),
+            skipped.len()
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        let result = |accounts, transactions, skipped| ImportGnuCashOutput {
+            accounts,
+            transactions,
+            skipped,
+        };
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} GnuCash transactions; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(result(mappings, Vec::new(), skipped)));
+        }
+        if dry_run {
+            info!("Dry run; {} GnuCash transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(result(mappings, transactions, skipped)));
+        }
+
+        let mut inserted = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let written = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert GnuCash transactions: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            inserted.extend(written);
+        }
+        let mut transactions = Vec::with_capacity(inserted.len());
+        for (transaction, category_id) in inserted.into_iter().zip(filed) {
+            let Some(category_id) = category_id else {
+                transactions.push(transaction);
+                continue;
+            };
+            let categorized = self
+                .supabase
+                .categorize_transaction(&transaction.id, Some(&category_id))
+                .await
+                .map_err(|err| {
+                    error!("Failed to categorize GnuCash transaction: {}", err);
+                    ToolError::failed("categorize transaction", err)
+                })?;
+            let transaction =
+                categorized.map_or(transaction, |categorized| categorized.transaction);
+            transactions.push(transaction);
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "Imported {} GnuCash transactions in {:?}",
+            transactions.len(),
+            duration
+        );
+
+        self.audit(
+            "import_gnucash",
+            hash,
+            transactions.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(result(
+            mappings,
+            transactions.into_iter().map(Written::Row).collect(),
+            skipped,
+        )))
+    }
+
+    #[tool(description = "Migrate a YNAB budget, from the register CSV of YNAB's budget export (with its plan CSV for categories nothing is filed under yet) or through the YNAB API, embedding each description. Budget accounts become offchain accounts and categories expense categories, reusing those of the same name; inflows to Ready to Assign are income without a category, and transfers between budget accounts are recorded in both. Transactions imported before and those of zero amount are skipped, so a budget can be imported again while YNAB is still in use.", annotations(destructive_hint = false, idempotent_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<ImportYnabOutput>>())]
+    #[instrument(skip(self, input), fields(register = input.register.is_some(), budget_id = ?input.budget_id))]
+    pub async fn import_ynab(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ImportYnabInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let register = input
+            .register
+            .as_deref()
+            .filter(|register| !register.trim().is_empty());
+        let (source, budget_id, budget, currency) = match register {
+            Some(register) => {
+                if input.currency.trim().is_empty() {
+                    return Err(missing_field("currency"));
+                }
+                let currency = currency_code(input.currency.trim(), "currency")?;
+                let budget = ynab::parse_export(
+                    register,
+                    input.plan.as_deref(),
+                    input.day_first,
+                    validation::currency_decimals(&currency),
+                )
+                .map_err(|err| {
+                    warn!("Rejected YNAB export: {:#}", err);
+                    let field = if err.to_string() == "plan" {
+                        "plan"
+                    } else {
+                        "register"
+                    };
+                    McpError::from(ToolError::invalid(format!("{err:#}"), field))
+                })?;
+                (YnabSource::Export, None, budget, currency)
+            }
+            None => {
+                let client = self.ynab()?;
+                let budget_id = input
+                    .budget_id
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|budget_id| !budget_id.is_empty())
+                    .unwrap_or(client.budget_id())
+                    .to_string();
+                let (budget, currency) =
+                    tokio::try_join!(client.budget(&budget_id), client.currency(&budget_id))
+                        .map_err(|err| ynab_failed("read YNAB budget", err))?;
+                (YnabSource::Api, Some(budget_id), budget, currency)
+            }
+        };
+        let transactions = budget.transactions;
+        info!(
+            "Importing {} YNAB categories and {} transactions",
+            budget.categories.len(),
+            transactions.len()
+        );
+        let dry_run = self.is_dry_run(dry_run);
+
+        let existing_accounts = self.all_accounts().await?;
+        let existing_categories = self.supabase.list_categories().await.unwrap_or_else(|err| {
+            warn!("Upserting every YNAB category; categories could not be listed: {:#}", err);
+            Vec::new()
+        });
+        // By YNAB name: the account to book to (the YNAB name itself for one a
+        // dry run has not created) and whether it existed before; and the
+        // category to file under.
+        let mut booked_to: HashMap<&str, (String, bool)> = HashMap::new();
+        let mut accounts = Vec::new();
+        for transaction in &transactions {
+            let name = transaction.account.as_str();
+            if booked_to.contains_key(name) {
+                continue;
+            }
+            let found = existing_accounts.iter().find(|existing| {
+                existing.name == name && existing.r#type == AccountType::Offchain
+            });
+            let id = match found {
+                Some(found) => Some(found.id.clone()),
+                None if dry_run => None,
+                None => {
+                    let upsert = UpsertAccountInput {
+                        name: name.to_string(),
+                        r#type: AccountType::Offchain,
+                        currency: currency.clone(),
+                        network: None,
+                        institution: None,
+                    };
+                    let record = self.supabase.upsert_account(&upsert).await.map_err(|err| {
+                        error!("Failed to upsert YNAB account {}: {}", name, err);
+                        ToolError::failed("upsert account", err)
+                    })?;
+                    Some(record.id)
+                }
+            };
+            let booked = id.clone().unwrap_or_else(|| name.to_string());
+            booked_to.insert(name, (booked, found.is_some()));
+            accounts.push(YnabMapping {
+                name: name.to_string(),
+                id,
+                created: found.is_none(),
+                transactions: 0,
+            });
+        }
+        let mut filed_under: HashMap<&str, String> = HashMap::new();
+        let mut categories = Vec::new();
+        for category in &budget.categories {
+            let found = existing_categories
+                .iter()
+                .find(|existing| existing.name == category.name);
+            let id = match found {
+                Some(found) => Some(found.id.clone()),
+                None if dry_run => None,
+                None => {
+                    let upsert = UpsertCategoryInput {
+                        name: category.name.clone(),
+                        kind: Some(CategoryKind::Expense),
+                        description: None,
+                    };
+                    let embedding = self.embedder.embed(&upsert.name).await.map_err(|err| {
+                        error!("Failed to generate category embedding: {}", err);
+                        ToolError::failed("generate category embedding", err)
+                    })?;
+                    let record = self
+                        .supabase
+                        .upsert_category(&upsert, Some(embedding))
+                        .await
+                        .map_err(|err| {
+                            error!("Failed to upsert YNAB category {}: {}", category.name, err);
+                            ToolError::failed("upsert category", err)
+                        })?;
+                    Some(record.id)
+                }
+            };
+            if let Some(id) = &id {
+                filed_under.insert(category.name.as_str(), id.clone());
+            }
+            categories.push(YnabMapping {
+                name: category.name.clone(),
+                id,
+                created: found.is_none(),
+                transactions: 0,
+            });
+        }
+
+        let mut recorded = HashSet::new();
+        for (name, (account_id, existed)) in &booked_to {
+            if *existed {
+                recorded.extend(self.recorded_ynab_ids(account_id, name, &transactions).await?);
+            }
+        }
+
+        let mut skipped = Vec::new();
+        let mut inputs = Vec::new();
+        let mut filed = Vec::new();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        let mut filed_counts: HashMap<&str, usize> = HashMap::new();
+        for transaction in &transactions {
+            let reason = if transaction.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if recorded.contains(&transaction.id) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                skipped.push(SkippedEntry {
+                    reference: Some(transaction.id.clone()),
+                    booked_at: transaction.date.clone(),
+                    amount: transaction.amount,
+                    reason,
+                });
+                continue;
+            }
+            let Some((account_id, _)) = booked_to.get(transaction.account.as_str()) else {
+                continue;
+            };
+            inputs.push(transaction.to_input(account_id, &currency));
+            *counts.entry(transaction.account.as_str()).or_default() += 1;
+            let category = transaction.category.as_deref().and_then(|category| {
+                *filed_counts.entry(category).or_default() += 1;
+                filed_under.get(category).cloned()
+            });
+            filed.push(category);
+        }
+        for mapping in &mut accounts {
+            mapping.transactions = counts.get(mapping.name.as_str()).copied().unwrap_or(0);
+        }
+        for mapping in &mut categories {
+            mapping.transactions = filed_counts.get(mapping.name.as_str()).copied().unwrap_or(0);
+        }
+        info!(
+            "{} new YNAB transactions, {} skipped",
+            inputs.len(),
+            skipped.len()
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(inputs).await?;
+        let result = |accounts, categories, transactions, skipped| ImportYnabOutput {
+            source,
+            budget_id: budget_id.clone(),
+            accounts,
+            categories,
+            transactions,
+            skipped,
+        };
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} YNAB transactions; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(result(accounts, categories, Vec::new(), skipped)));
+        }
+        if dry_run {
+            info!("Dry run; {} YNAB transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(result(
+                accounts,
+                categories,
+                transactions,
+                skipped,
+            )));
+        }
+
+        let mut inserted = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let written = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert YNAB transactions: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            inserted.extend(written);
+        }
+        let mut transactions = Vec::with_capacity(inserted.len());
+        for (transaction, category_id) in inserted.into_iter().zip(filed) {
+            let Some(category_id) = category_id else {
+                transactions.push(transaction);
+                continue;
+            };
+            let categorized = self
+                .supabase
+                .categorize_transaction(&transaction.id, Some(&category_id))
+                .await
+                .map_err(|err| {
+                    error!("Failed to categorize YNAB transaction: {}", err);
+                    ToolError::failed("categorize transaction", err)
+                })?;
+            let transaction =
+                categorized.map_or(transaction, |categorized| categorized.transaction);
+            transactions.push(transaction);
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "Imported {} YNAB transactions in {:?}",
+            transactions.len(),
+            duration
+        );
+
+        self.audit(
+            "import_ynab",
+            hash,
+            transactions.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(result(
+            accounts,
+            categories,
+            transactions.into_iter().map(Written::Row).collect(),
+            skipped,
+        )))
+    }
+
+    #[tool(description = "Add the expense categories a YNAB budget lacks, matching names ignoring case, so transactions can be filed under them in YNAB and imported back with import_ynab. They go to the category group `group`, Exaspoon by default, which is added when the budget has none of that name. Categories already in the budget and what is assigned to them are left alone, so running it again adds nothing.", annotations(destructive_hint = true, idempotent_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<ExportYnabOutput>>())]
+    #[instrument(skip(self, input), fields(budget_id = ?input.budget_id, group = ?input.group))]
+    pub async fn export_ynab(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ExportYnabInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let client = self.ynab()?;
+        let hash = input_hash(&input);
+        let budget_id = input
+            .budget_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|budget_id| !budget_id.is_empty())
+            .unwrap_or(client.budget_id())
+            .to_string();
+        let group = input
+            .group
+            .as_deref()
+            .map(str::trim)
+            .filter(|group| !group.is_empty())
+            .unwrap_or(ynab::DEFAULT_GROUP)
+            .to_string();
+        info!("Adding expense categories to YNAB budget {}", budget_id);
+
+        let (categories, ynab_categories) = tokio::join!(
+            self.supabase.list_categories(),
+            client.categories(&budget_id)
+        );
+        let categories = categories.map_err(|err| {
+            error!("Failed to list categories: {}", err);
+            ToolError::failed("list categories", err)
+        })?;
+        let ynab_categories =
+            ynab_categories.map_err(|err| ynab_failed("read YNAB categories", err))?;
+        let mut names = categories
+            .iter()
+            .filter(|category| category.kind == CategoryKind::Expense)
+            .map(|category| category.name.trim())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup_by(|name, other| name.eq_ignore_ascii_case(other));
+        let mut exports = names
+            .into_iter()
+            .map(|name| {
+                let existing = ynab_categories
+                    .iter()
+                    .find(|ynab| ynab.name.trim().eq_ignore_ascii_case(name));
+                YnabCategoryExport {
+                    category: name.to_string(),
+                    ynab_category_id: existing.and_then(|existing| existing.id.clone()),
+                    status: match existing {
+                        Some(_) => YnabCategoryStatus::Existing,
+                        None => YnabCategoryStatus::Added,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        let missing = exports
+            .iter()
+            .filter(|export| export.status == YnabCategoryStatus::Added)
+            .map(|export| export.category.clone())
+            .collect::<Vec<_>>();
+        let result = |categories| ExportYnabOutput {
+            budget_id: budget_id.clone(),
+            group: group.clone(),
+            categories,
+        };
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} YNAB categories not added", missing.len());
+            return Ok(dry_run_result(result(exports)));
+        }
+
+        if !missing.is_empty() {
+            let added = match client.add_categories(&budget_id, &group, &missing).await {
+                Ok(added) => added,
+                Err(err) => {
+                    if err.downcast_ref::<ynab::Interrupted>().is_some() {
+                        self.audit("export_ynab", hash, [None]).await;
+                    }
+                    return Err(ynab_failed("add YNAB categories", err));
+                }
+            };
+            // Categories are added in order; those left when the call was
+            // cancelled are not listed.
+            let mut added = added.into_iter();
+            exports.retain_mut(|export| {
+                if export.status == YnabCategoryStatus::Existing {
+                    return true;
+                }
+                match added.next() {
+                    Some(category) => {
+                        export.ynab_category_id = category.id;
+                        true
+                    }
+                    None => false,
+                }
+            });
+            self.audit("export_ynab", hash, [None]).await;
+        }
+
+        let duration = start_time.elapsed();
+        let added = exports
+            .iter()
+            .filter(|export| export.status == YnabCategoryStatus::Added)
+            .count();
+        info!("Added {} YNAB categories in {:?}", added, duration);
+
+        Ok(batch_result(result(exports)))
+    }
+
+    #[tool(description = "Import transactions given as JSON objects in create_transaction's schema, embedding each description. Each record is checked against that schema and the field rules on its own: valid ones are inserted and invalid ones are returned with the offending field and why, so one bad record does not hold back the rest. At most 5000 records per call.", annotations(destructive_hint = false), output_schema = cached_schema_for_type::<Outcome<ImportJsonOutput>>())]
+    #[instrument(skip(self, input), fields(count = input.records.len()))]
+    pub async fn import_json(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ImportJsonInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        if input.records.is_empty() || input.records.len() > MAX_JSON_RECORDS {
+            return Err(ToolError::invalid(
+                format!("records must contain between 1 and {MAX_JSON_RECORDS} items"),
+                "records",
+            )
+            .into());
+        }
+        info!("Importing {} JSON records", input.records.len());
+
+        let schema = cached_schema_for_type::<CreateTransactionInput>();
+        let mut transactions = Vec::new();
+        let mut rejected = Vec::new();
+        for (index, record) in input.records.iter().enumerate() {
+            match check_record(&schema, index, record) {
+                Ok(transaction) => transactions.push(transaction),
+                Err((field, reason)) => {
+                    debug!("Rejected JSON record {}: {}", index, reason);
+                    rejected.push(RejectedRecord {
+                        index,
+                        field,
+                        reason,
+                    });
+                }
+            }
+        }
+        info!(
+            "{} valid JSON records, {} rejected",
+            transactions.len(),
+            rejected.len()
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} JSON records; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(ImportJsonOutput {
+                transactions: Vec::new(),
+                rejected,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} JSON records not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(ImportJsonOutput {
+                transactions,
+                rejected,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert JSON records: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
+
+        let duration = start_time.elapsed();
+        info!("Imported {} JSON records in {:?}", records.len(), duration);
+
+        self.audit(
+            "import_json",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(ImportJsonOutput {
+            transactions: records.into_iter().map(Written::Row).collect(),
+            rejected,
+        }))
+    }
+
+    #[tool(description = "Link an account to a bank login through Plaid, so that sync_bank_account can pull its transactions. Pass the public_token Plaid Link returned, which is exchanged for an access token, or the access_token of a Plaid item exchanged elsewhere, and for logins with several bank accounts the plaid_account_id to sync. Relinking an account replaces its link and syncs it from the start again. The access token is stored encrypted and never returned.", annotations(destructive_hint = false, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<LinkBankAccountOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn link_bank_account(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<LinkBankAccountInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let plaid = self.plaid()?;
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let token = |token: &Option<String>| {
+            token
+                .as_deref()
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(str::to_string)
+        };
+        let (public_token, access_token) = (token(&input.public_token), token(&input.access_token));
+        if public_token.is_some() && access_token.is_some() {
+            return Err(ToolError::invalid(
+                "give either public_token or access_token, not both",
+                "access_token",
+            )
+            .into());
+        }
+
+        let previous = self
+            .supabase
+            .bank_link(&input.account_id)
+            .await
+            .map_err(|err| {
+                error!("Failed to read bank link: {}", err);
+                ToolError::failed("read bank link", err)
+            })?;
+        let mut output = LinkBankAccountOutput {
+            account_id: input.account_id.clone(),
+            item_id: None,
+            plaid_account_id: input.plaid_account_id.clone(),
+            replaced: previous.is_some(),
+        };
+        let item = match (public_token, access_token) {
+            (Some(_), None) if self.is_dry_run(dry_run) => {
+                info!("Dry run; public token not exchanged");
+                return Ok(dry_run_result(output));
+            }
+            (Some(public_token), None) => plaid
+                .exchange_public_token(&public_token)
+                .await
+                .map_err(|err| plaid_failed("exchange Plaid public token", err))?,
+            (None, Some(access_token)) => Item {
+                item_id: plaid
+                    .item_id(&access_token)
+                    .await
+                    .map_err(|err| plaid_failed("look up Plaid item", err))?,
+                access_token,
+            },
+            _ => return Err(missing_field("public_token")),
+        };
+        output.item_id = Some(item.item_id.clone());
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; bank link not saved");
+            return Ok(dry_run_result(output));
+        }
+
+        let access_token = plaid
+            .seal(&input.account_id, &item.access_token)
+            .map_err(|err| ToolError::failed("encrypt access token", err))?;
+        self.supabase
+            .save_bank_link(&BankLink {
+                account_id: input.account_id.clone(),
+                item_id: item.item_id,
+                access_token,
+                plaid_account_id: input.plaid_account_id.clone(),
+                cursor: None,
+                updated_at: None,
+            })
+            .await
+            .map_err(|err| {
+                error!("Failed to save bank link: {}", err);
+                ToolError::failed("save bank link", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Linked account {} to a bank in {:?}", input.account_id, duration);
+
+        self.audit(
+            "link_bank_account",
+            input_hash(&input),
+            [Some(input.account_id.as_str())],
+        )
+        .await;
+
+        Ok(success(output))
+    }
+
+    #[tool(description = "Pull the transactions a bank account linked with link_bank_account booked since its last sync from Plaid and record them, embedding each description. Pending transactions wait for a later sync, and those whose Plaid transaction_id is already recorded for the account are skipped, so a sync can be repeated safely. Transactions the bank changed or withdrew since they were synced are listed but left as recorded.", annotations(destructive_hint = false, idempotent_hint = false, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<SyncBankAccountOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn sync_bank_account(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<SyncBankAccountInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let plaid = self.plaid()?;
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let link = self
+            .supabase
+            .bank_link(&input.account_id)
+            .await
+            .map_err(|err| {
+                error!("Failed to read bank link: {}", err);
+                ToolError::failed("read bank link", err)
+            })?
+            .ok_or_else(|| {
+                ToolError::invalid(
+                    format!(
+                        "account {} is not linked to a bank; call link_bank_account first",
+                        input.account_id
+                    ),
+                    "account_id",
+                )
+            })?;
+        let access_token = plaid
+            .open(&link.account_id, &link.access_token)
+            .map_err(|err| ToolError::failed("decrypt access token", err))?;
+        let changes = plaid
+            .sync(&access_token, link.cursor.as_deref())
+            .await
+            .map_err(|err| plaid_failed("sync bank transactions", err))?;
+
+        // An item can hold several bank accounts; only the linked one counts.
+        let linked = |plaid_account_id: &str| {
+            link.plaid_account_id
+                .as_deref()
+                .is_none_or(|linked| linked == plaid_account_id)
+        };
+        let added = changes
+            .added
+            .iter()
+            .filter(|transaction| linked(&transaction.account_id))
+            .collect::<Vec<_>>();
+        let mut seen = self.recorded_plaid_ids(&input.account_id, &added).await?;
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        for transaction in added {
+            let reason = if transaction.pending {
+                Some(SkipReason::Pending)
+            } else if transaction.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if !seen.insert(transaction.transaction_id.clone()) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedBankTransaction {
+                    transaction_id: transaction.transaction_id.clone(),
+                    reason,
+                }),
+                None => transactions.push(
+                    transaction
+                        .to_input(&input.account_id, &input.currency)
+                        .map_err(|err| ToolError::failed("read Plaid transaction", err))?,
+                ),
+            }
+        }
+        if transactions.iter().any(|transaction| transaction.currency.is_empty()) {
+            return Err(missing_field("currency"));
+        }
+        let modified = changes
+            .modified
+            .iter()
+            .filter(|transaction| linked(&transaction.account_id))
+            .map(|transaction| transaction.transaction_id.clone())
+            .collect();
+        let removed = changes
+            .removed
+            .iter()
+            .filter(|removed| removed.account_id.as_deref().is_none_or(linked))
+            .map(|removed| removed.transaction_id.clone())
+            .collect();
+        info!(
+            "{} new bank transactions, {} skipped",
+            transactions.len(),
+            skipped.len()
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} bank transactions; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(SyncBankAccountOutput {
+                transactions: Vec::new(),
+                skipped,
+                modified,
+                removed,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} bank transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(SyncBankAccountOutput {
+                transactions,
+                skipped,
+                modified,
+                removed,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert bank transactions: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
+        // Only once everything is recorded, so a failed sync starts over
+        // from the same place. A token stored before tokens were encrypted is
+        // encrypted now.
+        let access_token = plaid
+            .seal(&link.account_id, &access_token)
+            .map_err(|err| ToolError::failed("encrypt access token", err))?;
+        self.supabase
+            .save_bank_link(&BankLink {
+                access_token,
+                cursor: Some(changes.next_cursor).filter(|cursor| !cursor.is_empty()),
+                ..link
+            })
+            .await
+            .map_err(|err| {
+                error!("Failed to save sync cursor: {}", err);
+                ToolError::failed("save sync cursor", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!(
+            "Synced {} bank transactions in {:?}",
+            records.len(),
+            duration
+        );
+
+        self.audit(
+            "sync_bank_account",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(SyncBankAccountOutput {
+            transactions: records.into_iter().map(Written::Row).collect(),
+            skipped,
+            modified,
+            removed,
+        }))
+    }
+
+    #[tool(description = "Link an account to a bank account at a European bank through GoCardless Bank Account Data, so that sync_openbanking can pull its transactions. Pass the requisition_id of a requisition the user has authorized at the bank, and for requisitions covering several bank accounts the openbanking_account_id to sync. The link is kept in the account's metadata; relinking an account replaces it and syncs from as far back as the bank allows again.", annotations(destructive_hint = false, idempotent_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<LinkOpenBankingAccountOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn link_openbanking_account(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<LinkOpenBankingAccountInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let gocardless = self.gocardless()?;
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let requisition_id = input.requisition_id.trim();
+        if requisition_id.is_empty() {
+            return Err(missing_field("requisition_id"));
+        }
+
+        let requisition = gocardless
+            .requisition(requisition_id)
+            .await
+            .map_err(|err| gocardless_failed("look up GoCardless requisition", err))?;
+        if requisition.status != REQUISITION_LINKED {
+            return Err(ToolError::invalid(
+                format!(
+                    "requisition {} is {}, not linked; the user has to finish authorizing it at the bank first",
+                    requisition.id, requisition.status
+                ),
+                "requisition_id",
+            )
+            .into());
+        }
+        let requested = input
+            .openbanking_account_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty());
+        let openbanking_account_id = match (requested, requisition.accounts.as_slice()) {
+            (Some(id), accounts) if accounts.iter().any(|account| account == id) => id.to_string(),
+            (Some(id), accounts) => {
+                return Err(ToolError::invalid(
+                    format!(
+                        "requisition {} does not cover bank account {id}, only {}",
+                        requisition.id,
+                        accounts.join(", ")
+                    ),
+                    "openbanking_account_id",
+                )
+                .into())
+            }
+            (None, [only]) => only.clone(),
+            (None, []) => {
+                return Err(ToolError::invalid(
+                    format!("requisition {} covers no bank accounts", requisition.id),
+                    "requisition_id",
+                )
+                .into())
+            }
+            (None, several) => {
+                return Err(ToolError::invalid(
+                    format!(
+                        "requisition {} covers several bank accounts; pick one of {} as openbanking_account_id",
+                        requisition.id,
+                        several.join(", ")
+                    ),
+                    "openbanking_account_id",
+                )
+                .into())
+            }
+        };
+
+        let previous = self.openbanking_link(&input.account_id).await?;
+        let link = OpenBankingLink {
+            requisition_id: requisition.id,
+            institution_id: requisition.institution_id,
+            openbanking_account_id,
+            synced_through: None,
+        };
+        let output = LinkOpenBankingAccountOutput {
+            account_id: input.account_id.clone(),
+            link: link.clone(),
+            replaced: previous.is_some(),
+        };
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; open banking link not saved");
+            return Ok(dry_run_result(output));
+        }
+
+        self.save_openbanking_link(&input.account_id, &link).await?;
+
+        let duration = start_time.elapsed();
+        info!(
+            "Linked account {} to {} in {:?}",
+            input.account_id, link.institution_id, duration
+        );
+
+        self.audit(
+            "link_openbanking_account",
+            input_hash(&input),
+            [Some(input.account_id.as_str())],
+        )
+        .await;
+
+        Ok(success(output))
+    }
+
+    #[tool(description = "Pull the transactions a bank account linked with link_openbanking_account booked since its last sync from GoCardless and record them, embedding each description. Pending transactions wait for a later sync, and those already recorded for the account, by the bank's transaction id, are skipped, so a sync can be repeated safely. Banks allow only a few syncs per account a day.", annotations(destructive_hint = false, idempotent_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<SyncOpenBankingOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn sync_openbanking(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<SyncOpenBankingInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let gocardless = self.gocardless()?;
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let link = self
+            .openbanking_link(&input.account_id)
+            .await?
+            .ok_or_else(|| {
+                ToolError::invalid(
+                    format!(
+                        "account {} is not linked to a bank; call link_openbanking_account first",
+                        input.account_id
+                    ),
+                    "account_id",
+                )
+            })?;
+        // GoCardless has no cursor; reading from a little before the last
+        // booking date catches late bookings, and the overlap is skipped below.
+        let date_from = link
+            .synced_through
+            .as_deref()
+            .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            .map(|date| date - chrono::Duration::days(OPENBANKING_OVERLAP_DAYS));
+        let fetched = gocardless
+            .transactions(&link.openbanking_account_id, date_from)
+            .await
+            .map_err(|err| gocardless_failed("read bank transactions", err))?;
+
+        let mut seen = self
+            .recorded_entry_ids(&input.account_id, &fetched.booked)
+            .await?;
+        let mut synced_through = link.synced_through.clone();
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        for transaction in &fetched.booked {
+            let unreadable = |err| ToolError::failed("read GoCardless transaction", err);
+            let booked_on = transaction
+                .booked_on()
+                .map_err(unreadable)?
+                .format("%Y-%m-%d")
+                .to_string();
+            if synced_through
+                .as_deref()
+                .is_none_or(|through| through < booked_on.as_str())
+            {
+                synced_through = Some(booked_on);
+            }
+            let reason = if transaction.amount().map_err(unreadable)? == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if !seen.insert(transaction.entry_id()) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedBankTransaction {
+                    transaction_id: transaction.id(),
+                    reason,
+                }),
+                None => transactions.push(
+                    transaction
+                        .to_input(&input.account_id)
+                        .map_err(unreadable)?,
+                ),
+            }
+        }
+        info!(
+            "{} new bank transactions, {} skipped, {} pending",
+            transactions.len(),
+            skipped.len(),
+            fetched.pending
+        );
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} bank transactions; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(SyncOpenBankingOutput {
+                transactions: Vec::new(),
+                skipped,
+                pending: fetched.pending,
+                synced_through: link.synced_through,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} bank transactions not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(SyncOpenBankingOutput {
+                transactions,
+                skipped,
+                pending: fetched.pending,
+                synced_through,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert bank transactions: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
+        // Only once everything is recorded, so a failed sync reads the same
+        // days again.
+        if synced_through != link.synced_through {
+            let link = OpenBankingLink {
+                synced_through: synced_through.clone(),
+                ..link
+            };
+            self.save_openbanking_link(&input.account_id, &link).await?;
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "Synced {} bank transactions in {:?}",
+            records.len(),
+            duration
+        );
+
+        self.audit(
+            "sync_openbanking",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(SyncOpenBankingOutput {
+            transactions: records.into_iter().map(Written::Row).collect(),
+            skipped,
+            pending: fetched.pending,
+            synced_through,
+        }))
+    }
+
+    #[tool(description = "Pull the transfers of an onchain account's address from its blockchain and record them: on Ethereum ether and ERC-20 token transfers, each in the token's own currency, on Bitcoin the confirmed transactions of an address or a wallet's xpub in BTC, and on Solana SOL and SPL token transfers with the token's symbol as the currency, as income or expenses by their direction, with the network fee of each transaction the address sent as an expense. Pass the address on an account's first sync; it is kept in the account's metadata, and an account without a network gets the address's chain as its network. Transfers already recorded for the account, by transaction hash, are skipped, so a sync can be repeated safely; when complete is false, sync again for the rest.", annotations(destructive_hint = false, idempotent_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<SyncOnchainOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn sync_onchain(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<SyncOnchainInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let account = self.onchain_account(&input.account_id).await?;
+        let previous = self.onchain_link(&input.account_id).await?;
+        let requested = input
+            .address
+            .as_deref()
+            .map(str::trim)
+            .filter(|address| !address.is_empty());
+        let link = match (requested, previous.clone()) {
+            (None, Some(link)) => link,
+            (None, None) => {
+                return Err(ToolError::invalid(
+                    format!(
+                        "account {} has no address to sync yet; pass its address",
+                        input.account_id
+                    ),
+                    "address",
+                )
+                .into())
+            }
+            (Some(address), previous) => {
+                let chain = match account.network.as_deref() {
+                    Some(network) => onchain::chain_of_network(network).ok_or_else(|| {
+                        ToolError::invalid(
+                            format!(
+                                "account {} is on {network}, which sync_onchain does not read",
+                                input.account_id
+                            ),
+                            "account_id",
+                        )
+                    })?,
+                    None => onchain::chain_of_address(address).ok_or_else(|| {
+                        ToolError::invalid(
+                            format!("{address} is not an address of a chain sync_onchain reads"),
+                            "address",
+                        )
+                    })?,
+                };
+                let address = onchain::parse_address(chain, address)
+                    .map_err(|err| ToolError::invalid(err.to_string(), "address"))?;
+                match previous {
+                    Some(link) if link.chain == chain && link.address == address => link,
+                    _ => OnchainLink {
+                        chain,
+                        address,
+                        synced_block: None,
+                        synced_signature: None,
+                    },
+                }
+            }
+        };
+
+        // The block the previous sync stopped at is read again, as a full
+        // page may have ended within it; its transfers are skipped below.
+        let start_block = link.synced_block.unwrap_or_default();
+        let fetched = match link.chain {
+            Chain::Ethereum => self
+                .etherscan()?
+                .transfers(&link.address, start_block)
+                .await
+                .map_err(|err| etherscan_failed("read Ethereum transfers", err))?,
+            Chain::Bitcoin => self
+                .blockbook()?
+                .transfers(&link.address, start_block)
+                .await
+                .map_err(|err| blockbook_failed("read Bitcoin transactions", err))?,
+            Chain::Solana => self
+                .solana()?
+                .transfers(&link.address, start_block, link.synced_signature.as_deref())
+                .await
+                .map_err(|err| solana_failed("read Solana transfers", err))?,
+        };
+
+        let mut seen = self
+            .recorded_transfer_ids(&input.account_id, &fetched.transfers)
+            .await?;
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        for transfer in &fetched.transfers {
+            let reason = if transfer.pending {
+                Some(SkipReason::Pending)
+            } else if transfer.failed {
+                Some(SkipReason::Failed)
+            } else if transfer.spam {
+                Some(SkipReason::Spam)
+            } else if transfer.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if !seen.insert(transfer.entry_id()) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedTransfer {
+                    transfer_id: transfer.id.clone(),
+                    reason,
+                }),
+                None => transactions.push(transfer.to_input(&input.account_id)),
+            }
+        }
+        info!(
+            "{} new {} transfers, {} skipped",
+            transactions.len(),
+            link.chain,
+            skipped.len()
+        );
+        let synced = OnchainLink {
+            synced_block: fetched.synced_block.or(link.synced_block),
+            synced_signature: fetched.synced_signature.or(link.synced_signature.clone()),
+            ..link.clone()
+        };
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} transfers; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(SyncOnchainOutput {
+                account_id: input.account_id.clone(),
+                link,
+                transactions: Vec::new(),
+                skipped,
+                complete: false,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} transfers not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(SyncOnchainOutput {
+                account_id: input.account_id.clone(),
+                link: synced,
+                transactions,
+                skipped,
+                complete: fetched.complete,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert onchain transfers: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
+        // Only once everything is recorded, so a failed sync reads the same
+        // blocks again.
+        if previous.as_ref() != Some(&synced) {
+            self.save_onchain_link(&input.account_id, &synced).await?;
+        }
+        if account.network.is_none() {
+            self.record_network(&account, synced.chain).await?;
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "Synced {} transfers of {} in {:?}",
+            records.len(),
+            synced.address,
+            duration
+        );
+
+        self.audit(
+            "sync_onchain",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(SyncOnchainOutput {
+            account_id: input.account_id,
+            link: synced,
+            transactions: records.into_iter().map(Written::Row).collect(),
+            skipped,
+            complete: fetched.complete,
+        }))
+    }
+
+    #[tool(description = "Pull the movements of an exchange account from its exchange and record them: on Binance deposits, withdrawals and the spot trades of the given trading pairs, on Coinbase the transactions of every wallet, each in the asset's own currency. Money into the account is income and money out an expense; a trade is recorded as what it sold, an expense, and what it bought, income, with its fee as another expense. Pass the exchange on an account's first sync unless the account's institution names it; it is kept in the account's metadata with where each sync stopped. Deposits and withdrawals still in progress are skipped and read again later, and movements already recorded for the account are skipped, so a sync can be repeated safely.", annotations(destructive_hint = false, idempotent_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<SyncExchangeOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = %input.account_id))]
+    pub async fn sync_exchange(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<SyncExchangeInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        if input.account_id.trim().is_empty() {
+            return Err(missing_field("account_id"));
+        }
+        let account = self.exchange_account(&input.account_id).await?;
+        let previous = self.exchange_link(&input.account_id).await?;
+        let exchange = input
+            .exchange
+            .or(previous.as_ref().map(|link| link.exchange))
+            .or_else(|| {
+                account
+                    .institution
+                    .as_deref()
+                    .and_then(exchange::exchange_of_institution)
+            })
+            .ok_or_else(|| {
+                ToolError::invalid(
+                    format!(
+                        "account {} has no exchange to sync from yet; pass its exchange",
+                        input.account_id
+                    ),
+                    "exchange",
+                )
+            })?;
+        let symbols = match input.symbols.as_deref() {
+            Some(symbols) => trading_pairs(exchange, symbols)?,
+            None => match &previous {
+                Some(link) if link.exchange == exchange => link.symbols.clone(),
+                _ => Vec::new(),
+            },
+        };
+        let link = match previous.clone() {
+            Some(link) if link.exchange == exchange => ExchangeLink { symbols, ..link },
+            _ => ExchangeLink {
+                exchange,
+                symbols,
+                cursors: BTreeMap::new(),
+            },
+        };
+
+        let fetched = match exchange {
+            Exchange::Binance => self
+                .binance()?
+                .movements(&link.symbols, &link.cursors)
+                .await
+                .map_err(|err| binance_failed("read Binance movements", err))?,
+            Exchange::Coinbase => self
+                .coinbase()?
+                .movements(&link.cursors)
+                .await
+                .map_err(|err| coinbase_failed("read Coinbase transactions", err))?,
+        };
+
+        let mut seen = self
+            .recorded_movement_ids(&input.account_id, &fetched.movements)
+            .await?;
+        let mut skipped = Vec::new();
+        let mut transactions = Vec::new();
+        for movement in &fetched.movements {
+            let reason = if movement.pending {
+                Some(SkipReason::Pending)
+            } else if movement.failed {
+                Some(SkipReason::Failed)
+            } else if movement.amount == 0.0 {
+                Some(SkipReason::ZeroAmount)
+            } else if !seen.insert(movement.entry_id()) {
+                Some(SkipReason::Duplicate)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedMovement {
+                    movement_id: movement.id.clone(),
+                    reason,
+                }),
+                None => transactions.push(movement.to_input(&input.account_id)),
+            }
+        }
+        info!(
+            "{} new {} movements, {} skipped",
+            transactions.len(),
+            exchange,
+            skipped.len()
+        );
+        let synced = ExchangeLink {
+            cursors: fetched.cursors,
+            ..link.clone()
+        };
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} movements; none inserted",
+                rows.len()
+            );
+            return Ok(batch_result(SyncExchangeOutput {
+                account_id: input.account_id.clone(),
+                link,
+                transactions: Vec::new(),
+                skipped,
+            }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} movements not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(SyncExchangeOutput {
+                account_id: input.account_id.clone(),
+                link: synced,
+                transactions,
+                skipped,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert exchange movements: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
+        // Only once everything is recorded, so a failed sync reads the same
+        // movements again.
+        if previous.as_ref() != Some(&synced) {
+            self.save_exchange_link(&input.account_id, &synced).await?;
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "Synced {} movements from {} in {:?}",
+            records.len(),
+            exchange,
+            duration
+        );
+
+        self.audit(
+            "sync_exchange",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(SyncExchangeOutput {
+            account_id: input.account_id,
+            link: synced,
+            transactions: records.into_iter().map(Written::Row).collect(),
+            skipped,
+        }))
+    }
+
+    #[tool(description = "Read a receipt photo or scan with a vision model: merchant, date, currency, total, tax and line items. With an account_id the purchase is also recorded there as an expense in the receipt's currency, or the account's when it shows none, dated the day of the receipt; with split, as one expense per line item plus one for tax and other charges, all carrying the receipt's id. A purchase already recorded in the account is not recorded again. The image is base64, a data: URL or an https:// URL.", annotations(destructive_hint = false, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<ParseReceiptOutput>>())]
+    #[instrument(skip(self, input), fields(account_id = ?input.account_id, split = input.split))]
+    pub async fn parse_receipt(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ParseReceiptInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let receipts = self.receipts()?;
+        let image_url =
+            receipt::image_url(&input.image, input.mime_type.as_deref()).map_err(|err| {
+                warn!("Rejected receipt image: {:#}", err);
+                McpError::from(ToolError::invalid(format!("{err:#}"), "image"))
+            })?;
+        let account_id = input
+            .account_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|account_id| !account_id.is_empty());
+        // Checked before the model is paid to read the receipt.
+        let account = match account_id {
+            Some(account_id) => Some(self.find_account(account_id).await?),
+            None => None,
+        };
+        info!("Reading a receipt with {}", receipts.model());
+
+        let read = receipts
+            .read(&image_url)
+            .await
+            .map_err(|err| vision_failed("read receipt", err))?;
+        let Some(account) = account else {
+            info!("Read receipt in {:?}", start_time.elapsed());
+            return Ok(success(ParseReceiptOutput {
+                receipt: read,
+                model: receipts.model().to_string(),
+                transactions: Vec::new(),
+                already_recorded: false,
+            }));
+        };
+        let transactions =
+            receipt::transactions(&read, &account.id, &account.currency, input.split).map_err(
+                |err| {
+                    warn!("Nothing to record from the receipt: {:#}", err);
+                    McpError::from(ToolError::invalid(format!("{err:#}"), "image"))
+                },
+            )?;
+        let id = receipt::purchase_id(&read, &account.currency);
+        if self
+            .recorded_receipt_ids(&account.id, &transactions)
+            .await?
+            .contains(&id)
+        {
+            info!("Receipt {} is already recorded in {}", id, account.id);
+            return Ok(success(ParseReceiptOutput {
+                receipt: read,
+                model: receipts.model().to_string(),
+                transactions: Vec::new(),
+                already_recorded: true,
+            }));
+        }
+
+        let hash = input_hash(&input);
+        let rows = self.embed_transactions(transactions).await?;
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} receipt expenses not inserted", rows.len());
+            let transactions = rows
+                .into_iter()
+                .map(|(transaction, embedding)| {
+                    Written::Planned(planned(transaction, embedding.as_deref()))
+                })
+                .collect();
+            return Ok(dry_run_result(ParseReceiptOutput {
+                receipt: read,
+                model: receipts.model().to_string(),
+                transactions,
+                already_recorded: false,
+            }));
+        }
+
+        let mut records = Vec::with_capacity(rows.len());
+        for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+            let inserted = self
+                .supabase
+                .insert_transactions(batch)
+                .await
+                .map_err(|err| {
+                    error!("Failed to insert receipt expenses: {}", err);
+                    ToolError::failed("insert transactions", err)
+                })?;
+            records.extend(inserted);
+        }
+
+        let duration = start_time.elapsed();
+        info!(
+            "Recorded {} receipt expenses in {:?}",
+            records.len(),
+            duration
+        );
+
+        self.audit(
+            "parse_receipt",
+            hash,
+            records.iter().map(|record| Some(record.id.as_str())),
+        )
+        .await;
+
+        Ok(success(ParseReceiptOutput {
+            receipt: read,
+            model: receipts.model().to_string(),
+            transactions: records.into_iter().map(Written::Row).collect(),
+            already_recorded: false,
+        }))
+    }
+
+    #[tool(description = "Apply an ordered list of operations (upsert_account, create_transactions, categorize) all or none, e.g. an account with its imported transactions filed under categories. Where an operation takes an id, $N stands for the row written by operation N, counting from 0, and $N.I for the Ith transaction created by operation N. Returns what each operation wrote; when one fails, the batch is undone and the error names the step.", annotations(destructive_hint = true), output_schema = cached_schema_for_type::<Outcome<ExecuteBatchOutput>>())]
+    #[instrument(skip(self, input), fields(operations = input.operations.len()))]
+    pub async fn execute_batch(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ExecuteBatchInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Executing batch of {} operations", input.operations.len());
+        batch::check_batch(&input.operations)?;
+
+        let hash = input_hash(&input);
+        let steps = self.embed_batch(&input.operations).await?;
+        if cancellation::is_cancelled() {
+            warn!(
+                "Cancelled after embedding {} of {} operations; none applied",
+                steps.len(),
+                input.operations.len()
+            );
+            return Ok(batch_result(ExecuteBatchOutput { steps: Vec::new() }));
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; batch of {} operations not applied", steps.len());
+            let steps = input
+                .operations
+                .into_iter()
+                .zip(&steps)
+                .map(|(operation, step)| {
+                    Written::Planned(planned(operation, batch::first_embedding(step)))
+                })
+                .collect();
+            return Ok(dry_run_result(ExecuteBatchOutput { steps }));
+        }
+
+        let results = self.supabase.execute_batch(&steps).await.map_err(|err| {
+            error!("Failed to execute batch: {}", err);
+            batch::batch_failed(err)
+        })?;
+
+        let duration = start_time.elapsed();
+        info!("Executed batch of {} operations in {:?}", results.len(), duration);
+
+        let records = results.iter().flat_map(batch::written_ids).map(Some);
+        self.audit("execute_batch", hash, records).await;
+
+        Ok(success(ExecuteBatchOutput {
+            steps: results.into_iter().map(Written::Row).collect(),
+        }))
+    }
+
+    #[tool(description = "Semantic nearest-neighbor search over historical transactions.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<TransactionMatchesOutput>())]
+    #[instrument(skip(self), fields(query = %input.query, limit = ?input.limit))]
+    pub async fn search_similar_transactions(
+        &self,
+        Parameters(input): Parameters<SearchSimilarInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Searching for similar transactions with query: {}", input.query);
+        
+        if input.query.trim().is_empty() {
+            warn!("Empty query provided for transaction search");
+            return Err(ToolError::invalid("query must not be empty", "query").into());
+        }
+
+        let embedding = self
+            .embedder
+            .embed_query(input.query.trim())
+            .await
+            .map_err(|err| {
+                error!("Failed to embed query text: {}", err);
+                ToolError::failed("embed query text", err)
+            })?;
+
+        let matches = self
+            .supabase
+            .search_similar_transactions(embedding, input.limit)
+            .await
+            .map_err(|err| {
+                error!("Failed to search similar transactions: {}", err);
+                ToolError::failed("search similar transactions", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Found {} similar transactions in {:?}", matches.len(), duration);
+        debug!("Transaction matches: {:?}", matches);
+
+        Ok(success(TransactionMatchesOutput { matches }))
+    }
+
+    #[tool(description = "Keyword search over transaction descriptions and raw source text, e.g. 'invoice 4812'. Every word must appear; use search_similar_transactions for fuzzy matches.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<TextMatchesOutput>())]
+    #[instrument(skip(self), fields(query = %input.query, limit = ?input.limit))]
+    pub async fn search_transactions_text(
+        &self,
+        Parameters(input): Parameters<SearchTextInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Searching transactions for text: {}", input.query);
+
+        if input.query.trim().is_empty() {
+            warn!("Empty query provided for text search");
+            return Err(ToolError::invalid("query must not be empty", "query").into());
+        }
+
+        let matches = self
+            .supabase
+            .search_transactions_text(input.query.trim(), input.limit)
+            .await
+            .map_err(|err| {
+                error!("Failed to search transactions by text: {}", err);
+                ToolError::failed("search transactions by text", err)
+            })?;
+
+        info!("Found {} matching transactions in {:?}", matches.len(), start_time.elapsed());
+        debug!("Text matches: {:?}", matches);
+
+        Ok(success(TextMatchesOutput { matches }))
+    }
+
+    #[tool(description = "Create or update a category with embeddings for semantic search.", annotations(destructive_hint = true, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<CategoryOutput>>())]
+    #[instrument(skip(self), fields(category_name = %input.name, kind = ?input.kind))]
+    pub async fn upsert_category(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<UpsertCategoryInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Upserting category: {}", input.name);
+        
+        let description_source = input.description.as_deref().unwrap_or(input.name.as_str());
+        let embedding = self
+            .embedder
+            .embed(description_source)
+            .await
+            .map_err(|err| {
+                error!("Failed to generate category embedding: {}", err);
+                ToolError::failed("generate category embedding", err)
+            })?;
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; category not upserted");
+            return Ok(dry_run_result(CategoryOutput {
+                category: Written::Planned(planned(input, Some(&embedding))),
+            }));
+        }
+
+        let category = self
+            .supabase
+            .upsert_category(&input, Some(embedding))
+            .await
+            .map_err(|err| {
+                error!("Failed to upsert category: {}", err);
+                ToolError::failed("upsert category", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Category upserted successfully in {:?}", duration);
+        debug!("Category record: {:?}", category);
+
+        self.audit("upsert_category", input_hash(&input), [Some(category.id.as_str())])
+            .await;
+
+        Ok(success(CategoryOutput {
+            category: Written::Row(category),
+        }))
+    }
+
+    #[tool(description = "Semantic search across categories by embedding query.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<CategoryMatchesOutput>())]
+    #[instrument(skip(self), fields(query = %input.query, limit = ?input.limit))]
+    pub async fn search_similar_categories(
+        &self,
+        Parameters(input): Parameters<SearchSimilarInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Searching for similar categories with query: {}", input.query);
+        
+        if input.query.trim().is_empty() {
+            warn!("Empty query provided for category search");
+            return Err(ToolError::invalid("query must not be empty", "query").into());
+        }
+
+        let embedding = self
+            .embedder
+            .embed_query(input.query.trim())
+            .await
+            .map_err(|err| {
+                error!("Failed to embed query text: {}", err);
+                ToolError::failed("embed query text", err)
+            })?;
+
+        let matches = self
+            .supabase
+            .search_similar_categories(embedding, input.limit)
+            .await
+            .map_err(|err| {
+                error!("Failed to search similar categories: {}", err);
+                ToolError::failed("search similar categories", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Found {} similar categories in {:?}", matches.len(), duration);
+        debug!("Category matches: {:?}", matches);
+
+        Ok(success(CategoryMatchesOutput { matches }))
+    }
+
+    #[tool(description = "List accounts with optional filters by type or name substring, paginated with limit/offset and a total count.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<AccountsOutput>())]
+    #[instrument(skip(self), fields(account_type = ?input.r#type, search = ?input.search))]
+    pub async fn list_accounts(
+        &self,
+        Parameters(input): Parameters<ListAccountsInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Listing accounts with filters: type={:?}, search={:?}", input.r#type, input.search);
+        
+        let accounts = self
+            .supabase
+            .list_accounts(&input)
+            .await
+            .map_err(|err| {
+                error!("Failed to list accounts: {}", err);
+                ToolError::failed("list accounts", err)
+            })?;
+
+        // Without a page the list itself is the full result.
+        let total = if input.limit.is_some() || input.offset.is_some_and(|offset| offset > 0) {
+            self.supabase.count_accounts(&input).await.map_err(|err| {
+                error!("Failed to count accounts: {}", err);
+                ToolError::failed("count accounts", err)
+            })?
+        } else {
+            accounts.len() as u64
+        };
+
+        let duration = start_time.elapsed();
+        info!("Found {} of {} accounts in {:?}", accounts.len(), total, duration);
+        debug!("Account list: {:?}", accounts);
+
+        Ok(success(AccountsOutput { accounts, total }))
+    }
+
+    #[tool(description = "List transactions newest first with optional date, account, category and direction filters, paginated with limit/offset and a total count.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<TransactionPageOutput>())]
+    #[instrument(skip(self), fields(limit = ?input.limit, offset = ?input.offset))]
+    pub async fn list_transactions(
+        &self,
+        Parameters(input): Parameters<ListTransactionsInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Listing transactions with filters: {:?}", input.filters);
+
+        let (transactions, total) = tokio::try_join!(
+            self.supabase.list_transactions(&input),
+            self.supabase.count_transactions(&input.filters),
+        )
+        .map_err(|err| {
+            error!("Failed to list transactions: {}", err);
+            ToolError::failed("list transactions", err)
+        })?;
+
+        let duration = start_time.elapsed();
+        info!("Found {} of {} transactions in {:?}", transactions.len(), total, duration);
+        debug!("Transaction list: {:?}", transactions);
+
+        Ok(success(TransactionPageOutput {
+            transactions,
+            total,
+            limit: page_limit(input.limit, Some(DEFAULT_TRANSACTION_PAGE)),
+            offset: input.offset.unwrap_or(0),
+        }))
+    }
+
+    #[tool(description = "Create or update an account keyed by name+type.", annotations(destructive_hint = true, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<AccountOutput>>())]
+    #[instrument(skip(self), fields(account_name = %input.name, account_type = %input.r#type, currency = %input.currency))]
+    pub async fn upsert_account(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<UpsertAccountInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Upserting account: {} ({})", input.name, input.r#type);
+        if input.currency.trim().is_empty() {
+            return Err(missing_field("currency"));
+        }
+        
+        let embedding = self
+            .embedder
+            .embed(&input.name)
+            .await
+            .map_err(|err| {
+                error!("Failed to generate account embedding: {}", err);
+                ToolError::failed("generate account embedding", err)
+            })?;
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; account not upserted");
+            return Ok(dry_run_result(AccountOutput {
+                account: Written::Planned(planned(input, Some(&embedding))),
+            }));
+        }
+
+        let account = self
+            .supabase
+            .upsert_account(&input)
+            .await
+            .map_err(|err| {
+                error!("Failed to upsert account: {}", err);
+                ToolError::failed("upsert account", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Account upserted successfully in {:?}", duration);
+        debug!("Account record: {:?}", account);
+
+        self.audit("upsert_account", input_hash(&input), [Some(account.id.as_str())])
+            .await;
+
+        Ok(success(AccountOutput {
+            account: Written::Row(account),
+        }))
+    }
+
+    #[tool(description = "Set defaults for the rest of this session: the account_id and currency that create_transaction(s) and upsert_account use when a call leaves them out, and a UTC offset such as +03:00 or a timezone such as Europe/Berlin for dates and times given without an offset. Omitted fields keep their value and an empty string clears one. Returns the defaults in effect.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<SessionDefaultsOutput>())]
+    #[instrument(skip(self))]
+    pub async fn set_session_defaults(
+        &self,
+        Parameters(input): Parameters<SessionDefaults>,
+    ) -> Result<CallToolResult, McpError> {
+        let defaults = self.update_session_defaults(input);
+        Ok(success(SessionDefaultsOutput { defaults }))
+    }
+
+    #[tool(description = "Soft-delete a transaction so it no longer appears in lists, searches or totals.", annotations(destructive_hint = true, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<DeletedOutput>>())]
+    #[instrument(skip(self), fields(id = %input.id))]
+    pub async fn delete_transaction(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<DeleteRecordInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        self.soft_delete_record(RecordKind::Transaction, input, dry_run).await
+    }
+
+    #[tool(description = "Soft-delete a category so it no longer appears in searches.", annotations(destructive_hint = true, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<DeletedOutput>>())]
+    #[instrument(skip(self), fields(id = %input.id))]
+    pub async fn delete_category(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<DeleteRecordInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        self.soft_delete_record(RecordKind::Category, input, dry_run).await
+    }
+
+    #[tool(description = "Soft-delete an account. Its transactions must be deleted first.", annotations(destructive_hint = true, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<DeletedOutput>>())]
+    #[instrument(skip(self), fields(id = %input.id))]
+    pub async fn delete_account(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<DeleteRecordInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        // Purging an account cascades to its transactions, so live ones must
+        // not be left behind a deleted account.
+        let remaining = self
+            .supabase
+            .count_account_transactions(input.id.trim())
+            .await
+            .map_err(|err| {
+                error!("Failed to count account transactions: {}", err);
+                ToolError::failed("count account transactions", err)
+            })?;
+        if remaining > 0 {
+            warn!("Refused to delete account {} with {} transactions", input.id, remaining);
+            return Err(ToolError::invalid(
+                format!("account still has {remaining} transactions; delete them first"),
+                "id",
+            )
+            .into());
+        }
+
+        self.soft_delete_record(RecordKind::Account, input, dry_run).await
+    }
+}
+
+/// Destructive upkeep and raw database access, each also gated by its own
+/// setting.
+#[tool_router(router = maintenance_tools)]
+impl ExaspoonDbServer {
+    #[tool(description = "Permanently remove soft-deleted rows, optionally of one kind or deleted before an RFC 3339 timestamp.", annotations(destructive_hint = true), output_schema = cached_schema_for_type::<Outcome<PurgeOutput>>())]
+    #[instrument(skip(self), fields(kind = ?input.kind, deleted_before = ?input.deleted_before))]
+    pub async fn purge_deleted(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<PurgeDeletedInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Purging soft-deleted rows");
+
+        if let Some(deleted_before) = &input.deleted_before {
+            if chrono::DateTime::parse_from_rfc3339(deleted_before).is_err() {
+                warn!("Invalid purge cutoff: {}", deleted_before);
+                return Err(ToolError::invalid(
+                    "deleted_before must be an RFC 3339 timestamp",
+                    "deleted_before",
+                )
+                .into());
+            }
+        }
+
+        let kinds = input.kind.map_or(RecordKind::ALL.to_vec(), |kind| vec![kind]);
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; nothing purged");
+            return Ok(dry_run_result(PurgeOutput {
+                tables: Some(kinds.iter().map(|kind| kind.table().to_string()).collect()),
+                deleted_before: input.deleted_before,
+                ..PurgeOutput::default()
+            }));
+        }
+        let mut purged = BTreeMap::new();
+        for kind in kinds {
+            let count = self
+                .supabase
+                .purge_deleted(kind, input.deleted_before.as_deref())
+                .await
+                .map_err(|err| {
+                    error!("Failed to purge {}: {}", kind.table(), err);
+                    ToolError::failed(&format!("purge {}", kind.table()), err)
+                })?;
+            purged.insert(kind.table().to_string(), count);
+        }
+
+        let duration = start_time.elapsed();
+        info!("Purged soft-deleted rows in {:?}: {:?}", duration, purged);
+
+        self.audit("purge_deleted", input_hash(&input), [None]).await;
+
+        Ok(success(PurgeOutput {
+            purged: Some(purged),
+            ..PurgeOutput::default()
+        }))
+    }
+
+    #[tool(description = "Find transaction and category embeddings that are missing, of the wrong dimension, or orphaned on deleted or text-less rows. `report` counts them; `backfill` re-embeds and `clear` removes them, one batch per call.", annotations(destructive_hint = true), output_schema = cached_schema_for_type::<Outcome<EmbeddingMaintenanceOutput>>())]
+    #[instrument(skip(self), fields(kind = ?input.kind, action = ?input.action))]
+    pub async fn embedding_maintenance(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<EmbeddingMaintenanceInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Running embedding maintenance: {:?}", input.action);
+
+        let batch_size = input.batch_size.unwrap_or(DEFAULT_MAINTENANCE_BATCH);
+        if !(1..=MAX_MAINTENANCE_BATCH).contains(&batch_size) {
+            return Err(ToolError::invalid(
+                format!("batch_size must be between 1 and {MAX_MAINTENANCE_BATCH}"),
+                "batch_size",
+            )
+            .into());
+        }
+        if input.kind == Some(RecordKind::Account) {
+            return Err(ToolError::invalid("accounts have no embeddings", "kind").into());
+        }
+        let dimension = match input.dimension {
+            Some(0) => {
+                return Err(ToolError::invalid("dimension must be positive", "dimension").into())
+            }
+            Some(dimension) => dimension,
+            None => self
+                .embedder
+                .embed("dimension probe")
+                .await
+                .map_err(|err| {
+                    error!("Failed to probe embedding dimension: {}", err);
+                    ToolError::failed("probe embedding dimension", err)
+                })?
+                .len(),
+        };
+
+        let kinds = input.kind.map_or(
+            vec![RecordKind::Transaction, RecordKind::Category],
+            |kind| vec![kind],
+        );
+        let dry_run = self.is_dry_run(dry_run);
+        let mut tables = BTreeMap::new();
+        let mut changed = Vec::new();
+        for kind in kinds {
+            if cancellation::is_cancelled() {
+                break;
+            }
+            let summary = match input.action {
+                EmbeddingMaintenanceAction::Report => self
+                    .report_embedding_issues(kind, dimension, batch_size)
+                    .await
+                    .map(TableMaintenance::Report),
+                action => self
+                    .repair_embeddings(kind, dimension, batch_size, action, dry_run, &mut changed)
+                    .await
+                    .map(TableMaintenance::Repair),
+            }
+            .map_err(|err| {
+                error!("Embedding maintenance of {} failed: {}", kind.table(), err);
+                ToolError::failed(&format!("maintain {} embeddings", kind.table()), err)
+            })?;
+            tables.insert(kind.table().to_string(), summary);
+        }
+
+        info!(
+            "Embedding maintenance finished in {:?}: {:?}",
+            start_time.elapsed(),
+            tables
+        );
+        let summary = EmbeddingMaintenanceOutput {
+            action: input.action,
+            dimension,
+            tables,
+        };
+        if dry_run {
+            return Ok(dry_run_result(summary));
+        }
+        if !changed.is_empty() {
+            self.audit(
+                "embedding_maintenance",
+                input_hash(&input),
+                changed.iter().map(|id| Some(id.as_str())),
+            )
+            .await;
+        }
+
+        self.log_to_client(LoggingLevel::Info, "Embedding maintenance finished", json!(summary))
+            .await;
+        Ok(batch_result(summary))
+    }
+
+    #[tool(description = "Populate demo accounts, categories and a few months of realistic transactions, with embeddings, so the search tools have data to try. Transactions are skipped if the demo accounts already have some.", annotations(destructive_hint = false, idempotent_hint = true), output_schema = cached_schema_for_type::<Outcome<SeedDemoDataOutput>>())]
+    #[instrument(skip(self), fields(months = ?input.months))]
+    pub async fn seed_demo_data(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<SeedDemoDataInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let months = input.months.unwrap_or(DEFAULT_DEMO_MONTHS);
+        if !(1..=MAX_DEMO_MONTHS).contains(&months) {
+            return Err(ToolError::invalid(
+                format!("months must be between 1 and {MAX_DEMO_MONTHS}"),
+                "months",
+            )
+            .into());
+        }
+        info!("Seeding {} months of demo data", months);
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; no demo data written");
+            // The demo accounts that exist already, which the real run would
+            // upsert onto.
+            let accounts = demo::demo_accounts();
+            let existing_ids = self
+                .all_accounts()
+                .await?
+                .into_iter()
+                .filter(|account| {
+                    accounts
+                        .iter()
+                        .any(|demo| demo.name == account.name && demo.r#type == account.r#type)
+                })
+                .map(|account| account.id)
+                .collect::<Vec<_>>();
+            let skipped_transactions = self.demo_transaction_count(&existing_ids).await? > 0;
+            let today = Utc::now().date_naive();
+            return Ok(dry_run_result(SeedDemoDataOutput {
+                accounts: accounts.len(),
+                categories: demo::demo_categories().len(),
+                transactions: if skipped_transactions {
+                    0
+                } else {
+                    demo::demo_transactions(today, months).len()
+                },
+                skipped_transactions,
+            }));
+        }
+
+        let progress = Progress::current();
+        let accounts = demo::demo_accounts();
+        let categories = demo::demo_categories();
+        let mut total = accounts.len() + categories.len();
+        let mut done = 0;
+        let mut account_ids = HashMap::new();
+        for account in accounts {
+            if cancellation::is_cancelled() {
+                break;
+            }
+            let record = self.supabase.upsert_account(&account).await.map_err(|err| {
+                error!("Failed to upsert demo account {}: {}", account.name, err);
+                ToolError::failed("upsert demo account", err)
+            })?;
+            account_ids.insert(account.name, record.id);
+            done += 1;
+            progress.report(done, total, "Seeded demo accounts").await;
+        }
+
+        let mut category_ids = Vec::new();
+        for category in categories {
+            if cancellation::is_cancelled() {
+                break;
+            }
+            let source = category.description.as_deref().unwrap_or(category.name.as_str());
+            let embedding = self.embedder.embed(source).await.map_err(|err| {
+                error!("Failed to generate category embedding: {}", err);
+                ToolError::failed("generate category embedding", err)
+            })?;
+            let record = self
+                .supabase
+                .upsert_category(&category, Some(embedding))
+                .await
+                .map_err(|err| {
+                    error!("Failed to upsert demo category {}: {}", category.name, err);
+                    ToolError::failed("upsert demo category", err)
+                })?;
+            category_ids.push(record.id);
+            done += 1;
+            progress.report(done, total, "Seeded demo categories").await;
+        }
+
+        let ids = account_ids.values().cloned().collect::<Vec<_>>();
+        let existing = self.demo_transaction_count(&ids).await?;
+        let mut transactions = Vec::new();
+        if existing == 0 {
+            let today = Utc::now().date_naive();
+            let demos = demo::demo_transactions(today, months);
+            total += demos.len();
+            let mut rows = Vec::with_capacity(demos.len());
+            for demo in demos {
+                if cancellation::is_cancelled() {
+                    break;
+                }
+                let mut transaction = demo.transaction;
+                transaction.account_id = account_ids[demo.account].clone();
+                let embedding = self
+                    .embedder
+                    .maybe_embed(transaction.description.as_deref())
+                    .await
+                    .map_err(|err| {
+                        error!("Failed to generate transaction embedding: {}", err);
+                        ToolError::failed("generate transaction embedding", err)
+                    })?;
+                rows.push((transaction, embedding));
+                done += 1;
+                progress.report(done, total, "Embedded demo transactions").await;
+            }
+            for batch in rows.chunks(MAX_BATCH_TRANSACTIONS) {
+                if cancellation::is_cancelled() {
+                    break;
+                }
+                let inserted = self.supabase.insert_transactions(batch).await.map_err(|err| {
+                    error!("Failed to insert demo transactions: {}", err);
+                    ToolError::failed("insert demo transactions", err)
+                })?;
+                transactions.extend(inserted);
+            }
+        } else {
+            info!("Demo accounts already hold {} transactions; not adding more", existing);
+            self.log_to_client(
+                LoggingLevel::Notice,
+                "Demo transactions skipped; the demo accounts already have some",
+                json!({ "existing_transactions": existing }),
+            )
+            .await;
+        }
+
+        info!(
+            "Seeded {} accounts, {} categories and {} transactions in {:?}",
+            account_ids.len(),
+            category_ids.len(),
+            transactions.len(),
+            start_time.elapsed()
+        );
+        let records = account_ids
+            .values()
+            .chain(&category_ids)
+            .chain(transactions.iter().map(|transaction| &transaction.id))
+            .map(|id| Some(id.as_str()));
+        self.audit("seed_demo_data", input_hash(&input), records)
+            .await;
+
+        let summary = SeedDemoDataOutput {
+            accounts: account_ids.len(),
+            categories: category_ids.len(),
+            transactions: transactions.len(),
+            skipped_transactions: existing > 0,
+        };
+        self.log_to_client(LoggingLevel::Info, "Demo data seeded", json!(summary))
+            .await;
+        Ok(batch_result(summary))
+    }
+
+    #[tool(description = "Call an allowlisted Postgres function with named JSON parameters and return its result.", output_schema = cached_schema_for_type::<Outcome<CallRpcOutput>>())]
+    #[instrument(skip(self, input), fields(function = %input.function))]
+    pub async fn call_rpc(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<CallRpcInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let function = input.function.trim();
+        if !self.rpc_allowlist.iter().any(|allowed| allowed == function) {
+            return Err(ToolError::invalid(
+                format!("function {function:?} is not in RPC_ALLOWLIST"),
+                "function",
+            )
+            .with("allowed", &*self.rpc_allowlist)
+            .into());
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} not called", function);
+            return Ok(dry_run_result(CallRpcOutput {
+                function: function.to_string(),
+                result: None,
+                params: Some(input.params),
+            }));
+        }
+        info!("Calling allowlisted function {}", function);
+
+        // Hashed before the params move into the call; the function may
+        // write, so every call is audited.
+        let hash = input_hash(&input);
+        let result = self
+            .supabase
+            .call_function(function, Value::Object(input.params))
+            .await
+            .map_err(|err| {
+                error!("Failed to call {}: {}", function, err);
+                ToolError::failed(&format!("call {function}"), err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Called {} in {:?}", function, duration);
+        debug!("{} result: {:?}", function, result);
+
+        self.audit("call_rpc", hash, [None]).await;
+
+        Ok(success(CallRpcOutput {
+            function: function.to_string(),
+            result: Some(result),
+            params: None,
+        }))
+    }
+}
+
+/// Reports over the ledger and its change history, and their exports.
+#[tool_router(router = analytics_tools)]
+impl ExaspoonDbServer {
+    #[tool(description = "List the change history of mutations made through this server, newest first, optionally for one tool or row.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<AuditEventsOutput>())]
+    #[instrument(skip(self), fields(tool = ?input.tool, record_id = ?input.record_id))]
+    pub async fn list_audit_events(
+        &self,
+        Parameters(input): Parameters<ListAuditEventsInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Listing audit events");
+
+        let events = self
+            .supabase
+            .list_audit_events(&input)
+            .await
+            .map_err(|err| {
+                error!("Failed to list audit events: {}", err);
+                ToolError::failed("list audit events", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Found {} audit events in {:?}", events.len(), duration);
+
+        Ok(success(AuditEventsOutput { events }))
+    }
+
+    #[tool(description = "Sum transaction amounts by category, account or direction, per currency and optional day/week/month/year period.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<SpendingOutput>())]
+    #[instrument(skip(self), fields(group_by = ?input.group_by, period = ?input.period))]
+    pub async fn aggregate_spending(
+        &self,
+        Parameters(input): Parameters<AggregateSpendingInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Aggregating spending by {}", input.group_by.as_ref());
+
+        let buckets = self
+            .supabase
+            .aggregate_spending(&input)
+            .await
+            .map_err(|err| {
+                error!("Failed to aggregate spending: {}", err);
+                ToolError::failed("aggregate spending", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Aggregated spending into {} buckets in {:?}", buckets.len(), duration);
+        debug!("Spending buckets: {:?}", buckets);
+        let base_totals = self.in_base_currency(&buckets, None).await;
+
+        Ok(success(SpendingOutput {
+            buckets,
+            base_totals,
+        }))
+    }
+
+    #[tool(description = "Summarize a month: totals by direction, expenses by category and the largest expenses, plus a narrative written by the client's model through MCP sampling when the client supports it. `month` is YYYY-MM, the current month by default.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<PeriodSummaryOutput>())]
+    #[instrument(skip(self), fields(month = ?input.month))]
+    pub async fn summarize_period(
+        &self,
+        Parameters(input): Parameters<SummarizePeriodInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let month = prompts::parse_month(input.month.as_deref())?;
+        info!("Summarizing {}", month.format("%Y-%m"));
+
+        let summary = self.period_summary(month).await?;
+
+        let duration = start_time.elapsed();
+        info!("Summarized period in {:?}", duration);
+        debug!("Period summary: {:?}", summary);
+
+        Ok(success(summary))
+    }
+
+    #[tool(description = "Value the accounts: income less expenses per account and currency through the end of a `date` (YYYY-MM-DD), today by default, and with an FX provider their total in the base currency at that day's rates. Transfers between accounts are left out.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<NetWorthOutput>())]
+    #[instrument(skip(self), fields(date = ?input.date))]
+    pub async fn get_net_worth(
+        &self,
+        Parameters(input): Parameters<GetNetWorthInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let date = input
+            .date
+            .as_deref()
+            .map(|date| {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                    McpError::from(ToolError::invalid("date must be YYYY-MM-DD", "date"))
+                })
+            })
+            .transpose()?;
+        info!("Valuing accounts at {:?}", date);
+
+        let filters = TransactionFilters {
+            to: date.map(|date| format!("{}T00:00:00Z", date + chrono::Days::new(1))),
+            ..TransactionFilters::default()
+        };
+        let (income, expenses) = tokio::try_join!(
+            self.aggregate(
+                SpendingGroupBy::Account,
+                None,
+                TransactionFilters {
+                    direction: Some(TransactionDirection::Income),
+                    ..filters.clone()
+                },
+            ),
+            self.aggregate(
+                SpendingGroupBy::Account,
+                None,
+                TransactionFilters {
+                    direction: Some(TransactionDirection::Expense),
+                    ..filters
+                },
+            ),
+        )?;
+        let mut sums: BTreeMap<(Option<String>, String), (f64, u64)> = BTreeMap::new();
+        let signed = income
+            .iter()
+            .map(|bucket| (bucket, 1.0))
+            .chain(expenses.iter().map(|bucket| (bucket, -1.0)));
+        for (bucket, sign) in signed {
+            let sum = sums
+                .entry((bucket.key.clone(), bucket.currency.clone()))
+                .or_default();
+            sum.0 += sign * bucket.total;
+            sum.1 += bucket.count;
+        }
+        let balances: Vec<SpendingBucket> = sums
+            .into_iter()
+            .map(|((key, currency), (total, count))| SpendingBucket {
+                key,
+                period_start: None,
+                currency,
+                total,
+                count,
+            })
+            .collect();
+        let base_totals = self.in_base_currency(&balances, date).await;
+        let net_worth = base_totals.as_ref().map(|base| {
+            let total: f64 = base.buckets.iter().map(|bucket| bucket.total).sum();
+            (total * 100.0).round() / 100.0
+        });
+
+        let duration = start_time.elapsed();
+        info!("Valued {} balances in {:?}", balances.len(), duration);
+
+        Ok(success(NetWorthOutput {
+            date: date.map(|date| date.to_string()),
+            balances,
+            base_totals,
+            net_worth,
+        }))
+    }
+
+    #[tool(description = "Convert an amount between currencies at the latest rate, or at a `date`'s (YYYY-MM-DD). `to` defaults to the base currency.", annotations(read_only_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<ConvertCurrencyOutput>())]
+    #[instrument(skip(self), fields(from = %input.from, to = ?input.to, date = ?input.date))]
+    pub async fn convert_currency(
+        &self,
+        Parameters(input): Parameters<ConvertCurrencyInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let fx = self.fx()?;
+        let from = currency_code(&input.from, "from")?;
+        let to = match input.to.as_deref() {
+            Some(to) if !to.is_empty() => currency_code(to, "to")?,
+            _ => fx.base_currency().to_string(),
+        };
+        let date = input
+            .date
+            .as_deref()
+            .map(|date| {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                    McpError::from(ToolError::invalid("date must be YYYY-MM-DD", "date"))
+                })
+            })
+            .transpose()?;
+        info!("Converting {} {} into {}", input.amount, from, to);
+
+        let rate = fx
+            .rate(&from, &to, date)
+            .await
+            .map_err(|err| match err.downcast_ref::<UnknownCurrency>() {
+                Some(UnknownCurrency(code)) => {
+                    warn!("No exchange rates for {}", code);
+                    let field = if *code == from { "from" } else { "to" };
+                    ToolError::invalid(format!("no exchange rates for {code}"), field).into()
+                }
+                None => fx_failed("read exchange rates", err),
+            })?;
+        let converted = input.amount * rate.rate;
+
+        let duration = start_time.elapsed();
+        info!(
+            "Converted {} {} into {} {} in {:?}",
+            input.amount, from, converted, to, duration
+        );
+
+        Ok(success(ConvertCurrencyOutput {
+            amount: input.amount,
+            from,
+            to,
+            converted,
+            rate: rate.rate,
+            date: rate.date.to_string(),
+            provider: fx.provider(),
+        }))
+    }
+
+    #[tool(description = "Price a crypto asset, by ticker symbol such as BTC or CoinGecko coin id, in a fiat currency at the latest price or a `date`'s (YYYY-MM-DD), valuing `amount` units of it when given. `vs_currency` defaults to the base currency.", annotations(read_only_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<AssetPriceOutput>())]
+    #[instrument(skip(self), fields(asset = %input.asset, vs_currency = ?input.vs_currency, date = ?input.date))]
+    pub async fn get_asset_price(
+        &self,
+        Parameters(input): Parameters<GetAssetPriceInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let prices = self.prices()?;
+        let asset = input.asset.trim().to_ascii_uppercase();
+        if asset.is_empty() {
+            return Err(ToolError::invalid("asset must not be empty", "asset").into());
+        }
+        let vs_currency = match input.vs_currency.as_deref() {
+            Some(currency) if !currency.is_empty() => currency_code(currency, "vs_currency")?,
+            _ => prices.base_currency().to_string(),
+        };
+        let date = input
+            .date
+            .as_deref()
+            .map(|date| {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                    McpError::from(ToolError::invalid("date must be YYYY-MM-DD", "date"))
+                })
+            })
+            .transpose()?;
+        info!("Pricing {} in {}", asset, vs_currency);
+
+        let price = prices
+            .price(&input.asset, &vs_currency, date)
+            .await
+            .map_err(|err| {
+                if let Some(UnknownAsset(asset)) = err.downcast_ref::<UnknownAsset>() {
+                    warn!("No CoinGecko coin for {}", asset);
+                    return ToolError::invalid(format!("no CoinGecko coin for {asset}"), "asset")
+                        .into();
+                }
+                if let Some(UnknownCurrency(currency)) = err.downcast_ref::<UnknownCurrency>() {
+                    warn!("No CoinGecko prices in {}", currency);
+                    return ToolError::invalid(
+                        format!("no CoinGecko prices in {currency}"),
+                        "vs_currency",
+                    )
+                    .into();
+                }
+                coingecko_failed("read asset price", err)
+            })?;
+
+        let duration = start_time.elapsed();
+        info!("Priced {} at {} {} in {:?}", asset, price.price, vs_currency, duration);
+
+        Ok(success(AssetPriceOutput {
+            asset,
+            coin_id: price.coin_id,
+            vs_currency,
+            price: price.price,
+            as_of: price.as_of,
+            amount: input.amount,
+            value: input.amount.map(|amount| amount * price.price),
+        }))
+    }
+
+    #[tool(description = "Export transactions, or monthly totals by category, to a Google Sheets tab, which is replaced in one step, or to a Notion database, which gets a page per row; exporting to Notion again updates the pages of rows already exported. Accounts and categories are written by name, expenses as negative amounts, at most 1000 rows. A Notion export stopped partway reports pages_written in the error data; running it again finishes it.", annotations(destructive_hint = true, open_world_hint = true), output_schema = cached_schema_for_type::<Outcome<ExportToSheetOutput>>())]
+    #[instrument(skip(self, input), fields(destination = ?input.destination, report = input.report.as_ref()))]
+    pub async fn export_to_sheet(
+        &self,
+        Parameters(DryRun { input, dry_run }): Parameters<DryRun<ExportToSheetInput>>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let destination = match (input.destination, &self.google_sheets, &self.notion) {
+            (Some(destination), _, _) => destination,
+            (None, Some(_), None) => SheetDestination::GoogleSheets,
+            (None, None, Some(_)) => SheetDestination::Notion,
+            _ => {
+                return Err(ToolError::invalid(
+                    "destination is required when both Google Sheets and Notion are configured",
+                    "destination",
+                )
+                .into())
+            }
+        };
+        let configured = match destination {
+            SheetDestination::GoogleSheets => self.google_sheets()?.spreadsheet_id(),
+            SheetDestination::Notion => self.notion()?.database_id(),
+        };
+        let target = input
+            .target
+            .as_deref()
+            .map(str::trim)
+            .filter(|target| !target.is_empty())
+            .or(configured)
+            .ok_or_else(|| {
+                McpError::from(ToolError::invalid(
+                    format!("target is required; no {destination} target is configured"),
+                    "target",
+                ))
+            })?
+            .to_string();
+        let sheet = match destination {
+            SheetDestination::GoogleSheets => Some(
+                input
+                    .sheet
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|sheet| !sheet.is_empty())
+                    .unwrap_or(match input.report {
+                        SheetReport::Transactions => "Transactions",
+                        SheetReport::MonthlySummary => "Monthly summary",
+                    })
+                    .to_string(),
+            ),
+            SheetDestination::Notion => None,
+        };
+        info!("Exporting {} to {}", input.report.as_ref(), destination);
+        // Financial data leaves for a target the caller picks, so every
+        // export is audited, even one that stopped partway.
+        let hash = input_hash(&input);
+
+        let (table, truncated) = self.sheet_table(input.report, &input.filters).await?;
+        if truncated {
+            warn!("Exporting only the newest {} rows", MAX_SHEET_ROWS);
+        }
+        if self.is_dry_run(dry_run) {
+            info!("Dry run; {} rows not exported", table.rows.len());
+            return Ok(dry_run_result(ExportToSheetOutput {
+                destination,
+                report: input.report,
+                target,
+                sheet,
+                rows: table.rows.len(),
+                truncated,
+                url: None,
+            }));
+        }
+
+        let (rows, url) = match (destination, &sheet) {
+            (SheetDestination::GoogleSheets, Some(sheet)) => {
+                let written = self
+                    .google_sheets()?
+                    .write(&target, sheet, &table)
+                    .await
+                    .map_err(|err| google_failed("write Google Sheet", err))?;
+                (table.rows.len(), Some(written.url))
+            }
+            _ => {
+                let added = match self.notion()?.append(&target, &table).await {
+                    Ok(added) => added,
+                    Err(err) => {
+                        if err.downcast_ref::<Interrupted>().is_some() {
+                            self.audit("export_to_sheet", hash, [None]).await;
+                        }
+                        return Err(notion_failed("write Notion pages", err));
+                    }
+                };
+                (added.pages, added.url)
+            }
+        };
+        self.audit("export_to_sheet", hash, [None]).await;
+
+        let duration = start_time.elapsed();
+        info!("Exported {} rows to {} in {:?}", rows, destination, duration);
+
+        Ok(batch_result(ExportToSheetOutput {
+            destination,
+            report: input.report,
+            target,
+            sheet,
+            rows,
+            truncated,
+            url,
+        }))
+    }
+
+    #[tool(description = "Export accounts, categories and transactions as a plain-text accounting journal, beancount or ledger (which hledger reads too), to audit with those tools. Each transaction balances its account under Assets: against an Expenses: or Income: account named after its category; transfers go to or come from Equity:Transfers, by the direction their import recorded. The journal is in the journal field. At most the newest 5000 transactions.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<ExportLedgerOutput>())]
+    #[instrument(skip(self), fields(format = input.format.as_ref()))]
+    pub async fn export_ledger(
+        &self,
+        Parameters(input): Parameters<ExportLedgerInput>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        info!("Exporting a {} journal", input.format.as_ref());
+
+        let (accounts, categories, newest) = tokio::join!(
+            self.all_accounts(),
+            self.all_categories(),
+            self.newest_transactions(&input.filters, MAX_LEDGER_TRANSACTIONS)
+        );
+        let (mut transactions, truncated) = newest?;
+        if truncated {
+            warn!("Exporting only the newest {} transactions", MAX_LEDGER_TRANSACTIONS);
+        }
+        transactions.reverse();
+        let journal = ledger::write(input.format, &accounts?, &categories, &transactions);
+
+        let duration = start_time.elapsed();
+        info!(
+            "Exported {} transactions as {} in {:?}",
+            transactions.len(),
+            input.format.as_ref(),
+            duration
+        );
+
+        Ok(success(ExportLedgerOutput {
+            format: input.format,
+            transactions: transactions.len(),
+            truncated,
+            journal,
+        }))
+    }
+}
+
+/// Liveness, dependency health, metrics and capabilities of the server
+/// itself.
+#[tool_router(router = monitoring_tools)]
+impl ExaspoonDbServer {
+    #[tool(description = "Cheap liveness check that touches neither the database nor the embedding provider: uptime, server and protocol versions, and the configured backends.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<PingOutput>())]
+    #[instrument(skip(self))]
+    pub async fn ping(&self) -> Result<CallToolResult, McpError> {
+        debug!("Answering ping");
+        Ok(success(PingOutput {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            server_name: self.identity.name.clone(),
+            server_version: self.identity.version.clone(),
+            protocol_version: PROTOCOL_VERSION.to_string(),
+            providers: self.providers.as_deref().cloned(),
+        }))
+    }
+
+    #[tool(description = "Probe the database, search RPCs and embedding provider, reporting per-dependency status and latency.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<HealthCheckOutput>())]
+    #[instrument(skip(self))]
+    pub async fn health_check(&self) -> Result<CallToolResult, McpError> {
+        let health = self.probe_health().await;
+        if health.status == HealthStatus::Degraded {
+            self.log_to_client(
+                LoggingLevel::Warning,
+                "Dependencies degraded",
+                json!({ "checks": &health.checks }),
+            )
+            .await;
+        }
+        Ok(success(health))
+    }
+
+    #[tool(description = "Describe every tool this server exposes: its purpose, the tables and RPCs it needs, whether it writes, and an example call using ids from the database. Call this first to learn how to use the server.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<CapabilitiesOutput>())]
+    #[instrument(skip(self))]
+    pub async fn describe_capabilities(&self) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let capabilities = self.capabilities().await;
+        info!(
+            "Described {} tools in {:?}",
+            capabilities.tools.len(),
+            start_time.elapsed()
+        );
+        Ok(success(capabilities))
+    }
+
+    #[tool(description = "Call counts, error rates, latency percentiles and histograms since the server started: per tool, per kind of embedding request, and per database table and RPC, slowest in total first.", annotations(read_only_hint = true), output_schema = cached_schema_for_type::<ServerMetricsOutput>())]
+    #[instrument(skip(self))]
+    pub async fn server_metrics(&self) -> Result<CallToolResult, McpError> {
+        let operations = self.supabase.metrics();
+        debug!("Reporting metrics for {} operations", operations.len());
+        Ok(success(ServerMetricsOutput {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            sessions: self.sessions.active(),
+            tools: self.tool_metrics.snapshot(),
+            embeddings: self.embedding_metrics.snapshot(),
+            operations,
+        }))
+    }
+}
+
+impl ExaspoonDbServer {
+    async fn soft_delete_record(
+        &self,
+        kind: RecordKind,
+        input: DeleteRecordInput,
+        dry_run: Option<bool>,
+    ) -> Result<CallToolResult, McpError> {
+        let start_time = Instant::now();
+        let id = input.id.trim();
+        info!("Deleting {} {}", kind.as_ref(), id);
+
+        if id.is_empty() {
+            warn!("Empty id provided for {} delete", kind.as_ref());
+            return Err(ToolError::invalid("id must not be empty", "id").into());
+        }
+
+        let dry_run = self.is_dry_run(dry_run);
+        let deleted = if dry_run {
+            self.supabase.record_exists(kind, id).await
+        } else {
+            self.supabase.soft_delete(kind, id).await
+        }
+        .map_err(|err| {
+            error!("Failed to delete {}: {}", kind.as_ref(), err);
+            ToolError::failed(&format!("delete {}", kind.as_ref()), err)
+        })?;
+        if !deleted {
+            warn!("No live {} with id {}", kind.as_ref(), id);
+            return Err(ToolError::new(
+                ErrorKind::NotFound,
+                format!("no {} with id {id}", kind.as_ref()),
+            )
+            .with("field", "id")
+            .into());
+        }
+        if dry_run {
+            info!("Dry run; {} {} not deleted", kind.as_ref(), id);
+            return Ok(dry_run_result(DeletedOutput {
+                deleted: DeletedRecord { kind, id: id.to_string() },
+            }));
+        }
+
+        let duration = start_time.elapsed();
+        info!("Deleted {} {} in {:?}", kind.as_ref(), id, duration);
+        self.audit(&format!("delete_{}", kind.as_ref()), input_hash(&input), [Some(id)])
+            .await;
+
+        Ok(success(DeletedOutput {
+            deleted: DeletedRecord { kind, id: id.to_string() },
+        }))
+    }
+
+    /// Runs a tool call within the caller's auth context, with its progress
+    /// token, cancellation and client available to the tool. Its arguments
+    /// are validated and the call waits for a free slot first, within its
+    /// time limit.
+    async fn dispatch(
+        &self,
+        mut request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Some(arguments) = &mut request.arguments {
+            self.apply_session_defaults(&request.name, arguments);
+            validation::validate(arguments).map_err(|invalid| {
+                warn!("Rejected {} call: invalid {}", request.name, invalid.field);
+                invalid_argument(invalid)
+            })?;
+        }
+        self.throttle(&request.name)?;
+        let preview = self.check_confirmation(&mut request)?;
+        let tool = request.name.clone();
+        let writes = self
+            .tool_router
+            .map
+            .get(tool.as_ref())
+            .is_some_and(|route| can_write(&route.attr));
+        let auth = AuthContext::from_meta(&context.meta);
+        debug!("Tool call carries user token: {}", auth.access_token.is_some());
+        let progress = Progress::new(context.peer.clone(), &context.meta);
+        let cancel = context.ct.clone();
+        let peer = context.peer.clone();
+        let tcc = ToolCallContext::new(self, request, context);
+        let call = elicitation::scope(peer, self.tool_router.call(tcc));
+        let call = cancellation::scope(cancel, call);
+        let call = async {
+            let _permit = self.slots.acquire(writes).await;
+            auth.scope(progress.scope(call)).await
+        };
+        let result = self.time_limit(&tool, call).await;
+        match preview {
+            Some(fingerprint) => {
+                result.map(|result| self.issue_confirmation(&tool, &fingerprint, result))
+            }
+            None => result,
+        }
+    }
+
+    /// Runs `call` of `tool` within the tool's time limit. A call that
+    /// outlives it is dropped and fails with [`TIMED_OUT`].
+    async fn time_limit(
+        &self,
+        tool: &str,
+        call: impl Future<Output = Result<CallToolResult, McpError>>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(limit) = self.timeouts.for_tool(tool) else {
+            return call.await;
+        };
+        tokio::time::timeout(limit, call).await.unwrap_or_else(|_| {
+            warn!("{} timed out after {:?}", tool, limit);
+            let tool = self.tool_names.exposed(tool);
+            let message = Message::TimedOut { tool: &tool, limit }.text();
+            Err(ToolError::new(ErrorKind::TimedOut, message)
+                .with("tool", tool)
+                .with("timeout_ms", limit.as_millis() as u64)
+                .into())
+        })
+    }
+
+    /// Counts a call of `tool` against the session's rate limit, failing
+    /// with [`RATE_LIMITED`] when it is over budget.
+    fn throttle(&self, tool: &str) -> Result<(), McpError> {
+        let embeds = EMBEDDING_TOOLS.contains(&tool);
+        self.rate_limiter.acquire(embeds).map_err(|limited| {
+            warn!("Throttled {}: {}", tool, limited);
+            ToolError::new(ErrorKind::RateLimited, Message::RateLimited(&limited).text())
+                .with("budget", limited.budget)
+                .with("limit", limited.limit)
+                .with("window_secs", limited.window.as_secs())
+                .with("retry_after_ms", limited.retry_in.as_millis() as u64)
+                .into()
+        })
+    }
+
+    /// Whether a mutating call should skip its writes: always under
+    /// [`Self::with_dry_run`], otherwise when the call asked for it.
+    fn is_dry_run(&self, requested: Option<bool>) -> bool {
+        self.dry_run || requested.unwrap_or(false)
+    }
+
+    /// Embeds each transaction's description, reporting progress. Stops
+    /// early, returning those embedded so far, once the call is cancelled.
+    async fn embed_transactions(
+        &self,
+        transactions: Vec<CreateTransactionInput>,
+    ) -> Result<Vec<(CreateTransactionInput, Option<Vec<f32>>)>, McpError> {
+        let progress = Progress::current();
+        let total = transactions.len();
+        let mut rows = Vec::with_capacity(total);
+        for transaction in transactions {
+            if cancellation::is_cancelled() {
+                break;
+            }
+            let embedding = self
+                .embedder
+                .maybe_embed(transaction.description.as_deref())
+                .await
+                .map_err(|err| {
+                    error!("Failed to generate transaction embedding: {}", err);
+                    ToolError::failed("generate transaction embedding", err)
+                })?;
+            rows.push((transaction, embedding));
+            progress.report(rows.len(), total, "Embedded transactions").await;
+        }
+        Ok(rows)
+    }
+
+    /// FITIDs of the live transactions of `account_id` imported from a
+    /// statement, posted within [`OFX_DUPLICATE_WINDOW_DAYS`] of the dates
+    /// in `statement`. Banks sometimes re-date a pending transaction once it
+    /// posts, so the window is wider than the statement.
+    async fn recorded_fitids(
+        &self,
+        account_id: &str,
+        statement: &[StatementTransaction],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = statement
+            .iter()
+            .filter_map(|line| chrono::DateTime::parse_from_rfc3339(&line.posted_at).ok());
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, OFX_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| ofx::fitid(raw_source))
+            .collect())
+    }
+
+    /// Plaid `transaction_id`s of the live transactions of `account_id`
+    /// recorded by a sync, dated within [`PLAID_DUPLICATE_WINDOW_DAYS`] of
+    /// `transactions`.
+    async fn recorded_plaid_ids(
+        &self,
+        account_id: &str,
+        transactions: &[&PlaidTransaction],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = transactions.iter().filter_map(|transaction| {
+            let occurred_at = transaction.occurred_at().ok()?;
+            chrono::DateTime::parse_from_rfc3339(&occurred_at).ok()
+        });
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, PLAID_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| plaid::transaction_id(raw_source))
+            .collect())
+    }
+
+    /// Raw sources of the live transactions of `account_id` dated from
+    /// `window_days` before the first of `dates` to as many after the last.
+    async fn raw_sources_around(
+        &self,
+        account_id: &str,
+        dates: impl Iterator<Item = chrono::DateTime<chrono::FixedOffset>> + Clone,
+        window_days: i64,
+    ) -> Result<Vec<String>, McpError> {
+        let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+            return Ok(Vec::new());
+        };
+        let window = chrono::Duration::days(window_days);
+        let bound = |date: chrono::DateTime<chrono::FixedOffset>| {
+            date.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        };
+        let filters = TransactionFilters {
+            from: Some(bound(first - window)),
+            to: Some(bound(last + window)),
+            account_id: Some(account_id.to_string()),
+            ..Default::default()
+        };
+        self.raw_sources(&filters).await
+    }
+
+    /// Identities, as [`gocardless::entry_id`] reads them, of the live
+    /// transactions of `account_id` recorded by an open-banking sync, dated
+    /// within [`OPENBANKING_DUPLICATE_WINDOW_DAYS`] of `transactions`.
+    async fn recorded_entry_ids(
+        &self,
+        account_id: &str,
+        transactions: &[BookedTransaction],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = transactions.iter().filter_map(|transaction| {
+            let occurred_at = transaction.occurred_at().ok()?;
+            chrono::DateTime::parse_from_rfc3339(&occurred_at).ok()
+        });
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, OPENBANKING_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| gocardless::entry_id(raw_source))
+            .collect())
+    }
+
+    /// The open-banking link kept in the metadata of `account_id`, if any.
+    async fn openbanking_link(&self, account_id: &str) -> Result<Option<OpenBankingLink>, McpError> {
+        let metadata = self
+            .supabase
+            .account_metadata(account_id)
+            .await
+            .map_err(|err| {
+                error!("Failed to read account metadata: {}", err);
+                ToolError::failed("read account metadata", err)
+            })?;
+        metadata
+            .get(OPENBANKING_METADATA_KEY)
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|err| ToolError::failed("read open banking link", err.into()).into())
+    }
+
+    async fn save_openbanking_link(
+        &self,
+        account_id: &str,
+        link: &OpenBankingLink,
+    ) -> Result<(), McpError> {
+        let value = serde_json::to_value(link)
+            .map_err(|err| ToolError::failed("save open banking link", err.into()))?;
+        self.supabase
+            .set_account_metadata(account_id, OPENBANKING_METADATA_KEY, value)
+            .await
+            .map_err(|err| {
+                error!("Failed to save open banking link: {}", err);
+                ToolError::failed("save open banking link", err).into()
+            })
+    }
+
+    /// The GoCardless client, which the [`OPENBANKING_TOOLS`] are only listed
+    /// with.
+    fn gocardless(&self) -> Result<&GoCardlessClient, McpError> {
+        self.gocardless.as_deref().ok_or_else(|| {
+            ToolError::failed(
+                "reach GoCardless",
+                anyhow::anyhow!("GOCARDLESS_SECRET_ID is not set"),
+            )
+            .into()
+        })
+    }
+
+    /// Identities, as [`onchain::entry_id`] reads them, of the live
+    /// transactions of `account_id` recorded by an onchain sync, dated
+    /// within [`ONCHAIN_DUPLICATE_WINDOW_DAYS`] of `transfers`.
+    async fn recorded_transfer_ids(
+        &self,
+        account_id: &str,
+        transfers: &[Transfer],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = transfers
+            .iter()
+            .map(|transfer| transfer.timestamp.fixed_offset());
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, ONCHAIN_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| onchain::entry_id(raw_source))
+            .collect())
+    }
+
+    /// The account `account_id`, or the invalid_params error for
+    /// `account_id` when there is none.
+    async fn find_account(&self, account_id: &str) -> Result<Account, McpError> {
+        let account = self
+            .all_accounts()
+            .await?
+            .into_iter()
+            .find(|account| account.id == account_id)
+            .ok_or_else(|| {
+                ToolError::invalid(format!("account {account_id} was not found"), "account_id")
+            })?;
+        Ok(account)
+    }
+
+    async fn all_accounts(&self) -> Result<Vec<Account>, McpError> {
+        self.supabase
+            .list_accounts(&ListAccountsInput::default())
+            .await
+            .map_err(|err| {
+                error!("Failed to list accounts: {}", err);
+                ToolError::failed("list accounts", err).into()
+            })
+    }
+
+    /// How many transactions the demo accounts `account_ids` hold already.
+    /// Accounts and categories upsert by name, but transactions would be
+    /// duplicated, so `seed_demo_data` adds none once there are some.
+    async fn demo_transaction_count(&self, account_ids: &[String]) -> Result<u64, McpError> {
+        let mut existing = 0;
+        for account_id in account_ids {
+            existing += self
+                .supabase
+                .count_account_transactions(account_id)
+                .await
+                .map_err(|err| {
+                    error!("Failed to count demo transactions: {}", err);
+                    ToolError::failed("count demo transactions", err)
+                })?;
+        }
+        Ok(existing)
+    }
+
+    /// Every category, or none where the backend cannot list them, in which
+    /// case exports show category ids.
+    async fn all_categories(&self) -> Vec<Category> {
+        self.supabase.list_categories().await.unwrap_or_else(|err| {
+            warn!("Exporting category ids; categories could not be listed: {:#}", err);
+            Vec::new()
+        })
+    }
+
+    /// The newest `max` transactions matching `filters`, and whether more
+    /// matched.
+    async fn newest_transactions(
+        &self,
+        filters: &TransactionFilters,
+        max: usize,
+    ) -> Result<(Vec<Transaction>, bool), McpError> {
+        let mut transactions = Vec::new();
+        // One past the most taken tells whether there were more.
+        while transactions.len() <= max {
+            let page = self
+                .supabase
+                .list_transactions(&ListTransactionsInput {
+                    filters: filters.clone(),
+                    limit: Some(MAX_PAGE_SIZE),
+                    offset: Some(transactions.len() as u32),
+                })
+                .await
+                .map_err(|err| {
+                    error!("Failed to list transactions: {}", err);
+                    ToolError::failed("list transactions", err)
+                })?;
+            let full = page.len() == MAX_PAGE_SIZE as usize;
+            transactions.extend(page);
+            if !full {
+                break;
+            }
+        }
+        let truncated = transactions.len() > max;
+        transactions.truncate(max);
+        Ok((transactions, truncated))
+    }
+
+    /// The account `account_id`, which `sync_onchain` only syncs when it is
+    /// an onchain one.
+    async fn onchain_account(&self, account_id: &str) -> Result<Account, McpError> {
+        let account = self.find_account(account_id).await?;
+        if account.r#type != AccountType::Onchain {
+            return Err(ToolError::invalid(
+                format!(
+                    "account {account_id} is {}; sync_onchain syncs onchain accounts",
+                    account.r#type
+                ),
+                "account_id",
+            )
+            .into());
+        }
+        Ok(account)
+    }
+
+    /// The address kept in the metadata of `account_id`, if any.
+    async fn onchain_link(&self, account_id: &str) -> Result<Option<OnchainLink>, McpError> {
+        let metadata = self
+            .supabase
+            .account_metadata(account_id)
+            .await
+            .map_err(|err| {
+                error!("Failed to read account metadata: {}", err);
+                ToolError::failed("read account metadata", err)
+            })?;
+        metadata
+            .get(ONCHAIN_METADATA_KEY)
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|err| ToolError::failed("read onchain address", err.into()).into())
+    }
+
+    async fn save_onchain_link(&self, account_id: &str, link: &OnchainLink) -> Result<(), McpError> {
+        let value = serde_json::to_value(link)
+            .map_err(|err| ToolError::failed("save onchain address", err.into()))?;
+        self.supabase
+            .set_account_metadata(account_id, ONCHAIN_METADATA_KEY, value)
+            .await
+            .map_err(|err| {
+                error!("Failed to save onchain address: {}", err);
+                ToolError::failed("save onchain address", err).into()
+            })
+    }
+
+    /// Records `chain` as the network of `account`, which had none, so that
+    /// it is listed with the chain its address was found to be on.
+    async fn record_network(&self, account: &Account, chain: Chain) -> Result<(), McpError> {
+        self.supabase
+            .set_account_network(&account.id, &chain.to_string())
+            .await
+            .map_err(|err| {
+                error!("Failed to record account network: {}", err);
+                ToolError::failed("record account network", err).into()
+            })
+    }
+
+    /// The Etherscan client, which Ethereum addresses are synced with.
+    fn etherscan(&self) -> Result<&EtherscanClient, McpError> {
+        self.etherscan.as_deref().ok_or_else(|| {
+            ToolError::failed(
+                "reach Etherscan",
+                anyhow::anyhow!("ETHERSCAN_API_KEY is not set"),
+            )
+            .into()
+        })
+    }
+
+    /// The Blockbook client, which Bitcoin addresses are synced with.
+    fn blockbook(&self) -> Result<&BlockbookClient, McpError> {
+        self.blockbook.as_deref().ok_or_else(|| {
+            ToolError::failed("reach Blockbook", anyhow::anyhow!("BLOCKBOOK_URL is not set")).into()
+        })
+    }
+
+    /// The Solana RPC client, which Solana wallets are synced with.
+    fn solana(&self) -> Result<&SolanaClient, McpError> {
+        self.solana.as_deref().ok_or_else(|| {
+            ToolError::failed("reach Solana RPC", anyhow::anyhow!("SOLANA_RPC_URL is not set"))
+                .into()
+        })
+    }
+
+    /// Ids of the movements already recorded for `account_id` around the
+    /// dates of `movements`, as [`exchange::entry_id`] reads them.
+    async fn recorded_movement_ids(
+        &self,
+        account_id: &str,
+        movements: &[Movement],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = movements
+            .iter()
+            .map(|movement| movement.timestamp.fixed_offset());
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, EXCHANGE_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| exchange::entry_id(raw_source))
+            .collect())
+    }
+
+    /// The account `account_id`, which `sync_exchange` only syncs when it is
+    /// an exchange one.
+    async fn exchange_account(&self, account_id: &str) -> Result<Account, McpError> {
+        let account = self.find_account(account_id).await?;
+        if account.r#type != AccountType::Exchange {
+            return Err(ToolError::invalid(
+                format!(
+                    "account {account_id} is {}; sync_exchange syncs exchange accounts",
+                    account.r#type
+                ),
+                "account_id",
+            )
+            .into());
+        }
+        Ok(account)
+    }
+
+    /// The exchange kept in the metadata of `account_id`, if any.
+    async fn exchange_link(&self, account_id: &str) -> Result<Option<ExchangeLink>, McpError> {
+        let metadata = self
+            .supabase
+            .account_metadata(account_id)
+            .await
+            .map_err(|err| {
+                error!("Failed to read account metadata: {}", err);
+                ToolError::failed("read account metadata", err)
+            })?;
+        metadata
+            .get(EXCHANGE_METADATA_KEY)
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|err| ToolError::failed("read exchange link", err.into()).into())
+    }
+
+    async fn save_exchange_link(&self, account_id: &str, link: &ExchangeLink) -> Result<(), McpError> {
+        let value = serde_json::to_value(link)
+            .map_err(|err| ToolError::failed("save exchange link", err.into()))?;
+        self.supabase
+            .set_account_metadata(account_id, EXCHANGE_METADATA_KEY, value)
+            .await
+            .map_err(|err| {
+                error!("Failed to save exchange link: {}", err);
+                ToolError::failed("save exchange link", err).into()
+            })
+    }
+
+    /// The Binance client, which Binance accounts are synced with.
+    fn binance(&self) -> Result<&BinanceClient, McpError> {
+        self.binance.as_deref().ok_or_else(|| {
+            ToolError::failed("reach Binance", anyhow::anyhow!("BINANCE_API_KEY is not set"))
+                .into()
+        })
+    }
+
+    /// The Coinbase client, which Coinbase accounts are synced with.
+    fn coinbase(&self) -> Result<&CoinbaseClient, McpError> {
+        self.coinbase.as_deref().ok_or_else(|| {
+            ToolError::failed("reach Coinbase", anyhow::anyhow!("COINBASE_API_KEY is not set"))
+                .into()
+        })
+    }
+
+    /// The exchange rates, which the [`FX_TOOLS`] are only listed with.
+    fn fx(&self) -> Result<&FxClient, McpError> {
+        self.fx.as_deref().ok_or_else(|| {
+            ToolError::failed("reach exchange rates", anyhow::anyhow!("FX_PROVIDER is not set"))
+                .into()
+        })
+    }
+
+    /// The vision model, which the [`RECEIPT_TOOLS`] are only listed with.
+    fn receipts(&self) -> Result<&ReceiptReader, McpError> {
+        self.receipts.as_deref().ok_or_else(|| {
+            ToolError::failed("reach the vision model", anyhow::anyhow!("RECEIPT_MODEL is not set"))
+                .into()
+        })
+    }
+
+    /// The CoinGecko client, which the [`PRICE_TOOLS`] are only listed with.
+    fn prices(&self) -> Result<&CoinGeckoClient, McpError> {
+        self.prices.as_deref().ok_or_else(|| {
+            ToolError::failed("reach CoinGecko", anyhow::anyhow!("PRICE_PROVIDER is not set"))
+                .into()
+        })
+    }
+
+    /// The Google Sheets client, which `export_to_sheet` writes to Google
+    /// with.
+    fn google_sheets(&self) -> Result<&GoogleSheetsClient, McpError> {
+        self.google_sheets.as_deref().ok_or_else(|| {
+            ToolError::failed(
+                "reach Google Sheets",
+                anyhow::anyhow!("GOOGLE_SHEETS_CLIENT_ID is not set"),
+            )
+            .into()
+        })
+    }
+
+    /// The Notion client, which `export_to_sheet` writes to Notion with.
+    fn notion(&self) -> Result<&NotionClient, McpError> {
+        self.notion.as_deref().ok_or_else(|| {
+            ToolError::failed("reach Notion", anyhow::anyhow!("NOTION_TOKEN is not set")).into()
+        })
+    }
+
+    /// The YNAB client, which the [`YNAB_TOOLS`] are only listed with and
+    /// `import_ynab` reads the API with.
+    fn ynab(&self) -> Result<&YnabClient, McpError> {
+        self.ynab.as_deref().ok_or_else(|| {
+            ToolError::failed("reach YNAB", anyhow::anyhow!("YNAB_TOKEN is not set")).into()
+        })
+    }
+
+    /// The Plaid client, which the [`BANK_TOOLS`] are only listed with.
+    fn plaid(&self) -> Result<&PlaidClient, McpError> {
+        self.plaid.as_deref().ok_or_else(|| {
+            ToolError::failed("reach Plaid", anyhow::anyhow!("PLAID_CLIENT_ID is not set")).into()
+        })
+    }
+
+    /// GnuCash split ids, as [`gnucash::split_id`] reads them, of the live
+    /// transactions of `account_id` recorded by an earlier import, dated
+    /// within [`GNUCASH_DUPLICATE_WINDOW_DAYS`] of the entries of the GnuCash
+    /// account `gnucash_id`.
+    async fn recorded_split_ids(
+        &self,
+        account_id: &str,
+        gnucash_id: &str,
+        entries: &[gnucash::Entry],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = entries
+            .iter()
+            .filter(|entry| entry.account == gnucash_id)
+            .filter_map(|entry| chrono::DateTime::parse_from_rfc3339(&entry.posted_at).ok());
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, GNUCASH_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| gnucash::split_id(raw_source))
+            .collect())
+    }
+
+    /// YNAB ids, as [`ynab::transaction_id`] reads them, of the live
+    /// transactions of `account_id` recorded by an earlier import, dated
+    /// within [`YNAB_DUPLICATE_WINDOW_DAYS`] of the transactions of the YNAB
+    /// account `account`.
+    async fn recorded_ynab_ids(
+        &self,
+        account_id: &str,
+        account: &str,
+        transactions: &[ynab::YnabTransaction],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = transactions
+            .iter()
+            .filter(|transaction| transaction.account == account)
+            .filter_map(|transaction| {
+                chrono::DateTime::parse_from_rfc3339(&transaction.date).ok()
+            });
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, YNAB_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| ynab::transaction_id(raw_source))
+            .collect())
+    }
+
+    /// Receipt ids, as [`receipt::receipt_id`] reads them, of the live
+    /// transactions of `account_id` recorded from a receipt, dated within
+    /// [`RECEIPT_DUPLICATE_WINDOW_DAYS`] of `expenses`.
+    async fn recorded_receipt_ids(
+        &self,
+        account_id: &str,
+        expenses: &[CreateTransactionInput],
+    ) -> Result<HashSet<String>, McpError> {
+        let dates = expenses
+            .iter()
+            .filter_map(|expense| chrono::DateTime::parse_from_rfc3339(&expense.occurred_at).ok());
+        let raw_sources = self
+            .raw_sources_around(account_id, dates, RECEIPT_DUPLICATE_WINDOW_DAYS)
+            .await?;
+        Ok(raw_sources
+            .iter()
+            .filter_map(|raw_source| receipt::receipt_id(raw_source))
+            .collect())
+    }
+
+    /// How many live transactions of `account_id` booked on the dates of
+    /// `entries` have each raw source. An entry with the same source text as
+    /// one of them was imported before.
+    async fn recorded_entries(
+        &self,
+        account_id: &str,
+        entries: &[StatementEntry],
+    ) -> Result<HashMap<String, usize>, McpError> {
+        let dates = entries
+            .iter()
+            .filter_map(|entry| chrono::DateTime::parse_from_rfc3339(&entry.booked_at).ok());
+        let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+            return Ok(HashMap::new());
+        };
+        let bound = |date: chrono::DateTime<chrono::FixedOffset>| {
+            date.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        };
+        let filters = TransactionFilters {
+            from: Some(bound(first)),
+            to: Some(bound(last + chrono::Duration::days(1))),
+            account_id: Some(account_id.to_string()),
+            ..Default::default()
+        };
+        let mut recorded = HashMap::new();
+        for raw_source in self.raw_sources(&filters).await? {
+            *recorded.entry(raw_source).or_insert(0) += 1;
+        }
+        Ok(recorded)
+    }
+
+    /// Raw sources of the live transactions matching `filters`, read a page
+    /// at a time.
+    async fn raw_sources(&self, filters: &TransactionFilters) -> Result<Vec<String>, McpError> {
+        let (pages, mut received) = tokio::sync::mpsc::channel::<Vec<Transaction>>(2);
+        let collect = async move {
+            let mut raw_sources = Vec::new();
+            while let Some(page) = received.recv().await {
+                raw_sources.extend(page.into_iter().filter_map(|row| row.raw_source));
+            }
+            raw_sources
+        };
+        let (streamed, raw_sources) = tokio::join!(
+            self.supabase
+                .stream_transactions(filters, MAX_PAGE_SIZE, pages),
+            collect
+        );
+        streamed.map_err(|err| {
+            error!("Failed to list recorded transactions: {}", err);
+            ToolError::failed("list recorded transactions", err)
+        })?;
+        Ok(raw_sources)
+    }
+
+    /// The existing category for each distinct category of `records`, in
+    /// order of first use. A category with the same name as the QIF one, or
+    /// as its last part (`Groceries` for `Food:Groceries`), wins whether or
+    /// not it has an embedding; otherwise the most similar one does if it
+    /// reaches `min_similarity`.
+    async fn map_qif_categories(
+        &self,
+        records: &[qif::QifTransaction],
+        min_similarity: f64,
+    ) -> Result<Vec<QifCategoryMapping>, McpError> {
+        let mut mappings: Vec<QifCategoryMapping> = Vec::new();
+        for name in records.iter().filter_map(|record| record.category.as_ref()) {
+            let known = mappings
+                .iter_mut()
+                .find(|mapping| &mapping.qif_category == name);
+            match known {
+                Some(mapping) => mapping.transactions += 1,
+                None => mappings.push(QifCategoryMapping {
+                    qif_category: name.clone(),
+                    transactions: 1,
+                    category: None,
+                    matched_by: None,
+                    similarity: None,
+                }),
+            }
+        }
+        let existing = self.supabase.list_categories().await.map_err(|err| {
+            error!("Failed to list categories: {}", err);
+            ToolError::failed("list categories", err)
+        })?;
+        for mapping in &mut mappings {
+            let leaf = mapping
+                .qif_category
+                .rsplit(':')
+                .next()
+                .unwrap_or_default()
+                .trim();
+            let named = |name: &str| {
+                existing
+                    .iter()
+                    .find(|category| category.name.trim().eq_ignore_ascii_case(name))
+            };
+            if let Some(found) = named(mapping.qif_category.trim()).or_else(|| named(leaf)) {
+                debug!(
+                    "QIF category {:?} maps to {:?} by name",
+                    mapping.qif_category, found.name
+                );
+                mapping.category = Some(found.clone());
+                mapping.matched_by = Some(MatchedBy::Name);
+                continue;
+            }
+
+            let query = mapping.qif_category.replace(':', " ");
+            let embedding = self.embedder.embed_query(&query).await.map_err(|err| {
+                error!("Failed to embed QIF category: {}", err);
+                ToolError::failed("embed QIF category", err)
+            })?;
+            let matches = self
+                .supabase
+                .search_similar_categories(embedding, Some(1))
+                .await
+                .map_err(|err| {
+                    error!("Failed to search similar categories: {}", err);
+                    ToolError::failed("search similar categories", err)
+                })?;
+            let found = matches.into_iter().next().filter(|best| {
+                best.similarity
+                    .is_some_and(|similarity| similarity >= min_similarity)
+            });
+            if let Some(found) = found {
+                debug!(
+                    "QIF category {:?} maps to {:?} by similarity",
+                    mapping.qif_category, found.category.name
+                );
+                mapping.category = Some(found.category);
+                mapping.matched_by = Some(MatchedBy::Similarity);
+                mapping.similarity = found.similarity;
+            }
+        }
+        Ok(mappings)
+    }
+
+    /// The full router minus the tools this instance has not enabled.
+    fn routes(&self) -> ToolRouter<Self> {
+        let mut router = ToolRouter::new();
+        for group in self.tool_groups.iter() {
+            router.merge(group.router());
+        }
+        if !self.admin_tools {
+            for name in ADMIN_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if !self.demo_seed {
+            router.remove_route("seed_demo_data");
+        }
+        if self.rpc_allowlist.is_empty() {
+            router.remove_route("call_rpc");
+        }
+        if self.plaid.is_none() {
+            for name in BANK_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.gocardless.is_none() {
+            for name in OPENBANKING_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.etherscan.is_none() && self.blockbook.is_none() && self.solana.is_none() {
+            for name in ONCHAIN_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.binance.is_none() && self.coinbase.is_none() {
+            for name in EXCHANGE_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.fx.is_none() {
+            for name in FX_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.prices.is_none() {
+            for name in PRICE_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.receipts.is_none() {
+            for name in RECEIPT_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.google_sheets.is_none() && self.notion.is_none() {
+            for name in SHEET_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        if self.ynab.is_none() {
+            for name in YNAB_TOOLS {
+                router.remove_route(name);
+            }
+        }
+        let disabled = router
+            .list_all()
+            .into_iter()
+            .filter(|tool| {
+                (self.read_only && can_write(tool))
+                    || self
+                        .disabled_tools
+                        .iter()
+                        .any(|pattern| tool_matches(pattern, &tool.name))
+            })
+            .collect::<Vec<_>>();
+        for tool in disabled {
+            router.remove_route(&tool.name);
+        }
+        if self.confirmations.is_some() {
+            for route in router.map.values_mut() {
+                if needs_confirmation(&route.attr) {
+                    route.attr = confirmation::with_token_argument(route.attr.clone());
+                }
+            }
+        }
+        router
+    }
+
+    /// Probes the database, the embedding provider and, with a vector from
+    /// it, the search RPCs.
+    async fn probe_dependencies(&self) -> Vec<DependencyHealth> {
+        let ((database, _), (embedding_check, embedding)) = tokio::join!(
+            probe("database", self.supabase.ping()),
+            probe("embedding", self.embedder.embed_query("health check")),
+        );
+        let mut checks = vec![database, embedding_check];
+
+        // The search RPCs need a query vector, so they are only probed when
+        // the embedding provider produced one.
+        match embedding {
+            Some(embedding) => {
+                let ((transactions, _), (categories, _)) = tokio::join!(
+                    probe(
+                        "search_similar_transactions",
+                        self.supabase
+                            .search_similar_transactions(embedding.clone(), Some(1)),
+                    ),
+                    probe(
+                        "search_similar_categories",
+                        self.supabase.search_similar_categories(embedding, Some(1)),
+                    ),
+                );
+                checks.push(transactions);
+                checks.push(categories);
+            }
+            None => {
+                for name in ["search_similar_transactions", "search_similar_categories"] {
+                    checks.push(DependencyHealth {
+                        name: name.to_string(),
+                        ok: false,
+                        latency_ms: 0,
+                        error: Some("skipped: embedding provider unavailable".to_string()),
+                    });
+                }
+            }
+        }
+        checks
+    }
+
+    /// Probes every dependency as `health_check` does, for callers outside
+    /// an MCP session such as the health endpoint.
+    pub async fn probe_health(&self) -> HealthCheckOutput {
+        let start_time = Instant::now();
+        info!("Running health check");
+
+        let checks = self.probe_dependencies().await;
+        let healthy = checks.iter().all(|check| check.ok);
+        let duration = start_time.elapsed();
+        if healthy {
+            info!("Health check passed in {:?}", duration);
+        } else {
+            warn!("Health check degraded in {:?}: {:?}", duration, checks);
+        }
+        HealthCheckOutput {
+            status: if healthy { HealthStatus::Ok } else { HealthStatus::Degraded },
+            checks,
+        }
+    }
+
+    /// Whether the server has started shutting down and refuses new calls.
+    pub fn is_draining(&self) -> bool {
+        self.drain.is_draining()
+    }
+
+    /// Checks a deployment before it serves clients: the tables the tools
+    /// read and write, then everything `health_check` probes.
+    pub async fn startup_check(&self) -> HealthCheckOutput {
+        let start_time = Instant::now();
+        let accounts = ListAccountsInput {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let transactions = ListTransactionsInput {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let audit_events = ListAuditEventsInput {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let ((accounts, _), (categories, _), (transactions, _), (audit_log, _)) = tokio::join!(
+            probe("accounts", self.supabase.list_accounts(&accounts)),
+            probe("categories", self.supabase.list_category_names("", 1)),
+            probe("transactions", self.supabase.list_transactions(&transactions)),
+            probe(AUDIT_LOG_TABLE, self.supabase.list_audit_events(&audit_events)),
+        );
+        let mut checks = vec![accounts, categories, transactions, audit_log];
+        checks.extend(self.probe_dependencies().await);
+        let healthy = checks.iter().all(|check| check.ok);
+        info!("Startup check finished in {:?}", start_time.elapsed());
+        HealthCheckOutput {
+            status: if healthy { HealthStatus::Ok } else { HealthStatus::Degraded },
+            checks,
+        }
+    }
+
+    /// Writes a throwaway account, category and transaction, finds them
+    /// through the search RPCs and deletes them for good, for a one-shot
+    /// smoke test of a new deployment. The steps stop at the first failure,
+    /// but whatever was written is always cleaned up.
+    pub async fn verify_deployment(&self) -> HealthCheckOutput {
+        let start_time = Instant::now();
+        let mut checks = Vec::new();
+        let mut created = Vec::new();
+        self.verify_steps(&mut checks, &mut created).await;
+
+        // Newest first, so the transaction goes before its account. A
+        // failed delete does not stop the others.
+        let cleanup = async {
+            let mut failures = Vec::new();
+            for (kind, id) in created.iter().rev() {
+                if let Err(err) = self.supabase.delete_record(*kind, id).await {
+                    failures.push(format!("{} {id}: {err:#}", kind.table()));
+                }
+            }
+            if failures.is_empty() {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("failed to delete {}", failures.join("; ")))
+            }
+        };
+        checks.push(probe("cleanup", cleanup).await.0);
+        let healthy = checks.iter().all(|check| check.ok);
+        info!(
+            "Deployment verification finished in {:?}",
+            start_time.elapsed()
+        );
+        HealthCheckOutput {
+            status: if healthy { HealthStatus::Ok } else { HealthStatus::Degraded },
+            checks,
+        }
+    }
+
+    /// The steps of [`Self::verify_deployment`] up to the first that fails,
+    /// noting each row written in `created`.
+    async fn verify_steps(
+        &self,
+        checks: &mut Vec<DependencyHealth>,
+        created: &mut Vec<(RecordKind, String)>,
+    ) {
+        let marker = format!("exaspoon-verify-{}", uuid::Uuid::new_v4().simple());
+        let description = format!("{marker} deployment check");
+
+        let (check, embedding) = probe("embedding", self.embedder.embed(&description)).await;
+        checks.push(check);
+        let Some(embedding) = embedding else { return };
+        let (check, query) =
+            probe("embedding query", self.embedder.embed_query(&description)).await;
+        checks.push(check);
+        let Some(query) = query else { return };
+        let dimensions = async {
+            if query.len() != embedding.len() {
+                anyhow::bail!(
+                    "queries embed to {} dimensions but rows to {}",
+                    query.len(),
+                    embedding.len()
+                );
+            }
+            self.supabase
+                .embedding_issues(RecordKind::Transaction, embedding.len(), None, 1)
+                .await
+        };
+        let (check, issues) = probe("vector dimensions", dimensions).await;
+        checks.push(check);
+        if issues.is_none() {
+            return;
+        }
+
+        let account = UpsertAccountInput {
+            name: marker.clone(),
+            r#type: AccountType::Offchain,
+            currency: "USD".to_string(),
+            network: None,
+            institution: None,
+        };
+        let (check, account) =
+            probe("create account", self.supabase.upsert_account(&account)).await;
+        checks.push(check);
+        let Some(account) = account else { return };
+        created.push((RecordKind::Account, account.id.clone()));
+
+        let category = UpsertCategoryInput {
+            name: marker.clone(),
+            kind: Some(CategoryKind::Expense),
+            description: Some(description.clone()),
+        };
+        let (check, category) = probe(
+            "create category",
+            self.supabase
+                .upsert_category(&category, Some(embedding.clone())),
+        )
+        .await;
+        checks.push(check);
+        let Some(category) = category else { return };
+        created.push((RecordKind::Category, category.id.clone()));
+
+        let transaction = CreateTransactionInput {
+            account_id: account.id,
+            amount: 1.0,
+            currency: "USD".to_string(),
+            direction: TransactionDirection::Expense,
+            occurred_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            description: Some(description),
+            raw_source: None,
+        };
+        let (check, transaction) = probe(
+            "create transaction",
+            self.supabase
+                .insert_transaction(&transaction, Some(embedding)),
+        )
+        .await;
+        checks.push(check);
+        let Some(transaction) = transaction else {
+            return;
+        };
+        created.push((RecordKind::Transaction, transaction.id.clone()));
+
+        let transactions = async {
+            let matches = self
+                .supabase
+                .search_similar_transactions(query.clone(), Some(MAX_SIMILAR_LIMIT))
+                .await?;
+            if !matches.iter().any(|found| found.transaction.id == transaction.id) {
+                anyhow::bail!("the new transaction is not among {} matches", matches.len());
+            }
+            anyhow::Ok(())
+        };
+        let categories = async {
+            let matches = self
+                .supabase
+                .search_similar_categories(query.clone(), Some(MAX_SIMILAR_LIMIT))
+                .await?;
+            if !matches.iter().any(|found| found.category.id == category.id) {
+                anyhow::bail!("the new category is not among {} matches", matches.len());
+            }
+            anyhow::Ok(())
+        };
+        let text = async {
+            let matches = self
+                .supabase
+                .search_transactions_text(&marker, Some(1))
+                .await?;
+            if !matches.iter().any(|found| found.transaction.id == transaction.id) {
+                anyhow::bail!("the new transaction was not found by its description");
+            }
+            anyhow::Ok(())
+        };
+        let ((transactions, _), (categories, _), (text, _)) = tokio::join!(
+            probe("search_similar_transactions", transactions),
+            probe("search_similar_categories", categories),
+            probe("search_transactions_text", text),
+        );
+        checks.extend([transactions, categories, text]);
+    }
+
+    /// Refuses new tool calls, waits up to `deadline` for the running ones
+    /// to finish and flushes the call log, returning how many calls were
+    /// still running at the deadline.
+    pub async fn shut_down(&self, deadline: Duration) -> usize {
+        let start_time = Instant::now();
+        info!("Draining {} tool calls", self.drain.in_flight());
+        let unfinished = self.drain.drain(deadline).await;
+        if unfinished > 0 {
+            warn!("{} tool calls still running after {:?}", unfinished, deadline);
+        }
+        if let Some(call_log) = &self.call_log {
+            if let Err(err) = call_log.flush().await {
+                error!("{:#}", err);
+            }
+        }
+        info!("Drained tool calls in {:?}", start_time.elapsed());
+        unfinished
+    }
+
+    /// The locale `peer` asked for, else the configured one.
+    fn locale_of(&self, peer: &Peer<RoleServer>) -> Locale {
+        peer.peer_info()
+            .and_then(Locale::requested_by)
+            .unwrap_or(self.locale)
+    }
+
+    /// Tells the clients subscribed to `uri`, if any, that the resource
+    /// changed. A client that can no longer be reached is unsubscribed.
+    pub async fn notify_resource_updated(&self, uri: &str) {
+        let peers = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get(uri)
+            .cloned()
+            .unwrap_or_default();
+        for (session_id, peer) in peers {
+            let param = ResourceUpdatedNotificationParam {
+                uri: uri.to_string(),
+            };
+            if let Err(err) = peer.notify_resource_updated(param).await {
+                warn!("Failed to notify subscriber of {}: {}", uri, err);
+                self.unsubscribe_session(uri, &session_id);
+            }
+        }
+    }
+
+    fn unsubscribe_session(&self, uri: &str, session_id: &str) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if let Some(peers) = subscriptions.get_mut(uri) {
+            peers.remove(session_id);
+            if peers.is_empty() {
+                subscriptions.remove(uri);
+            }
+        }
+    }
+
+    /// The newest page of transactions as JSON.
+    async fn read_transactions_resource(&self) -> Result<ReadResourceResult, McpError> {
+        let start_time = Instant::now();
+        let input = ListTransactionsInput::default();
+        let transactions = self.supabase.list_transactions(&input).await.map_err(|err| {
+            error!("Failed to read transactions resource: {}", err);
+            ToolError::failed("read transactions", err)
+        })?;
+
+        let duration = start_time.elapsed();
+        info!("Read {} transactions for resource in {:?}", transactions.len(), duration);
+
+        let text = serde_json::to_string(&transactions)
+            .map_err(|err| ToolError::failed("serialize transactions", err.into()))?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: TRANSACTIONS_RESOURCE_URI.to_string(),
+                mime_type: Some("application/json".to_string()),
+                text,
+                meta: None,
+            }],
+        })
+    }
+
+    /// Counts every embedding issue of `kind`, reading `page_size` rows at a
+    /// time, and lists a few ids of each.
+    async fn report_embedding_issues(
+        &self,
+        kind: RecordKind,
+        dimension: usize,
+        page_size: u32,
+    ) -> anyhow::Result<EmbeddingReport> {
+        let mut report = EmbeddingReport::default();
+        let mut after: Option<String> = None;
+        loop {
+            let issues = self
+                .supabase
+                .embedding_issues(kind, dimension, after.as_deref(), page_size)
+                .await?;
+            for issue in &issues {
+                report.record(issue, MAINTENANCE_SAMPLE_IDS);
+            }
+            let more = issues.len() == page_size as usize && !cancellation::is_cancelled();
+            match issues.last() {
+                Some(last) if more => after = Some(last.id.clone()),
+                _ => return Ok(report),
+            }
+        }
+    }
+
+    /// Re-embeds (`backfill`) or removes (`clear`) up to `batch_size`
+    /// embeddings of `kind`, adding the ids it changed to `changed`. A dry
+    /// run computes the embeddings but stores nothing.
+    async fn repair_embeddings(
+        &self,
+        kind: RecordKind,
+        dimension: usize,
+        batch_size: u32,
+        action: EmbeddingMaintenanceAction,
+        dry_run: bool,
+        changed: &mut Vec<String>,
+    ) -> anyhow::Result<EmbeddingRepair> {
+        let wanted = |issue: EmbeddingIssueKind| match action {
+            EmbeddingMaintenanceAction::Backfill => issue != EmbeddingIssueKind::Orphaned,
+            _ => issue != EmbeddingIssueKind::Missing,
+        };
+        // Progress counts every id changed in this call, so it keeps rising
+        // from one table to the next.
+        let progress = Progress::current();
+        let total = changed.len() + batch_size as usize;
+        let message = format!("Repaired {} embeddings", kind.table());
+        let mut updated = 0u32;
+        let mut after: Option<String> = None;
+        let complete = loop {
+            let issues = self
+                .supabase
+                .embedding_issues(kind, dimension, after.as_deref(), batch_size)
+                .await?;
+            for issue in issues.iter().filter(|issue| wanted(issue.issue)) {
+                if updated == batch_size || cancellation::is_cancelled() {
+                    break;
+                }
+                let embedding = match (action, issue.content.as_deref()) {
+                    (EmbeddingMaintenanceAction::Backfill, Some(content)) => {
+                        Some(self.embedder.embed(content).await?)
+                    }
+                    _ => None,
+                };
+                if !dry_run {
+                    self.supabase
+                        .set_embedding(kind, &issue.id, embedding)
+                        .await?;
+                }
+                changed.push(issue.id.clone());
+                updated += 1;
+                progress.report(changed.len(), total, &message).await;
+            }
+            if updated == batch_size || cancellation::is_cancelled() {
+                break false;
+            }
+            match issues.last() {
+                Some(last) if issues.len() == batch_size as usize => after = Some(last.id.clone()),
+                _ => break true,
+            }
+        };
+        debug!("Updated {} {} embeddings", updated, kind.table());
+        Ok(EmbeddingRepair { updated, complete })
+    }
+
+    /// Records one audit event per affected row. Failures are logged rather
+    /// than returned, because the change itself has already been made.
+    async fn audit<'a>(
+        &self,
+        tool: &str,
+        input_hash: String,
+        record_ids: impl IntoIterator<Item = Option<&'a str>>,
+    ) {
+        let occurred_at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let events = record_ids
+            .into_iter()
+            .map(|record_id| AuditEvent {
+                tool: tool.to_string(),
+                input_hash: input_hash.clone(),
+                record_id: record_id.map(str::to_string),
+                occurred_at: occurred_at.clone(),
+                session_id: Some(self.session_id.clone()),
+                details: None,
+            })
+            .collect::<Vec<_>>();
+        if let Err(err) = self.supabase.record_audit_events(&events).await {
+            error!("Failed to record {} audit events for {}: {}", events.len(), tool, err);
+            self.log_to_client(
+                LoggingLevel::Error,
+                "Failed to record audit events",
+                json!({ "tool": tool, "events": events.len(), "error": err.to_string() }),
+            )
+            .await;
+        }
+    }
+}
+
+impl ServerHandler for ExaspoonDbServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_prompts()
+                .enable_logging()
+                .enable_completions()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .build(),
+            server_info: Implementation {
+                name: self.identity.name.clone(),
+                title: self.identity.title.clone(),
+                version: self.identity.version.clone(),
+                icons: None,
+                website_url: None,
+            },
+            instructions: Some(self.identity.instructions.clone()),
+        }
+    }
+
+    /// Runs the call in the client's locale, timing it for `server_metrics`
+    /// and recording it when a call log is configured. Every error leaves
+    /// with an `error_code`, including those rmcp raises itself. Once the
+    /// server is shutting down, calls are refused. Tools are called by the
+    /// names clients list them under and known by their own from then on.
+    async fn call_tool(
+        &self,
+        mut request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let version = protocol::negotiated(&context.peer);
+        let Some(tool) = self.tool_names.original(&request.name) else {
+            warn!("Refused call of unknown tool {}", request.name);
+            return Err(unknown_tool(&request.name));
+        };
+        request.name = tool.clone().into();
+        let arguments = self
+            .call_log
+            .as_ref()
+            .map(|_| Value::Object(request.arguments.clone().unwrap_or_default()));
+        let locale = self.locale_of(&context.peer);
+        let correlation_id = correlation::generate();
+        let span = info_span!("tool_call", tool = %tool, correlation_id = %correlation_id);
+        let Some(_in_flight) = self.drain.enter() else {
+            span.in_scope(|| warn!("Refused {} call: shutting down", tool));
+            let refused = Message::ShuttingDown.localize(locale);
+            let error = ToolError::new(ErrorKind::UpstreamUnavailable, refused)
+                .with(CORRELATION_ID_FIELD, correlation_id);
+            return Err(error.into());
+        };
+        let start_time = Instant::now();
+        let call = i18n::scope(locale, self.dispatch(request, context));
+        let outcome = correlation::scope(correlation_id.clone(), call)
+            .instrument(span.clone())
+            .await
+            .map_err(errors::with_error_code)
+            .map_err(|err| errors::with_correlation_id(err, &correlation_id));
+        let elapsed = start_time.elapsed();
+        let succeeded = outcome.as_ref().is_ok_and(|result| result.is_error != Some(true));
+        info!(
+            parent: &span,
+            duration_ms = elapsed.as_millis() as u64,
+            ok = succeeded,
+            "Finished {} call",
+            tool
+        );
+        self.tool_metrics.record(&tool, elapsed, succeeded);
+        if let (Some(call_log), Some(arguments)) = (&self.call_log, arguments) {
+            call_log
+                .record(&tool, &self.session_id, arguments, &outcome, elapsed)
+                .await;
+        }
+        outcome.map(|result| protocol::result_for(&version, result))
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let version = protocol::negotiated(&context.peer);
+        let mut tools = protocol::tools_for(&version, self.tool_router.list_all());
+        if !self.tool_names.is_identity() {
+            for tool in &mut tools {
+                tool.name = self.tool_names.exposed(&tool.name).into();
+            }
+        }
+        Ok(ListToolsResult::with_all_items(tools))
+    }
+
+    /// Builds the prompt under the caller's auth context and locale, like
+    /// `call_tool`.
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        let auth = AuthContext::from_meta(&context.meta);
+        let locale = self.locale_of(&context.peer);
+        let prompt_context = PromptContext::new(self, request.name, request.arguments, context);
+        let prompt = auth.scope(self.prompt_router.get_prompt(prompt_context));
+        i18n::scope(locale, prompt).await
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        Ok(ListPromptsResult::with_all_items(self.prompt_router.list_all()))
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let transactions = RawResource {
+            description: Some(
+                "Latest transactions, newest first. Subscribe to be notified of changes."
+                    .to_string(),
+            ),
+            mime_type: Some("application/json".to_string()),
+            ..RawResource::new(TRANSACTIONS_RESOURCE_URI, "transactions")
+        };
+        Ok(ListResourcesResult::with_all_items(vec![transactions.no_annotation()]))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        known_resource(&request.uri)?;
+        let auth = AuthContext::from_meta(&context.meta);
+        auth.scope(self.read_transactions_resource()).await
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        known_resource(&request.uri)?;
+        info!("Client subscribed to {}", request.uri);
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .entry(request.uri)
+            .or_default()
+            .insert(self.session_id.clone(), context.peer);
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        info!("Client unsubscribed from {}", request.uri);
+        self.unsubscribe_session(&request.uri, &self.session_id);
+        Ok(())
+    }
+
+    /// Suggests argument values, reading names under the caller's auth
+    /// context like `call_tool`.
+    async fn complete(
+        &self,
+        request: CompleteRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CompleteResult, McpError> {
+        let auth = AuthContext::from_meta(&context.meta);
+        let completion = auth.scope(self.complete_argument(&request.argument)).await?;
+        Ok(CompleteResult { completion })
+    }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        info!("Client log level set to {:?}", request.level);
+        let mut log = self.client_log.lock().unwrap();
+        log.peer = Some(context.peer);
+        log.level = request.level;
+        Ok(())
+    }
+
+    /// Remembers the client so events can be logged to it before it sets a
+    /// level of its own.
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        debug!("Client initialized");
+        self.client_log.lock().unwrap().peer = Some(context.peer);
+    }
+}
+
+fn unknown_tool(name: &str) -> McpError {
+    ToolError::new(ErrorKind::NotFound, format!("unknown tool {name}"))
+        .with("tool", name)
+        .into()
+}
+
+fn known_resource(uri: &str) -> Result<(), McpError> {
+    if uri == TRANSACTIONS_RESOURCE_URI {
+        Ok(())
+    } else {
+        Err(ToolError::new(ErrorKind::NotFound, format!("unknown resource {uri}"))
+            .with("uri", uri)
+            .into())
+    }
+}
+
+/// Times `check` and records its outcome under `name`, keeping the value it
+/// produced for follow-up probes.
+async fn probe<T>(
+    name: &str,
+    check: impl Future<Output = anyhow::Result<T>>,
+) -> (DependencyHealth, Option<T>) {
+    let start_time = Instant::now();
+    let result = check.await;
+    let latency_ms = start_time.elapsed().as_millis() as u64;
+    match result {
+        Ok(value) => (
+            DependencyHealth {
+                name: name.to_string(),
+                ok: true,
+                latency_ms,
+                error: None,
+            },
+            Some(value),
+        ),
+        Err(err) => (
+            DependencyHealth {
+                name: name.to_string(),
+                ok: false,
+                latency_ms,
+                error: Some(err.to_string()),
+            },
+            None,
+        ),
+    }
+}
+
+/// Whether `tool` may write, judged by its annotations: anything not marked
+/// read-only.
+/// Whether calls of `tool`, annotated as destructive, only preview their
+/// effect and return a confirmation token until called with it, once
+/// [`ExaspoonDbServer::with_confirmation`] is.
+pub fn needs_confirmation(tool: &Tool) -> bool {
+    tool.annotations
+        .as_ref()
+        .and_then(|annotations| annotations.destructive_hint)
+        == Some(true)
+}
+
+fn can_write(tool: &Tool) -> bool {
+    tool.annotations
+        .as_ref()
+        .and_then(|annotations| annotations.read_only_hint)
+        != Some(true)
+}
+
+/// SHA-256 of `input`'s JSON, as hex.
+pub(crate) fn input_hash(input: &impl Serialize) -> String {
+    let json = serde_json::to_vec(input).unwrap_or_default();
+    format!("{:x}", Sha256::digest(json))
+}
+
+/// The transaction in `import_json`'s record at `index`, or the path of the
+/// value that keeps it out and why: a mismatch with the transaction
+/// `schema`, a broken field rule, or a missing account or currency.
+fn check_record(
+    schema: &JsonObject,
+    index: usize,
+    record: &Value,
+) -> Result<CreateTransactionInput, (String, String)> {
+    let path = format!("records[{index}]");
+    validation::conform(schema, &path, record)
+        .map_err(|mismatch| (mismatch.field, mismatch.message))?;
+    validation::validate_at(&path, record).map_err(|invalid| {
+        let message = Message::Invalid {
+            field: &invalid.field,
+            rule: invalid.rule,
+        }
+        .text();
+        (invalid.field, message)
+    })?;
+    let transaction: CreateTransactionInput =
+        serde_json::from_value(record.clone()).map_err(|err| (path.clone(), err.to_string()))?;
+    if let Some(field) = missing_fields(&transaction).first() {
+        let field = format!("{path}.{field}");
+        let message = Message::Required { field: &field }.text();
+        return Err((field, message));
+    }
+    Ok(transaction)
+}
+
+/// A failed Plaid call, with Plaid's error type and code in the error data
+/// when it sent them, e.g. `ITEM_LOGIN_REQUIRED` once the bank login must be
+/// renewed through Plaid Link.
+fn plaid_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let plaid = err.downcast_ref::<PlaidError>().map(|rejected| {
+        json!({
+            "error_type": rejected.error_type,
+            "error_code": rejected.error_code,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match plaid {
+        Some(plaid) => error.with("plaid", plaid).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed GoCardless call, with the status code and summary GoCardless
+/// sent in the error data, e.g. a 429 once the bank's daily limit of syncs
+/// is used up.
+fn gocardless_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let gocardless = err.downcast_ref::<GoCardlessError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "summary": rejected.summary,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match gocardless {
+        Some(gocardless) => error.with("gocardless", gocardless).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed Etherscan call, with the message and result Etherscan sent in
+/// the error data, e.g. `Max rate limit reached` once the API key's calls
+/// per second are used up.
+fn etherscan_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let etherscan = err.downcast_ref::<EtherscanError>().map(|rejected| {
+        json!({
+            "message": rejected.message,
+            "result": rejected.result,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match etherscan {
+        Some(etherscan) => error.with("etherscan", etherscan).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed Blockbook call, with the status code and error Blockbook sent
+/// in the error data, e.g. a 400 for an address of another network.
+fn blockbook_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let blockbook = err.downcast_ref::<BlockbookError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "error": rejected.error,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match blockbook {
+        Some(blockbook) => error.with("blockbook", blockbook).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed Solana RPC call, with the code and message the node sent in the
+/// error data, e.g. `-32005` once the node's rate limit is hit.
+fn solana_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let solana = err.downcast_ref::<SolanaRpcError>().map(|rejected| {
+        json!({
+            "code": rejected.code,
+            "message": rejected.message,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match solana {
+        Some(solana) => error.with("solana", solana).into(),
+        None => error.into(),
+    }
+}
+
+/// The Binance trading pairs `symbols` names, uppercased and without
+/// repeats. Coinbase lists trades with each wallet, so it takes none.
+fn trading_pairs(exchange: Exchange, symbols: &[String]) -> Result<Vec<String>, McpError> {
+    if exchange != Exchange::Binance && !symbols.is_empty() {
+        return Err(ToolError::invalid(
+            format!("{exchange} lists trades with each wallet; symbols are for Binance"),
+            "symbols",
+        )
+        .into());
+    }
+    let mut pairs: Vec<String> = Vec::with_capacity(symbols.len());
+    for symbol in symbols {
+        let symbol = symbol.trim().to_ascii_uppercase();
+        if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ToolError::invalid(
+                format!("{symbol:?} is not a trading pair such as BTCUSDT"),
+                "symbols",
+            )
+            .into());
+        }
+        if !pairs.contains(&symbol) {
+            pairs.push(symbol);
+        }
+    }
+    Ok(pairs)
+}
+
+/// A failed Binance call, with the status code, code and message Binance
+/// sent in the error data, e.g. `-2015` for a key without read permission.
+fn binance_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let binance = err.downcast_ref::<BinanceError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "code": rejected.code,
+            "msg": rejected.msg,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match binance {
+        Some(binance) => error.with("binance", binance).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed Coinbase call, with the status code, id and message Coinbase
+/// sent in the error data, e.g. `authentication_error` for an unknown key.
+fn coinbase_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let coinbase = err.downcast_ref::<CoinbaseError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "id": rejected.id,
+            "message": rejected.message,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match coinbase {
+        Some(coinbase) => error.with("coinbase", coinbase).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed exchange rate lookup, with the provider and the code and message
+/// it sent in the error data, e.g. exchangerate.host's `101` for a missing
+/// access key.
+fn fx_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let fx = err.downcast_ref::<FxError>().map(|rejected| {
+        json!({
+            "provider": rejected.provider,
+            "code": rejected.code,
+            "message": rejected.message,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match fx {
+        Some(fx) => error.with("fx", fx).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed CoinGecko call, with the status code, and CoinGecko's own code
+/// and message, in the error data, e.g. `429` once the rate limit is spent.
+fn coingecko_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let coingecko = err.downcast_ref::<CoinGeckoError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "error_code": rejected.error_code,
+            "message": rejected.message,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match coingecko {
+        Some(coingecko) => error.with("coingecko", coingecko).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed call to the vision model, with the status code, and the API's
+/// code and message, in the error data, e.g. `model_not_found`.
+fn vision_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let vision = err.downcast_ref::<VisionError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "code": rejected.code,
+            "message": rejected.message,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match vision {
+        Some(vision) => error.with("vision", vision).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed Google call, with the status code, and Google's status and
+/// message, in the error data, e.g. `PERMISSION_DENIED` for a spreadsheet
+/// the user cannot edit.
+fn google_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let google = err.downcast_ref::<GoogleError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "status": rejected.status,
+            "message": rejected.message,
+        })
+    });
+    let error = ToolError::failed(action, err);
+    match google {
+        Some(google) => error.with("google", google).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed YNAB call, with the status code, and YNAB's error id, name and
+/// detail, in the error data, e.g. `404.2` `resource_not_found` for an
+/// unknown budget, and `categories_added` when an export stopped partway.
+fn ynab_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let ynab = err.downcast_ref::<YnabError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "id": rejected.id,
+            "name": rejected.name,
+            "detail": rejected.detail,
+        })
+    });
+    let categories_added = err
+        .downcast_ref::<ynab::Interrupted>()
+        .map(|interrupted| interrupted.categories);
+    let mut error = ToolError::failed(action, err);
+    if let Some(categories) = categories_added {
+        error = error.with("categories_added", categories);
+    }
+    match ynab {
+        Some(ynab) => error.with("ynab", ynab).into(),
+        None => error.into(),
+    }
+}
+
+/// A failed Notion call, with the status code, and Notion's code and
+/// message, in the error data, e.g. `object_not_found` for a database not
+/// shared with the integration, and `pages_written` when an export stopped
+/// partway.
+fn notion_failed(action: &str, err: anyhow::Error) -> McpError {
+    error!("Failed to {}: {:#}", action, err);
+    let notion = err.downcast_ref::<NotionError>().map(|rejected| {
+        json!({
+            "status_code": rejected.status_code,
+            "code": rejected.code,
+            "message": rejected.message,
+        })
+    });
+    let pages_written = err
+        .downcast_ref::<Interrupted>()
+        .map(|interrupted| interrupted.pages);
+    let mut error = ToolError::failed(action, err);
+    if let Some(pages) = pages_written {
+        error = error.with("pages_written", pages);
+    }
+    match notion {
+        Some(notion) => error.with("notion", notion).into(),
+        None => error.into(),
+    }
+}
+
+/// `code` as [`validation::currency_code`] writes it, or the invalid_params
+/// error for `field` unless it is a currency or asset code.
+fn currency_code(code: &str, field: &str) -> Result<String, McpError> {
+    validation::currency_code(code).ok_or_else(|| {
+        invalid_argument(Invalid {
+            field: field.to_string(),
+            rule: validation::Rule::Currency,
+        })
+    })
+}
+
+/// The invalid_params error for an argument that failed validation.
+fn invalid_argument(invalid: Invalid) -> McpError {
+    let message = Message::Invalid {
+        field: &invalid.field,
+        rule: invalid.rule,
+    }
+    .text();
+    ToolError::invalid(message, &invalid.field)
+        .with("rule", invalid.rule.as_ref())
+        .into()
+}
+
+fn success(output: impl Serialize + Render) -> CallToolResult {
+    structured(Outcome {
+        output,
+        dry_run: false,
+        cancelled: false,
+        confirmation: None,
+    })
+}
+
+/// A batch tool's result. When the client cancelled the call part way it is
+/// flagged `"cancelled": true` and holds only the work done before stopping.
+fn batch_result(output: impl Serialize + Render) -> CallToolResult {
+    structured(Outcome {
+        output,
+        dry_run: false,
+        cancelled: cancellation::is_cancelled(),
+        confirmation: None,
+    })
+}
+
+/// A dry run's result: what the tool would have written, flagged as such.
+fn dry_run_result(output: impl Serialize + Render) -> CallToolResult {
+    structured(Outcome {
+        output,
+        dry_run: true,
+        cancelled: false,
+        confirmation: None,
+    })
+}
+
+/// `outcome` as structured content, with the JSON as text content after a
+/// rendering for clients that only display text.
+fn structured(outcome: Outcome<impl Serialize + Render>) -> CallToolResult {
+    let mut text = outcome.output.render(outcome.dry_run);
+    if outcome.cancelled {
+        text.push_str("\nCancelled part way; only the work above was done.");
+    }
+    let mut result =
+        CallToolResult::structured(serde_json::to_value(outcome).unwrap_or(Value::Null));
+    result.content.insert(0, Content::text(text));
+    result
+}
+
+/// Whether `name` is `pattern`, or starts with it when it ends in `*`.
+fn tool_matches(pattern: &str, name: &str) -> bool {
+    match pattern.trim().strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern.trim(),
+    }
+}
+
+/// The row a dry run would have written. Embeddings are summarized by their
+/// dimension rather than returned in full.
+fn planned<T>(row: T, embedding: Option<&[f32]>) -> Planned<T> {
+    Planned {
+        row,
+        embedding_dimension: embedding.map(<[f32]>::len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        Account, AccountType, Categorized, Category, CategoryKind, CategoryMatch,
+        CreateTransactionInput, CreateTransactionsInput, ListAccountsInput, ListTransactionsInput, SearchSimilarInput,
+        SpendingBucket, SpendingGroupBy, SpendingPeriod, ToolCapability, Transaction,
+        TransactionDirection,
+        EmbeddingIssue, TransactionFilters, TransactionMatch, TransactionTextMatch,
+        UpsertAccountInput, UpsertCategoryInput,
+    };
+    use crate::{
+        config::{
+            BinanceConfig, EtherscanConfig, FxConfig, GoCardlessConfig, HttpClientConfig,
+            NotionConfig, PlaidConfig, PriceConfig, ReceiptConfig, YnabConfig,
+        },
+        embedding::Embedder,
+        metrics::{Metrics, OperationMetrics},
+        supabase::Database,
+    };
+    #[cfg(feature = "supabase")]
+    use crate::{circuit::CircuitOpen, postgrest::PostgrestError};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use rmcp::model::{ArgumentInfo, ErrorCode};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn rejects_blank_transaction_query() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.1]));
+        let server = ExaspoonDbServer::new(db, embedder);
+
+        let err = server
+            .search_similar_transactions(Parameters(SearchSimilarInput {
+                query: "   ".into(),
+                limit: None,
+            }))
+            .await
+            .expect_err("expected validation error");
+
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn search_similar_transactions_returns_matches() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| {
+            state.transaction_matches = vec![TransactionMatch {
+                transaction: transaction("txn-42"),
+                similarity: Some(0.87),
+            }];
+        });
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.2, 0.4]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+
+        let result = server
+            .search_similar_transactions(Parameters(SearchSimilarInput {
+                query: "Rent".into(),
+                limit: Some(7),
+            }))
+            .await
+            .expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["matches"][0]["id"], "txn-42");
+        assert_eq!(payload["matches"][0]["similarity"], 0.87);
+        assert_eq!(embedder.calls(), vec!["Rent"]);
+        assert_eq!(db.transaction_search_limits(), vec![Some(7)]);
+    }
+
+    #[tokio::test]
+    async fn search_transactions_text_skips_embedding() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| state.transactions = vec![transaction("txn-4812")]);
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.1]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+
+        let result = server
+            .search_transactions_text(Parameters(SearchTextInput {
+                query: "  invoice 4812 ".into(),
+                limit: Some(3),
+            }))
+            .await
+            .expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["matches"][0]["id"], "txn-4812");
+        assert_eq!(payload["matches"][0]["rank"], 0.5);
+        assert!(embedder.calls().is_empty());
+        assert_eq!(
+            db.state.lock().unwrap().text_searches,
+            vec![("invoice 4812".to_string(), Some(3))]
+        );
+
+        let err = server
+            .search_transactions_text(Parameters(SearchTextInput {
+                query: " ".into(),
+                limit: None,
+            }))
+            .await
+            .expect_err("expected validation error");
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn create_transaction_embeds_description() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.5]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        let input = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 42.0,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: Some("Coffee".into()),
+            raw_source: None,
+        };
+
+        let _ = server
+            .create_transaction(Parameters(input.clone().into()))
+            .await
+            .expect("tool call should succeed");
+
+        let inserts = db.inserted_transactions();
+        assert_eq!(inserts.len(), 1);
+        assert_eq!(inserts[0].0.description.as_deref(), Some("Coffee"));
+        assert_eq!(inserts[0].1, Some(vec![0.5]));
+        assert_eq!(embedder.calls(), vec!["Coffee"]);
+    }
+
+    #[tokio::test]
+    async fn create_transaction_skips_embedding_without_description() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.9]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        let input = CreateTransactionInput {
+            account_id: "acct-2".into(),
+            amount: 10.0,
+            currency: "USD".into(),
+            direction: TransactionDirection::Income,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: None,
+            raw_source: None,
+        };
+
+        server
+            .create_transaction(Parameters(input.into()))
+            .await
+            .expect("tool call should succeed");
+
+        let inserts = db.inserted_transactions();
+        assert_eq!(inserts[0].1, None);
+        assert!(embedder.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_transactions_embeds_each_description() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.3]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        let coffee = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 4.5,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: Some("Coffee".into()),
+            raw_source: None,
+        };
+        let salary = CreateTransactionInput {
+            amount: 3000.0,
+            direction: TransactionDirection::Income,
+            description: None,
+            ..coffee.clone()
+        };
+
+        let result = server
+            .create_transactions(Parameters(DryRun::from(CreateTransactionsInput {
+                transactions: vec![coffee, salary],
+            })))
+            .await
+            .expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["transactions"].as_array().unwrap().len(), 2);
+        let inserts = db.inserted_transactions();
+        assert_eq!(inserts[0].1, Some(vec![0.3]));
+        assert_eq!(inserts[1].1, None);
+        assert_eq!(embedder.calls(), vec!["Coffee"]);
+    }
+
+    #[tokio::test]
+    async fn create_transactions_rejects_empty_batch() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let err = server
+            .create_transactions(Parameters(DryRun::from(CreateTransactionsInput {
+                transactions: Vec::new(),
+            })))
+            .await
+            .expect_err("expected validation error");
+
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn create_transactions_names_missing_required_fields() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.3]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        let coffee = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 4.5,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: Some("Coffee".into()),
+            raw_source: None,
+        };
+        let no_currency = CreateTransactionInput {
+            currency: String::new(),
+            ..coffee.clone()
+        };
+
+        let err = server
+            .create_transactions(Parameters(DryRun::from(CreateTransactionsInput {
+                transactions: vec![coffee, no_currency],
+            })))
+            .await
+            .expect_err("expected validation error");
+
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(err.data.unwrap()["field"], "transactions[1].currency");
+        assert!(db.inserted_transactions().is_empty());
+        assert!(embedder.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn aggregate_spending_returns_buckets() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| {
+            state.spending_buckets = vec![SpendingBucket {
+                key: Some("cat-food".into()),
+                period_start: Some("2024-01-01T00:00:00Z".into()),
+                currency: "USD".into(),
+                total: 120.5,
+                count: 3,
+            }];
+        });
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let result = server
+            .aggregate_spending(Parameters(AggregateSpendingInput {
+                group_by: SpendingGroupBy::Category,
+                period: Some(SpendingPeriod::Month),
+                filters: Default::default(),
+            }))
+            .await
+            .expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["buckets"][0]["key"], "cat-food");
+        assert_eq!(payload["buckets"][0]["total"], 120.5);
+        assert_eq!(payload["buckets"][0]["count"], 3);
+    }
+
+    #[tokio::test]
+    async fn delete_account_requires_no_remaining_transactions() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| state.transaction_total = 2);
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let err = server
+            .delete_account(Parameters(DryRun::from(DeleteRecordInput {
+                id: "acct-1".into(),
+            })))
+            .await
+            .expect_err("expected validation error");
+
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(db.state.lock().unwrap().soft_deleted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mutations_are_recorded_in_the_audit_log() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.5])));
+        let input = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 42.0,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: None,
+            raw_source: None,
+        };
+
+        server
+            .create_transaction(Parameters(input.clone().into()))
+            .await
+            .expect("tool call should succeed");
+        server
+            .delete_transaction(Parameters(DryRun::from(DeleteRecordInput {
+                id: "txn-1".into(),
+            })))
+            .await
+            .expect("tool call should succeed");
+
+        let result = server
+            .list_audit_events(Parameters(ListAuditEventsInput::default()))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        let events = payload["events"].as_array().expect("events array");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["tool"], "create_transaction");
+        assert_eq!(events[0]["record_id"], "txn-default");
+        assert_eq!(events[0]["input_hash"], input_hash(&input));
+        assert_eq!(events[0]["input_hash"].as_str().unwrap().len(), 64);
+        assert_eq!(events[1]["tool"], "delete_transaction");
+        assert_eq!(events[0]["session_id"], events[1]["session_id"]);
+        assert_eq!(events[0]["session_id"], server.session_id.as_str());
+    }
+
+    #[tokio::test]
+    async fn transactions_resource_reads_latest_page() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| state.transactions = vec![transaction("txn-1")]);
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let result = server
+            .read_transactions_resource()
+            .await
+            .expect("resource read should succeed");
+        let ResourceContents::TextResourceContents { uri, text, .. } = &result.contents[0] else {
+            panic!("expected text contents");
+        };
+        assert_eq!(uri, TRANSACTIONS_RESOURCE_URI);
+        let rows: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(rows[0]["id"], "txn-1");
+
+        let err = known_resource("exaspoon://accounts").expect_err("unknown resource");
+        assert_eq!(err.code, ErrorCode::RESOURCE_NOT_FOUND);
+        // Without a subscriber there is no one to notify.
+        server.notify_resource_updated(TRANSACTIONS_RESOURCE_URI).await;
+    }
+
+    #[tokio::test]
+    async fn delete_transaction_reports_missing_rows() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let result = server
+            .delete_transaction(Parameters(DryRun::from(DeleteRecordInput {
+                id: " txn-1 ".into(),
+            })))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["deleted"]["kind"], "transaction");
+        assert_eq!(payload["deleted"]["id"], "txn-1");
+
+        let err = server
+            .delete_transaction(Parameters(DryRun::from(DeleteRecordInput {
+                id: "missing".into(),
+            })))
+            .await
+            .expect_err("expected missing row");
+        assert_eq!(err.code, ErrorCode::RESOURCE_NOT_FOUND);
+        assert_eq!(err.data.unwrap()["error_code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn seed_demo_data_is_opt_in() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])));
+        assert!(!server.tool_router.has_route("seed_demo_data"));
+
+        let server = server.with_demo_seed();
+        assert!(server.tool_router.has_route("seed_demo_data"));
+        let err = server
+            .seed_demo_data(Parameters(DryRun::from(SeedDemoDataInput { months: Some(13) })))
+            .await
+            .expect_err("expected validation error");
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(db.state.lock().unwrap().inserted_transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn completes_enum_values_and_live_names() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| {
+            let account = |name: &str, r#type| Account {
+                name: name.into(),
+                r#type,
+                ..state.account_response.clone()
+            };
+            state.accounts = vec![
+                account("Checking", AccountType::Offchain),
+                account("Checking", AccountType::Onchain),
+                account("Wallet", AccountType::Onchain),
+            ];
+            state.category_matches = vec![CategoryMatch {
+                category: Category {
+                    name: "Groceries".into(),
+                    ..state.category_response.clone()
+                },
+                similarity: None,
+            }];
+        });
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+        let complete = |name: &str, value: &str| {
+            let argument = ArgumentInfo {
+                name: name.into(),
+                value: value.into(),
+            };
+            let server = server.clone();
+            async move { server.complete_argument(&argument).await.unwrap().values }
+        };
+
+        assert_eq!(complete("direction", "EX").await, vec!["expense"]);
+        assert_eq!(
+            complete("type", "").await,
+            vec!["onchain", "offchain", "exchange"]
+        );
+        assert_eq!(complete("kind", "tr").await, vec!["transfer", "transaction"]);
+        assert_eq!(complete("account", "").await, vec!["Checking", "Wallet"]);
+        assert_eq!(complete("category", "gro").await, vec!["Groceries"]);
+        assert_eq!(complete("month", "").await.len(), 12);
+        assert!(complete("line", "STARBUCKS").await.is_empty());
+    }
+
+    #[test]
+    fn tools_are_annotated_for_confirmation_policies() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_admin_tools()
+        .with_demo_seed()
+        .with_rpc_allowlist(vec!["monthly_report".into()]);
+        let tools = server.tool_router.list_all();
+        let annotations = |name: &str| {
+            let tool = tools.iter().find(|tool| tool.name == name).expect(name);
+            tool.annotations.clone().unwrap_or_default()
+        };
+
+        for name in [
+            "list_accounts",
+            "list_transactions",
+            "search_similar_transactions",
+            "search_similar_categories",
+            "search_transactions_text",
+            "aggregate_spending",
+            "list_audit_events",
+            "health_check",
+            "server_metrics",
+            "describe_capabilities",
+            "set_session_defaults",
+        ] {
+            assert_eq!(annotations(name).read_only_hint, Some(true), "{name}");
+        }
+        for name in [
+            "delete_transaction",
+            "delete_category",
+            "delete_account",
+            "purge_deleted",
+            "upsert_account",
+            "upsert_category",
+            "execute_batch",
+        ] {
+            assert_eq!(annotations(name).destructive_hint, Some(true), "{name}");
+        }
+        for name in ["upsert_account", "upsert_category", "seed_demo_data"] {
+            assert_eq!(annotations(name).idempotent_hint, Some(true), "{name}");
+        }
+        assert_eq!(annotations("seed_demo_data").destructive_hint, Some(false));
+        assert_eq!(annotations("create_transactions").idempotent_hint, None);
+        // Allowlisted functions can do anything, so call_rpc keeps the
+        // worst-case defaults.
+        assert_eq!(annotations("call_rpc"), Default::default());
+    }
+
+    #[test]
+    fn read_only_mode_keeps_only_read_only_tools() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_admin_tools()
+        .with_demo_seed()
+        .with_rpc_allowlist(vec!["monthly_report".into()])
+        .with_read_only();
+
+        let names = server
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|tool| tool.name.to_string())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"list_transactions".to_string()));
+        assert!(names.contains(&"summarize_period".to_string()));
+        assert!(names.contains(&"health_check".to_string()));
+        for name in [
+            "create_transaction",
+            "create_transactions",
+            "upsert_category",
+            "upsert_account",
+            "delete_transaction",
+            "purge_deleted",
+            "embedding_maintenance",
+            "seed_demo_data",
+            "call_rpc",
+        ] {
+            assert!(!names.contains(&name.to_string()), "{name}");
+        }
+    }
+
+    #[test]
+    fn disabled_tools_are_removed_by_name_or_prefix() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_disabled_tools(vec!["delete_*".into(), "upsert_account".into()])
+        .with_admin_tools();
+
+        for name in ["delete_transaction", "delete_category", "delete_account", "upsert_account"] {
+            assert!(!server.tool_router.has_route(name), "{name}");
+        }
+        assert!(server.tool_router.has_route("upsert_category"));
+        assert!(server.tool_router.has_route("purge_deleted"));
+        assert!(server.tool_router.has_route("list_accounts"));
+
+        let server = server.with_disabled_tools(vec!["purge_deleted".into()]);
+        assert!(!server.tool_router.has_route("purge_deleted"));
+        assert!(server.tool_router.has_route("delete_account"));
+    }
+
+    #[test]
+    fn tool_groups_are_mounted_independently() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_admin_tools()
+        .with_tool_groups(vec![ToolGroup::Core, ToolGroup::Monitoring]);
+
+        assert!(server.tool_router.has_route("create_transaction"));
+        assert!(server.tool_router.has_route("ping"));
+        assert!(!server.tool_router.has_route("aggregate_spending"));
+        assert!(!server.tool_router.has_route("purge_deleted"));
+
+        // Group membership does not override the switches within a group.
+        let server = server.with_tool_groups(vec![ToolGroup::Maintenance]);
+        assert!(server.tool_router.has_route("purge_deleted"));
+        assert!(!server.tool_router.has_route("seed_demo_data"));
+        assert!(!server.tool_router.has_route("create_transaction"));
+
+        // Every tool belongs to exactly one group.
+        let everything = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_admin_tools()
+        .with_demo_seed()
+        .with_rpc_allowlist(vec!["f".into()]);
+        assert!(!everything.tool_router.has_route("sync_bank_account"));
+        let plaid = PlaidClient::new(
+            &PlaidConfig {
+                client_id: "client".into(),
+                secret: "secret".into(),
+                base_url: plaid::PLAID_SANDBOX_URL.into(),
+                token_key: [7; 32],
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let gocardless = GoCardlessClient::new(
+            &GoCardlessConfig {
+                secret_id: "secret-id".into(),
+                secret_key: "secret-key".into(),
+                base_url: gocardless::GOCARDLESS_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let etherscan = EtherscanClient::new(
+            &EtherscanConfig {
+                api_key: "api-key".into(),
+                base_url: onchain::ethereum::ETHERSCAN_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let binance = BinanceClient::new(
+            &BinanceConfig {
+                api_key: "api-key".into(),
+                api_secret: "api-secret".into(),
+                base_url: exchange::binance::BINANCE_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let fx = FxClient::new(
+            &FxConfig {
+                provider: crate::models::FxProvider::Ecb,
+                base_url: crate::fx::FRANKFURTER_BASE_URL.into(),
+                api_key: None,
+                base_currency: "USD".into(),
+                cache_ttl: Duration::from_secs(60),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let prices = CoinGeckoClient::new(
+            &PriceConfig {
+                base_url: crate::prices::COINGECKO_BASE_URL.into(),
+                api_key: None,
+                api_plan: crate::config::CoinGeckoPlan::Demo,
+                base_currency: "USD".into(),
+                cache_ttl: Duration::from_secs(60),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let receipts = ReceiptReader::new(
+            &ReceiptConfig {
+                model: "gpt-4o-mini".into(),
+                base_url: receipt::OPENAI_BASE_URL.into(),
+                api_key: None,
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let notion = NotionClient::new(
+            &NotionConfig {
+                token: "secret_token".into(),
+                database_id: None,
+                base_url: crate::sheets::notion::NOTION_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let ynab = YnabClient::new(
+            &YnabConfig {
+                token: "ynab-token".into(),
+                budget_id: None,
+                base_url: ynab::YNAB_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let everything = everything
+            .with_plaid(plaid)
+            .with_gocardless(gocardless)
+            .with_etherscan(etherscan)
+            .with_binance(binance)
+            .with_fx(fx)
+            .with_prices(prices)
+            .with_receipts(receipts)
+            .with_notion(notion)
+            .with_ynab(ynab);
+        let grouped = ToolGroup::ALL
+            .into_iter()
+            .map(|group| group.router().list_all().len())
+            .sum::<usize>();
+        assert_eq!(grouped, everything.tool_router.list_all().len());
+        assert_eq!("Analytics".parse::<ToolGroup>().unwrap(), ToolGroup::Analytics);
+        assert!("reports".parse::<ToolGroup>().is_err());
+    }
+
+    #[test]
+    fn throttled_calls_fail_with_rate_limited() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_rate_limit(RateLimitConfig::new(2, 1));
+
+        server.throttle("create_transaction").unwrap();
+        let err = server.throttle("search_similar_categories").unwrap_err();
+        assert_eq!(err.code, RATE_LIMITED);
+        assert_eq!(err.data.as_ref().unwrap()["error_code"], "rate_limited");
+        assert_eq!(err.data.as_ref().unwrap()["budget"], "embedding_calls");
+        assert_eq!(err.data.as_ref().unwrap()["limit"], 1);
+        assert_eq!(err.data.as_ref().unwrap()["window_secs"], 60);
+
+        server.throttle("list_accounts").unwrap();
+        let err = server.throttle("list_categories").unwrap_err();
+        assert_eq!(err.data.as_ref().unwrap()["budget"], "calls");
+        assert!(err.data.unwrap()["retry_after_ms"].as_u64().unwrap() > 59_000);
+    }
+
+    #[test]
+    fn get_info_reports_the_configured_identity() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        );
+        let info = server.get_info();
+        assert_eq!(info.server_info.name, "exaspoon-db-mcp");
+        assert_eq!(info.server_info.version, env!("CARGO_PKG_VERSION"));
+
+        let server = server.with_identity(ServerIdentity {
+            name: "family-budget".into(),
+            title: Some("Family Budget".into()),
+            version: "2024.1".into(),
+            instructions: "Family budget, EUR base currency.".into(),
+        });
+        let info = server.get_info();
+        assert_eq!(info.server_info.name, "family-budget");
+        assert_eq!(info.server_info.title.as_deref(), Some("Family Budget"));
+        assert_eq!(info.server_info.version, "2024.1");
+        assert_eq!(info.instructions.as_deref(), Some("Family budget, EUR base currency."));
+    }
+
+    #[tokio::test]
+    async fn calls_past_their_time_limit_fail_with_timed_out() {
+        let per_tool = HashMap::from([("seed_demo_data".to_string(), Duration::ZERO)]);
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        )
+        .with_timeouts(ToolTimeouts::new(Duration::from_millis(20), per_tool));
+
+        let err = server
+            .time_limit("list_accounts", std::future::pending())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, TIMED_OUT);
+        assert_eq!(err.data.unwrap()["timeout_ms"], 20);
+
+        let quick = async { Ok(CallToolResult::success(Vec::new())) };
+        assert!(server.time_limit("list_accounts", quick).await.is_ok());
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            Ok(CallToolResult::success(Vec::new()))
+        };
+        assert!(server.time_limit("seed_demo_data", slow).await.is_ok(), "zero is no limit");
+    }
+
+    #[tokio::test]
+    async fn tools_declare_output_schemas_their_results_match() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])))
+            .with_admin_tools()
+            .with_demo_seed()
+            .with_rpc_allowlist(vec!["monthly_report".into()]);
+        for tool in server.tool_router.list_all() {
+            let schema = tool.output_schema.as_ref().expect(&tool.name);
+            assert_eq!(schema["type"], "object", "{}", tool.name);
+        }
+        let tools = server.tool_router.list_all();
+        let schema = |name: &str| {
+            let tool = tools.iter().find(|tool| tool.name == name).expect(name);
+            tool.output_schema.clone().unwrap()
+        };
+        let properties = &schema("create_transactions")["properties"];
+        assert!(properties["transactions"].is_object());
+        assert!(properties["dry_run"].is_object());
+        assert!(properties["cancelled"].is_object());
+        assert!(schema("list_accounts")["properties"]["dry_run"].is_null());
+
+        let input = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 4.5,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: Some("Coffee".into()),
+            raw_source: None,
+        };
+        let written = |result: CallToolResult| {
+            let payload = result.structured_content.expect("structured payload");
+            serde_json::from_value::<Outcome<TransactionOutput>>(payload).unwrap()
+        };
+        let result = server
+            .create_transaction(Parameters(DryRun::from(input.clone())))
+            .await
+            .expect("tool call should succeed");
+        let outcome = written(result);
+        assert!(!outcome.dry_run);
+        assert!(matches!(outcome.output.transaction, Written::Row(_)));
+
+        let result = server
+            .create_transaction(Parameters(DryRun {
+                input,
+                dry_run: Some(true),
+            }))
+            .await
+            .expect("tool call should succeed");
+        let outcome = written(result);
+        assert!(outcome.dry_run);
+        assert!(matches!(
+            outcome.output.transaction,
+            Written::Planned(Planned { embedding_dimension: Some(1), .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn embedding_maintenance_reports_and_repairs_in_batches() {
+        let db = Arc::new(FakeDatabase::default());
+        let issue = |id: &str, issue, content: Option<&str>| EmbeddingIssue {
+            id: id.into(),
+            issue,
+            content: content.map(str::to_string),
+        };
+        db.configure(|state| {
+            state.embedding_issues = vec![
+                issue("txn-1", EmbeddingIssueKind::Missing, Some("Coffee")),
+                issue("txn-2", EmbeddingIssueKind::Dimension, Some("Rent")),
+                issue("txn-3", EmbeddingIssueKind::Orphaned, None),
+                issue("txn-4", EmbeddingIssueKind::Missing, Some("Fuel")),
+            ];
+        });
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.1, 0.2, 0.3]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        assert!(!server.tool_router.has_route("embedding_maintenance"));
+        let server = server.with_admin_tools();
+        assert!(server.tool_router.has_route("embedding_maintenance"));
+
+        let input = |action, batch_size| {
+            DryRun::from(EmbeddingMaintenanceInput {
+                kind: Some(RecordKind::Transaction),
+                action,
+                batch_size: Some(batch_size),
+                dimension: None,
+            })
+        };
+        let result = server
+            .embedding_maintenance(Parameters(input(EmbeddingMaintenanceAction::Report, 2)))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["dimension"], 3);
+        let report = &payload["tables"]["transactions"];
+        assert_eq!(report["missing"], 2);
+        assert_eq!(report["dimension"], 1);
+        assert_eq!(report["orphaned"], 1);
+        assert_eq!(report["sample_ids"]["missing"], json!(["txn-1", "txn-4"]));
+        assert!(db.state.lock().unwrap().embeddings_set.is_empty());
+
+        let result = server
+            .embedding_maintenance(Parameters(input(EmbeddingMaintenanceAction::Backfill, 2)))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["tables"]["transactions"], json!({ "updated": 2, "complete": false }));
+        assert_eq!(embedder.calls(), vec!["dimension probe", "dimension probe", "Coffee", "Rent"]);
+
+        let result = server
+            .embedding_maintenance(Parameters(input(EmbeddingMaintenanceAction::Clear, 2)))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["tables"]["transactions"], json!({ "updated": 1, "complete": true }));
+        assert_eq!(
+            db.state.lock().unwrap().embeddings_set,
+            vec![
+                ("txn-1".to_string(), Some(3)),
+                ("txn-2".to_string(), Some(3)),
+                ("txn-3".to_string(), None),
+            ]
+        );
+
+        let err = server
+            .embedding_maintenance(Parameters(DryRun::from(EmbeddingMaintenanceInput {
+                kind: Some(RecordKind::Account),
+                ..Default::default()
+            })))
+            .await
+            .expect_err("expected validation error");
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn cancelled_calls_stop_and_report_partial_results() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| {
+            state.embedding_issues = ["txn-1", "txn-2", "txn-3"]
+                .map(|id| EmbeddingIssue {
+                    id: id.into(),
+                    issue: EmbeddingIssueKind::Missing,
+                    content: Some(format!("Row {id}")),
+                })
+                .to_vec();
+        });
+        // The dimension probe is the first call, so two rows get re-embedded.
+        let token = CancellationToken::new();
+        let embedder = FakeEmbedder::new(vec![0.1, 0.2]).cancelling_after(3, token.clone());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(embedder)).with_admin_tools();
+
+        let call = server.embedding_maintenance(Parameters(DryRun::from(EmbeddingMaintenanceInput {
+            kind: Some(RecordKind::Transaction),
+            action: EmbeddingMaintenanceAction::Backfill,
+            batch_size: Some(10),
+            dimension: None,
+        })));
+        let result = cancellation::scope(token.clone(), call)
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["cancelled"], true);
+        assert_eq!(payload["tables"]["transactions"], json!({ "updated": 2, "complete": false }));
+        assert_eq!(db.state.lock().unwrap().embeddings_set.len(), 2);
+        assert_eq!(db.state.lock().unwrap().audit_events.len(), 2);
+
+        let coffee = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 4.5,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: Some("Coffee".into()),
+            raw_source: None,
+        };
+        let call = server.create_transactions(Parameters(DryRun::from(CreateTransactionsInput {
+            transactions: vec![coffee.clone(), coffee],
+        })));
+        let result = cancellation::scope(token, call)
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload, json!({ "transactions": [], "cancelled": true }));
+        assert!(db.inserted_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dry_runs_embed_but_do_not_write() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.1, 0.2]));
+        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        let tool = server
+            .tool_router
+            .list_all()
+            .into_iter()
+            .find(|tool| tool.name == "create_transaction")
+            .expect("create_transaction tool");
+        let properties = tool.input_schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("account_id"));
+        assert!(properties.contains_key("dry_run"));
+
+        let result = server
+            .create_transaction(Parameters(DryRun {
+                input: CreateTransactionInput {
+                    account_id: "acct-1".into(),
+                    amount: 4.5,
+                    currency: "USD".into(),
+                    direction: TransactionDirection::Expense,
+                    occurred_at: "2024-01-02T03:04:05Z".into(),
+                    description: Some("Coffee".into()),
+                    raw_source: None,
+                },
+                dry_run: Some(true),
+            }))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["dry_run"], true);
+        assert_eq!(payload["transaction"]["description"], "Coffee");
+        assert_eq!(payload["transaction"]["embedding_dimension"], 2);
+        assert_eq!(embedder.calls(), vec!["Coffee"]);
+
+        let server = server.with_dry_run();
+        let result = server
+            .delete_transaction(Parameters(DryRun {
+                input: DeleteRecordInput { id: "txn-1".into() },
+                dry_run: Some(false),
+            }))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["dry_run"], true);
+        assert_eq!(payload["deleted"]["id"], "txn-1");
+        // A dry run reports a missing row as the real delete would.
+        let err = server
+            .delete_transaction(Parameters(DryRun::from(DeleteRecordInput {
+                id: "missing".into(),
+            })))
+            .await
+            .expect_err("expected a missing row");
+        assert_eq!(err.data.unwrap()["error_code"], "not_found");
+
+        let result = server
+            .upsert_account(Parameters(DryRun::from(UpsertAccountInput {
+                name: "Wallet".into(),
+                r#type: AccountType::Offchain,
+                currency: "USD".into(),
+                network: None,
+                institution: None,
+            })))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["account"]["embedding_dimension"], 2);
+
+        let state = db.state.lock().unwrap();
+        assert!(state.inserted_transactions.is_empty());
+        assert!(state.soft_deleted.is_empty());
+        assert!(state.audit_events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn results_lead_with_a_text_rendering() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| state.transaction_response = transaction("txn-123"));
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+        let input = CreateTransactionInput {
+            account_id: "acct-1".into(),
+            amount: 42.0,
+            currency: "USD".into(),
+            direction: TransactionDirection::Expense,
+            occurred_at: "2024-01-02T03:04:05Z".into(),
+            description: None,
+            raw_source: None,
+        };
+        let texts = |result: CallToolResult| -> Vec<String> {
+            result
+                .content
+                .iter()
+                .map(|content| content.as_text().unwrap().text.clone())
+                .collect()
+        };
+
+        let result = server
+            .create_transaction(Parameters(input.clone().into()))
             .await
-            .map_err(|err| {
-                error!("Failed to generate account embedding: {}", err);
-                internal_error("generate account embedding", err)
-            })?;
+            .unwrap();
+        let structured = result.structured_content.clone().unwrap();
+        let texts = texts(result);
+        assert_eq!(texts[0], "Created transaction txn-123: -$42.00 at 2024-01-02");
+        assert_eq!(serde_json::from_str::<Value>(&texts[1]).unwrap(), structured);
 
-        let account = self
-            .supabase
-            .upsert_account(&input)
+        let result = server
+            .create_transaction(Parameters(DryRun {
+                input,
+                dry_run: Some(true),
+            }))
             .await
-            .map_err(|err| {
-                error!("Failed to upsert account: {}", err);
-                internal_error("upsert account", err)
-            })?;
+            .unwrap();
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Would create transaction -$42.00 at 2024-01-02"
+        );
+    }
 
-        let duration = start_time.elapsed();
-        info!("Account upserted successfully in {:?}", duration);
-        debug!("Account record: {:?}", account);
+    #[tokio::test]
+    async fn invalid_arguments_fail_with_the_field_path() {
+        let arguments = json!({
+            "transactions": [
+                { "amount": 12.5, "currency": "USD", "occurred_at": "2024-01-02T03:04:05Z" },
+                { "amount": -3, "currency": "USD", "occurred_at": "2024-01-02T03:04:05Z" },
+            ]
+        });
+        let invalid = validation::validate(arguments.as_object().unwrap()).unwrap_err();
+        let err = invalid_argument(invalid.clone());
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(err.message, "transactions[1].amount must be a positive number");
+        assert_eq!(
+            err.data,
+            Some(json!({
+                "field": "transactions[1].amount",
+                "rule": "positive",
+                "error_code": "validation",
+            }))
+        );
 
-        Ok(success(json!({ "account": account })))
+        let err = i18n::scope(Locale::Ru, async { invalid_argument(invalid) }).await;
+        assert_eq!(
+            err.message,
+            "Поле transactions[1].amount должно быть положительным числом"
+        );
     }
-}
 
-#[tool_handler]
-impl ServerHandler for ExaspoonDbServer {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some(
-                "Tools for managing accounts, transactions, and semantic search over Supabase data."
-                    .to_string(),
+    #[cfg(feature = "supabase")]
+    #[test]
+    fn failures_are_sorted_into_error_kinds() {
+        let rejected = |status, body: &str| {
+            anyhow::Error::new(PostgrestError::parse("insert into transactions", status, body))
+        };
+        let failed = |action, err| McpError::from(ToolError::failed(action, err));
+
+        let err = failed(
+            "upsert account",
+            rejected(
+                reqwest::StatusCode::CONFLICT,
+                r#"{"code":"23505","message":"duplicate key value violates unique constraint \"accounts_name_type_key\"","details":"Key (name, type)=(Checking, offchain) already exists.","hint":null}"#,
             ),
-        }
+        );
+        assert_eq!(err.code, CONFLICT);
+        assert!(err.message.contains("accounts_name_type_key"));
+        let data = err.data.expect("error data");
+        assert_eq!(data["error_code"], "conflict");
+        assert_eq!(data["postgrest"]["code"], "23505");
+        assert_eq!(data["postgrest"]["status"], 409);
+        assert_eq!(
+            data["postgrest"]["details"],
+            "Key (name, type)=(Checking, offchain) already exists."
+        );
+
+        let err = failed(
+            "insert transaction",
+            rejected(
+                reqwest::StatusCode::CONFLICT,
+                r#"{"code":"23503","message":"insert or update on table \"transactions\" violates foreign key constraint","details":"Key (account_id) is not present in table \"accounts\".","hint":null}"#,
+            ),
+        );
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(err.data.unwrap()["error_code"], "validation");
+
+        let err = failed(
+            "list accounts",
+            rejected(reqwest::StatusCode::SERVICE_UNAVAILABLE, "upstream down"),
+        );
+        assert_eq!(err.code, UPSTREAM_UNAVAILABLE);
+        assert_eq!(err.message, "Failed to list accounts");
+        assert_eq!(err.data.unwrap()["error_code"], "upstream_unavailable");
+
+        let open = CircuitOpen {
+            dependency: "Supabase".into(),
+            retry_in: Duration::from_secs(5),
+        };
+        let err = failed("list accounts", anyhow::Error::new(open).context("listing"));
+        assert_eq!(err.code, UPSTREAM_UNAVAILABLE);
+        let data = err.data.unwrap();
+        assert_eq!(data["dependency"], "Supabase");
+        assert_eq!(data["retry_after_ms"], 5000);
+
+        let err = failed(
+            "list accounts",
+            rejected(reqwest::StatusCode::FORBIDDEN, "permission denied"),
+        );
+        assert_eq!(err.code, ErrorCode::INTERNAL_ERROR);
+        let err = failed("embed query text", anyhow::anyhow!("provider down"));
+        assert_eq!(err.code, ErrorCode::INTERNAL_ERROR);
+        assert_eq!(
+            err.data,
+            Some(json!({ "details": "provider down", "error_code": "internal" }))
+        );
     }
-}
 
-fn internal_error(action: &str, err: anyhow::Error) -> McpError {
-    McpError::internal_error(
-        format!("Failed to {action}"),
-        Some(json!({ "details": err.to_string() })),
-    )
-}
+    #[test]
+    fn errors_raised_without_a_kind_get_one_from_their_code() {
+        let err = errors::with_error_code(McpError::invalid_params("bad arguments", None));
+        assert_eq!(err.data, Some(json!({ "error_code": "validation" })));
 
-fn success(value: Value) -> CallToolResult {
-    CallToolResult::structured(value)
-}
+        let err = errors::with_error_code(McpError::invalid_params(
+            "bad arguments",
+            Some(json!({ "field": "amount" })),
+        ));
+        assert_eq!(err.data, Some(json!({ "field": "amount", "error_code": "validation" })));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::models::{
-        CreateTransactionInput, ListAccountsInput, SearchSimilarInput, TransactionDirection,
-        UpsertAccountInput, UpsertCategoryInput,
-    };
-    use crate::{embedding::Embedder, supabase::Database};
-    use anyhow::Result;
-    use async_trait::async_trait;
-    use rmcp::model::ErrorCode;
-    use serde_json::{json, Value};
-    use std::sync::Mutex;
+        let err: McpError = ToolError::new(ErrorKind::NotFound, "gone").into();
+        let err = errors::with_error_code(err);
+        assert_eq!(err.code, ErrorCode::RESOURCE_NOT_FOUND);
+        assert_eq!(err.data, Some(json!({ "error_code": "not_found" })));
+        assert_eq!(
+            errors::with_error_code(McpError::new(ErrorCode::METHOD_NOT_FOUND, "no", None)).data,
+            Some(json!({ "error_code": "internal" }))
+        );
+    }
 
     #[tokio::test]
-    async fn rejects_blank_transaction_query() {
+    async fn call_rpc_only_calls_allowlisted_functions() {
         let db = Arc::new(FakeDatabase::default());
-        let embedder = Arc::new(FakeEmbedder::new(vec![0.1]));
-        let server = ExaspoonDbServer::new(db, embedder);
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])));
+        assert!(!server.tool_router.has_route("call_rpc"));
+
+        let server = server.with_rpc_allowlist(vec!["monthly_burn".into()]);
+        assert!(server.tool_router.has_route("call_rpc"));
+        assert!(!server.tool_router.has_route("purge_deleted"));
+
+        let mut params = serde_json::Map::new();
+        params.insert("months".into(), json!(3));
+        let result = server
+            .call_rpc(Parameters(DryRun::from(CallRpcInput {
+                function: " monthly_burn ".into(),
+                params,
+            })))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["function"], "monthly_burn");
+        assert_eq!(payload["result"], 1234.5);
+        assert_eq!(
+            db.state.lock().unwrap().function_calls,
+            vec![("monthly_burn".to_string(), json!({ "months": 3 }))]
+        );
+        let events = db.state.lock().unwrap().audit_events.clone();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tool, "call_rpc");
 
         let err = server
-            .search_similar_transactions(Parameters(SearchSimilarInput {
-                query: "   ".into(),
-                limit: None,
-            }))
+            .call_rpc(Parameters(DryRun::from(CallRpcInput {
+                function: "exec_sql".into(),
+                params: Default::default(),
+            })))
             .await
-            .expect_err("expected validation error");
+            .expect_err("expected allowlist rejection");
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(db.state.lock().unwrap().function_calls.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn purge_deleted_is_an_admin_tool() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])));
+        assert!(!server.tool_router.has_route("purge_deleted"));
+        assert!(server.tool_router.has_route("delete_transaction"));
+
+        let server = server.with_admin_tools();
+        assert!(server.tool_router.has_route("purge_deleted"));
+
+        let result = server
+            .purge_deleted(Parameters(PurgeDeletedInput::default().into()))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["purged"]["transactions"], 1);
+        assert_eq!(payload["purged"]["accounts"], 1);
+        let kinds: Vec<RecordKind> = db
+            .state
+            .lock()
+            .unwrap()
+            .purged
+            .iter()
+            .map(|(kind, _)| *kind)
+            .collect();
+        assert_eq!(kinds, RecordKind::ALL);
 
+        let err = server
+            .purge_deleted(Parameters(DryRun::from(PurgeDeletedInput {
+                kind: None,
+                deleted_before: Some("last week".into()),
+            })))
+            .await
+            .expect_err("expected validation error");
         assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
     }
 
     #[tokio::test]
-    async fn search_similar_transactions_returns_matches() {
+    async fn list_transactions_reports_page_and_total() {
         let db = Arc::new(FakeDatabase::default());
         db.configure(|state| {
-            state.transaction_matches = vec![json!({"id": "txn-42"})];
+            state.transactions = vec![transaction("txn-1"), transaction("txn-2")];
+            state.transaction_total = 42;
         });
-        let embedder = Arc::new(FakeEmbedder::new(vec![0.2, 0.4]));
-        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
 
         let result = server
-            .search_similar_transactions(Parameters(SearchSimilarInput {
-                query: "Rent".into(),
-                limit: Some(7),
+            .list_transactions(Parameters(ListTransactionsInput {
+                limit: Some(2),
+                offset: Some(10),
+                ..Default::default()
             }))
             .await
             .expect("tool call should succeed");
 
         let payload = result.structured_content.expect("structured payload");
-        assert_eq!(payload["matches"][0]["id"], "txn-42");
-        assert_eq!(embedder.calls(), vec!["Rent"]);
-        assert_eq!(db.transaction_search_limits(), vec![Some(7)]);
+        assert_eq!(payload["transactions"][1]["id"], "txn-2");
+        assert_eq!(payload["total"], 42);
+        assert_eq!(payload["limit"], 2);
+        assert_eq!(payload["offset"], 10);
+
+        let result = server
+            .list_transactions(Parameters(ListTransactionsInput::default()))
+            .await
+            .expect("tool call should succeed");
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["limit"], DEFAULT_TRANSACTION_PAGE);
+        assert_eq!(payload["offset"], 0);
     }
 
     #[tokio::test]
-    async fn create_transaction_embeds_description() {
+    async fn ping_reports_liveness_without_touching_backends() {
         let db = Arc::new(FakeDatabase::default());
-        let embedder = Arc::new(FakeEmbedder::new(vec![0.5]));
+        db.configure(|state| state.ping_error = Some("connection refused".into()));
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.1]));
+        let server = ExaspoonDbServer::new(db, embedder.clone()).with_providers(Providers {
+            database_backend: "supabase".into(),
+            embedding_provider: "openai".into(),
+            embedding_model: "text-embedding-3-small".into(),
+        });
+
+        let result = server.ping().await.expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["server_name"], "exaspoon-db-mcp");
+        assert_eq!(payload["protocol_version"], "2025-06-18");
+        assert_eq!(payload["providers"]["database_backend"], "supabase");
+        assert_eq!(payload["providers"]["embedding_model"], "text-embedding-3-small");
+        assert!(payload["uptime_secs"].is_u64());
+        assert!(embedder.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_every_dependency() {
+        let db = Arc::new(FakeDatabase::default());
+        let embedder = Arc::new(FakeEmbedder::new(vec![0.1]));
         let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
-        let input = CreateTransactionInput {
-            account_id: "acct-1".into(),
-            amount: 42.0,
-            currency: "USD".into(),
-            direction: TransactionDirection::Expense,
-            occurred_at: "2024-01-02T03:04:05Z".into(),
-            description: Some("Coffee".into()),
-            raw_source: None,
+
+        let result = server.health_check().await.expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["status"], "ok");
+        let names: Vec<&str> = payload["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|check| check["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "database",
+                "embedding",
+                "search_similar_transactions",
+                "search_similar_categories"
+            ]
+        );
+        assert_eq!(db.transaction_search_limits(), vec![Some(1)]);
+        assert_eq!(embedder.calls(), vec!["health check"]);
+    }
+
+    #[tokio::test]
+    async fn server_metrics_reports_backend_operations() {
+        let db = Arc::new(FakeDatabase::default());
+        let metrics = Metrics::new();
+        metrics.record("query accounts", Duration::from_millis(40), true);
+        metrics.record("query accounts", Duration::from_millis(60), false);
+        db.configure(|state| state.metrics = metrics.snapshot());
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+        server
+            .search_similar_categories(Parameters(SearchSimilarInput {
+                query: "coffee".into(),
+                limit: None,
+            }))
+            .await
+            .unwrap();
+
+        let result = server.server_metrics().await.expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        let operation = &payload["operations"][0];
+        assert_eq!(operation["operation"], "query accounts");
+        assert_eq!(operation["calls"], 2);
+        assert_eq!(operation["errors"], 1);
+        assert_eq!(operation["error_rate"], 0.5);
+        assert_eq!(operation["mean_ms"], 50.0);
+        assert_eq!(operation["p50_ms"], 50);
+        assert_eq!(operation["p95_ms"], 100);
+        let embedding = &payload["embeddings"][0];
+        assert_eq!(embedding["operation"], "embed_query");
+        assert_eq!(embedding["calls"], 1);
+        assert!(payload["uptime_secs"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_degraded_database() {
+        let db = Arc::new(FakeDatabase::default());
+        db.configure(|state| state.ping_error = Some("connection refused".into()));
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let result = server.health_check().await.expect("tool call should succeed");
+
+        let payload = result.structured_content.expect("structured payload");
+        assert_eq!(payload["status"], "degraded");
+        assert_eq!(payload["checks"][0]["ok"], false);
+        assert_eq!(payload["checks"][0]["error"], "connection refused");
+        assert_eq!(payload["checks"][1]["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn startup_check_probes_tables_then_dependencies() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db.clone(), Arc::new(FakeEmbedder::new(vec![0.1])));
+
+        let report = server.startup_check().await;
+        assert_eq!(report.status, HealthStatus::Ok);
+        let names: Vec<&str> = report.checks.iter().map(|check| check.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "accounts",
+                "categories",
+                "transactions",
+                "audit_log",
+                "database",
+                "embedding",
+                "search_similar_transactions",
+                "search_similar_categories"
+            ]
+        );
+
+        db.configure(|state| state.ping_error = Some("connection refused".into()));
+        let report = server.startup_check().await;
+        assert_eq!(report.status, HealthStatus::Degraded);
+        let failed: Vec<&str> = report
+            .checks
+            .iter()
+            .filter(|check| !check.ok)
+            .map(|check| check.name.as_str())
+            .collect();
+        assert_eq!(failed, vec!["database"]);
+    }
+
+    #[test]
+    fn confirmed_tools_take_a_confirmation_token() {
+        let server = ExaspoonDbServer::new(
+            Arc::new(FakeDatabase::default()),
+            Arc::new(FakeEmbedder::new(vec![0.1])),
+        );
+        let takes_token = |server: &ExaspoonDbServer, name: &str| {
+            server.tool_router.map[name].attr.input_schema["properties"]
+                .get("confirmation_token")
+                .is_some()
         };
+        assert!(!takes_token(&server, "delete_transaction"));
 
-        let _ = server
-            .create_transaction(Parameters(input.clone()))
+        let notion = NotionClient::new(
+            &NotionConfig {
+                token: "secret_token".into(),
+                database_id: None,
+                base_url: crate::sheets::notion::NOTION_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let ynab = YnabClient::new(
+            &YnabConfig {
+                token: "ynab-token".into(),
+                budget_id: None,
+                base_url: ynab::YNAB_BASE_URL.into(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap();
+        let server = server
+            .with_admin_tools()
+            .with_notion(notion)
+            .with_ynab(ynab)
+            .with_confirmation(Duration::from_secs(60));
+        for name in [
+            "delete_transaction",
+            "delete_category",
+            "delete_account",
+            "purge_deleted",
+            "embedding_maintenance",
+            "upsert_account",
+            "export_to_sheet",
+            "export_ynab",
+        ] {
+            assert!(takes_token(&server, name), "{name}");
+        }
+        assert!(!takes_token(&server, "create_transaction"));
+        for route in server.tool_router.map.values() {
+            assert_eq!(
+                takes_token(&server, &route.attr.name),
+                needs_confirmation(&route.attr),
+                "{}",
+                route.attr.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn session_defaults_fill_what_calls_leave_out() {
+        let db = Arc::new(FakeDatabase::default());
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])));
+        let result = server
+            .set_session_defaults(Parameters(SessionDefaults {
+                account_id: Some("acct-9".into()),
+                currency: Some("EUR".into()),
+                timezone: Some("+03:00".into()),
+            }))
             .await
-            .expect("tool call should succeed");
+            .unwrap();
+        assert_eq!(result.structured_content.unwrap()["defaults"]["currency"], "EUR");
 
-        let inserts = db.inserted_transactions();
-        assert_eq!(inserts.len(), 1);
-        assert_eq!(inserts[0].0.description.as_deref(), Some("Coffee"));
-        assert_eq!(inserts[0].1, Some(vec![0.5]));
-        assert_eq!(embedder.calls(), vec!["Coffee"]);
+        let mut arguments = json!({
+            "transactions": [
+                { "amount": 5, "direction": "expense", "occurred_at": "2024-01-02" },
+                {
+                    "account_id": "acct-1", "currency": "USD", "amount": 7,
+                    "direction": "income", "occurred_at": "2024-01-02T03:04:05Z"
+                },
+            ]
+        });
+        let map = arguments.as_object_mut().unwrap();
+        server.apply_session_defaults("create_transactions", map);
+        assert_eq!(
+            arguments["transactions"],
+            json!([
+                {
+                    "account_id": "acct-9", "currency": "EUR", "amount": 5,
+                    "direction": "expense", "occurred_at": "2024-01-02T00:00:00+03:00"
+                },
+                {
+                    "account_id": "acct-1", "currency": "USD", "amount": 7,
+                    "direction": "income", "occurred_at": "2024-01-02T03:04:05Z"
+                },
+            ])
+        );
+
+        let mut arguments = json!({ "filters": { "from": "2024-01-01T09:30" } });
+        server.apply_session_defaults("list_transactions", arguments.as_object_mut().unwrap());
+        assert_eq!(arguments["filters"]["from"], "2024-01-01T09:30:00+03:00");
+        assert_eq!(arguments["filters"].get("account_id"), None);
+
+        // Postgres function arguments are the function's own.
+        let mut arguments =
+            json!({ "function": "monthly_report", "params": { "from": "2024-01-01" } });
+        server.apply_session_defaults("call_rpc", arguments.as_object_mut().unwrap());
+        assert_eq!(arguments["params"]["from"], "2024-01-01");
+
+        // A named timezone follows its daylight saving time.
+        server.update_session_defaults(SessionDefaults {
+            timezone: Some("Europe/Berlin".into()),
+            ..Default::default()
+        });
+        let mut arguments = json!({ "filters": { "from": "2024-01-01", "to": "2024-07-01" } });
+        server.apply_session_defaults("list_transactions", arguments.as_object_mut().unwrap());
+        assert_eq!(arguments["filters"]["from"], "2024-01-01T00:00:00+01:00");
+        assert_eq!(arguments["filters"]["to"], "2024-07-01T00:00:00+02:00");
+
+        // Omitted fields are kept and empty ones cleared.
+        let defaults = server.update_session_defaults(SessionDefaults {
+            currency: Some(String::new()),
+            ..Default::default()
+        });
+        assert_eq!(defaults.account_id.as_deref(), Some("acct-9"));
+        assert_eq!(defaults.currency, None);
+        let mut arguments = json!({ "name": "Cash", "type": "offchain" });
+        server.apply_session_defaults("upsert_account", arguments.as_object_mut().unwrap());
+        assert_eq!(arguments.get("currency"), None);
     }
 
     #[tokio::test]
-    async fn create_transaction_skips_embedding_without_description() {
+    async fn describe_capabilities_lists_mounted_tools_with_examples() {
         let db = Arc::new(FakeDatabase::default());
-        let embedder = Arc::new(FakeEmbedder::new(vec![0.9]));
-        let server = ExaspoonDbServer::new(db.clone(), embedder.clone());
-        let input = CreateTransactionInput {
-            account_id: "acct-2".into(),
-            amount: 10.0,
+        db.configure(|state| {
+            state.accounts = vec![Account {
+                id: "acct-9".into(),
+                currency: "EUR".into(),
+                ..state.account_response.clone()
+            }];
+            state.transactions = vec![transaction("txn-7")];
+        });
+        let server = ExaspoonDbServer::new(db, Arc::new(FakeEmbedder::new(vec![0.1])))
+            .with_tool_groups(vec![ToolGroup::Core, ToolGroup::Monitoring]);
+
+        let capabilities = server.capabilities().await;
+        let names: Vec<&str> = capabilities.tools.iter().map(|tool| tool.name.as_str()).collect();
+        assert!(names.contains(&"describe_capabilities"));
+        assert!(!names.contains(&"seed_demo_data"), "maintenance is not mounted");
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let create = tool_named(&capabilities, "create_transaction");
+        assert_eq!(create.group, "core");
+        assert!(create.writes);
+        assert!(create.prerequisites.contains(&"table transactions".to_string()));
+        assert_eq!(create.example["account_id"], "acct-9");
+        assert_eq!(create.example["currency"], "EUR");
+        serde_json::from_value::<CreateTransactionInput>(create.example.clone()).unwrap();
+        let health_check = tool_named(&capabilities, "health_check");
+        assert!(!health_check.writes);
+        assert_eq!(health_check.group, "monitoring");
+
+        let delete = tool_named(&capabilities, "delete_transaction");
+        assert_eq!(delete.example["id"], "txn-7");
+        assert_eq!(delete.example["dry_run"], true);
+    }
+
+    fn tool_named<'a>(capabilities: &'a CapabilitiesOutput, name: &str) -> &'a ToolCapability {
+        capabilities.tools.iter().find(|tool| tool.name == name).unwrap()
+    }
+
+    fn transaction(id: &str) -> Transaction {
+        Transaction {
+            id: id.into(),
+            account_id: "acct-1".into(),
+            amount: 42.0,
             currency: "USD".into(),
-            direction: TransactionDirection::Income,
+            direction: TransactionDirection::Expense,
             occurred_at: "2024-01-02T03:04:05Z".into(),
             description: None,
             raw_source: None,
-        };
-
-        server
-            .create_transaction(Parameters(input))
-            .await
-            .expect("tool call should succeed");
-
-        let inserts = db.inserted_transactions();
-        assert_eq!(inserts[0].1, None);
-        assert!(embedder.calls().is_empty());
+            category_id: None,
+            created_at: None,
+        }
     }
 
     #[derive(Default)]
     struct FakeEmbedder {
         vector: Vec<f32>,
         calls: Mutex<Vec<String>>,
+        cancel_after: Option<(usize, CancellationToken)>,
     }
 
     impl FakeEmbedder {
@@ -389,9 +7829,17 @@ mod tests {
             Self {
                 vector,
                 calls: Mutex::new(Vec::new()),
+                cancel_after: None,
             }
         }
 
+        /// Cancels `token` once `calls` embeddings have been made, like a
+        /// client giving up part way through a batch.
+        fn cancelling_after(mut self, calls: usize, token: CancellationToken) -> Self {
+            self.cancel_after = Some((calls, token));
+            self
+        }
+
         fn calls(&self) -> Vec<String> {
             self.calls.lock().unwrap().clone()
         }
@@ -400,7 +7848,13 @@ mod tests {
     #[async_trait]
     impl Embedder for FakeEmbedder {
         async fn embed(&self, text: &str) -> Result<Vec<f32>> {
-            self.calls.lock().unwrap().push(text.to_string());
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(text.to_string());
+            if let Some((after, token)) = &self.cancel_after {
+                if calls.len() == *after {
+                    token.cancel();
+                }
+            }
             Ok(self.vector.clone())
         }
 
@@ -443,12 +7897,25 @@ mod tests {
     struct FakeState {
         inserted_transactions: Vec<(CreateTransactionInput, Option<Vec<f32>>)>,
         searched_transaction_limits: Vec<Option<u32>>,
-        transaction_response: Value,
-        transaction_matches: Vec<Value>,
-        category_response: Value,
-        category_matches: Vec<Value>,
-        accounts: Vec<Value>,
-        account_response: Value,
+        transaction_response: Transaction,
+        transaction_matches: Vec<TransactionMatch>,
+        category_response: Category,
+        category_matches: Vec<CategoryMatch>,
+        accounts: Vec<Account>,
+        account_response: Account,
+        transactions: Vec<Transaction>,
+        transaction_total: u64,
+        spending_buckets: Vec<SpendingBucket>,
+        ping_error: Option<String>,
+        soft_deleted: Vec<(RecordKind, String)>,
+        deleted: Vec<(RecordKind, String)>,
+        purged: Vec<(RecordKind, Option<String>)>,
+        audit_events: Vec<AuditEvent>,
+        function_calls: Vec<(String, Value)>,
+        text_searches: Vec<(String, Option<u32>)>,
+        metrics: Vec<OperationMetrics>,
+        embedding_issues: Vec<EmbeddingIssue>,
+        embeddings_set: Vec<(String, Option<usize>)>,
     }
 
     impl Default for FakeState {
@@ -456,12 +7923,39 @@ mod tests {
             Self {
                 inserted_transactions: Vec::new(),
                 searched_transaction_limits: Vec::new(),
-                transaction_response: json!({ "id": "txn-default" }),
+                transaction_response: transaction("txn-default"),
                 transaction_matches: Vec::new(),
-                category_response: json!({ "id": "cat-default" }),
+                category_response: Category {
+                    id: "cat-default".into(),
+                    name: "Default".into(),
+                    kind: CategoryKind::Expense,
+                    description: None,
+                    created_at: None,
+                },
                 category_matches: Vec::new(),
                 accounts: Vec::new(),
-                account_response: json!({ "id": "acct-default" }),
+                account_response: Account {
+                    id: "acct-default".into(),
+                    name: "Default".into(),
+                    r#type: AccountType::Offchain,
+                    currency: "USD".into(),
+                    network: None,
+                    institution: None,
+                    created_at: None,
+                },
+                transactions: Vec::new(),
+                transaction_total: 0,
+                spending_buckets: Vec::new(),
+                ping_error: None,
+                soft_deleted: Vec::new(),
+                deleted: Vec::new(),
+                purged: Vec::new(),
+                audit_events: Vec::new(),
+                text_searches: Vec::new(),
+                metrics: Vec::new(),
+                embedding_issues: Vec::new(),
+                embeddings_set: Vec::new(),
+                function_calls: Vec::new(),
             }
         }
     }
@@ -472,7 +7966,7 @@ mod tests {
             &self,
             input: &CreateTransactionInput,
             embedding: Option<Vec<f32>>,
-        ) -> Result<Value> {
+        ) -> Result<Transaction> {
             let mut state = self.state.lock().unwrap();
             state.inserted_transactions.push((input.clone(), embedding));
             Ok(state.transaction_response.clone())
@@ -482,17 +7976,25 @@ mod tests {
             &self,
             _input: &UpsertCategoryInput,
             _embedding: Option<Vec<f32>>,
-        ) -> Result<Value> {
+        ) -> Result<Category> {
             let state = self.state.lock().unwrap();
             Ok(state.category_response.clone())
         }
 
-        async fn upsert_account(&self, _input: &UpsertAccountInput) -> Result<Value> {
+        async fn upsert_account(&self, _input: &UpsertAccountInput) -> Result<Account> {
             let state = self.state.lock().unwrap();
             Ok(state.account_response.clone())
         }
 
-        async fn list_accounts(&self, _params: &ListAccountsInput) -> Result<Vec<Value>> {
+        async fn categorize_transaction(
+            &self,
+            _id: &str,
+            _category_id: Option<&str>,
+        ) -> Result<Option<Categorized>> {
+            Ok(None)
+        }
+
+        async fn list_accounts(&self, _params: &ListAccountsInput) -> Result<Vec<Account>> {
             let state = self.state.lock().unwrap();
             Ok(state.accounts.clone())
         }
@@ -501,7 +8003,7 @@ mod tests {
             &self,
             _embedding: Vec<f32>,
             limit: Option<u32>,
-        ) -> Result<Vec<Value>> {
+        ) -> Result<Vec<TransactionMatch>> {
             let mut state = self.state.lock().unwrap();
             state.searched_transaction_limits.push(limit);
             Ok(state.transaction_matches.clone())
@@ -511,9 +8013,159 @@ mod tests {
             &self,
             _embedding: Vec<f32>,
             _limit: Option<u32>,
-        ) -> Result<Vec<Value>> {
+        ) -> Result<Vec<CategoryMatch>> {
             let state = self.state.lock().unwrap();
             Ok(state.category_matches.clone())
         }
+
+        async fn list_category_names(&self, _search: &str, _limit: u32) -> Result<Vec<String>> {
+            let state = self.state.lock().unwrap();
+            Ok(state
+                .category_matches
+                .iter()
+                .map(|found| found.category.name.clone())
+                .collect())
+        }
+
+        async fn search_transactions_text(
+            &self,
+            query: &str,
+            limit: Option<u32>,
+        ) -> Result<Vec<TransactionTextMatch>> {
+            let mut state = self.state.lock().unwrap();
+            state.text_searches.push((query.to_string(), limit));
+            Ok(state
+                .transactions
+                .iter()
+                .map(|transaction| TransactionTextMatch {
+                    transaction: transaction.clone(),
+                    rank: Some(0.5),
+                })
+                .collect())
+        }
+
+        async fn count_accounts(&self, _params: &ListAccountsInput) -> Result<u64> {
+            Ok(self.state.lock().unwrap().accounts.len() as u64)
+        }
+
+        async fn list_transactions(
+            &self,
+            _input: &ListTransactionsInput,
+        ) -> Result<Vec<Transaction>> {
+            Ok(self.state.lock().unwrap().transactions.clone())
+        }
+
+        async fn count_transactions(&self, _filters: &TransactionFilters) -> Result<u64> {
+            Ok(self.state.lock().unwrap().transaction_total)
+        }
+
+        async fn largest_transactions(
+            &self,
+            _filters: &TransactionFilters,
+            limit: u32,
+        ) -> Result<Vec<Transaction>> {
+            let mut transactions = self.state.lock().unwrap().transactions.clone();
+            transactions.sort_by(|left, right| right.amount.total_cmp(&left.amount));
+            transactions.truncate(limit as usize);
+            Ok(transactions)
+        }
+
+        async fn aggregate_spending(
+            &self,
+            _input: &AggregateSpendingInput,
+        ) -> Result<Vec<SpendingBucket>> {
+            Ok(self.state.lock().unwrap().spending_buckets.clone())
+        }
+
+        async fn record_audit_events(&self, events: &[AuditEvent]) -> Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.audit_events.extend_from_slice(events);
+            Ok(())
+        }
+
+        async fn list_audit_events(
+            &self,
+            _input: &ListAuditEventsInput,
+        ) -> Result<Vec<AuditEvent>> {
+            Ok(self.state.lock().unwrap().audit_events.clone())
+        }
+
+        async fn record_tool_calls(&self, _calls: &[AuditEvent]) -> Result<()> {
+            Ok(())
+        }
+
+        async fn soft_delete(&self, kind: RecordKind, id: &str) -> Result<bool> {
+            let mut state = self.state.lock().unwrap();
+            state.soft_deleted.push((kind, id.to_string()));
+            Ok(id != "missing")
+        }
+
+        async fn record_exists(&self, _kind: RecordKind, id: &str) -> Result<bool> {
+            Ok(id != "missing")
+        }
+
+        async fn delete_record(&self, kind: RecordKind, id: &str) -> Result<bool> {
+            let mut state = self.state.lock().unwrap();
+            state.deleted.push((kind, id.to_string()));
+            Ok(id != "missing")
+        }
+
+        async fn purge_deleted(
+            &self,
+            kind: RecordKind,
+            deleted_before: Option<&str>,
+        ) -> Result<u64> {
+            let mut state = self.state.lock().unwrap();
+            state.purged.push((kind, deleted_before.map(str::to_string)));
+            Ok(1)
+        }
+
+        async fn call_function(&self, function: &str, params: Value) -> Result<Value> {
+            let mut state = self.state.lock().unwrap();
+            state.function_calls.push((function.to_string(), params));
+            Ok(json!(1234.5))
+        }
+
+        async fn ping(&self) -> Result<()> {
+            match &self.state.lock().unwrap().ping_error {
+                Some(message) => Err(anyhow::anyhow!(message.clone())),
+                None => Ok(()),
+            }
+        }
+
+        fn metrics(&self) -> Vec<OperationMetrics> {
+            self.state.lock().unwrap().metrics.clone()
+        }
+
+        async fn embedding_issues(
+            &self,
+            _kind: RecordKind,
+            _dimension: usize,
+            after: Option<&str>,
+            limit: u32,
+        ) -> Result<Vec<EmbeddingIssue>> {
+            let state = self.state.lock().unwrap();
+            Ok(state
+                .embedding_issues
+                .iter()
+                .filter(|issue| after.is_none_or(|after| issue.id.as_str() > after))
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn set_embedding(
+            &self,
+            _kind: RecordKind,
+            id: &str,
+            embedding: Option<Vec<f32>>,
+        ) -> Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.embedding_issues.retain(|issue| issue.id != id);
+            state
+                .embeddings_set
+                .push((id.to_string(), embedding.map(|embedding| embedding.len())));
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,245 @@
+//! The server's side of `execute_batch`: checking the operations up front,
+//! embedding what they create, and reporting a failed step.
+
+use super::{
+    missing_field, missing_fields, ErrorKind, ExaspoonDbServer, ToolError, MAX_BATCH_TRANSACTIONS,
+};
+use crate::{
+    batch::{BatchFailed, BatchStep, MissingRow, Reference, MAX_BATCH_OPERATIONS},
+    cancellation,
+    i18n::Message,
+    models::{
+        BatchOperation, BatchStepResult, CategorizeInput, CreateTransactionInput, Transaction,
+    },
+    progress::Progress,
+};
+use rmcp::ErrorData as McpError;
+use tracing::{error, warn};
+
+impl ExaspoonDbServer {
+    /// Turns `operations` into steps, embedding the description of every
+    /// transaction they create. Stops early once the call is cancelled.
+    pub(crate) async fn embed_batch(
+        &self,
+        operations: &[BatchOperation],
+    ) -> Result<Vec<BatchStep>, McpError> {
+        let progress = Progress::current();
+        let total = transaction_count(operations);
+        let mut embedded = 0;
+        let mut steps = Vec::with_capacity(operations.len());
+        for operation in operations {
+            if cancellation::is_cancelled() {
+                break;
+            }
+            let step = match operation {
+                BatchOperation::UpsertAccount(input) => BatchStep::UpsertAccount(input.clone()),
+                BatchOperation::Categorize(input) => BatchStep::Categorize(input.clone()),
+                BatchOperation::CreateTransactions(input) => {
+                    let mut rows = Vec::with_capacity(input.transactions.len());
+                    for transaction in &input.transactions {
+                        let embedding = self
+                            .embedder
+                            .maybe_embed(transaction.description.as_deref())
+                            .await
+                            .map_err(|err| {
+                                error!("Failed to generate transaction embedding: {}", err);
+                                ToolError::failed("generate transaction embedding", err)
+                            })?;
+                        rows.push((transaction.clone(), embedding));
+                        embedded += 1;
+                        progress
+                            .report(embedded, total, "Embedded transactions")
+                            .await;
+                    }
+                    BatchStep::CreateTransactions(rows)
+                }
+            };
+            steps.push(step);
+        }
+        Ok(steps)
+    }
+
+    /// Inserts `rows` in batches of [`MAX_BATCH_TRANSACTIONS`], filing each
+    /// under its category from `filed` in the same batch, so that a batch is
+    /// written categorized or not at all.
+    pub(crate) async fn insert_filed(
+        &self,
+        rows: &[(CreateTransactionInput, Option<Vec<f32>>)],
+        filed: &[Option<String>],
+    ) -> Result<Vec<Transaction>, McpError> {
+        let mut inserted = Vec::with_capacity(rows.len());
+        let batches = rows
+            .chunks(MAX_BATCH_TRANSACTIONS)
+            .zip(filed.chunks(MAX_BATCH_TRANSACTIONS));
+        for (batch, categories) in batches {
+            let mut steps = vec![BatchStep::CreateTransactions(batch.to_vec())];
+            steps.extend(
+                categories
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(item, category_id)| {
+                        let category_id = category_id.clone()?;
+                        Some(BatchStep::Categorize(CategorizeInput {
+                            transaction_id: format!("$0.{item}"),
+                            category_id,
+                        }))
+                    }),
+            );
+            let results = self.supabase.execute_batch(&steps).await.map_err(|err| {
+                error!("Failed to insert transactions: {}", err);
+                ToolError::failed("insert transactions", err)
+            })?;
+            let mut written = Vec::new();
+            for result in results {
+                match result {
+                    BatchStepResult::CreateTransactions { transactions } => written = transactions,
+                    BatchStepResult::Categorize { transaction } => {
+                        if let Some(row) = written.iter_mut().find(|row| row.id == transaction.id) {
+                            *row = transaction;
+                        }
+                    }
+                    BatchStepResult::UpsertAccount { .. } => {}
+                }
+            }
+            inserted.extend(written);
+        }
+        Ok(inserted)
+    }
+}
+
+/// Rejects a batch that is empty or too large, leaves out a required field,
+/// or refers to an operation that does not come before the one referring.
+pub(crate) fn check_batch(operations: &[BatchOperation]) -> Result<(), McpError> {
+    if operations.is_empty() || operations.len() > MAX_BATCH_OPERATIONS {
+        warn!("Rejected batch of {} operations", operations.len());
+        return Err(ToolError::invalid(
+            format!("operations must contain between 1 and {MAX_BATCH_OPERATIONS} items"),
+            "operations",
+        )
+        .into());
+    }
+    let transactions = transaction_count(operations);
+    if transactions > MAX_BATCH_TRANSACTIONS {
+        warn!("Rejected batch of {} transactions", transactions);
+        return Err(ToolError::invalid(
+            format!("a batch may create at most {MAX_BATCH_TRANSACTIONS} transactions"),
+            "operations",
+        )
+        .into());
+    }
+
+    for (index, operation) in operations.iter().enumerate() {
+        let path = format!("operations[{index}]");
+        match operation {
+            BatchOperation::UpsertAccount(input) => {
+                if input.currency.trim().is_empty() {
+                    return Err(missing_field(&format!("{path}.currency")));
+                }
+            }
+            BatchOperation::CreateTransactions(input) => {
+                if input.transactions.is_empty() {
+                    return Err(ToolError::invalid(
+                        "transactions must not be empty",
+                        &format!("{path}.transactions"),
+                    )
+                    .into());
+                }
+                for (item, transaction) in input.transactions.iter().enumerate() {
+                    let path = format!("{path}.transactions[{item}]");
+                    if let Some(field) = missing_fields(transaction).first() {
+                        return Err(missing_field(&format!("{path}.{field}")));
+                    }
+                    check_reference(
+                        &transaction.account_id,
+                        index,
+                        &format!("{path}.account_id"),
+                    )?;
+                }
+            }
+            BatchOperation::Categorize(input) => {
+                for (field, value) in [
+                    ("transaction_id", &input.transaction_id),
+                    ("category_id", &input.category_id),
+                ] {
+                    let path = format!("{path}.{field}");
+                    if value.trim().is_empty() {
+                        return Err(missing_field(&path));
+                    }
+                    check_reference(value, index, &path)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails when `value` is a malformed reference or one to operation `index`
+/// or a later one.
+fn check_reference(value: &str, index: usize, path: &str) -> Result<(), McpError> {
+    match Reference::parse(value) {
+        Err(err) => Err(ToolError::invalid(err.to_string(), path).into()),
+        Ok(Some(reference)) if reference.step >= index => Err(ToolError::invalid(
+            format!("{value} must refer to an operation before this one"),
+            path,
+        )
+        .into()),
+        Ok(_) => Ok(()),
+    }
+}
+
+fn transaction_count(operations: &[BatchOperation]) -> usize {
+    operations
+        .iter()
+        .map(|operation| match operation {
+            BatchOperation::CreateTransactions(input) => input.transactions.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The embedding a step's dry run reports the dimension of.
+pub(crate) fn first_embedding(step: &BatchStep) -> Option<&[f32]> {
+    match step {
+        BatchStep::CreateTransactions(rows) => {
+            rows.iter().find_map(|(_, embedding)| embedding.as_deref())
+        }
+        _ => None,
+    }
+}
+
+/// Ids of the rows a step wrote, for the audit log.
+pub(crate) fn written_ids(result: &BatchStepResult) -> Vec<&str> {
+    match result {
+        BatchStepResult::UpsertAccount { account } => vec![account.id.as_str()],
+        BatchStepResult::CreateTransactions { transactions } => transactions
+            .iter()
+            .map(|transaction| transaction.id.as_str())
+            .collect(),
+        BatchStepResult::Categorize { transaction } => vec![transaction.id.as_str()],
+    }
+}
+
+/// The error for a batch that did not go through, naming the failed step
+/// and whether the earlier ones were undone.
+pub(crate) fn batch_failed(err: anyhow::Error) -> McpError {
+    let failed = match err.downcast::<BatchFailed>() {
+        Ok(failed) => failed,
+        Err(err) => return ToolError::failed("execute batch", err).into(),
+    };
+    let action = format!("execute batch step {}", failed.step);
+    let missing = failed
+        .source
+        .downcast_ref::<MissingRow>()
+        .map(ToString::to_string);
+    let error = match missing {
+        Some(missing) => {
+            let failed = Message::Failed { action: &action }.text();
+            ToolError::new(ErrorKind::NotFound, format!("{failed}: {missing}"))
+        }
+        None => ToolError::failed(&action, failed.source),
+    };
+    error
+        .with("step", failed.step)
+        .with("rolled_back", failed.rolled_back)
+        .into()
+}
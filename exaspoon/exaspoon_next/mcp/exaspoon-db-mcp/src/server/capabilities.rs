@@ -0,0 +1,517 @@
+//! `describe_capabilities`: what each mounted tool is for, what it needs from
+//! the database, and an example call built from the tool's input type, with
+//! ids from the database where it has rows.
+
+use super::{can_write, ExaspoonDbServer, ToolGroup};
+use crate::models::{
+    AccountType, AggregateSpendingInput, BatchOperation, CallRpcInput, CapabilitiesOutput,
+    CategorizeInput, CategoryKind, ConvertCurrencyInput, CreateTransactionInput,
+    CreateTransactionsInput, DeleteRecordInput, DryRun, EmbeddingMaintenanceInput, Exchange,
+    ExecuteBatchInput, ExportLedgerInput, ExportToSheetInput, ExportYnabInput, GetAssetPriceInput,
+    GetNetWorthInput, ImportBankStatementInput, ImportGnuCashInput, ImportJsonInput,
+    ImportOfxInput, ImportQifInput, ImportYnabInput, LedgerFormat, LinkBankAccountInput,
+    LinkOpenBankingAccountInput, ListAccountsInput, ListAuditEventsInput, ListTransactionsInput,
+    ParseReceiptInput, PurgeDeletedInput, RecordKind, SearchSimilarInput, SearchTextInput,
+    SeedDemoDataInput, SessionDefaults, SheetDestination, SheetReport, SpendingGroupBy,
+    SpendingPeriod, SummarizePeriodInput, SyncBankAccountInput, SyncExchangeInput,
+    SyncOnchainInput, SyncOpenBankingInput, ToolCapability, TransactionDirection,
+    TransactionFilters, UpsertAccountInput, UpsertCategoryInput,
+};
+use chrono::{Datelike, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+/// Values the examples use: taken from the newest rows when there are any,
+/// placeholders otherwise.
+struct Sample {
+    account_id: String,
+    currency: String,
+    transaction_id: String,
+    category_id: String,
+}
+
+impl Default for Sample {
+    fn default() -> Self {
+        Self {
+            account_id: "<account id from list_accounts>".to_string(),
+            currency: "USD".to_string(),
+            transaction_id: "<transaction id from list_transactions>".to_string(),
+            category_id: "<category id from search_similar_categories>".to_string(),
+        }
+    }
+}
+
+impl ExaspoonDbServer {
+    /// Every mounted tool, by the name clients call it, with its
+    /// prerequisites and an example.
+    pub(crate) async fn capabilities(&self) -> CapabilitiesOutput {
+        let sample = self.sample().await;
+        let mut tools: Vec<ToolCapability> = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|tool| {
+                let group = ToolGroup::ALL
+                    .into_iter()
+                    .find(|group| group.router().has_route(&tool.name))
+                    .unwrap_or(ToolGroup::Core);
+                ToolCapability {
+                    name: self.tool_names.exposed(&tool.name),
+                    purpose: tool.description.as_deref().unwrap_or_default().to_string(),
+                    group: group.as_ref().to_string(),
+                    writes: can_write(&tool),
+                    prerequisites: prerequisites(&tool.name)
+                        .iter()
+                        .map(|prerequisite| prerequisite.to_string())
+                        .collect(),
+                    example: self.example(&tool.name, &sample),
+                }
+            })
+            .collect();
+        tools.sort_by(|left, right| left.name.cmp(&right.name));
+        CapabilitiesOutput { tools }
+    }
+
+    /// Ids from the newest account and transaction. Lookups that fail leave
+    /// the placeholders, as the description should not depend on them.
+    async fn sample(&self) -> Sample {
+        let mut sample = Sample::default();
+        let accounts = ListAccountsInput {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let transactions = ListTransactionsInput {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let (accounts, transactions) = tokio::join!(
+            self.supabase.list_accounts(&accounts),
+            self.supabase.list_transactions(&transactions),
+        );
+        match accounts {
+            Ok(accounts) => {
+                if let Some(account) = accounts.into_iter().next() {
+                    sample.account_id = account.id;
+                    sample.currency = account.currency;
+                }
+            }
+            Err(err) => debug!("No sample account for examples: {}", err),
+        }
+        match transactions {
+            Ok(transactions) => {
+                if let Some(transaction) = transactions.into_iter().next() {
+                    sample.transaction_id = transaction.id;
+                    if let Some(category_id) = transaction.category_id {
+                        sample.category_id = category_id;
+                    }
+                }
+            }
+            Err(err) => debug!("No sample transaction for examples: {}", err),
+        }
+        sample
+    }
+
+    fn example(&self, tool: &str, sample: &Sample) -> Value {
+        let now = Utc::now();
+        let month_start = format!("{}-{:02}-01T00:00:00Z", now.year(), now.month());
+        let transaction = CreateTransactionInput {
+            account_id: sample.account_id.clone(),
+            amount: 12.5,
+            currency: sample.currency.clone(),
+            direction: TransactionDirection::Expense,
+            occurred_at: now.to_rfc3339_opts(SecondsFormat::Secs, true),
+            description: Some("Coffee at the corner cafe".to_string()),
+            raw_source: None,
+        };
+        let this_month = TransactionFilters {
+            from: Some(month_start.clone()),
+            ..Default::default()
+        };
+        match tool {
+            "create_transaction" => arguments(transaction),
+            "create_transactions" => arguments(CreateTransactionsInput {
+                transactions: vec![transaction],
+            }),
+            "import_ofx" => dry_run(ImportOfxInput {
+                account_id: sample.account_id.clone(),
+                ofx: format!(
+                    "<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>{}<BANKTRANLIST>\
+                     <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>{}<TRNAMT>-12.50\
+                     <FITID>202401050001<NAME>Corner Cafe</STMTTRN>\
+                     </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>",
+                    sample.currency,
+                    now.format("%Y%m%d")
+                ),
+                currency: String::new(),
+            }),
+            "import_bank_statement" => dry_run(ImportBankStatementInput {
+                account_id: sample.account_id.clone(),
+                statement: format!(
+                    ":20:STATEMENT\n:25:DE89370400440532013000\n:28C:1/1\n\
+                     :60F:C{date}{currency}0,00\n:61:{date}D12,50NMSCNONREF\n\
+                     :86:/REMI/Coffee/NAME/Corner Cafe/\n:62F:D{date}{currency}12,50\n-",
+                    date = now.format("%y%m%d"),
+                    currency = sample.currency
+                ),
+                format: None,
+                currency: String::new(),
+            }),
+            "import_qif" => dry_run(ImportQifInput {
+                account_id: sample.account_id.clone(),
+                qif: format!(
+                    "!Type:Bank\nD{}\nT-12.50\nPCorner Cafe\nLFood:Coffee\n^\n",
+                    now.format("%m/%d/%Y")
+                ),
+                currency: sample.currency.clone(),
+                day_first: false,
+                min_similarity: None,
+            }),
+            "import_gnucash" => dry_run(ImportGnuCashInput {
+                book: format!(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<gnc-v2>\n<gnc:book version=\"2.0.0\">\n\
+                     <gnc:account version=\"2.0.0\"><act:name>Root Account</act:name><act:id type=\"guid\">r</act:id><act:type>ROOT</act:type></gnc:account>\n\
+                     <gnc:account version=\"2.0.0\"><act:name>Checking</act:name><act:id type=\"guid\">a</act:id><act:type>BANK</act:type>\
+                     <act:commodity><cmdty:space>CURRENCY</cmdty:space><cmdty:id>{currency}</cmdty:id></act:commodity><act:parent type=\"guid\">r</act:parent></gnc:account>\n\
+                     <gnc:account version=\"2.0.0\"><act:name>Coffee</act:name><act:id type=\"guid\">e</act:id><act:type>EXPENSE</act:type>\
+                     <act:commodity><cmdty:space>CURRENCY</cmdty:space><cmdty:id>{currency}</cmdty:id></act:commodity><act:parent type=\"guid\">r</act:parent></gnc:account>\n\
+                     <gnc:transaction version=\"2.0.0\"><trn:id type=\"guid\">t</trn:id><trn:date-posted><ts:date>{date} 10:00:00 +0000</ts:date></trn:date-posted>\
+                     <trn:description>Corner Cafe</trn:description><trn:splits>\
+                     <trn:split><split:id type=\"guid\">s1</split:id><split:value>-1250/100</split:value><split:quantity>-1250/100</split:quantity><split:account type=\"guid\">a</split:account></trn:split>\
+                     <trn:split><split:id type=\"guid\">s2</split:id><split:value>1250/100</split:value><split:quantity>1250/100</split:quantity><split:account type=\"guid\">e</split:account></trn:split>\
+                     </trn:splits></gnc:transaction>\n</gnc:book>\n</gnc-v2>\n",
+                    currency = sample.currency,
+                    date = now.format("%Y-%m-%d")
+                ),
+            }),
+            "import_ynab" => dry_run(ImportYnabInput {
+                register: Some(format!(
+                    "\"Account\",\"Flag\",\"Date\",\"Payee\",\"Category Group/Category\",\"Category Group\",\"Category\",\"Memo\",\"Outflow\",\"Inflow\",\"Cleared\"\n\
+                     \"Checking\",\"\",\"{}\",\"Corner Cafe\",\"Everyday: Coffee\",\"Everyday\",\"Coffee\",\"\",12.50,0.00,\"Cleared\"\n",
+                    now.format("%m/%d/%Y")
+                )),
+                plan: None,
+                budget_id: None,
+                currency: sample.currency.clone(),
+                day_first: false,
+            }),
+            "import_json" => dry_run(ImportJsonInput {
+                records: vec![json!(transaction)],
+            }),
+            "link_bank_account" => dry_run(LinkBankAccountInput {
+                account_id: sample.account_id.clone(),
+                public_token: Some("public-sandbox-00000000-0000-0000-0000-000000000000".to_string()),
+                access_token: None,
+                plaid_account_id: None,
+            }),
+            "sync_bank_account" => dry_run(SyncBankAccountInput {
+                account_id: sample.account_id.clone(),
+                currency: String::new(),
+            }),
+            "link_openbanking_account" => dry_run(LinkOpenBankingAccountInput {
+                account_id: sample.account_id.clone(),
+                requisition_id: "<requisition id from GoCardless>".to_string(),
+                openbanking_account_id: None,
+            }),
+            "sync_openbanking" => dry_run(SyncOpenBankingInput {
+                account_id: sample.account_id.clone(),
+            }),
+            "sync_onchain" => dry_run(SyncOnchainInput {
+                account_id: sample.account_id.clone(),
+                address: Some("0xd8da6bf26964af9d7eed9e03e53415d37aa96045".to_string()),
+            }),
+            "sync_exchange" => dry_run(SyncExchangeInput {
+                account_id: sample.account_id.clone(),
+                exchange: Some(Exchange::Binance),
+                symbols: Some(vec!["BTCUSDT".to_string()]),
+            }),
+            "parse_receipt" => dry_run(ParseReceiptInput {
+                image: "https://example.com/receipts/grocery.jpg".to_string(),
+                mime_type: None,
+                account_id: Some(sample.account_id.clone()),
+                split: true,
+            }),
+            "export_to_sheet" => dry_run(ExportToSheetInput {
+                destination: Some(SheetDestination::GoogleSheets),
+                report: SheetReport::MonthlySummary,
+                filters: TransactionFilters {
+                    from: Some(format!("{}-01-01T00:00:00Z", Utc::now().year())),
+                    ..TransactionFilters::default()
+                },
+                target: None,
+                sheet: Some("Budget".to_string()),
+            }),
+            "export_ynab" => dry_run(ExportYnabInput {
+                budget_id: None,
+                group: None,
+            }),
+            "execute_batch" => dry_run(ExecuteBatchInput {
+                operations: vec![
+                    BatchOperation::UpsertAccount(UpsertAccountInput {
+                        name: "Travel Card".to_string(),
+                        r#type: AccountType::Offchain,
+                        currency: sample.currency.clone(),
+                        network: None,
+                        institution: Some("Example Bank".to_string()),
+                    }),
+                    BatchOperation::CreateTransactions(CreateTransactionsInput {
+                        transactions: vec![CreateTransactionInput {
+                            account_id: "$0".to_string(),
+                            ..transaction.clone()
+                        }],
+                    }),
+                    BatchOperation::Categorize(CategorizeInput {
+                        transaction_id: "$1.0".to_string(),
+                        category_id: sample.category_id.clone(),
+                    }),
+                ],
+            }),
+            "search_similar_transactions" => arguments(SearchSimilarInput {
+                query: "coffee".to_string(),
+                limit: Some(5),
+            }),
+            "search_transactions_text" => arguments(SearchTextInput {
+                query: "invoice 4812".to_string(),
+                limit: Some(10),
+            }),
+            "upsert_category" => dry_run(UpsertCategoryInput {
+                name: "Groceries".to_string(),
+                kind: Some(CategoryKind::Expense),
+                description: Some("Supermarkets and food stores".to_string()),
+            }),
+            "search_similar_categories" => arguments(SearchSimilarInput {
+                query: "supermarket".to_string(),
+                limit: Some(3),
+            }),
+            "list_accounts" => arguments(ListAccountsInput {
+                limit: Some(20),
+                ..Default::default()
+            }),
+            "list_transactions" => arguments(ListTransactionsInput {
+                filters: TransactionFilters {
+                    account_id: Some(sample.account_id.clone()),
+                    ..this_month
+                },
+                limit: Some(20),
+                offset: None,
+            }),
+            "upsert_account" => dry_run(UpsertAccountInput {
+                name: "Everyday Checking".to_string(),
+                r#type: AccountType::Offchain,
+                currency: sample.currency.clone(),
+                network: None,
+                institution: Some("Example Bank".to_string()),
+            }),
+            "set_session_defaults" => arguments(SessionDefaults {
+                account_id: Some(sample.account_id.clone()),
+                currency: Some(sample.currency.clone()),
+                timezone: Some("+00:00".to_string()),
+            }),
+            "delete_transaction" => dry_run(DeleteRecordInput {
+                id: sample.transaction_id.clone(),
+            }),
+            "delete_category" => dry_run(DeleteRecordInput {
+                id: sample.category_id.clone(),
+            }),
+            "delete_account" => dry_run(DeleteRecordInput {
+                id: sample.account_id.clone(),
+            }),
+            "purge_deleted" => dry_run(PurgeDeletedInput {
+                kind: Some(RecordKind::Transaction),
+                deleted_before: Some(month_start),
+            }),
+            "embedding_maintenance" => arguments(EmbeddingMaintenanceInput::default()),
+            "seed_demo_data" => dry_run(SeedDemoDataInput { months: Some(3) }),
+            "call_rpc" => dry_run(CallRpcInput {
+                function: self.rpc_allowlist.first().cloned().unwrap_or_default(),
+                params: Default::default(),
+            }),
+            "list_audit_events" => arguments(ListAuditEventsInput {
+                limit: Some(20),
+                ..Default::default()
+            }),
+            "aggregate_spending" => arguments(AggregateSpendingInput {
+                group_by: SpendingGroupBy::Category,
+                period: Some(SpendingPeriod::Month),
+                filters: TransactionFilters {
+                    direction: Some(TransactionDirection::Expense),
+                    ..this_month
+                },
+            }),
+            "summarize_period" => arguments(SummarizePeriodInput {
+                month: Some(now.format("%Y-%m").to_string()),
+            }),
+            "get_net_worth" => arguments(GetNetWorthInput::default()),
+            "convert_currency" => arguments(ConvertCurrencyInput {
+                amount: 100.0,
+                from: sample.currency.clone(),
+                to: Some("EUR".to_string()),
+                date: None,
+            }),
+            "get_asset_price" => arguments(GetAssetPriceInput {
+                asset: "BTC".to_string(),
+                vs_currency: Some(sample.currency.clone()),
+                date: None,
+                amount: Some(0.5),
+            }),
+            "export_ledger" => arguments(ExportLedgerInput {
+                format: LedgerFormat::Beancount,
+                filters: TransactionFilters {
+                    account_id: Some(sample.account_id.clone()),
+                    ..this_month
+                },
+            }),
+            _ => json!({}),
+        }
+    }
+}
+
+fn arguments(input: impl Serialize) -> Value {
+    serde_json::to_value(input).unwrap_or_else(|_| json!({}))
+}
+
+/// A destructive call's example, previewed rather than run.
+fn dry_run<T: Serialize>(input: T) -> Value {
+    arguments(DryRun {
+        input,
+        dry_run: Some(true),
+    })
+}
+
+/// What `tool` needs from the database or the deployment to work.
+fn prerequisites(tool: &str) -> &'static [&'static str] {
+    match tool {
+        "create_transaction" | "create_transactions" | "import_json" => &[
+            "table transactions",
+            "an account to book to (list_accounts or upsert_account)",
+            "embedding provider, for transactions with a description",
+        ],
+        "import_ofx" => &[
+            "table transactions",
+            "an account to book to (list_accounts or upsert_account)",
+            "embedding provider, for transactions with a description",
+        ],
+        "link_bank_account" => &[
+            "table bank_links (migration 0012_bank_links)",
+            "PLAID_CLIENT_ID, PLAID_SECRET and PLAID_TOKEN_KEY",
+            "a public token from Plaid Link, or an item's access token",
+        ],
+        "sync_bank_account" => &[
+            "table bank_links (migration 0012_bank_links)",
+            "PLAID_CLIENT_ID, PLAID_SECRET and PLAID_TOKEN_KEY",
+            "an account linked with link_bank_account",
+            "embedding provider, for transactions with a description",
+        ],
+        "link_openbanking_account" => &[
+            "GOCARDLESS_SECRET_ID and GOCARDLESS_SECRET_KEY",
+            "a requisition the user authorized at the bank",
+        ],
+        "sync_openbanking" => &[
+            "GOCARDLESS_SECRET_ID and GOCARDLESS_SECRET_KEY",
+            "an account linked with link_openbanking_account",
+            "embedding provider, for transactions with a description",
+        ],
+        "sync_onchain" => &[
+            "ETHERSCAN_API_KEY for Ethereum, BLOCKBOOK_URL for Bitcoin, SOLANA_RPC_URL for Solana",
+            "an onchain account (list_accounts or upsert_account)",
+            "embedding provider",
+        ],
+        "sync_exchange" => &[
+            "BINANCE_API_KEY and BINANCE_API_SECRET, or COINBASE_API_KEY and COINBASE_API_SECRET",
+            "an exchange account (list_accounts or upsert_account)",
+            "embedding provider",
+        ],
+        "parse_receipt" => &[
+            "RECEIPT_MODEL, a vision-capable model",
+            "an account to record the purchase in, if any (list_accounts)",
+            "embedding provider, to record the purchase",
+        ],
+        "export_to_sheet" => &[
+            "GOOGLE_SHEETS_CLIENT_ID, GOOGLE_SHEETS_CLIENT_SECRET and GOOGLE_SHEETS_REFRESH_TOKEN, or NOTION_TOKEN",
+            "a spreadsheet or Notion database, GOOGLE_SHEETS_SPREADSHEET_ID or NOTION_DATABASE_ID unless given as target",
+            "table transactions",
+            "rpc aggregate_spending (migration 0004_aggregate_spending), for monthly_summary",
+        ],
+        "import_bank_statement" => &[
+            "table transactions",
+            "an account to book to (list_accounts or upsert_account)",
+            "embedding provider, for transactions with a description",
+        ],
+        "import_gnucash" => &[
+            "tables accounts, categories and transactions",
+            "embedding provider",
+        ],
+        "import_ynab" => &[
+            "tables accounts, categories and transactions",
+            "YNAB_TOKEN, to read the budget through the API rather than an export",
+            "embedding provider",
+        ],
+        "export_ynab" => &[
+            "YNAB_TOKEN",
+            "a budget: budget_id, YNAB_BUDGET_ID or else the one used last",
+            "table categories",
+        ],
+        "import_qif" => &[
+            "table transactions",
+            "an account to book to (list_accounts or upsert_account)",
+            "rpc search_similar_categories (migration 0005_soft_delete)",
+            "embedding provider",
+        ],
+        "search_similar_transactions" => &[
+            "rpc search_similar_transactions (migration 0005_soft_delete)",
+            "embedding provider",
+        ],
+        "search_similar_categories" => &[
+            "rpc search_similar_categories (migration 0005_soft_delete)",
+            "embedding provider",
+        ],
+        "search_transactions_text" => {
+            &["rpc search_transactions_text (migration 0008_text_search)"]
+        }
+        "execute_batch" => &[
+            "rpc execute_batch (migration 0011_execute_batch)",
+            "embedding provider, for transactions with a description",
+        ],
+        "upsert_category" => &["table categories", "embedding provider"],
+        "upsert_account" | "list_accounts" => &["table accounts"],
+        "list_transactions" => &["table transactions"],
+        "delete_transaction" | "delete_category" | "delete_account" => {
+            &["column deleted_at (migration 0005_soft_delete)"]
+        }
+        "purge_deleted" => &["column deleted_at (migration 0005_soft_delete)"],
+        "embedding_maintenance" => &[
+            "rpc embedding_issues (migration 0009_embedding_maintenance)",
+            "embedding provider",
+        ],
+        "seed_demo_data" => &[
+            "tables accounts, categories and transactions",
+            "embedding provider",
+        ],
+        "call_rpc" => &["a Postgres function listed in RPC_ALLOWLIST"],
+        "list_audit_events" => {
+            &["table audit_log (migrations 0006_audit_log, 0010_audit_log_details)"]
+        }
+        "aggregate_spending" => &[
+            "rpc aggregate_spending (migration 0004_aggregate_spending)",
+            "FX_PROVIDER, for totals in BASE_CURRENCY",
+        ],
+        "summarize_period" => &[
+            "rpc aggregate_spending (migration 0004_aggregate_spending)",
+            "client sampling, for the narrative",
+            "FX_PROVIDER, for totals in BASE_CURRENCY",
+        ],
+        "get_net_worth" => &[
+            "rpc aggregate_spending (migration 0004_aggregate_spending)",
+            "FX_PROVIDER, for the total in BASE_CURRENCY",
+        ],
+        "convert_currency" => &["FX_PROVIDER, with FX_API_KEY for exchangerate_host"],
+        "get_asset_price" => &["PRICE_PROVIDER"],
+        "export_ledger" => &["table transactions", "table categories, for category names"],
+        "health_check" => &[
+            "rpc search_similar_transactions",
+            "rpc search_similar_categories",
+            "embedding provider",
+        ],
+        _ => &[],
+    }
+}
@@ -0,0 +1,142 @@
+//! Argument completion. Values are suggested by argument name, wherever the
+//! argument appears: the fixed values of the enum parameters, recent months,
+//! and live account and category names read from the database.
+
+use super::{ExaspoonDbServer, ToolError};
+use crate::models::{
+    AccountType, CategoryKind, ListAccountsInput, RecordKind, SpendingGroupBy, SpendingPeriod,
+    TransactionDirection,
+};
+use chrono::{Months, Utc};
+use rmcp::{
+    model::{ArgumentInfo, CompletionInfo},
+    ErrorData as McpError,
+};
+use tracing::{debug, error};
+
+/// Recent months suggested for a `month` argument, newest first.
+const COMPLETED_MONTHS: u32 = 12;
+
+impl ExaspoonDbServer {
+    /// Suggestions for `argument` given what the user has typed so far. Names
+    /// the server does not know get no suggestions.
+    pub(crate) async fn complete_argument(
+        &self,
+        argument: &ArgumentInfo,
+    ) -> Result<CompletionInfo, McpError> {
+        let typed = argument.value.trim();
+        let names = match argument.name.as_str() {
+            "direction" => {
+                let values = [
+                    TransactionDirection::Income,
+                    TransactionDirection::Expense,
+                    TransactionDirection::Transfer,
+                ];
+                return Ok(fixed(values.map(|value| value.as_ref()), typed));
+            }
+            "type" => {
+                let values = [
+                    AccountType::Onchain,
+                    AccountType::Offchain,
+                    AccountType::Exchange,
+                ];
+                return Ok(fixed(values.map(|value| value.as_ref()), typed));
+            }
+            // `kind` is a category kind on `upsert_category` and a record
+            // kind on the delete and maintenance tools.
+            "kind" => {
+                let categories = [
+                    CategoryKind::Income,
+                    CategoryKind::Expense,
+                    CategoryKind::Transfer,
+                ];
+                let values = categories
+                    .map(|value| value.as_ref())
+                    .into_iter()
+                    .chain(RecordKind::ALL.map(|value| value.as_ref()));
+                return Ok(fixed(values, typed));
+            }
+            "group_by" => {
+                let values = [
+                    SpendingGroupBy::Category,
+                    SpendingGroupBy::Account,
+                    SpendingGroupBy::Direction,
+                ];
+                return Ok(fixed(values.map(|value| value.as_ref()), typed));
+            }
+            "period" => {
+                let values = [
+                    SpendingPeriod::Day,
+                    SpendingPeriod::Week,
+                    SpendingPeriod::Month,
+                    SpendingPeriod::Year,
+                ];
+                return Ok(fixed(values.map(|value| value.as_ref()), typed));
+            }
+            "month" => {
+                let today = Utc::now().date_naive();
+                let months = (0..COMPLETED_MONTHS)
+                    .filter_map(|back| today.checked_sub_months(Months::new(back)))
+                    .map(|month| month.format("%Y-%m").to_string())
+                    .collect::<Vec<_>>();
+                return Ok(fixed(months.iter().map(String::as_str), typed));
+            }
+            "account" | "account_name" => self.account_names(typed).await?,
+            "category" | "category_name" => self
+                .supabase
+                .list_category_names(typed, CompletionInfo::MAX_VALUES as u32 + 1)
+                .await
+                .map_err(|err| {
+                    error!("Failed to complete category names: {}", err);
+                    ToolError::failed("complete category names", err)
+                })?,
+            other => {
+                debug!("No completions for argument {}", other);
+                Vec::new()
+            }
+        };
+        Ok(page(names))
+    }
+
+    async fn account_names(&self, typed: &str) -> Result<Vec<String>, McpError> {
+        let params = ListAccountsInput {
+            search: Some(typed.to_string()),
+            limit: Some(CompletionInfo::MAX_VALUES as u32 + 1),
+            ..ListAccountsInput::default()
+        };
+        let accounts = self.supabase.list_accounts(&params).await.map_err(|err| {
+            error!("Failed to complete account names: {}", err);
+            ToolError::failed("complete account names", err)
+        })?;
+        let mut names = accounts
+            .into_iter()
+            .map(|account| account.name)
+            .collect::<Vec<_>>();
+        // The same name can be used once per account type.
+        names.dedup();
+        Ok(names)
+    }
+}
+
+/// The `values` starting with what was typed, ignoring case.
+fn fixed<'a>(values: impl IntoIterator<Item = &'a str>, typed: &str) -> CompletionInfo {
+    let typed = typed.to_lowercase();
+    let values = values
+        .into_iter()
+        .filter(|value| value.to_lowercase().starts_with(&typed))
+        .map(str::to_string)
+        .collect();
+    page(values)
+}
+
+/// Caps `values` at the most one response may carry, flagging whether any
+/// were left out.
+fn page(mut values: Vec<String>) -> CompletionInfo {
+    let has_more = values.len() > CompletionInfo::MAX_VALUES;
+    values.truncate(CompletionInfo::MAX_VALUES);
+    CompletionInfo {
+        values,
+        total: None,
+        has_more: Some(has_more),
+    }
+}
@@ -0,0 +1,139 @@
+//! The server's side of two-phase confirmation: calls of tools annotated as
+//! destructive, see [`needs_confirmation`], without a token run as dry runs
+//! and return a token, and calls with one run once it is redeemed.
+
+use super::{input_hash, needs_confirmation, ExaspoonDbServer, ToolError};
+use crate::{
+    confirmation::{Refused, CONFIRMATION_TOKEN_FIELD},
+    i18n::Message,
+    models::Confirmation,
+};
+use rmcp::{
+    model::{CallToolRequestParam, CallToolResult, Content, JsonObject, Tool},
+    ErrorData as McpError,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+impl ExaspoonDbServer {
+    /// Takes the token out of `request`. Without one, turns the call into a
+    /// dry run and returns the fingerprint the preview's token is issued
+    /// for; with one, redeems it or fails.
+    pub(crate) fn check_confirmation(
+        &self,
+        request: &mut CallToolRequestParam,
+    ) -> Result<Option<String>, McpError> {
+        let Some(confirmations) = &self.confirmations else {
+            return Ok(None);
+        };
+        let destructive = self
+            .tool_router
+            .map
+            .get(request.name.as_ref())
+            .is_some_and(|route| needs_confirmation(&route.attr));
+        if self.dry_run || !destructive {
+            return Ok(None);
+        }
+        let arguments = request.arguments.get_or_insert_with(Map::new);
+        let token = arguments.remove(CONFIRMATION_TOKEN_FIELD);
+        let fingerprint = fingerprint(arguments);
+        match token {
+            None | Some(Value::Null) => {
+                info!("Previewing {} call until it is confirmed", request.name);
+                arguments.insert("dry_run".to_string(), Value::Bool(true));
+                Ok(Some(fingerprint))
+            }
+            Some(Value::String(token)) => {
+                confirmations
+                    .redeem(&token, &request.name, &fingerprint)
+                    .map_err(|refused| {
+                        warn!(
+                            "Refused {} confirmation: {}",
+                            request.name,
+                            refused.as_ref()
+                        );
+                        unconfirmed(refused)
+                    })?;
+                info!("Confirmed {} call", request.name);
+                Ok(None)
+            }
+            Some(_) => Err(unconfirmed(Refused::Unknown)),
+        }
+    }
+
+    /// Adds a token for `fingerprint` to the preview `result` of `tool`.
+    pub(crate) fn issue_confirmation(
+        &self,
+        tool: &str,
+        fingerprint: &str,
+        mut result: CallToolResult,
+    ) -> CallToolResult {
+        let Some(confirmations) = &self.confirmations else {
+            return result;
+        };
+        if result.is_error == Some(true) {
+            return result;
+        }
+        let confirmation = Confirmation {
+            token: confirmations.issue(tool, fingerprint),
+            expires_in_secs: confirmations.window().as_secs(),
+        };
+        let note = format!(
+            "\nTo go ahead, call {} again with the same arguments and {} {} within {}s.",
+            self.tool_names.exposed(tool),
+            CONFIRMATION_TOKEN_FIELD,
+            confirmation.token,
+            confirmation.expires_in_secs
+        );
+        if let Some(Value::Object(structured)) = &mut result.structured_content {
+            structured.insert("confirmation".to_string(), json!(confirmation));
+            let text = Value::Object(structured.clone()).to_string();
+            if let Some(json) = result.content.get_mut(1) {
+                *json = Content::text(text);
+            }
+        }
+        if let Some(rendering) = result.content.first_mut().and_then(|text| text.as_text()) {
+            let rendering = format!("{}{note}", rendering.text);
+            result.content[0] = Content::text(rendering);
+        }
+        result
+    }
+}
+
+/// `tool` with `confirmation_token` among its arguments.
+pub(crate) fn with_token_argument(mut tool: Tool) -> Tool {
+    let mut schema: JsonObject = (*tool.input_schema).clone();
+    if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+        properties.insert(
+            CONFIRMATION_TOKEN_FIELD.to_string(),
+            json!({
+                "type": "string",
+                "description": "Token from a preview of this exact call. Without one the \
+                    call only previews what it would do and returns a token.",
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// Hash of the arguments that matter to what a call does, whatever their
+/// order.
+fn fingerprint(arguments: &Map<String, Value>) -> String {
+    let arguments: BTreeMap<&String, &Value> = arguments
+        .iter()
+        .filter(|(key, _)| key.as_str() != "dry_run")
+        .collect();
+    input_hash(&arguments)
+}
+
+fn unconfirmed(refused: Refused) -> McpError {
+    ToolError::invalid(
+        Message::Unconfirmed(refused).text(),
+        CONFIRMATION_TOKEN_FIELD,
+    )
+    .with("refused", refused.as_ref())
+    .into()
+}
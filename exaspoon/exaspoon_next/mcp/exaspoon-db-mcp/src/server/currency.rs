@@ -0,0 +1,105 @@
+//! Totals of several currencies converted into the base currency for the
+//! reporting tools, at the rates of the FX provider and, for crypto assets,
+//! the prices of the price provider.
+
+use super::ExaspoonDbServer;
+use crate::fx::{self, FxClient, UnknownCurrency};
+use crate::models::{BaseTotals, SpendingBucket};
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::warn;
+
+impl ExaspoonDbServer {
+    /// `buckets` converted into the base currency and summed per key and
+    /// period, or `None` without an FX provider. A bucket with a period is
+    /// converted at the rate of the period's first day, one without at the
+    /// rate of `on`, or the latest. The rates of many days are read as one
+    /// time series. An asset the FX provider has no rates for, such as
+    /// `BTC`, is priced by the price provider when there is one; an ISO 4217
+    /// currency never is, so one the provider lacks is not taken for a token
+    /// of the same symbol. Currencies
+    /// without a rate are named in `unconverted` rather than failing the
+    /// report, as are those whose rate could not be fetched.
+    pub(crate) async fn in_base_currency(
+        &self,
+        buckets: &[SpendingBucket],
+        on: Option<NaiveDate>,
+    ) -> Option<BaseTotals> {
+        let fx = self.fx.as_deref()?;
+        let base = fx.base_currency().to_string();
+        let dates: Vec<_> = buckets
+            .iter()
+            .map(|bucket| {
+                bucket
+                    .period_start
+                    .as_deref()
+                    .and_then(|start| NaiveDate::parse_from_str(start.get(..10)?, "%Y-%m-%d").ok())
+                    .or(on)
+            })
+            .collect();
+        let days: BTreeSet<_> = dates.iter().flatten().collect();
+        if let (Some(&&start), Some(&&end)) = (days.first(), days.last()) {
+            if days.len() > 1 {
+                if let Err(err) = fx.load_series(&base, start, end).await {
+                    warn!("No {} rates from {} to {}: {:#}", base, start, end, err);
+                }
+            }
+        }
+        let mut totals: BTreeMap<(Option<String>, Option<String>), (f64, u64)> = BTreeMap::new();
+        let mut unconverted = Vec::new();
+        for (bucket, date) in buckets.iter().zip(dates) {
+            let rate = match self.base_rate(fx, &bucket.currency, &base, date).await {
+                Ok(rate) => rate,
+                Err(err) => {
+                    warn!("No {} rate for {}: {:#}", base, bucket.currency, err);
+                    if !unconverted.contains(&bucket.currency) {
+                        unconverted.push(bucket.currency.clone());
+                    }
+                    continue;
+                }
+            };
+            let total = totals
+                .entry((bucket.key.clone(), bucket.period_start.clone()))
+                .or_default();
+            total.0 += bucket.total * rate;
+            total.1 += bucket.count;
+        }
+        let buckets = totals
+            .into_iter()
+            .map(|((key, period_start), (total, count))| SpendingBucket {
+                key,
+                period_start,
+                currency: base.clone(),
+                total: (total * 100.0).round() / 100.0,
+                count,
+            })
+            .collect();
+        Some(BaseTotals {
+            currency: base,
+            buckets,
+            unconverted,
+        })
+    }
+
+    /// Units of `base` per unit of `currency` on `date`: the exchange rate,
+    /// or for an asset code without one the price of the asset.
+    async fn base_rate(
+        &self,
+        fx: &FxClient,
+        currency: &str,
+        base: &str,
+        date: Option<NaiveDate>,
+    ) -> Result<f64> {
+        match (fx.rate(currency, base, date).await, self.prices.as_deref()) {
+            (Ok(rate), _) => Ok(rate.rate),
+            (Err(err), Some(prices))
+                if err.downcast_ref::<UnknownCurrency>().is_some()
+                    && !fx::is_iso_currency(currency) =>
+            {
+                Ok(prices.price(currency, base, date).await?.price)
+            }
+            (Err(err), _) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Session defaults: the account and currency a call falls back to when it
+//! leaves them out, and the timezone of dates given without an offset. They
+//! are filled into a call's arguments before validation, so handlers see the
+//! same arguments as if the caller had sent them. `call_rpc` is left alone,
+//! as its params are a Postgres function's own.
+
+use super::ExaspoonDbServer;
+use crate::models::SessionDefaults;
+use crate::validation::{parse_timezone, Timezone};
+use chrono::{NaiveDate, NaiveDateTime, SecondsFormat};
+use serde_json::{Map, Value};
+use tracing::{debug, info};
+
+/// Arguments holding a date or time, at any depth.
+const DATE_FIELDS: &[&str] = &["occurred_at", "from", "to", "deleted_before"];
+
+impl ExaspoonDbServer {
+    /// The defaults in effect for this session.
+    pub(crate) fn session_defaults(&self) -> SessionDefaults {
+        self.session_defaults.lock().unwrap().clone()
+    }
+
+    /// Merges `update` into the session's defaults: fields it leaves out are
+    /// kept, and empty ones are cleared.
+    pub(crate) fn update_session_defaults(&self, update: SessionDefaults) -> SessionDefaults {
+        let mut defaults = self.session_defaults.lock().unwrap();
+        merge(&mut defaults.account_id, update.account_id);
+        merge(&mut defaults.currency, update.currency);
+        merge(&mut defaults.timezone, update.timezone);
+        info!("Session defaults now {:?}", *defaults);
+        defaults.clone()
+    }
+
+    /// Fills the session's defaults into the `arguments` of a `tool` call.
+    pub(crate) fn apply_session_defaults(&self, tool: &str, arguments: &mut Map<String, Value>) {
+        if tool == "call_rpc" {
+            return;
+        }
+        let defaults = self.session_defaults();
+        fill_defaults(&defaults, tool, arguments);
+        if let Some(timezone) = defaults.timezone.as_deref().and_then(parse_timezone) {
+            localize_dates(arguments, timezone);
+        }
+    }
+}
+
+/// Fills the account and currency defaults into the `arguments` of a `tool`
+/// call, or of each `execute_batch` operation named by its `op`.
+fn fill_defaults(defaults: &SessionDefaults, tool: &str, arguments: &mut Map<String, Value>) {
+    match tool {
+        "create_transaction" | "import_ofx" | "import_qif" | "import_bank_statement"
+        | "sync_bank_account" => fill_transaction(defaults, arguments),
+        "link_bank_account" | "link_openbanking_account" | "sync_openbanking" | "sync_onchain"
+        | "sync_exchange" => fill(arguments, "account_id", &defaults.account_id),
+        "create_transactions" | "import_json" => {
+            let key = if tool == "import_json" {
+                "records"
+            } else {
+                "transactions"
+            };
+            if let Some(Value::Array(transactions)) = arguments.get_mut(key) {
+                for transaction in transactions.iter_mut().filter_map(Value::as_object_mut) {
+                    fill_transaction(defaults, transaction);
+                }
+            }
+        }
+        "upsert_account" | "import_ynab" => fill(arguments, "currency", &defaults.currency),
+        "convert_currency" => fill(arguments, "from", &defaults.currency),
+        "execute_batch" => {
+            if let Some(Value::Array(operations)) = arguments.get_mut("operations") {
+                for operation in operations.iter_mut().filter_map(Value::as_object_mut) {
+                    let op = operation
+                        .get("op")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string();
+                    fill_defaults(defaults, &op, operation);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn merge(current: &mut Option<String>, update: Option<String>) {
+    if let Some(value) = update {
+        let value = value.trim();
+        *current = (!value.is_empty()).then(|| value.to_string());
+    }
+}
+
+fn fill_transaction(defaults: &SessionDefaults, transaction: &mut Map<String, Value>) {
+    fill(transaction, "account_id", &defaults.account_id);
+    fill(transaction, "currency", &defaults.currency);
+}
+
+/// Sets `field` to `default` when it is absent, null or blank.
+fn fill(arguments: &mut Map<String, Value>, field: &str, default: &Option<String>) {
+    let Some(default) = default else {
+        return;
+    };
+    let missing = match arguments.get(field) {
+        None | Some(Value::Null) => true,
+        Some(Value::String(value)) => value.trim().is_empty(),
+        Some(_) => false,
+    };
+    if missing {
+        debug!("Using session default {} for {}", default, field);
+        arguments.insert(field.to_string(), Value::from(default.as_str()));
+    }
+}
+
+/// Rewrites the [`DATE_FIELDS`] given without an offset as RFC 3339
+/// timestamps in `timezone`; a bare date means its midnight.
+fn localize_dates(arguments: &mut Map<String, Value>, timezone: Timezone) {
+    for (key, value) in arguments.iter_mut() {
+        match value {
+            Value::String(text) if DATE_FIELDS.contains(&key.as_str()) => {
+                if let Some(localized) = localize(text, timezone) {
+                    debug!("Read {} {} as {}", key, text, localized);
+                    *text = localized;
+                }
+            }
+            Value::Object(object) => localize_dates(object, timezone),
+            Value::Array(items) => {
+                for object in items.iter_mut().filter_map(Value::as_object_mut) {
+                    localize_dates(object, timezone);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn localize(text: &str, timezone: Timezone) -> Option<String> {
+    let text = text.trim();
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+    let local = timezone.localize(naive)?;
+    Some(local.to_rfc3339_opts(SecondsFormat::AutoSi, false))
+}
@@ -0,0 +1,202 @@
+//! Failed tool calls, sorted into a few kinds an agent can branch on. Each
+//! kind maps to a JSON-RPC error code, and its stable name is sent as
+//! `error_code` in the error data, next to the details.
+
+use super::{CONFLICT, RATE_LIMITED, TIMED_OUT, UPSTREAM_UNAVAILABLE};
+use crate::{
+    circuit::CircuitOpen,
+    correlation::CORRELATION_ID_FIELD,
+    i18n::Message,
+};
+#[cfg(feature = "supabase")]
+use crate::postgrest::{PostgrestError, PostgrestErrorKind};
+use rmcp::{model::ErrorCode, ErrorData as McpError};
+use serde_json::{json, Map, Value};
+
+/// Key of the error data holding the [`ErrorKind`].
+pub const ERROR_CODE_FIELD: &str = "error_code";
+
+/// Why a tool call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A row the call refers to does not exist.
+    NotFound,
+    /// The write clashes with an existing row.
+    Conflict,
+    /// An argument is missing, malformed or refused.
+    Validation,
+    /// The database or embedding provider cannot be reached right now;
+    /// retrying later may succeed.
+    UpstreamUnavailable,
+    /// The session is over its rate limit.
+    RateLimited,
+    /// The call ran past its tool's time limit.
+    TimedOut,
+    /// Anything else.
+    Internal,
+}
+
+impl ErrorKind {
+    pub const ALL: [Self; 7] = [
+        Self::NotFound,
+        Self::Conflict,
+        Self::Validation,
+        Self::UpstreamUnavailable,
+        Self::RateLimited,
+        Self::TimedOut,
+        Self::Internal,
+    ];
+
+    /// The stable name sent as `error_code`.
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::Conflict => "conflict",
+            Self::Validation => "validation",
+            Self::UpstreamUnavailable => "upstream_unavailable",
+            Self::RateLimited => "rate_limited",
+            Self::TimedOut => "timed_out",
+            Self::Internal => "internal",
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound => ErrorCode::RESOURCE_NOT_FOUND,
+            Self::Conflict => CONFLICT,
+            Self::Validation => ErrorCode::INVALID_PARAMS,
+            Self::UpstreamUnavailable => UPSTREAM_UNAVAILABLE,
+            Self::RateLimited => RATE_LIMITED,
+            Self::TimedOut => TIMED_OUT,
+            Self::Internal => ErrorCode::INTERNAL_ERROR,
+        }
+    }
+
+    /// The kind an error with `code` was raised as, for errors that did not
+    /// start out as a [`ToolError`], such as rmcp's own.
+    pub fn of_code(code: ErrorCode) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.code() == code)
+            .unwrap_or(Self::Internal)
+    }
+}
+
+/// A failed tool call, converted into the MCP error the client receives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub data: Map<String, Value>,
+}
+
+impl ToolError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            data: Map::new(),
+        }
+    }
+
+    /// A [`ErrorKind::Validation`] error naming the offending `field`.
+    pub fn invalid(message: impl Into<String>, field: &str) -> Self {
+        Self::new(ErrorKind::Validation, message).with("field", field)
+    }
+
+    /// Adds `key` to the error data.
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.data.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// A failure of `action`, e.g. `insert transaction`, sorted by what went
+    /// wrong: PostgREST constraint violations become conflicts or validation
+    /// errors, open circuits, unreachable hosts and gateway errors become
+    /// [`ErrorKind::UpstreamUnavailable`], and the rest is internal.
+    pub fn failed(action: &str, err: anyhow::Error) -> Self {
+        let failed = Message::Failed { action }.text();
+        let details = json!(err.to_string());
+        #[cfg(feature = "supabase")]
+        if let Some(rejected) = err.downcast_ref::<PostgrestError>() {
+            let kind = match rejected.kind() {
+                PostgrestErrorKind::Conflict => ErrorKind::Conflict,
+                PostgrestErrorKind::InvalidInput => ErrorKind::Validation,
+                PostgrestErrorKind::Other if rejected.status.is_server_error() => {
+                    ErrorKind::UpstreamUnavailable
+                }
+                PostgrestErrorKind::Other => ErrorKind::Internal,
+            };
+            // Constraint messages help the caller fix the call; other
+            // messages are only for the details.
+            let message = match kind {
+                ErrorKind::Conflict | ErrorKind::Validation => {
+                    format!("{failed}: {}", rejected.message)
+                }
+                _ => failed,
+            };
+            return Self::new(kind, message)
+                .with("details", details)
+                .with("postgrest", json!(rejected));
+        }
+        let error = Self::new(ErrorKind::Internal, failed).with("details", details);
+        if let Some(open) = cause::<CircuitOpen>(&err) {
+            let retry_after_ms = open.retry_in.as_millis() as u64;
+            return Self {
+                kind: ErrorKind::UpstreamUnavailable,
+                ..error
+            }
+            .with("dependency", open.dependency.as_str())
+            .with("retry_after_ms", retry_after_ms);
+        }
+        let unreachable = cause::<reqwest::Error>(&err)
+            .is_some_and(|err| err.is_connect() || err.is_timeout());
+        if unreachable {
+            return Self {
+                kind: ErrorKind::UpstreamUnavailable,
+                ..error
+            };
+        }
+        error
+    }
+}
+
+impl From<ToolError> for McpError {
+    fn from(error: ToolError) -> Self {
+        let mut data = error.data;
+        data.insert(ERROR_CODE_FIELD.to_string(), json!(error.kind.as_ref()));
+        McpError::new(error.kind.code(), error.message, Some(Value::Object(data)))
+    }
+}
+
+/// Adds the `error_code` of `err`'s JSON-RPC code to errors raised without
+/// one, so every failed call carries it.
+pub fn with_error_code(mut err: McpError) -> McpError {
+    let kind = ErrorKind::of_code(err.code);
+    match &mut err.data {
+        Some(Value::Object(data)) => {
+            data.entry(ERROR_CODE_FIELD)
+                .or_insert_with(|| json!(kind.as_ref()));
+        }
+        Some(_) => {}
+        None => err.data = Some(json!({ ERROR_CODE_FIELD: kind.as_ref() })),
+    }
+    err
+}
+
+/// Adds the id of the failed call to the error data, so the client can quote
+/// it when reporting the failure.
+pub fn with_correlation_id(mut err: McpError, correlation_id: &str) -> McpError {
+    match &mut err.data {
+        Some(Value::Object(data)) => {
+            data.insert(CORRELATION_ID_FIELD.to_string(), json!(correlation_id));
+        }
+        Some(_) => {}
+        None => err.data = Some(json!({ CORRELATION_ID_FIELD: correlation_id })),
+    }
+    err
+}
+
+fn cause<T: std::error::Error + 'static>(err: &anyhow::Error) -> Option<&T> {
+    err.chain().find_map(|cause| cause.downcast_ref::<T>())
+}
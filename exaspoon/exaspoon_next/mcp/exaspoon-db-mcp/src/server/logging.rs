@@ -0,0 +1,64 @@
+//! Notable server events forwarded to the client as MCP log notifications,
+//! so they show up in the client and not only on the server's stderr.
+
+use super::ExaspoonDbServer;
+use rmcp::{
+    model::{LoggingLevel, LoggingMessageNotificationParam},
+    Peer, RoleServer,
+};
+use serde_json::Value;
+use tracing::debug;
+
+/// Least severe level sent until the client picks one with
+/// `logging/setLevel`.
+pub const DEFAULT_CLIENT_LOG_LEVEL: LoggingLevel = LoggingLevel::Info;
+
+/// `logger` on every notification this server sends.
+const LOGGER: &str = "exaspoon-db-mcp";
+
+/// The connected client and the least severe level it wants to hear about.
+pub(crate) struct ClientLog {
+    pub(crate) peer: Option<Peer<RoleServer>>,
+    pub(crate) level: LoggingLevel,
+}
+
+impl Default for ClientLog {
+    fn default() -> Self {
+        Self {
+            peer: None,
+            level: DEFAULT_CLIENT_LOG_LEVEL,
+        }
+    }
+}
+
+impl ClientLog {
+    /// Whether an event at `level` should reach the client.
+    pub(crate) fn wants(&self, level: LoggingLevel) -> bool {
+        level as u8 >= self.level as u8
+    }
+}
+
+impl ExaspoonDbServer {
+    /// Sends `message` to the client as a log notification, with `fields`
+    /// merged into its data, when `level` is at or above the client's level.
+    /// Callers still log the event through `tracing`; this only mirrors it.
+    pub async fn log_to_client(&self, level: LoggingLevel, message: &str, fields: Value) {
+        let peer = {
+            let log = self.client_log.lock().unwrap();
+            match &log.peer {
+                Some(peer) if log.wants(level) => peer.clone(),
+                _ => return,
+            }
+        };
+        let mut data = fields;
+        data["message"] = Value::from(message);
+        let notification = LoggingMessageNotificationParam {
+            level,
+            logger: Some(LOGGER.to_string()),
+            data,
+        };
+        if let Err(err) = peer.notify_logging_message(notification).await {
+            debug!("Failed to send log notification: {}", err);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Required `create_transaction` fields the caller left out, asked of the
+//! user through MCP elicitation instead of failing the call outright.
+
+use super::{ExaspoonDbServer, ToolError};
+use crate::{
+    elicitation,
+    i18n::Message,
+    models::{Account, CreateTransactionInput, ListAccountsInput},
+};
+use rmcp::{
+    model::{ElicitationSchema, EnumSchema, PrimitiveSchema},
+    ErrorData as McpError,
+};
+use serde_json::Value;
+use tracing::{error, info};
+
+/// Most accounts offered to pick from when the account is missing.
+const MAX_OFFERED_ACCOUNTS: u32 = 50;
+
+impl ExaspoonDbServer {
+    /// Fills in a missing `account_id` or `currency` by asking the user. Fails
+    /// with invalid_params naming the first field still missing when the
+    /// client cannot elicit or the user declines.
+    pub(crate) async fn fill_missing_fields(
+        &self,
+        mut input: CreateTransactionInput,
+    ) -> Result<CreateTransactionInput, McpError> {
+        let missing = missing_fields(&input);
+        if missing.is_empty() {
+            return Ok(input);
+        }
+
+        let mut schema = ElicitationSchema::builder();
+        if missing.contains(&"account_id") {
+            let accounts = self.offered_accounts().await?;
+            schema = if accounts.is_empty() {
+                schema.required_string_with("account_id", |s| s.title("Account ID"))
+            } else {
+                schema.required_property("account_id", account_choice(&accounts))
+            };
+        }
+        if missing.contains(&"currency") {
+            schema = schema.required_string_with("currency", |s| {
+                s.title("Currency")
+                    .description("Currency or asset code, e.g. USD or USDT")
+                    .length(2, 44)
+            });
+        }
+        let schema = schema
+            .build()
+            .map_err(|err| ToolError::failed("build elicitation schema", anyhow::anyhow!(err)))?;
+        let amount = input.amount.to_string();
+        let message = Message::WhichFields {
+            fields: &missing,
+            direction: input.direction.as_ref(),
+            amount: &amount,
+            occurred_at: &input.occurred_at,
+        }
+        .text();
+
+        let answer = elicitation::ask(message, schema).await.unwrap_or(Value::Null);
+        if input.account_id.trim().is_empty() {
+            input.account_id = answered(&answer, "account_id");
+        }
+        if input.currency.trim().is_empty() {
+            input.currency = answered(&answer, "currency").to_uppercase();
+        }
+        if let Some(field) = missing_fields(&input).first() {
+            info!("Transaction still missing {}", field);
+            return Err(missing_field(field));
+        }
+        info!("User supplied missing {}", missing.join(" and "));
+        Ok(input)
+    }
+
+    async fn offered_accounts(&self) -> Result<Vec<Account>, McpError> {
+        let params = ListAccountsInput {
+            limit: Some(MAX_OFFERED_ACCOUNTS),
+            ..ListAccountsInput::default()
+        };
+        self.supabase.list_accounts(&params).await.map_err(|err| {
+            error!("Failed to list accounts to choose from: {}", err);
+            ToolError::failed("list accounts", err).into()
+        })
+    }
+}
+
+/// Required fields left empty on `input`, in the order they are asked for.
+pub(crate) fn missing_fields(input: &CreateTransactionInput) -> Vec<&'static str> {
+    let mut missing = Vec::new();
+    if input.account_id.trim().is_empty() {
+        missing.push("account_id");
+    }
+    if input.currency.trim().is_empty() {
+        missing.push("currency");
+    }
+    missing
+}
+
+/// The invalid_params error for a required `field` left empty.
+pub(crate) fn missing_field(field: &str) -> McpError {
+    ToolError::invalid(Message::Required { field }.text(), field).into()
+}
+
+/// A pick list of `accounts`, labelled with name, type and currency.
+fn account_choice(accounts: &[Account]) -> PrimitiveSchema {
+    let ids = accounts.iter().map(|account| account.id.clone()).collect();
+    let labels = accounts
+        .iter()
+        .map(|account| format!("{} ({}, {})", account.name, account.r#type, account.currency))
+        .collect();
+    PrimitiveSchema::Enum(EnumSchema::new(ids).enum_names(labels).title("Account"))
+}
+
+fn answered(answer: &Value, field: &str) -> String {
+    answer[field].as_str().unwrap_or_default().trim().to_string()
+}
@@ -0,0 +1,401 @@
+//! MCP prompts for common bookkeeping workflows. Each one fetches the data it
+//! needs through the [`Database`](crate::supabase::Database) trait and hands
+//! it to the model as context, so the model starts from the user's own
+//! numbers instead of having to call tools first.
+
+use super::{ExaspoonDbServer, ToolError};
+use crate::models::{
+    AggregateSpendingInput, CategorizeTransactionPromptArgs, FindUnusualSpendingPromptArgs,
+    ListTransactionsInput, MonthlyBudgetReviewPromptArgs, SpendingBucket, SpendingGroupBy,
+    SpendingPeriod, Transaction, TransactionDirection, TransactionFilters,
+};
+use crate::i18n;
+use chrono::{DateTime, Datelike, Months, NaiveDate, SecondsFormat, Utc};
+use rmcp::{
+    handler::server::wrapper::Parameters,
+    model::{GetPromptResult, PromptMessage, PromptMessageRole},
+    prompt, prompt_router, ErrorData as McpError,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+/// Similar categories and past transactions shown when categorizing a line.
+const CATEGORIZE_MATCHES: u32 = 5;
+/// Days `find_unusual_spending` checks unless asked otherwise.
+pub const DEFAULT_UNUSUAL_SPENDING_DAYS: u32 = 30;
+/// Longest window `find_unusual_spending` checks.
+pub const MAX_UNUSUAL_SPENDING_DAYS: u32 = 365;
+/// Months before the checked window that set what spending is typical.
+const BASELINE_MONTHS: u32 = 3;
+/// Most transactions read into a prompt.
+const PROMPT_TRANSACTIONS: u32 = 100;
+/// Largest expenses listed in a budget review.
+const LARGEST_EXPENSES: u32 = 10;
+
+#[prompt_router(vis = "pub(crate)")]
+impl ExaspoonDbServer {
+    #[prompt(
+        name = "categorize_transaction",
+        description = "Categorize a bank statement line, given the closest existing categories and similar past transactions."
+    )]
+    #[instrument(skip(self))]
+    pub async fn categorize_transaction_prompt(
+        &self,
+        Parameters(args): Parameters<CategorizeTransactionPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let start_time = Instant::now();
+        let line = args.line.trim();
+        if line.is_empty() {
+            return Err(ToolError::invalid("line must not be empty", "line").into());
+        }
+
+        let embedding = self.embedder.embed_query(line).await.map_err(|err| {
+            error!("Failed to embed statement line: {}", err);
+            ToolError::failed("embed statement line", err)
+        })?;
+        let (categories, transactions) = tokio::join!(
+            self.supabase
+                .search_similar_categories(embedding.clone(), Some(CATEGORIZE_MATCHES)),
+            self.supabase
+                .search_similar_transactions(embedding, Some(CATEGORIZE_MATCHES)),
+        );
+        let categories = categories.map_err(|err| {
+            error!("Failed to search similar categories: {}", err);
+            ToolError::failed("search similar categories", err)
+        })?;
+        let transactions = transactions.map_err(|err| {
+            error!("Failed to search similar transactions: {}", err);
+            ToolError::failed("search similar transactions", err)
+        })?;
+
+        let mut text = format!("Categorize this bank statement line:\n\n    {line}\n\n");
+        text.push_str("Closest existing categories:\n");
+        for candidate in &categories {
+            let category = &candidate.category;
+            let _ = write!(
+                text,
+                "- {} ({}, id {})",
+                category.name,
+                category.kind.as_ref(),
+                category.id
+            );
+            if let Some(description) = &category.description {
+                let _ = write!(text, ": {description}");
+            }
+            text.push('\n');
+        }
+        if categories.is_empty() {
+            text.push_str("- none yet\n");
+        }
+        text.push_str("\nSimilar past transactions:\n");
+        for candidate in &transactions {
+            text.push_str(&transaction_line(&candidate.transaction));
+        }
+        if transactions.is_empty() {
+            text.push_str("- none yet\n");
+        }
+        text.push_str(
+            "\nPick the category that fits best and explain the choice in one sentence. \
+             If none fits, propose a new category name and kind (income, expense or \
+             transfer) to create with upsert_category.",
+        );
+
+        info!(
+            "Built categorize_transaction prompt in {:?}",
+            start_time.elapsed()
+        );
+        Ok(GetPromptResult {
+            description: Some(format!("Categorize {line:?}")),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+
+    #[prompt(
+        name = "monthly_budget_review",
+        description = "Review a month's income and spending per category against the previous month. `month` is YYYY-MM, the current month by default."
+    )]
+    #[instrument(skip(self))]
+    pub async fn monthly_budget_review_prompt(
+        &self,
+        Parameters(args): Parameters<MonthlyBudgetReviewPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let start_time = Instant::now();
+        let month = parse_month(args.month.as_deref())?;
+        let next = month + Months::new(1);
+        let previous = month - Months::new(1);
+        let this_month = month_filters(month, next);
+
+        let (directions, categories, previous_categories, largest) = tokio::try_join!(
+            self.aggregate(SpendingGroupBy::Direction, None, this_month.clone()),
+            self.aggregate(
+                SpendingGroupBy::Category,
+                None,
+                expenses(this_month.clone())
+            ),
+            self.aggregate(
+                SpendingGroupBy::Category,
+                None,
+                expenses(month_filters(previous, month)),
+            ),
+            self.largest_expenses(this_month),
+        )?;
+
+        let label = month.format("%Y-%m");
+        let mut text = format!("Review my budget for {label}.\n\nTotals by direction:\n");
+        for bucket in &directions {
+            text.push_str(&bucket_line(bucket));
+        }
+        if directions.is_empty() {
+            text.push_str("- no transactions this month\n");
+        }
+        let previous_totals = previous_categories
+            .iter()
+            .map(|bucket| ((bucket.key.clone(), bucket.currency.clone()), bucket.total))
+            .collect::<BTreeMap<_, _>>();
+        let _ = writeln!(
+            text,
+            "\nExpenses by category id, with {} for comparison:",
+            previous.format("%Y-%m")
+        );
+        for bucket in &categories {
+            let before = previous_totals
+                .get(&(bucket.key.clone(), bucket.currency.clone()))
+                .copied()
+                .unwrap_or_default();
+            let _ = writeln!(
+                text,
+                "- {}: {} in {} transactions (previous month: {})",
+                bucket.key.as_deref().unwrap_or("uncategorized"),
+                i18n::format_amount(bucket.total, &bucket.currency),
+                bucket.count,
+                i18n::format_amount(before, &bucket.currency)
+            );
+        }
+        if categories.is_empty() {
+            text.push_str("- no expenses this month\n");
+        }
+        text.push_str("\nLargest expenses:\n");
+        for transaction in &largest {
+            text.push_str(&transaction_line(transaction));
+        }
+        text.push_str(
+            "\nSummarize where the money went, call out categories that grew notably \
+             compared with the previous month, and suggest one or two concrete ways to \
+             spend less next month.",
+        );
+
+        info!(
+            "Built monthly_budget_review prompt in {:?}",
+            start_time.elapsed()
+        );
+        Ok(GetPromptResult {
+            description: Some(format!("Budget review for {label}")),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+
+    #[prompt(
+        name = "find_unusual_spending",
+        description = "Look for unusual expenses in the last `days` days (30 by default) against the typical monthly spending per category."
+    )]
+    #[instrument(skip(self))]
+    pub async fn find_unusual_spending_prompt(
+        &self,
+        Parameters(args): Parameters<FindUnusualSpendingPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let start_time = Instant::now();
+        let days = match args.days.as_deref().map(str::trim) {
+            Some(days) => days
+                .parse::<u32>()
+                .ok()
+                .filter(|days| (1..=MAX_UNUSUAL_SPENDING_DAYS).contains(days))
+                .ok_or_else(|| {
+                    ToolError::invalid(
+                        format!(
+                            "days must be a whole number between 1 and {MAX_UNUSUAL_SPENDING_DAYS}"
+                        ),
+                        "days",
+                    )
+                })?,
+            None => DEFAULT_UNUSUAL_SPENDING_DAYS,
+        };
+        let window_start = Utc::now() - chrono::Duration::days(i64::from(days));
+        let baseline_start = window_start - Months::new(BASELINE_MONTHS);
+
+        let (baseline, transactions) = tokio::try_join!(
+            self.aggregate(
+                SpendingGroupBy::Category,
+                Some(SpendingPeriod::Month),
+                expenses(TransactionFilters {
+                    from: Some(timestamp(baseline_start)),
+                    to: Some(timestamp(window_start)),
+                    ..TransactionFilters::default()
+                }),
+            ),
+            self.recent_transactions(expenses(TransactionFilters {
+                from: Some(timestamp(window_start)),
+                ..TransactionFilters::default()
+            })),
+        )?;
+
+        let mut typical = BTreeMap::<(Option<String>, String), f64>::new();
+        for bucket in baseline {
+            *typical.entry((bucket.key, bucket.currency)).or_default() += bucket.total;
+        }
+        let mut text = format!(
+            "Find unusual spending in the last {days} days.\n\n\
+             Typical monthly expenses per category id over the {BASELINE_MONTHS} months before:\n"
+        );
+        for ((key, currency), total) in &typical {
+            let _ = writeln!(
+                text,
+                "- {}: {} per month",
+                key.as_deref().unwrap_or("uncategorized"),
+                i18n::format_amount(total / f64::from(BASELINE_MONTHS), currency)
+            );
+        }
+        if typical.is_empty() {
+            text.push_str("- no earlier expenses to compare with\n");
+        }
+        let _ = writeln!(text, "\nExpenses in the last {days} days, newest first:");
+        for transaction in &transactions {
+            text.push_str(&transaction_line(transaction));
+        }
+        if transactions.is_empty() {
+            text.push_str("- none\n");
+        }
+        text.push_str(
+            "\nFlag expenses that look unusual: amounts well above what is typical for their \
+             category, merchants that have not appeared before, possible duplicate charges, \
+             or categories already past their usual monthly total. Explain each flag in one \
+             line, and say so plainly if nothing stands out.",
+        );
+
+        info!(
+            "Built find_unusual_spending prompt in {:?}",
+            start_time.elapsed()
+        );
+        Ok(GetPromptResult {
+            description: Some(format!("Unusual spending in the last {days} days")),
+            messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+        })
+    }
+}
+
+impl ExaspoonDbServer {
+    pub(super) async fn aggregate(
+        &self,
+        group_by: SpendingGroupBy,
+        period: Option<SpendingPeriod>,
+        filters: TransactionFilters,
+    ) -> Result<Vec<SpendingBucket>, McpError> {
+        let input = AggregateSpendingInput {
+            group_by,
+            period,
+            filters,
+        };
+        self.supabase
+            .aggregate_spending(&input)
+            .await
+            .map_err(|err| {
+                error!("Failed to aggregate spending: {}", err);
+                ToolError::failed("aggregate spending", err).into()
+            })
+    }
+
+    /// The largest expenses matching `filters`, largest first.
+    pub(super) async fn largest_expenses(
+        &self,
+        filters: TransactionFilters,
+    ) -> Result<Vec<Transaction>, McpError> {
+        self.supabase
+            .largest_transactions(&expenses(filters), LARGEST_EXPENSES)
+            .await
+            .map_err(|err| {
+                error!("Failed to list the largest expenses: {}", err);
+                ToolError::failed("list largest expenses", err).into()
+            })
+    }
+
+    pub(super) async fn recent_transactions(
+        &self,
+        filters: TransactionFilters,
+    ) -> Result<Vec<Transaction>, McpError> {
+        let input = ListTransactionsInput {
+            filters,
+            limit: Some(PROMPT_TRANSACTIONS),
+            offset: None,
+        };
+        self.supabase
+            .list_transactions(&input)
+            .await
+            .map_err(|err| {
+                error!("Failed to list transactions: {}", err);
+                ToolError::failed("list transactions", err).into()
+            })
+    }
+}
+
+/// The first day of `month`, given as `YYYY-MM`, or of the current month.
+pub(super) fn parse_month(month: Option<&str>) -> Result<NaiveDate, McpError> {
+    match month.map(str::trim) {
+        Some(month) => {
+            NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").map_err(|_| {
+                ToolError::invalid("month must look like YYYY-MM", "month").into()
+            })
+        }
+        None => Ok(Utc::now().date_naive().with_day(1).unwrap_or_default()),
+    }
+}
+
+
+/// Filters for the transactions on or after `start` and before `end`.
+pub(super) fn month_filters(start: NaiveDate, end: NaiveDate) -> TransactionFilters {
+    TransactionFilters {
+        from: Some(format!("{}T00:00:00Z", start.format("%Y-%m-%d"))),
+        to: Some(format!("{}T00:00:00Z", end.format("%Y-%m-%d"))),
+        ..TransactionFilters::default()
+    }
+}
+
+pub(super) fn expenses(filters: TransactionFilters) -> TransactionFilters {
+    TransactionFilters {
+        direction: Some(TransactionDirection::Expense),
+        ..filters
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub(super) fn bucket_line(bucket: &SpendingBucket) -> String {
+    format!(
+        "- {}: {} in {} transactions\n",
+        bucket.key.as_deref().unwrap_or("unknown"),
+        i18n::format_amount(bucket.total, &bucket.currency),
+        bucket.count
+    )
+}
+
+pub(super) fn transaction_line(transaction: &Transaction) -> String {
+    format!(
+        "- {} {} {}: {} ({})\n",
+        transaction
+            .occurred_at
+            .get(..10)
+            .unwrap_or(&transaction.occurred_at),
+        transaction.direction.as_ref(),
+        i18n::format_amount(transaction.amount, &transaction.currency),
+        transaction
+            .description
+            .as_deref()
+            .unwrap_or("no description"),
+        transaction
+            .category_id
+            .as_deref()
+            .map_or("uncategorized".to_string(), |id| format!("category {id}"))
+    )
+}
@@ -0,0 +1,45 @@
+//! Protocol revision negotiation. The server speaks the newest revision it
+//! knows and leaves out result fields a client on an older one would not
+//! expect.
+
+use rmcp::model::{CallToolResult, ProtocolVersion, Tool};
+use rmcp::{Peer, RoleServer};
+
+/// The revision offered to every client. rmcp answers clients that ask for
+/// an older one with theirs.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::V_2025_06_18;
+
+/// The revision agreed with the client behind `peer`: the older of the one it
+/// asked for and [`PROTOCOL_VERSION`].
+pub(crate) fn negotiated(peer: &Peer<RoleServer>) -> ProtocolVersion {
+    match peer.peer_info() {
+        Some(client) if client.protocol_version < PROTOCOL_VERSION => {
+            client.protocol_version.clone()
+        }
+        _ => PROTOCOL_VERSION,
+    }
+}
+
+/// `tools` as described to a client on `version`: output schemas and titles
+/// arrived in 2025-06-18, annotations in 2025-03-26.
+pub(crate) fn tools_for(version: &ProtocolVersion, mut tools: Vec<Tool>) -> Vec<Tool> {
+    for tool in &mut tools {
+        if *version < ProtocolVersion::V_2025_06_18 {
+            tool.output_schema = None;
+            tool.title = None;
+        }
+        if *version < ProtocolVersion::V_2025_03_26 {
+            tool.annotations = None;
+        }
+    }
+    tools
+}
+
+/// `result` as returned to a client on `version`. Structured content arrived
+/// in 2025-06-18; the same JSON is always in the text content too.
+pub(crate) fn result_for(version: &ProtocolVersion, mut result: CallToolResult) -> CallToolResult {
+    if *version < ProtocolVersion::V_2025_06_18 {
+        result.structured_content = None;
+    }
+    result
+}
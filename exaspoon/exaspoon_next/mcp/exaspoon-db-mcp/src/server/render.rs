@@ -0,0 +1,1012 @@
+//! Short text renderings of tool results, sent as the first text content
+//! ahead of the JSON so clients that only display text still show
+//! something readable, e.g. `Created transaction txn-123: -$42.00 at
+//! 2024-01-02`.
+
+use super::prompts::bucket_line;
+use crate::i18n;
+use crate::metrics::OperationMetrics;
+use crate::models::{
+    Account, AccountOutput, AccountsOutput, AssetPriceOutput, AuditEventsOutput, BaseTotals,
+    BatchOperation, BatchStepResult, CallRpcOutput, CapabilitiesOutput, Category,
+    CategoryMatchesOutput, CategoryOutput, ConvertCurrencyOutput, CreateTransactionInput,
+    DeletedOutput, EmbeddingMaintenanceAction, EmbeddingMaintenanceOutput, ExecuteBatchOutput,
+    ExportLedgerOutput, ExportToSheetOutput, ExportYnabOutput, GnuCashAccountMapping,
+    GnuCashImportedAs, HealthCheckOutput, HealthStatus, ImportBankStatementOutput,
+    ImportGnuCashOutput, ImportJsonOutput, ImportOfxOutput, ImportQifOutput, ImportYnabOutput,
+    LinkBankAccountOutput, LinkOpenBankingAccountOutput, MatchedBy, NetWorthOutput,
+    ParseReceiptOutput, PeriodSummaryOutput, PingOutput, PurgeOutput, QifCategoryMapping,
+    SeedDemoDataOutput, ServerMetricsOutput, SessionDefaultsOutput, SheetDestination, SheetReport,
+    SkipReason, SpendingBucket, SpendingOutput, SyncBankAccountOutput, SyncExchangeOutput,
+    SyncOnchainOutput, SyncOpenBankingOutput, TableMaintenance, TextMatchesOutput, Transaction,
+    TransactionDirection, TransactionMatchesOutput, TransactionOutput, TransactionPageOutput,
+    TransactionsOutput, Written, YnabCategoryExport, YnabCategoryStatus, YnabMapping, YnabSource,
+};
+use std::fmt::Write;
+
+/// Most items listed before the rest are only counted.
+const MAX_LINES: usize = 20;
+
+/// A tool result as text.
+pub(crate) trait Render {
+    /// `dry_run` says nothing was written, only planned.
+    fn render(&self, dry_run: bool) -> String;
+}
+
+fn verb(dry_run: bool, done: &str, planned: &str) -> String {
+    if dry_run {
+        format!("Would {planned}")
+    } else {
+        done.to_string()
+    }
+}
+
+/// `amount` signed by direction, expenses being negative, in the call's
+/// locale.
+fn money(amount: f64, currency: &str, direction: TransactionDirection) -> String {
+    let signed = match direction {
+        TransactionDirection::Expense => -amount,
+        _ => amount,
+    };
+    i18n::format_amount(signed, currency)
+}
+
+fn date(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
+}
+
+fn transaction(transaction: &Transaction) -> String {
+    let mut line = format!(
+        "{}: {} at {}",
+        transaction.id,
+        money(
+            transaction.amount,
+            &transaction.currency,
+            transaction.direction
+        ),
+        date(&transaction.occurred_at)
+    );
+    if let Some(description) = &transaction.description {
+        let _ = write!(line, " ({description})");
+    }
+    line
+}
+
+fn planned_transaction(input: &CreateTransactionInput) -> String {
+    format!(
+        "{} at {}",
+        money(input.amount, &input.currency, input.direction),
+        date(&input.occurred_at)
+    )
+}
+
+fn written_transaction(written: &Written<Transaction, CreateTransactionInput>) -> String {
+    match written {
+        Written::Row(row) => transaction(row),
+        Written::Planned(planned) => planned_transaction(&planned.row),
+    }
+}
+
+fn account(account: &Account) -> String {
+    format!(
+        "{}: {} ({}, {})",
+        account.id, account.name, account.r#type, account.currency
+    )
+}
+
+fn category(category: &Category) -> String {
+    format!(
+        "{}: {} ({})",
+        category.id,
+        category.name,
+        category.kind.as_ref()
+    )
+}
+
+fn similarity(similarity: Option<f64>) -> String {
+    similarity
+        .map(|similarity| format!(", similarity {similarity:.2}"))
+        .unwrap_or_default()
+}
+
+/// `header`, then one line per item, the items past [`MAX_LINES`] counted.
+fn list<T>(header: String, items: &[T], line: impl Fn(&T) -> String) -> String {
+    let mut text = header;
+    for item in items.iter().take(MAX_LINES) {
+        let _ = write!(text, "\n- {}", line(item));
+    }
+    if items.len() > MAX_LINES {
+        let _ = write!(text, "\n… and {} more", items.len() - MAX_LINES);
+    }
+    text
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    match count {
+        1 => format!("1 {one}"),
+        _ => format!("{count} {many}"),
+    }
+}
+
+fn batch_step(step: &Written<BatchStepResult, BatchOperation>) -> String {
+    match step {
+        Written::Row(BatchStepResult::UpsertAccount { account: row }) => {
+            format!("upsert_account {}", account(row))
+        }
+        Written::Row(BatchStepResult::CreateTransactions { transactions }) => format!(
+            "create_transactions {}",
+            plural(transactions.len(), "transaction", "transactions")
+        ),
+        Written::Row(BatchStepResult::Categorize { transaction }) => format!(
+            "categorize {} under {}",
+            transaction.id,
+            transaction.category_id.as_deref().unwrap_or("no category")
+        ),
+        Written::Planned(planned) => match &planned.row {
+            BatchOperation::UpsertAccount(input) => format!(
+                "upsert_account {} ({}, {})",
+                input.name, input.r#type, input.currency
+            ),
+            BatchOperation::CreateTransactions(input) => format!(
+                "create_transactions {}",
+                plural(input.transactions.len(), "transaction", "transactions")
+            ),
+            BatchOperation::Categorize(input) => format!(
+                "categorize {} under {}",
+                input.transaction_id, input.category_id
+            ),
+        },
+    }
+}
+
+impl Render for TransactionOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let created = verb(dry_run, "Created", "create");
+        format!(
+            "{created} transaction {}",
+            written_transaction(&self.transaction)
+        )
+    }
+}
+
+impl Render for ExecuteBatchOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let applied = verb(dry_run, "Applied", "apply");
+        let header = format!(
+            "{applied} {}",
+            plural(self.steps.len(), "operation", "operations")
+        );
+        list(header, &self.steps, batch_step)
+    }
+}
+
+impl Render for TransactionsOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let created = verb(dry_run, "Created", "create");
+        let header = format!(
+            "{created} {}",
+            plural(self.transactions.len(), "transaction", "transactions")
+        );
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for ImportOfxOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let imported = verb(dry_run, "Imported", "import");
+        let mut header = format!(
+            "{imported} {}",
+            plural(self.transactions.len(), "transaction", "transactions")
+        );
+        let duplicates = self
+            .skipped
+            .iter()
+            .filter(|skipped| skipped.reason == SkipReason::Duplicate)
+            .count();
+        if duplicates > 0 {
+            let _ = write!(header, ", skipped {duplicates} already recorded");
+        }
+        let zero = self.skipped.len() - duplicates;
+        if zero > 0 {
+            let _ = write!(header, ", skipped {zero} of zero amount");
+        }
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for ImportBankStatementOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let imported = verb(dry_run, "Imported", "import");
+        let mut header = format!(
+            "{imported} {} from the {} statement",
+            plural(self.transactions.len(), "transaction", "transactions"),
+            self.format.as_ref()
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::Pending, "pending"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for ImportGnuCashOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let imported = verb(dry_run, "Imported", "import");
+        let created = self
+            .accounts
+            .iter()
+            .filter(|mapping| mapping.created)
+            .count();
+        let mut header = format!(
+            "{imported} {} from the GnuCash book, into {} ({created} new)",
+            plural(self.transactions.len(), "transaction", "transactions"),
+            plural(
+                self.accounts.len(),
+                "account or category",
+                "accounts and categories"
+            )
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        list(header, &self.accounts, gnucash_account)
+    }
+}
+
+fn gnucash_account(mapping: &GnuCashAccountMapping) -> String {
+    let kind = match mapping.imported_as {
+        GnuCashImportedAs::Account => "account",
+        GnuCashImportedAs::Category => "category",
+    };
+    let new = if mapping.created { "new " } else { "" };
+    format!(
+        "{} → {new}{kind} {} ({})",
+        mapping.gnucash_account,
+        mapping.name,
+        plural(mapping.transactions, "transaction", "transactions")
+    )
+}
+
+impl Render for ImportYnabOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let imported = verb(dry_run, "Imported", "import");
+        let source = match (self.source, &self.budget_id) {
+            (YnabSource::Api, Some(budget_id)) => format!("YNAB budget {budget_id}"),
+            _ => "the YNAB export".to_string(),
+        };
+        let mappings = self
+            .accounts
+            .iter()
+            .map(|mapping| ("account", mapping))
+            .chain(self.categories.iter().map(|mapping| ("category", mapping)))
+            .collect::<Vec<_>>();
+        let created = mappings
+            .iter()
+            .filter(|(_, mapping)| mapping.created)
+            .count();
+        let mut header = format!(
+            "{imported} {} from {source}, into {} and {} ({created} new)",
+            plural(self.transactions.len(), "transaction", "transactions"),
+            plural(self.accounts.len(), "account", "accounts"),
+            plural(self.categories.len(), "category", "categories")
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        list(header, &mappings, |(kind, mapping)| {
+            ynab_mapping(kind, mapping)
+        })
+    }
+}
+
+fn ynab_mapping(kind: &str, mapping: &YnabMapping) -> String {
+    let new = if mapping.created { "new " } else { "" };
+    format!(
+        "{new}{kind} {} ({})",
+        mapping.name,
+        plural(mapping.transactions, "transaction", "transactions")
+    )
+}
+
+impl Render for ImportJsonOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let imported = verb(dry_run, "Imported", "import");
+        let mut header = format!(
+            "{imported} {}",
+            plural(self.transactions.len(), "transaction", "transactions")
+        );
+        if self.rejected.is_empty() {
+            return list(header, &self.transactions, written_transaction);
+        }
+        let _ = write!(
+            header,
+            ", rejected {}",
+            plural(self.rejected.len(), "record", "records")
+        );
+        list(header, &self.rejected, |rejected| rejected.reason.clone())
+    }
+}
+
+impl Render for LinkBankAccountOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let linked = verb(dry_run, "Linked", "link");
+        let mut text = format!("{linked} account {} to", self.account_id);
+        match &self.item_id {
+            Some(item_id) => {
+                let _ = write!(text, " Plaid item {item_id}");
+            }
+            None => text.push_str(" the Plaid item of the public token"),
+        }
+        if let Some(plaid_account_id) = &self.plaid_account_id {
+            let _ = write!(text, ", bank account {plaid_account_id}");
+        }
+        if self.replaced {
+            text.push_str(", replacing its earlier link");
+        }
+        text
+    }
+}
+
+impl Render for SyncBankAccountOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let synced = verb(dry_run, "Synced", "sync");
+        let mut header = format!(
+            "{synced} {}",
+            plural(self.transactions.len(), "transaction", "transactions")
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::Pending, "pending"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        if !self.modified.is_empty() {
+            let _ = write!(header, "; the bank changed {}", self.modified.len());
+        }
+        if !self.removed.is_empty() {
+            let _ = write!(header, "; the bank removed {}", self.removed.len());
+        }
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for LinkOpenBankingAccountOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let linked = verb(dry_run, "Linked", "link");
+        let mut text = format!(
+            "{linked} account {} to bank account {} at {}",
+            self.account_id, self.link.openbanking_account_id, self.link.institution_id
+        );
+        if self.replaced {
+            text.push_str(", replacing its earlier link");
+        }
+        text
+    }
+}
+
+impl Render for SyncOpenBankingOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let synced = verb(dry_run, "Synced", "sync");
+        let mut header = format!(
+            "{synced} {}",
+            plural(self.transactions.len(), "transaction", "transactions")
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        if self.pending > 0 {
+            let _ = write!(header, "; {} pending", self.pending);
+        }
+        if let Some(synced_through) = &self.synced_through {
+            let _ = write!(header, "; booked through {synced_through}");
+        }
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for SyncOnchainOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let synced = verb(dry_run, "Synced", "sync");
+        let mut header = format!(
+            "{synced} {} of {} on {}",
+            plural(self.transactions.len(), "transfer", "transfers"),
+            self.link.address,
+            self.link.chain
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+            (SkipReason::Failed, "reverted"),
+            (SkipReason::Spam, "of spam tokens"),
+            (SkipReason::Pending, "unconfirmed"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        if let Some(block) = self.link.synced_block {
+            let _ = write!(header, "; read through block {block}");
+        }
+        if !self.complete {
+            header.push_str("; more remain, sync again");
+        }
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for SyncExchangeOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let synced = verb(dry_run, "Synced", "sync");
+        let mut header = format!(
+            "{synced} {} from {}",
+            plural(self.transactions.len(), "movement", "movements"),
+            self.link.exchange
+        );
+        for (reason, label) in [
+            (SkipReason::Duplicate, "already recorded"),
+            (SkipReason::ZeroAmount, "of zero amount"),
+            (SkipReason::Failed, "cancelled"),
+            (SkipReason::Pending, "in progress"),
+        ] {
+            let count = self
+                .skipped
+                .iter()
+                .filter(|skipped| skipped.reason == reason)
+                .count();
+            if count > 0 {
+                let _ = write!(header, ", skipped {count} {label}");
+            }
+        }
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+impl Render for ParseReceiptOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let receipt = &self.receipt;
+        let mut header = format!(
+            "Read a receipt from {}",
+            receipt.merchant.as_deref().unwrap_or("an unknown merchant")
+        );
+        if let Some(date) = &receipt.date {
+            let _ = write!(header, " on {date}");
+        }
+        match (receipt.total, &receipt.currency) {
+            (Some(total), Some(currency)) => {
+                let _ = write!(header, ", total {}", i18n::format_amount(total, currency));
+            }
+            (Some(total), None) => {
+                let _ = write!(header, ", total {total}");
+            }
+            (None, _) => {}
+        }
+        let _ = write!(
+            header,
+            ", {}",
+            plural(receipt.items.len(), "line item", "line items")
+        );
+        if self.already_recorded {
+            header.push_str("; already recorded, so not recorded again");
+        }
+        if self.transactions.is_empty() {
+            return header;
+        }
+        let _ = write!(
+            header,
+            "; {} {}",
+            verb(dry_run, "recorded", "record").to_lowercase(),
+            plural(self.transactions.len(), "expense", "expenses")
+        );
+        list(header, &self.transactions, written_transaction)
+    }
+}
+
+/// The journal itself, ready to save, after a comment line that both formats
+/// skip.
+impl Render for ExportLedgerOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let mut text = format!(
+            "Wrote a {} journal of {}",
+            self.format.as_ref(),
+            plural(self.transactions, "transaction", "transactions")
+        );
+        if self.truncated {
+            text.push_str(", the newest only");
+        }
+        text.push_str("; it is in the journal field");
+        text
+    }
+}
+
+impl Render for ExportToSheetOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let report = match self.report {
+            SheetReport::Transactions => plural(self.rows, "transaction", "transactions"),
+            SheetReport::MonthlySummary => plural(self.rows, "summary row", "summary rows"),
+        };
+        let mut text = format!("{} {report}", verb(dry_run, "Exported", "export"));
+        match (self.destination, &self.sheet) {
+            (SheetDestination::GoogleSheets, Some(sheet)) => {
+                let _ = write!(
+                    text,
+                    " to the {sheet:?} tab of Google Sheet {}",
+                    self.target
+                );
+            }
+            _ => {
+                let _ = write!(text, " to Notion database {}", self.target);
+            }
+        }
+        if self.truncated {
+            text.push_str(", the newest only");
+        }
+        if let Some(url) = &self.url {
+            let _ = write!(text, "\n{url}");
+        }
+        text
+    }
+}
+
+impl Render for ExportYnabOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let added = self
+            .categories
+            .iter()
+            .filter(|export| export.status == YnabCategoryStatus::Added)
+            .count();
+        let header = format!(
+            "{} {} to group {} of YNAB budget {}",
+            verb(dry_run, "Added", "add"),
+            plural(added, "category", "categories"),
+            self.group,
+            self.budget_id
+        );
+        list(header, &self.categories, ynab_category)
+    }
+}
+
+fn ynab_category(export: &YnabCategoryExport) -> String {
+    let status = match export.status {
+        YnabCategoryStatus::Existing => "already in the budget",
+        YnabCategoryStatus::Added => "added",
+    };
+    format!("{} ({status})", export.category)
+}
+
+impl Render for ImportQifOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let imported = verb(dry_run, "Imported", "import");
+        let mut header = format!(
+            "{imported} {}",
+            plural(self.transactions.len(), "transaction", "transactions")
+        );
+        if self.skipped_zero_amount > 0 {
+            let _ = write!(
+                header,
+                ", skipped {} of zero amount",
+                self.skipped_zero_amount
+            );
+        }
+        let mapped = self
+            .categories
+            .iter()
+            .filter(|mapping| mapping.category.is_some())
+            .count();
+        if !self.categories.is_empty() {
+            let _ = write!(
+                header,
+                ", mapped {mapped} of {}",
+                plural(self.categories.len(), "QIF category", "QIF categories")
+            );
+        }
+        list(header, &self.categories, qif_category)
+    }
+}
+
+fn qif_category(mapping: &QifCategoryMapping) -> String {
+    let target = match (&mapping.category, mapping.matched_by, mapping.similarity) {
+        (Some(category), Some(MatchedBy::Similarity), Some(similarity)) => {
+            format!("{} (similarity {similarity:.2})", category.name)
+        }
+        (Some(category), _, _) => category.name.clone(),
+        (None, _, _) => "no match".to_string(),
+    };
+    format!(
+        "{} ({}) → {target}",
+        mapping.qif_category,
+        plural(mapping.transactions, "transaction", "transactions")
+    )
+}
+
+impl Render for TransactionMatchesOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = format!(
+            "Found {}",
+            plural(
+                self.matches.len(),
+                "similar transaction",
+                "similar transactions"
+            )
+        );
+        list(header, &self.matches, |found| {
+            format!(
+                "{}{}",
+                transaction(&found.transaction),
+                similarity(found.similarity)
+            )
+        })
+    }
+}
+
+impl Render for TextMatchesOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = format!(
+            "Found {}",
+            plural(
+                self.matches.len(),
+                "matching transaction",
+                "matching transactions"
+            )
+        );
+        list(header, &self.matches, |found| {
+            transaction(&found.transaction)
+        })
+    }
+}
+
+impl Render for CategoryOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let saved = verb(dry_run, "Saved", "save");
+        match &self.category {
+            Written::Row(row) => format!("{saved} category {}", category(row)),
+            Written::Planned(planned) => format!("{saved} category {}", planned.row.name),
+        }
+    }
+}
+
+impl Render for CategoryMatchesOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = format!(
+            "Found {}",
+            plural(self.matches.len(), "similar category", "similar categories")
+        );
+        list(header, &self.matches, |found| {
+            format!(
+                "{}{}",
+                category(&found.category),
+                similarity(found.similarity)
+            )
+        })
+    }
+}
+
+impl Render for AccountsOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = format!("Showing {} of {} accounts", self.accounts.len(), self.total);
+        list(header, &self.accounts, account)
+    }
+}
+
+impl Render for TransactionPageOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = format!(
+            "Showing {} of {} transactions",
+            self.transactions.len(),
+            self.total
+        );
+        list(header, &self.transactions, transaction)
+    }
+}
+
+impl Render for AccountOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let saved = verb(dry_run, "Saved", "save");
+        match &self.account {
+            Written::Row(row) => format!("{saved} account {}", account(row)),
+            Written::Planned(planned) => format!("{saved} account {}", planned.row.name),
+        }
+    }
+}
+
+impl Render for DeletedOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let deleted = verb(dry_run, "Deleted", "delete");
+        format!(
+            "{deleted} {} {}",
+            self.deleted.kind.as_ref(),
+            self.deleted.id
+        )
+    }
+}
+
+impl Render for PurgeOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let mut text = match (&self.purged, &self.tables) {
+            (Some(purged), _) => {
+                let counts: Vec<String> = purged
+                    .iter()
+                    .map(|(table, rows)| format!("{rows} from {table}"))
+                    .collect();
+                format!("Purged soft-deleted rows: {}", counts.join(", "))
+            }
+            (None, Some(tables)) => format!(
+                "{} soft-deleted rows from {}",
+                verb(dry_run, "Purged", "purge"),
+                tables.join(", ")
+            ),
+            (None, None) => "Purged no rows".to_string(),
+        };
+        if let Some(before) = &self.deleted_before {
+            let _ = write!(text, " deleted before {before}");
+        }
+        text
+    }
+}
+
+impl Render for EmbeddingMaintenanceOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let action = match self.action {
+            EmbeddingMaintenanceAction::Report => "Embedding report".to_string(),
+            EmbeddingMaintenanceAction::Backfill => verb(dry_run, "Backfilled", "backfill"),
+            EmbeddingMaintenanceAction::Clear => verb(dry_run, "Cleared", "clear"),
+        };
+        let header = format!("{action}, dimension {}", self.dimension);
+        let tables: Vec<_> = self.tables.iter().collect();
+        list(header, &tables, |(table, outcome)| match outcome {
+            TableMaintenance::Report(report) => format!(
+                "{table}: {} missing, {} of the wrong dimension, {} orphaned",
+                report.missing, report.dimension, report.orphaned
+            ),
+            TableMaintenance::Repair(repair) => format!(
+                "{table}: {} updated{}",
+                repair.updated,
+                if repair.complete { "" } else { ", more left" }
+            ),
+        })
+    }
+}
+
+impl Render for AuditEventsOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = plural(self.events.len(), "audit event", "audit events");
+        list(header, &self.events, |event| {
+            let record = event.record_id.as_deref().unwrap_or("no record");
+            format!("{} {} ({record})", event.occurred_at, event.tool)
+        })
+    }
+}
+
+impl Render for SpendingOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = plural(self.buckets.len(), "spending bucket", "spending buckets");
+        let mut text = list(header, &self.buckets, spending_line);
+        if let Some(base) = &self.base_totals {
+            let _ = write!(text, "\n{}", base_totals(base));
+        }
+        text
+    }
+}
+
+fn spending_line(bucket: &SpendingBucket) -> String {
+    let line = bucket_line(bucket);
+    let line = line.trim_start_matches("- ").trim_end();
+    match &bucket.period_start {
+        Some(period) => format!("{} {line}", date(period)),
+        None => line.to_string(),
+    }
+}
+
+/// Buckets in the base currency, naming the currencies left out.
+fn base_totals(base: &BaseTotals) -> String {
+    let mut header = format!("In {}", base.currency);
+    if !base.unconverted.is_empty() {
+        let _ = write!(header, ", without {}", base.unconverted.join(", "));
+    }
+    list(header, &base.buckets, spending_line)
+}
+
+impl Render for PeriodSummaryOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        if let Some(narrative) = &self.narrative {
+            return narrative.clone();
+        }
+        let header = format!("Totals for {}", self.month);
+        let mut text = list(header, &self.totals, spending_line);
+        if let Some(base) = &self.base_totals {
+            let _ = write!(text, "\n{}", base_totals(base));
+        }
+        text
+    }
+}
+
+impl Render for NetWorthOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = match &self.date {
+            Some(date) => format!("Balances at the end of {date}"),
+            None => "Balances".to_string(),
+        };
+        let mut text = list(header, &self.balances, spending_line);
+        if let (Some(base), Some(net_worth)) = (&self.base_totals, self.net_worth) {
+            let _ = write!(
+                text,
+                "\nNet worth {}\n{}",
+                i18n::format_amount(net_worth, &base.currency),
+                base_totals(base)
+            );
+        }
+        text
+    }
+}
+
+impl Render for ConvertCurrencyOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        format!(
+            "{} {} is {} {} at {} on {} ({})",
+            self.amount, self.from, self.converted, self.to, self.rate, self.date, self.provider
+        )
+    }
+}
+
+impl Render for AssetPriceOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let mut text = format!(
+            "{} ({}) is {} {} as of {}",
+            self.asset, self.coin_id, self.price, self.vs_currency, self.as_of
+        );
+        if let (Some(amount), Some(value)) = (self.amount, self.value) {
+            let _ = write!(text, "; {amount} is worth {value} {}", self.vs_currency);
+        }
+        text
+    }
+}
+
+impl Render for SeedDemoDataOutput {
+    fn render(&self, dry_run: bool) -> String {
+        let mut text = format!(
+            "{} {} accounts, {} categories and {} transactions",
+            verb(dry_run, "Seeded", "seed"),
+            self.accounts,
+            self.categories,
+            self.transactions
+        );
+        if self.skipped_transactions {
+            text.push_str("; the demo accounts already had transactions");
+        }
+        text
+    }
+}
+
+impl Render for CallRpcOutput {
+    fn render(&self, dry_run: bool) -> String {
+        format!("{} {}", verb(dry_run, "Called", "call"), self.function)
+    }
+}
+
+impl Render for PingOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        format!(
+            "{} {} up for {}s, MCP {}",
+            self.server_name, self.server_version, self.uptime_secs, self.protocol_version
+        )
+    }
+}
+
+impl Render for HealthCheckOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = match self.status {
+            HealthStatus::Ok => "Healthy",
+            HealthStatus::Degraded => "Degraded",
+        };
+        list(header.to_string(), &self.checks, |check| {
+            let status = if check.ok { "ok" } else { "failed" };
+            match &check.error {
+                Some(error) => format!(
+                    "{}: {status} in {}ms ({error})",
+                    check.name, check.latency_ms
+                ),
+                None => format!("{}: {status} in {}ms", check.name, check.latency_ms),
+            }
+        })
+    }
+}
+
+impl Render for ServerMetricsOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let calls: u64 = self.tools.iter().map(|tool| tool.calls).sum();
+        let header = format!("Up for {}s, {calls} tool calls", self.uptime_secs);
+        list(header, &self.tools, |tool: &OperationMetrics| {
+            format!(
+                "{}: {} calls, {} errors, mean {:.0}ms",
+                tool.operation, tool.calls, tool.errors, tool.mean_ms
+            )
+        })
+    }
+}
+
+impl Render for SessionDefaultsOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let defaults = &self.defaults;
+        let set: Vec<String> = [
+            ("account", &defaults.account_id),
+            ("currency", &defaults.currency),
+            ("timezone", &defaults.timezone),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| format!("{name} {value}")))
+        .collect();
+        if set.is_empty() {
+            "No session defaults set".to_string()
+        } else {
+            format!("Session defaults: {}", set.join(", "))
+        }
+    }
+}
+
+impl Render for CapabilitiesOutput {
+    fn render(&self, _dry_run: bool) -> String {
+        let header = format!("{} tools", self.tools.len());
+        list(header, &self.tools, |tool| {
+            let purpose = tool.purpose.split(". ").next().unwrap_or_default();
+            format!(
+                "{} ({}): {}",
+                tool.name,
+                tool.group,
+                purpose.trim_end_matches('.')
+            )
+        })
+    }
+}
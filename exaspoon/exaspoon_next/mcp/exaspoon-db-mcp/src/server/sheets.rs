@@ -0,0 +1,291 @@
+//! The reports `export_to_sheet` writes, as tables with account and category
+//! names in place of their ids.
+
+use super::{ExaspoonDbServer, ToolError};
+use crate::models::{
+    AggregateSpendingInput, ListAccountsInput, ListTransactionsInput, SheetReport, SpendingGroupBy,
+    SpendingPeriod, TransactionDirection, TransactionFilters,
+};
+use crate::sheets::{Cell, Column, ColumnKind, Table, MAX_SHEET_ROWS};
+use crate::supabase::MAX_PAGE_SIZE;
+use rmcp::ErrorData as McpError;
+use std::collections::HashMap;
+use tracing::{error, warn};
+
+const TRANSACTION_COLUMNS: &[Column] = &[
+    Column {
+        name: "Description",
+        kind: ColumnKind::Text,
+    },
+    Column {
+        name: "Date",
+        kind: ColumnKind::Date,
+    },
+    Column {
+        name: "Amount",
+        kind: ColumnKind::Number,
+    },
+    Column {
+        name: "Currency",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Direction",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Account",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Category",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Transaction id",
+        kind: ColumnKind::Text,
+    },
+];
+
+const SUMMARY_COLUMNS: &[Column] = &[
+    Column {
+        name: "Month",
+        kind: ColumnKind::Text,
+    },
+    Column {
+        name: "Direction",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Category",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Currency",
+        kind: ColumnKind::Select,
+    },
+    Column {
+        name: "Total",
+        kind: ColumnKind::Number,
+    },
+    Column {
+        name: "Transactions",
+        kind: ColumnKind::Number,
+    },
+];
+
+impl ExaspoonDbServer {
+    /// `report` over the transactions matching `filters`, and whether rows
+    /// past [`MAX_SHEET_ROWS`] were left out.
+    pub(crate) async fn sheet_table(
+        &self,
+        report: SheetReport,
+        filters: &TransactionFilters,
+    ) -> Result<(Table, bool), McpError> {
+        let (accounts, categories) =
+            tokio::try_join!(self.account_names_by_id(), self.category_names_by_id())?;
+        match report {
+            SheetReport::Transactions => {
+                self.transaction_table(filters, &accounts, &categories)
+                    .await
+            }
+            SheetReport::MonthlySummary => self.summary_table(filters, &categories).await,
+        }
+    }
+
+    /// A row per transaction, newest first, expenses negative.
+    async fn transaction_table(
+        &self,
+        filters: &TransactionFilters,
+        accounts: &HashMap<String, String>,
+        categories: &HashMap<String, String>,
+    ) -> Result<(Table, bool), McpError> {
+        let mut transactions = Vec::new();
+        // One past the most written tells whether there were more.
+        while transactions.len() <= MAX_SHEET_ROWS {
+            let page = self
+                .supabase
+                .list_transactions(&ListTransactionsInput {
+                    filters: filters.clone(),
+                    limit: Some(MAX_PAGE_SIZE),
+                    offset: Some(transactions.len() as u32),
+                })
+                .await
+                .map_err(|err| {
+                    error!("Failed to list transactions: {}", err);
+                    ToolError::failed("list transactions", err)
+                })?;
+            let full = page.len() == MAX_PAGE_SIZE as usize;
+            transactions.extend(page);
+            if !full {
+                break;
+            }
+        }
+        let truncated = transactions.len() > MAX_SHEET_ROWS;
+        transactions.truncate(MAX_SHEET_ROWS);
+
+        let keys = transactions
+            .iter()
+            .map(|transaction| transaction.id.clone())
+            .collect();
+        let rows = transactions
+            .into_iter()
+            .map(|transaction| {
+                let signed = match transaction.direction {
+                    TransactionDirection::Expense => -transaction.amount,
+                    _ => transaction.amount,
+                };
+                vec![
+                    Cell::optional(transaction.description),
+                    Cell::text(transaction.occurred_at.get(..10).unwrap_or_default()),
+                    Cell::Number(signed),
+                    Cell::text(transaction.currency),
+                    Cell::text(transaction.direction.as_ref()),
+                    Cell::optional(accounts.get(&transaction.account_id).cloned()),
+                    Cell::optional(
+                        transaction
+                            .category_id
+                            .and_then(|category_id| categories.get(&category_id).cloned()),
+                    ),
+                    Cell::text(transaction.id),
+                ]
+            })
+            .collect();
+        Ok((
+            Table {
+                columns: TRANSACTION_COLUMNS.to_vec(),
+                rows,
+                keys,
+            },
+            truncated,
+        ))
+    }
+
+    /// A row per month, direction, category and currency, newest month
+    /// first. Transfers are left out unless `filters` asks for them.
+    async fn summary_table(
+        &self,
+        filters: &TransactionFilters,
+        categories: &HashMap<String, String>,
+    ) -> Result<(Table, bool), McpError> {
+        let directions = match filters.direction {
+            Some(direction) => vec![direction],
+            None => vec![TransactionDirection::Income, TransactionDirection::Expense],
+        };
+        let mut rows = Vec::new();
+        for direction in directions {
+            let filters = TransactionFilters {
+                direction: Some(direction),
+                ..filters.clone()
+            };
+            let buckets = self
+                .supabase
+                .aggregate_spending(&AggregateSpendingInput {
+                    group_by: SpendingGroupBy::Category,
+                    period: Some(SpendingPeriod::Month),
+                    filters,
+                })
+                .await
+                .map_err(|err| {
+                    error!("Failed to aggregate spending: {}", err);
+                    ToolError::failed("aggregate spending", err)
+                })?;
+            rows.extend(buckets.into_iter().map(|bucket| {
+                let month = bucket
+                    .period_start
+                    .as_deref()
+                    .and_then(|start| start.get(..7))
+                    .unwrap_or_default()
+                    .to_string();
+                let category = bucket
+                    .key
+                    .map(|category_id| categories.get(&category_id).cloned().unwrap_or(category_id))
+                    .unwrap_or_else(|| "Uncategorized".to_string());
+                (
+                    month,
+                    direction,
+                    category,
+                    bucket.currency,
+                    bucket.total,
+                    bucket.count,
+                )
+            }));
+        }
+        rows.sort_by(|left, right| {
+            right
+                .0
+                .cmp(&left.0)
+                .then_with(|| left.1.as_ref().cmp(right.1.as_ref()))
+                .then_with(|| left.2.cmp(&right.2))
+                .then_with(|| left.3.cmp(&right.3))
+        });
+        let truncated = rows.len() > MAX_SHEET_ROWS;
+        rows.truncate(MAX_SHEET_ROWS);
+
+        let keys = rows
+            .iter()
+            .map(|(month, direction, category, currency, _, _)| {
+                format!("{month}/{}/{category}/{currency}", direction.as_ref())
+            })
+            .collect();
+        let rows = rows
+            .into_iter()
+            .map(|(month, direction, category, currency, total, count)| {
+                vec![
+                    Cell::Text(month),
+                    Cell::text(direction.as_ref()),
+                    Cell::Text(category),
+                    Cell::Text(currency),
+                    Cell::Number(total),
+                    Cell::Number(count as f64),
+                ]
+            })
+            .collect();
+        Ok((
+            Table {
+                columns: SUMMARY_COLUMNS.to_vec(),
+                rows,
+                keys,
+            },
+            truncated,
+        ))
+    }
+
+    async fn account_names_by_id(&self) -> Result<HashMap<String, String>, McpError> {
+        let accounts = self
+            .supabase
+            .list_accounts(&ListAccountsInput {
+                r#type: None,
+                search: None,
+                limit: None,
+                offset: None,
+            })
+            .await
+            .map_err(|err| {
+                error!("Failed to list accounts: {}", err);
+                ToolError::failed("list accounts", err)
+            })?;
+        Ok(accounts
+            .into_iter()
+            .map(|account| (account.id, account.name))
+            .collect())
+    }
+
+    /// Category names by id, or none where the backend cannot list them, in
+    /// which case the export shows ids.
+    async fn category_names_by_id(&self) -> Result<HashMap<String, String>, McpError> {
+        match self.supabase.list_categories().await {
+            Ok(categories) => Ok(categories
+                .into_iter()
+                .map(|category| (category.id, category.name))
+                .collect()),
+            Err(err) => {
+                warn!(
+                    "Exporting category ids; categories could not be listed: {:#}",
+                    err
+                );
+                Ok(HashMap::new())
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! `summarize_period`: a month's figures, narrated by the client's model
+//! through MCP sampling.
+
+use super::prompts::{bucket_line, expenses, month_filters, transaction_line};
+use super::ExaspoonDbServer;
+use crate::models::{PeriodSummaryOutput, SpendingGroupBy};
+use crate::{elicitation, i18n};
+use chrono::{Months, NaiveDate};
+use rmcp::ErrorData as McpError;
+use std::fmt::Write;
+use tracing::info;
+
+/// Longest narrative asked of the client's model.
+const SUMMARY_MAX_TOKENS: u32 = 600;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You are a personal finance assistant. Write a short, \
+    plain-language summary of the user's month using only the figures given. Do not invent \
+    numbers.";
+
+impl ExaspoonDbServer {
+    /// Totals by direction, expenses by category and the largest expenses of
+    /// the month starting on `month`, with a `narrative` when the client's
+    /// model wrote one.
+    pub(crate) async fn period_summary(
+        &self,
+        month: NaiveDate,
+    ) -> Result<PeriodSummaryOutput, McpError> {
+        let this_month = month_filters(month, month + Months::new(1));
+        let (totals, categories, notable) = tokio::try_join!(
+            self.aggregate(SpendingGroupBy::Direction, None, this_month.clone()),
+            self.aggregate(
+                SpendingGroupBy::Category,
+                None,
+                expenses(this_month.clone())
+            ),
+            self.largest_expenses(this_month),
+        )?;
+        // At the month's rates, as `aggregate_spending` converts it by month.
+        let base_totals = self.in_base_currency(&totals, Some(month)).await;
+        let base_expenses_by_category = self.in_base_currency(&categories, Some(month)).await;
+
+        let label = month.format("%Y-%m").to_string();
+        let mut prompt = format!("Summarize my finances for {label}.\n\nTotals by direction:\n");
+        for bucket in &totals {
+            prompt.push_str(&bucket_line(bucket));
+        }
+        if totals.is_empty() {
+            prompt.push_str("- no transactions this month\n");
+        }
+        if let Some(base) = &base_totals {
+            let _ = writeln!(prompt, "\nTotals in {}:", base.currency);
+            for bucket in &base.buckets {
+                prompt.push_str(&bucket_line(bucket));
+            }
+        }
+        prompt.push_str("\nExpenses by category id:\n");
+        for bucket in &categories {
+            prompt.push_str(&bucket_line(bucket));
+        }
+        prompt.push_str("\nLargest expenses:\n");
+        for transaction in &notable {
+            prompt.push_str(&transaction_line(transaction));
+        }
+        let _ = write!(
+            prompt,
+            "\nIn two or three short paragraphs, say how {label} went: income against \
+             spending, where most of the money went, and any expense worth a second look."
+        );
+
+        // The figures stay in English; only the narrative is the user's.
+        let system_prompt = format!(
+            "{SUMMARY_SYSTEM_PROMPT} Write in {}.",
+            i18n::current().language()
+        );
+        let sampled = elicitation::sample(&system_prompt, prompt, SUMMARY_MAX_TOKENS).await;
+        if sampled.is_none() {
+            info!("No narrative for {}; returning the figures only", label);
+        }
+        let (narrative, model) = sampled.map_or((None, None), |sampled| {
+            (Some(sampled.text), Some(sampled.model))
+        });
+        Ok(PeriodSummaryOutput {
+            month: label,
+            totals,
+            expenses_by_category: categories,
+            base_totals,
+            base_expenses_by_category,
+            notable_transactions: notable,
+            narrative,
+            model,
+        })
+    }
+}
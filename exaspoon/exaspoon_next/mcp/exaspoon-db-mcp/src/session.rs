@@ -0,0 +1,66 @@
+//! Bookkeeping for the client sessions of a server that serves many clients
+//! at once over a network transport, so one process cannot be made to hold an
+//! unbounded number of them.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub const DEFAULT_MAX_SESSIONS: usize = 100;
+pub const DEFAULT_SESSION_IDLE_TIMEOUT_SECS: u64 = 1800;
+
+/// Counts the open sessions of a server against its limit.
+#[derive(Debug)]
+pub struct Sessions {
+    active: AtomicUsize,
+    /// Zero means no limit.
+    limit: usize,
+}
+
+/// Held while a session is open; dropping it frees its place.
+#[derive(Debug)]
+pub struct SessionSlot {
+    sessions: Arc<Sessions>,
+}
+
+impl Sessions {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            active: AtomicUsize::new(0),
+            limit,
+        }
+    }
+
+    /// Takes a place for a new session, or `None` while the limit is reached.
+    pub fn open(self: &Arc<Self>) -> Option<SessionSlot> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (self.limit == 0 || active < self.limit).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| SessionSlot {
+                sessions: self.clone(),
+            })
+    }
+
+    /// Sessions currently open.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Whether a new session would be refused.
+    pub fn is_full(&self) -> bool {
+        self.limit != 0 && self.active() >= self.limit
+    }
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SESSIONS)
+    }
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        self.sessions.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
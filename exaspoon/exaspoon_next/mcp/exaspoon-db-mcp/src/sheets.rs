@@ -0,0 +1,59 @@
+//! Reports pushed to where people keep their budgets by hand, for
+//! `export_to_sheet`: a tab of a Google Sheet through the Sheets API, or a
+//! Notion database through the Notion API. A report is a [`Table`], a row
+//! per transaction or per month and category, which each destination writes
+//! in its own way: the Sheets tab is replaced by it, and the Notion database
+//! gets a page per row with the table's columns added as properties, which
+//! the next export of the same row updates.
+
+pub mod google;
+pub mod notion;
+
+/// Most rows one export writes. Notion takes a request per row.
+pub const MAX_SHEET_ROWS: usize = 1000;
+
+/// What a column holds, which Notion needs to pick a property type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Text,
+    Number,
+    /// A `YYYY-MM-DD` day, or a `YYYY-MM` month.
+    Date,
+    /// One of a few values, such as a currency or a direction.
+    Select,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Empty,
+}
+
+/// A report as rows under named columns. The first column names the row,
+/// and becomes the title of a Notion page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Cell>>,
+    /// What each row is of, the same in every export: a transaction id, or a
+    /// month, direction, category and currency.
+    pub keys: Vec<String>,
+}
+
+impl Cell {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// `text` or, without one, an empty cell.
+    pub fn optional(text: Option<impl Into<String>>) -> Self {
+        text.map_or(Self::Empty, Self::text)
+    }
+}
@@ -0,0 +1,345 @@
+//! Google Sheets, written with the OAuth refresh token of a user who can
+//! edit the spreadsheet. An export replaces the whole of one tab, adding the
+//! tab when the spreadsheet has none of that name, so exporting again leaves
+//! the same sheet rather than a second copy of the rows. The rows are written
+//! and what is left of the tab cleared in one request, so a failed export
+//! leaves the tab as it was. Values are written as they are, so a
+//! description starting with `=` stays text instead of becoming a formula.
+
+use super::{Cell, Table};
+use crate::config::{GoogleSheetsConfig, HttpClientConfig};
+use crate::correlation;
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument};
+
+pub const GOOGLE_SHEETS_BASE_URL: &str = "https://sheets.googleapis.com/v4";
+
+pub const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Seconds before an access token expires at which a new one is fetched.
+const TOKEN_EXPIRY_MARGIN_SECS: u64 = 60;
+
+/// Writes to Google Sheets on behalf of one Google user.
+pub struct GoogleSheetsClient {
+    http: Client,
+    base_url: String,
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    spreadsheet_id: Option<String>,
+    /// The access token and when to fetch another.
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+/// Where an export went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetWrite {
+    /// The range written, e.g. `'Transactions'!A1:H42`.
+    pub range: String,
+    /// Link to the tab.
+    pub url: String,
+}
+
+/// An error response of Google, with its status when it sent one, e.g.
+/// `PERMISSION_DENIED`, or the OAuth error of a refresh token Google no
+/// longer accepts, e.g. `invalid_grant`.
+#[derive(Debug, Clone)]
+pub struct GoogleError {
+    pub status_code: u16,
+    pub status: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for GoogleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Google error {}: {}", self.status_code, self.message)
+    }
+}
+
+impl std::error::Error for GoogleError {}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Seconds the access token is valid for.
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct Spreadsheet {
+    #[serde(default)]
+    sheets: Vec<Sheet>,
+}
+
+#[derive(Deserialize)]
+struct Sheet {
+    properties: SheetProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SheetProperties {
+    sheet_id: i64,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct BatchUpdate {
+    #[serde(default)]
+    replies: Vec<Reply>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Reply {
+    #[serde(default)]
+    add_sheet: Option<Sheet>,
+}
+
+/// Either shape Google rejects a request with: the APIs' error object, or
+/// OAuth's error code and description.
+#[derive(Deserialize)]
+struct Rejected {
+    error: Value,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+impl GoogleSheetsClient {
+    pub fn new(config: &GoogleSheetsConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Google Sheets export to {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Google Sheets")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            token_url: config.token_url.clone(),
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            refresh_token: config.refresh_token.clone(),
+            spreadsheet_id: config.spreadsheet_id.clone(),
+            token: Mutex::default(),
+        })
+    }
+
+    /// The spreadsheet exports go to unless given another.
+    pub fn spreadsheet_id(&self) -> Option<&str> {
+        self.spreadsheet_id.as_deref()
+    }
+
+    /// Replaces the tab `sheet` of `spreadsheet_id` with `table`, its column
+    /// names as the first row, adding the tab if there is none.
+    #[instrument(skip(self, table), fields(rows = table.rows.len()))]
+    pub async fn write(
+        &self,
+        spreadsheet_id: &str,
+        sheet: &str,
+        table: &Table,
+    ) -> Result<SheetWrite> {
+        let start_time = Instant::now();
+        let sheet_id = self.sheet_id(spreadsheet_id, sheet).await?;
+
+        let mut rows = vec![json!({
+            "values": table
+                .columns
+                .iter()
+                .map(|column| cell_data(&Cell::text(column.name)))
+                .collect::<Vec<_>>(),
+        })];
+        rows.extend(
+            table
+                .rows
+                .iter()
+                .map(|row| json!({ "values": row.iter().map(cell_data).collect::<Vec<_>>() })),
+        );
+        // Cells of the whole tab the rows leave out are cleared, as the
+        // update covers the tab.
+        let _: BatchUpdate = self
+            .batch_update(
+                spreadsheet_id,
+                json!([{
+                    "updateCells": {
+                        "range": { "sheetId": sheet_id },
+                        "rows": rows,
+                        "fields": "userEnteredValue",
+                    },
+                }]),
+            )
+            .await?;
+        info!(
+            "Wrote {} rows to Google Sheets in {:?}",
+            table.rows.len(),
+            start_time.elapsed()
+        );
+        // Quoted, as a tab name may look like a cell reference.
+        let tab = format!("'{}'", sheet.replace('\'', "''"));
+        Ok(SheetWrite {
+            range: format!(
+                "{tab}!A1:{}{}",
+                column_name(table.columns.len()),
+                table.rows.len() + 1
+            ),
+            url: format!(
+                "https://docs.google.com/spreadsheets/d/{spreadsheet_id}/edit#gid={sheet_id}"
+            ),
+        })
+    }
+
+    /// The id of the tab `sheet`, added first if the spreadsheet has none.
+    async fn sheet_id(&self, spreadsheet_id: &str, sheet: &str) -> Result<i64> {
+        let url = self.url(&[spreadsheet_id])?;
+        let spreadsheet: Spreadsheet = self
+            .send(
+                self.http
+                    .get(url.clone())
+                    .query(&[("fields", "sheets.properties(sheetId,title)")]),
+            )
+            .await?;
+        if let Some(found) = spreadsheet
+            .sheets
+            .iter()
+            .find(|found| found.properties.title == sheet)
+        {
+            return Ok(found.properties.sheet_id);
+        }
+
+        debug!("Adding the tab {:?}", sheet);
+        let added = self
+            .batch_update(
+                spreadsheet_id,
+                json!([{ "addSheet": { "properties": { "title": sheet } } }]),
+            )
+            .await?;
+        added
+            .replies
+            .into_iter()
+            .find_map(|reply| reply.add_sheet)
+            .map(|added| added.properties.sheet_id)
+            .context("Google Sheets did not say which tab it added")
+    }
+
+    /// Applies `requests` to `spreadsheet_id`, all of them or none.
+    async fn batch_update(&self, spreadsheet_id: &str, requests: Value) -> Result<BatchUpdate> {
+        let mut url = self.url(&[spreadsheet_id])?;
+        let path = format!("{}:batchUpdate", url.path());
+        url.set_path(&path);
+        self.send(self.http.post(url).json(&json!({ "requests": requests })))
+            .await
+    }
+
+    /// `{base_url}/spreadsheets/` followed by `segments`, each escaped.
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.base_url).context("invalid Google Sheets base URL")?;
+        url.path_segments_mut()
+            .map_err(|()| anyhow!("invalid Google Sheets base URL"))?
+            .pop_if_empty()
+            .push("spreadsheets")
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// An access token, fetched with the refresh token when there is none
+    /// or it is about to expire.
+    async fn access_token(&self) -> Result<String> {
+        let cached = self.token.lock().unwrap().clone();
+        if let Some((token, refresh_at)) = cached {
+            if Instant::now() < refresh_at {
+                return Ok(token);
+            }
+        }
+        debug!("Fetching a Google access token");
+        let response = self
+            .http
+            .post(&self.token_url)
+            .headers(correlation::headers())
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("refresh_token", self.refresh_token.as_str()),
+            ])
+            .send()
+            .await
+            .context("Google token request failed")?;
+        let token: TokenResponse = parse(response).await?;
+        let valid_for =
+            Duration::from_secs(token.expires_in.saturating_sub(TOKEN_EXPIRY_MARGIN_SECS));
+        *self.token.lock().unwrap() =
+            Some((token.access_token.clone(), Instant::now() + valid_for));
+        Ok(token.access_token)
+    }
+
+    /// Sends `request` with an access token and parses the response.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let token = self.access_token().await?;
+        let response = request
+            .bearer_auth(token)
+            .headers(correlation::headers())
+            .send()
+            .await
+            .context("Google Sheets request failed")?;
+        parse(response).await
+    }
+}
+
+/// `cell` as the Sheets API's cell data. Text is a string value, which is
+/// never read as a formula, and an empty cell has no value, clearing it.
+fn cell_data(cell: &Cell) -> Value {
+    match cell {
+        Cell::Text(text) => json!({ "userEnteredValue": { "stringValue": text } }),
+        Cell::Number(number) => json!({ "userEnteredValue": { "numberValue": number } }),
+        Cell::Empty => json!({}),
+    }
+}
+
+/// The letters of the `number`th column, counting from 1: `A`, ..., `Z`,
+/// `AA`, ...
+fn column_name(mut number: usize) -> String {
+    let mut name = Vec::new();
+    while number > 0 {
+        number -= 1;
+        name.push(b'A' + (number % 26) as u8);
+        number /= 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+async fn parse<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        error!("Google request failed ({}): {}", status, body);
+        let (code, message) = match serde_json::from_str::<Rejected>(&body) {
+            Ok(Rejected {
+                error: Value::String(code),
+                error_description,
+            }) => (Some(code.clone()), error_description.unwrap_or(code)),
+            Ok(Rejected { error, .. }) => (
+                error["status"].as_str().map(str::to_string),
+                error["message"]
+                    .as_str()
+                    .map_or_else(|| status.to_string(), str::to_string),
+            ),
+            Err(_) => (None, status.to_string()),
+        };
+        return Err(anyhow!(GoogleError {
+            status_code: status.as_u16(),
+            status: code,
+            message,
+        }));
+    }
+    response
+        .json()
+        .await
+        .context("failed to parse Google Sheets response")
+}
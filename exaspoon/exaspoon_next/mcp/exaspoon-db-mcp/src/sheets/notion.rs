@@ -0,0 +1,404 @@
+//! Notion databases, written with the token of an internal integration the
+//! database is shared with. The table's first column goes into the
+//! database's title property, whatever it is called, and the other columns
+//! into properties of their names, which are added when the database lacks
+//! them. Each row becomes a page keeping the row's key in [`KEY_PROPERTY`],
+//! so exporting again updates the pages of rows already exported instead of
+//! adding them twice, and an export cut short is finished by running it
+//! again. Requests Notion rate limits are sent again after its Retry-After.
+
+use super::{Cell, Column, ColumnKind, Table};
+use crate::cancellation;
+use crate::config::{HttpClientConfig, NotionConfig};
+use crate::correlation;
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, instrument, warn};
+
+pub const NOTION_BASE_URL: &str = "https://api.notion.com/v1";
+
+/// The Notion API version requests are made against.
+pub const NOTION_VERSION: &str = "2022-06-28";
+
+/// Property each page keeps the key of its row in.
+pub const KEY_PROPERTY: &str = "Export key";
+
+/// Longest text Notion takes in one rich text object.
+const MAX_TEXT_CHARS: usize = 2000;
+
+/// Most pages Notion lists per query.
+const QUERY_PAGE_SIZE: usize = 100;
+
+/// Times a rate-limited request is sent again before the export gives up.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Longest wait for a rate limit, whatever Retry-After asks for.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Writes to Notion with one integration token.
+pub struct NotionClient {
+    http: Client,
+    base_url: String,
+    token: String,
+    database_id: Option<String>,
+}
+
+/// What an export added to a database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PagesAdded {
+    /// Pages added or updated.
+    pub pages: usize,
+    /// Link to the database.
+    pub url: Option<String>,
+}
+
+/// Context of an error that stopped an export partway, with the pages it
+/// had written by then.
+#[derive(Debug, Clone, Copy)]
+pub struct Interrupted {
+    pub pages: usize,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stopped after writing {} Notion pages", self.pages)
+    }
+}
+
+/// An error response of Notion, with its code, e.g. `object_not_found` for
+/// a database that is not shared with the integration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotionError {
+    #[serde(rename = "status")]
+    pub status_code: u16,
+    pub code: String,
+    pub message: String,
+}
+
+impl fmt::Display for NotionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Notion error {} {}: {}",
+            self.status_code, self.code, self.message
+        )
+    }
+}
+
+impl std::error::Error for NotionError {}
+
+#[derive(Deserialize)]
+struct Database {
+    #[serde(default)]
+    url: Option<String>,
+    properties: HashMap<String, Property>,
+}
+
+#[derive(Deserialize)]
+struct Property {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct Query {
+    results: Vec<Page>,
+    #[serde(default)]
+    has_more: bool,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    id: String,
+    #[serde(default)]
+    properties: HashMap<String, Value>,
+}
+
+impl NotionClient {
+    pub fn new(config: &NotionConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing Notion export to {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for Notion")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            token: config.token.clone(),
+            database_id: config.database_id.clone(),
+        })
+    }
+
+    /// The database exports go to unless given another.
+    pub fn database_id(&self) -> Option<&str> {
+        self.database_id.as_deref()
+    }
+
+    /// Adds a page per row of `table` to `database_id`, or updates the page
+    /// an earlier export added for it, after adding the properties the
+    /// database lacks. Stops early when the tool call is cancelled. An
+    /// error partway carries the pages written by then as [`Interrupted`].
+    #[instrument(skip(self, table), fields(rows = table.rows.len()))]
+    pub async fn append(&self, database_id: &str, table: &Table) -> Result<PagesAdded> {
+        let start_time = Instant::now();
+        let database_url = self.url(&["databases", database_id])?;
+        let database: Database = self.send(self.http.get(database_url.clone())).await?;
+        let title = database
+            .properties
+            .iter()
+            .find(|(_, property)| property.kind == "title")
+            .map(|(name, _)| name.clone())
+            .context("the Notion database has no title property")?;
+
+        let mut missing = Map::new();
+        for column in table.columns.iter().skip(1) {
+            let kind = property_type(column.kind);
+            match database.properties.get(column.name) {
+                Some(property) if property.kind == kind => {}
+                Some(property) => bail!(
+                    "the Notion database's {} property is {}, not {kind}",
+                    column.name,
+                    property.kind
+                ),
+                None => {
+                    missing.insert(column.name.to_string(), typed(kind, json!({})));
+                }
+            }
+        }
+        let keyed = match database.properties.get(KEY_PROPERTY) {
+            Some(property) if property.kind == "rich_text" => true,
+            Some(property) => bail!(
+                "the Notion database's {KEY_PROPERTY} property is {}, not rich_text",
+                property.kind
+            ),
+            None => {
+                missing.insert(KEY_PROPERTY.to_string(), typed("rich_text", json!({})));
+                false
+            }
+        };
+        if !missing.is_empty() {
+            debug!("Adding {} properties to the Notion database", missing.len());
+            let _: Value = self
+                .send(
+                    self.http
+                        .patch(database_url)
+                        .json(&json!({ "properties": missing })),
+                )
+                .await?;
+        }
+        let exported = match keyed {
+            true => self.exported_pages(database_id).await?,
+            false => HashMap::new(),
+        };
+
+        let mut pages = 0;
+        let mut updated = 0;
+        for (row, key) in table.rows.iter().zip(&table.keys) {
+            if cancellation::is_cancelled() {
+                warn!("Cancelled after writing {} Notion pages", pages);
+                break;
+            }
+            let mut properties: Map<String, Value> = table
+                .columns
+                .iter()
+                .zip(row)
+                .enumerate()
+                .map(|(index, (column, cell))| {
+                    if index == 0 {
+                        (title.clone(), json!({ "title": rich_text(cell) }))
+                    } else {
+                        (column.name.to_string(), property_value(column, cell))
+                    }
+                })
+                .collect();
+            properties.insert(
+                KEY_PROPERTY.to_string(),
+                json!({ "rich_text": rich_text(&Cell::text(key.as_str())) }),
+            );
+            let request = match exported.get(key) {
+                Some(page_id) => self.url(&["pages", page_id]).map(|url| {
+                    self.http
+                        .patch(url)
+                        .json(&json!({ "properties": properties }))
+                }),
+                None => self.url(&["pages"]).map(|url| {
+                    self.http.post(url).json(&json!({
+                        "parent": { "database_id": database_id },
+                        "properties": properties,
+                    }))
+                }),
+            };
+            let written: Result<Value> = match request {
+                Ok(request) => self.send(request).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = written {
+                return Err(err.context(Interrupted { pages }));
+            }
+            pages += 1;
+            updated += usize::from(exported.contains_key(key));
+        }
+        info!(
+            "Wrote {} Notion pages, {} of them updated, in {:?}",
+            pages,
+            updated,
+            start_time.elapsed()
+        );
+        Ok(PagesAdded {
+            pages,
+            url: database.url,
+        })
+    }
+
+    /// The pages of `database_id` an earlier export wrote, by the key of
+    /// their row.
+    async fn exported_pages(&self, database_id: &str) -> Result<HashMap<String, String>> {
+        let url = self.url(&["databases", database_id, "query"])?;
+        let mut pages = HashMap::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut body = json!({
+                "filter": { "property": KEY_PROPERTY, "rich_text": { "is_not_empty": true } },
+                "page_size": QUERY_PAGE_SIZE,
+            });
+            if let Some(cursor) = &cursor {
+                body["start_cursor"] = json!(cursor);
+            }
+            let query: Query = self.send(self.http.post(url.clone()).json(&body)).await?;
+            for page in query.results {
+                let key = page.properties.get(KEY_PROPERTY).map(plain_text);
+                if let Some(key) = key.filter(|key| !key.is_empty()) {
+                    pages.insert(key, page.id);
+                }
+            }
+            match query.next_cursor {
+                Some(next) if query.has_more => cursor = Some(next),
+                _ => break,
+            }
+        }
+        debug!(
+            "{} pages of the Notion database were exported before",
+            pages.len()
+        );
+        Ok(pages)
+    }
+
+    /// `{base_url}/` followed by `segments`, each escaped, so an id cannot
+    /// reach another endpoint.
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.base_url).context("invalid Notion base URL")?;
+        url.path_segments_mut()
+            .map_err(|()| anyhow!("invalid Notion base URL"))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// Sends `request` with the token and parses the response, waiting out
+    /// Notion's rate limit up to [`MAX_RATE_LIMIT_RETRIES`] times.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let mut retries = 0;
+        let response = loop {
+            let response = request
+                .try_clone()
+                .context("a Notion request cannot be sent again")?
+                .bearer_auth(&self.token)
+                .header("Notion-Version", NOTION_VERSION)
+                .headers(correlation::headers())
+                .send()
+                .await
+                .context("Notion request failed")?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS
+                || retries == MAX_RATE_LIMIT_RETRIES
+                || cancellation::is_cancelled()
+            {
+                break response;
+            }
+            let wait = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .map_or(Duration::from_secs(1), Duration::from_secs_f64)
+                .min(MAX_RETRY_AFTER);
+            retries += 1;
+            warn!("Notion rate limited the export; retrying in {:?}", wait);
+            tokio::time::sleep(wait).await;
+        };
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Notion request failed ({}): {}", status, body);
+            return Err(match serde_json::from_str::<NotionError>(&body) {
+                Ok(err) => anyhow!(err),
+                Err(_) => anyhow!(NotionError {
+                    status_code: status.as_u16(),
+                    code: String::new(),
+                    message: status.to_string(),
+                }),
+            });
+        }
+        response
+            .json()
+            .await
+            .context("failed to parse Notion response")
+    }
+}
+
+fn property_type(kind: ColumnKind) -> &'static str {
+    match kind {
+        ColumnKind::Text => "rich_text",
+        ColumnKind::Number => "number",
+        ColumnKind::Date => "date",
+        ColumnKind::Select => "select",
+    }
+}
+
+fn property_value(column: &Column, cell: &Cell) -> Value {
+    match (column.kind, cell) {
+        (_, Cell::Empty) if column.kind != ColumnKind::Text => {
+            typed(property_type(column.kind), Value::Null)
+        }
+        (ColumnKind::Number, Cell::Number(number)) => json!({ "number": number }),
+        (ColumnKind::Date, Cell::Text(date)) => json!({ "date": { "start": date } }),
+        // Notion option names cannot hold commas.
+        (ColumnKind::Select, Cell::Text(name)) => {
+            json!({ "select": { "name": name.replace(',', " ") } })
+        }
+        _ => json!({ "rich_text": rich_text(cell) }),
+    }
+}
+
+/// The text of a rich text property value.
+fn plain_text(value: &Value) -> String {
+    value["rich_text"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|text| text["plain_text"].as_str())
+        .collect()
+}
+
+fn rich_text(cell: &Cell) -> Value {
+    let text = match cell {
+        Cell::Text(text) => text.chars().take(MAX_TEXT_CHARS).collect::<String>(),
+        Cell::Number(number) => number.to_string(),
+        Cell::Empty => return json!([]),
+    };
+    json!([{ "type": "text", "text": { "content": text } }])
+}
+
+/// `{ kind: value }`, the shape of Notion properties and their values.
+fn typed(kind: &str, value: Value) -> Value {
+    let mut typed = Map::new();
+    typed.insert(kind.to_string(), value);
+    Value::Object(typed)
+}
@@ -0,0 +1,91 @@
+//! Graceful shutdown: once the server starts draining it refuses new tool
+//! calls, and waits a bounded time for those already running to finish, so
+//! stopping it mid-import does not leave half-written rows behind.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+
+pub const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 10_000;
+
+/// Tracks the tool calls in flight and whether new ones are still admitted.
+#[derive(Debug)]
+pub struct Drain {
+    draining: AtomicBool,
+    in_flight: watch::Sender<usize>,
+}
+
+/// Held while an admitted tool call runs; dropping it lets a drain finish.
+#[derive(Debug)]
+pub struct InFlight<'a> {
+    drain: &'a Drain,
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Self {
+            draining: AtomicBool::new(false),
+            in_flight: watch::Sender::new(0),
+        }
+    }
+}
+
+impl Drain {
+    /// Admits a tool call, or `None` once the server is draining.
+    pub fn enter(&self) -> Option<InFlight<'_>> {
+        if self.is_draining() {
+            return None;
+        }
+        self.in_flight.send_modify(|calls| *calls += 1);
+        let call = InFlight { drain: self };
+        // A drain that started meanwhile may already have seen no calls.
+        (!self.is_draining()).then_some(call)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// How many admitted calls are still running.
+    pub fn in_flight(&self) -> usize {
+        *self.in_flight.borrow()
+    }
+
+    /// Stops admitting calls and waits up to `deadline` for the running ones
+    /// to finish, returning how many are still running.
+    pub async fn drain(&self, deadline: Duration) -> usize {
+        self.draining.store(true, Ordering::SeqCst);
+        let mut in_flight = self.in_flight.subscribe();
+        let _ = tokio::time::timeout(deadline, in_flight.wait_for(|calls| *calls == 0)).await;
+        self.in_flight()
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.drain.in_flight.send_modify(|calls| *calls -= 1);
+    }
+}
+
+/// Resolves on SIGTERM or SIGINT (Ctrl-C), naming the signal.
+pub async fn signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = terminate.recv() => "SIGTERM",
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+            },
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
@@ -0,0 +1,125 @@
+//! Plain socket transports for local supervisors: newline-delimited JSON-RPC,
+//! exactly as over stdio, on connections to a TCP port or a Unix domain
+//! socket. Each connection runs a session of its own, so one long-lived
+//! process serves every client without exposing an HTTP endpoint.
+
+use crate::server::ExaspoonDbServer;
+use anyhow::Result;
+use rmcp::ServiceExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Accepts TCP connections on `listener` until `stop` is cancelled, serving
+/// each in a [`ExaspoonDbServer::new_session`]. Connections beyond the
+/// session limit are closed straight away.
+pub async fn serve_tcp(
+    server: ExaspoonDbServer,
+    listener: TcpListener,
+    stop: CancellationToken,
+) -> Result<()> {
+    let address = listener.local_addr()?;
+    info!("Serving MCP on tcp:{}", address);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    if let Some(session) = server.new_session() {
+                        tokio::spawn(run(session, stream, peer.to_string()));
+                    }
+                }
+                Err(err) => warn!("Failed to accept a connection: {}", err),
+            },
+            _ = stop.cancelled() => {
+                info!("Stopped serving MCP on tcp:{}", address);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{bind_unix, serve_unix};
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use anyhow::{bail, Context};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use tokio::net::UnixListener;
+
+    /// Listens on the socket at `path`, readable and writable by its owner
+    /// only, replacing a socket left behind by an earlier run. A socket
+    /// another server still listens on, or any other file there, is left
+    /// alone and fails the bind.
+    pub fn bind_unix(path: &Path) -> Result<UnixListener> {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                bail!("{} exists and is not a socket", path.display());
+            }
+            if UnixStream::connect(path).is_ok() {
+                bail!("another server is listening on {}", path.display());
+            }
+            debug!("Removing stale socket {}", path.display());
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to listen on {}", path.display()))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict access to {}", path.display()))?;
+        Ok(listener)
+    }
+
+    /// Like [`serve_tcp`], for connections to the Unix domain socket of
+    /// `listener`, which is removed once serving stops.
+    pub async fn serve_unix(
+        server: ExaspoonDbServer,
+        listener: UnixListener,
+        stop: CancellationToken,
+    ) -> Result<()> {
+        let path = listener
+            .local_addr()?
+            .as_pathname()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        info!("Serving MCP on unix:{}", path.display());
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        if let Some(session) = server.new_session() {
+                            tokio::spawn(run(session, stream, path.display().to_string()));
+                        }
+                    }
+                    Err(err) => warn!("Failed to accept a connection: {}", err),
+                },
+                _ = stop.cancelled() => {
+                    if let Err(err) = std::fs::remove_file(&path) {
+                        warn!("Failed to remove socket {}: {}", path.display(), err);
+                    }
+                    info!("Stopped serving MCP on unix:{}", path.display());
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Runs `session` over `stream` until the client disconnects.
+async fn run<S>(session: ExaspoonDbServer, stream: S, peer: String)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    info!("Client session started from {}", peer);
+    match session.serve(stream).await {
+        Ok(running) => {
+            let reason = running.waiting().await;
+            debug!("Client session from {} ended: {:?}", peer, reason);
+        }
+        Err(err) => warn!("Client session from {} failed to start: {}", peer, err),
+    }
+}
@@ -0,0 +1,1283 @@
+use crate::{
+    batch::{self, BatchFailed, BatchStep, MissingRow},
+    embedding::cosine_similarity,
+    models::{
+        Account, AggregateSpendingInput, AuditEvent, BankLink, BatchStepResult, Categorized, Category,
+        CategoryKind, CategoryMatch, CreateTransactionInput, EmbeddingIssue, ListAccountsInput, ListAuditEventsInput,
+        ListTransactionsInput, RecordKind,
+        SpendingBucket, SpendingGroupBy, SpendingPeriod, Transaction, TransactionFilters,
+        TransactionMatch, TransactionTextMatch, UpsertAccountInput, UpsertCategoryInput,
+    },
+    supabase::{
+        decode_row, embedded_table, embedding_issue, page_limit, resolve_limit, search_terms,
+        Database, AUDIT_LOG_TABLE, BANK_LINKS_TABLE, DEFAULT_AUDIT_PAGE, DEFAULT_TEXT_SEARCH_LIMIT,
+        DEFAULT_TRANSACTION_PAGE,
+    },
+};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use rusqlite::{params, types::ValueRef, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tracing::{debug, info, instrument};
+
+const SCHEMA: &str = "
+create table if not exists accounts (
+  id           text primary key default (lower(hex(randomblob(16)))),
+  name         text not null,
+  type         text not null check (type in ('onchain','offchain','exchange')),
+  currency     text not null,
+  network      text,
+  institution  text,
+  metadata     text,
+  created_at   text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  deleted_at   text,
+  unique (name, type)
+);
+
+create table if not exists categories (
+  id           text primary key default (lower(hex(randomblob(16)))),
+  name         text not null unique,
+  kind         text not null default 'expense' check (kind in ('income','expense','transfer')),
+  description  text,
+  embedding    blob,
+  created_at   text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  deleted_at   text
+);
+
+create table if not exists transactions (
+  id           text primary key default (lower(hex(randomblob(16)))),
+  account_id   text not null references accounts(id) on delete cascade,
+  amount       real not null,
+  currency     text not null,
+  direction    text not null check (direction in ('income','expense','transfer')),
+  occurred_at  text not null,
+  description  text,
+  raw_source   text,
+  category_id  text references categories(id),
+  embedding    blob,
+  created_at   text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  deleted_at   text
+);
+
+create index if not exists transactions_account_idx
+  on transactions(account_id, occurred_at);
+
+create table if not exists audit_log (
+  id           text primary key default (lower(hex(randomblob(16)))),
+  tool         text not null,
+  input_hash   text not null,
+  record_id    text,
+  session_id   text,
+  occurred_at  text not null,
+  details      text
+);
+
+create index if not exists audit_log_record_idx
+  on audit_log(record_id, occurred_at);
+
+create table if not exists tool_calls (
+  id           text primary key default (lower(hex(randomblob(16)))),
+  tool         text not null,
+  input_hash   text not null,
+  record_id    text,
+  session_id   text,
+  occurred_at  text not null,
+  details      text
+);
+
+create table if not exists bank_links (
+  account_id        text primary key references accounts(id) on delete cascade,
+  item_id           text not null,
+  access_token      text not null,
+  plaid_account_id  text,
+  cursor            text,
+  updated_at        text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+";
+
+/// A single-file SQLite backend for running the server fully offline.
+///
+/// Embeddings are stored as little-endian `f32` blobs and searched with a
+/// brute-force cosine scan, which is fast enough for a personal ledger.
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDatabase {
+    #[instrument(skip(path), fields(path = %path.as_ref().display()))]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        info!("Opening SQLite database");
+        let conn = Connection::open(path.as_ref())
+            .with_context(|| format!("failed to open SQLite database {}", path.as_ref().display()))?;
+        Self::initialize(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory().context("failed to open in-memory SQLite")?;
+        Self::initialize(conn)
+    }
+
+    fn initialize(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", "on")
+            .context("failed to enable SQLite foreign keys")?;
+        conn.execute_batch(SCHEMA)
+            .context("failed to create SQLite schema")?;
+        // Files created before soft delete existed lack the column.
+        for kind in RecordKind::ALL {
+            add_missing_column(&conn, kind.table(), "deleted_at", "text")?;
+        }
+        add_missing_column(&conn, AUDIT_LOG_TABLE, "details", "text")?;
+        add_missing_column(&conn, "accounts", "metadata", "text")?;
+        allow_exchange_accounts(&conn)?;
+        debug!("SQLite schema ready");
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs blocking SQLite work off the async runtime.
+    async fn with_conn<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow!("SQLite connection lock poisoned"))?;
+            work(&conn)
+        })
+        .await
+        .context("SQLite task panicked")?
+    }
+}
+
+#[async_trait]
+impl Database for SqliteDatabase {
+    #[instrument(skip(self, input), fields(account_id = %input.account_id, amount = %input.amount))]
+    async fn insert_transaction(
+        &self,
+        input: &CreateTransactionInput,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<Transaction> {
+        let start_time = Instant::now();
+        info!("Inserting transaction into SQLite");
+
+        let input = input.clone();
+        let result = self
+            .with_conn(move |conn| insert_transaction_row(conn, &input, embedding.as_deref()))
+            .await?;
+
+        info!("Transaction inserted successfully in {:?}", start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self, rows), fields(count = rows.len()))]
+    async fn insert_transactions(
+        &self,
+        rows: &[(CreateTransactionInput, Option<Vec<f32>>)],
+    ) -> Result<Vec<Transaction>> {
+        let start_time = Instant::now();
+        info!("Inserting {} transactions into SQLite", rows.len());
+
+        let rows = rows.to_vec();
+        let result = self
+            .with_conn(move |conn| {
+                let tx = conn
+                    .unchecked_transaction()
+                    .context("failed to begin SQLite transaction")?;
+                let inserted = rows
+                    .iter()
+                    .map(|(input, embedding)| {
+                        insert_transaction_row(&tx, input, embedding.as_deref())
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                tx.commit().context("failed to commit transactions")?;
+                Ok(inserted)
+            })
+            .await?;
+
+        info!("Inserted {} transactions in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self, input), fields(category_name = %input.name, kind = ?input.kind))]
+    async fn upsert_category(
+        &self,
+        input: &UpsertCategoryInput,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<Category> {
+        let start_time = Instant::now();
+        info!("Upserting category in SQLite");
+
+        let name = input.name.clone();
+        let kind = input.kind.unwrap_or(CategoryKind::Expense);
+        let description = input
+            .description
+            .clone()
+            .unwrap_or_else(|| input.name.clone());
+        let result = self
+            .with_conn(move |conn| {
+                let row = conn
+                    .query_row(
+                        "insert into categories (name, kind, description, embedding)
+                         values (?1, ?2, ?3, ?4)
+                         on conflict (name) do update set
+                           kind = excluded.kind,
+                           description = excluded.description,
+                           embedding = excluded.embedding,
+                           deleted_at = null
+                         returning *",
+                        params![
+                            name,
+                            kind.as_ref(),
+                            description,
+                            embedding.as_deref().map(encode_embedding),
+                        ],
+                        row_json,
+                    )
+                    .context("failed to upsert category")?;
+                decode_row("categories", row)
+            })
+            .await?;
+
+        info!("Category upserted successfully in {:?}", start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self, input), fields(account_name = %input.name, account_type = %input.r#type))]
+    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Account> {
+        let start_time = Instant::now();
+        info!("Upserting account in SQLite");
+
+        let input = input.clone();
+        let result = self
+            .with_conn(move |conn| upsert_account_row(conn, &input))
+            .await?;
+
+        info!("Account upserted successfully in {:?}", start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(id = %id, category_id = ?category_id))]
+    async fn categorize_transaction(
+        &self,
+        id: &str,
+        category_id: Option<&str>,
+    ) -> Result<Option<Categorized>> {
+        info!("Categorizing transaction {} in SQLite", id);
+        let id = id.to_string();
+        let category_id = category_id.map(str::to_string);
+        self.with_conn(move |conn| {
+            let tx = conn
+                .unchecked_transaction()
+                .context("failed to begin SQLite transaction")?;
+            let categorized = categorize_transaction_row(&tx, &id, category_id.as_deref())?;
+            tx.commit().context("failed to commit categorization")?;
+            Ok(categorized)
+        })
+        .await
+    }
+
+    #[instrument(skip(self, steps), fields(count = steps.len()))]
+    async fn execute_batch(&self, steps: &[BatchStep]) -> Result<Vec<BatchStepResult>> {
+        let start_time = Instant::now();
+        info!("Executing batch of {} steps in SQLite", steps.len());
+
+        let steps = steps.to_vec();
+        let result = self
+            .with_conn(move |conn| {
+                let tx = conn
+                    .unchecked_transaction()
+                    .context("failed to begin SQLite transaction")?;
+                let mut done = Vec::with_capacity(steps.len());
+                for (index, step) in steps.iter().enumerate() {
+                    let result =
+                        apply_batch_step(&tx, step, &done).map_err(|source| BatchFailed {
+                            step: index,
+                            rolled_back: true,
+                            source,
+                        })?;
+                    done.push(result);
+                }
+                tx.commit().context("failed to commit batch")?;
+                Ok(done)
+            })
+            .await?;
+
+        info!("Executed batch of {} steps in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self, params), fields(account_type = ?params.r#type, search = ?params.search))]
+    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Account>> {
+        let start_time = Instant::now();
+        info!("Listing accounts from SQLite");
+
+        let (account_type, search) = account_filter_params(params);
+        let limit = page_limit(params.limit, None).map_or(-1, i64::from);
+        let offset = params.offset.unwrap_or(0);
+        let result: Vec<Account> = self
+            .with_conn(move |conn| {
+                let mut statement = conn.prepare(&format!(
+                    "select * from accounts where {ACCOUNT_FILTERS}
+                     order by name limit ?3 offset ?4"
+                ))?;
+                let rows = statement
+                    .query_map(params![account_type, search, limit, offset], row_json)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                    .context("failed to list accounts")?;
+                rows.into_iter()
+                    .map(|row| decode_row("accounts", row))
+                    .collect()
+            })
+            .await?;
+
+        info!("Listed {} accounts in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    async fn count_accounts(&self, params: &ListAccountsInput) -> Result<u64> {
+        let (account_type, search) = account_filter_params(params);
+        self.with_conn(move |conn| {
+            let count: i64 = conn
+                .query_row(
+                    &format!("select count(*) from accounts where {ACCOUNT_FILTERS}"),
+                    params![account_type, search],
+                    |row| row.get(0),
+                )
+                .context("failed to count accounts")?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    #[instrument(skip(self), fields(limit = ?input.limit, offset = ?input.offset))]
+    async fn list_transactions(&self, input: &ListTransactionsInput) -> Result<Vec<Transaction>> {
+        let start_time = Instant::now();
+        info!("Listing transactions from SQLite");
+
+        let filters = input.filters.clone();
+        let limit = page_limit(input.limit, Some(DEFAULT_TRANSACTION_PAGE)).map_or(-1, i64::from);
+        let offset = input.offset.unwrap_or(0);
+        let result: Vec<Transaction> = self
+            .with_conn(move |conn| {
+                let mut statement = conn.prepare(&format!(
+                    "select * from transactions where {TRANSACTION_FILTERS}
+                     order by occurred_at desc, id desc limit ?6 offset ?7"
+                ))?;
+                let [from, to, account_id, category_id, direction] =
+                    transaction_filter_params(&filters);
+                let rows = statement
+                    .query_map(
+                        params![from, to, account_id, category_id, direction, limit, offset],
+                        row_json,
+                    )?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                    .context("failed to list transactions")?;
+                rows.into_iter()
+                    .map(|row| decode_row("transactions", row))
+                    .collect()
+            })
+            .await?;
+
+        info!("Listed {} transactions in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    async fn largest_transactions(
+        &self,
+        filters: &TransactionFilters,
+        limit: u32,
+    ) -> Result<Vec<Transaction>> {
+        let filters = filters.clone();
+        self.with_conn(move |conn| {
+            let mut statement = conn.prepare(&format!(
+                "select * from transactions where {TRANSACTION_FILTERS}
+                 order by amount desc, occurred_at desc, id desc limit ?6"
+            ))?;
+            let [from, to, account_id, category_id, direction] =
+                transaction_filter_params(&filters);
+            let rows = statement
+                .query_map(
+                    params![from, to, account_id, category_id, direction, limit],
+                    row_json,
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .context("failed to list the largest transactions")?;
+            rows.into_iter()
+                .map(|row| decode_row("transactions", row))
+                .collect()
+        })
+        .await
+    }
+
+    async fn count_transactions(&self, filters: &TransactionFilters) -> Result<u64> {
+        let filters = filters.clone();
+        self.with_conn(move |conn| {
+            let count: i64 = conn
+                .query_row(
+                    &format!("select count(*) from transactions where {TRANSACTION_FILTERS}"),
+                    transaction_filter_params(&filters),
+                    |row| row.get(0),
+                )
+                .context("failed to count transactions")?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
+    async fn search_similar_transactions(
+        &self,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionMatch>> {
+        let start_time = Instant::now();
+        info!("Searching for similar transactions");
+
+        let result = self
+            .search("transactions", embedding, limit)
+            .await?
+            .into_iter()
+            .map(|(transaction, similarity)| TransactionMatch {
+                transaction,
+                similarity: Some(similarity),
+            })
+            .collect::<Vec<_>>();
+
+        info!("Found {} similar transactions in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
+    async fn search_similar_categories(
+        &self,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<CategoryMatch>> {
+        let start_time = Instant::now();
+        info!("Searching for similar categories");
+
+        let result = self
+            .search("categories", embedding, limit)
+            .await?
+            .into_iter()
+            .map(|(category, similarity)| CategoryMatch {
+                category,
+                similarity: Some(similarity),
+            })
+            .collect::<Vec<_>>();
+
+        info!("Found {} similar categories in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    async fn list_category_names(&self, search: &str, limit: u32) -> Result<Vec<String>> {
+        let needle = search.trim().to_lowercase();
+        self.with_conn(move |conn| {
+            let mut statement = conn.prepare(
+                "select name from categories
+                 where deleted_at is null and instr(lower(name), ?1) > 0
+                 order by name limit ?2",
+            )?;
+            let names = statement
+                .query_map(params![needle, limit], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+                .context("failed to list category names")?;
+            Ok(names)
+        })
+        .await
+    }
+
+    async fn list_categories(&self) -> Result<Vec<Category>> {
+        self.with_conn(|conn| {
+            let mut statement = conn.prepare(
+                "select id, name, kind, description, created_at from categories
+                 where deleted_at is null order by name",
+            )?;
+            let rows = statement
+                .query_map([], row_json)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .context("failed to list categories")?;
+            rows.into_iter()
+                .map(|row| decode_row("categories", row))
+                .collect()
+        })
+        .await
+    }
+
+    async fn search_transactions_text(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionTextMatch>> {
+        let start_time = Instant::now();
+        info!("Searching transactions for {:?}", query);
+
+        let terms = search_terms(query);
+        let limit = page_limit(limit, Some(DEFAULT_TEXT_SEARCH_LIMIT)).map_or(-1, i64::from);
+        let result: Vec<Transaction> = self
+            .with_conn(move |conn| {
+                let conditions: String = (1..=terms.len())
+                    .map(|n| {
+                        format!(
+                            " and instr(lower(coalesce(description, '') || ' ' \
+                             || coalesce(raw_source, '')), ?{n}) > 0"
+                        )
+                    })
+                    .collect();
+                let mut statement = conn.prepare(&format!(
+                    "select * from transactions where deleted_at is null{conditions}
+                     order by occurred_at desc, id desc limit ?{}",
+                    terms.len() + 1
+                ))?;
+                let values = terms
+                    .into_iter()
+                    .map(rusqlite::types::Value::Text)
+                    .chain([rusqlite::types::Value::Integer(limit)]);
+                let rows = statement
+                    .query_map(rusqlite::params_from_iter(values), row_json)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                    .context("failed to search transactions")?;
+                rows.into_iter()
+                    .map(|row| decode_row("transactions", row))
+                    .collect()
+            })
+            .await?;
+
+        info!("Found {} matching transactions in {:?}", result.len(), start_time.elapsed());
+        Ok(result
+            .into_iter()
+            .map(|transaction| TransactionTextMatch {
+                transaction,
+                rank: None,
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self), fields(group_by = ?input.group_by, period = ?input.period))]
+    async fn aggregate_spending(
+        &self,
+        input: &AggregateSpendingInput,
+    ) -> Result<Vec<SpendingBucket>> {
+        let start_time = Instant::now();
+        info!("Aggregating spending in SQLite");
+
+        let key = match input.group_by {
+            SpendingGroupBy::Category => "category_id",
+            SpendingGroupBy::Account => "account_id",
+            SpendingGroupBy::Direction => "direction",
+        };
+        // Buckets are rendered as UTC timestamps; weeks start on Monday.
+        let period_start = match input.period {
+            None => "null",
+            Some(SpendingPeriod::Day) => "strftime('%Y-%m-%dT00:00:00Z', occurred_at)",
+            Some(SpendingPeriod::Week) => {
+                "strftime('%Y-%m-%dT00:00:00Z', occurred_at, '-6 days', 'weekday 1')"
+            }
+            Some(SpendingPeriod::Month) => "strftime('%Y-%m-01T00:00:00Z', occurred_at)",
+            Some(SpendingPeriod::Year) => "strftime('%Y-01-01T00:00:00Z', occurred_at)",
+        };
+        let sql = format!(
+            "select {key} as key, {period_start} as period_start, currency,
+                    sum(amount) as total, count(*) as count
+             from transactions
+             where {TRANSACTION_FILTERS}
+             group by 1, 2, 3
+             order by 2 nulls first, 4 desc"
+        );
+        let filters = input.filters.clone();
+        let result = self
+            .with_conn(move |conn| {
+                let mut statement = conn.prepare(&sql)?;
+                let rows = statement
+                    .query_map(transaction_filter_params(&filters), row_json)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                    .context("failed to aggregate spending")?;
+                rows.into_iter()
+                    .map(|row| decode_row("aggregate_spending", row))
+                    .collect::<Result<Vec<SpendingBucket>>>()
+            })
+            .await?;
+
+        info!("Aggregated spending into {} buckets in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self, events), fields(count = events.len()))]
+    async fn record_audit_events(&self, events: &[AuditEvent]) -> Result<()> {
+        let events = events.to_vec();
+        self.with_conn(move |conn| {
+            let tx = conn
+                .unchecked_transaction()
+                .context("failed to begin SQLite transaction")?;
+            for event in &events {
+                tx.execute(
+                    "insert into audit_log
+                       (tool, input_hash, record_id, session_id, occurred_at, details)
+                     values (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        event.tool,
+                        event.input_hash,
+                        event.record_id,
+                        event.session_id,
+                        event.occurred_at,
+                        event.details.as_ref().map(Value::to_string),
+                    ],
+                )
+                .context("failed to record audit event")?;
+            }
+            tx.commit().context("failed to commit audit events")?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(skip(self, calls), fields(count = calls.len()))]
+    async fn record_tool_calls(&self, calls: &[AuditEvent]) -> Result<()> {
+        let calls = calls.to_vec();
+        self.with_conn(move |conn| {
+            let tx = conn
+                .unchecked_transaction()
+                .context("failed to begin SQLite transaction")?;
+            for call in &calls {
+                tx.execute(
+                    "insert into tool_calls
+                       (tool, input_hash, record_id, session_id, occurred_at, details)
+                     values (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        call.tool,
+                        call.input_hash,
+                        call.record_id,
+                        call.session_id,
+                        call.occurred_at,
+                        call.details.as_ref().map(Value::to_string),
+                    ],
+                )
+                .context("failed to record tool call")?;
+            }
+            tx.commit().context("failed to commit tool calls")?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(skip(self), fields(tool = ?input.tool, record_id = ?input.record_id))]
+    async fn list_audit_events(&self, input: &ListAuditEventsInput) -> Result<Vec<AuditEvent>> {
+        let start_time = Instant::now();
+        info!("Listing audit events from SQLite");
+
+        let tool = input.tool.clone();
+        let record_id = input.record_id.clone();
+        let limit = page_limit(input.limit, Some(DEFAULT_AUDIT_PAGE)).map_or(-1, i64::from);
+        let offset = input.offset.unwrap_or(0);
+        let result: Vec<AuditEvent> = self
+            .with_conn(move |conn| {
+                let mut statement = conn.prepare(
+                    "select * from audit_log
+                     where (?1 is null or tool = ?1) and (?2 is null or record_id = ?2)
+                     order by occurred_at desc, rowid desc limit ?3 offset ?4",
+                )?;
+                let rows = statement
+                    .query_map(params![tool, record_id, limit, offset], row_json)?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+                    .context("failed to list audit events")?;
+                rows.into_iter()
+                    .map(|mut row| {
+                        // `details` is stored as JSON text.
+                        if let Some(Value::String(details)) = row.get("details") {
+                            row["details"] = serde_json::from_str(details)
+                                .context("failed to parse audit event details")?;
+                        }
+                        decode_row(AUDIT_LOG_TABLE, row)
+                    })
+                    .collect()
+            })
+            .await?;
+
+        info!("Listed {} audit events in {:?}", result.len(), start_time.elapsed());
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn soft_delete(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        info!("Soft-deleting {} record {}", kind.table(), id);
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            let changed = conn
+                .execute(
+                    &format!(
+                        "update {} set deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+                         where id = ?1 and deleted_at is null",
+                        kind.table()
+                    ),
+                    params![id],
+                )
+                .with_context(|| format!("failed to soft-delete from {}", kind.table()))?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    async fn record_exists(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                &format!(
+                    "select exists(select 1 from {} where id = ?1 and deleted_at is null)",
+                    kind.table()
+                ),
+                params![id],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("failed to look up {} row", kind.table()))
+        })
+        .await
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn delete_record(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        info!("Deleting {} record {}", kind.table(), id);
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            let tx = conn
+                .unchecked_transaction()
+                .context("failed to begin SQLite transaction")?;
+            if kind == RecordKind::Category {
+                tx.execute(
+                    "update transactions set category_id = null where category_id = ?1",
+                    params![id],
+                )
+                .context("failed to uncategorize transactions")?;
+            }
+            let deleted = tx
+                .execute(
+                    &format!("delete from {} where id = ?1", kind.table()),
+                    params![id],
+                )
+                .with_context(|| format!("failed to delete from {}", kind.table()))?;
+            tx.commit().context("failed to commit delete")?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn purge_deleted(&self, kind: RecordKind, deleted_before: Option<&str>) -> Result<u64> {
+        let start_time = Instant::now();
+        info!("Purging soft-deleted {} rows from SQLite", kind.table());
+
+        let deleted_before = deleted_before.map(str::to_string);
+        let purged = self
+            .with_conn(move |conn| {
+                let purgeable = "deleted_at is not null and (?1 is null or deleted_at < ?1)";
+                let tx = conn
+                    .unchecked_transaction()
+                    .context("failed to begin SQLite transaction")?;
+                // Purging a category leaves its transactions uncategorized.
+                if kind == RecordKind::Category {
+                    tx.execute(
+                        &format!(
+                            "update transactions set category_id = null
+                             where category_id in (select id from categories where {purgeable})"
+                        ),
+                        params![deleted_before],
+                    )
+                    .context("failed to uncategorize transactions")?;
+                }
+                let purged = tx
+                    .execute(
+                        &format!("delete from {} where {purgeable}", kind.table()),
+                        params![deleted_before],
+                    )
+                    .with_context(|| format!("failed to purge {}", kind.table()))?;
+                tx.commit().context("failed to commit purge")?;
+                Ok(purged as u64)
+            })
+            .await?;
+
+        info!("Purged {} {} rows in {:?}", purged, kind.table(), start_time.elapsed());
+        Ok(purged)
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn embedding_issues(
+        &self,
+        kind: RecordKind,
+        dimension: usize,
+        after: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<EmbeddingIssue>> {
+        let table = embedded_table(kind)?;
+        let content = match kind {
+            RecordKind::Category => "coalesce(nullif(trim(description), ''), name)",
+            _ => "description",
+        };
+        let after = after.map(str::to_string);
+        self.with_conn(move |conn| {
+            let mut statement = conn.prepare(&format!(
+                "select id, deleted_at is not null, {content}, embedding from {table}
+                 where ?1 is null or id > ?1 order by id"
+            ))?;
+            let mut rows = statement.query(params![after])?;
+            let mut issues = Vec::new();
+            while issues.len() < limit as usize {
+                let Some(row) = rows.next()? else {
+                    break;
+                };
+                let content: Option<String> = row.get(2)?;
+                let stored: Option<Vec<u8>> = row.get(3)?;
+                let stored = stored.map(|bytes| decode_embedding(&bytes).len());
+                let deleted: bool = row.get(1)?;
+                if let Some(issue) = embedding_issue(deleted, content.as_deref(), stored, dimension)
+                {
+                    issues.push(EmbeddingIssue {
+                        id: row.get(0)?,
+                        issue,
+                        content,
+                    });
+                }
+            }
+            Ok(issues)
+        })
+        .await
+        .with_context(|| format!("failed to scan {table} embeddings"))
+    }
+
+    #[instrument(skip(self, embedding), fields(table = %kind.table()))]
+    async fn set_embedding(
+        &self,
+        kind: RecordKind,
+        id: &str,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<()> {
+        let table = embedded_table(kind)?;
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            let changed = conn
+                .execute(
+                    &format!("update {table} set embedding = ?1 where id = ?2"),
+                    params![embedding.as_deref().map(encode_embedding), id],
+                )
+                .with_context(|| format!("failed to update {table} embedding"))?;
+            if changed == 0 {
+                return Err(anyhow!("no {table} row with id {id}"));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn bank_link(&self, account_id: &str) -> Result<Option<BankLink>> {
+        let account_id = account_id.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "select * from bank_links where account_id = ?1",
+                params![account_id],
+                row_json,
+            )
+            .optional()
+            .context("failed to read bank link")?
+            .map(|row| decode_row(BANK_LINKS_TABLE, row))
+            .transpose()
+        })
+        .await
+    }
+
+    #[instrument(skip(self, link), fields(account_id = %link.account_id))]
+    async fn save_bank_link(&self, link: &BankLink) -> Result<BankLink> {
+        let link = link.clone();
+        self.with_conn(move |conn| {
+            let row = conn
+                .query_row(
+                    "insert into bank_links
+                       (account_id, item_id, access_token, plaid_account_id, cursor)
+                     values (?1, ?2, ?3, ?4, ?5)
+                     on conflict (account_id) do update set
+                       item_id = excluded.item_id,
+                       access_token = excluded.access_token,
+                       plaid_account_id = excluded.plaid_account_id,
+                       cursor = excluded.cursor,
+                       updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+                     returning *",
+                    params![
+                        link.account_id,
+                        link.item_id,
+                        link.access_token,
+                        link.plaid_account_id,
+                        link.cursor,
+                    ],
+                    row_json,
+                )
+                .context("failed to save bank link")?;
+            decode_row(BANK_LINKS_TABLE, row)
+        })
+        .await
+    }
+
+    async fn account_metadata(&self, account_id: &str) -> Result<Map<String, Value>> {
+        let account_id = account_id.to_string();
+        self.with_conn(move |conn| stored_metadata(conn, &account_id))
+            .await
+    }
+
+    #[instrument(skip(self, value))]
+    async fn set_account_metadata(&self, account_id: &str, key: &str, value: Value) -> Result<()> {
+        let (account_id, key) = (account_id.to_string(), key.to_string());
+        self.with_conn(move |conn| {
+            let mut metadata = stored_metadata(conn, &account_id)?;
+            metadata.insert(key, value);
+            conn.execute(
+                "update accounts set metadata = ?2 where id = ?1",
+                params![account_id, Value::Object(metadata).to_string()],
+            )
+            .context("failed to save account metadata")?;
+            Ok(())
+        })
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn set_account_network(&self, account_id: &str, network: &str) -> Result<()> {
+        let (account_id, network) = (account_id.to_string(), network.to_string());
+        self.with_conn(move |conn| {
+            let updated = conn
+                .execute(
+                    "update accounts set network = ?2 where id = ?1 and deleted_at is null",
+                    params![account_id, network],
+                )
+                .context("failed to save account network")?;
+            if updated == 0 {
+                return Err(anyhow!("accounts record {account_id} was not found"));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.query_row("select 1", [], |_| Ok(()))
+                .context("SQLite ping failed")
+        })
+        .await
+    }
+}
+
+impl SqliteDatabase {
+    /// Scores every embedded row of `table` against `embedding` and returns the
+    /// best matches, most similar first.
+    async fn search<T>(
+        &self,
+        table: &'static str,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<(T, f64)>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let limit = resolve_limit(limit) as usize;
+        self.with_conn(move |conn| {
+            let mut statement = conn.prepare(&format!(
+                "select * from {table} where embedding is not null and deleted_at is null"
+            ))?;
+            let mut scored = statement
+                .query_map([], |row| {
+                    let stored: Vec<u8> = row.get("embedding")?;
+                    Ok((row_json(row)?, stored))
+                })?
+                .filter_map(|row| match row {
+                    Ok((value, stored)) => {
+                        cosine_similarity(&embedding, &decode_embedding(&stored))
+                            .map(|similarity| Ok((value, similarity)))
+                    }
+                    Err(err) => Some(Err(err)),
+                })
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .with_context(|| format!("failed to scan {table} embeddings"))?;
+
+            scored.sort_by(|left, right| right.1.total_cmp(&left.1));
+            scored.truncate(limit);
+            scored
+                .into_iter()
+                .map(|(value, similarity)| Ok((decode_row(table, value)?, similarity)))
+                .collect()
+        })
+        .await
+    }
+}
+
+/// The `metadata` of the live account `account_id`, stored as JSON text.
+fn stored_metadata(conn: &Connection, account_id: &str) -> Result<Map<String, Value>> {
+    let metadata: Option<String> = conn
+        .query_row(
+            "select metadata from accounts where id = ?1 and deleted_at is null",
+            params![account_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("failed to read account metadata")?
+        .ok_or_else(|| anyhow!("accounts record {account_id} was not found"))?;
+    match metadata {
+        Some(metadata) => serde_json::from_str(&metadata)
+            .with_context(|| format!("metadata of account {account_id} is not a JSON object")),
+        None => Ok(Map::new()),
+    }
+}
+
+/// Adds `column` to `table` unless a previous version already created it.
+fn add_missing_column(conn: &Connection, table: &str, column: &str, ty: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("select 1 from pragma_table_info('{table}') where name = ?1"))?
+        .exists(params![column])?;
+    if !exists {
+        conn.execute_batch(&format!("alter table {table} add column {column} {ty}"))
+            .with_context(|| format!("failed to add {table}.{column}"))?;
+        info!("Added column {}.{}", table, column);
+    }
+    Ok(())
+}
+
+/// Rebuilds the accounts table of files created before exchange accounts
+/// existed, whose type check rejects them. SQLite cannot alter a check, so
+/// the table is copied into one with the current definition, following
+/// SQLite's steps for altering a table with foreign keys off.
+fn allow_exchange_accounts(conn: &Connection) -> Result<()> {
+    let sql: String = conn.query_row(
+        "select sql from sqlite_master where type = 'table' and name = 'accounts'",
+        [],
+        |row| row.get(0),
+    )?;
+    if sql.contains("'exchange'") {
+        return Ok(());
+    }
+    conn.pragma_update(None, "foreign_keys", "off")?;
+    let rebuilt = conn
+        .execute_batch(
+            "begin;
+            create table accounts_new (
+              id           text primary key default (lower(hex(randomblob(16)))),
+              name         text not null,
+              type         text not null check (type in ('onchain','offchain','exchange')),
+              currency     text not null,
+              network      text,
+              institution  text,
+              metadata     text,
+              created_at   text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+              deleted_at   text,
+              unique (name, type)
+            );
+            insert into accounts_new
+              (id, name, type, currency, network, institution, metadata, created_at, deleted_at)
+              select id, name, type, currency, network, institution, metadata, created_at, deleted_at
+              from accounts;
+            drop table accounts;
+            alter table accounts_new rename to accounts;
+            commit;",
+        )
+        .context("failed to allow exchange accounts");
+    if rebuilt.is_err() {
+        let _ = conn.execute_batch("rollback");
+    }
+    conn.pragma_update(None, "foreign_keys", "on")?;
+    rebuilt?;
+    info!("Rebuilt accounts to allow exchange accounts");
+    Ok(())
+}
+
+/// Converts a row into a JSON object keyed by column name, skipping blobs so
+/// embeddings never leak into tool output.
+fn row_json(row: &Row<'_>) -> rusqlite::Result<Value> {
+    let mut object = Map::new();
+    for (index, name) in row.as_ref().column_names().into_iter().enumerate() {
+        let value = match row.get_ref(index)? {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(value) => Value::from(value),
+            ValueRef::Real(value) => Value::from(value),
+            ValueRef::Text(value) => Value::from(String::from_utf8_lossy(value).into_owned()),
+            ValueRef::Blob(_) => continue,
+        };
+        object.insert(name.to_string(), value);
+    }
+    Ok(Value::Object(object))
+}
+
+fn insert_transaction_row(
+    conn: &Connection,
+    input: &CreateTransactionInput,
+    embedding: Option<&[f32]>,
+) -> Result<Transaction> {
+    let row = conn
+        .query_row(
+            "insert into transactions
+               (account_id, amount, currency, direction, occurred_at, description,
+                raw_source, embedding)
+             values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             returning *",
+            params![
+                input.account_id,
+                input.amount,
+                input.currency,
+                input.direction.as_ref(),
+                input.occurred_at,
+                input.description,
+                input.raw_source,
+                embedding.map(encode_embedding),
+            ],
+            row_json,
+        )
+        .context("failed to insert transaction")?;
+    decode_row("transactions", row)
+}
+
+fn upsert_account_row(conn: &Connection, input: &UpsertAccountInput) -> Result<Account> {
+    let row = conn
+        .query_row(
+            "insert into accounts (name, type, currency, network, institution)
+             values (?1, ?2, ?3, ?4, ?5)
+             on conflict (name, type) do update set
+               currency = excluded.currency,
+               network = excluded.network,
+               institution = excluded.institution,
+               deleted_at = null
+             returning *",
+            params![
+                input.name,
+                input.r#type.as_ref(),
+                input.currency,
+                input.network,
+                input.institution,
+            ],
+            row_json,
+        )
+        .context("failed to upsert account")?;
+    decode_row("accounts", row)
+}
+
+fn categorize_transaction_row(
+    conn: &Connection,
+    id: &str,
+    category_id: Option<&str>,
+) -> Result<Option<Categorized>> {
+    if let Some(category_id) = category_id {
+        let live = conn
+            .prepare("select 1 from categories where id = ?1 and deleted_at is null")?
+            .exists(params![category_id])?;
+        if !live {
+            return Err(MissingRow(format!("no live category with id {category_id}")).into());
+        }
+    }
+    let previous: Option<Option<String>> = conn
+        .query_row(
+            "select category_id from transactions where id = ?1 and deleted_at is null",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("failed to read transaction category")?;
+    let Some(previous_category_id) = previous else {
+        return Ok(None);
+    };
+    let row = conn
+        .query_row(
+            "update transactions set category_id = ?2 where id = ?1 returning *",
+            params![id, category_id],
+            row_json,
+        )
+        .context("failed to categorize transaction")?;
+    Ok(Some(Categorized {
+        transaction: decode_row("transactions", row)?,
+        previous_category_id,
+    }))
+}
+
+/// Applies one batch step inside the batch's transaction.
+fn apply_batch_step(
+    conn: &Connection,
+    step: &BatchStep,
+    done: &[BatchStepResult],
+) -> Result<BatchStepResult> {
+    match step {
+        BatchStep::UpsertAccount(input) => Ok(BatchStepResult::UpsertAccount {
+            account: upsert_account_row(conn, input)?,
+        }),
+        BatchStep::CreateTransactions(rows) => {
+            let transactions = rows
+                .iter()
+                .map(|(input, embedding)| {
+                    let mut input = input.clone();
+                    input.account_id = batch::resolve(&input.account_id, done)?;
+                    insert_transaction_row(conn, &input, embedding.as_deref())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(BatchStepResult::CreateTransactions { transactions })
+        }
+        BatchStep::Categorize(input) => {
+            let id = batch::resolve(&input.transaction_id, done)?;
+            let category_id = batch::resolve(&input.category_id, done)?;
+            let categorized = categorize_transaction_row(conn, &id, Some(&category_id))?
+                .ok_or_else(|| MissingRow(format!("no live transaction with id {id}")))?;
+            Ok(BatchStepResult::Categorize {
+                transaction: categorized.transaction,
+            })
+        }
+    }
+}
+
+/// Where clause over `?1` (type) and `?2` (lowercase name substring).
+const ACCOUNT_FILTERS: &str = "deleted_at is null
+    and (?1 is null or type = ?1)
+    and (?2 is null or instr(lower(name), ?2) > 0)";
+
+/// Where clause over the parameters from [`transaction_filter_params`].
+const TRANSACTION_FILTERS: &str = "deleted_at is null
+    and (?1 is null or occurred_at >= ?1)
+    and (?2 is null or occurred_at < ?2)
+    and (?3 is null or account_id = ?3)
+    and (?4 is null or category_id = ?4)
+    and (?5 is null or direction = ?5)";
+
+fn account_filter_params(params: &ListAccountsInput) -> (Option<String>, Option<String>) {
+    let account_type = params.r#type.map(|value| value.as_ref().to_string());
+    let search = params
+        .search
+        .as_ref()
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty());
+    (account_type, search)
+}
+
+fn transaction_filter_params(filters: &TransactionFilters) -> [Option<String>; 5] {
+    [
+        filters.from.clone(),
+        filters.to.clone(),
+        filters.account_id.clone(),
+        filters.category_id.clone(),
+        filters
+            .direction
+            .map(|direction| direction.as_ref().to_string()),
+    ]
+}
+
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
@@ -0,0 +1,82 @@
+//! Bank statements in the formats European banks export: SWIFT MT940 and
+//! its ISO 20022 successor CAMT.053. Both list booked entries with a
+//! credit or debit mark, the account's currency and free text about the
+//! counterparty; each entry is kept with its source text, which tells
+//! entries imported before from new ones.
+
+use crate::models::{CreateTransactionInput, StatementFormat, TransactionDirection};
+use anyhow::{bail, Result};
+
+mod camt;
+mod mt940;
+
+/// One booked entry of a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementEntry {
+    /// The bank's reference for the entry, when it gives one.
+    pub reference: Option<String>,
+    /// Booking date as an RFC 3339 timestamp at midnight UTC.
+    pub booked_at: String,
+    /// Negative for money leaving the account.
+    pub amount: f64,
+    /// ISO 4217 code of the amount, when the statement states it.
+    pub currency: Option<String>,
+    /// Who paid or was paid.
+    pub counterparty: Option<String>,
+    /// Remittance information or other text about the entry.
+    pub details: Option<String>,
+    /// Not yet booked, e.g. a CAMT entry with status `PDNG`.
+    pub pending: bool,
+    /// The entry as it appeared in the statement, which is stored as the
+    /// transaction's raw source.
+    pub raw: String,
+}
+
+impl StatementEntry {
+    /// The entry as `create_transaction` takes it. `currency` is used when
+    /// the statement states none.
+    pub fn to_input(&self, account_id: &str, currency: &str) -> CreateTransactionInput {
+        let direction = if self.amount < 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        };
+        let description = match (&self.counterparty, &self.details) {
+            (Some(counterparty), Some(details)) => Some(format!("{counterparty} - {details}")),
+            (Some(text), None) | (None, Some(text)) => Some(text.clone()),
+            (None, None) => None,
+        };
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount.abs(),
+            currency: self
+                .currency
+                .clone()
+                .unwrap_or_else(|| currency.to_string()),
+            direction,
+            occurred_at: self.booked_at.clone(),
+            description,
+            raw_source: Some(self.raw.clone()),
+        }
+    }
+}
+
+/// The format of `document`: CAMT.053 for an XML bank-to-customer
+/// statement, MT940 for a document with a `:61:` statement line.
+pub fn detect(document: &str) -> Result<StatementFormat> {
+    if document.contains("BkToCstmrStmt") {
+        Ok(StatementFormat::Camt053)
+    } else if document.contains(":20:") && document.contains(":61:") {
+        Ok(StatementFormat::Mt940)
+    } else {
+        bail!("not an MT940 or CAMT.053 statement")
+    }
+}
+
+/// The entries of a statement in `format`, in file order.
+pub fn parse(document: &str, format: StatementFormat) -> Result<Vec<StatementEntry>> {
+    match format {
+        StatementFormat::Mt940 => mt940::parse(document),
+        StatementFormat::Camt053 => camt::parse(document),
+    }
+}
@@ -0,0 +1,107 @@
+//! ISO 20022 CAMT.053 bank-to-customer statements: XML with one `<Ntry>`
+//! per booked entry under each `<Stmt>`.
+
+use super::StatementEntry;
+use crate::xml::Element;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+
+pub(super) fn parse(document: &str) -> Result<Vec<StatementEntry>> {
+    let root = Element::parse(document)?;
+    let Some(report) = root.descendants("BkToCstmrStmt").next() else {
+        bail!("not a CAMT.053 statement: no <BkToCstmrStmt> element");
+    };
+    let mut entries = Vec::new();
+    for statement in report.children("Stmt") {
+        let currency = statement.text_at(&["Acct", "Ccy"]);
+        for entry in statement.children("Ntry") {
+            let number = entries.len() + 1;
+            let parsed = parse_entry(document, entry, currency.clone())
+                .with_context(|| format!("entry {number}"))?;
+            entries.push(parsed);
+        }
+    }
+    Ok(entries)
+}
+
+fn parse_entry(
+    document: &str,
+    entry: &Element,
+    currency: Option<String>,
+) -> Result<StatementEntry> {
+    let amount = entry.child("Amt").context("no <Amt>")?;
+    let value = amount
+        .text
+        .parse::<f64>()
+        .ok()
+        .with_context(|| format!("amount {:?} is not a number", amount.text))?;
+    let debit = match entry.text_at(&["CdtDbtInd"]).as_deref() {
+        Some("DBIT") => true,
+        Some("CRDT") => false,
+        other => bail!("credit or debit indicator {other:?} is not CRDT or DBIT"),
+    };
+    let status = entry
+        .text_at(&["Sts", "Cd"])
+        .or_else(|| entry.text_at(&["Sts"]))
+        .unwrap_or_default();
+    let date = entry
+        .text_at(&["BookgDt", "Dt"])
+        .or_else(|| entry.text_at(&["BookgDt", "DtTm"]))
+        .or_else(|| entry.text_at(&["ValDt", "Dt"]))
+        .context("no booking date")?;
+
+    let details = entry.descendants("TxDtls").next();
+    let party = if debit { "Cdtr" } else { "Dbtr" };
+    let counterparty = details.and_then(|details| {
+        details
+            .text_at(&["RltdPties", party, "Nm"])
+            .or_else(|| details.text_at(&["RltdPties", party, "Pty", "Nm"]))
+    });
+    let remittance = details
+        .map(|details| {
+            details
+                .descendants("Ustrd")
+                .map(|line| line.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|text| !text.is_empty())
+        .or_else(|| entry.text_at(&["AddtlNtryInf"]));
+    let reference = entry
+        .text_at(&["AcctSvcrRef"])
+        .or_else(|| entry.text_at(&["NtryRef"]))
+        .or_else(|| details.and_then(|details| details.text_at(&["Refs", "AcctSvcrRef"])))
+        .or_else(|| details.and_then(|details| details.text_at(&["Refs", "EndToEndId"])))
+        .filter(|reference| reference != "NOTPROVIDED");
+
+    Ok(StatementEntry {
+        reference,
+        booked_at: booking_date(&date)?,
+        // A reversal (`RvslInd`) is already booked in the direction that
+        // undoes the original entry, so its indicator gives the sign.
+        amount: if debit { -value } else { value },
+        currency: amount.attribute("Ccy").map(str::to_string).or(currency),
+        counterparty,
+        details: remittance,
+        pending: !status.is_empty() && status != "BOOK",
+        raw: document[entry.span.0..entry.span.1]
+            .lines()
+            .map(str::trim)
+            .collect(),
+    })
+}
+
+/// An ISO date or date and time as RFC 3339 in UTC, dates at midnight.
+fn booking_date(value: &str) -> Result<String> {
+    let invalid = || format!("{value:?} is not an ISO date");
+    let parsed = match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => date.and_hms_opt(0, 0, 0).with_context(invalid)?.and_utc(),
+        Err(_) => match DateTime::parse_from_rfc3339(value) {
+            Ok(parsed) => parsed.with_timezone(&Utc),
+            Err(_) => chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .with_context(invalid)?
+                .and_utc(),
+        },
+    };
+    Ok(parsed.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
@@ -0,0 +1,208 @@
+//! SWIFT MT940 customer statements: fields such as `:61:`, each starting a
+//! line, with an optional `:86:` of free text after each statement line.
+
+use super::StatementEntry;
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate, SecondsFormat};
+
+/// Keys of the `/KEY/value` form of `:86:` that end a remittance text.
+const INFO_KEYS: &[&str] = &[
+    "ADDR", "BENM", "BIC", "CNTP", "CREF", "CSID", "EREF", "IBAN", "MARF", "NAME", "ORDP", "PURP",
+    "REMI", "RTRN", "TRCD", "ULTB", "ULTD",
+];
+
+pub(super) fn parse(document: &str) -> Result<Vec<StatementEntry>> {
+    let fields = fields(document);
+    let mut entries = Vec::new();
+    let mut currency = None;
+    for (index, (tag, value)) in fields.iter().enumerate() {
+        match tag.as_str() {
+            "60F" | "60M" => {
+                currency = value
+                    .get(7..10)
+                    .filter(|code| code.bytes().all(|b| b.is_ascii_alphabetic()))
+                    .map(str::to_ascii_uppercase);
+            }
+            "61" => {
+                let information = fields
+                    .get(index + 1)
+                    .filter(|(tag, _)| tag == "86")
+                    .map(|(_, value)| value.as_str());
+                let number = entries.len() + 1;
+                let entry = entry(value, information, currency.clone())
+                    .with_context(|| format!("statement line {number}"))?;
+                entries.push(entry);
+            }
+            _ => {}
+        }
+    }
+    if !fields.iter().any(|(tag, _)| tag == "20") {
+        bail!("not an MT940 statement: no :20: field");
+    }
+    Ok(entries)
+}
+
+/// The `:tag:` fields of `document` in order, with continuation lines
+/// joined to their field by newlines. SWIFT block wrappers are skipped.
+fn fields(document: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut open = false;
+    for line in document.lines().map(str::trim_end) {
+        if let Some((tag, value)) = field_start(line) {
+            fields.push((tag.to_string(), value.to_string()));
+            open = true;
+        } else if line.starts_with('{') || line.starts_with('-') {
+            open = false;
+        } else if let Some((_, value)) = fields.last_mut().filter(|_| open) {
+            value.push('\n');
+            value.push_str(line);
+        }
+    }
+    fields
+}
+
+/// `:61:2401050105D42,50NMSC` as `("61", "2401050105D42,50NMSC")`.
+fn field_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(':')?;
+    let (tag, value) = rest.split_once(':')?;
+    let valid = (2..=3).contains(&tag.len())
+        && tag.bytes().take(2).all(|b| b.is_ascii_digit())
+        && tag.bytes().skip(2).all(|b| b.is_ascii_uppercase());
+    valid.then_some((tag, value))
+}
+
+/// An entry from a `:61:` statement line and the `:86:` after it.
+fn entry(
+    line: &str,
+    information: Option<&str>,
+    currency: Option<String>,
+) -> Result<StatementEntry> {
+    let (first, supplementary) = line.split_once('\n').unwrap_or((line, ""));
+    let value_date = date(first.get(..6).context("no value date")?)?;
+    let mut rest = &first[6..];
+    let mut booked = value_date;
+    if rest.len() >= 4 && rest.as_bytes()[..4].iter().all(u8::is_ascii_digit) {
+        booked = entry_date(value_date, &rest[..4])?;
+        rest = &rest[4..];
+    }
+    let (sign, marked) = match rest {
+        rest if rest.starts_with("RC") => (-1.0, &rest[2..]),
+        rest if rest.starts_with("RD") => (1.0, &rest[2..]),
+        rest if rest.starts_with('C') => (1.0, &rest[1..]),
+        rest if rest.starts_with('D') => (-1.0, &rest[1..]),
+        _ => bail!("no debit or credit mark in {first:?}"),
+    };
+    // The third letter of the currency code, given for some funds.
+    let marked = marked
+        .strip_prefix(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(marked);
+    let digits = marked
+        .find(|c: char| !c.is_ascii_digit() && c != ',')
+        .unwrap_or(marked.len());
+    let amount = marked[..digits]
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .with_context(|| format!("amount {:?} is not a number", &marked[..digits]))?;
+    // The transaction type, e.g. `NMSC`, precedes the references.
+    let references = marked[digits..].get(4..).unwrap_or_default();
+    let (customer, bank) = references.split_once("//").unwrap_or((references, ""));
+    let reference = [bank, customer]
+        .into_iter()
+        .map(str::trim)
+        .find(|reference| !reference.is_empty() && *reference != "NONREF")
+        .map(str::to_string);
+
+    let (counterparty, mut details) = information.map(describe).unwrap_or_default();
+    if details.is_none() && !supplementary.trim().is_empty() {
+        details = Some(supplementary.trim().to_string());
+    }
+    let mut raw = format!(":61:{line}");
+    if let Some(information) = information {
+        raw.push_str("\n:86:");
+        raw.push_str(information);
+    }
+    Ok(StatementEntry {
+        reference,
+        booked_at: booked
+            .and_hms_opt(0, 0, 0)
+            .context("invalid date")?
+            .and_utc()
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        amount: sign * amount,
+        currency,
+        counterparty,
+        details,
+        pending: false,
+        raw,
+    })
+}
+
+fn date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("20{value}"), "%Y%m%d")
+        .with_context(|| format!("{value:?} is not a YYMMDD date"))
+}
+
+/// The `MMDD` entry date of a statement line, in the year of its value date
+/// or the one next to it when they straddle a new year.
+fn entry_date(value_date: NaiveDate, mmdd: &str) -> Result<NaiveDate> {
+    let month: u32 = mmdd[..2].parse()?;
+    let year = match (value_date.month(), month) {
+        (12, 1) => value_date.year() + 1,
+        (1, 12) => value_date.year() - 1,
+        _ => value_date.year(),
+    };
+    NaiveDate::parse_from_str(&format!("{year}{mmdd}"), "%Y%m%d")
+        .with_context(|| format!("{mmdd:?} is not an MMDD date"))
+}
+
+/// The counterparty and remittance text of a `:86:` field, which is either
+/// structured with `?NN` subfields (German banks), structured with `/KEY/`
+/// pairs, or free text.
+fn describe(information: &str) -> (Option<String>, Option<String>) {
+    let text = information.replace('\n', "");
+    if text.contains("?20") || text.contains("?00") {
+        let mut name = String::new();
+        let mut remittance = String::new();
+        for subfield in text.split('?').skip(1) {
+            let (Some(code), Some(value)) = (subfield.get(..2), subfield.get(2..)) else {
+                continue;
+            };
+            match code {
+                "32" | "33" => name.push_str(value),
+                "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" | "60"
+                | "61" | "62" | "63" => remittance.push_str(value),
+                _ => {}
+            }
+        }
+        return (non_empty(&name), non_empty(&remittance));
+    }
+    if text.starts_with('/') {
+        let name = keyed(&text, "NAME");
+        let remittance = keyed(&text, "REMI");
+        let remittance = remittance.as_deref().map(|text| {
+            text.strip_prefix("USTD//")
+                .or_else(|| text.strip_prefix("STRD/"))
+                .unwrap_or(text)
+        });
+        return (name, remittance.and_then(non_empty));
+    }
+    (None, non_empty(&information.replace('\n', " ")))
+}
+
+/// The value after `/KEY/` in `text`, up to the next known key.
+fn keyed(text: &str, key: &str) -> Option<String> {
+    let start = text.find(&format!("/{key}/"))? + key.len() + 2;
+    let value = &text[start..];
+    let end = INFO_KEYS
+        .iter()
+        .filter_map(|next| value.find(&format!("/{next}/")))
+        .min()
+        .unwrap_or(value.len());
+    non_empty(value[..end].trim_end_matches('/'))
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
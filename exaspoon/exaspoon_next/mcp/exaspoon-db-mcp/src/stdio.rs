@@ -0,0 +1,266 @@
+//! The stdio transport, watched for a client that goes away. When stdin
+//! closes, the server sees no end of input until the requests read before it
+//! have been answered. A closed stdout stops the server instead of failing
+//! every write, and a client silent for too long, with no request of its
+//! still running, can be let go so an abandoned server does not linger.
+
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+/// Tells when the client last wrote to the server or had a request
+/// answered, which of its requests are unanswered, and whether it has gone.
+#[derive(Debug, Clone)]
+pub struct StdioMonitor {
+    last_active: Arc<Mutex<Instant>>,
+    /// Ids of the requests read and not yet answered.
+    pending: Arc<watch::Sender<HashSet<String>>>,
+    closed: CancellationToken,
+    reason: Arc<OnceLock<&'static str>>,
+}
+
+/// Input that records when the client writes and holds back its end.
+#[derive(Debug)]
+pub struct MonitoredInput<R> {
+    inner: R,
+    monitor: StdioMonitor,
+    lines: LineBuffer,
+}
+
+/// Output that discards writes once the client has closed its end.
+#[derive(Debug)]
+pub struct MonitoredOutput<W> {
+    inner: W,
+    monitor: StdioMonitor,
+    lines: LineBuffer,
+    broken: bool,
+}
+
+/// Wraps the input and output of the stdio transport, usually stdin and
+/// stdout.
+pub fn monitor<R, W>(input: R, output: W) -> (MonitoredInput<R>, MonitoredOutput<W>, StdioMonitor) {
+    let monitor = StdioMonitor {
+        last_active: Arc::new(Mutex::new(Instant::now())),
+        pending: Arc::new(watch::Sender::new(HashSet::new())),
+        closed: CancellationToken::new(),
+        reason: Arc::default(),
+    };
+    let input = MonitoredInput {
+        inner: input,
+        monitor: monitor.clone(),
+        lines: LineBuffer::default(),
+    };
+    let output = MonitoredOutput {
+        inner: output,
+        monitor: monitor.clone(),
+        lines: LineBuffer::default(),
+        broken: false,
+    };
+    (input, output, monitor)
+}
+
+impl StdioMonitor {
+    /// Resolves once the client has closed its end of either stream, naming
+    /// which.
+    pub async fn closed(&self) -> &'static str {
+        self.closed.cancelled().await;
+        self.reason.get().copied().unwrap_or("stdio closed")
+    }
+
+    /// Resolves once every request the client has sent is answered, or
+    /// cancelled by it.
+    pub async fn answered(&self) {
+        let mut pending = self.pending.subscribe();
+        let _ = pending.wait_for(HashSet::is_empty).await;
+    }
+
+    /// Requests the client has sent that are not yet answered.
+    pub fn unanswered(&self) -> usize {
+        self.pending.borrow().len()
+    }
+
+    /// Resolves once the client has written nothing and had no request
+    /// running for `timeout`; never without one.
+    pub async fn idle(&self, timeout: Option<Duration>) {
+        let Some(timeout) = timeout else {
+            return std::future::pending().await;
+        };
+        loop {
+            self.answered().await;
+            let deadline = *self.last_active.lock().unwrap() + timeout;
+            if Instant::now() >= deadline && self.unanswered() == 0 {
+                return;
+            }
+            tokio::time::sleep_until(deadline.into()).await;
+        }
+    }
+
+    fn close(&self, reason: &'static str) {
+        let _ = self.reason.set(reason);
+        self.closed.cancel();
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// Notes a message the client sent: a request to be answered, or the
+    /// cancellation of one.
+    fn received(&self, message: &Value) {
+        let id = message.get("id").map(Value::to_string);
+        match (message.get("method").and_then(Value::as_str), id) {
+            (Some("notifications/cancelled"), _) => {
+                let cancelled = &message["params"]["requestId"];
+                self.pending
+                    .send_if_modified(|pending| pending.remove(&cancelled.to_string()));
+            }
+            (Some(_), Some(id)) => {
+                self.pending.send_modify(|pending| {
+                    pending.insert(id);
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Notes a message the server sent, which answers a request when it
+    /// carries an id and no method.
+    fn sent(&self, message: &Value) {
+        if message.get("method").is_some() {
+            return;
+        }
+        if let Some(id) = message.get("id") {
+            if self
+                .pending
+                .send_if_modified(|pending| pending.remove(&id.to_string()))
+            {
+                self.touch();
+            }
+        }
+    }
+}
+
+/// Collects the bytes of a stream into lines, each a JSON-RPC message or a
+/// batch of them.
+#[derive(Debug, Default)]
+struct LineBuffer {
+    partial: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Adds `bytes`, calling `each` with every message of the lines they
+    /// complete. Lines that are not JSON are skipped; the service reports
+    /// those itself.
+    fn push(&mut self, bytes: &[u8], mut each: impl FnMut(&Value)) {
+        for chunk in bytes.split_inclusive(|byte| *byte == b'\n') {
+            self.partial.extend_from_slice(chunk);
+            if !chunk.ends_with(b"\n") {
+                continue;
+            }
+            match serde_json::from_slice::<Value>(&self.partial) {
+                Ok(Value::Array(batch)) => batch.iter().for_each(&mut each),
+                Ok(message) => each(&message),
+                Err(_) => {}
+            }
+            self.partial.clear();
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for MonitoredInput<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.monitor.closed.is_cancelled() {
+            return Poll::Pending;
+        }
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) if buf.filled().len() > filled => {
+                let this = &mut *self;
+                this.monitor.touch();
+                let monitor = &this.monitor;
+                this.lines
+                    .push(&buf.filled()[filled..], |message| monitor.received(message));
+                Poll::Ready(Ok(()))
+            }
+            // End of input, or input that can no longer be read. The service
+            // is stopped once running calls finish, rather than at once.
+            Poll::Ready(Ok(())) if buf.remaining() > 0 => {
+                self.monitor.close("stdin closed");
+                Poll::Pending
+            }
+            Poll::Ready(Err(_)) => {
+                self.monitor.close("stdin failed");
+                Poll::Pending
+            }
+            poll => poll,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> MonitoredOutput<W> {
+    /// Notes the messages completed by `bytes`, written or, once the client
+    /// has gone, dropped.
+    fn scan(&mut self, bytes: &[u8]) {
+        let monitor = &self.monitor;
+        self.lines.push(bytes, |message| monitor.sent(message));
+    }
+
+    fn check<T>(&mut self, poll: Poll<io::Result<T>>, written: T) -> Poll<io::Result<T>> {
+        match poll {
+            Poll::Ready(Err(err)) if err.kind() == io::ErrorKind::BrokenPipe => {
+                self.broken = true;
+                self.monitor.close("stdout closed");
+                Poll::Ready(Ok(written))
+            }
+            poll => poll,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for MonitoredOutput<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.broken {
+            self.scan(buf);
+            return Poll::Ready(Ok(buf.len()));
+        }
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        match self.check(poll, buf.len()) {
+            Poll::Ready(Ok(written)) => {
+                self.scan(&buf[..written]);
+                Poll::Ready(Ok(written))
+            }
+            poll => poll,
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.broken {
+            return Poll::Ready(Ok(()));
+        }
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.check(poll, ())
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.broken {
+            return Poll::Ready(Ok(()));
+        }
+        let poll = Pin::new(&mut self.inner).poll_shutdown(cx);
+        self.check(poll, ())
+    }
+}
@@ -1,20 +1,32 @@
 use crate::{
-    config::AppConfig,
+    batch::{self, BatchStep},
+    cancellation,
+    metrics::OperationMetrics,
     models::{
-        AccountType, CategoryKind, CreateTransactionInput, ListAccountsInput, UpsertAccountInput,
+        Account, AggregateSpendingInput, BankLink, BatchStepResult, Categorized, Category,
+        CategoryMatch, AuditEvent, CreateTransactionInput, EmbeddingIssue, ListAccountsInput, ListAuditEventsInput,
+        ListTransactionsInput, RecordKind,
+        SpendingBucket, Transaction, TransactionFilters, TransactionMatch, TransactionTextMatch,
+        UpsertAccountInput,
         UpsertCategoryInput,
     },
 };
-use anyhow::{anyhow, Context, Result};
+#[cfg(any(feature = "memory-backend", feature = "sqlite"))]
+use crate::models::EmbeddingIssueKind;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use reqwest::{
-    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE},
-    Client,
-};
-use serde_json::{json, Value};
-use std::time::Instant;
-use supabase_rs::SupabaseClient;
-use tracing::{debug, error, info, instrument, warn};
+#[cfg(any(feature = "supabase", feature = "sqlite"))]
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use tokio::sync::mpsc;
+#[cfg(any(feature = "supabase", feature = "sqlite"))]
+use tracing::error;
+
+#[cfg(feature = "supabase")]
+mod gateway;
+
+#[cfg(feature = "supabase")]
+pub use gateway::SupabaseGateway;
 
 #[async_trait]
 pub trait Database: Send + Sync {
@@ -22,446 +34,300 @@ pub trait Database: Send + Sync {
         &self,
         input: &CreateTransactionInput,
         embedding: Option<Vec<f32>>,
-    ) -> Result<Value>;
+    ) -> Result<Transaction>;
+    /// Inserts several transactions, returning them in input order. The
+    /// default inserts them one at a time; backends that can write a batch in
+    /// one round trip should override it.
+    async fn insert_transactions(
+        &self,
+        rows: &[(CreateTransactionInput, Option<Vec<f32>>)],
+    ) -> Result<Vec<Transaction>> {
+        let mut inserted = Vec::with_capacity(rows.len());
+        for (input, embedding) in rows {
+            inserted.push(self.insert_transaction(input, embedding.clone()).await?);
+        }
+        Ok(inserted)
+    }
     async fn upsert_category(
         &self,
         input: &UpsertCategoryInput,
         embedding: Option<Vec<f32>>,
-    ) -> Result<Value>;
-    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Value>;
-    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Value>>;
+    ) -> Result<Category>;
+    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Account>;
+    /// Files a live transaction under a live category, or under none, and
+    /// returns it with the category it had before. Returns `None` when no
+    /// live transaction has that id.
+    async fn categorize_transaction(
+        &self,
+        id: &str,
+        category_id: Option<&str>,
+    ) -> Result<Option<Categorized>>;
+    /// Applies `steps` in order, all or none, failing with a [`BatchFailed`]
+    /// that names the step that went wrong. The default undoes the earlier
+    /// steps when one fails; backends with transactions should override it.
+    async fn execute_batch(&self, steps: &[BatchStep]) -> Result<Vec<BatchStepResult>> {
+        batch::execute_with_compensation(self, steps).await
+    }
+    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Account>>;
+    /// Counts the accounts `list_accounts` would return without a page.
+    async fn count_accounts(&self, params: &ListAccountsInput) -> Result<u64>;
+    /// Lists transactions newest first.
+    async fn list_transactions(&self, input: &ListTransactionsInput) -> Result<Vec<Transaction>>;
+    async fn count_transactions(&self, filters: &TransactionFilters) -> Result<u64>;
+    /// The `limit` transactions matching `filters` with the largest amounts,
+    /// largest first, ranked across every match rather than a page.
+    async fn largest_transactions(
+        &self,
+        filters: &TransactionFilters,
+        limit: u32,
+    ) -> Result<Vec<Transaction>>;
+    /// Counts the live transactions of `account_id` for checks that decide a
+    /// write, so backends with a read replica must not answer from it.
+    async fn count_account_transactions(&self, account_id: &str) -> Result<u64> {
+        self.count_transactions(&TransactionFilters {
+            account_id: Some(account_id.to_string()),
+            ..TransactionFilters::default()
+        })
+        .await
+    }
+    /// Sends every transaction matching `filters` to `pages`, newest first and
+    /// `page_size` rows at a time, so an export never holds the whole result
+    /// set. Stops early once the receiver is dropped or the tool call is
+    /// cancelled, and returns how many rows were sent. The default pages with
+    /// offsets; the gateway overrides it with keyset pagination, which stays
+    /// fast deep into large tables.
+    async fn stream_transactions(
+        &self,
+        filters: &TransactionFilters,
+        page_size: u32,
+        pages: mpsc::Sender<Vec<Transaction>>,
+    ) -> Result<u64> {
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        let mut offset = 0u32;
+        let mut sent = 0u64;
+        while !cancellation::is_cancelled() {
+            let page = self
+                .list_transactions(&ListTransactionsInput {
+                    filters: filters.clone(),
+                    limit: Some(page_size),
+                    offset: Some(offset),
+                })
+                .await?;
+            let rows = page.len();
+            if rows == 0 || pages.send(page).await.is_err() {
+                break;
+            }
+            sent += rows as u64;
+            if rows < page_size as usize {
+                break;
+            }
+            offset = offset.saturating_add(page_size);
+        }
+        Ok(sent)
+    }
     async fn search_similar_transactions(
         &self,
         embedding: Vec<f32>,
         limit: Option<u32>,
-    ) -> Result<Vec<Value>>;
+    ) -> Result<Vec<TransactionMatch>>;
     async fn search_similar_categories(
         &self,
         embedding: Vec<f32>,
         limit: Option<u32>,
-    ) -> Result<Vec<Value>>;
-}
-
-#[derive(Clone)]
-pub struct SupabaseGateway {
-    client: SupabaseClient,
-    http: Client,
-    _rest_base: String,
-    rpc_base: String,
-    service_key: String,
-    schema: String,
-}
-
-impl SupabaseGateway {
-    #[instrument]
-    pub fn new(config: &AppConfig) -> Result<Self> {
-        info!("Initializing Supabase gateway");
-        debug!("Supabase URL: {}", config.supabase_url);
-        
-        let client = SupabaseClient::new(
-            config.supabase_url.clone(),
-            config.supabase_service_key.clone(),
-        )
-        .map_err(|err| {
-            error!("Failed to initialize Supabase client: {}", err);
-            anyhow!("failed to initialize Supabase client: {err}")
-        })?;
-
-        let use_native_tls = std::env::var("USE_NATIVE_TLS")
-            .map(|value| value.eq_ignore_ascii_case("true"))
-            .unwrap_or(false);
-        
-        let tls_min_version = std::env::var("TLS_MIN_VERSION")
-            .unwrap_or_else(|_| "1.2".to_string());
-        
-        let danger_accept_invalid_certs = std::env::var("DANGER_ACCEPT_INVALID_CERTS")
-            .map(|value| value.eq_ignore_ascii_case("true"))
-            .unwrap_or(false);
-        
-        info!("Using TLS backend: {}", if use_native_tls { "native" } else { "rustls" });
-        info!("TLS min version: {}", tls_min_version);
-        if danger_accept_invalid_certs {
-            warn!("WARNING: TLS certificate verification disabled - FOR TESTING ONLY");
-        }
-        
-        let http = if use_native_tls {
-            let mut builder = Client::builder().use_native_tls();
-            if danger_accept_invalid_certs {
-                builder = builder.danger_accept_invalid_certs(true);
-            }
-            builder.build()
-                .context("failed to build HTTP client with native TLS")?
-        } else {
-            let mut builder = Client::builder().use_rustls_tls();
-            if danger_accept_invalid_certs {
-                builder = builder.danger_accept_invalid_certs(true);
-            }
-            builder.build()
-                .context("failed to build HTTP client with rustls")?
-        };
-        
-        let base = config.supabase_url.trim_end_matches('/');
-        let use_plain_base = std::env::var("SUPABASE_RS_DONT_REST_V1_URL")
-            .map(|value| value.eq_ignore_ascii_case("true"))
-            .unwrap_or(false);
-        let rest_base = if use_plain_base {
-            base.to_string()
-        } else {
-            format!("{}/rest/v1", base)
-        };
-
-        info!("Supabase gateway initialized successfully");
-        Ok(Self {
-            client,
-            http,
-            rpc_base: format!("{}/rpc", rest_base),
-            _rest_base: rest_base,
-            service_key: config.supabase_service_key.clone(),
-            schema: "public".to_string(),
-        })
+    ) -> Result<Vec<CategoryMatch>>;
+    /// Names of live categories containing `search`, ignoring case, in
+    /// alphabetical order.
+    async fn list_category_names(&self, search: &str, limit: u32) -> Result<Vec<String>>;
+    /// Every live category, by name, for exports that show names rather
+    /// than ids and imports that match names to existing categories.
+    async fn list_categories(&self) -> Result<Vec<Category>> {
+        bail!("this backend does not list categories")
     }
-}
-
-#[async_trait]
-impl Database for SupabaseGateway {
-    #[instrument(skip(self, input), fields(account_id = %input.account_id, amount = %input.amount))]
-    async fn insert_transaction(
+    /// Finds live transactions whose description or raw source contains
+    /// every word of `query`, for lookups such as `invoice 4812` where
+    /// semantic search is overkill.
+    async fn search_transactions_text(
         &self,
-        input: &CreateTransactionInput,
-        embedding: Option<Vec<f32>>,
-    ) -> Result<Value> {
-        let start_time = Instant::now();
-        info!("Inserting transaction into database");
-        
-        let payload = json!({
-            "account_id": &input.account_id,
-            "amount": input.amount,
-            "currency": &input.currency,
-            "direction": input.direction.as_ref(),
-            "occurred_at": &input.occurred_at,
-            "description": input.description.clone(),
-            "raw_source": input.raw_source.clone(),
-            "embedding": embedding,
-        });
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionTextMatch>>;
+    /// Sums transaction amounts per group, currency and optional calendar
+    /// period inside the database.
+    async fn aggregate_spending(
+        &self,
+        input: &AggregateSpendingInput,
+    ) -> Result<Vec<SpendingBucket>>;
 
-        let result = self.insert_and_fetch("transactions", payload).await?;
-        let duration = start_time.elapsed();
-        info!("Transaction inserted successfully in {:?}", duration);
-        
-        Ok(result)
-    }
+    /// Appends rows to the audit log.
+    async fn record_audit_events(&self, events: &[AuditEvent]) -> Result<()>;
+    /// Lists audit events newest first.
+    async fn list_audit_events(&self, input: &ListAuditEventsInput) -> Result<Vec<AuditEvent>>;
+    /// Appends tool calls to the call log table, apart from the audit log.
+    async fn record_tool_calls(&self, calls: &[AuditEvent]) -> Result<()>;
+    /// Marks a live row as deleted so that reads, counts and RPCs skip it.
+    /// Returns `false` when no live row has that id.
+    async fn soft_delete(&self, kind: RecordKind, id: &str) -> Result<bool>;
+    /// Whether a live row of `kind` has that id, for dry runs and previews
+    /// that report what a delete would do.
+    async fn record_exists(&self, kind: RecordKind, id: &str) -> Result<bool>;
+    /// Permanently removes one row, live or soft-deleted, as purging would.
+    /// Returns `false` when no row has that id.
+    async fn delete_record(&self, kind: RecordKind, id: &str) -> Result<bool>;
+    /// Permanently removes soft-deleted rows of `kind`, optionally only those
+    /// deleted before an RFC 3339 timestamp, and returns how many went.
+    async fn purge_deleted(&self, kind: RecordKind, deleted_before: Option<&str>) -> Result<u64>;
 
-    #[instrument(skip(self, input), fields(category_name = %input.name, kind = ?input.kind))]
-    async fn upsert_category(
+    /// Rows of `kind` whose embedding needs attention, ordered by id and
+    /// starting after `after`: live rows with text but no embedding,
+    /// embeddings whose length is not `dimension`, and embeddings on deleted
+    /// rows or rows with nothing to embed. Accounts have no embeddings.
+    async fn embedding_issues(
         &self,
-        input: &UpsertCategoryInput,
+        kind: RecordKind,
+        dimension: usize,
+        after: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<EmbeddingIssue>>;
+    /// Replaces or, with `None`, removes the embedding of one row, whether or
+    /// not it is deleted.
+    async fn set_embedding(
+        &self,
+        kind: RecordKind,
+        id: &str,
         embedding: Option<Vec<f32>>,
-    ) -> Result<Value> {
-        let start_time = Instant::now();
-        info!("Upserting category in database");
-        
-        let description = input
-            .description
-            .clone()
-            .unwrap_or_else(|| input.name.clone());
-        let payload = json!({
-            "name": &input.name,
-            "kind": input.kind.unwrap_or(CategoryKind::Expense).as_ref(),
-            "description": description,
-            "embedding": embedding,
-        });
+    ) -> Result<()>;
 
-        let result = if let Some(existing) = self
-            .fetch_first("categories", &[("name", input.name.as_str())])
-            .await?
-        {
-            debug!("Updating existing category");
-            let id = self.extract_id(&existing)?;
-            self.client
-                .update("categories", &id, payload)
-                .await
-                .map_err(|err| {
-                    error!("Failed to update category: {}", err);
-                    anyhow!("failed to update category: {err}")
-                })?;
-            self.fetch_by_id("categories", &id).await?
-        } else {
-            debug!("Creating new category");
-            self.insert_and_fetch("categories", payload).await?
-        };
-        
-        let duration = start_time.elapsed();
-        info!("Category upserted successfully in {:?}", duration);
-        
-        Ok(result)
+    /// The bank link of `account_id`, if it has one.
+    async fn bank_link(&self, account_id: &str) -> Result<Option<BankLink>> {
+        let _ = account_id;
+        bail!("this backend does not store bank links")
     }
-
-    #[instrument(skip(self, input), fields(account_name = %input.name, account_type = %input.r#type))]
-    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Value> {
-        let start_time = Instant::now();
-        info!("Upserting account in database");
-        
-        let payload = json!({
-            "name": &input.name,
-            "type": input.r#type.as_ref(),
-            "currency": &input.currency,
-            "network": input.network.clone(),
-            "institution": input.institution.clone(),
-        });
-
-        let result = if let Some(existing) = self.fetch_account(&input.name, input.r#type).await? {
-            debug!("Updating existing account");
-            let id = self.extract_id(&existing)?;
-            self.client
-                .update("accounts", &id, payload)
-                .await
-                .map_err(|err| {
-                    error!("Failed to update account: {}", err);
-                    anyhow!("failed to update account: {err}")
-                })?;
-            self.fetch_by_id("accounts", &id).await?
-        } else {
-            debug!("Creating new account");
-            self.insert_and_fetch("accounts", payload).await?
-        };
-        
-        let duration = start_time.elapsed();
-        info!("Account upserted successfully in {:?}", duration);
-        
-        Ok(result)
+    /// Creates or replaces the bank link of `link.account_id` and returns it
+    /// as stored.
+    async fn save_bank_link(&self, link: &BankLink) -> Result<BankLink> {
+        let _ = link;
+        bail!("this backend does not store bank links")
     }
 
-    #[instrument(skip(self, params), fields(account_type = ?params.r#type, search = ?params.search))]
-    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Value>> {
-        let start_time = Instant::now();
-        info!("Listing accounts from database");
-        
-        let mut query = self.client.select("accounts").order("name", true);
-        if let Some(kind) = params.r#type {
-            query = query.eq("type", kind.as_ref());
-        }
-
-        let rows = query
-            .execute()
-            .await
-            .map_err(|err| {
-                error!("Failed to list accounts: {}", err);
-                anyhow!("failed to list accounts: {err}")
-            })?;
-
-        let result = if let Some(needle) = params
-            .search
-            .as_ref()
-            .map(|value| value.trim())
-            .filter(|value| !value.is_empty())
-            .map(|value| value.to_lowercase())
-        {
-            debug!("Filtering accounts by search term: {}", needle);
-            rows.into_iter()
-                .filter(|row| {
-                    row.get("name")
-                        .and_then(Value::as_str)
-                        .map(|value| value.to_lowercase().contains(&needle))
-                        .unwrap_or(false)
-                })
-                .collect::<Vec<_>>()
-        } else {
-            rows
-        };
-        
-        let duration = start_time.elapsed();
-        info!("Retrieved {} accounts in {:?}", result.len(), duration);
-        
-        Ok(result)
+    /// The `metadata` object of account `account_id`, empty when nothing
+    /// was stored there.
+    async fn account_metadata(&self, account_id: &str) -> Result<Map<String, Value>> {
+        let _ = account_id;
+        bail!("this backend does not store account metadata")
     }
-
-    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
-    async fn search_similar_transactions(
-        &self,
-        embedding: Vec<f32>,
-        limit: Option<u32>,
-    ) -> Result<Vec<Value>> {
-        let start_time = Instant::now();
-        info!("Searching for similar transactions");
-        
-        let result = self.call_rpc(
-            "search_similar_transactions",
-            json!({
-                "query_embedding": embedding,
-                "match_count": resolve_limit(limit),
-            }),
-        ).await?;
-        
-        let duration = start_time.elapsed();
-        info!("Found {} similar transactions in {:?}", result.len(), duration);
-        
-        Ok(result)
+    /// Sets `key` of the `metadata` of account `account_id` to `value`,
+    /// keeping its other keys.
+    async fn set_account_metadata(&self, account_id: &str, key: &str, value: Value) -> Result<()> {
+        let _ = (account_id, key, value);
+        bail!("this backend does not store account metadata")
     }
 
-    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
-    async fn search_similar_categories(
-        &self,
-        embedding: Vec<f32>,
-        limit: Option<u32>,
-    ) -> Result<Vec<Value>> {
-        let start_time = Instant::now();
-        info!("Searching for similar categories");
-        
-        let result = self.call_rpc(
-            "search_similar_categories",
-            json!({
-                "query_embedding": embedding,
-                "match_count": resolve_limit(limit),
-            }),
-        ).await?;
-        
-        let duration = start_time.elapsed();
-        info!("Found {} similar categories in {:?}", result.len(), duration);
-        
-        Ok(result)
+    /// Sets the `network` of live account `account_id`, leaving the rest of
+    /// the account as it is.
+    async fn set_account_network(&self, account_id: &str, network: &str) -> Result<()> {
+        let _ = (account_id, network);
+        bail!("this backend does not update account networks")
     }
-}
 
-impl SupabaseGateway {
-    #[instrument(skip(self), fields(table = %table))]
-    async fn insert_and_fetch(&self, table: &str, payload: Value) -> Result<Value> {
-        let start_time = Instant::now();
-        debug!("Inserting record into {}", table);
-        
-        let id = self
-            .client
-            .insert(table, payload)
-            .await
-            .map_err(|err| {
-                error!("Failed to insert into {}: {}", table, err);
-                anyhow!("failed to insert into {table}: {err}")
-            })?;
-        
-        let result = self.fetch_by_id(table, &Self::normalize_id(&id)).await?;
-        let duration = start_time.elapsed();
-        debug!("Record inserted and fetched in {:?}", duration);
-        
-        Ok(result)
+    /// Calls the Postgres function `function` with named `params` and returns
+    /// whatever it returns. In-process backends have no such functions.
+    async fn call_function(&self, function: &str, params: Value) -> Result<Value> {
+        let _ = params;
+        bail!("calling {function} requires the supabase backend")
     }
 
-    #[instrument(skip(self), fields(table = %table, filters = ?filters))]
-    async fn fetch_first(&self, table: &str, filters: &[(&str, &str)]) -> Result<Option<Value>> {
-        debug!("Fetching first record from {} with filters: {:?}", table, filters);
-        
-        let mut query = self.client.select(table).limit(1);
-        for (column, value) in filters {
-            query = query.eq(column, value);
-        }
-
-        let rows = query
-            .execute()
-            .await
-            .map_err(|err| {
-                error!("Failed to query {}: {}", table, err);
-                anyhow!("failed to query {table}: {err}")
-            })?;
-        
-        let result = rows.into_iter().next();
-        debug!("Found {} records", if result.is_some() { 1 } else { 0 });
-        
-        Ok(result)
+    /// Cheap round trip that proves the backend is reachable. In-process
+    /// backends have nothing to probe.
+    async fn ping(&self) -> Result<()> {
+        Ok(())
     }
 
-    #[instrument(skip(self), fields(name = %name, account_type = %account_type))]
-    async fn fetch_account(&self, name: &str, account_type: AccountType) -> Result<Option<Value>> {
-        self.fetch_first(
-            "accounts",
-            &[("name", name), ("type", account_type.as_ref())],
-        )
-        .await
+    /// Request counts and latencies per table and RPC. In-process backends
+    /// record none.
+    fn metrics(&self) -> Vec<OperationMetrics> {
+        Vec::new()
     }
+}
 
-    #[instrument(skip(self), fields(table = %table, id = %id))]
-    async fn fetch_by_id(&self, table: &str, id: &str) -> Result<Value> {
-        debug!("Fetching {} by id: {}", table, id);
-        
-        self.fetch_first(table, &[("id", id)])
-            .await?
-            .ok_or_else(|| {
-                error!("{} record {} was not found", table, id);
-                anyhow!("{table} record {id} was not found")
-            })
-    }
+/// Transactions `list_transactions` returns per page unless asked otherwise.
+pub const DEFAULT_TRANSACTION_PAGE: u32 = 100;
+/// Audit events `list_audit_events` returns per page unless asked otherwise.
+pub const DEFAULT_AUDIT_PAGE: u32 = 100;
+/// Largest page any list accepts.
+pub const MAX_PAGE_SIZE: u32 = 1000;
+/// Most matches a similarity search returns.
+pub const MAX_SIMILAR_LIMIT: u32 = 25;
+/// Matches returned by `search_transactions_text` when no limit is given.
+pub const DEFAULT_TEXT_SEARCH_LIMIT: u32 = 20;
 
-    fn extract_id(&self, value: &Value) -> Result<String> {
-        value
-            .get("id")
-            .and_then(Value::as_str)
-            .map(|id| id.to_string())
-            .ok_or_else(|| {
-                error!("Row missing id column");
-                anyhow!("row missing id column")
-            })
-    }
+/// Column that holds the tenant (user or household) a row belongs to.
+pub const TENANT_COLUMN: &str = "user_id";
+/// Argument through which the search RPCs receive the tenant to filter on.
+pub const TENANT_RPC_PARAM: &str = "filter_user_id";
+/// Columns that, after [`TENANT_COLUMN`], identify a category on upsert.
+pub const CATEGORY_KEY: &str = "name";
+/// Columns that, after [`TENANT_COLUMN`], identify an account on upsert.
+pub const ACCOUNT_KEY: &str = "name,type";
+/// Columns that, after [`TENANT_COLUMN`], identify a bank link on upsert.
+pub const BANK_LINK_KEY: &str = "account_id";
+/// Table that records every mutation made through the server.
+pub const AUDIT_LOG_TABLE: &str = "audit_log";
+/// Table the call log records every tool call in.
+pub const TOOL_CALLS_TABLE: &str = "tool_calls";
+/// Table that keeps the Plaid item each linked account syncs from.
+pub const BANK_LINKS_TABLE: &str = "bank_links";
+/// Column that marks a row as soft-deleted once set.
+pub const DELETED_AT_COLUMN: &str = "deleted_at";
 
-    fn normalize_id(id: &str) -> String {
-        id.trim_matches('"').to_string()
-    }
+/// Resolves a requested page size against `default`, capped at
+/// [`MAX_PAGE_SIZE`]. `None` means no limit.
+pub(crate) fn page_limit(limit: Option<u32>, default: Option<u32>) -> Option<u32> {
+    limit.or(default).map(|limit| limit.clamp(1, MAX_PAGE_SIZE))
+}
 
-    #[instrument(skip(self), fields(function = %function))]
-    async fn call_rpc(&self, function: &str, payload: Value) -> Result<Vec<Value>> {
-        let start_time = Instant::now();
-        debug!("Calling RPC function: {}", function);
-        
-        let url = format!("{}/{}", self.rpc_base, function);
-        let response = self
-            .http
-            .post(url)
-            .headers(self.rpc_headers()?)
-            .json(&payload)
-            .send()
-            .await
-            .with_context(|| format!("RPC {function} request failed"))?;
+#[cfg(any(feature = "supabase", feature = "sqlite"))]
+pub(crate) fn decode_row<T: DeserializeOwned>(table: &str, row: Value) -> Result<T> {
+    serde_json::from_value(row).map_err(|err| {
+        error!("Unexpected {} row shape: {}", table, err);
+        anyhow::anyhow!("unexpected {table} row shape: {err}")
+    })
+}
 
-        let result = if response.status().is_success() {
-            response
-                .json::<Vec<Value>>()
-                .await
-                .context("failed to parse RPC response")?
-        } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            error!("RPC {} failed ({}): {}", function, status, body);
-            return Err(anyhow!("RPC {function} failed ({status}): {body}"));
-        };
-        
-        let duration = start_time.elapsed();
-        debug!("RPC {} completed in {:?} with {} results", function, duration, result.len());
-        
-        Ok(result)
+/// Classifies one row's embedding for `embedding_issues`. `content` is the
+/// text the row is embedded from and `stored` the length of its embedding.
+#[cfg(any(feature = "memory-backend", feature = "sqlite"))]
+pub(crate) fn embedding_issue(
+    deleted: bool,
+    content: Option<&str>,
+    stored: Option<usize>,
+    dimension: usize,
+) -> Option<EmbeddingIssueKind> {
+    let has_content = content.is_some_and(|text| !text.trim().is_empty());
+    match stored {
+        Some(_) if deleted || !has_content => Some(EmbeddingIssueKind::Orphaned),
+        Some(length) if length != dimension => Some(EmbeddingIssueKind::Dimension),
+        None if !deleted && has_content => Some(EmbeddingIssueKind::Missing),
+        _ => None,
     }
+}
 
-    #[instrument(skip(self))]
-    fn rpc_headers(&self) -> Result<HeaderMap> {
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            "apikey",
-            HeaderValue::from_str(&self.service_key).context("invalid apikey header value")?,
-        );
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", self.service_key))
-                .context("invalid authorization header value")?,
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
-        headers.insert(
-            "Accept-Profile",
-            HeaderValue::from_str(&self.schema).context("invalid profile header")?,
-        );
-        headers.insert(
-            "Content-Profile",
-            HeaderValue::from_str(&self.schema).context("invalid profile header")?,
-        );
-        Ok(headers)
+/// Rejects record kinds that carry no embedding.
+#[cfg(any(feature = "supabase", feature = "memory-backend", feature = "sqlite"))]
+pub(crate) fn embedded_table(kind: RecordKind) -> Result<&'static str> {
+    match kind {
+        RecordKind::Transaction | RecordKind::Category => Ok(kind.table()),
+        RecordKind::Account => bail!("accounts have no embeddings"),
     }
 }
 
-fn resolve_limit(limit: Option<u32>) -> u32 {
-    limit.unwrap_or(5).clamp(1, 25)
+/// Lowercased words of a keyword search; the offline backends require every
+/// one of them to appear.
+#[cfg(any(feature = "memory-backend", feature = "sqlite"))]
+pub(crate) fn search_terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+#[cfg(any(feature = "supabase", feature = "memory-backend", feature = "sqlite"))]
+pub(crate) fn resolve_limit(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(5).clamp(1, MAX_SIMILAR_LIMIT)
 }
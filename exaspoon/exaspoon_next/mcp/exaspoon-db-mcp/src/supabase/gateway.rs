@@ -0,0 +1,1448 @@
+//! [`Database`] over Supabase's PostgREST API: rows through `/rest/v1`,
+//! searches and batches through `/rest/v1/rpc`, with retries, a circuit
+//! breaker and the caller's access token for row level security.
+
+use super::{
+    decode_row, embedded_table, page_limit, resolve_limit, Database, ACCOUNT_KEY, AUDIT_LOG_TABLE,
+    BANK_LINKS_TABLE, BANK_LINK_KEY, CATEGORY_KEY, DEFAULT_AUDIT_PAGE, DEFAULT_TEXT_SEARCH_LIMIT,
+    DEFAULT_TRANSACTION_PAGE, DELETED_AT_COLUMN, MAX_PAGE_SIZE, TENANT_COLUMN, TENANT_RPC_PARAM,
+    TOOL_CALLS_TABLE,
+};
+use crate::{
+    auth::AuthContext,
+    batch::{BatchFailed, BatchStep, MissingRow},
+    cancellation,
+    circuit::CircuitBreaker,
+    correlation,
+    metrics::{Metrics, OperationMetrics},
+    postgrest::{PostgrestError, NO_DATA_FOUND},
+    config::{validate_schema, AppConfig},
+    retry::{Failure, RetryPolicy},
+    models::{
+        Account, AggregateSpendingInput, BankLink, BatchStepResult, Categorized, Category, CategoryKind,
+        CategoryMatch, AuditEvent, CreateTransactionInput, EmbeddingIssue, ListAccountsInput, ListAuditEventsInput,
+        ListTransactionsInput, RecordKind,
+        SpendingBucket, Transaction, TransactionFilters, TransactionMatch, TransactionTextMatch,
+        UpsertAccountInput,
+        UpsertCategoryInput,
+    },
+};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    Client, RequestBuilder, Response, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, instrument, warn};
+
+#[derive(Clone)]
+pub struct SupabaseGateway {
+    http: Client,
+    /// Primary endpoint; every write goes here.
+    rest_base: String,
+    /// Endpoint for lists, counts, searches and aggregates. Same as
+    /// `rest_base` unless `SUPABASE_READ_URL` is set.
+    read_rest_base: String,
+    read_rpc_base: String,
+    service_key: String,
+    session_token: Option<String>,
+    require_user_auth: bool,
+    tenant_id: Option<String>,
+    schema: String,
+    retry: RetryPolicy,
+    breaker: Arc<CircuitBreaker>,
+    metrics: Arc<Metrics>,
+}
+
+impl SupabaseGateway {
+    #[instrument]
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        info!("Initializing Supabase gateway");
+        debug!("Supabase URL: {}", config.supabase_url);
+
+        let use_native_tls = std::env::var("USE_NATIVE_TLS")
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        
+        let tls_min_version = std::env::var("TLS_MIN_VERSION")
+            .unwrap_or_else(|_| "1.2".to_string());
+        
+        let danger_accept_invalid_certs = std::env::var("DANGER_ACCEPT_INVALID_CERTS")
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        
+        info!("Using TLS backend: {}", if use_native_tls { "native" } else { "rustls" });
+        info!("TLS min version: {}", tls_min_version);
+        if danger_accept_invalid_certs {
+            warn!("WARNING: TLS certificate verification disabled - FOR TESTING ONLY");
+        }
+        
+        debug!("HTTP client settings: {:?}", config.http);
+        let http = if use_native_tls {
+            native_tls_client(config, danger_accept_invalid_certs)?
+        } else {
+            let mut builder = config.http.apply(Client::builder().use_rustls_tls());
+            if danger_accept_invalid_certs {
+                builder = builder.danger_accept_invalid_certs(true);
+            }
+            builder.build()
+                .context("failed to build HTTP client with rustls")?
+        };
+        
+        let schema = validate_schema(&config.supabase_schema)?;
+
+        let use_plain_base = std::env::var("SUPABASE_RS_DONT_REST_V1_URL")
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let rest_url = |url: &str| {
+            let base = url.trim_end_matches('/');
+            if use_plain_base {
+                base.to_string()
+            } else {
+                format!("{}/rest/v1", base)
+            }
+        };
+        let rest_base = rest_url(&config.supabase_url);
+        let read_rest_base = match &config.supabase_read_url {
+            Some(read_url) => {
+                info!("Routing reads to {}", read_url);
+                rest_url(read_url)
+            }
+            None => rest_base.clone(),
+        };
+
+        if config.supabase_require_user_auth {
+            info!("Supabase requests require a user access token");
+        } else if config.supabase_access_token.is_some() {
+            info!("Supabase requests default to the configured user access token");
+        }
+
+        if let Some(tenant_id) = &config.tenant_id {
+            info!("Scoping all rows to tenant {}", tenant_id);
+        }
+
+        info!("Using Postgres schema {}", schema);
+        info!("Supabase gateway initialized successfully");
+        Ok(Self {
+            http,
+            read_rpc_base: format!("{}/rpc", read_rest_base),
+            read_rest_base,
+            rest_base,
+            service_key: config.supabase_service_key.clone(),
+            session_token: config.supabase_access_token.clone(),
+            require_user_auth: config.supabase_require_user_auth,
+            tenant_id: config.tenant_id.clone(),
+            schema: schema.to_string(),
+            retry: config.supabase_retry,
+            breaker: Arc::new(CircuitBreaker::new("Supabase", config.circuit_breaker)),
+            metrics: Arc::new(Metrics::new()),
+        })
+    }
+}
+
+#[cfg(feature = "native-tls")]
+fn native_tls_client(config: &AppConfig, danger_accept_invalid_certs: bool) -> Result<Client> {
+    let mut builder = config.http.apply(Client::builder().use_native_tls());
+    if danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder.build()
+        .context("failed to build HTTP client with native TLS")
+}
+
+#[cfg(not(feature = "native-tls"))]
+fn native_tls_client(_config: &AppConfig, _danger_accept_invalid_certs: bool) -> Result<Client> {
+    bail!("USE_NATIVE_TLS requires building with the `native-tls` feature")
+}
+
+#[async_trait]
+impl Database for SupabaseGateway {
+    #[instrument(skip(self, input), fields(account_id = %input.account_id, amount = %input.amount))]
+    async fn insert_transaction(
+        &self,
+        input: &CreateTransactionInput,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<Transaction> {
+        let start_time = Instant::now();
+        info!("Inserting transaction into database");
+        
+        let payload = transaction_payload(input, embedding);
+
+        let row = self.insert_row("transactions", payload).await?;
+        let result = decode_row("transactions", row)?;
+        let duration = start_time.elapsed();
+        info!("Transaction inserted successfully in {:?}", duration);
+        
+        Ok(result)
+    }
+
+    #[instrument(skip(self, rows), fields(count = rows.len()))]
+    async fn insert_transactions(
+        &self,
+        rows: &[(CreateTransactionInput, Option<Vec<f32>>)],
+    ) -> Result<Vec<Transaction>> {
+        let start_time = Instant::now();
+        info!("Inserting {} transactions into database", rows.len());
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let payloads = rows
+            .iter()
+            .map(|(input, embedding)| transaction_payload(input, embedding.clone()))
+            .collect();
+        let result = self
+            .insert_rows("transactions", payloads)
+            .await?
+            .into_iter()
+            .map(|row| decode_row("transactions", row))
+            .collect::<Result<Vec<Transaction>>>()?;
+        let duration = start_time.elapsed();
+        info!("Inserted {} transactions in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    #[instrument(skip(self, input), fields(category_name = %input.name, kind = ?input.kind))]
+    async fn upsert_category(
+        &self,
+        input: &UpsertCategoryInput,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<Category> {
+        let start_time = Instant::now();
+        info!("Upserting category in database");
+        
+        let description = input
+            .description
+            .clone()
+            .unwrap_or_else(|| input.name.clone());
+        let payload = json!({
+            "name": &input.name,
+            "kind": input.kind.unwrap_or(CategoryKind::Expense).as_ref(),
+            "description": description,
+            "embedding": embedding,
+        });
+
+        let row = self.upsert_row("categories", CATEGORY_KEY, payload).await?;
+        let result = decode_row("categories", row)?;
+        
+        let duration = start_time.elapsed();
+        info!("Category upserted successfully in {:?}", duration);
+        
+        Ok(result)
+    }
+
+    #[instrument(skip(self, input), fields(account_name = %input.name, account_type = %input.r#type))]
+    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Account> {
+        let start_time = Instant::now();
+        info!("Upserting account in database");
+        
+        let payload = json!({
+            "name": &input.name,
+            "type": input.r#type.as_ref(),
+            "currency": &input.currency,
+            "network": input.network.clone(),
+            "institution": input.institution.clone(),
+        });
+
+        let row = self.upsert_row("accounts", ACCOUNT_KEY, payload).await?;
+        let result = decode_row("accounts", row)?;
+        
+        let duration = start_time.elapsed();
+        info!("Account upserted successfully in {:?}", duration);
+        
+        Ok(result)
+    }
+
+    /// Reads the old category and writes the new one in one RPC, so no other
+    /// write can slip in between.
+    #[instrument(skip(self), fields(id = %id, category_id = ?category_id))]
+    async fn categorize_transaction(
+        &self,
+        id: &str,
+        category_id: Option<&str>,
+    ) -> Result<Option<Categorized>> {
+        info!("Categorizing transaction {}", id);
+        let result = self
+            .call_function(
+                "categorize_transaction",
+                json!({ "transaction_id": id, "new_category_id": category_id }),
+            )
+            .await?;
+        if result.is_null() {
+            return Ok(None);
+        }
+        decode_row("transactions", result).map(Some)
+    }
+
+    /// Sends the whole batch to the `execute_batch` function, which applies
+    /// it in one Postgres transaction. The function reports a failed step
+    /// instead of raising, so the step can be named; its writes are rolled
+    /// back all the same.
+    #[instrument(skip(self, steps), fields(count = steps.len()))]
+    async fn execute_batch(&self, steps: &[BatchStep]) -> Result<Vec<BatchStepResult>> {
+        let start_time = Instant::now();
+        info!("Executing batch of {} steps", steps.len());
+
+        let payload: Vec<Value> = steps.iter().map(batch_step_payload).collect();
+        let response = self
+            .call_function("execute_batch", json!({ "steps": payload }))
+            .await?;
+        if let Some(step) = response.get("failed_step").and_then(Value::as_u64) {
+            let code = response.get("code").and_then(Value::as_str);
+            let message = response
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            warn!("Batch step {} failed and was rolled back: {}", step, message);
+            let source = match code {
+                Some(NO_DATA_FOUND) => anyhow!(MissingRow(message)),
+                _ => anyhow!(PostgrestError {
+                    operation: "execute batch".to_string(),
+                    status: StatusCode::BAD_REQUEST,
+                    code: code.map(str::to_string),
+                    message,
+                    details: None,
+                    hint: None,
+                }),
+            };
+            return Err(BatchFailed {
+                step: step as usize,
+                rolled_back: true,
+                source,
+            }
+            .into());
+        }
+        let steps = response
+            .get("steps")
+            .cloned()
+            .ok_or_else(|| anyhow!("execute_batch returned neither steps nor a failed step"))?;
+        let result: Vec<BatchStepResult> = serde_json::from_value(steps)
+            .map_err(|err| anyhow!("failed to decode execute_batch result: {err}"))?;
+
+        let duration = start_time.elapsed();
+        info!("Executed batch of {} steps in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    #[instrument(skip(self, params), fields(account_type = ?params.r#type, search = ?params.search))]
+    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Account>> {
+        let start_time = Instant::now();
+        info!("Listing accounts from database");
+        
+        let mut query = account_filter_query(params);
+        query.push(("order", "name.asc".to_string()));
+        query.extend(page_query(page_limit(params.limit, None), params.offset));
+
+        let result = self
+            .select_rows("accounts", &query)
+            .await?
+            .into_iter()
+            .map(|row| decode_row::<Account>("accounts", row))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let duration = start_time.elapsed();
+        info!("Retrieved {} accounts in {:?}", result.len(), duration);
+        
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(account_type = ?params.r#type, search = ?params.search))]
+    async fn count_accounts(&self, params: &ListAccountsInput) -> Result<u64> {
+        self.count_rows("accounts", &account_filter_query(params)).await
+    }
+
+    #[instrument(skip(self), fields(limit = ?input.limit, offset = ?input.offset))]
+    async fn list_transactions(&self, input: &ListTransactionsInput) -> Result<Vec<Transaction>> {
+        let start_time = Instant::now();
+        info!("Listing transactions from database");
+
+        let mut query = transaction_filter_query(&input.filters);
+        query.push(("order", "occurred_at.desc,id.desc".to_string()));
+        query.extend(page_query(
+            page_limit(input.limit, Some(DEFAULT_TRANSACTION_PAGE)),
+            input.offset,
+        ));
+
+        let result = self
+            .select_rows("transactions", &query)
+            .await?
+            .into_iter()
+            .map(|row| decode_row::<Transaction>("transactions", row))
+            .collect::<Result<Vec<_>>>()?;
+
+        let duration = start_time.elapsed();
+        info!("Retrieved {} transactions in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    #[instrument(skip(self))]
+    async fn largest_transactions(
+        &self,
+        filters: &TransactionFilters,
+        limit: u32,
+    ) -> Result<Vec<Transaction>> {
+        let mut query = transaction_filter_query(filters);
+        query.push(("order", "amount.desc,occurred_at.desc,id.desc".to_string()));
+        query.push(("limit", limit.clamp(1, MAX_PAGE_SIZE).to_string()));
+        self.select_rows("transactions", &query)
+            .await?
+            .into_iter()
+            .map(|row| decode_row::<Transaction>("transactions", row))
+            .collect()
+    }
+
+    #[instrument(skip(self))]
+    async fn count_transactions(&self, filters: &TransactionFilters) -> Result<u64> {
+        self.count_rows("transactions", &transaction_filter_query(filters))
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn count_account_transactions(&self, account_id: &str) -> Result<u64> {
+        let filters = TransactionFilters {
+            account_id: Some(account_id.to_string()),
+            ..TransactionFilters::default()
+        };
+        self.count_rows_from(
+            &self.rest_base,
+            "transactions",
+            &transaction_filter_query(&filters),
+        )
+        .await
+    }
+
+    #[instrument(skip(self, pages))]
+    async fn stream_transactions(
+        &self,
+        filters: &TransactionFilters,
+        page_size: u32,
+        pages: mpsc::Sender<Vec<Transaction>>,
+    ) -> Result<u64> {
+        let start_time = Instant::now();
+        info!("Streaming transactions in pages of {}", page_size);
+
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        let mut cursor: Option<(String, String)> = None;
+        let mut sent = 0u64;
+        loop {
+            if cancellation::is_cancelled() {
+                debug!("Export cancelled after {} transactions", sent);
+                break;
+            }
+            let mut query = transaction_filter_query(filters);
+            if let Some((occurred_at, id)) = &cursor {
+                query.push(("or", keyset_after(occurred_at, id)));
+            }
+            query.push(("order", "occurred_at.desc,id.desc".to_string()));
+            query.push(("limit", page_size.to_string()));
+
+            let page = self
+                .select_rows("transactions", &query)
+                .await?
+                .into_iter()
+                .map(|row| decode_row::<Transaction>("transactions", row))
+                .collect::<Result<Vec<_>>>()?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            cursor = Some((last.occurred_at.clone(), last.id.clone()));
+            let rows = page.len();
+            if pages.send(page).await.is_err() {
+                debug!("Export receiver dropped after {} transactions", sent);
+                break;
+            }
+            sent += rows as u64;
+            if rows < page_size as usize {
+                break;
+            }
+        }
+
+        info!("Streamed {} transactions in {:?}", sent, start_time.elapsed());
+        Ok(sent)
+    }
+
+    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
+    async fn search_similar_transactions(
+        &self,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionMatch>> {
+        let start_time = Instant::now();
+        info!("Searching for similar transactions");
+        
+        let result = self.call_rpc(
+            "search_similar_transactions",
+            json!({
+                "query_embedding": embedding,
+                "match_count": resolve_limit(limit),
+            }),
+        ).await?;
+        
+        let duration = start_time.elapsed();
+        info!("Found {} similar transactions in {:?}", result.len(), duration);
+        
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(embedding_dim = %embedding.len(), limit = ?limit))]
+    async fn search_similar_categories(
+        &self,
+        embedding: Vec<f32>,
+        limit: Option<u32>,
+    ) -> Result<Vec<CategoryMatch>> {
+        let start_time = Instant::now();
+        info!("Searching for similar categories");
+        
+        let result = self.call_rpc(
+            "search_similar_categories",
+            json!({
+                "query_embedding": embedding,
+                "match_count": resolve_limit(limit),
+            }),
+        ).await?;
+        
+        let duration = start_time.elapsed();
+        info!("Found {} similar categories in {:?}", result.len(), duration);
+        
+        Ok(result)
+    }
+
+    #[instrument(skip(self))]
+    async fn list_category_names(&self, search: &str, limit: u32) -> Result<Vec<String>> {
+        let mut query = vec![
+            ("order", "name.asc".to_string()),
+            ("limit", limit.to_string()),
+        ];
+        let needle = search.trim();
+        if !needle.is_empty() {
+            query.push(("name", format!("ilike.*{}*", escape_like(needle))));
+        }
+        let names = self
+            .select_rows("categories", &query)
+            .await?
+            .into_iter()
+            .filter_map(|row| row.get("name")?.as_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        debug!("Found {} category names matching {:?}", names.len(), needle);
+        Ok(names)
+    }
+
+    /// Pages through every category on the primary, since imports create
+    /// the categories this does not find.
+    #[instrument(skip(self))]
+    async fn list_categories(&self) -> Result<Vec<Category>> {
+        let mut categories = Vec::new();
+        loop {
+            let query = [
+                ("order", "name.asc,id.asc".to_string()),
+                ("limit", MAX_PAGE_SIZE.to_string()),
+                ("offset", categories.len().to_string()),
+            ];
+            let rows = self.select_primary_rows("categories", &query).await?;
+            let full = rows.len() == MAX_PAGE_SIZE as usize;
+            for row in rows {
+                categories.push(decode_row::<Category>("categories", row)?);
+            }
+            if !full {
+                break;
+            }
+        }
+        debug!("Listed {} categories", categories.len());
+        Ok(categories)
+    }
+
+    #[instrument(skip(self))]
+    async fn search_transactions_text(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<TransactionTextMatch>> {
+        let start_time = Instant::now();
+        let result: Vec<TransactionTextMatch> = self
+            .call_rpc(
+                "search_transactions_text",
+                json!({
+                    "search_query": query,
+                    "match_count": page_limit(limit, Some(DEFAULT_TEXT_SEARCH_LIMIT)),
+                }),
+            )
+            .await?;
+        info!(
+            "Found {} transactions matching {:?} in {:?}",
+            result.len(),
+            query,
+            start_time.elapsed()
+        );
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(group_by = ?input.group_by, period = ?input.period))]
+    async fn aggregate_spending(
+        &self,
+        input: &AggregateSpendingInput,
+    ) -> Result<Vec<SpendingBucket>> {
+        let start_time = Instant::now();
+        info!("Aggregating spending in database");
+
+        let filters = &input.filters;
+        let result = self
+            .call_rpc(
+                "aggregate_spending",
+                json!({
+                    "group_by": input.group_by.as_ref(),
+                    "period": input.period.map(|period| period.as_ref()),
+                    "from_date": filters.from,
+                    "to_date": filters.to,
+                    "filter_account_id": filters.account_id,
+                    "filter_category_id": filters.category_id,
+                    "filter_direction": filters.direction.map(|direction| direction.as_ref()),
+                }),
+            )
+            .await?;
+
+        let duration = start_time.elapsed();
+        info!("Aggregated spending into {} buckets in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    #[instrument(skip(self, events), fields(count = events.len()))]
+    async fn record_audit_events(&self, events: &[AuditEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let payloads = events
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        self.insert_rows(AUDIT_LOG_TABLE, payloads).await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self, calls), fields(count = calls.len()))]
+    async fn record_tool_calls(&self, calls: &[AuditEvent]) -> Result<()> {
+        if calls.is_empty() {
+            return Ok(());
+        }
+        let payloads = calls
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        self.insert_rows(TOOL_CALLS_TABLE, payloads).await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(tool = ?input.tool, record_id = ?input.record_id))]
+    async fn list_audit_events(&self, input: &ListAuditEventsInput) -> Result<Vec<AuditEvent>> {
+        let start_time = Instant::now();
+        info!("Listing audit events from database");
+
+        let mut query = Vec::new();
+        if let Some(tool) = &input.tool {
+            query.push(("tool", format!("eq.{tool}")));
+        }
+        if let Some(record_id) = &input.record_id {
+            query.push(("record_id", format!("eq.{record_id}")));
+        }
+        query.push(("order", "occurred_at.desc,id.desc".to_string()));
+        query.extend(page_query(
+            page_limit(input.limit, Some(DEFAULT_AUDIT_PAGE)),
+            input.offset,
+        ));
+
+        let result = self
+            .select_rows(AUDIT_LOG_TABLE, &query)
+            .await?
+            .into_iter()
+            .map(|row| decode_row::<AuditEvent>(AUDIT_LOG_TABLE, row))
+            .collect::<Result<Vec<_>>>()?;
+
+        let duration = start_time.elapsed();
+        info!("Retrieved {} audit events in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn soft_delete(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let start_time = Instant::now();
+        let table = kind.table();
+        info!("Soft-deleting {} record {}", table, id);
+
+        let url = format!("{}/{}", self.rest_base, table);
+        let mut query = vec![("id", format!("eq.{id}")), ("select", "id".to_string())];
+        query.extend(self.live_rows_query(table));
+        let deleted_at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        // Not retried: a replay after a lost response would find no live row.
+        let rows = self
+            .execute::<Vec<Value>, _>(&format!("soft delete from {table}"), false, || {
+                Ok(self
+                    .http
+                    .patch(&url)
+                    .query(&query)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "return=representation")
+                    .json(&json!({ DELETED_AT_COLUMN: deleted_at })))
+            })
+            .await?;
+
+        let duration = start_time.elapsed();
+        info!("Soft-deleted {} {} rows in {:?}", rows.len(), table, duration);
+
+        Ok(!rows.is_empty())
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn record_exists(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let query = [("id", format!("eq.{id}"))];
+        let count = self
+            .count_rows_from(&self.rest_base, kind.table(), &query)
+            .await?;
+        Ok(count > 0)
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn delete_record(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let table = kind.table();
+        info!("Deleting {} record {}", table, id);
+
+        let url = format!("{}/{}", self.rest_base, table);
+        let mut query = vec![("id", format!("eq.{id}")), ("select", "id".to_string())];
+        query.extend(self.tenant_query());
+        let rows = self
+            .execute::<Vec<Value>, _>(&format!("delete from {table}"), true, || {
+                Ok(self
+                    .http
+                    .delete(&url)
+                    .query(&query)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "return=representation"))
+            })
+            .await?;
+        Ok(!rows.is_empty())
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn purge_deleted(&self, kind: RecordKind, deleted_before: Option<&str>) -> Result<u64> {
+        let start_time = Instant::now();
+        let table = kind.table();
+        info!("Purging soft-deleted {} rows", table);
+
+        let url = format!("{}/{}", self.rest_base, table);
+        let mut query = vec![
+            (DELETED_AT_COLUMN, "not.is.null".to_string()),
+            ("select", "id".to_string()),
+        ];
+        if let Some(deleted_before) = deleted_before {
+            query.push((DELETED_AT_COLUMN, format!("lt.{deleted_before}")));
+        }
+        query.extend(self.tenant_query());
+        let rows = self
+            .execute::<Vec<Value>, _>(&format!("purge {table}"), false, || {
+                Ok(self
+                    .http
+                    .delete(&url)
+                    .query(&query)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "return=representation"))
+            })
+            .await?;
+
+        let duration = start_time.elapsed();
+        info!("Purged {} {} rows in {:?}", rows.len(), table, duration);
+
+        Ok(rows.len() as u64)
+    }
+
+    #[instrument(skip(self), fields(table = %kind.table()))]
+    async fn embedding_issues(
+        &self,
+        kind: RecordKind,
+        dimension: usize,
+        after: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<EmbeddingIssue>> {
+        let table = embedded_table(kind)?;
+        // Maintenance re-embeds or clears what this reports.
+        self.call_primary_rpc(
+            "embedding_issues",
+            json!({
+                "target_table": table,
+                "expected_dimension": dimension,
+                "after_id": after,
+                "batch_size": limit.clamp(1, MAX_PAGE_SIZE),
+            }),
+        )
+        .await
+    }
+
+    #[instrument(skip(self, embedding), fields(table = %kind.table()))]
+    async fn set_embedding(
+        &self,
+        kind: RecordKind,
+        id: &str,
+        embedding: Option<Vec<f32>>,
+    ) -> Result<()> {
+        let table = embedded_table(kind)?;
+        debug!("Setting embedding of {} row {}", table, id);
+
+        let url = format!("{}/{}", self.rest_base, table);
+        let mut query = vec![("id", format!("eq.{id}")), ("select", "id".to_string())];
+        query.extend(self.tenant_query());
+        let payload = json!({ "embedding": embedding });
+        let rows = self
+            .execute::<Vec<Value>, _>(&format!("update {table} embedding"), true, || {
+                Ok(self
+                    .http
+                    .patch(&url)
+                    .query(&query)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "return=representation")
+                    .json(&payload))
+            })
+            .await?;
+        if rows.is_empty() {
+            bail!("no {table} row with id {id}");
+        }
+        Ok(())
+    }
+
+    /// Custom functions may write, so they go to the primary and are never
+    /// retried. Functions that return nothing yield `null`.
+    #[instrument(skip(self, params), fields(function = %function))]
+    async fn call_function(&self, function: &str, params: Value) -> Result<Value> {
+        let start_time = Instant::now();
+        info!("Calling custom RPC function: {}", function);
+        let mut params = params;
+        if let (Some(tenant_id), Some(object)) = (&self.tenant_id, params.as_object_mut()) {
+            object.insert(TENANT_RPC_PARAM.to_string(), json!(tenant_id));
+        }
+
+        let url = format!("{}/rpc/{}", self.rest_base, function);
+        let body = self
+            .send(&format!("RPC {function}"), false, || {
+                Ok(self
+                    .http
+                    .post(&url)
+                    .headers(self.rest_headers()?)
+                    .json(&params))
+            })
+            .await?
+            .text()
+            .await
+            .map_err(|err| anyhow!("failed to read RPC {function} response: {err}"))?;
+        let result = if body.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&body)
+                .map_err(|err| anyhow!("failed to parse RPC {function} response: {err}"))?
+        };
+
+        let duration = start_time.elapsed();
+        info!("Custom RPC {} completed in {:?}", function, duration);
+
+        Ok(result)
+    }
+
+    #[instrument(skip(self))]
+    async fn bank_link(&self, account_id: &str) -> Result<Option<BankLink>> {
+        // The sync reads the cursor it then advances.
+        let query = [("account_id", format!("eq.{account_id}"))];
+        self.select_primary_rows(BANK_LINKS_TABLE, &query)
+            .await?
+            .into_iter()
+            .next()
+            .map(|row| decode_row(BANK_LINKS_TABLE, row))
+            .transpose()
+    }
+
+    #[instrument(skip(self, link), fields(account_id = %link.account_id))]
+    async fn save_bank_link(&self, link: &BankLink) -> Result<BankLink> {
+        let payload = json!({
+            "account_id": &link.account_id,
+            "item_id": &link.item_id,
+            "access_token": &link.access_token,
+            "plaid_account_id": &link.plaid_account_id,
+            "cursor": &link.cursor,
+            "updated_at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        });
+        let row = self
+            .upsert_row(BANK_LINKS_TABLE, BANK_LINK_KEY, payload)
+            .await?;
+        decode_row(BANK_LINKS_TABLE, row)
+    }
+
+    #[instrument(skip(self))]
+    async fn account_metadata(&self, account_id: &str) -> Result<Map<String, Value>> {
+        let query = [("id", format!("eq.{account_id}"))];
+        let row = self
+            .select_primary_rows("accounts", &query)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("accounts record {account_id} was not found"))?;
+        Ok(match row.get("metadata") {
+            Some(Value::Object(metadata)) => metadata.clone(),
+            _ => Map::new(),
+        })
+    }
+
+    /// PostgREST cannot set a single key of a `jsonb` column, so the
+    /// `set_account_metadata` function merges it in on the server.
+    #[instrument(skip(self, value))]
+    async fn set_account_metadata(&self, account_id: &str, key: &str, value: Value) -> Result<()> {
+        debug!("Setting metadata {} of account {}", key, account_id);
+        let params = json!({
+            "target_account": account_id,
+            "metadata_key": key,
+            "metadata_value": value,
+        });
+        let found = self.call_function("set_account_metadata", params).await?;
+        if found != Value::Bool(true) {
+            bail!("accounts record {account_id} was not found");
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn set_account_network(&self, account_id: &str, network: &str) -> Result<()> {
+        debug!("Setting network of account {} to {}", account_id, network);
+        let url = format!("{}/accounts", self.rest_base);
+        let mut query = vec![
+            ("id", format!("eq.{account_id}")),
+            ("select", "id".to_string()),
+        ];
+        query.extend(self.live_rows_query("accounts"));
+        let payload = json!({ "network": network });
+        let rows = self
+            .execute::<Vec<Value>, _>("update accounts network", true, || {
+                Ok(self
+                    .http
+                    .patch(&url)
+                    .query(&query)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "return=representation")
+                    .json(&payload))
+            })
+            .await?;
+        if rows.is_empty() {
+            bail!("accounts record {account_id} was not found");
+        }
+        Ok(())
+    }
+
+    /// Probes the primary and, when reads are split off, the read endpoint.
+    #[instrument(skip(self))]
+    async fn ping(&self) -> Result<()> {
+        self.ping_endpoint("ping accounts", &self.rest_base).await?;
+        if self.read_rest_base != self.rest_base {
+            self.ping_endpoint("ping read endpoint accounts", &self.read_rest_base)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn metrics(&self) -> Vec<OperationMetrics> {
+        self.metrics.snapshot()
+    }
+}
+
+impl SupabaseGateway {
+    async fn ping_endpoint(&self, operation: &str, rest_base: &str) -> Result<()> {
+        let url = format!("{}/accounts", rest_base);
+        // Not retried: a health probe should report the first failure.
+        self.execute::<Vec<Value>, _>(operation, false, || {
+            Ok(self
+                .http
+                .get(&url)
+                .query(&[("select", "id"), ("limit", "1")])
+                .headers(self.rest_headers()?))
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Reads rows from `table` on the read endpoint. `query` holds PostgREST
+    /// query parameters such as `("type", "eq.offchain")`.
+    async fn select_rows(&self, table: &str, query: &[(&str, String)]) -> Result<Vec<Value>> {
+        self.select_rows_from(&self.read_rest_base, table, query)
+            .await
+    }
+
+    /// Reads rows from `table` on the primary, for reads that decide a write
+    /// and so must not lag behind the writes before them.
+    async fn select_primary_rows(
+        &self,
+        table: &str,
+        query: &[(&str, String)],
+    ) -> Result<Vec<Value>> {
+        self.select_rows_from(&self.rest_base, table, query).await
+    }
+
+    #[instrument(skip(self), fields(table = %table, query = ?query))]
+    async fn select_rows_from(
+        &self,
+        rest_base: &str,
+        table: &str,
+        query: &[(&str, String)],
+    ) -> Result<Vec<Value>> {
+        let start_time = Instant::now();
+        debug!("Selecting rows from {}", table);
+
+        let url = format!("{}/{}", rest_base, table);
+        let scope = self.live_rows_query(table);
+        let result: Vec<Value> = self
+            .execute(&format!("query {table}"), true, || {
+                Ok(self
+                    .http
+                    .get(&url)
+                    .query(&[("select", "*")])
+                    .query(query)
+                    .query(&scope)
+                    .headers(self.rest_headers()?))
+            })
+            .await?;
+
+        let duration = start_time.elapsed();
+        debug!("Selected {} rows in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    /// Counts the rows of `table` matching `query` on the read endpoint.
+    async fn count_rows(&self, table: &str, query: &[(&str, String)]) -> Result<u64> {
+        self.count_rows_from(&self.read_rest_base, table, query)
+            .await
+    }
+
+    /// Counts the rows of `table` matching `query` with `Prefer: count=exact`,
+    /// reading the total from the `Content-Range` header of a `HEAD` request.
+    #[instrument(skip(self), fields(table = %table, query = ?query))]
+    async fn count_rows_from(
+        &self,
+        rest_base: &str,
+        table: &str,
+        query: &[(&str, String)],
+    ) -> Result<u64> {
+        let start_time = Instant::now();
+        debug!("Counting rows in {}", table);
+
+        let url = format!("{}/{}", rest_base, table);
+        let scope = self.live_rows_query(table);
+        let response = self
+            .send(&format!("count {table}"), true, || {
+                Ok(self
+                    .http
+                    .head(&url)
+                    .query(&[("select", "id"), ("limit", "1")])
+                    .query(query)
+                    .query(&scope)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "count=exact"))
+            })
+            .await?;
+
+        let content_range = response
+            .headers()
+            .get("content-range")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| anyhow!("count {table} response has no Content-Range header"))?;
+        let total = parse_content_range_total(content_range)
+            .ok_or_else(|| anyhow!("count {table} returned an unexpected Content-Range {content_range:?}"))?;
+
+        let duration = start_time.elapsed();
+        debug!("Counted {} rows in {:?}", total, duration);
+
+        Ok(total)
+    }
+
+    async fn insert_row(&self, table: &str, payload: Value) -> Result<Value> {
+        self.insert_rows(table, vec![payload])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                error!("Insert into {} returned no rows", table);
+                anyhow!("insert into {table} returned no rows")
+            })
+    }
+
+    /// Inserts `payloads` as one JSON array, which PostgREST writes in a
+    /// single statement, and returns the stored rows in the same order.
+    #[instrument(skip(self, payloads), fields(table = %table, count = payloads.len()))]
+    async fn insert_rows(&self, table: &str, payloads: Vec<Value>) -> Result<Vec<Value>> {
+        let start_time = Instant::now();
+        debug!("Inserting {} records into {}", payloads.len(), table);
+        let payloads: Vec<Value> = payloads
+            .into_iter()
+            .map(|payload| self.with_tenant(payload))
+            .collect();
+
+        let url = format!("{}/{}", self.rest_base, table);
+        let result = self
+            .execute::<Vec<Value>, _>(&format!("insert into {table}"), false, || {
+                Ok(self
+                    .http
+                    .post(&url)
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "return=representation")
+                    .json(&payloads))
+            })
+            .await?;
+
+        let duration = start_time.elapsed();
+        debug!("Inserted {} records in {:?}", result.len(), duration);
+
+        Ok(result)
+    }
+
+    /// Calls a read-only RPC function on the read endpoint.
+    async fn call_rpc<T: DeserializeOwned>(&self, function: &str, payload: Value) -> Result<Vec<T>> {
+        self.call_rpc_at(&self.read_rpc_base, function, payload)
+            .await
+    }
+
+    /// Calls a read-only RPC function on the primary, for results that decide
+    /// a write.
+    async fn call_primary_rpc<T: DeserializeOwned>(
+        &self,
+        function: &str,
+        payload: Value,
+    ) -> Result<Vec<T>> {
+        self.call_rpc_at(&format!("{}/rpc", self.rest_base), function, payload)
+            .await
+    }
+
+    #[instrument(skip(self), fields(function = %function))]
+    async fn call_rpc_at<T: DeserializeOwned>(
+        &self,
+        rpc_base: &str,
+        function: &str,
+        payload: Value,
+    ) -> Result<Vec<T>> {
+        let start_time = Instant::now();
+        debug!("Calling RPC function: {}", function);
+        let mut payload = payload;
+        if let (Some(tenant_id), Some(params)) = (&self.tenant_id, payload.as_object_mut()) {
+            params.insert(TENANT_RPC_PARAM.to_string(), json!(tenant_id));
+        }
+        
+        let url = format!("{}/{}", rpc_base, function);
+        let result: Vec<T> = self
+            .execute(&format!("RPC {function}"), true, || {
+                Ok(self
+                    .http
+                    .post(&url)
+                    .headers(self.rest_headers()?)
+                    .json(&payload))
+            })
+            .await?;
+        
+        let duration = start_time.elapsed();
+        debug!("RPC {} completed in {:?} with {} results", function, duration, result.len());
+        
+        Ok(result)
+    }
+
+    /// Inserts `payload` or merges it into the row that already holds the same
+    /// `on_conflict` key, in a single PostgREST round trip.
+    #[instrument(skip(self, payload), fields(table = %table, on_conflict = %on_conflict))]
+    async fn upsert_row(&self, table: &str, on_conflict: &str, payload: Value) -> Result<Value> {
+        let start_time = Instant::now();
+        debug!("Upserting record into {} on conflict ({})", table, on_conflict);
+        let mut payload = self.with_tenant(payload);
+        // Upserting a soft-deleted row's natural key brings the row back.
+        if RecordKind::ALL.iter().any(|kind| kind.table() == table) {
+            if let Some(row) = payload.as_object_mut() {
+                row.insert(DELETED_AT_COLUMN.to_string(), Value::Null);
+            }
+        }
+        // Natural keys are unique per tenant, so the tenant column joins the
+        // conflict target; without a tenant it is null, which the keys treat
+        // as a tenant of its own.
+        let on_conflict = format!("{TENANT_COLUMN},{on_conflict}");
+
+        let url = format!("{}/{}", self.rest_base, table);
+        let result = self
+            .execute::<Vec<Value>, _>(&format!("upsert into {table}"), false, || {
+                Ok(self
+                    .http
+                    .post(&url)
+                    .query(&[("on_conflict", &on_conflict)])
+                    .headers(self.rest_headers()?)
+                    .header("Prefer", "resolution=merge-duplicates,return=representation")
+                    .json(&payload))
+            })
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                error!("Upsert into {} returned no rows", table);
+                anyhow!("upsert into {table} returned no rows")
+            })?;
+
+        let duration = start_time.elapsed();
+        debug!("Record upserted in {:?}", duration);
+
+        Ok(result)
+    }
+
+    /// Sends the request produced by `build` and parses a JSON response.
+    /// Idempotent requests are retried on transient failures.
+    async fn execute<T, F>(&self, operation: &str, idempotent: bool, build: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> Result<RequestBuilder>,
+    {
+        self.send(operation, idempotent, build)
+            .await?
+            .json::<T>()
+            .await
+            .map_err(|err| anyhow!("failed to parse {operation} response: {err}"))
+    }
+
+    /// Sends the request produced by `build` until it gets a successful
+    /// status, retrying idempotent requests on transient failures. Transient
+    /// failures also count towards the circuit breaker, and an open circuit
+    /// fails the request without sending it. Each call is recorded in the
+    /// gateway's metrics under `operation`, retries included.
+    async fn send<F>(&self, operation: &str, idempotent: bool, build: F) -> Result<Response>
+    where
+        F: Fn() -> Result<RequestBuilder>,
+    {
+        let start_time = Instant::now();
+        let policy = if idempotent {
+            self.retry
+        } else {
+            RetryPolicy::new(0, Duration::ZERO)
+        };
+        let build = &build;
+        let response = policy
+            .run(operation, || async move {
+                self.breaker
+                    .allow()
+                    .map_err(|open| Failure::Permanent(open.into()))?;
+                let result = self.attempt(operation, build).await;
+                match &result {
+                    Err(Failure::Transient(_)) => self.breaker.record_failure(),
+                    _ => self.breaker.record_success(),
+                }
+                result
+            })
+            .await;
+        self.metrics
+            .record(operation, start_time.elapsed(), response.is_ok());
+        response
+    }
+
+    async fn attempt<F>(&self, operation: &str, build: &F) -> Result<Response, Failure>
+    where
+        F: Fn() -> Result<RequestBuilder>,
+    {
+        let response = build()
+            .map_err(Failure::Permanent)?
+            .send()
+            .await
+            .map_err(|err| {
+                Failure::from_reqwest(anyhow!("{operation} request failed: {err}"), &err)
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Failure::from_status(
+                PostgrestError::parse(operation, status, &body).into(),
+                status,
+            ));
+        }
+
+        Ok(response)
+    }
+
+    /// Filters that keep a query to the configured tenant's rows.
+    fn tenant_query(&self) -> Vec<(&'static str, String)> {
+        self.tenant_id
+            .iter()
+            .map(|tenant_id| (TENANT_COLUMN, format!("eq.{tenant_id}")))
+            .collect()
+    }
+
+    /// Filters that keep a query to the tenant's rows of `table` that are not
+    /// soft-deleted.
+    fn live_rows_query(&self, table: &str) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        if RecordKind::ALL.iter().any(|kind| kind.table() == table) {
+            query.push((DELETED_AT_COLUMN, "is.null".to_string()));
+        }
+        query.extend(self.tenant_query());
+        query
+    }
+
+    /// Stamps the tenant column on a row about to be written.
+    fn with_tenant(&self, mut payload: Value) -> Value {
+        if let (Some(tenant_id), Some(row)) = (&self.tenant_id, payload.as_object_mut()) {
+            row.insert(TENANT_COLUMN.to_string(), json!(tenant_id));
+        }
+        payload
+    }
+
+    /// The bearer token for the current request: the caller's access token if
+    /// the tool call carried one, then the session token, then the service key.
+    fn bearer_token(&self) -> Result<String> {
+        let token = AuthContext::current()
+            .access_token
+            .or_else(|| self.session_token.clone());
+        match token {
+            Some(token) => Ok(token),
+            None if self.require_user_auth => Err(anyhow!(
+                "a Supabase user access token is required (SUPABASE_REQUIRE_USER_AUTH is set)"
+            )),
+            None => Ok(self.service_key.clone()),
+        }
+    }
+
+    fn rest_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "apikey",
+            HeaderValue::from_str(&self.service_key).context("invalid apikey header value")?,
+        );
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.bearer_token()?))
+                .context("invalid authorization header value")?,
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(
+            "Accept-Profile",
+            HeaderValue::from_str(&self.schema).context("invalid profile header")?,
+        );
+        headers.insert(
+            "Content-Profile",
+            HeaderValue::from_str(&self.schema).context("invalid profile header")?,
+        );
+        headers.extend(correlation::headers());
+        Ok(headers)
+    }
+}
+
+fn transaction_payload(input: &CreateTransactionInput, embedding: Option<Vec<f32>>) -> Value {
+    json!({
+        "account_id": &input.account_id,
+        "amount": input.amount,
+        "currency": &input.currency,
+        "direction": input.direction.as_ref(),
+        "occurred_at": &input.occurred_at,
+        "description": input.description.clone(),
+        "raw_source": input.raw_source.clone(),
+        "embedding": embedding,
+    })
+}
+
+/// One step as the `execute_batch` function reads it.
+fn batch_step_payload(step: &BatchStep) -> Value {
+    match step {
+        BatchStep::UpsertAccount(input) => json!({
+            "op": "upsert_account",
+            "name": &input.name,
+            "type": input.r#type.as_ref(),
+            "currency": &input.currency,
+            "network": input.network.clone(),
+            "institution": input.institution.clone(),
+        }),
+        BatchStep::CreateTransactions(rows) => json!({
+            "op": "create_transactions",
+            "transactions": rows
+                .iter()
+                .map(|(input, embedding)| transaction_payload(input, embedding.clone()))
+                .collect::<Vec<_>>(),
+        }),
+        BatchStep::Categorize(input) => json!({
+            "op": "categorize",
+            "transaction_id": &input.transaction_id,
+            "category_id": &input.category_id,
+        }),
+    }
+}
+
+fn account_filter_query(params: &ListAccountsInput) -> Vec<(&'static str, String)> {
+    let mut query = Vec::new();
+    if let Some(kind) = params.r#type {
+        query.push(("type", format!("eq.{}", kind.as_ref())));
+    }
+    if let Some(needle) = params
+        .search
+        .as_ref()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+    {
+        query.push(("name", format!("ilike.*{}*", escape_like(needle))));
+    }
+    query
+}
+
+fn transaction_filter_query(filters: &TransactionFilters) -> Vec<(&'static str, String)> {
+    let mut query = Vec::new();
+    if let Some(from) = &filters.from {
+        query.push(("occurred_at", format!("gte.{from}")));
+    }
+    if let Some(to) = &filters.to {
+        query.push(("occurred_at", format!("lt.{to}")));
+    }
+    if let Some(account_id) = &filters.account_id {
+        query.push(("account_id", format!("eq.{account_id}")));
+    }
+    if let Some(category_id) = &filters.category_id {
+        query.push(("category_id", format!("eq.{category_id}")));
+    }
+    if let Some(direction) = filters.direction {
+        query.push(("direction", format!("eq.{}", direction.as_ref())));
+    }
+    query
+}
+
+/// PostgREST `or` filter for the rows after `(occurred_at, id)` in
+/// `occurred_at.desc,id.desc` order. Values are quoted because timestamps
+/// contain `:` and `+`.
+fn keyset_after(occurred_at: &str, id: &str) -> String {
+    format!(
+        "(occurred_at.lt.\"{occurred_at}\",and(occurred_at.eq.\"{occurred_at}\",id.lt.\"{id}\"))"
+    )
+}
+
+fn page_query(limit: Option<u32>, offset: Option<u32>) -> Vec<(&'static str, String)> {
+    let mut query = Vec::new();
+    if let Some(limit) = limit {
+        query.push(("limit", limit.to_string()));
+    }
+    if let Some(offset) = offset.filter(|offset| *offset > 0) {
+        query.push(("offset", offset.to_string()));
+    }
+    query
+}
+
+/// Escapes `LIKE` wildcards so a search term matches literally. PostgREST
+/// turns `*` into `%` on its own, so `*` cannot be matched literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Reads the total from a PostgREST `Content-Range` such as `0-24/3573` or
+/// `*/0`.
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
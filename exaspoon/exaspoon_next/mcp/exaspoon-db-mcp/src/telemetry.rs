@@ -0,0 +1,58 @@
+//! Exports the server's `tracing` spans (tool calls, Supabase requests and
+//! embedding requests) over OTLP/HTTP, so they can be followed in Jaeger,
+//! Tempo, Honeycomb or any other OpenTelemetry backend.
+
+use crate::config::TracingConfig;
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::{warn, Subscriber};
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+/// Batches finished spans and sends them to the configured collector.
+/// Dropping it flushes the spans still queued.
+#[derive(Debug)]
+pub struct OtlpExporter {
+    provider: SdkTracerProvider,
+}
+
+impl OtlpExporter {
+    /// An exporter for `config`, or `None` when no OTLP endpoint is set.
+    pub fn from_config(config: &TracingConfig) -> Result<Option<Self>> {
+        let Some(endpoint) = &config.otlp_endpoint else {
+            return Ok(None);
+        };
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .with_headers(config.otlp_headers.clone())
+            .build()
+            .with_context(|| format!("failed to build the OTLP exporter for {endpoint}"))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .build();
+        Ok(Some(Self { provider }))
+    }
+
+    /// A layer sending every span recorded under it to this exporter.
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer(env!("CARGO_PKG_NAME")))
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            warn!("Failed to flush spans: {}", err);
+        }
+    }
+}
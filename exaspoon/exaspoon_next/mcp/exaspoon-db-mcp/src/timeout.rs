@@ -0,0 +1,34 @@
+//! Time limits on tool calls, so a wedged downstream call fails its tool call
+//! instead of leaving the client waiting forever.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const DEFAULT_TOOL_TIMEOUT_MS: u64 = 120_000;
+
+/// How long each tool may run. A zero limit means no limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolTimeouts {
+    /// Applies to tools without a limit of their own.
+    pub default: Duration,
+    /// Limits by tool name.
+    pub per_tool: HashMap<String, Duration>,
+}
+
+impl Default for ToolTimeouts {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(DEFAULT_TOOL_TIMEOUT_MS), HashMap::new())
+    }
+}
+
+impl ToolTimeouts {
+    pub fn new(default: Duration, per_tool: HashMap<String, Duration>) -> Self {
+        Self { default, per_tool }
+    }
+
+    /// The limit on `tool`, if it has one.
+    pub fn for_tool(&self, tool: &str) -> Option<Duration> {
+        let limit = self.per_tool.get(tool).copied().unwrap_or(self.default);
+        (!limit.is_zero()).then_some(limit)
+    }
+}
@@ -0,0 +1,76 @@
+//! Names clients see for the tools, so the server can share a client with
+//! other MCP servers whose tools are called the same: a prefix put before
+//! every tool's name, and aliases replacing the names of single tools.
+//! Everything inside the server keeps using the original names.
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Longest tool name MCP clients are expected to accept.
+const MAX_TOOL_NAME_LEN: usize = 128;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolNames {
+    /// Put before the name of every tool without an alias.
+    prefix: String,
+    /// Names clients see, by original tool name.
+    aliases: HashMap<String, String>,
+}
+
+impl ToolNames {
+    /// Fails when a resulting name is not a valid tool name or two tools
+    /// would be called the same.
+    pub fn new(prefix: String, aliases: HashMap<String, String>) -> Result<Self> {
+        let prefix = prefix.trim().to_string();
+        if !prefix.is_empty() && !valid_name(&prefix) {
+            bail!("TOOL_NAME_PREFIX {prefix:?} may only hold letters, digits, '_', '-' and '.'");
+        }
+        let mut seen = HashMap::new();
+        for (tool, alias) in &aliases {
+            if !valid_name(alias) {
+                bail!("alias {alias:?} of {tool} is not a valid tool name");
+            }
+            if let Some(other) = seen.insert(alias.as_str(), tool.as_str()) {
+                bail!("{tool} and {other} are both aliased {alias}");
+            }
+        }
+        Ok(Self { prefix, aliases })
+    }
+
+    /// Whether every tool keeps its original name.
+    pub fn is_identity(&self) -> bool {
+        self.prefix.is_empty() && self.aliases.is_empty()
+    }
+
+    /// Original names of the tools given an alias.
+    pub fn aliased(&self) -> impl Iterator<Item = &str> {
+        self.aliases.keys().map(String::as_str)
+    }
+
+    /// The name clients see for `tool`.
+    pub fn exposed(&self, tool: &str) -> String {
+        match self.aliases.get(tool) {
+            Some(alias) => alias.clone(),
+            None => format!("{}{tool}", self.prefix),
+        }
+    }
+
+    /// The original name of the tool clients call `name`, or `None` when no
+    /// tool is exposed under it, including a tool's original name once it
+    /// has been prefixed or aliased.
+    pub fn original(&self, name: &str) -> Option<String> {
+        if let Some((tool, _)) = self.aliases.iter().find(|(_, alias)| *alias == name) {
+            return Some(tool.clone());
+        }
+        let tool = name.strip_prefix(&self.prefix)?;
+        (!self.aliases.contains_key(tool)).then(|| tool.to_string())
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_TOOL_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
@@ -0,0 +1,391 @@
+//! Checks on tool arguments that hold whichever tool receives them. They run
+//! before the arguments reach a handler, so a bad date, amount, currency,
+//! oversized text or page size is rejected the same way by every tool, naming
+//! the offending field by its path, e.g. `transactions[2].amount`.
+
+use crate::onchain::{ethereum, solana};
+use crate::supabase::MAX_PAGE_SIZE;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use serde_json::{Map, Value};
+
+/// Longest `description` of a transaction or category.
+pub const MAX_DESCRIPTION_CHARS: usize = 1_000;
+/// Longest `raw_source`, e.g. a pasted bank statement line or email.
+pub const MAX_RAW_SOURCE_CHARS: usize = 10_000;
+/// Longest account or category `name`.
+pub const MAX_NAME_CHARS: usize = 200;
+
+/// Keys whose values are passed through untouched, such as `call_rpc`'s
+/// arguments for a Postgres function, or checked by their tool, such as
+/// `import_json`'s records, each of which may fail on its own.
+const FREE_FORM: &[&str] = &["params", "records"];
+
+/// What the value of a field must be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// An RFC 3339 timestamp.
+    Rfc3339,
+    /// A number above zero.
+    Positive,
+    /// A currency or asset code as [`currency_code`] writes it, e.g. `USD`,
+    /// `USDT` or a Solana mint. Empty passes, as a missing currency may still
+    /// be asked of the user.
+    Currency,
+    /// A string of at most this many characters.
+    MaxChars(usize),
+    /// An integer within the bounds, inclusive.
+    Range(u64, u64),
+    /// A UTC offset or IANA timezone, see [`parse_timezone`]. Empty passes,
+    /// as it clears a session default.
+    Timezone,
+}
+
+impl Rule {
+    /// The rule for arguments named `key`, if any.
+    pub fn for_field(key: &str) -> Option<Self> {
+        match key {
+            "occurred_at" => Some(Self::Rfc3339),
+            "amount" => Some(Self::Positive),
+            "currency" => Some(Self::Currency),
+            "description" => Some(Self::MaxChars(MAX_DESCRIPTION_CHARS)),
+            "raw_source" => Some(Self::MaxChars(MAX_RAW_SOURCE_CHARS)),
+            "name" => Some(Self::MaxChars(MAX_NAME_CHARS)),
+            "limit" => Some(Self::Range(1, MAX_PAGE_SIZE as u64)),
+            "timezone" => Some(Self::Timezone),
+            _ => None,
+        }
+    }
+
+    pub fn as_ref(&self) -> &'static str {
+        match self {
+            Self::Rfc3339 => "rfc3339",
+            Self::Positive => "positive",
+            Self::Currency => "currency_code",
+            Self::MaxChars(_) => "max_chars",
+            Self::Range(..) => "range",
+            Self::Timezone => "timezone",
+        }
+    }
+
+    fn admits(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::Rfc3339, Value::String(text)) => {
+                chrono::DateTime::parse_from_rfc3339(text).is_ok()
+            }
+            (Self::Positive, Value::Number(number)) => {
+                number.as_f64().is_some_and(|amount| amount > 0.0)
+            }
+            (Self::Currency, Value::String(code)) => {
+                code.is_empty() || currency_code(code).is_some_and(|normal| normal == *code)
+            }
+            (Self::MaxChars(max), Value::String(text)) => text.chars().count() <= *max,
+            (Self::Range(min, max), Value::Number(number)) => number
+                .as_u64()
+                .is_some_and(|value| (*min..=*max).contains(&value)),
+            (Self::Timezone, Value::String(text)) => {
+                text.is_empty() || parse_timezone(text).is_some()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Longest ticker taken as a currency or asset code, e.g. `USDC.E`.
+pub const MAX_TICKER_CHARS: usize = 11;
+
+/// `code` as balances are kept in: an ISO 4217 code or asset ticker of 2 to
+/// [`MAX_TICKER_CHARS`] letters, digits, `.`, `-` or `_`, uppercased; an
+/// Ethereum token contract in lower case; or a Solana mint as written, since
+/// base58 is case-sensitive.
+pub fn currency_code(code: &str) -> Option<String> {
+    let code = code.trim();
+    let ticker = (2..=MAX_TICKER_CHARS).contains(&code.len())
+        && code.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && code
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_'));
+    if ticker {
+        return Some(code.to_ascii_uppercase());
+    }
+    ethereum::parse_address(code)
+        .or_else(|_| solana::parse_address(code))
+        .ok()
+}
+
+/// Currencies whose minor unit is a thousandth.
+const THREE_DECIMAL_CURRENCIES: &[&str] = &["BHD", "IQD", "JOD", "KWD", "LYD", "OMR", "TND"];
+
+/// Decimals amounts of `code` are written with: 3 for the dinars and rials
+/// counted in thousandths, such as KWD and OMR, 2 for anything else.
+pub fn currency_decimals(code: &str) -> usize {
+    if THREE_DECIMAL_CURRENCIES.contains(&code.trim().to_ascii_uppercase().as_str()) {
+        3
+    } else {
+        2
+    }
+}
+
+/// Where dates and times given without an offset are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timezone {
+    /// The same offset all year.
+    Offset(FixedOffset),
+    /// An IANA zone, whose offset follows its daylight saving rules.
+    Zone(Tz),
+}
+
+impl Timezone {
+    /// `naive` as a local time here. A time that occurs twice when clocks go
+    /// back is the first, and one skipped when they go forward is read an
+    /// hour later.
+    pub fn localize(&self, naive: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Self::Offset(offset) => offset.from_local_datetime(&naive).single(),
+            Self::Zone(zone) => zone
+                .from_local_datetime(&naive)
+                .earliest()
+                .or_else(|| {
+                    zone.from_local_datetime(&(naive + TimeDelta::hours(1)))
+                        .earliest()
+                })
+                .map(|local| local.with_timezone(&local.offset().fix())),
+        }
+    }
+}
+
+/// A UTC offset, see [`parse_utc_offset`], or an IANA timezone such as
+/// `Europe/Berlin`.
+pub fn parse_timezone(text: &str) -> Option<Timezone> {
+    parse_utc_offset(text)
+        .map(Timezone::Offset)
+        .or_else(|| text.trim().parse().ok().map(Timezone::Zone))
+}
+
+/// `UTC`, `Z` or an offset such as `+03:00` or `-0530`.
+pub fn parse_utc_offset(text: &str) -> Option<FixedOffset> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("utc") || text.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match text.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = text[1..].replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// An argument that broke its field's [`Rule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invalid {
+    /// Path of the argument, e.g. `filters.limit` or `transactions[0].amount`.
+    pub field: String,
+    pub rule: Rule,
+}
+
+/// Checks every argument with a [`Rule`], at any depth, returning the first
+/// one that breaks it. Absent and `null` arguments are left to the handler.
+pub fn validate(arguments: &Map<String, Value>) -> Result<(), Invalid> {
+    validate_object("", arguments)
+}
+
+fn validate_object(prefix: &str, object: &Map<String, Value>) -> Result<(), Invalid> {
+    for (key, value) in object {
+        if FREE_FORM.contains(&key.as_str()) || value.is_null() {
+            continue;
+        }
+        let field = join(prefix, key);
+        if let Some(rule) = Rule::for_field(key) {
+            if !rule.admits(value) {
+                return Err(Invalid { field, rule });
+            }
+        }
+        validate_value(&field, value)?;
+    }
+    Ok(())
+}
+
+fn validate_value(field: &str, value: &Value) -> Result<(), Invalid> {
+    match value {
+        Value::Object(object) => validate_object(field, object),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, item)| validate_value(&format!("{field}[{index}]"), item)),
+        _ => Ok(()),
+    }
+}
+
+/// A value that does not match its JSON Schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Path of the value, e.g. `records[2].direction`.
+    pub field: String,
+    /// What is wrong, naming the field, e.g. `records[2].amount must be a
+    /// number`.
+    pub message: String,
+}
+
+/// Checks `value`, found at `field`, against `schema` as a tool advertises
+/// its input: `type` (with `nullable`), `enum`, `const`, `required`,
+/// `properties`, `additionalProperties: false`, `items`, `anyOf` and `oneOf`
+/// (either form passing), and `$ref`s into the schema's `$defs`. Other
+/// keywords are not checked; the field rules are, by [`validate_at`].
+pub fn conform(schema: &Map<String, Value>, field: &str, value: &Value) -> Result<(), Mismatch> {
+    conform_to(schema, schema, field, value)
+}
+
+/// Checks every argument with a [`Rule`] inside `value`, found at `field`.
+pub fn validate_at(field: &str, value: &Value) -> Result<(), Invalid> {
+    validate_value(field, value)
+}
+
+fn conform_to(
+    root: &Map<String, Value>,
+    schema: &Map<String, Value>,
+    field: &str,
+    value: &Value,
+) -> Result<(), Mismatch> {
+    let mismatch = |what: String| {
+        Err(Mismatch {
+            field: field.to_string(),
+            message: format!("{field} {what}"),
+        })
+    };
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let definition = reference
+            .strip_prefix("#/$defs/")
+            .and_then(|name| root.get("$defs")?.get(name)?.as_object());
+        if let Some(definition) = definition {
+            conform_to(root, definition, field, value)?;
+        }
+    }
+    if let Some(types) = schema.get("type") {
+        let mut types: Vec<&str> = match types {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if schema.get("nullable") == Some(&Value::Bool(true)) {
+            types.push("null");
+        }
+        if !types.is_empty() && !types.iter().any(|name| is_type(name, value)) {
+            let names: Vec<_> = types.iter().map(|name| type_name(name)).collect();
+            return mismatch(format!("must be {}", names.join(" or ")));
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<_> = allowed.iter().map(display).collect();
+            return mismatch(format!("must be one of {}", allowed.join(", ")));
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            return mismatch(format!("must be {}", display(constant)));
+        }
+    }
+    for key in ["anyOf", "oneOf"] {
+        if let Some(forms) = schema.get(key).and_then(Value::as_array) {
+            let passes = forms
+                .iter()
+                .filter_map(Value::as_object)
+                .any(|form| conform_to(root, form, field, value).is_ok());
+            if !passes {
+                return mismatch(format!("matches none of its {} allowed forms", forms.len()));
+            }
+        }
+    }
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            let required = schema.get("required").and_then(Value::as_array);
+            for key in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    return Err(Mismatch {
+                        field: join(field, key),
+                        message: format!("{} is required", join(field, key)),
+                    });
+                }
+            }
+            let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+            for (key, item) in object {
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(Value::Object(property)) => {
+                        conform_to(root, property, &join(field, key), item)?
+                    }
+                    Some(_) => {}
+                    None if closed => {
+                        return Err(Mismatch {
+                            field: join(field, key),
+                            message: format!("{} is not a known field", join(field, key)),
+                        });
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items").and_then(Value::as_object) {
+                for (index, item) in items.iter().enumerate() {
+                    conform_to(root, item_schema, &format!("{field}[{index}]"), item)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn is_type(name: &str, value: &Value) -> bool {
+    match (name, value) {
+        ("null", Value::Null) => true,
+        ("boolean", Value::Bool(_)) => true,
+        ("string", Value::String(_)) => true,
+        ("number", Value::Number(_)) => true,
+        ("integer", Value::Number(number)) => {
+            number.is_i64() || number.is_u64() || number.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        ("array", Value::Array(_)) => true,
+        ("object", Value::Object(_)) => true,
+        _ => false,
+    }
+}
+
+fn type_name(name: &str) -> &str {
+    match name {
+        "boolean" => "true or false",
+        "string" => "a string",
+        "number" => "a number",
+        "integer" => "an integer",
+        "array" => "an array",
+        "object" => "an object",
+        other => other,
+    }
+}
+
+/// Strings unquoted, other values as JSON.
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
@@ -0,0 +1,128 @@
+//! The WebSocket transport: clients connect to a long-lived server over
+//! `ws://` and exchange one JSON-RPC message per text frame, each connection
+//! running a session of its own, as over the HTTP transport.
+
+use crate::{origin::AllowedHosts, server::ExaspoonDbServer};
+use anyhow::Result;
+use futures_util::{future, SinkExt, StreamExt};
+use rmcp::{
+    model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
+    ServiceExt,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        self,
+        handshake::server::{Callback, ErrorResponse, Request, Response},
+        http::{header, StatusCode},
+        Message,
+    },
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Accepts WebSocket connections on `listener` until `stop` is cancelled,
+/// serving each in a [`ExaspoonDbServer::new_session`]. Connections beyond
+/// the session limit are closed before the handshake, and handshakes whose
+/// `Host` or `Origin` is not among `allowed_hosts` are refused with 403.
+pub async fn serve(
+    server: ExaspoonDbServer,
+    listener: TcpListener,
+    allowed_hosts: AllowedHosts,
+    stop: CancellationToken,
+) -> Result<()> {
+    let address = listener.local_addr()?;
+    let allowed_hosts = Arc::new(allowed_hosts);
+    info!("Serving MCP at ws://{}", address);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    if let Some(session) = server.new_session() {
+                        tokio::spawn(connect(session, stream, peer, allowed_hosts.clone()));
+                    }
+                }
+                Err(err) => warn!("Failed to accept a connection: {}", err),
+            },
+            _ = stop.cancelled() => {
+                info!("Stopped serving MCP at ws://{}", address);
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Runs `session` over the WebSocket `stream` until either side closes it.
+async fn connect(
+    session: ExaspoonDbServer,
+    stream: TcpStream,
+    peer: SocketAddr,
+    allowed_hosts: Arc<AllowedHosts>,
+) {
+    let socket = match accept_hdr_async(stream, CheckHost(allowed_hosts)).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Rejected WebSocket handshake from {}: {}", peer, err);
+            return;
+        }
+    };
+    info!("Client session started from {}", peer);
+    let (sink, frames) = socket.split();
+    let sink = sink.with(|message: ServerJsonRpcMessage| {
+        future::ready(
+            serde_json::to_string(&message)
+                .map(Message::text)
+                .map_err(|err| tungstenite::Error::Io(std::io::Error::other(err))),
+        )
+    });
+    let messages = frames
+        .take_while(|frame| future::ready(matches!(frame, Ok(frame) if !frame.is_close())))
+        .filter_map(|frame| {
+            future::ready(match frame {
+                Ok(Message::Text(text)) => {
+                    match serde_json::from_str::<ClientJsonRpcMessage>(&text) {
+                        Ok(message) => Some(message),
+                        Err(err) => {
+                            warn!("Ignored malformed message: {}", err);
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            })
+        });
+    match session.serve((Box::pin(sink), Box::pin(messages))).await {
+        Ok(running) => {
+            let reason = running.waiting().await;
+            debug!("Client session from {} ended: {:?}", peer, reason);
+        }
+        Err(err) => warn!("Client session from {} failed to start: {}", peer, err),
+    }
+}
+
+/// Refuses a handshake addressed to a host, or sent from a browser origin,
+/// that its [`AllowedHosts`] do not admit.
+struct CheckHost(Arc<AllowedHosts>);
+
+impl Callback for CheckHost {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let (host, origin) = (header(header::HOST), header(header::ORIGIN));
+        if self.0.admits(host, origin) {
+            return Ok(response);
+        }
+        let mut refused = ErrorResponse::new(Some(format!(
+            "host {host:?} or origin {origin:?} not allowed"
+        )));
+        *refused.status_mut() = StatusCode::FORBIDDEN;
+        Err(refused)
+    }
+}
@@ -0,0 +1,183 @@
+//! A small XML reader for the documents imported here, which keeps each
+//! element's text, attributes and children and its place in the document.
+
+use anyhow::{bail, Context, Result};
+
+/// An XML element with the text directly inside it, trimmed and unescaped,
+/// and its byte range in the document. Namespace prefixes are dropped.
+#[derive(Debug, Default)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Element>,
+    pub span: (usize, usize),
+}
+
+impl Element {
+    /// The root element of `document`.
+    pub fn parse(document: &str) -> Result<Self> {
+        let mut stack = vec![Element::default()];
+        let mut rest = 0;
+        while let Some(found) = document[rest..].find('<') {
+            let open = rest + found;
+            let text = unescape(document[rest..open].trim());
+            if let Some(current) = stack.last_mut() {
+                current.text.push_str(&text);
+            }
+            let end = if document[open..].starts_with("<!--") {
+                document[open..].find("-->").map(|end| open + end + 2)
+            } else {
+                document[open..].find('>').map(|end| open + end)
+            }
+            .context("unterminated tag")?;
+            rest = end + 1;
+            let inner = &document[open + 1..end];
+            if inner.starts_with('?') || inner.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = inner.strip_prefix('/') {
+                let mut element = stack.pop().context("unbalanced tags")?;
+                if element.name != local_name(name.trim()) {
+                    bail!("<{}> is closed by </{}>", element.name, name.trim());
+                }
+                element.span.1 = rest;
+                stack
+                    .last_mut()
+                    .context("unbalanced tags")?
+                    .children
+                    .push(element);
+                continue;
+            }
+            let closed = inner.ends_with('/');
+            let inner = inner.trim_end_matches('/');
+            let (name, attributes) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+            let element = Element {
+                name: local_name(name).to_string(),
+                attributes: attributes_of(attributes),
+                span: (open, rest),
+                ..Default::default()
+            };
+            if closed {
+                stack
+                    .last_mut()
+                    .context("unbalanced tags")?
+                    .children
+                    .push(element);
+            } else {
+                stack.push(element);
+            }
+        }
+        let document_node = match (stack.pop(), stack.is_empty()) {
+            (Some(node), true) => node,
+            (Some(element), false) => bail!("<{}> is not closed", element.name),
+            (None, _) => bail!("unbalanced tags"),
+        };
+        document_node
+            .children
+            .into_iter()
+            .next()
+            .context("not an XML document")
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Elements named `name` anywhere below this one, depth first.
+    pub fn descendants<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        let mut pending = vec![self];
+        std::iter::from_fn(move || {
+            while let Some(element) = pending.pop() {
+                pending.extend(element.children.iter().rev());
+                if element.name == name && !std::ptr::eq(element, self) {
+                    return Some(element);
+                }
+            }
+            None
+        })
+    }
+
+    /// The non-empty text of the element at `path` below this one.
+    pub fn text_at(&self, path: &[&str]) -> Option<String> {
+        let mut element = self;
+        for name in path {
+            element = element.child(name)?;
+        }
+        (!element.text.is_empty()).then(|| element.text.clone())
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// `ns:Ntry` as `Ntry`.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// `Ccy="EUR" xmlns="..."` as pairs, values unescaped.
+fn attributes_of(text: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = text;
+    while let Some((key, value)) = rest.split_once('=') {
+        let value = value.trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = value[1..].find(quote) else {
+            break;
+        };
+        attributes.push((
+            local_name(key.trim()).to_string(),
+            unescape(&value[1..1 + end]),
+        ));
+        rest = &value[end + 2..];
+    }
+    attributes
+}
+
+/// `text` with its entity and character references replaced.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest.find(';').map(|end| &rest[1..end]);
+        let character = reference.and_then(|reference| match reference {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "amp" => Some('&'),
+            _ => {
+                let code = match reference.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => reference.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (character, reference) {
+            (Some(character), Some(reference)) => {
+                unescaped.push(character);
+                rest = &rest[reference.len() + 2..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
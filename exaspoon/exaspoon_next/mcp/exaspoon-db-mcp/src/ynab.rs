@@ -0,0 +1,740 @@
+//! YNAB budgets, for `import_ynab` and `export_ynab`: the register and plan
+//! CSV files of YNAB's "Export budget", or the YNAB API reached with a
+//! personal access token. The API counts money in milliunits, thousandths
+//! of the budget's currency.
+//!
+//! YNAB files outflows under categories of category groups, and inflows
+//! under Ready to Assign, which only stands for money not assigned yet and
+//! so is no category here; neither are the categories YNAB keeps for its own
+//! bookkeeping. A transfer between two budget accounts appears in both.
+
+use crate::cancellation;
+use crate::config::{HttpClientConfig, YnabConfig};
+use crate::correlation;
+use crate::models::{CreateTransactionInput, TransactionDirection};
+use crate::qif;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::NaiveDate;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Instant;
+use tracing::{error, info, instrument};
+
+pub const YNAB_BASE_URL: &str = "https://api.ynab.com/v1";
+
+/// What the YNAB API takes for the budget opened last.
+pub const LAST_USED_BUDGET: &str = "last-used";
+
+/// Category group `export_ynab` adds categories to unless told another.
+pub const DEFAULT_GROUP: &str = "Exaspoon";
+
+/// Category groups YNAB keeps for itself: Ready to Assign and Uncategorized,
+/// hidden categories, and the payments of credit card accounts.
+const INTERNAL_GROUPS: &[&str] = &[
+    "Inflow",
+    "Internal Master Category",
+    "Hidden Categories",
+    "Credit Card Payments",
+];
+
+/// Payees of transfers start with this in register exports, e.g.
+/// `Transfer : Savings`.
+const TRANSFER_PAYEE: &str = "Transfer : ";
+
+/// The categories and transactions of a budget.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    /// In budget order, without YNAB's internal ones.
+    pub categories: Vec<YnabCategory>,
+    /// Oldest first, split transactions as a transaction per split.
+    pub transactions: Vec<YnabTransaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YnabCategory {
+    /// YNAB's id; exports carry none.
+    pub id: Option<String>,
+    pub name: String,
+    pub group: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct YnabTransaction {
+    /// YNAB's id, or for a register row one made from its contents.
+    pub id: String,
+    /// Name of the budget account.
+    pub account: String,
+    /// RFC 3339 in UTC.
+    pub date: String,
+    pub payee: Option<String>,
+    /// `None` for inflows to Ready to Assign, transfers and uncategorized
+    /// transactions.
+    pub category: Option<String>,
+    pub memo: Option<String>,
+    /// Negative for money leaving the account.
+    pub amount: f64,
+    /// Whether the money moved to or from another budget account.
+    pub transfer: bool,
+}
+
+impl YnabTransaction {
+    pub fn direction(&self) -> TransactionDirection {
+        if self.transfer {
+            TransactionDirection::Transfer
+        } else if self.amount < 0.0 {
+            TransactionDirection::Expense
+        } else {
+            TransactionDirection::Income
+        }
+    }
+
+    /// The transaction as `create_transaction` takes it, with its YNAB id and
+    /// signed amount as its raw source so that [`transaction_id`] and
+    /// [`inflow`] find them again.
+    pub fn to_input(&self, account_id: &str, currency: &str) -> CreateTransactionInput {
+        let description = [self.payee.as_deref(), self.memo.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" - ");
+        CreateTransactionInput {
+            account_id: account_id.to_string(),
+            amount: self.amount.abs(),
+            currency: currency.to_string(),
+            direction: self.direction(),
+            occurred_at: self.date.clone(),
+            description: (!description.is_empty()).then_some(description),
+            raw_source: Some(json!({ "ynab_id": self.id, "amount": self.amount }).to_string()),
+        }
+    }
+}
+
+/// Whether a transaction recorded by an import brought money into its
+/// account, read back from its raw source.
+pub fn inflow(raw_source: &str) -> Option<bool> {
+    transaction_id(raw_source)?;
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("amount")?.as_f64().map(|amount| amount > 0.0)
+}
+
+/// The YNAB id of a transaction recorded by an import, read back from its
+/// raw source.
+pub fn transaction_id(raw_source: &str) -> Option<String> {
+    if !raw_source.starts_with('{') {
+        return None;
+    }
+    let raw: Value = serde_json::from_str(raw_source).ok()?;
+    raw.get("ynab_id")?
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+/// Whether YNAB keeps the categories of `group` for itself.
+pub fn is_internal(group: &str) -> bool {
+    INTERNAL_GROUPS
+        .iter()
+        .any(|internal| internal.eq_ignore_ascii_case(group.trim()))
+}
+
+/// The budget in the register CSV of an export, with the categories of its
+/// plan CSV when given, which include those no transaction is filed under.
+/// Dates such as `05/01/2024` are read day first when `day_first` is set, and
+/// amounts as those of a currency with `decimals` decimals, which tells
+/// `1.500` of a dinar from 1500 dollars.
+pub fn parse_export(
+    register: &str,
+    plan: Option<&str>,
+    day_first: bool,
+    decimals: usize,
+) -> Result<Budget> {
+    let mut categories = Vec::new();
+    if let Some(plan) = plan {
+        let (header, rows) = csv(plan).context("plan")?;
+        let group = column(&header, "Category Group").context("plan")?;
+        let name = column(&header, "Category").context("plan")?;
+        for row in &rows {
+            categories.push(category(cell(row, group), cell(row, name)));
+        }
+    }
+
+    let (header, rows) = csv(register).context("register")?;
+    let required = |name: &str| column(&header, name).context("register");
+    let (account, date) = (required("Account")?, required("Date")?);
+    let (outflow, inflow) = (required("Outflow")?, required("Inflow")?);
+    let [payee, group, name, memo] =
+        ["Payee", "Category Group", "Category", "Memo"].map(|name| column(&header, name).ok());
+    let optional = |row: &[String], index: Option<usize>| {
+        index
+            .map(|index| cell(row, index))
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    let mut transactions = Vec::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    for (index, row) in rows.iter().enumerate() {
+        let line = || format!("register row {}", index + 1);
+        let booked = cell(row, date);
+        let booked = qif::parse_date(booked, day_first)
+            .map_err(|_| anyhow!("{booked:?} is not a date"))
+            .with_context(line)?;
+        let amount = money(cell(row, inflow), decimals).with_context(line)?
+            - money(cell(row, outflow), decimals).with_context(line)?;
+        let payee = optional(row, payee);
+        let transfer = payee
+            .as_deref()
+            .is_some_and(|payee| payee.starts_with(TRANSFER_PAYEE));
+        let filed = match (optional(row, group), optional(row, name)) {
+            (Some(group), Some(name)) if !transfer && !is_internal(&group) => {
+                categories.push(category(&group, &name));
+                Some(name)
+            }
+            _ => None,
+        };
+        let memo = optional(row, memo).map(|memo| split_memo(&memo).to_string());
+        let account = cell(row, account).to_string();
+
+        // Register rows carry no ids, so one is made from what the row says,
+        // and counted for rows that say the same. The category and memo are
+        // left out, since they are edited in YNAB after the fact.
+        let mut hasher = Sha256::new();
+        for part in [
+            account.as_str(),
+            booked.as_str(),
+            payee.as_deref().unwrap_or_default(),
+            &format!("{amount:.3}"),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let digest = hasher.finalize();
+        let hash = digest[..8]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let occurrence = occurrences.entry(hash.clone()).or_default();
+        let id = format!("register-{hash}-{occurrence}");
+        *occurrence += 1;
+
+        transactions.push(YnabTransaction {
+            id,
+            account,
+            date: booked,
+            payee,
+            category: filed,
+            memo: memo.filter(|memo| !memo.is_empty()),
+            amount,
+            transfer,
+        });
+    }
+    transactions.sort_by(|left, right| left.date.cmp(&right.date));
+
+    let mut seen = HashSet::new();
+    categories.retain(|category| {
+        !category.name.is_empty()
+            && !is_internal(&category.group)
+            && seen.insert(category.name.clone())
+    });
+    Ok(Budget {
+        categories,
+        transactions,
+    })
+}
+
+fn category(group: &str, name: &str) -> YnabCategory {
+    YnabCategory {
+        id: None,
+        name: name.trim().to_string(),
+        group: group.trim().to_string(),
+    }
+}
+
+/// The memo of a split's row without the `Split (1/3) ` YNAB puts before it.
+fn split_memo(memo: &str) -> &str {
+    let Some(rest) = memo.strip_prefix("Split (") else {
+        return memo;
+    };
+    match rest.split_once(')') {
+        Some((counter, rest)) if counter.chars().all(|c| c.is_ascii_digit() || c == '/') => {
+            rest.trim_start()
+        }
+        _ => memo,
+    }
+}
+
+/// The index of the `name` column in `header`.
+fn column(header: &[String], name: &str) -> Result<usize> {
+    header
+        .iter()
+        .position(|column| column == name)
+        .with_context(|| format!("no {name} column"))
+}
+
+fn cell(row: &[String], index: usize) -> &str {
+    row.get(index).map(|value| value.trim()).unwrap_or_default()
+}
+
+/// An amount as YNAB writes it in the user's number format, e.g. `$1,234.56`
+/// or `1.234,56 €`, of a currency with `decimals` decimals; empty for none.
+fn money(value: &str, decimals: usize) -> Result<f64> {
+    Ok(qif::parse_amount(value, decimals)?.unwrap_or(0.0))
+}
+
+/// The header and rows of a CSV document, quoted the way YNAB quotes every
+/// field.
+fn csv(document: &str) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+    let document = document.trim_start_matches('\u{feff}');
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = document.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("a quoted field is not closed");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    if rows.is_empty() {
+        bail!("no header row");
+    }
+    let header = rows
+        .remove(0)
+        .into_iter()
+        .map(|name| name.trim().to_string())
+        .collect();
+    Ok((header, rows))
+}
+
+/// Reads and writes budgets through the YNAB API with one personal access
+/// token.
+pub struct YnabClient {
+    http: Client,
+    base_url: String,
+    token: String,
+    budget_id: String,
+}
+
+/// Context of an error that stopped adding categories partway, with the
+/// categories added by then.
+#[derive(Debug, Clone, Copy)]
+pub struct Interrupted {
+    pub categories: usize,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stopped after adding {} YNAB categories",
+            self.categories
+        )
+    }
+}
+
+/// An error response of the YNAB API, e.g. `404.2` `resource_not_found` for
+/// an unknown budget.
+#[derive(Debug, Clone, Deserialize)]
+pub struct YnabError {
+    #[serde(skip)]
+    pub status_code: u16,
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub detail: String,
+}
+
+impl fmt::Display for YnabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "YNAB error {} {}: {}",
+            self.status_code, self.name, self.detail
+        )
+    }
+}
+
+impl std::error::Error for YnabError {}
+
+#[derive(Deserialize)]
+struct Data<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: YnabError,
+}
+
+#[derive(Deserialize)]
+struct SettingsResponse {
+    settings: Settings,
+}
+
+#[derive(Deserialize)]
+struct Settings {
+    currency_format: Option<CurrencyFormat>,
+}
+
+#[derive(Deserialize)]
+struct CurrencyFormat {
+    iso_code: String,
+}
+
+#[derive(Deserialize)]
+struct CategoriesResponse {
+    category_groups: Vec<CategoryGroup>,
+}
+
+#[derive(Deserialize)]
+struct CategoryGroup {
+    #[serde(default)]
+    id: String,
+    name: String,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    deleted: bool,
+    categories: Vec<ApiCategory>,
+}
+
+#[derive(Deserialize)]
+struct CategoryGroupResponse {
+    category_group: ApiCategoryGroup,
+}
+
+#[derive(Deserialize)]
+struct ApiCategoryGroup {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CategoryResponse {
+    category: ApiCategory,
+}
+
+#[derive(Deserialize)]
+struct ApiCategory {
+    id: String,
+    name: String,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Deserialize)]
+struct TransactionsResponse {
+    transactions: Vec<ApiTransaction>,
+}
+
+#[derive(Deserialize)]
+struct ApiTransaction {
+    id: String,
+    date: String,
+    amount: i64,
+    #[serde(default)]
+    memo: Option<String>,
+    account_name: String,
+    #[serde(default)]
+    payee_name: Option<String>,
+    #[serde(default)]
+    category_name: Option<String>,
+    #[serde(default)]
+    transfer_account_id: Option<String>,
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    subtransactions: Vec<ApiSubtransaction>,
+}
+
+#[derive(Deserialize)]
+struct ApiSubtransaction {
+    id: String,
+    amount: i64,
+    #[serde(default)]
+    memo: Option<String>,
+    #[serde(default)]
+    payee_name: Option<String>,
+    #[serde(default)]
+    category_name: Option<String>,
+    #[serde(default)]
+    transfer_account_id: Option<String>,
+    #[serde(default)]
+    deleted: bool,
+}
+
+impl YnabClient {
+    pub fn new(config: &YnabConfig, http: &HttpClientConfig) -> Result<Self> {
+        info!("Initializing YNAB client for {}", config.base_url);
+        Ok(Self {
+            http: http
+                .apply(Client::builder())
+                .build()
+                .context("failed to build HTTP client for YNAB")?,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            token: config.token.clone(),
+            budget_id: config
+                .budget_id
+                .clone()
+                .unwrap_or_else(|| LAST_USED_BUDGET.to_string()),
+        })
+    }
+
+    /// The budget read and written unless a call names another.
+    pub fn budget_id(&self) -> &str {
+        &self.budget_id
+    }
+
+    /// ISO 4217 code of the budget's currency.
+    #[instrument(skip(self))]
+    pub async fn currency(&self, budget_id: &str) -> Result<String> {
+        let url = self.url(&["budgets", budget_id, "settings"])?;
+        let response: SettingsResponse = self.send(self.http.get(url)).await?;
+        response
+            .settings
+            .currency_format
+            .map(|format| format.iso_code.to_ascii_uppercase())
+            .filter(|code| !code.is_empty())
+            .context("the YNAB budget has no currency")
+    }
+
+    /// The budget's categories in budget order, without deleted, hidden and
+    /// internal ones.
+    #[instrument(skip(self))]
+    pub async fn categories(&self, budget_id: &str) -> Result<Vec<YnabCategory>> {
+        Ok(self
+            .category_groups(budget_id)
+            .await?
+            .into_iter()
+            .flat_map(|group| {
+                group
+                    .categories
+                    .into_iter()
+                    .filter(|category| !category.hidden && !category.deleted)
+                    .map(move |category| YnabCategory {
+                        id: Some(category.id),
+                        name: category.name,
+                        group: group.name.clone(),
+                    })
+            })
+            .collect())
+    }
+
+    /// The budget's categories and transactions, leaving out deleted ones.
+    #[instrument(skip(self))]
+    pub async fn budget(&self, budget_id: &str) -> Result<Budget> {
+        let start_time = Instant::now();
+        let categories = self.categories(budget_id).await?;
+        let url = self.url(&["budgets", budget_id, "transactions"])?;
+        let response: TransactionsResponse = self.send(self.http.get(url)).await?;
+
+        let known: HashSet<&str> = categories
+            .iter()
+            .map(|category| category.name.as_str())
+            .collect();
+        let filed = |name: Option<String>, transfer: bool| {
+            name.filter(|name| !transfer && known.contains(name.as_str()))
+        };
+        let mut transactions = Vec::new();
+        for transaction in response.transactions {
+            if transaction.deleted {
+                continue;
+            }
+            let date = NaiveDate::parse_from_str(&transaction.date, "%Y-%m-%d")
+                .with_context(|| format!("{:?} is not a date", transaction.date))?;
+            let date = format!("{date}T00:00:00Z");
+            let splits = transaction
+                .subtransactions
+                .into_iter()
+                .filter(|split| !split.deleted)
+                .collect::<Vec<_>>();
+            if splits.is_empty() {
+                let transfer = transaction.transfer_account_id.is_some();
+                transactions.push(YnabTransaction {
+                    id: transaction.id,
+                    account: transaction.account_name,
+                    date,
+                    payee: transaction.payee_name,
+                    category: filed(transaction.category_name, transfer),
+                    memo: transaction.memo.filter(|memo| !memo.is_empty()),
+                    amount: milliunits(transaction.amount),
+                    transfer,
+                });
+                continue;
+            }
+            for split in splits {
+                let transfer = split.transfer_account_id.is_some();
+                transactions.push(YnabTransaction {
+                    id: split.id,
+                    account: transaction.account_name.clone(),
+                    date: date.clone(),
+                    payee: split.payee_name.or_else(|| transaction.payee_name.clone()),
+                    category: filed(split.category_name, transfer),
+                    memo: split
+                        .memo
+                        .or_else(|| transaction.memo.clone())
+                        .filter(|memo| !memo.is_empty()),
+                    amount: milliunits(split.amount),
+                    transfer,
+                });
+            }
+        }
+        transactions.sort_by(|left, right| left.date.cmp(&right.date));
+        info!(
+            "Read {} YNAB categories and {} transactions in {:?}",
+            categories.len(),
+            transactions.len(),
+            start_time.elapsed()
+        );
+        Ok(Budget {
+            categories,
+            transactions,
+        })
+    }
+
+    /// Adds the categories `names` to the category group `group`, which is
+    /// added first unless the budget has one of that name, ignoring case.
+    /// Stops early when the tool call is cancelled. An error partway carries
+    /// the categories added by then as [`Interrupted`].
+    #[instrument(skip(self, names), fields(categories = names.len()))]
+    pub async fn add_categories(
+        &self,
+        budget_id: &str,
+        group: &str,
+        names: &[String],
+    ) -> Result<Vec<YnabCategory>> {
+        let start_time = Instant::now();
+        let existing = self
+            .category_groups(budget_id)
+            .await?
+            .into_iter()
+            .find(|existing| existing.name.trim().eq_ignore_ascii_case(group.trim()));
+        let group_id = match existing {
+            Some(existing) => existing.id,
+            None => {
+                let url = self.url(&["budgets", budget_id, "category_groups"])?;
+                let response: CategoryGroupResponse = self
+                    .send(
+                        self.http
+                            .post(url)
+                            .json(&json!({ "category_group": { "name": group } })),
+                    )
+                    .await?;
+                info!("Added YNAB category group {}", group);
+                response.category_group.id
+            }
+        };
+
+        let url = self.url(&["budgets", budget_id, "categories"])?;
+        let mut added = Vec::with_capacity(names.len());
+        for name in names {
+            if cancellation::is_cancelled() {
+                break;
+            }
+            let request = self.http.post(url.clone()).json(&json!({
+                "category": { "name": name, "category_group_id": group_id },
+            }));
+            let response: CategoryResponse = match self.send(request).await {
+                Ok(response) => response,
+                Err(err) => {
+                    return Err(err.context(Interrupted {
+                        categories: added.len(),
+                    }))
+                }
+            };
+            added.push(YnabCategory {
+                id: Some(response.category.id),
+                name: response.category.name,
+                group: group.to_string(),
+            });
+        }
+        info!(
+            "Added {} YNAB categories in {:?}",
+            added.len(),
+            start_time.elapsed()
+        );
+        Ok(added)
+    }
+
+    /// The budget's category groups in budget order, without deleted, hidden
+    /// and internal ones.
+    async fn category_groups(&self, budget_id: &str) -> Result<Vec<CategoryGroup>> {
+        let url = self.url(&["budgets", budget_id, "categories"])?;
+        let response: CategoriesResponse = self.send(self.http.get(url)).await?;
+        Ok(response
+            .category_groups
+            .into_iter()
+            .filter(|group| !group.hidden && !group.deleted && !is_internal(&group.name))
+            .collect())
+    }
+
+    /// `{base_url}/` followed by `segments`, each escaped, so an id cannot
+    /// reach another endpoint.
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.base_url).context("invalid YNAB base URL")?;
+        url.path_segments_mut()
+            .map_err(|()| anyhow!("invalid YNAB base URL"))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// Sends `request` with the token and parses the `data` of the response.
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response = request
+            .bearer_auth(&self.token)
+            .headers(correlation::headers())
+            .send()
+            .await
+            .context("YNAB request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("YNAB request failed ({}): {}", status, body);
+            let mut err = serde_json::from_str::<ErrorResponse>(&body)
+                .map(|response| response.error)
+                .unwrap_or_else(|_| YnabError {
+                    status_code: 0,
+                    id: String::new(),
+                    name: String::new(),
+                    detail: status.to_string(),
+                });
+            err.status_code = status.as_u16();
+            return Err(anyhow!(err));
+        }
+        let response: Data<T> = response
+            .json()
+            .await
+            .context("failed to parse YNAB response")?;
+        Ok(response.data)
+    }
+}
+
+fn milliunits(amount: i64) -> f64 {
+    amount as f64 / 1000.0
+}
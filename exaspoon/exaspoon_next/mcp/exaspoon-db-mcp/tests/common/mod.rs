@@ -2,19 +2,35 @@
 
 use anyhow::Result;
 use async_trait::async_trait;
+use serde_json::{json, Value};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 // Import from the crate using the library name from Cargo.toml
 use exaspoon_db_mcp::{
-    config::AppConfig,
-    embedding::Embedder,
+    circuit::CircuitBreakerConfig,
+    concurrency::ConcurrencyLimits,
+    config::{
+        AppConfig, HttpClientConfig, ServerIdentity, Transport, DEFAULT_BIND_ADDRESS,
+        DEFAULT_HEALTH_PROBE_INTERVAL_SECS,
+    },
+    embedding::{Embedder, EmbeddingEncoding},
+    i18n::Locale,
     models::{
-        AccountType, CategoryKind, CreateTransactionInput, ListAccountsInput, SearchSimilarInput,
-        TransactionDirection, UpsertAccountInput, UpsertCategoryInput,
+        Account, AccountType, AggregateSpendingInput, AuditEvent, Categorized, Category,
+        CategoryKind, CategoryMatch, CreateTransactionInput, EmbeddingIssue, ListAccountsInput,
+        ListAuditEventsInput, ListTransactionsInput, RecordKind, SearchSimilarInput,
+        SpendingBucket, Transaction, TransactionDirection, TransactionFilters, TransactionMatch,
+        TransactionTextMatch, UpsertAccountInput, UpsertCategoryInput,
     },
+    rate_limit::RateLimitConfig,
+    retry::RetryPolicy,
+    server::ToolGroup,
+    session::DEFAULT_MAX_SESSIONS,
+    shutdown::DEFAULT_SHUTDOWN_TIMEOUT_MS,
     supabase::Database,
+    timeout::ToolTimeouts,
 };
-use serde_json::{json, Value};
 
 /// A mock embedder for testing purposes.
 #[derive(Clone)]
@@ -91,7 +107,11 @@ impl MockDatabase {
 
     /// Returns all transaction search limits.
     pub fn transaction_search_limits(&self) -> Vec<Option<u32>> {
-        self.state.lock().unwrap().searched_transaction_limits.clone()
+        self.state
+            .lock()
+            .unwrap()
+            .searched_transaction_limits
+            .clone()
     }
 
     /// Returns all upserted categories.
@@ -116,7 +136,7 @@ impl Database for MockDatabase {
         &self,
         input: &CreateTransactionInput,
         embedding: Option<Vec<f32>>,
-    ) -> Result<Value> {
+    ) -> Result<Transaction> {
         let mut state = self.state.lock().unwrap();
         state.inserted_transactions.push((input.clone(), embedding));
         Ok(state.transaction_response.clone())
@@ -126,19 +146,27 @@ impl Database for MockDatabase {
         &self,
         input: &UpsertCategoryInput,
         embedding: Option<Vec<f32>>,
-    ) -> Result<Value> {
+    ) -> Result<Category> {
         let mut state = self.state.lock().unwrap();
         state.upserted_categories.push((input.clone(), embedding));
         Ok(state.category_response.clone())
     }
 
-    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Value> {
+    async fn upsert_account(&self, input: &UpsertAccountInput) -> Result<Account> {
         let mut state = self.state.lock().unwrap();
         state.upserted_accounts.push(input.clone());
         Ok(state.account_response.clone())
     }
 
-    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Value>> {
+    async fn categorize_transaction(
+        &self,
+        _id: &str,
+        _category_id: Option<&str>,
+    ) -> Result<Option<Categorized>> {
+        Ok(None)
+    }
+
+    async fn list_accounts(&self, params: &ListAccountsInput) -> Result<Vec<Account>> {
         let mut state = self.state.lock().unwrap();
         state.account_list_params.push(params.clone());
         Ok(state.accounts.clone())
@@ -148,7 +176,7 @@ impl Database for MockDatabase {
         &self,
         embedding: Vec<f32>,
         limit: Option<u32>,
-    ) -> Result<Vec<Value>> {
+    ) -> Result<Vec<TransactionMatch>> {
         let mut state = self.state.lock().unwrap();
         state.searched_transaction_limits.push(limit);
         Ok(state.transaction_matches.clone())
@@ -158,10 +186,108 @@ impl Database for MockDatabase {
         &self,
         embedding: Vec<f32>,
         _limit: Option<u32>,
-    ) -> Result<Vec<Value>> {
+    ) -> Result<Vec<CategoryMatch>> {
         let state = self.state.lock().unwrap();
         Ok(state.category_matches.clone())
     }
+
+    async fn list_category_names(&self, _search: &str, _limit: u32) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    async fn search_transactions_text(
+        &self,
+        _query: &str,
+        _limit: Option<u32>,
+    ) -> Result<Vec<TransactionTextMatch>> {
+        Ok(Vec::new())
+    }
+
+    async fn count_accounts(&self, _params: &ListAccountsInput) -> Result<u64> {
+        let state = self.state.lock().unwrap();
+        Ok(state.accounts.len() as u64)
+    }
+
+    async fn list_transactions(&self, _input: &ListTransactionsInput) -> Result<Vec<Transaction>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.transactions.clone())
+    }
+
+    async fn count_transactions(&self, _filters: &TransactionFilters) -> Result<u64> {
+        let state = self.state.lock().unwrap();
+        Ok(state.transactions.len() as u64)
+    }
+
+    async fn largest_transactions(
+        &self,
+        _filters: &TransactionFilters,
+        limit: u32,
+    ) -> Result<Vec<Transaction>> {
+        let mut transactions = self.state.lock().unwrap().transactions.clone();
+        transactions.sort_by(|left, right| right.amount.total_cmp(&left.amount));
+        transactions.truncate(limit as usize);
+        Ok(transactions)
+    }
+
+    async fn aggregate_spending(
+        &self,
+        _input: &AggregateSpendingInput,
+    ) -> Result<Vec<SpendingBucket>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.spending_buckets.clone())
+    }
+
+    async fn record_audit_events(&self, events: &[AuditEvent]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.audit_events.extend_from_slice(events);
+        Ok(())
+    }
+
+    async fn list_audit_events(&self, _input: &ListAuditEventsInput) -> Result<Vec<AuditEvent>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.audit_events.clone())
+    }
+
+    async fn record_tool_calls(&self, _calls: &[AuditEvent]) -> Result<()> {
+        Ok(())
+    }
+
+    async fn soft_delete(&self, kind: RecordKind, id: &str) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        state.soft_deleted.push((kind, id.to_string()));
+        Ok(true)
+    }
+
+    async fn record_exists(&self, _kind: RecordKind, _id: &str) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn delete_record(&self, _kind: RecordKind, _id: &str) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn purge_deleted(&self, _kind: RecordKind, _deleted_before: Option<&str>) -> Result<u64> {
+        Ok(0)
+    }
+
+    async fn embedding_issues(
+        &self,
+        _kind: RecordKind,
+        _dimension: usize,
+        _after: Option<&str>,
+        _limit: u32,
+    ) -> Result<Vec<EmbeddingIssue>> {
+        Ok(Vec::new())
+    }
+
+    async fn set_embedding(
+        &self,
+        _kind: RecordKind,
+        _id: &str,
+        _embedding: Option<Vec<f32>>,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Internal state for mock database.
@@ -172,23 +298,31 @@ pub struct MockState {
     /// All transaction search limits.
     pub searched_transaction_limits: Vec<Option<u32>>,
     /// Default transaction response.
-    pub transaction_response: Value,
+    pub transaction_response: Transaction,
     /// Transaction search matches.
-    pub transaction_matches: Vec<Value>,
+    pub transaction_matches: Vec<TransactionMatch>,
     /// All upserted categories.
     pub upserted_categories: Vec<(UpsertCategoryInput, Option<Vec<f32>>)>,
     /// Default category response.
-    pub category_response: Value,
+    pub category_response: Category,
     /// Category search matches.
-    pub category_matches: Vec<Value>,
+    pub category_matches: Vec<CategoryMatch>,
     /// All upserted accounts.
     pub upserted_accounts: Vec<UpsertAccountInput>,
     /// Default account response.
-    pub account_response: Value,
+    pub account_response: Account,
     /// Account list results.
-    pub accounts: Vec<Value>,
+    pub accounts: Vec<Account>,
     /// All account list parameters.
     pub account_list_params: Vec<ListAccountsInput>,
+    /// Transaction list results.
+    pub transactions: Vec<Transaction>,
+    /// Spending aggregation results.
+    pub spending_buckets: Vec<SpendingBucket>,
+    /// All soft-deleted rows.
+    pub soft_deleted: Vec<(RecordKind, String)>,
+    /// All recorded audit events.
+    pub audit_events: Vec<AuditEvent>,
 }
 
 impl Default for MockState {
@@ -196,15 +330,19 @@ impl Default for MockState {
         Self {
             inserted_transactions: Vec::new(),
             searched_transaction_limits: Vec::new(),
-            transaction_response: json!({ "id": "txn-default" }),
+            transaction_response: sample_transaction("txn-default"),
             transaction_matches: Vec::new(),
             upserted_categories: Vec::new(),
-            category_response: json!({ "id": "cat-default" }),
+            category_response: sample_category("cat-default"),
             category_matches: Vec::new(),
             upserted_accounts: Vec::new(),
-            account_response: json!({ "id": "acct-default" }),
+            account_response: sample_account("acct-default"),
             accounts: Vec::new(),
             account_list_params: Vec::new(),
+            transactions: Vec::new(),
+            spending_buckets: Vec::new(),
+            soft_deleted: Vec::new(),
+            audit_events: Vec::new(),
         }
     }
 }
@@ -212,11 +350,73 @@ impl Default for MockState {
 /// Creates a test configuration with mock values.
 pub fn test_config() -> AppConfig {
     AppConfig {
+        transport: Transport::Stdio,
+        bind_address: DEFAULT_BIND_ADDRESS.parse().unwrap(),
+        allowed_hosts: Vec::new(),
+        max_sessions: DEFAULT_MAX_SESSIONS,
+        session_idle_timeout: None,
+        stdio_idle_timeout: None,
+        health_address: None,
+        health_probe_interval: Duration::from_secs(DEFAULT_HEALTH_PROBE_INTERVAL_SECS),
+        database_backend: "supabase".to_string(),
+        sqlite_path: "exaspoon.db".to_string(),
         supabase_url: "https://test.supabase.co".to_string(),
+        supabase_read_url: None,
         supabase_service_key: "test-service-key".to_string(),
+        supabase_schema: "public".to_string(),
+        supabase_access_token: None,
+        supabase_require_user_auth: false,
+        supabase_retry: RetryPolicy::default(),
+        supabase_realtime: false,
+        tenant_id: None,
+        admin_tools: false,
+        demo_seed: false,
+        dry_run: false,
+        confirmation_window: None,
+        read_only: false,
+        rpc_allowlist: Vec::new(),
+        disabled_tools: Vec::new(),
+        tool_groups: ToolGroup::ALL.to_vec(),
+        tool_names: Default::default(),
+        rate_limit: RateLimitConfig::default(),
+        concurrency: ConcurrencyLimits::default(),
+        tool_timeouts: ToolTimeouts::default(),
+        shutdown_timeout: Duration::from_millis(DEFAULT_SHUTDOWN_TIMEOUT_MS),
+        pid_file: None,
+        call_log: None,
+        call_log_redacted_fields: Vec::new(),
+        http: HttpClientConfig::default(),
+        identity: ServerIdentity::default(),
+        locale: Locale::default(),
+        circuit_breaker: CircuitBreakerConfig::default(),
         openai_api_key: "test-openai-key".to_string(),
         openai_base_url: Some("https://test.openai.com".to_string()),
+        azure_openai: None,
+        plaid: None,
+        gocardless: None,
+        etherscan: None,
+        blockbook: None,
+        solana: None,
+        binance: None,
+        coinbase: None,
+        fx: None,
+        prices: None,
+        receipt: None,
+        google_sheets: None,
+        notion: None,
+        ynab: None,
+        ollama_base_url: None,
+        cohere_api_key: None,
+        voyage_api_key: None,
+        embedding_provider: "openai".to_string(),
         embedding_model: "text-embedding-3-large".to_string(),
+        embedding_dimensions: None,
+        embedding_encoding: EmbeddingEncoding::Base64,
+        embedding_query_prompt: None,
+        embedding_document_prompt: None,
+        pii_redaction: false,
+        pii_redaction_patterns: Vec::new(),
+        log_level: tracing::Level::INFO,
     }
 }
 
@@ -253,6 +453,74 @@ pub fn sample_account_input() -> UpsertAccountInput {
     }
 }
 
+/// Creates a sample stored transaction with the given id.
+pub fn sample_transaction(id: &str) -> Transaction {
+    Transaction {
+        id: id.to_string(),
+        account_id: "acct-1".to_string(),
+        amount: 42.0,
+        currency: "USD".to_string(),
+        direction: TransactionDirection::Expense,
+        occurred_at: "2024-01-02T03:04:05Z".to_string(),
+        description: Some("Coffee".to_string()),
+        raw_source: None,
+        category_id: None,
+        created_at: None,
+    }
+}
+
+/// Creates a sample stored category with the given id.
+pub fn sample_category(id: &str) -> Category {
+    Category {
+        id: id.to_string(),
+        name: "Food".to_string(),
+        kind: CategoryKind::Expense,
+        description: Some("Food and dining expenses".to_string()),
+        created_at: None,
+    }
+}
+
+/// Creates a sample stored account with the given id.
+pub fn sample_account(id: &str) -> Account {
+    Account {
+        id: id.to_string(),
+        name: "Checking".to_string(),
+        r#type: AccountType::Offchain,
+        currency: "USD".to_string(),
+        network: None,
+        institution: Some("Test Bank".to_string()),
+        created_at: None,
+    }
+}
+
+/// Creates a sample stored account with the given id and name.
+pub fn sample_named_account(id: &str, name: &str) -> Account {
+    Account {
+        name: name.to_string(),
+        ..sample_account(id)
+    }
+}
+
+/// Creates a sample transaction search match.
+pub fn sample_transaction_match(id: &str, description: &str) -> TransactionMatch {
+    let mut transaction = sample_transaction(id);
+    transaction.description = Some(description.to_string());
+    TransactionMatch {
+        transaction,
+        similarity: Some(0.9),
+    }
+}
+
+/// Creates a sample category search match.
+pub fn sample_category_match(id: &str, name: &str) -> CategoryMatch {
+    let mut category = sample_category(id);
+    category.name = name.to_string();
+    CategoryMatch {
+        category,
+        similarity: Some(0.8),
+    }
+}
+
 /// Creates a sample search input for testing.
 pub fn sample_search_input() -> SearchSimilarInput {
     SearchSimilarInput {
@@ -260,3 +528,35 @@ pub fn sample_search_input() -> SearchSimilarInput {
         limit: Some(5),
     }
 }
+
+/// MCP protocol version the transport tests initialize with.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// The `initialize` request a transport test client opens its session with.
+pub fn initialize_request(id: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" }
+        }
+    })
+}
+
+/// The notification a client sends once its session is initialized.
+pub fn initialized_notification() -> Value {
+    json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })
+}
+
+/// A `tools/call` request for `name` with `arguments`.
+pub fn tool_call(id: u64, name: &str, arguments: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": name, "arguments": arguments }
+    })
+}
@@ -11,7 +11,6 @@ use rmcp::{
     handler::server::wrapper::Parameters,
     model::ErrorCode,
 };
-use serde_json::json;
 use std::sync::Arc;
 
 mod common;
@@ -33,7 +32,7 @@ async fn test_server_create_transaction_with_description() {
     };
 
     let result = server
-        .create_transaction(Parameters(input.clone()))
+        .create_transaction(Parameters(input.clone().into()))
         .await
         .expect("tool call should succeed");
 
@@ -73,7 +72,7 @@ async fn test_server_create_transaction_without_description() {
     };
 
     let result = server
-        .create_transaction(Parameters(input.clone()))
+        .create_transaction(Parameters(input.clone().into()))
         .await
         .expect("tool call should succeed");
 
@@ -104,8 +103,8 @@ async fn test_server_search_similar_transactions_with_query() {
     // Configure mock database to return specific matches
     db.configure(|state| {
         state.transaction_matches = vec![
-            json!({ "id": "txn-1", "description": "Coffee shop" }),
-            json!({ "id": "txn-2", "description": "Cafe" }),
+            common::sample_transaction_match("txn-1", "Coffee shop"),
+            common::sample_transaction_match("txn-2", "Cafe"),
         ];
     });
 
@@ -169,7 +168,7 @@ async fn test_server_upsert_category() {
     };
 
     let result = server
-        .upsert_category(Parameters(input.clone()))
+        .upsert_category(Parameters(input.clone().into()))
         .await
         .expect("tool call should succeed");
 
@@ -201,7 +200,7 @@ async fn test_server_upsert_category_without_description() {
     };
 
     let result = server
-        .upsert_category(Parameters(input.clone()))
+        .upsert_category(Parameters(input.clone().into()))
         .await
         .expect("tool call should succeed");
 
@@ -229,8 +228,8 @@ async fn test_server_search_similar_categories() {
     // Configure mock database to return specific matches
     db.configure(|state| {
         state.category_matches = vec![
-            json!({ "id": "cat-1", "name": "Food" }),
-            json!({ "id": "cat-2", "name": "Dining" }),
+            common::sample_category_match("cat-1", "Food"),
+            common::sample_category_match("cat-2", "Dining"),
         ];
     });
 
@@ -286,14 +285,15 @@ async fn test_server_list_accounts() {
     // Configure mock database to return specific accounts
     db.configure(|state| {
         state.accounts = vec![
-            json!({ "id": "acct-1", "name": "Test Account 1", "type": "offchain" }),
-            json!({ "id": "acct-2", "name": "Test Account 2", "type": "offchain" }),
+            common::sample_named_account("acct-1", "Test Account 1"),
+            common::sample_named_account("acct-2", "Test Account 2"),
         ];
     });
 
     let input = ListAccountsInput {
         r#type: Some(AccountType::Offchain),
         search: Some("Test".to_string()),
+        ..Default::default()
     };
 
     let result = server
@@ -331,7 +331,7 @@ async fn test_server_upsert_account() {
     };
 
     let result = server
-        .upsert_account(Parameters(input.clone()))
+        .upsert_account(Parameters(input.clone().into()))
         .await
         .expect("tool call should succeed");
 
@@ -365,7 +365,7 @@ async fn test_server_complete_workflow() {
         network: None,
         institution: Some("Test Bank".to_string()),
     };
-    server.upsert_account(Parameters(acct_input)).await.unwrap();
+    server.upsert_account(Parameters(acct_input.into())).await.unwrap();
 
     // 2. Create a category
     let cat_input = UpsertCategoryInput {
@@ -373,7 +373,7 @@ async fn test_server_complete_workflow() {
         kind: Some(CategoryKind::Expense),
         description: Some("Food and dining expenses".to_string()),
     };
-    server.upsert_category(Parameters(cat_input)).await.unwrap();
+    server.upsert_category(Parameters(cat_input.into())).await.unwrap();
 
     // 3. Create a transaction
     let txn_input = CreateTransactionInput {
@@ -385,7 +385,7 @@ async fn test_server_complete_workflow() {
         description: Some("Coffee".to_string()),
         raw_source: None,
     };
-    server.create_transaction(Parameters(txn_input)).await.unwrap();
+    server.create_transaction(Parameters(txn_input.into())).await.unwrap();
 
     // 4. Search for similar transactions
     let search_input = SearchSimilarInput {
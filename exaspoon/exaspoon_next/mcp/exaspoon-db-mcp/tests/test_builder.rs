@@ -0,0 +1,85 @@
+//! Tests for embedding the server with `ExaspoonServerBuilder`.
+#![cfg(all(feature = "memory-backend", unix))]
+
+use exaspoon_db_mcp::builder::ExaspoonServerBuilder;
+use exaspoon_db_mcp::config::Transport;
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::server::ToolGroup;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+async fn request(
+    stream: &mut BufReader<UnixStream>,
+    id: u64,
+    method: &str,
+    params: Value,
+) -> Value {
+    let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    let line = format!("{message}\n");
+    stream.get_mut().write_all(line.as_bytes()).await.unwrap();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await.unwrap();
+        let message: Value = serde_json::from_str(&line).unwrap();
+        if message["id"] == json!(id) && message.get("method").is_none() {
+            return message;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_built_server_serves_the_chosen_tools_until_stopped() {
+    let path = std::env::temp_dir().join(format!("exaspoon-{}.sock", uuid::Uuid::new_v4()));
+    let server = ExaspoonServerBuilder::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    )
+    .with_tool_groups(vec![ToolGroup::Core, ToolGroup::Monitoring])
+    .with_read_only()
+    .with_transport(Transport::Unix(path.clone()))
+    .build();
+    assert_eq!(server.transport(), &Transport::Unix(path.clone()));
+
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(server.serve_until(stop.clone()));
+    let stream = loop {
+        match UnixStream::connect(&path).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::task::yield_now().await,
+        }
+    };
+    let mut stream = BufReader::new(stream);
+    let params = json!({
+        "protocolVersion": "2025-06-18",
+        "capabilities": {},
+        "clientInfo": { "name": "test", "version": "0" }
+    });
+    request(&mut stream, 0, "initialize", params).await;
+    let initialized = "{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}\n";
+    stream
+        .get_mut()
+        .write_all(initialized.as_bytes())
+        .await
+        .unwrap();
+
+    let listed = request(&mut stream, 1, "tools/list", json!({})).await;
+    let tools = listed["result"]["tools"].as_array().unwrap();
+    let names = tools
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"list_accounts"), "{names:?}");
+    assert!(names.contains(&"ping"), "{names:?}");
+    assert!(!names.contains(&"create_transaction"), "{names:?}");
+    assert!(!names.contains(&"aggregate_spending"), "{names:?}");
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,137 @@
+//! Tests for the tool call log and its redaction.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::call_log::{CallLog, CallLogTarget, REDACTED};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::ListAuditEventsInput;
+use exaspoon_db_mcp::redaction::Redactor;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::model::{CallToolResult, Content, ErrorCode};
+use rmcp::ErrorData as McpError;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn call_log(target: &CallLogTarget, database: Arc<dyn Database>) -> CallLog {
+    let redacted_fields = vec!["raw_source".to_string(), "Institution".to_string()];
+    CallLog::open(
+        target,
+        database,
+        redacted_fields,
+        Redactor::new(&[]).unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_redaction_replaces_listed_fields_and_masks_pii() {
+    let log = call_log(&CallLogTarget::Table, Arc::new(MemoryDatabase::new()));
+    let redacted = log.redact(json!({
+        "description": "Refund to jane@example.com",
+        "raw_source": "POS 4111 1111 1111 1111",
+        "transactions": [{ "institution": { "name": "Bank" }, "amount": 12.5 }],
+    }));
+
+    assert_eq!(
+        redacted,
+        json!({
+            "description": "Refund to [EMAIL]",
+            "raw_source": REDACTED,
+            "transactions": [{ "institution": REDACTED, "amount": 12.5 }],
+        })
+    );
+}
+
+#[test]
+fn test_bank_credentials_are_redacted_unlisted() {
+    let log = call_log(&CallLogTarget::Table, Arc::new(MemoryDatabase::new()));
+    let redacted = log.redact(json!({
+        "account_id": "acct-1",
+        "public_token": "public-sandbox-1",
+        "Access_Token": "access-sandbox-1",
+    }));
+
+    assert_eq!(
+        redacted,
+        json!({
+            "account_id": "acct-1",
+            "public_token": REDACTED,
+            "Access_Token": REDACTED,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_calls_are_appended_to_a_jsonl_file() {
+    let path = std::env::temp_dir().join(format!("calls-{}.jsonl", uuid::Uuid::new_v4()));
+    let log = call_log(
+        &CallLogTarget::Jsonl(path.clone()),
+        Arc::new(MemoryDatabase::new()),
+    );
+
+    let result = CallToolResult::structured(json!({ "account": { "institution": "Bank" } }));
+    log.record(
+        "upsert_account",
+        "session-1",
+        json!({ "name": "Main", "institution": "Bank" }),
+        &Ok(result),
+        Duration::from_millis(12),
+    )
+    .await;
+    let failed = McpError::new(ErrorCode::INVALID_PARAMS, "query must not be blank", None);
+    log.record(
+        "search_similar_transactions",
+        "session-1",
+        json!({ "query": " " }),
+        &Err(failed),
+        Duration::from_millis(1),
+    )
+    .await;
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let records: Vec<Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["tool"], "upsert_account");
+    assert_eq!(records[0]["session_id"], "session-1");
+    assert_eq!(records[0]["duration_ms"], 12);
+    assert_eq!(records[0]["arguments"]["institution"], REDACTED);
+    assert_eq!(records[0]["result"]["account"]["institution"], REDACTED);
+    assert_eq!(records[0]["input_hash"].as_str().unwrap().len(), 64);
+    assert!(records[0].get("error").is_none());
+    assert_eq!(records[1]["error"]["message"], "query must not be blank");
+    assert!(records[1].get("result").is_none());
+}
+
+#[tokio::test]
+async fn test_calls_are_recorded_apart_from_the_audit_log() {
+    let database = Arc::new(MemoryDatabase::new());
+    let log = call_log(&CallLogTarget::Table, database.clone());
+
+    let result = CallToolResult::success(vec![Content::text("ok")]);
+    log.record(
+        "list_accounts",
+        "session-1",
+        json!({ "limit": 5 }),
+        &Ok(result),
+        Duration::from_millis(3),
+    )
+    .await;
+
+    let events = database
+        .list_audit_events(&ListAuditEventsInput::default())
+        .await
+        .unwrap();
+    assert!(events.is_empty());
+    let events = database.tool_calls().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].tool, "list_accounts");
+    assert_eq!(events[0].record_id, None);
+    let details = events[0].details.as_ref().unwrap();
+    assert_eq!(details["arguments"], json!({ "limit": 5 }));
+    assert_eq!(details["result"][0]["text"], "ok");
+    assert_eq!(details["duration_ms"], 3);
+}
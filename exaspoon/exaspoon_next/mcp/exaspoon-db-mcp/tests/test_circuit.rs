@@ -0,0 +1,70 @@
+//! Tests for the circuit breaker around external calls.
+
+use anyhow::anyhow;
+use exaspoon_db_mcp::circuit::{CircuitBreaker, CircuitBreakerConfig, CircuitOpen};
+use std::time::Duration;
+
+fn breaker(failure_threshold: u32, open_duration: Duration) -> CircuitBreaker {
+    CircuitBreaker::new(
+        "Supabase",
+        CircuitBreakerConfig::new(failure_threshold, open_duration),
+    )
+}
+
+#[tokio::test]
+async fn test_circuit_opens_after_consecutive_failures() {
+    let breaker = breaker(2, Duration::from_secs(60));
+
+    breaker.record_failure();
+    breaker.record_success();
+    breaker.record_failure();
+    assert!(breaker.allow().is_ok());
+    breaker.record_failure();
+
+    let err = breaker
+        .call(async { Ok::<_, anyhow::Error>("never sent") })
+        .await
+        .unwrap_err();
+    let open = err
+        .downcast_ref::<CircuitOpen>()
+        .expect("circuit open error");
+    assert_eq!(open.dependency, "Supabase");
+    assert!(open.retry_in > Duration::from_secs(50));
+    assert!(err.to_string().contains("Supabase is unavailable"));
+}
+
+#[tokio::test]
+async fn test_circuit_half_open_probe_decides() {
+    let breaker = breaker(1, Duration::from_millis(20));
+    breaker.record_failure();
+    assert!(breaker.allow().is_err());
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert!(
+        breaker.allow().is_ok(),
+        "first call after the cooldown probes"
+    );
+    assert!(breaker.allow().is_err(), "others wait for the probe");
+    breaker.record_failure();
+    assert!(
+        breaker.allow().is_err(),
+        "a failed probe reopens the circuit"
+    );
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    let result = breaker.call(async { Ok::<_, anyhow::Error>(7) }).await;
+    assert_eq!(result.unwrap(), 7);
+    assert!(!breaker.is_open());
+    assert!(breaker.allow().is_ok());
+}
+
+#[tokio::test]
+async fn test_circuit_with_zero_threshold_never_opens() {
+    let breaker = breaker(0, Duration::from_secs(60));
+
+    for _ in 0..10 {
+        let result: anyhow::Result<()> = breaker.call(async { Err(anyhow!("503")) }).await;
+        assert_eq!(result.unwrap_err().to_string(), "503");
+    }
+    assert!(!breaker.is_open());
+}
@@ -0,0 +1,204 @@
+//! Tests for the command line and its operational subcommands.
+
+use clap::Parser;
+use exaspoon_db_mcp::cli::{Cli, Command, EmbeddedKind};
+
+mod common;
+
+fn parse(args: &[&str]) -> Command {
+    let args = std::iter::once("exaspoon-db-mcp").chain(args.iter().copied());
+    Cli::try_parse_from(args).unwrap().command()
+}
+
+#[test]
+fn test_cli_parses_subcommands_and_defaults_to_serve() {
+    assert_eq!(parse(&[]), Command::Serve { daemon: false });
+    assert_eq!(parse(&["serve", "--daemon"]), Command::Serve { daemon: true });
+    assert_eq!(parse(&["--check"]), Command::Check);
+    assert_eq!(parse(&["check"]), Command::Check);
+    assert_eq!(parse(&["verify"]), Command::Verify);
+    assert_eq!(
+        parse(&["migrate", "--print"]),
+        Command::Migrate { print: true }
+    );
+    assert_eq!(
+        parse(&["import", "rows.jsonl", "--dry-run"]),
+        Command::Import {
+            file: "rows.jsonl".into(),
+            dry_run: true
+        }
+    );
+    assert_eq!(
+        parse(&["reembed", "--kind", "category"]),
+        Command::Reembed {
+            kind: Some(EmbeddedKind::Category),
+            batch_size: None
+        }
+    );
+
+    let cli = Cli::try_parse_from(["exaspoon-db-mcp", "seed", "--config", "prod.yaml"]).unwrap();
+    assert_eq!(cli.config, Some("prod.yaml".into()));
+
+    assert!(Cli::try_parse_from(["exaspoon-db-mcp", "import"]).is_err());
+    assert!(Cli::try_parse_from(["exaspoon-db-mcp", "reembed", "--kind", "account"]).is_err());
+}
+
+#[cfg(feature = "memory-backend")]
+mod memory {
+    use super::common;
+    use exaspoon_db_mcp::cli;
+    use exaspoon_db_mcp::export::ExportFormat;
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{
+        HealthStatus, ListAccountsInput, RecordKind, TransactionFilters,
+    };
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn server_with(database: &Arc<MemoryDatabase>, vector: Vec<f32>) -> ExaspoonDbServer {
+        ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vector)),
+        )
+    }
+
+    async fn export(database: &MemoryDatabase) -> Vec<serde_json::Value> {
+        let mut out = Vec::new();
+        let filters = TransactionFilters::default();
+        let exported = cli::export(database, &filters, ExportFormat::Jsonl, &mut out).await.unwrap();
+        let lines = String::from_utf8(out).unwrap();
+        let rows: Vec<serde_json::Value> = lines
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len() as u64, exported);
+        rows
+    }
+
+    #[tokio::test]
+    async fn test_exported_transactions_import_back() {
+        let database = Arc::new(MemoryDatabase::new());
+        let server = server_with(&database, vec![0.1, 0.2]);
+        let seeded = cli::seed(&server, Some(1), false).await.unwrap();
+        assert!(seeded.output.transactions > 0);
+        let rows = export(&database).await;
+        assert_eq!(rows.len(), seeded.output.transactions);
+
+        let lines = rows
+            .iter()
+            .map(|row| format!("{row}\n\n"))
+            .collect::<String>();
+        let planned = cli::import(&server, lines.as_bytes(), true).await.unwrap();
+        assert_eq!(planned, rows.len());
+        assert_eq!(export(&database).await.len(), rows.len());
+
+        let imported = cli::import(&server, lines.as_bytes(), false).await.unwrap();
+        assert_eq!(imported, rows.len());
+        let descriptions = |rows: &[serde_json::Value]| {
+            let mut descriptions = rows
+                .iter()
+                .map(|row| row["description"].to_string())
+                .collect::<Vec<_>>();
+            descriptions.sort();
+            descriptions
+        };
+        let twice = [rows.clone(), rows.clone()].concat();
+        assert_eq!(descriptions(&export(&database).await), descriptions(&twice));
+
+        // Nothing is written when any line is malformed.
+        let broken = format!("{}\n{{\"amount\": 1}}\n", rows[0]);
+        let err = cli::import(&server, broken.as_bytes(), false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err:#}");
+        assert_eq!(export(&database).await.len(), rows.len() * 2);
+
+        // Rows for an account the database doesn't have fail with the reason.
+        let other = server_with(&Arc::new(MemoryDatabase::new()), vec![0.1, 0.2]);
+        let err = cli::import(&other, lines.as_bytes(), false)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("imported 0 transactions"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reembed_embeds_rows_after_a_model_change() {
+        let database = Arc::new(MemoryDatabase::new());
+        let seeded = cli::seed(&server_with(&database, vec![0.1, 0.2]), Some(1), false)
+            .await
+            .unwrap();
+
+        // The new model's vectors are longer, so every embedding is stale.
+        let upgraded = server_with(&database, vec![0.1, 0.2, 0.3]);
+        let embedded = cli::reembed(&upgraded, None, Some(7), false).await.unwrap();
+        assert_eq!(embedded["categories"], seeded.output.categories as u64);
+        assert!(embedded["transactions"] > 0);
+
+        let again = cli::reembed(&upgraded, None, None, false).await.unwrap();
+        assert!(again.values().all(|embedded| *embedded == 0), "{again:?}");
+        assert_eq!(
+            database
+                .count_transactions(&TransactionFilters::default())
+                .await
+                .unwrap(),
+            seeded.output.transactions as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reembed_needs_yes_when_calls_are_confirmed() {
+        let database = Arc::new(MemoryDatabase::new());
+        cli::seed(&server_with(&database, vec![0.1, 0.2]), Some(1), false)
+            .await
+            .unwrap();
+        let upgraded =
+            server_with(&database, vec![0.1, 0.2, 0.3]).with_confirmation(Duration::from_secs(60));
+
+        let err = cli::reembed(&upgraded, None, None, false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--yes"), "{err:#}");
+
+        let embedded = cli::reembed(&upgraded, None, None, true).await.unwrap();
+        assert!(embedded["transactions"] > 0, "{embedded:?}");
+    }
+
+    #[tokio::test]
+    async fn test_verify_writes_finds_and_removes_its_rows() {
+        let database = Arc::new(MemoryDatabase::new());
+        let server = server_with(&database, vec![0.6, 0.8]);
+
+        let report = server.verify_deployment().await;
+        assert_eq!(report.status, HealthStatus::Ok, "{:?}", report.checks);
+        let names = report
+            .checks
+            .iter()
+            .map(|check| check.name.as_str())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"search_similar_transactions"), "{names:?}");
+        assert!(names.contains(&"search_similar_categories"), "{names:?}");
+        assert_eq!(names.last(), Some(&"cleanup"));
+
+        let accounts = database
+            .list_accounts(&ListAccountsInput::default())
+            .await
+            .unwrap();
+        assert!(accounts.is_empty(), "{accounts:?}");
+        assert!(database.list_category_names("", 10).await.unwrap().is_empty());
+        let filters = TransactionFilters::default();
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 0);
+        // Deleted for good, not left soft-deleted.
+        for kind in [
+            RecordKind::Transaction,
+            RecordKind::Category,
+            RecordKind::Account,
+        ] {
+            assert_eq!(database.purge_deleted(kind, None).await.unwrap(), 0);
+        }
+    }
+}
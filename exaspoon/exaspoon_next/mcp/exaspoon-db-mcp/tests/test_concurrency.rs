@@ -0,0 +1,61 @@
+//! Tests for the caps on concurrent tool calls.
+
+use exaspoon_db_mcp::concurrency::{CallSlots, ConcurrencyLimits};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn slots(calls: usize, writes: usize) -> Arc<CallSlots> {
+    Arc::new(CallSlots::new(ConcurrencyLimits { calls, writes }))
+}
+
+#[tokio::test]
+async fn test_calls_wait_for_a_free_slot() {
+    let slots = slots(2, 0);
+    let first = slots.acquire(false).await;
+    let _second = slots.acquire(true).await;
+
+    let waiting = tokio::spawn({
+        let slots = slots.clone();
+        async move {
+            let _third = slots.acquire(false).await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!waiting.is_finished(), "third call waits");
+
+    drop(first);
+    timeout(Duration::from_secs(1), waiting)
+        .await
+        .expect("a freed slot lets it run")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_queued_writes_do_not_hold_up_reads() {
+    let slots = slots(2, 1);
+    let _write = slots.acquire(true).await;
+
+    let queued_write = tokio::spawn({
+        let slots = slots.clone();
+        async move {
+            let _write = slots.acquire(true).await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!queued_write.is_finished());
+
+    timeout(Duration::from_secs(1), slots.acquire(false))
+        .await
+        .expect("the read takes the free call slot");
+    queued_write.abort();
+}
+
+#[tokio::test]
+async fn test_zero_means_no_limit() {
+    let slots = slots(0, 0);
+    let mut permits = Vec::new();
+    for _ in 0..100 {
+        permits.push(slots.acquire(true).await);
+    }
+}
@@ -1,7 +1,28 @@
 //! Tests for configuration loading and validation.
 
-use exaspoon_db_mcp::config::AppConfig;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use exaspoon_db_mcp::config::{
+    parse_otlp_headers, AppConfig, BinanceConfig, CoinGeckoPlan, CoinbaseConfig, EtherscanConfig,
+    FxConfig, GoCardlessConfig, GoogleSheetsConfig, HttpClientConfig, NotionConfig, PlaidConfig,
+    PriceConfig, ReceiptConfig, Transport, YnabConfig,
+};
+use exaspoon_db_mcp::exchange::{binance::BINANCE_BASE_URL, coinbase::COINBASE_BASE_URL};
+use exaspoon_db_mcp::fx::{EXCHANGERATE_HOST_BASE_URL, FRANKFURTER_BASE_URL};
+use exaspoon_db_mcp::gocardless::GOCARDLESS_BASE_URL;
+use exaspoon_db_mcp::models::FxProvider;
+use exaspoon_db_mcp::onchain::ethereum::ETHERSCAN_BASE_URL;
+use exaspoon_db_mcp::plaid::{PLAID_PRODUCTION_URL, PLAID_SANDBOX_URL};
+use exaspoon_db_mcp::prices::{COINGECKO_BASE_URL, COINGECKO_PRO_BASE_URL};
+use exaspoon_db_mcp::sheets::{
+    google::{GOOGLE_SHEETS_BASE_URL, GOOGLE_TOKEN_URL},
+    notion::NOTION_BASE_URL,
+};
+use exaspoon_db_mcp::tool_names::ToolNames;
+use exaspoon_db_mcp::ynab::YNAB_BASE_URL;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::env;
+use std::time::Duration;
 
 mod common;
 
@@ -135,3 +156,364 @@ fn test_config_from_env_missing_openai_api_key() {
     env::remove_var("SUPABASE_URL");
     env::remove_var("SUPABASE_SERVICE_KEY");
 }
+
+#[test]
+fn test_http_client_config_defaults() {
+    let http = HttpClientConfig::default();
+
+    assert_eq!(http.connect_timeout, Duration::from_secs(10));
+    assert_eq!(http.request_timeout, Duration::from_secs(30));
+    assert_eq!(http.pool_idle_timeout, Duration::from_secs(90));
+    assert_eq!(http.pool_max_idle_per_host, None);
+}
+
+#[test]
+fn test_http_client_config_builds_client() {
+    let http = HttpClientConfig {
+        pool_max_idle_per_host: Some(4),
+        ..HttpClientConfig::default()
+    };
+
+    assert!(http.apply(reqwest::Client::builder()).build().is_ok());
+}
+
+#[test]
+fn test_tool_names_reject_invalid_or_clashing_names() {
+    let alias = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(tool, alias)| (tool.to_string(), alias.to_string()))
+            .collect::<HashMap<_, _>>()
+    };
+
+    let names = ToolNames::new(" fin_ ".to_string(), alias(&[("ping", "alive")])).unwrap();
+    assert_eq!(names.exposed("list_accounts"), "fin_list_accounts");
+    assert_eq!(names.exposed("ping"), "alive");
+    assert_eq!(names.original("fin_list_accounts").as_deref(), Some("list_accounts"));
+    assert_eq!(names.original("alive").as_deref(), Some("ping"));
+    assert_eq!(names.original("fin_ping"), None);
+    assert_eq!(names.original("list_accounts"), None);
+    assert!(ToolNames::default().is_identity());
+
+    assert!(ToolNames::new("fin x".to_string(), HashMap::new()).is_err());
+    assert!(ToolNames::new(String::new(), alias(&[("ping", "a/b")])).is_err());
+    let clash = alias(&[("ping", "alive"), ("health_check", "alive")]);
+    assert!(ToolNames::new(String::new(), clash).is_err());
+}
+
+#[test]
+fn test_transport_parses_every_mode() {
+    assert_eq!("stdio".parse::<Transport>().unwrap(), Transport::Stdio);
+    assert_eq!("HTTP".parse::<Transport>().unwrap(), Transport::Http);
+    assert_eq!("websocket".parse::<Transport>().unwrap(), Transport::WebSocket);
+    assert_eq!(
+        "tcp:localhost:9000".parse::<Transport>().unwrap(),
+        Transport::Tcp("localhost:9000".to_string())
+    );
+    assert_eq!(
+        "unix:/run/exaspoon.sock".parse::<Transport>().unwrap(),
+        Transport::Unix(PathBuf::from("/run/exaspoon.sock"))
+    );
+
+    assert!("tcp:9000".parse::<Transport>().is_err());
+    assert!("unix:".parse::<Transport>().is_err());
+    assert!("grpc".parse::<Transport>().is_err());
+}
+
+#[test]
+fn test_otlp_headers_parse_as_key_value_pairs() {
+    let headers = parse_otlp_headers("x-honeycomb-team=abc123, x-honeycomb-dataset = mcp,");
+    let headers = headers.unwrap();
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers["x-honeycomb-team"], "abc123");
+    assert_eq!(headers["x-honeycomb-dataset"], "mcp");
+    // Values may themselves contain `=`.
+    let headers = parse_otlp_headers("authorization=Basic a2V5=").unwrap();
+    assert_eq!(headers["authorization"], "Basic a2V5=");
+
+    assert!(parse_otlp_headers("no-value").is_err());
+    assert!(parse_otlp_headers("=value").is_err());
+}
+
+#[test]
+fn test_secrets_are_read_from_files() {
+    let path = env::temp_dir().join(format!("exaspoon-secret-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "voyage-key\n").unwrap();
+    env::remove_var("VOYAGE_API_KEY");
+    env::set_var("VOYAGE_API_KEY_FILE", &path);
+    assert_eq!(AppConfig::secret("VOYAGE_API_KEY").unwrap().as_deref(), Some("voyage-key"));
+
+    // Both at once is a mistake rather than a precedence question.
+    env::set_var("VOYAGE_API_KEY", "inline-key");
+    assert!(AppConfig::secret("VOYAGE_API_KEY").is_err());
+    env::remove_var("VOYAGE_API_KEY");
+
+    std::fs::write(&path, "\n").unwrap();
+    assert!(AppConfig::secret("VOYAGE_API_KEY").is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(AppConfig::secret("VOYAGE_API_KEY").is_err());
+    env::remove_var("VOYAGE_API_KEY_FILE");
+    assert_eq!(AppConfig::secret("VOYAGE_API_KEY").unwrap(), None);
+}
+
+#[test]
+fn test_plaid_is_configured_by_its_client_id() {
+    env::remove_var("PLAID_CLIENT_ID");
+    env::set_var("PLAID_ENV", "production");
+    assert_eq!(PlaidConfig::from_env().unwrap(), None);
+
+    env::set_var("PLAID_CLIENT_ID", "client-1");
+    env::remove_var("PLAID_SECRET");
+    assert!(PlaidConfig::from_env().is_err());
+
+    env::set_var("PLAID_SECRET", "secret-1");
+    assert!(PlaidConfig::from_env().is_err());
+    env::set_var("PLAID_TOKEN_KEY", "c2hvcnQ=");
+    let err = PlaidConfig::from_env().unwrap_err();
+    assert!(err.to_string().contains("32 bytes"), "{err}");
+
+    env::set_var("PLAID_TOKEN_KEY", STANDARD.encode([7; 32]));
+    let config = PlaidConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.client_id, "client-1");
+    assert_eq!(config.token_key, [7; 32]);
+    assert_eq!(config.base_url, PLAID_PRODUCTION_URL);
+    env::remove_var("PLAID_ENV");
+    let config = PlaidConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.base_url, PLAID_SANDBOX_URL);
+    env::set_var("PLAID_ENV", "development");
+    assert!(PlaidConfig::from_env().is_err());
+
+    env::remove_var("PLAID_CLIENT_ID");
+    env::remove_var("PLAID_SECRET");
+    env::remove_var("PLAID_TOKEN_KEY");
+    env::remove_var("PLAID_ENV");
+}
+
+#[test]
+fn test_gocardless_is_configured_by_its_secret_id() {
+    env::remove_var("GOCARDLESS_SECRET_ID");
+    assert_eq!(GoCardlessConfig::from_env().unwrap(), None);
+
+    env::set_var("GOCARDLESS_SECRET_ID", "id-1");
+    env::remove_var("GOCARDLESS_SECRET_KEY");
+    assert!(GoCardlessConfig::from_env().is_err());
+
+    env::set_var("GOCARDLESS_SECRET_KEY", "key-1");
+    let config = GoCardlessConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.secret_key, "key-1");
+    assert_eq!(config.base_url, GOCARDLESS_BASE_URL);
+
+    env::remove_var("GOCARDLESS_SECRET_ID");
+    env::remove_var("GOCARDLESS_SECRET_KEY");
+}
+
+#[test]
+fn test_etherscan_is_configured_by_its_api_key() {
+    env::remove_var("ETHERSCAN_API_KEY");
+    assert_eq!(EtherscanConfig::from_env().unwrap(), None);
+
+    env::set_var("ETHERSCAN_API_KEY", "key-1");
+    let config = EtherscanConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.api_key, "key-1");
+    assert_eq!(config.base_url, ETHERSCAN_BASE_URL);
+
+    env::remove_var("ETHERSCAN_API_KEY");
+}
+
+#[test]
+fn test_exchanges_are_configured_by_their_api_keys() {
+    env::remove_var("BINANCE_API_KEY");
+    env::remove_var("COINBASE_API_KEY");
+    assert_eq!(BinanceConfig::from_env().unwrap(), None);
+    assert_eq!(CoinbaseConfig::from_env().unwrap(), None);
+
+    env::set_var("BINANCE_API_KEY", "key-1");
+    env::remove_var("BINANCE_API_SECRET");
+    assert!(BinanceConfig::from_env().is_err());
+
+    env::set_var("BINANCE_API_SECRET", "secret-1");
+    let config = BinanceConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.api_secret, "secret-1");
+    assert_eq!(config.base_url, BINANCE_BASE_URL);
+
+    env::set_var("COINBASE_API_KEY", "key-2");
+    env::set_var("COINBASE_API_SECRET", "secret-2");
+    let config = CoinbaseConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.api_key, "key-2");
+    assert_eq!(config.base_url, COINBASE_BASE_URL);
+
+    for name in [
+        "BINANCE_API_KEY",
+        "BINANCE_API_SECRET",
+        "COINBASE_API_KEY",
+        "COINBASE_API_SECRET",
+    ] {
+        env::remove_var(name);
+    }
+}
+
+#[test]
+fn test_fx_is_configured_by_its_provider() {
+    for name in ["FX_PROVIDER", "FX_API_KEY", "BASE_CURRENCY", "FX_CACHE_TTL_SECS"] {
+        env::remove_var(name);
+    }
+    assert_eq!(FxConfig::from_env().unwrap(), None);
+
+    env::set_var("FX_PROVIDER", "ecb");
+    let config = FxConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.provider, FxProvider::Ecb);
+    assert_eq!(config.base_url, FRANKFURTER_BASE_URL);
+    assert_eq!(config.api_key, None);
+    assert_eq!(config.base_currency, "USD");
+    assert_eq!(config.cache_ttl, Duration::from_secs(3600));
+
+    env::set_var("BASE_CURRENCY", "eur");
+    env::set_var("FX_CACHE_TTL_SECS", "60");
+    let config = FxConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.base_currency, "EUR");
+    assert_eq!(config.cache_ttl, Duration::from_secs(60));
+
+    env::set_var("BASE_CURRENCY", "euro");
+    assert!(FxConfig::from_env().is_err());
+    env::remove_var("BASE_CURRENCY");
+
+    env::set_var("FX_PROVIDER", "exchangerate_host");
+    assert!(FxConfig::from_env().is_err());
+    env::set_var("FX_API_KEY", "access-key");
+    let config = FxConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.api_key.as_deref(), Some("access-key"));
+    assert_eq!(config.base_url, EXCHANGERATE_HOST_BASE_URL);
+
+    env::set_var("FX_PROVIDER", "oanda");
+    assert!(FxConfig::from_env().is_err());
+
+    for name in ["FX_PROVIDER", "FX_API_KEY", "FX_CACHE_TTL_SECS"] {
+        env::remove_var(name);
+    }
+}
+
+#[test]
+fn test_receipts_are_configured_by_their_model() {
+    for name in ["RECEIPT_MODEL", "RECEIPT_BASE_URL", "RECEIPT_API_KEY"] {
+        env::remove_var(name);
+    }
+    assert_eq!(ReceiptConfig::from_env().unwrap(), None);
+
+    // The OpenAI fallbacks are left alone, as other tests set them.
+    env::set_var("RECEIPT_MODEL", "llava");
+    env::set_var("RECEIPT_BASE_URL", "http://localhost:11434/v1");
+    env::set_var("RECEIPT_API_KEY", "receipt-key");
+    let config = ReceiptConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.model, "llava");
+    assert_eq!(config.base_url, "http://localhost:11434/v1");
+    assert_eq!(config.api_key.as_deref(), Some("receipt-key"));
+
+    for name in ["RECEIPT_MODEL", "RECEIPT_BASE_URL", "RECEIPT_API_KEY"] {
+        env::remove_var(name);
+    }
+}
+
+#[test]
+fn test_sheet_exports_are_configured_by_their_credentials() {
+    let names = [
+        "GOOGLE_SHEETS_CLIENT_ID",
+        "GOOGLE_SHEETS_CLIENT_SECRET",
+        "GOOGLE_SHEETS_REFRESH_TOKEN",
+        "GOOGLE_SHEETS_SPREADSHEET_ID",
+        "NOTION_TOKEN",
+        "NOTION_DATABASE_ID",
+    ];
+    for name in names {
+        env::remove_var(name);
+    }
+    assert_eq!(GoogleSheetsConfig::from_env().unwrap(), None);
+    assert_eq!(NotionConfig::from_env().unwrap(), None);
+
+    env::set_var("GOOGLE_SHEETS_CLIENT_ID", "client.apps.googleusercontent.com");
+    env::set_var("GOOGLE_SHEETS_CLIENT_SECRET", "client-secret");
+    // The refresh token is needed as well.
+    assert!(GoogleSheetsConfig::from_env().is_err());
+    env::set_var("GOOGLE_SHEETS_REFRESH_TOKEN", "refresh-token");
+    env::set_var("GOOGLE_SHEETS_SPREADSHEET_ID", "sheet-1");
+    let config = GoogleSheetsConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.client_secret, "client-secret");
+    assert_eq!(config.spreadsheet_id.as_deref(), Some("sheet-1"));
+    assert_eq!(config.base_url, GOOGLE_SHEETS_BASE_URL);
+    assert_eq!(config.token_url, GOOGLE_TOKEN_URL);
+
+    env::set_var("NOTION_TOKEN", "secret_notion");
+    let config = NotionConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.token, "secret_notion");
+    assert_eq!(config.database_id, None);
+    assert_eq!(config.base_url, NOTION_BASE_URL);
+
+    for name in names {
+        env::remove_var(name);
+    }
+}
+
+#[test]
+fn test_ynab_is_configured_by_its_token() {
+    let names = ["YNAB_TOKEN", "YNAB_BUDGET_ID", "YNAB_BASE_URL"];
+    for name in names {
+        env::remove_var(name);
+    }
+    assert_eq!(YnabConfig::from_env().unwrap(), None);
+
+    env::set_var("YNAB_TOKEN", "ynab-token");
+    let config = YnabConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.token, "ynab-token");
+    assert_eq!(config.budget_id, None);
+    assert_eq!(config.base_url, YNAB_BASE_URL);
+
+    env::set_var("YNAB_BUDGET_ID", "budget-1");
+    let config = YnabConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.budget_id.as_deref(), Some("budget-1"));
+
+    for name in names {
+        env::remove_var(name);
+    }
+}
+
+#[test]
+fn test_prices_are_configured_by_their_provider() {
+    let names = [
+        "PRICE_PROVIDER",
+        "PRICE_API_KEY",
+        "PRICE_API_PLAN",
+        "PRICE_BASE_URL",
+        "PRICE_CACHE_TTL_SECS",
+    ];
+    for name in names {
+        env::remove_var(name);
+    }
+    assert_eq!(PriceConfig::from_env().unwrap(), None);
+
+    env::set_var("PRICE_PROVIDER", "coingecko");
+    let config = PriceConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.base_url, COINGECKO_BASE_URL);
+    assert_eq!(config.api_key, None);
+    assert_eq!(config.api_plan, CoinGeckoPlan::Demo);
+    assert_eq!(config.cache_ttl, Duration::from_secs(300));
+
+    env::set_var("PRICE_API_KEY", "demo-key");
+    env::set_var("PRICE_CACHE_TTL_SECS", "30");
+    let config = PriceConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.api_key.as_deref(), Some("demo-key"));
+    assert_eq!(config.cache_ttl, Duration::from_secs(30));
+
+    env::set_var("PRICE_API_PLAN", "pro");
+    let config = PriceConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.api_plan, CoinGeckoPlan::Pro);
+    assert_eq!(config.base_url, COINGECKO_PRO_BASE_URL);
+    env::set_var("PRICE_API_PLAN", "enterprise");
+    assert!(PriceConfig::from_env().is_err());
+    env::remove_var("PRICE_API_PLAN");
+
+    env::set_var("PRICE_PROVIDER", "coinmarketcap");
+    assert!(PriceConfig::from_env().is_err());
+
+    for name in names {
+        env::remove_var(name);
+    }
+}
@@ -0,0 +1,115 @@
+//! Tests for TOML and YAML configuration files.
+
+use exaspoon_db_mcp::config::{AppConfig, Transport};
+use exaspoon_db_mcp::config_file::{ConfigFile, SETTINGS};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+const TOML: &str = r#"
+[database]
+backend = "sqlite"
+sqlite_path = "/var/lib/exaspoon/finance.db"
+
+[embedding]
+provider = "mock"
+dimensions = 256
+
+[embedding.openai]
+api_key = "sk-test"
+
+[transport]
+mode = "http"
+max_sessions = 10
+
+[tools]
+read_only = true
+disabled = ["call_rpc", "purge_deleted"]
+aliases = { ping = "alive" }
+
+[otel.headers]
+x-honeycomb-team = "abc123"
+"#;
+
+const YAML: &str = r#"
+database:
+  backend: sqlite
+  sqlite_path: /var/lib/exaspoon/finance.db
+embedding:
+  provider: mock
+  dimensions: 256
+  openai:
+    api_key: sk-test
+transport:
+  mode: http
+  max_sessions: 10
+tools:
+  read_only: true
+  disabled: [call_rpc, purge_deleted]
+  aliases:
+    ping: alive
+otel:
+  headers:
+    x-honeycomb-team: abc123
+"#;
+
+fn parse(name: &str, text: &str) -> BTreeMap<String, String> {
+    let file = ConfigFile::parse(Path::new(name), text).unwrap();
+    assert!(file.unknown.is_empty(), "{:?}", file.unknown);
+    file.settings
+}
+
+#[test]
+fn test_config_file_sections_become_environment_variables() {
+    let settings = parse("exaspoon.toml", TOML);
+    assert_eq!(settings["DATABASE_BACKEND"], "sqlite");
+    assert_eq!(settings["SQLITE_PATH"], "/var/lib/exaspoon/finance.db");
+    assert_eq!(settings["EMBEDDING_DIMENSIONS"], "256");
+    assert_eq!(settings["OPENAI_API_KEY"], "sk-test");
+    assert_eq!(settings["TRANSPORT"], "http");
+    assert_eq!(settings["READ_ONLY"], "true");
+    assert_eq!(settings["DISABLED_TOOLS"], r#"["call_rpc","purge_deleted"]"#);
+    assert_eq!(settings["TOOL_ALIASES"], r#"{"ping":"alive"}"#);
+    assert_eq!(settings["OTEL_EXPORTER_OTLP_HEADERS"], "x-honeycomb-team=abc123");
+
+    assert_eq!(parse("exaspoon.yaml", YAML), settings);
+    assert!(parse("exaspoon.yml", "").is_empty());
+}
+
+#[test]
+fn test_config_file_skips_unknown_settings() {
+    let path = Path::new("exaspoon.toml");
+    let text = "[database]\nbackedn = \"sqlite\"\nbackend = \"memory\"\n[future]\nknob = 1\n";
+    let file = ConfigFile::parse(path, text).unwrap();
+    assert_eq!(file.unknown, vec!["database.backedn", "future.knob"]);
+    assert_eq!(file.settings["DATABASE_BACKEND"], "memory");
+    assert!(ConfigFile::parse(path, "[tools]\nread_only = [").is_err());
+    assert!(ConfigFile::parse(Path::new("exaspoon.ini"), "").is_err());
+
+    // Every setting names a distinct variable.
+    let vars = SETTINGS.iter().map(|(_, var)| var).collect::<HashSet<_>>();
+    assert_eq!(vars.len(), SETTINGS.len());
+}
+
+#[test]
+fn test_environment_overrides_config_file() {
+    let dir = std::env::temp_dir().join(format!("exaspoon-config-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("exaspoon.toml");
+    std::fs::write(&path, TOML).unwrap();
+
+    std::env::set_var("TRANSPORT", "stdio");
+    let file = ConfigFile::find(Some(&path)).unwrap().unwrap();
+    assert_eq!(file.apply(), vec!["TRANSPORT"]);
+
+    let config = AppConfig::from_env().unwrap();
+    assert_eq!(config.database_backend, "sqlite");
+    assert_eq!(config.transport, Transport::Stdio);
+    assert!(config.read_only);
+    assert_eq!(config.disabled_tools, vec!["call_rpc", "purge_deleted"]);
+
+    assert!(ConfigFile::find(Some(&dir.join("missing.toml"))).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+    for var in file.settings.keys() {
+        std::env::remove_var(var);
+    }
+}
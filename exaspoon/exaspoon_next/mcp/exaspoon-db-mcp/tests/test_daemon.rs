@@ -0,0 +1,54 @@
+//! Tests for running as a system service.
+#![cfg(unix)]
+
+use exaspoon_db_mcp::daemon::{self, PidFile};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("exaspoon-daemon-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_notify_reaches_the_service_manager_socket() {
+    std::env::remove_var("NOTIFY_SOCKET");
+    assert!(!daemon::notify("READY=1").unwrap());
+
+    let dir = temp_dir();
+    let path = dir.join("notify.sock");
+    let manager = UnixDatagram::bind(&path).unwrap();
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    assert!(daemon::notify("READY=1").unwrap());
+    std::env::remove_var("NOTIFY_SOCKET");
+
+    let mut buf = [0; 64];
+    let len = manager.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"READY=1");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_pid_file_is_removed_on_drop_and_guards_a_running_process() {
+    let dir = temp_dir();
+    let path = dir.join("exaspoon.pid");
+
+    let pid_file = PidFile::create(&path).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(written.trim(), std::process::id().to_string());
+    drop(pid_file);
+    assert!(!path.exists());
+
+    // A file naming a process that is gone is replaced.
+    std::fs::write(&path, format!("{}\n", u32::MAX)).unwrap();
+    drop(PidFile::create(&path).unwrap());
+
+    if std::path::Path::new("/proc/1").exists() {
+        std::fs::write(&path, "1\n").unwrap();
+        let err = PidFile::create(&path).unwrap_err();
+        assert!(err.to_string().contains("running process 1"), "{err}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1\n");
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
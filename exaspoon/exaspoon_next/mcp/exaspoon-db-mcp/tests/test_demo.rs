@@ -0,0 +1,50 @@
+//! Tests for the demo dataset behind `seed_demo_data`.
+
+use chrono::NaiveDate;
+use exaspoon_db_mcp::demo::{demo_accounts, demo_categories, demo_transactions, MAX_DEMO_MONTHS};
+
+fn date(value: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn test_demo_transactions_cover_requested_months_up_to_today() {
+    let today = date("2024-03-10");
+    let transactions = demo_transactions(today, 3);
+
+    let first = &transactions.first().unwrap().transaction.occurred_at;
+    let last = &transactions.last().unwrap().transaction.occurred_at;
+    assert!(first.starts_with("2024-01-01"), "{first}");
+    assert!(last.as_str() <= "2024-03-10T12:00:00Z", "{last}");
+    assert!(transactions
+        .iter()
+        .all(|demo| demo.transaction.occurred_at.as_str() <= "2024-03-10T23:59:59Z"));
+
+    let accounts = demo_accounts();
+    assert!(transactions
+        .iter()
+        .all(|demo| accounts.iter().any(|account| account.name == demo.account)));
+    assert!(transactions
+        .iter()
+        .all(|demo| demo.transaction.amount > 0.0 && demo.transaction.description.is_some()));
+}
+
+#[test]
+fn test_demo_transactions_are_deterministic_and_bounded() {
+    let today = date("2024-12-31");
+    let first = demo_transactions(today, 2);
+    let second = demo_transactions(today, 2);
+    let amounts = |rows: &[exaspoon_db_mcp::demo::DemoTransaction]| {
+        rows.iter()
+            .map(|demo| demo.transaction.amount)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(amounts(&first), amounts(&second));
+
+    let capped = demo_transactions(today, 100);
+    assert_eq!(
+        capped.len(),
+        demo_transactions(today, MAX_DEMO_MONTHS).len()
+    );
+    assert_eq!(demo_categories().len(), 9);
+}
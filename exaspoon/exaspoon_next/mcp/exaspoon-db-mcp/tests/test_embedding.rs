@@ -1,9 +1,33 @@
 //! Tests for embedding service.
 
-use exaspoon_db_mcp::embedding::Embedder;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use exaspoon_db_mcp::circuit::{CircuitBreakerConfig, CircuitOpen};
+#[cfg(feature = "openai")]
+use exaspoon_db_mcp::config::AzureOpenAiConfig;
+#[cfg(feature = "openai")]
+use exaspoon_db_mcp::embedding::EmbeddingService;
+use exaspoon_db_mcp::embedding::{
+    decode_base64_embedding, DeterministicEmbedder, Embedder, EmbedderFactory, EmbeddingEncoding,
+    EmbeddingPrompts, HashingEmbedder,
+};
 
 mod common;
 
+/// Embedder whose provider is down.
+struct UnreachableEmbedder;
+
+#[async_trait]
+impl Embedder for UnreachableEmbedder {
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+        Err(anyhow!("embedding request failed"))
+    }
+
+    async fn maybe_embed(&self, _text: Option<&str>) -> Result<Option<Vec<f32>>> {
+        Err(anyhow!("embedding request failed"))
+    }
+}
+
 #[tokio::test]
 async fn test_mock_embedder_embed() {
     let embedder = common::MockEmbedder::new(vec![0.1, 0.2, 0.3]);
@@ -69,6 +93,151 @@ async fn test_mock_embedder_multiple_calls() {
     assert_eq!(calls[2], "test3");
 }
 
+#[test]
+fn test_embedding_prompts_use_template_placeholder() {
+    let prompts = EmbeddingPrompts {
+        query: Some("Represent this query for retrieval: {text}".to_string()),
+        document: None,
+    };
+
+    assert_eq!(
+        prompts.render_query("coffee"),
+        "Represent this query for retrieval: coffee"
+    );
+    assert_eq!(prompts.render_document("coffee"), "coffee");
+}
+
+#[test]
+fn test_embedding_prompts_treat_template_without_placeholder_as_prefix() {
+    let prompts = EmbeddingPrompts {
+        query: Some("query: ".to_string()),
+        document: Some("passage: ".to_string()),
+    };
+
+    assert_eq!(prompts.render_query("rent"), "query: rent");
+    assert_eq!(prompts.render_document("rent"), "passage: rent");
+}
+
+#[tokio::test]
+async fn test_mock_embedder_embed_query_defaults_to_embed() {
+    let embedder = common::MockEmbedder::new(vec![0.1]);
+
+    let result = embedder.embed_query("query text").await.unwrap();
+    assert_eq!(result, vec![0.1]);
+    assert_eq!(embedder.calls(), vec!["query text"]);
+}
+
+#[cfg(feature = "openai")]
+#[test]
+fn test_embedding_service_builds_for_azure_deployment() {
+    let azure = AzureOpenAiConfig {
+        endpoint: "https://test.openai.azure.com/".to_string(),
+        api_key: "test-azure-key".to_string(),
+        deployment: "embeddings".to_string(),
+        api_version: "2024-10-21".to_string(),
+    };
+
+    assert!(EmbeddingService::azure(&azure, "text-embedding-3-large").is_ok());
+}
+
+#[test]
+fn test_decode_base64_embedding() {
+    // 1.0f32, -2.5f32 as little-endian bytes.
+    let decoded = decode_base64_embedding("AACAPwAAIMA=").unwrap();
+    assert_eq!(decoded, vec![1.0, -2.5]);
+}
+
+#[test]
+fn test_decode_base64_embedding_rejects_truncated_payload() {
+    assert!(decode_base64_embedding("AACA").is_err());
+    assert!(decode_base64_embedding("not base64!").is_err());
+}
+
+#[test]
+fn test_embedding_encoding_from_str() {
+    assert_eq!("float".parse::<EmbeddingEncoding>().unwrap(), EmbeddingEncoding::Float);
+    assert_eq!("BASE64".parse::<EmbeddingEncoding>().unwrap(), EmbeddingEncoding::Base64);
+    assert!("binary".parse::<EmbeddingEncoding>().is_err());
+}
+
+#[tokio::test]
+async fn test_embedder_factory_builds_local_provider() {
+    let mut config = common::test_config();
+    config.embedding_provider = "local".to_string();
+    config.embedding_dimensions = Some(64);
+
+    let embedder = EmbedderFactory::default().build(&config).unwrap();
+    let vector = embedder.embed("Coffee at the airport").await.unwrap();
+
+    assert_eq!(vector.len(), 64);
+}
+
+#[test]
+fn test_embedder_factory_rejects_unknown_provider() {
+    let mut config = common::test_config();
+    config.embedding_provider = "word2vec".to_string();
+
+    let err = EmbedderFactory::default().build(&config).err().unwrap();
+    assert!(err.to_string().contains("unknown embedding provider"));
+}
+
+#[test]
+fn test_embedder_factory_requires_provider_credentials() {
+    let mut config = common::test_config();
+    config.embedding_provider = "cohere".to_string();
+
+    let err = EmbedderFactory::default().build(&config).err().unwrap();
+    assert!(err.to_string().contains("COHERE_API_KEY"));
+}
+
+#[tokio::test]
+async fn test_embedder_factory_accepts_custom_providers() {
+    let mut factory = EmbedderFactory::empty();
+    factory.register("fixed", |_config| Ok(std::sync::Arc::new(common::MockEmbedder::new(vec![1.0]))));
+    let mut config = common::test_config();
+    config.embedding_provider = "fixed".to_string();
+
+    let embedder = factory.build(&config).unwrap();
+    assert_eq!(embedder.embed("anything").await.unwrap(), vec![1.0]);
+    assert_eq!(factory.providers(), vec!["fixed"]);
+}
+
+#[tokio::test]
+async fn test_embedder_factory_adds_circuit_breaker_to_remote_providers() {
+    let mut factory = EmbedderFactory::empty();
+    factory.register("down", |_config| Ok(std::sync::Arc::new(UnreachableEmbedder)));
+    let mut config = common::test_config();
+    config.embedding_provider = "down".to_string();
+    config.circuit_breaker = CircuitBreakerConfig::new(1, std::time::Duration::from_secs(60));
+
+    let embedder = factory.build(&config).unwrap();
+    let first = embedder.embed("coffee").await.unwrap_err();
+    assert!(first.downcast_ref::<CircuitOpen>().is_none());
+    let second = embedder.embed_query("coffee").await.unwrap_err();
+    let open = second.downcast_ref::<CircuitOpen>().expect("circuit open error");
+    assert_eq!(open.dependency, "down embedding provider");
+}
+
+#[test]
+fn test_hashing_embedder_scores_lexical_overlap() {
+    let embedder = HashingEmbedder::new(256);
+    let cosine = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+
+    let coffee = embedder.vectorize("Coffee at Blue Bottle");
+    let similar = embedder.vectorize("blue bottle coffee");
+    let unrelated = embedder.vectorize("Monthly rent payment");
+
+    assert!(cosine(&coffee, &similar) > cosine(&coffee, &unrelated));
+}
+
+#[test]
+fn test_deterministic_embedder_is_stable() {
+    let embedder = DeterministicEmbedder::new(16);
+
+    assert_eq!(embedder.vectorize("rent"), embedder.vectorize("rent"));
+    assert_ne!(embedder.vectorize("rent"), embedder.vectorize("coffee"));
+}
+
 // Note: We can't test the actual EmbeddingService without mocking the OpenAI client,
 // which would require more complex setup. The MockEmbedder provides sufficient testing
 // for the Embedder trait interface used by the server.
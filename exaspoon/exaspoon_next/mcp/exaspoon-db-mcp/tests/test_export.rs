@@ -0,0 +1,162 @@
+//! Tests for exporting transactions as JSON Lines and Parquet.
+
+use clap::Parser;
+use exaspoon_db_mcp::cli::{Cli, Command};
+use exaspoon_db_mcp::export::ExportFormat;
+use std::path::Path;
+
+mod common;
+
+#[test]
+fn test_format_is_told_from_the_output_path() {
+    assert_eq!(
+        ExportFormat::for_path(Path::new("out/transactions.parquet")),
+        ExportFormat::Parquet
+    );
+    assert_eq!(
+        ExportFormat::for_path(Path::new("TRANSACTIONS.PARQUET")),
+        ExportFormat::Parquet
+    );
+    assert_eq!(
+        ExportFormat::for_path(Path::new("transactions.jsonl")),
+        ExportFormat::Jsonl
+    );
+    assert_eq!(
+        ExportFormat::for_path(Path::new("transactions")),
+        ExportFormat::Jsonl
+    );
+
+    let args = [
+        "exaspoon-db-mcp",
+        "export",
+        "-o",
+        "rows.dat",
+        "--format",
+        "parquet",
+    ];
+    let Command::Export { output, format, .. } = Cli::try_parse_from(args).unwrap().command()
+    else {
+        panic!("not an export");
+    };
+    assert_eq!(output, Some("rows.dat".into()));
+    assert_eq!(format, Some(ExportFormat::Parquet));
+    assert!(Cli::try_parse_from(["exaspoon-db-mcp", "export", "--format", "csv"]).is_err());
+}
+
+#[cfg(feature = "memory-backend")]
+mod memory {
+    use super::common;
+    use exaspoon_db_mcp::cli;
+    use exaspoon_db_mcp::export::{self, ExportFormat};
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{TransactionDirection, TransactionFilters};
+    use exaspoon_db_mcp::supabase::Database;
+    use std::sync::Arc;
+
+    async fn database() -> Arc<MemoryDatabase> {
+        let database = Arc::new(MemoryDatabase::new());
+        let account = database
+            .upsert_account(&common::sample_account_input())
+            .await
+            .unwrap();
+        for (amount, description, day) in [(12.5, Some("Coffee"), 5), (3000.0, None, 6)] {
+            let mut input = common::sample_transaction_input();
+            input.account_id = account.id.clone();
+            input.amount = amount;
+            input.description = description.map(str::to_string);
+            input.occurred_at = format!("2024-01-{day:02}T12:30:00+02:00");
+            if description.is_none() {
+                input.direction = TransactionDirection::Income;
+            }
+            database.insert_transaction(&input, None).await.unwrap();
+        }
+        database
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_export_writes_one_transaction_per_line() {
+        let database = database().await;
+        let mut out = Vec::new();
+        let filters = TransactionFilters::default();
+        let exported = export::export(&*database, &filters, ExportFormat::Jsonl, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(exported, 2);
+        let lines = String::from_utf8(out).unwrap();
+        let rows: Vec<serde_json::Value> = lines
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["amount"], 3000.0);
+        assert_eq!(rows[1]["description"], "Coffee");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_parquet_export_reads_back_with_typed_columns() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let database = database().await;
+        let path = std::env::temp_dir().join(format!("export-{}.parquet", uuid::Uuid::new_v4()));
+        let out = std::fs::File::create(&path).unwrap();
+        let filters = TransactionFilters::default();
+        let exported = cli::export(&*database, &filters, ExportFormat::Parquet, out)
+            .await
+            .unwrap();
+        assert_eq!(exported, 2);
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 2);
+        let columns: Vec<_> = metadata
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                "id",
+                "account_id",
+                "amount",
+                "currency",
+                "direction",
+                "occurred_at",
+                "description",
+                "raw_source",
+                "category_id",
+                "created_at"
+            ]
+        );
+
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        // Newest first; 12:30 at +02:00 is 10:30 UTC.
+        assert_eq!(rows[0].get_double(2).unwrap(), 3000.0);
+        assert_eq!(rows[0].get_string(4).unwrap(), "income");
+        assert!(rows[0].get_string(6).is_err(), "description is null");
+        assert_eq!(rows[1].get_string(6).unwrap(), "Coffee");
+        assert_eq!(
+            rows[1].get_timestamp_micros(5).unwrap(),
+            1_704_450_600_000_000
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    #[tokio::test]
+    async fn test_parquet_export_needs_the_feature() {
+        let database = database().await;
+        let filters = TransactionFilters::default();
+        let err = cli::export(&*database, &filters, ExportFormat::Parquet, Vec::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("`parquet` feature"), "{err}");
+    }
+}
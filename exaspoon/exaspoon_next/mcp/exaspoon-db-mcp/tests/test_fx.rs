@@ -0,0 +1,548 @@
+//! Tests for exchange rates from the ECB and exchangerate.host,
+//! `convert_currency` and the reports' totals in the base currency.
+#![cfg(feature = "memory-backend")]
+
+use chrono::NaiveDate;
+use exaspoon_db_mcp::config::{FxConfig, HttpClientConfig};
+use exaspoon_db_mcp::fx::{FxClient, FxError, UnknownCurrency};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    AggregateSpendingInput, ConvertCurrencyInput, FxProvider, GetNetWorthInput, SpendingGroupBy,
+    SpendingPeriod, TransactionDirection, TransactionFilters,
+};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+fn fx_config(provider: FxProvider, api: &MockServer) -> FxConfig {
+    FxConfig {
+        provider,
+        base_url: api.uri(),
+        api_key: match provider {
+            FxProvider::Ecb => None,
+            FxProvider::ExchangerateHost => Some("access-key".to_string()),
+        },
+        base_currency: "USD".to_string(),
+        cache_ttl: Duration::from_secs(3600),
+    }
+}
+
+/// Frankfurter's rates per US dollar on `date`.
+fn frankfurter_rates(date: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "amount": 1.0,
+        "base": "USD",
+        "date": date,
+        "rates": { "EUR": 0.8, "GBP": 0.5 },
+    }))
+}
+
+fn day(text: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+}
+
+#[tokio::test]
+async fn test_frankfurter_rates_are_inverted_and_cached() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/latest"))
+        .and(query_param("base", "USD"))
+        .respond_with(frankfurter_rates("2024-03-01"))
+        .expect(1)
+        .mount(&api)
+        .await;
+    let fx = FxClient::new(
+        &fx_config(FxProvider::Ecb, &api),
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+
+    let rate = fx.rate("EUR", "USD", None).await.unwrap();
+    assert_eq!(rate.rate, 1.25);
+    assert_eq!(rate.date, day("2024-03-01"));
+    // The second currency comes from the rates already fetched.
+    assert_eq!(fx.rate("gbp", "usd", None).await.unwrap().rate, 2.0);
+    assert_eq!(fx.rate("USD", "USD", None).await.unwrap().rate, 1.0);
+}
+
+#[tokio::test]
+async fn test_frankfurter_history_and_unknown_currencies() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/2024-01-15"))
+        .and(query_param("base", "USD"))
+        .respond_with(frankfurter_rates("2024-01-15"))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/latest"))
+        .and(query_param("base", "XYZ"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({ "message": "not found" })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/1990-01-02"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({ "message": "not found" })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/currencies"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "EUR": "Euro",
+            "GBP": "British Pound",
+            "JPY": "Japanese Yen",
+            "USD": "United States Dollar",
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/latest"))
+        .and(query_param("base", "JPY"))
+        .respond_with(
+            ResponseTemplate::new(500).set_body_json(json!({ "message": "upstream down" })),
+        )
+        .mount(&api)
+        .await;
+    let fx = FxClient::new(
+        &fx_config(FxProvider::Ecb, &api),
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+
+    let on = Some(day("2024-01-15"));
+    assert_eq!(fx.rate("EUR", "USD", on).await.unwrap().rate, 1.25);
+    assert_eq!(fx.rate("EUR", "USD", on).await.unwrap().rate, 1.25);
+
+    let err = fx.rate("CHF", "USD", on).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownCurrency>().unwrap().0, "CHF");
+    let err = fx.rate("USD", "XYZ", None).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownCurrency>().unwrap().0, "XYZ");
+    // A day without rates is not an unknown currency.
+    let err = fx
+        .rate("EUR", "USD", Some(day("1990-01-02")))
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<UnknownCurrency>().is_none());
+    assert_eq!(err.downcast_ref::<FxError>().unwrap().code, 404);
+
+    let err = fx.rate("USD", "JPY", None).await.unwrap_err();
+    let rejected = err.downcast_ref::<FxError>().unwrap();
+    assert_eq!(rejected.code, 500);
+    assert_eq!(rejected.message, "upstream down");
+}
+
+#[tokio::test]
+async fn test_exchangerate_host_quotes_and_errors() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/live"))
+        .and(query_param("access_key", "access-key"))
+        .and(query_param("source", "EUR"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": true,
+            "source": "EUR",
+            "timestamp": 1709251200,
+            "quotes": { "EURUSD": 1.25 },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/historical"))
+        .and(query_param("date", "2024-01-15"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": false,
+            "error": {
+                "code": 106,
+                "type": "no_rates_available",
+                "info": "Your query did not return any results.",
+            },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/live"))
+        .and(query_param("source", "XYZ"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "success": false,
+            "error": { "code": 201, "type": "invalid_source_currency" },
+        })))
+        .mount(&api)
+        .await;
+    let fx = FxClient::new(
+        &fx_config(FxProvider::ExchangerateHost, &api),
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+
+    let rate = fx.rate("USD", "EUR", None).await.unwrap();
+    assert_eq!(rate.rate, 0.8);
+    assert_eq!(rate.date, day("2024-03-01"));
+
+    let err = fx
+        .rate("USD", "EUR", Some(day("2024-01-15")))
+        .await
+        .unwrap_err();
+    let rejected = err.downcast_ref::<FxError>().unwrap();
+    assert_eq!(rejected.provider, FxProvider::ExchangerateHost);
+    assert_eq!(rejected.code, 106);
+    assert_eq!(rejected.message, "Your query did not return any results.");
+
+    let err = fx.rate("USD", "XYZ", None).await.unwrap_err();
+    assert!(err.downcast_ref::<UnknownCurrency>().is_some());
+
+    let mut config = fx_config(FxProvider::ExchangerateHost, &api);
+    config.api_key = None;
+    assert!(FxClient::new(&config, &HttpClientConfig::default()).is_err());
+}
+
+fn convert_input(amount: f64, from: &str, to: Option<&str>) -> ConvertCurrencyInput {
+    ConvertCurrencyInput {
+        amount,
+        from: from.to_string(),
+        to: to.map(str::to_string),
+        date: None,
+    }
+}
+
+#[tokio::test]
+async fn test_convert_currency_into_the_base_currency() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/latest"))
+        .and(query_param("base", "USD"))
+        .respond_with(frankfurter_rates("2024-03-01"))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/latest"))
+        .and(query_param("base", "EUR"))
+        .respond_with(ResponseTemplate::new(502).set_body_string("bad gateway"))
+        .mount(&api)
+        .await;
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    );
+    assert!(server
+        .convert_currency(Parameters(convert_input(1.0, "EUR", None)))
+        .await
+        .is_err());
+    let server = server.with_fx(
+        FxClient::new(
+            &fx_config(FxProvider::Ecb, &api),
+            &HttpClientConfig::default(),
+        )
+        .unwrap(),
+    );
+
+    let converted = server
+        .convert_currency(Parameters(convert_input(100.0, "eur", None)))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(converted["from"], "EUR");
+    assert_eq!(converted["to"], "USD");
+    assert_eq!(converted["converted"], 125.0);
+    assert_eq!(converted["rate"], 1.25);
+    assert_eq!(converted["date"], "2024-03-01");
+    assert_eq!(converted["provider"], "ecb");
+
+    // Not rounded to cents, which other currencies do not have.
+    let converted = server
+        .convert_currency(Parameters(convert_input(0.333, "EUR", None)))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(converted["converted"], 0.333 * 1.25);
+
+    let err = server
+        .convert_currency(Parameters(convert_input(1.0, "CHF", Some("USD"))))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "from");
+
+    let err = server
+        .convert_currency(Parameters(convert_input(1.0, "euro", None)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "from");
+
+    let mut input = convert_input(1.0, "EUR", None);
+    input.date = Some("15/01/2024".to_string());
+    let err = server
+        .convert_currency(Parameters(input))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "date");
+
+    let err = server
+        .convert_currency(Parameters(convert_input(1.0, "USD", Some("EUR"))))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["fx"]["provider"], "ecb");
+    assert_eq!(data["fx"]["code"], 502);
+}
+
+#[tokio::test]
+async fn test_aggregate_spending_adds_totals_in_the_base_currency() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/2024-01-01"))
+        .and(query_param("base", "USD"))
+        .respond_with(frankfurter_rates("2024-01-01"))
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    for (amount, currency) in [(10.0, "EUR"), (5.0, "USD"), (3.0, "CHF")] {
+        input.amount = amount;
+        input.currency = currency.to_string();
+        database.insert_transaction(&input, None).await.unwrap();
+    }
+    let server = ExaspoonDbServer::new(
+        database,
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    );
+    let spending = AggregateSpendingInput {
+        group_by: SpendingGroupBy::Direction,
+        period: Some(SpendingPeriod::Month),
+        filters: TransactionFilters::default(),
+    };
+
+    let report = server
+        .aggregate_spending(Parameters(spending.clone()))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(report["buckets"].as_array().unwrap().len(), 3);
+    assert!(report.get("base_totals").is_none());
+
+    let server = server.with_fx(
+        FxClient::new(
+            &fx_config(FxProvider::Ecb, &api),
+            &HttpClientConfig::default(),
+        )
+        .unwrap(),
+    );
+    let report = server
+        .aggregate_spending(Parameters(spending))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    let base = &report["base_totals"];
+    assert_eq!(base["currency"], "USD");
+    assert_eq!(
+        base["buckets"],
+        json!([{
+            "key": "expense",
+            "period_start": "2024-01-01T00:00:00Z",
+            "currency": "USD",
+            "total": 17.5,
+            "count": 2,
+        }])
+    );
+    assert_eq!(base["unconverted"], json!(["CHF"]));
+}
+
+#[tokio::test]
+async fn test_daily_totals_read_their_rates_as_one_series() {
+    let api = MockServer::start().await;
+    // Friday's rates stand in for the weekend.
+    Mock::given(method("GET"))
+        .and(path("/2024-01-05..2024-01-08"))
+        .and(query_param("base", "USD"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "amount": 1.0,
+            "base": "USD",
+            "start_date": "2024-01-05",
+            "end_date": "2024-01-08",
+            "rates": {
+                "2024-01-05": { "EUR": 0.8 },
+                "2024-01-08": { "EUR": 0.5 },
+            },
+        })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    input.currency = "EUR".to_string();
+    input.amount = 10.0;
+    for day in ["05", "06", "08"] {
+        input.occurred_at = format!("2024-01-{day}T12:00:00Z");
+        database.insert_transaction(&input, None).await.unwrap();
+    }
+    let server = ExaspoonDbServer::new(
+        database,
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_fx(
+        FxClient::new(
+            &fx_config(FxProvider::Ecb, &api),
+            &HttpClientConfig::default(),
+        )
+        .unwrap(),
+    );
+
+    let report = server
+        .aggregate_spending(Parameters(AggregateSpendingInput {
+            group_by: SpendingGroupBy::Direction,
+            period: Some(SpendingPeriod::Day),
+            filters: TransactionFilters::default(),
+        }))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    let totals: Vec<_> = report["base_totals"]["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bucket| bucket["total"].as_f64().unwrap())
+        .collect();
+    assert_eq!(totals, vec![12.5, 12.5, 20.0]);
+}
+
+#[tokio::test]
+async fn test_net_worth_values_every_account_in_the_base_currency() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/2024-01-31"))
+        .and(query_param("base", "USD"))
+        .respond_with(frankfurter_rates("2024-01-31"))
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let checking = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut savings = common::sample_account_input();
+    savings.name = "Savings".to_string();
+    savings.currency = "EUR".to_string();
+    let savings = database.upsert_account(&savings).await.unwrap();
+    let mut input = common::sample_transaction_input();
+    for (account, amount, currency, direction, day) in [
+        (
+            &checking.id,
+            1000.0,
+            "USD",
+            TransactionDirection::Income,
+            "2024-01-02",
+        ),
+        (
+            &checking.id,
+            250.0,
+            "USD",
+            TransactionDirection::Expense,
+            "2024-01-10",
+        ),
+        (
+            &checking.id,
+            100.0,
+            "USD",
+            TransactionDirection::Transfer,
+            "2024-01-11",
+        ),
+        (
+            &savings.id,
+            400.0,
+            "EUR",
+            TransactionDirection::Income,
+            "2024-01-15",
+        ),
+        (
+            &savings.id,
+            50.0,
+            "EUR",
+            TransactionDirection::Income,
+            "2024-02-15",
+        ),
+    ] {
+        input.account_id = account.clone();
+        input.amount = amount;
+        input.currency = currency.to_string();
+        input.direction = direction;
+        input.occurred_at = format!("{day}T12:00:00Z");
+        database.insert_transaction(&input, None).await.unwrap();
+    }
+    let server = ExaspoonDbServer::new(
+        database,
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    );
+    let at_month_end = || GetNetWorthInput {
+        date: Some("2024-01-31".to_string()),
+    };
+
+    let worth = server
+        .get_net_worth(Parameters(at_month_end()))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(worth["balances"].as_array().unwrap().len(), 2);
+    assert!(worth.get("net_worth").is_none());
+
+    let server = server.with_fx(
+        FxClient::new(
+            &fx_config(FxProvider::Ecb, &api),
+            &HttpClientConfig::default(),
+        )
+        .unwrap(),
+    );
+    let worth = server
+        .get_net_worth(Parameters(at_month_end()))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(worth["date"], "2024-01-31");
+    let balance = |account: &str| {
+        worth["balances"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|bucket| bucket["key"] == account)
+            .map(|bucket| {
+                (
+                    bucket["total"].as_f64().unwrap(),
+                    bucket["currency"].clone(),
+                )
+            })
+            .unwrap()
+    };
+    assert_eq!(balance(&checking.id), (750.0, json!("USD")));
+    assert_eq!(balance(&savings.id), (400.0, json!("EUR")));
+    assert_eq!(worth["net_worth"], 1250.0);
+
+    let err = server
+        .get_net_worth(Parameters(GetNetWorthInput {
+            date: Some("31/01/2024".to_string()),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "date");
+}
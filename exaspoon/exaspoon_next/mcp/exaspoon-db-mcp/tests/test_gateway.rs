@@ -0,0 +1,1186 @@
+//! Tests for the Supabase gateway against a mock PostgREST server.
+#![cfg(feature = "supabase")]
+
+use exaspoon_db_mcp::auth::{AuthContext, ACCESS_TOKEN_META_KEY};
+use exaspoon_db_mcp::batch::{BatchFailed, BatchStep, MissingRow};
+use exaspoon_db_mcp::circuit::{CircuitBreakerConfig, CircuitOpen};
+use exaspoon_db_mcp::correlation::{self, CORRELATION_HEADER};
+use exaspoon_db_mcp::models::{
+    AggregateSpendingInput, AuditEvent, BatchStepResult, CategorizeInput, EmbeddingIssueKind,
+    ListAccountsInput, ListAuditEventsInput, RecordKind, SpendingGroupBy, SpendingPeriod,
+    TransactionDirection, TransactionFilters, UpsertAccountInput,
+};
+use exaspoon_db_mcp::postgrest::{PostgrestError, PostgrestErrorKind, FOREIGN_KEY_VIOLATION};
+use exaspoon_db_mcp::retry::RetryPolicy;
+use exaspoon_db_mcp::supabase::{Database, SupabaseGateway};
+use rmcp::model::Meta;
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::matchers::{
+    body_partial_json, header, headers, method, path, query_param, query_param_is_missing,
+};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+mod common;
+
+async fn gateway(
+    server: &MockServer,
+    configure: impl FnOnce(&mut exaspoon_db_mcp::config::AppConfig),
+) -> SupabaseGateway {
+    let mut config = common::test_config();
+    config.supabase_url = server.uri();
+    configure(&mut config);
+    SupabaseGateway::new(&config).unwrap()
+}
+
+fn transaction_row(id: &str, occurred_at: &str) -> Value {
+    json!({
+        "id": id, "account_id": "acct-1", "amount": 10.0, "currency": "USD",
+        "direction": "expense", "occurred_at": occurred_at
+    })
+}
+
+fn account_rows() -> serde_json::Value {
+    json!([{ "id": "acct-1", "name": "Checking", "type": "offchain", "currency": "USD" }])
+}
+
+#[tokio::test]
+async fn test_gateway_uses_service_key_without_user_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("order", "name.asc"))
+        .and(header("apikey", "test-service-key"))
+        .and(header("authorization", "Bearer test-service-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].name, "Checking");
+}
+
+#[tokio::test]
+async fn test_gateway_forwards_user_token_from_auth_context() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(header("apikey", "test-service-key"))
+        .and(header("authorization", "Bearer user-jwt"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.supabase_access_token = Some("session-jwt".to_string());
+    })
+    .await;
+    let accounts = AuthContext::new("user-jwt")
+        .scope(db.list_accounts(&ListAccountsInput::default()))
+        .await
+        .unwrap();
+
+    assert_eq!(accounts.len(), 1);
+}
+
+#[tokio::test]
+async fn test_gateway_falls_back_to_session_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/search_similar_categories"))
+        .and(header("authorization", "Bearer session-jwt"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.supabase_access_token = Some("session-jwt".to_string());
+    })
+    .await;
+
+    let matches = db.search_similar_categories(vec![0.1], None).await.unwrap();
+    assert!(matches.is_empty());
+}
+
+#[tokio::test]
+async fn test_gateway_requires_user_token_when_configured() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| config.supabase_require_user_auth = true).await;
+    let err = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("user access token is required"));
+}
+
+#[tokio::test]
+async fn test_gateway_filters_reads_by_tenant() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("user_id", "eq.household-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+
+    assert_eq!(accounts.len(), 1);
+}
+
+#[tokio::test]
+async fn test_gateway_stamps_tenant_on_upserts() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("on_conflict", "user_id,name,type"))
+        .and(body_partial_json(
+            json!({ "user_id": "household-1", "name": "Checking", "deleted_at": null }),
+        ))
+        .respond_with(ResponseTemplate::new(201).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    assert_eq!(account.id, "acct-1");
+}
+
+#[tokio::test]
+async fn test_gateway_upserts_on_the_tenant_key_without_a_tenant() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("on_conflict", "user_id,name,type"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    assert_eq!(account.id, "acct-1");
+    let requests = server.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert!(body.get("user_id").is_none());
+}
+
+#[tokio::test]
+async fn test_gateway_passes_tenant_to_search_rpc() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/search_similar_transactions"))
+        .and(body_partial_json(json!({ "filter_user_id": "household-1", "match_count": 5 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+
+    let matches = db
+        .search_similar_transactions(vec![0.1], None)
+        .await
+        .unwrap();
+    assert!(matches.is_empty());
+}
+
+#[tokio::test]
+async fn test_gateway_inserts_transactions_in_one_request() {
+    let server = MockServer::start().await;
+    let stored = json!([
+        { "id": "txn-1", "account_id": "acct-1", "amount": 42.0, "currency": "USD",
+          "direction": "expense", "occurred_at": "2024-01-02T03:04:05Z" },
+        { "id": "txn-2", "account_id": "acct-1", "amount": 42.0, "currency": "USD",
+          "direction": "expense", "occurred_at": "2024-01-02T03:04:05Z" }
+    ]);
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/transactions"))
+        .and(header("prefer", "return=representation"))
+        .and(|request: &Request| {
+            serde_json::from_slice::<Vec<Value>>(&request.body)
+                .map(|rows| {
+                    rows.len() == 2 && rows.iter().all(|row| row["user_id"] == "household-1")
+                })
+                .unwrap_or(false)
+        })
+        .respond_with(ResponseTemplate::new(201).set_body_json(stored))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+    let rows = vec![
+        (common::sample_transaction_input(), Some(vec![0.1])),
+        (common::sample_transaction_input(), None),
+    ];
+    let inserted = db.insert_transactions(&rows).await.unwrap();
+
+    let ids: Vec<&str> = inserted.iter().map(|txn| txn.id.as_str()).collect();
+    assert_eq!(ids, vec!["txn-1", "txn-2"]);
+}
+
+#[tokio::test]
+async fn test_gateway_writes_use_returned_representation() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/transactions"))
+        .and(header("prefer", "return=representation"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{
+            "id": "txn-17", "account_id": "acct-1", "amount": 42.0, "currency": "USD",
+            "direction": "expense", "occurred_at": "2024-01-02T03:04:05Z",
+            "created_at": "2024-01-02T03:04:06Z"
+        }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/accounts"))
+        .and(headers(
+            "prefer",
+            vec!["resolution=merge-duplicates", "return=representation"],
+        ))
+        .respond_with(ResponseTemplate::new(201).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let transaction = db
+        .insert_transaction(&common::sample_transaction_input(), None)
+        .await
+        .unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    assert_eq!(transaction.id, "txn-17");
+    assert_eq!(
+        transaction.created_at.as_deref(),
+        Some("2024-01-02T03:04:06Z")
+    );
+    assert_eq!(account.id, "acct-1");
+}
+
+#[tokio::test]
+async fn test_gateway_soft_deletes_and_hides_deleted_rows() {
+    let server = MockServer::start().await;
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param("id", "eq.txn-1"))
+        .and(query_param("deleted_at", "is.null"))
+        .and(query_param("user_id", "eq.household-1"))
+        .and(|request: &Request| {
+            serde_json::from_slice::<Value>(&request.body)
+                .map(|body| body["deleted_at"].is_string())
+                .unwrap_or(false)
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": "txn-1" }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("deleted_at", "is.null"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+
+    assert!(db
+        .soft_delete(RecordKind::Transaction, "txn-1")
+        .await
+        .unwrap());
+    db.list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_gateway_surfaces_postgres_error_fields() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/transactions"))
+        .respond_with(ResponseTemplate::new(409).set_body_json(json!({
+            "code": "23503",
+            "message": "insert or update on table \"transactions\" violates foreign key constraint \"transactions_account_id_fkey\"",
+            "details": "Key (account_id)=(acct-1) is not present in table \"accounts\".",
+            "hint": null,
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let err = db
+        .insert_transaction(&common::sample_transaction_input(), None)
+        .await
+        .unwrap_err();
+
+    let rejected = err
+        .downcast_ref::<PostgrestError>()
+        .expect("PostgREST error");
+    assert_eq!(rejected.operation, "insert into transactions");
+    assert_eq!(rejected.status.as_u16(), 409);
+    assert_eq!(rejected.code.as_deref(), Some(FOREIGN_KEY_VIOLATION));
+    assert_eq!(rejected.kind(), PostgrestErrorKind::InvalidInput);
+    assert!(err.to_string().contains("is not present in table"));
+}
+
+#[tokio::test]
+async fn test_gateway_purges_only_soft_deleted_rows() {
+    let server = MockServer::start().await;
+    Mock::given(method("DELETE"))
+        .and(path("/rest/v1/categories"))
+        .and(|request: &Request| {
+            let filters: Vec<String> = request
+                .url
+                .query_pairs()
+                .filter(|(key, _)| key == "deleted_at")
+                .map(|(_, value)| value.into_owned())
+                .collect();
+            filters == ["not.is.null", "lt.2024-01-01T00:00:00Z"]
+        })
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([{ "id": "cat-1" }, { "id": "cat-2" }])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let purged = db
+        .purge_deleted(RecordKind::Category, Some("2024-01-01T00:00:00Z"))
+        .await
+        .unwrap();
+
+    assert_eq!(purged, 2);
+}
+
+#[tokio::test]
+async fn test_gateway_scans_and_patches_embeddings() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/embedding_issues"))
+        .and(body_partial_json(json!({
+            "target_table": "transactions",
+            "expected_dimension": 3,
+            "after_id": "txn-1",
+            "batch_size": 50,
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": "txn-2", "issue": "dimension", "content": "Rent" },
+            { "id": "txn-3", "issue": "orphaned", "content": null },
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param("id", "eq.txn-2"))
+        .and(body_partial_json(json!({ "embedding": [0.0, 1.0, 0.0] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": "txn-2" }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param("id", "eq.txn-404"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let issues = db
+        .embedding_issues(RecordKind::Transaction, 3, Some("txn-1"), 50)
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].issue, EmbeddingIssueKind::Dimension);
+    assert_eq!(issues[1].content, None);
+
+    db.set_embedding(RecordKind::Transaction, "txn-2", Some(vec![0.0, 1.0, 0.0]))
+        .await
+        .unwrap();
+    assert!(db
+        .set_embedding(RecordKind::Transaction, "txn-404", None)
+        .await
+        .is_err());
+    assert!(db
+        .embedding_issues(RecordKind::Account, 3, None, 50)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_gateway_records_and_lists_audit_events() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/audit_log"))
+        .and(body_partial_json(json!([{
+            "user_id": "household-1",
+            "tool": "delete_account",
+            "record_id": "acct-1",
+        }])))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": "evt-1" }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/audit_log"))
+        .and(query_param("record_id", "eq.acct-1"))
+        .and(query_param("user_id", "eq.household-1"))
+        .and(query_param("order", "occurred_at.desc,id.desc"))
+        .and(|request: &Request| {
+            !request
+                .url
+                .query_pairs()
+                .any(|(key, _)| key == "deleted_at")
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "tool": "delete_account",
+            "input_hash": "ab".repeat(32),
+            "record_id": "acct-1",
+            "occurred_at": "2024-01-01T00:00:00.000Z",
+            "session_id": "session-1",
+        }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+    db.record_audit_events(&[AuditEvent {
+        tool: "delete_account".to_string(),
+        input_hash: "ab".repeat(32),
+        record_id: Some("acct-1".to_string()),
+        occurred_at: "2024-01-01T00:00:00.000Z".to_string(),
+        session_id: Some("session-1".to_string()),
+        details: None,
+    }])
+    .await
+    .unwrap();
+    let events = db
+        .list_audit_events(&ListAuditEventsInput {
+            record_id: Some("acct-1".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].session_id.as_deref(), Some("session-1"));
+}
+
+#[tokio::test]
+async fn test_gateway_fails_fast_once_circuit_opens() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.supabase_retry = RetryPolicy::new(1, Duration::ZERO);
+        config.circuit_breaker = CircuitBreakerConfig::new(2, Duration::from_secs(60));
+    })
+    .await;
+    let params = ListAccountsInput::default();
+
+    let first = db.list_accounts(&params).await.unwrap_err();
+    assert!(first.to_string().contains("503"));
+    let second = db.list_accounts(&params).await.unwrap_err();
+    assert!(second.downcast_ref::<CircuitOpen>().is_some(), "{second}");
+}
+
+#[tokio::test]
+async fn test_gateway_records_metrics_per_table_and_rpc() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/search_similar_transactions"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("bad embedding"))
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let params = ListAccountsInput::default();
+    db.list_accounts(&params).await.unwrap();
+    db.list_accounts(&params).await.unwrap();
+    db.search_similar_transactions(vec![0.1], None)
+        .await
+        .unwrap_err();
+
+    let metrics = db.metrics();
+    let accounts = metrics
+        .iter()
+        .find(|operation| operation.operation == "query accounts")
+        .expect("accounts metrics");
+    assert_eq!((accounts.calls, accounts.errors), (2, 0));
+    assert_eq!(
+        accounts
+            .histogram
+            .iter()
+            .map(|bucket| bucket.count)
+            .sum::<u64>(),
+        2
+    );
+    let search = metrics
+        .iter()
+        .find(|operation| operation.operation == "RPC search_similar_transactions")
+        .expect("search metrics");
+    assert_eq!((search.calls, search.errors), (1, 1));
+}
+
+#[tokio::test]
+async fn test_gateway_streams_transactions_with_keyset_pages() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param("order", "occurred_at.desc,id.desc"))
+        .and(query_param("limit", "2"))
+        .and(query_param("account_id", "eq.acct-1"))
+        .and(query_param_is_missing("or"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            transaction_row("txn-3", "2024-03-01T00:00:00+00:00"),
+            transaction_row("txn-2", "2024-02-01T00:00:00+00:00"),
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param(
+            "or",
+            "(occurred_at.lt.\"2024-02-01T00:00:00+00:00\",\
+             and(occurred_at.eq.\"2024-02-01T00:00:00+00:00\",id.lt.\"txn-2\"))",
+        ))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([transaction_row(
+                "txn-1",
+                "2024-01-01T00:00:00+00:00"
+            )])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let filters = TransactionFilters {
+        account_id: Some("acct-1".to_string()),
+        ..TransactionFilters::default()
+    };
+    let (sender, mut pages) = tokio::sync::mpsc::channel(1);
+    let export = tokio::spawn(async move { db.stream_transactions(&filters, 2, sender).await });
+
+    let mut ids = Vec::new();
+    while let Some(page) = pages.recv().await {
+        assert!(page.len() <= 2);
+        ids.extend(page.into_iter().map(|transaction| transaction.id));
+    }
+    assert_eq!(export.await.unwrap().unwrap(), 3);
+    assert_eq!(ids, ["txn-3", "txn-2", "txn-1"]);
+}
+
+#[tokio::test]
+async fn test_gateway_ranks_largest_transactions_server_side() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param("order", "amount.desc,occurred_at.desc,id.desc"))
+        .and(query_param("limit", "10"))
+        .and(query_param("direction", "eq.expense"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([transaction_row(
+            "txn-1",
+            "2024-01-01T00:00:00+00:00"
+        )])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let filters = TransactionFilters {
+        direction: Some(TransactionDirection::Expense),
+        ..TransactionFilters::default()
+    };
+    let largest = db.largest_transactions(&filters, 10).await.unwrap();
+    assert_eq!(largest.len(), 1);
+}
+
+#[tokio::test]
+async fn test_gateway_routes_reads_to_read_endpoint() {
+    let primary = MockServer::start().await;
+    let replica = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/accounts"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&primary)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(2)
+        .mount(&replica)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/search_similar_transactions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&replica)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("limit", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&primary)
+        .await;
+
+    let db = gateway(&primary, |config| {
+        config.supabase_read_url = Some(replica.uri());
+    })
+    .await;
+    db.upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    db.search_similar_transactions(vec![0.1], None)
+        .await
+        .unwrap();
+    db.ping().await.unwrap();
+
+    assert_eq!(accounts[0].id, "acct-1");
+}
+
+#[tokio::test]
+async fn test_gateway_reads_that_decide_writes_on_primary() {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path("/rest/v1/transactions"))
+        .and(query_param("account_id", "eq.acct-1"))
+        .respond_with(ResponseTemplate::new(200).insert_header("content-range", "0-0/3"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/bank_links"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": "acct-1", "metadata": { "plaid": "item-1" } }
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/categories"))
+        .and(query_param("offset", "0"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": "cat-1", "name": "Groceries", "kind": "expense" }
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/embedding_issues"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // Nothing listens on the read endpoint.
+    let db = gateway(&server, |config| {
+        config.supabase_read_url = Some("http://127.0.0.1:9".to_string());
+    })
+    .await;
+
+    assert_eq!(db.count_account_transactions("acct-1").await.unwrap(), 3);
+    assert!(db.bank_link("acct-1").await.unwrap().is_none());
+    let metadata = db.account_metadata("acct-1").await.unwrap();
+    assert_eq!(metadata["plaid"], "item-1");
+    let categories = db.list_categories().await.unwrap();
+    assert_eq!(categories[0].name, "Groceries");
+    let issues = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 10)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[tokio::test]
+async fn test_gateway_calls_custom_functions_on_primary() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/monthly_burn"))
+        .and(body_partial_json(
+            json!({ "months": 3, "filter_user_id": "household-1" }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(1234.5)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/refresh_rollups"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+        config.supabase_read_url = Some("http://127.0.0.1:9".to_string());
+    })
+    .await;
+
+    let burn = db
+        .call_function("monthly_burn", json!({ "months": 3 }))
+        .await
+        .unwrap();
+    assert_eq!(burn, json!(1234.5));
+    let refreshed = db
+        .call_function("refresh_rollups", json!({}))
+        .await
+        .unwrap();
+    assert_eq!(refreshed, Value::Null);
+}
+
+#[tokio::test]
+async fn test_gateway_merges_account_metadata_on_the_server() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/set_account_metadata"))
+        .and(body_partial_json(json!({
+            "target_account": "acct-1",
+            "metadata_key": "plaid",
+            "metadata_value": { "cursor": "c-2" },
+            "filter_user_id": "household-1",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(true)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/set_account_metadata"))
+        .and(body_partial_json(json!({ "target_account": "acct-2" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| {
+        config.tenant_id = Some("household-1".to_string());
+    })
+    .await;
+
+    db.set_account_metadata("acct-1", "plaid", json!({ "cursor": "c-2" }))
+        .await
+        .unwrap();
+    let err = db
+        .set_account_metadata("acct-2", "plaid", json!({ "cursor": "c-2" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("acct-2 was not found"));
+}
+
+#[tokio::test]
+async fn test_gateway_executes_batches_in_one_rpc() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/execute_batch"))
+        .and(body_partial_json(json!({
+            "steps": [{ "op": "categorize", "transaction_id": "txn-1", "category_id": "cat-1" }],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "steps": [{
+                "op": "categorize",
+                "transaction": transaction_row("txn-1", "2024-01-02T03:04:05Z"),
+            }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/execute_batch"))
+        .and(body_partial_json(json!({
+            "steps": [{ "op": "categorize", "transaction_id": "txn-1", "category_id": "cat-404" }],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "failed_step": 0, "code": "P0002", "message": "no live category with id cat-404",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let categorize = |category_id: &str| {
+        vec![BatchStep::Categorize(CategorizeInput {
+            transaction_id: "txn-1".to_string(),
+            category_id: category_id.to_string(),
+        })]
+    };
+
+    let results = db.execute_batch(&categorize("cat-1")).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(matches!(
+        &results[0],
+        BatchStepResult::Categorize { transaction } if transaction.id == "txn-1"
+    ));
+
+    let err = db.execute_batch(&categorize("cat-404")).await.unwrap_err();
+    let failed = err.downcast_ref::<BatchFailed>().unwrap();
+    assert_eq!(failed.step, 0);
+    assert!(failed.rolled_back);
+    assert!(failed.source.downcast_ref::<MissingRow>().is_some());
+}
+
+#[tokio::test]
+async fn test_gateway_runs_batches_for_their_own_tenant() {
+    let server = MockServer::start().await;
+    // Both households keep an account named Checking.
+    let households = [
+        ("household-1", "acct-1", "USD"),
+        ("household-2", "acct-2", "EUR"),
+    ];
+    for (tenant, id, currency) in households {
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/rpc/execute_batch"))
+            .and(body_partial_json(json!({
+                "steps": [{ "op": "upsert_account", "name": "Checking", "currency": currency }],
+                "filter_user_id": tenant,
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "steps": [{
+                    "op": "upsert_account",
+                    "account": {
+                        "id": id, "user_id": tenant, "name": "Checking",
+                        "type": "offchain", "currency": currency,
+                    },
+                }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    for (tenant, id, currency) in households {
+        let db = gateway(&server, |config| {
+            config.tenant_id = Some(tenant.to_string());
+        })
+        .await;
+        let step = BatchStep::UpsertAccount(UpsertAccountInput {
+            currency: currency.to_string(),
+            ..common::sample_account_input()
+        });
+
+        let results = db.execute_batch(&[step]).await.unwrap();
+        assert!(matches!(
+            &results[0],
+            BatchStepResult::UpsertAccount { account } if account.id == id
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_gateway_searches_transactions_text_via_rpc() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/search_transactions_text"))
+        .and(body_partial_json(json!({
+            "search_query": "invoice 4812",
+            "match_count": 20,
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": "txn-4812",
+            "account_id": "acct-1",
+            "amount": 99.0,
+            "currency": "USD",
+            "direction": "expense",
+            "occurred_at": "2024-01-02T03:04:05Z",
+            "description": "Invoice 4812",
+            "rank": 0.75
+        }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let matches = db
+        .search_transactions_text("invoice 4812", None)
+        .await
+        .unwrap();
+
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].transaction.id, "txn-4812");
+    assert_eq!(matches[0].rank, Some(0.75));
+}
+
+#[tokio::test]
+async fn test_gateway_aggregates_spending_via_rpc() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/aggregate_spending"))
+        .and(body_partial_json(json!({
+            "group_by": "category",
+            "period": "month",
+            "from_date": "2024-01-01T00:00:00Z",
+            "filter_account_id": null,
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "key": "cat-food",
+            "period_start": "2024-01-01T00:00:00+00:00",
+            "currency": "USD",
+            "total": 120.5,
+            "count": 3
+        }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let buckets = db
+        .aggregate_spending(&AggregateSpendingInput {
+            group_by: SpendingGroupBy::Category,
+            period: Some(SpendingPeriod::Month),
+            filters: TransactionFilters {
+                from: Some("2024-01-01T00:00:00Z".to_string()),
+                ..TransactionFilters::default()
+            },
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(buckets.len(), 1);
+    assert_eq!(buckets[0].key.as_deref(), Some("cat-food"));
+    assert_eq!(buckets[0].total, 120.5);
+    assert_eq!(buckets[0].count, 3);
+}
+
+#[tokio::test]
+async fn test_gateway_counts_rows_from_content_range() {
+    let server = MockServer::start().await;
+    Mock::given(method("HEAD"))
+        .and(path("/rest/v1/transactions"))
+        .and(header("prefer", "count=exact"))
+        .and(query_param("direction", "eq.expense"))
+        .respond_with(ResponseTemplate::new(200).insert_header("content-range", "0-0/42"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let total = db
+        .count_transactions(&TransactionFilters {
+            direction: Some(TransactionDirection::Expense),
+            ..TransactionFilters::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(total, 42);
+}
+
+#[tokio::test]
+async fn test_gateway_pages_and_searches_accounts_server_side() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(query_param("name", "ilike.*chk\\_1*"))
+        .and(query_param("limit", "10"))
+        .and(query_param("offset", "20"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let accounts = db
+        .list_accounts(&ListAccountsInput {
+            search: Some(" chk_1 ".to_string()),
+            limit: Some(10),
+            offset: Some(20),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(accounts.len(), 1);
+}
+
+#[tokio::test]
+async fn test_gateway_lists_category_names_by_substring() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/categories"))
+        .and(query_param("name", "ilike.*gro*"))
+        .and(query_param("order", "name.asc"))
+        .and(query_param("limit", "5"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": "cat-1", "name": "Groceries", "kind": "expense" }
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let names = db.list_category_names(" gro ", 5).await.unwrap();
+
+    assert_eq!(names, vec!["Groceries"]);
+}
+
+#[tokio::test]
+async fn test_gateway_sends_configured_schema_profile() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(header("accept-profile", "finance"))
+        .and(header("content-profile", "finance"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/rpc/search_similar_categories"))
+        .and(header("content-profile", "finance"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |config| config.supabase_schema = "finance".to_string()).await;
+
+    db.list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    db.search_similar_categories(vec![0.1], None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_gateway_sends_correlation_id_of_the_call() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .and(header(CORRELATION_HEADER, "call-42"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(account_rows()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/accounts"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+
+    let db = gateway(&server, |_| {}).await;
+    let input = ListAccountsInput::default();
+    let accounts = correlation::scope("call-42".to_string(), db.list_accounts(&input))
+        .await
+        .unwrap();
+    assert_eq!(accounts.len(), 1);
+
+    // Outside of a tool call there is no id to send.
+    assert!(db.list_accounts(&input).await.unwrap().is_empty());
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests[1].headers.contains_key(CORRELATION_HEADER));
+}
+
+#[test]
+fn test_gateway_rejects_invalid_schema() {
+    let mut config = common::test_config();
+    config.supabase_schema = "finance; drop".to_string();
+
+    let err = SupabaseGateway::new(&config).err().expect("schema should be rejected");
+    assert!(err.to_string().contains("invalid SUPABASE_SCHEMA"));
+}
+
+#[test]
+fn test_auth_context_from_meta() {
+    let mut meta = Meta::new();
+    assert_eq!(AuthContext::from_meta(&meta), AuthContext::default());
+
+    meta.0
+        .insert(ACCESS_TOKEN_META_KEY.to_string(), json!("Bearer user-jwt"));
+    assert_eq!(AuthContext::from_meta(&meta), AuthContext::new("user-jwt"));
+
+    meta.0
+        .insert(ACCESS_TOKEN_META_KEY.to_string(), json!("  "));
+    assert_eq!(AuthContext::from_meta(&meta).access_token, None);
+}
+
+#[test]
+fn test_auth_context_current_outside_scope_is_empty() {
+    assert_eq!(AuthContext::current(), AuthContext::default());
+}
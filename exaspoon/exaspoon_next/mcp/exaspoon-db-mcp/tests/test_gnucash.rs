@@ -0,0 +1,613 @@
+//! Tests for reading GnuCash books and `import_gnucash`.
+
+use exaspoon_db_mcp::gnucash::{self, Role};
+use exaspoon_db_mcp::models::{CategoryKind, TransactionDirection};
+
+mod common;
+
+/// Writes a GnuCash account element.
+fn account(name: &str, id: &str, r#type: &str, parent: &str, placeholder: bool) -> String {
+    let slots = if placeholder {
+        "<act:slots><slot><slot:key>placeholder</slot:key><slot:value type=\"string\">true</slot:value></slot></act:slots>"
+    } else {
+        ""
+    };
+    format!(
+        "<gnc:account version=\"2.0.0\">
+  <act:name>{name}</act:name>
+  <act:id type=\"guid\">{id}</act:id>
+  <act:type>{type}</act:type>
+  <act:commodity><cmdty:space>CURRENCY</cmdty:space><cmdty:id>USD</cmdty:id></act:commodity>
+  {slots}
+  <act:parent type=\"guid\">{parent}</act:parent>
+</gnc:account>\n"
+    )
+}
+
+/// Writes a GnuCash transaction element of `splits`, each an id, an amount in
+/// cents, an account and a memo.
+fn transaction(
+    id: &str,
+    date: &str,
+    description: &str,
+    splits: &[(&str, i64, &str, &str)],
+) -> String {
+    let splits = splits
+        .iter()
+        .map(|(id, cents, account, memo)| {
+            format!(
+                "<trn:split>
+  <split:id type=\"guid\">{id}</split:id>
+  <split:memo>{memo}</split:memo>
+  <split:value>{cents}/100</split:value>
+  <split:quantity>{cents}/100</split:quantity>
+  <split:account type=\"guid\">{account}</split:account>
+</trn:split>\n"
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<gnc:transaction version=\"2.0.0\">
+  <trn:id type=\"guid\">{id}</trn:id>
+  <trn:currency><cmdty:space>CURRENCY</cmdty:space><cmdty:id>USD</cmdty:id></trn:currency>
+  <trn:date-posted><ts:date>{date}</ts:date></trn:date-posted>
+  <trn:description>{description}</trn:description>
+  <trn:splits>\n{splits}</trn:splits>
+</gnc:transaction>\n"
+    )
+}
+
+/// A book with an opening balance, a purchase split across two expense
+/// accounts, a salary, a transfer to savings, a transaction of zero amount
+/// and a scheduled transaction's template.
+fn book() -> String {
+    let accounts = [
+        "<gnc:account version=\"2.0.0\"><act:name>Root Account</act:name><act:id type=\"guid\">root</act:id><act:type>ROOT</act:type></gnc:account>\n".to_string(),
+        account("Assets", "assets", "ASSET", "root", true),
+        account("Current Assets", "current", "ASSET", "assets", true),
+        account("Checking", "checking", "BANK", "current", false),
+        account("Savings", "savings", "BANK", "assets", false),
+        account("Expenses", "expenses", "EXPENSE", "root", true),
+        account("Groceries", "groceries", "EXPENSE", "expenses", false),
+        account("Household", "household", "EXPENSE", "expenses", false),
+        account("Income", "income", "INCOME", "root", true),
+        account("Salary", "salary", "INCOME", "income", false),
+        account("Equity", "equity", "EQUITY", "root", true),
+        account("Opening Balances", "opening", "EQUITY", "equity", false),
+    ];
+    let transactions = [
+        transaction(
+            "t-open",
+            "2024-01-01 10:59:00 +0000",
+            "Opening Balance",
+            &[
+                ("s-open-1", 100000, "checking", ""),
+                ("s-open-2", -100000, "opening", ""),
+            ],
+        ),
+        transaction(
+            "t-shop",
+            "2024-01-05 18:00:00 +0100",
+            "Corner Store",
+            &[
+                ("s-shop-1", -6000, "checking", ""),
+                ("s-shop-2", 4500, "groceries", "Food"),
+                ("s-shop-3", 1500, "household", "Soap &amp; sponges"),
+            ],
+        ),
+        transaction(
+            "t-pay",
+            "2024-01-31 09:00:00 +0000",
+            "ACME Payroll",
+            &[
+                ("s-pay-1", 300000, "checking", ""),
+                ("s-pay-2", -300000, "salary", "January"),
+            ],
+        ),
+        transaction(
+            "t-save",
+            "2024-02-01 09:00:00 +0000",
+            "To savings",
+            &[
+                ("s-save-1", -50000, "checking", ""),
+                ("s-save-2", 50000, "savings", ""),
+            ],
+        ),
+        transaction(
+            "t-zero",
+            "2024-02-02 09:00:00 +0000",
+            "Voided",
+            &[
+                ("s-zero-1", 0, "checking", ""),
+                ("s-zero-2", 0, "groceries", ""),
+            ],
+        ),
+    ];
+    let template = format!(
+        "<gnc:template-transactions>\n{}{}</gnc:template-transactions>\n",
+        account("Template", "template", "BANK", "root", false),
+        transaction(
+            "t-template",
+            "2024-01-01 10:59:00 +0000",
+            "Rent",
+            &[
+                ("s-template-1", -80000, "template", ""),
+                ("s-template-2", 80000, "template", "")
+            ],
+        )
+    );
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\" ?>
+<gnc-v2 xmlns:gnc=\"http://www.gnucash.org/XML/gnc\" xmlns:act=\"http://www.gnucash.org/XML/act\">
+<gnc:count-data cd:type=\"book\">1</gnc:count-data>
+<gnc:book version=\"2.0.0\">
+<book:id type=\"guid\">book</book:id>
+{}{}{template}</gnc:book>
+</gnc-v2>\n",
+        accounts.concat(),
+        transactions.concat()
+    )
+}
+
+#[test]
+fn test_parse_reads_accounts_and_transactions_but_not_templates() {
+    let book = gnucash::parse(&book()).unwrap();
+    assert_eq!(book.accounts.len(), 12);
+    assert_eq!(book.transactions.len(), 5);
+
+    let checking = book.account("checking").unwrap();
+    assert_eq!(checking.r#type, "BANK");
+    assert_eq!(checking.commodity.as_deref(), Some("USD"));
+    assert!(!checking.placeholder);
+    assert!(book.account("assets").unwrap().placeholder);
+    assert_eq!(book.full_name("checking"), "Assets:Current Assets:Checking");
+    assert_eq!(book.imported_name("checking"), "Current Assets:Checking");
+    assert_eq!(book.imported_name("assets"), "Assets");
+    assert_eq!(book.role(checking), Role::Account);
+    assert_eq!(
+        book.role(book.account("salary").unwrap()),
+        Role::Category(CategoryKind::Income)
+    );
+    assert_eq!(book.role(book.account("opening").unwrap()), Role::Other);
+
+    let shop = &book.transactions[1];
+    assert_eq!(shop.posted_at, "2024-01-05T17:00:00Z");
+    assert_eq!(shop.splits[2].memo.as_deref(), Some("Soap & sponges"));
+    assert_eq!(shop.splits[0].value, -60.0);
+
+    let imported = book
+        .imported_accounts()
+        .iter()
+        .map(|account| account.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        imported,
+        ["checking", "savings", "groceries", "household", "salary"]
+    );
+}
+
+#[test]
+fn test_entries_split_purchases_by_category_and_record_transfers_per_account() {
+    let book = gnucash::parse(&book()).unwrap();
+    let entries = book.entries();
+    let summary = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.split.as_str(),
+                entry.account.as_str(),
+                entry.category.as_deref(),
+                entry.direction,
+                entry.amount,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            (
+                "s-open-1",
+                "checking",
+                None,
+                TransactionDirection::Transfer,
+                1000.0
+            ),
+            (
+                "s-shop-2",
+                "checking",
+                Some("groceries"),
+                TransactionDirection::Expense,
+                -45.0
+            ),
+            (
+                "s-shop-3",
+                "checking",
+                Some("household"),
+                TransactionDirection::Expense,
+                -15.0
+            ),
+            (
+                "s-pay-2",
+                "checking",
+                Some("salary"),
+                TransactionDirection::Income,
+                3000.0
+            ),
+            (
+                "s-save-1",
+                "checking",
+                None,
+                TransactionDirection::Transfer,
+                -500.0
+            ),
+            (
+                "s-save-2",
+                "savings",
+                None,
+                TransactionDirection::Transfer,
+                500.0
+            ),
+            (
+                "s-zero-2",
+                "checking",
+                Some("groceries"),
+                TransactionDirection::Expense,
+                0.0
+            ),
+        ]
+    );
+    assert_eq!(
+        entries[1].description.as_deref(),
+        Some("Corner Store - Food")
+    );
+    assert_eq!(
+        entries[3].description.as_deref(),
+        Some("ACME Payroll - January")
+    );
+
+    let input = entries[1].to_input("acct-1", "USD");
+    assert_eq!(input.amount, 45.0);
+    assert_eq!(input.occurred_at, "2024-01-05T17:00:00Z");
+    let raw_source = input.raw_source.unwrap();
+    assert_eq!(gnucash::split_id(&raw_source).as_deref(), Some("s-shop-2"));
+    assert_eq!(gnucash::split_id("not json"), None);
+}
+
+#[test]
+fn test_entries_convert_category_splits_into_the_account_currency() {
+    let book = book().replace(
+        "<split:quantity>-6000/100</split:quantity>",
+        "<split:quantity>-5400/100</split:quantity>",
+    );
+    let entries = gnucash::parse(&book).unwrap().entries();
+    assert!((entries[1].amount + 40.5).abs() < 1e-9);
+    assert!((entries[2].amount + 13.5).abs() < 1e-9);
+}
+
+/// [`book`] with `accounts` and `transactions` added.
+fn book_with(accounts: &[String], transactions: &[String]) -> String {
+    book().replace(
+        "<gnc:template-transactions>",
+        &format!(
+            "{}{}<gnc:template-transactions>",
+            accounts.concat(),
+            transactions.concat()
+        ),
+    )
+}
+
+#[test]
+fn test_entries_book_what_is_left_of_an_account_split_as_a_transfer() {
+    let book = book_with(
+        &[
+            account("Brokerage", "brokerage", "STOCK", "assets", false),
+            account("Fees", "fees", "EXPENSE", "expenses", false),
+        ],
+        &[
+            transaction(
+                "t-buy",
+                "2024-03-01 09:00:00 +0000",
+                "Buy shares",
+                &[
+                    ("s-buy-1", -101000, "checking", ""),
+                    ("s-buy-2", 100000, "brokerage", ""),
+                    ("s-buy-3", 1000, "fees", "Commission"),
+                ],
+            ),
+            transaction(
+                "t-move",
+                "2024-03-02 09:00:00 +0000",
+                "To savings",
+                &[
+                    ("s-move-1", -10000, "checking", ""),
+                    ("s-move-2", 9000, "savings", ""),
+                    ("s-move-3", 1000, "fees", "Wire fee"),
+                ],
+            ),
+        ],
+    );
+    let entries = gnucash::parse(&book).unwrap().entries();
+    let summary = entries
+        .iter()
+        .filter(|entry| entry.transaction == "t-buy" || entry.transaction == "t-move")
+        .map(|entry| {
+            (
+                entry.split.as_str(),
+                entry.account.as_str(),
+                entry.category.as_deref(),
+                entry.direction,
+                entry.amount,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            (
+                "s-buy-3",
+                "checking",
+                Some("fees"),
+                TransactionDirection::Expense,
+                -10.0
+            ),
+            (
+                "s-buy-1",
+                "checking",
+                None,
+                TransactionDirection::Transfer,
+                -1000.0
+            ),
+            (
+                "s-move-3",
+                "checking",
+                Some("fees"),
+                TransactionDirection::Expense,
+                -10.0
+            ),
+            (
+                "s-move-1",
+                "checking",
+                None,
+                TransactionDirection::Transfer,
+                -90.0
+            ),
+            (
+                "s-move-2",
+                "savings",
+                None,
+                TransactionDirection::Transfer,
+                90.0
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_reads_base64_books_and_rejects_compressed_ones() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let encoded = STANDARD.encode(book());
+    assert_eq!(gnucash::parse(&encoded).unwrap().transactions.len(), 5);
+
+    let compressed = STANDARD.encode([0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00]);
+    let err = gnucash::parse(&compressed).unwrap_err();
+    assert!(err.to_string().contains("compressed"), "{err:#}");
+
+    let err = gnucash::parse("<gnc-v2><gnc:count-data>1</gnc:count-data></gnc-v2>").unwrap_err();
+    assert!(err.to_string().contains("not a GnuCash book"), "{err:#}");
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_parse_reads_sqlite_books_without_scheduled_transactions() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let path = std::env::temp_dir().join(format!("gnucash-{}.gnucash", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "create table books (guid text, root_account_guid text, root_template_guid text);
+         create table commodities (guid text, namespace text, mnemonic text);
+         create table accounts (guid text, name text, account_type text, commodity_guid text,
+           parent_guid text, description text, placeholder integer, hidden integer);
+         create table transactions (guid text, currency_guid text, post_date text, description text);
+         create table splits (guid text, tx_guid text, account_guid text, memo text,
+           value_num integer, value_denom integer, quantity_num integer, quantity_denom integer);
+         insert into books values ('book', 'root', 'template-root');
+         insert into commodities values ('usd', 'CURRENCY', 'USD');
+         insert into accounts values
+           ('root', 'Root Account', 'ROOT', null, null, '', 0, 0),
+           ('template-root', 'Template Root', 'ROOT', null, null, '', 0, 0),
+           ('checking', 'Checking', 'BANK', 'usd', 'root', '', 0, 0),
+           ('dining', 'Dining', 'EXPENSE', 'usd', 'root', 'Eating out', 0, 0),
+           ('template', 'Template', 'BANK', 'usd', 'template-root', '', 0, 0);
+         insert into transactions values
+           ('t-lunch', 'usd', '2024-01-15 10:59:00', 'Deli'),
+           ('t-template', 'usd', '2024-01-16 10:59:00', 'Rent');
+         insert into splits values
+           ('s-lunch-1', 't-lunch', 'checking', '', -1250, 100, -1250, 100),
+           ('s-lunch-2', 't-lunch', 'dining', 'Sandwich', 1250, 100, 1250, 100),
+           ('s-template-1', 't-template', 'template', '', -800, 1, -800, 1),
+           ('s-template-2', 't-template', 'template', '', 800, 1, 800, 1);",
+    )
+    .unwrap();
+    drop(conn);
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let book = gnucash::parse(&STANDARD.encode(bytes)).unwrap();
+    assert_eq!(book.accounts.len(), 3);
+    assert_eq!(
+        book.account("dining").unwrap().description.as_deref(),
+        Some("Eating out")
+    );
+    assert_eq!(book.transactions.len(), 1);
+    let entries = book.entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].amount, -12.5);
+    assert_eq!(entries[0].posted_at, "2024-01-15T10:59:00Z");
+    assert_eq!(entries[0].description.as_deref(), Some("Deli - Sandwich"));
+}
+
+#[cfg(feature = "memory-backend")]
+mod tool {
+    use super::{book, common};
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{
+        CategoryKind, DryRun, ImportGnuCashInput, TransactionFilters, UpsertAccountInput,
+        UpsertCategoryInput,
+    };
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use rmcp::handler::server::wrapper::Parameters;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_import_gnucash_creates_accounts_and_categories_and_skips_splits_imported_before()
+    {
+        let database = Arc::new(MemoryDatabase::new());
+        let groceries = database
+            .upsert_category(
+                &UpsertCategoryInput {
+                    name: "Groceries".to_string(),
+                    kind: Some(CategoryKind::Expense),
+                    description: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        let server = ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        );
+        let input = ImportGnuCashInput { book: book() };
+        let filters = TransactionFilters::default();
+
+        let planned = server
+            .import_gnucash(Parameters(DryRun {
+                input: input.clone(),
+                dry_run: Some(true),
+            }))
+            .await
+            .unwrap();
+        let planned = planned.structured_content.unwrap();
+        assert_eq!(planned["transactions"].as_array().unwrap().len(), 6);
+        assert_eq!(planned["transactions"][0]["account_id"], "checking");
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 0);
+        assert!(database
+            .list_accounts(&Default::default())
+            .await
+            .unwrap()
+            .is_empty());
+
+        let result = server
+            .import_gnucash(Parameters(DryRun::from(input.clone())))
+            .await
+            .unwrap();
+        let payload = result.structured_content.unwrap();
+        let accounts = payload["accounts"].as_array().unwrap();
+        assert_eq!(accounts.len(), 5);
+        assert_eq!(
+            accounts[0]["gnucash_account"],
+            "Assets:Current Assets:Checking"
+        );
+        assert_eq!(accounts[0]["imported_as"], "account");
+        assert_eq!(accounts[0]["name"], "Current Assets:Checking");
+        assert_eq!(accounts[0]["created"], true);
+        assert_eq!(accounts[0]["transactions"], 5);
+        assert_eq!(accounts[1]["transactions"], 1);
+        assert_eq!(accounts[2]["imported_as"], "category");
+        assert_eq!(accounts[2]["id"], json!(groceries.id));
+        assert_eq!(accounts[2]["created"], false);
+        assert_eq!(accounts[4]["name"], "Salary");
+        assert_eq!(accounts[4]["created"], true);
+        assert_eq!(payload["skipped"][0]["reference"], "s-zero-2");
+        assert_eq!(payload["skipped"][0]["reason"], "zero_amount");
+
+        let transactions = payload["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 6);
+        let checking = accounts[0]["id"].as_str().unwrap();
+        assert_eq!(transactions[1]["account_id"], checking);
+        assert_eq!(transactions[1]["category_id"], json!(groceries.id));
+        assert_eq!(transactions[1]["amount"], 45.0);
+        assert_eq!(transactions[1]["direction"], "expense");
+        assert_eq!(transactions[3]["category_id"], accounts[4]["id"]);
+        assert_eq!(transactions[5]["account_id"], accounts[1]["id"]);
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 6);
+        let categories = database.list_categories().await.unwrap();
+        assert_eq!(categories.len(), 3);
+
+        let again = server
+            .import_gnucash(Parameters(DryRun::from(input)))
+            .await
+            .unwrap();
+        let again = again.structured_content.unwrap();
+        assert!(again["transactions"].as_array().unwrap().is_empty());
+        let skipped = again["skipped"].as_array().unwrap();
+        assert_eq!(skipped.len(), 7);
+        assert_eq!(
+            skipped
+                .iter()
+                .filter(|skipped| skipped["reason"] == "duplicate")
+                .count(),
+            6
+        );
+        assert_eq!(again["accounts"][0]["created"], false);
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 6);
+    }
+    #[tokio::test]
+    async fn test_import_gnucash_checks_the_currency_of_accounts() {
+        let database = Arc::new(MemoryDatabase::new());
+        let server = ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        );
+
+        let unknown = book().replace(
+            "<cmdty:id>USD</cmdty:id></act:commodity>",
+            "<cmdty:id>US Dollar</cmdty:id></act:commodity>",
+        );
+        let err = server
+            .import_gnucash(Parameters(DryRun::from(ImportGnuCashInput {
+                book: unknown,
+            })))
+            .await
+            .unwrap_err();
+        assert!(
+            err.message
+                .contains("\"US Dollar\", which is not a currency"),
+            "{}",
+            err.message
+        );
+
+        database
+            .upsert_account(&UpsertAccountInput {
+                name: "Current Assets:Checking".to_string(),
+                currency: "EUR".to_string(),
+                ..common::sample_account_input()
+            })
+            .await
+            .unwrap();
+        let err = server
+            .import_gnucash(Parameters(DryRun::from(ImportGnuCashInput {
+                book: book(),
+            })))
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("held in EUR"), "{}", err.message);
+        assert_eq!(
+            database.list_accounts(&Default::default()).await.unwrap()[0].currency,
+            "EUR",
+            "the namesake keeps its currency"
+        );
+        assert_eq!(
+            database
+                .count_transactions(&TransactionFilters::default())
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
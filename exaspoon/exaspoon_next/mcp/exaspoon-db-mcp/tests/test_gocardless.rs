@@ -0,0 +1,328 @@
+//! Tests for linking accounts to European banks and syncing them through
+//! GoCardless Bank Account Data.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::config::{GoCardlessConfig, HttpClientConfig};
+use exaspoon_db_mcp::gocardless::{self, BookedTransaction, GoCardlessClient};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    DryRun, LinkOpenBankingAccountInput, SyncOpenBankingInput, TransactionDirection,
+    TransactionFilters,
+};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+const BANK_ACCOUNT: &str = "7e944232-bda9-40bc-b784-660c7ab5fe78";
+
+fn booked(id: &str, amount: &str, date: &str) -> Value {
+    json!({
+        "transactionId": id,
+        "bookingDate": date,
+        "valueDate": date,
+        "transactionAmount": { "amount": amount, "currency": "EUR" },
+        "creditorName": "Bäckerei Müller",
+        "debtorName": "Erika Mustermann",
+        "remittanceInformationUnstructured": "Kartenzahlung",
+    })
+}
+
+fn transactions(booked: Vec<Value>, pending: Vec<Value>) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "transactions": { "booked": booked, "pending": pending },
+    }))
+}
+
+async fn mount_token_and_requisition(gocardless: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/token/new/"))
+        .and(body_partial_json(
+            json!({ "secret_id": "id-1", "secret_key": "key-1" }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access": "access-1",
+            "access_expires": 86400,
+            "refresh": "refresh-1",
+            "refresh_expires": 2592000,
+        })))
+        .expect(1)
+        .mount(gocardless)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/requisitions/req-1/"))
+        .and(header("authorization", "Bearer access-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "req-1",
+            "status": "LN",
+            "institution_id": "SANDBOXFINANCE_SFIN0000",
+            "accounts": [BANK_ACCOUNT],
+            "link": "https://ob.gocardless.com/psd2/start/req-1",
+        })))
+        .mount(gocardless)
+        .await;
+}
+
+async fn server(gocardless: &MockServer) -> (Arc<MemoryDatabase>, ExaspoonDbServer, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let client = GoCardlessClient::new(
+        &GoCardlessConfig {
+            secret_id: "id-1".to_string(),
+            secret_key: "key-1".to_string(),
+            base_url: gocardless.uri(),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_gocardless(client);
+    (database, server, account.id)
+}
+
+fn link_input(account_id: &str) -> LinkOpenBankingAccountInput {
+    LinkOpenBankingAccountInput {
+        account_id: account_id.to_string(),
+        requisition_id: "req-1".to_string(),
+        openbanking_account_id: None,
+    }
+}
+
+fn sync_input(account_id: &str) -> SyncOpenBankingInput {
+    SyncOpenBankingInput {
+        account_id: account_id.to_string(),
+    }
+}
+
+#[test]
+fn test_booked_transactions_map_to_transaction_inputs() {
+    let mut raw = booked("tx-1", "-4.20", "2024-03-01");
+    let mut transaction: BookedTransaction = serde_json::from_value(raw.clone()).unwrap();
+    transaction.raw = raw.clone();
+    let input = transaction.to_input("acct-1").unwrap();
+    assert_eq!(input.amount, 4.2);
+    assert_eq!(input.currency, "EUR");
+    assert_eq!(input.direction, TransactionDirection::Expense);
+    assert_eq!(input.occurred_at, "2024-03-01T00:00:00Z");
+    assert_eq!(input.description.as_deref(), Some("Bäckerei Müller"));
+    let raw_source = input.raw_source.unwrap();
+    assert_eq!(gocardless::entry_id(&raw_source).as_deref(), Some("tx-1"));
+    assert_eq!(
+        gocardless::entry_id(r#"{"transaction_id":"plaid-1"}"#),
+        None
+    );
+
+    raw["transactionAmount"]["amount"] = json!("1500.00");
+    raw["bookingDateTime"] = json!("2024-03-01T09:30:00+01:00");
+    raw.as_object_mut().unwrap().remove("transactionId");
+    raw.as_object_mut().unwrap().remove("debtorName");
+    let mut transaction: BookedTransaction = serde_json::from_value(raw.clone()).unwrap();
+    transaction.raw = raw;
+    let input = transaction.to_input("acct-1").unwrap();
+    assert_eq!(input.direction, TransactionDirection::Income);
+    assert_eq!(input.occurred_at, "2024-03-01T08:30:00Z");
+    assert_eq!(input.description.as_deref(), Some("Kartenzahlung"));
+    // Without ids, the whole entry tells it apart.
+    let raw_source = input.raw_source.unwrap();
+    assert_eq!(gocardless::entry_id(&raw_source), Some(raw_source.clone()));
+    assert_eq!(transaction.id(), "2024-03-01 1500.00 EUR");
+}
+
+#[tokio::test]
+async fn test_sync_records_booked_transactions_once() {
+    let gocardless = MockServer::start().await;
+    mount_token_and_requisition(&gocardless).await;
+    // The second sync reads again from three days before the first one's
+    // latest booking date.
+    Mock::given(method("GET"))
+        .and(path(format!("/accounts/{BANK_ACCOUNT}/transactions/")))
+        .and(query_param("date_from", "2024-03-02"))
+        .respond_with(transactions(
+            vec![
+                booked("tx-2", "1500.00", "2024-03-05"),
+                booked("tx-3", "-9.99", "2024-03-06"),
+            ],
+            vec![],
+        ))
+        .mount(&gocardless)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/accounts/{BANK_ACCOUNT}/transactions/")))
+        .respond_with(transactions(
+            vec![
+                booked("tx-1", "-4.20", "2024-03-01"),
+                booked("tx-2", "1500.00", "2024-03-05"),
+                booked("tx-zero", "0.00", "2024-03-05"),
+            ],
+            vec![booked("", "-12.00", "2024-03-06")],
+        ))
+        .with_priority(10)
+        .mount(&gocardless)
+        .await;
+    let (database, server, account_id) = server(&gocardless).await;
+
+    let linked = server
+        .link_openbanking_account(Parameters(DryRun::from(link_input(&account_id))))
+        .await
+        .unwrap();
+    let linked = linked.structured_content.unwrap();
+    assert_eq!(linked["link"]["openbanking_account_id"], BANK_ACCOUNT);
+    assert_eq!(linked["replaced"], false);
+    let metadata = database.account_metadata(&account_id).await.unwrap();
+    assert_eq!(
+        metadata["openbanking"]["institution_id"],
+        "SANDBOXFINANCE_SFIN0000"
+    );
+
+    let synced = server
+        .sync_openbanking(Parameters(DryRun::from(sync_input(&account_id))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    assert_eq!(recorded.len(), 2);
+    assert_eq!(recorded[0]["direction"], "expense");
+    assert_eq!(recorded[1]["direction"], "income");
+    assert_eq!(recorded[1]["description"], "Erika Mustermann");
+    assert_eq!(
+        synced["skipped"],
+        json!([{ "transaction_id": "tx-zero", "reason": "zero_amount" }])
+    );
+    assert_eq!(synced["pending"], 1);
+    assert_eq!(synced["synced_through"], "2024-03-05");
+
+    let again = server
+        .sync_openbanking(Parameters(DryRun::from(sync_input(&account_id))))
+        .await
+        .unwrap();
+    let again = again.structured_content.unwrap();
+    assert_eq!(again["transactions"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        again["skipped"],
+        json!([{ "transaction_id": "tx-2", "reason": "duplicate" }])
+    );
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 3);
+    let metadata = database.account_metadata(&account_id).await.unwrap();
+    assert_eq!(metadata["openbanking"]["synced_through"], "2024-03-06");
+}
+
+#[tokio::test]
+async fn test_link_needs_a_linked_requisition_and_one_account() {
+    let gocardless = MockServer::start().await;
+    mount_token_and_requisition(&gocardless).await;
+    Mock::given(method("GET"))
+        .and(path("/requisitions/req-2/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "req-2",
+            "status": "GA",
+            "institution_id": "SANDBOXFINANCE_SFIN0000",
+            "accounts": [],
+        })))
+        .mount(&gocardless)
+        .await;
+    let (database, server, account_id) = server(&gocardless).await;
+
+    let pending = LinkOpenBankingAccountInput {
+        requisition_id: "req-2".to_string(),
+        ..link_input(&account_id)
+    };
+    let err = server
+        .link_openbanking_account(Parameters(DryRun::from(pending)))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("not linked"), "{}", err.message);
+
+    let elsewhere = LinkOpenBankingAccountInput {
+        openbanking_account_id: Some("another-account".to_string()),
+        ..link_input(&account_id)
+    };
+    let err = server
+        .link_openbanking_account(Parameters(DryRun::from(elsewhere)))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains(BANK_ACCOUNT), "{}", err.message);
+    assert!(database
+        .account_metadata(&account_id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let err = server
+        .sync_openbanking(Parameters(DryRun::from(sync_input(&account_id))))
+        .await
+        .unwrap_err();
+    assert!(
+        err.message.contains("link_openbanking_account"),
+        "{}",
+        err.message
+    );
+}
+
+#[tokio::test]
+async fn test_gocardless_errors_carry_their_status() {
+    let gocardless = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token/new/"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "summary": "Authentication failed",
+            "detail": "No active account found with the given credentials",
+            "status_code": 401,
+        })))
+        .mount(&gocardless)
+        .await;
+    let (_, server, account_id) = server(&gocardless).await;
+
+    let err = server
+        .link_openbanking_account(Parameters(DryRun::from(link_input(&account_id))))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["gocardless"]["status_code"], 401);
+    assert_eq!(data["gocardless"]["summary"], "Authentication failed");
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_keeps_account_metadata() {
+    use exaspoon_db_mcp::sqlite::SqliteDatabase;
+
+    let database = SqliteDatabase::open_in_memory().unwrap();
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    assert!(database
+        .account_metadata(&account.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    database
+        .set_account_metadata(
+            &account.id,
+            "openbanking",
+            json!({ "requisition_id": "req-1" }),
+        )
+        .await
+        .unwrap();
+    database
+        .set_account_metadata(&account.id, "note", json!("joint account"))
+        .await
+        .unwrap();
+    let metadata = database.account_metadata(&account.id).await.unwrap();
+    assert_eq!(metadata["openbanking"]["requisition_id"], "req-1");
+    assert_eq!(metadata["note"], "joint account");
+    assert!(database.account_metadata("elsewhere").await.is_err());
+}
@@ -0,0 +1,101 @@
+//! Tests for the health endpoints served next to the transports.
+#![cfg(all(feature = "http", feature = "memory-backend"))]
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use exaspoon_db_mcp::embedding::Embedder;
+use exaspoon_db_mcp::health::{self, HEALTHZ_PATH, READYZ_PATH};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+mod common;
+
+/// Embedder whose provider is down.
+struct UnreachableEmbedder;
+
+#[async_trait]
+impl Embedder for UnreachableEmbedder {
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+        Err(anyhow!("embedding request failed"))
+    }
+
+    async fn maybe_embed(&self, _text: Option<&str>) -> Result<Option<Vec<f32>>> {
+        Err(anyhow!("embedding request failed"))
+    }
+}
+
+/// Serves the health endpoints of `server`, returning their base URL.
+async fn serve(server: ExaspoonDbServer) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(health::serve(server, listener, Duration::from_secs(60)));
+    url
+}
+
+async fn get(url: &str) -> (u16, Value) {
+    let response = reqwest::get(url).await.unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+/// Polls `url` until it stops answering as the first probe is still running.
+async fn after_first_probe(url: &str) -> (u16, Value) {
+    for _ in 0..100 {
+        let (status, body) = get(url).await;
+        if body["status"] != "starting" {
+            return (status, body);
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("no probe finished");
+}
+
+#[tokio::test]
+async fn test_health_endpoints_pass_while_dependencies_answer() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    );
+    let url = serve(server.clone()).await;
+
+    let (status, body) = after_first_probe(&format!("{url}{READYZ_PATH}")).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["status"], "ok");
+    let (status, _) = get(&format!("{url}{HEALTHZ_PATH}")).await;
+    assert_eq!(status, 200);
+
+    // A server shutting down stays alive but takes no new traffic.
+    server.shut_down(Duration::ZERO).await;
+    let (status, body) = get(&format!("{url}{READYZ_PATH}")).await;
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "draining");
+    let (status, _) = get(&format!("{url}{HEALTHZ_PATH}")).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_health_endpoints_fail_when_a_dependency_is_down() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(UnreachableEmbedder),
+    );
+    let url = serve(server).await;
+
+    let (status, body) = after_first_probe(&format!("{url}{HEALTHZ_PATH}")).await;
+    assert_eq!(status, 503, "{body}");
+    assert_eq!(body["status"], "degraded");
+    let embedding = body["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|check| check["name"] == "embedding")
+        .unwrap();
+    assert_eq!(embedding["ok"], false);
+
+    let (status, _) = get(&format!("{url}{READYZ_PATH}")).await;
+    assert_eq!(status, 503);
+}
@@ -0,0 +1,214 @@
+//! Tests for the streamable HTTP transport, driven with plain HTTP requests.
+#![cfg(all(feature = "http", feature = "memory-backend"))]
+
+use exaspoon_db_mcp::http::{self, MCP_PATH};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::origin::AllowedHosts;
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+struct HttpClient {
+    http: reqwest::Client,
+    url: String,
+    session_id: String,
+}
+
+impl HttpClient {
+    async fn connect(url: &str) -> Self {
+        let http = reqwest::Client::new();
+        let response = http
+            .post(url)
+            .header("accept", "application/json, text/event-stream")
+            .json(&common::initialize_request(0))
+            .send()
+            .await
+            .unwrap();
+        let session_id = response.headers()[SESSION_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let initialized = last_message(&response.text().await.unwrap());
+        assert_eq!(
+            initialized["result"]["protocolVersion"],
+            common::PROTOCOL_VERSION
+        );
+        let client = Self {
+            http,
+            url: url.to_string(),
+            session_id,
+        };
+        let accepted = client.post(common::initialized_notification()).await;
+        assert_eq!(accepted.status(), reqwest::StatusCode::ACCEPTED);
+        client
+    }
+
+    async fn post(&self, message: Value) -> reqwest::Response {
+        self.http
+            .post(&self.url)
+            .header("accept", "application/json, text/event-stream")
+            .header(SESSION_HEADER, &self.session_id)
+            .json(&message)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    async fn call(&self, id: u64, name: &str, arguments: Value) -> Value {
+        let response = self.post(common::tool_call(id, name, arguments)).await;
+        last_message(&response.text().await.unwrap())
+    }
+}
+
+/// The last JSON-RPC message of a server-sent event stream.
+fn last_message(events: &str) -> Value {
+    let data = events
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(str::trim)
+        .rfind(|data| !data.is_empty())
+        .unwrap_or_else(|| panic!("no message in {events:?}"));
+    serde_json::from_str(data).unwrap()
+}
+
+#[tokio::test]
+async fn test_http_clients_get_sessions_of_their_own() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}{}", listener.local_addr().unwrap(), MCP_PATH);
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(http::serve(
+        server,
+        listener,
+        None,
+        AllowedHosts::default(),
+        stop.clone(),
+    ));
+
+    let first = HttpClient::connect(&url).await;
+    let second = HttpClient::connect(&url).await;
+    assert_ne!(first.session_id, second.session_id);
+
+    let pong = first.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    // Defaults one client sets do not leak into another's session.
+    let set = first
+        .call(2, "set_session_defaults", json!({ "currency": "EUR" }))
+        .await;
+    assert_eq!(
+        set["result"]["structuredContent"]["defaults"]["currency"],
+        "EUR"
+    );
+    let other = second.call(1, "set_session_defaults", json!({})).await;
+    assert_eq!(other["result"]["structuredContent"]["defaults"], json!({}));
+    let kept = first.call(3, "set_session_defaults", json!({})).await;
+    assert_eq!(
+        kept["result"]["structuredContent"]["defaults"]["currency"],
+        "EUR"
+    );
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_refuses_foreign_hosts_and_origins() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let url = format!("http://127.0.0.1:{port}{MCP_PATH}");
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(http::serve(
+        server,
+        listener,
+        None,
+        AllowedHosts::default(),
+        stop.clone(),
+    ));
+
+    let http = reqwest::Client::new();
+    let initialize = |host: String, origin: Option<&str>| {
+        let mut request = http
+            .post(&url)
+            .header("accept", "application/json, text/event-stream")
+            .header("host", host)
+            .json(&common::initialize_request(0));
+        if let Some(origin) = origin {
+            request = request.header("origin", origin);
+        }
+        request.send()
+    };
+
+    // A page on another site that resolves its own name to this machine.
+    let rebound = initialize(format!("evil.example:{port}"), None)
+        .await
+        .unwrap();
+    assert_eq!(rebound.status(), reqwest::StatusCode::FORBIDDEN);
+    let cross_site = initialize(format!("localhost:{port}"), Some("https://evil.example"))
+        .await
+        .unwrap();
+    assert_eq!(cross_site.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let local = initialize(format!("localhost:{port}"), Some("http://localhost:3000"))
+        .await
+        .unwrap();
+    assert_eq!(local.status(), reqwest::StatusCode::OK);
+    // Clients other than browsers send no origin.
+    let _ = HttpClient::connect(&url).await;
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_sessions_beyond_the_limit_are_asked_to_retry() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    )
+    .with_max_sessions(1);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}{}", listener.local_addr().unwrap(), MCP_PATH);
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(http::serve(
+        server,
+        listener,
+        None,
+        AllowedHosts::default(),
+        stop.clone(),
+    ));
+
+    let first = HttpClient::connect(&url).await;
+    let refused = reqwest::Client::new()
+        .post(&url)
+        .header("accept", "application/json, text/event-stream")
+        .json(&common::initialize_request(0))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        refused.headers()["retry-after"],
+        http::SESSION_RETRY_AFTER_SECS.to_string()
+    );
+
+    // The open session is still served.
+    let pong = first.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
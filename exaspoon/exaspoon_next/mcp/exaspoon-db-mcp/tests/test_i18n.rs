@@ -0,0 +1,88 @@
+//! Tests for message locales.
+
+use exaspoon_db_mcp::i18n::{self, Locale, Message};
+use exaspoon_db_mcp::rate_limit::RateLimited;
+use std::time::Duration;
+
+#[test]
+fn test_locales_parse_by_language() {
+    assert_eq!("ru".parse::<Locale>().unwrap(), Locale::Ru);
+    assert_eq!("ru-RU".parse::<Locale>().unwrap(), Locale::Ru);
+    assert_eq!("ru_RU.UTF-8".parse::<Locale>().unwrap(), Locale::Ru);
+    assert_eq!(" EN-gb ".parse::<Locale>().unwrap(), Locale::En);
+    assert!("de".parse::<Locale>().is_err());
+    assert!("".parse::<Locale>().is_err());
+}
+
+#[tokio::test]
+async fn test_messages_render_in_the_current_locale() {
+    let message = Message::Failed {
+        action: "insert transaction",
+    };
+    assert_eq!(message.text(), "Failed to insert transaction");
+    let russian = i18n::scope(Locale::Ru, async { message.text() }).await;
+    assert_eq!(
+        russian,
+        "Не удалось выполнить операцию «insert transaction»"
+    );
+
+    let limited = RateLimited {
+        budget: "embedding_calls",
+        limit: 30,
+        window: Duration::from_secs(60),
+        retry_in: Duration::from_millis(1500),
+    };
+    assert_eq!(
+        Message::RateLimited(&limited).localize(Locale::En),
+        limited.to_string()
+    );
+    assert_eq!(
+        Message::RateLimited(&limited).localize(Locale::Ru),
+        "достигнут лимит в 30 вызовов инструментов с эмбеддингами за 60 с; повторите через 2 с"
+    );
+
+    let question = Message::WhichFields {
+        fields: &["account_id", "currency"],
+        direction: "expense",
+        amount: "12.5",
+        occurred_at: "2024-01-15",
+    };
+    assert_eq!(
+        question.localize(Locale::En),
+        "Which account_id and currency should the expense of 12.5 on 2024-01-15 use?"
+    );
+    assert_eq!(
+        question.localize(Locale::Ru),
+        "Укажите account_id и currency для расхода на 12.5 от 2024-01-15."
+    );
+}
+
+#[test]
+fn test_amounts_are_written_the_locale_way() {
+    assert_eq!(Locale::En.format_amount(-1234.5, "USD"), "-$1,234.50");
+    assert_eq!(
+        Locale::Ru.format_amount(-1234.5, "USD"),
+        "-1\u{a0}234,50\u{a0}$"
+    );
+    assert_eq!(
+        Locale::En.format_amount(1_234_567.0, "RUB"),
+        "₽1,234,567.00"
+    );
+    assert_eq!(
+        Locale::Ru.format_amount(1_234_567.0, "RUB"),
+        "1\u{a0}234\u{a0}567,00\u{a0}₽"
+    );
+    assert_eq!(Locale::En.format_amount(1500.0, "JPY"), "¥1,500");
+    assert_eq!(Locale::En.format_amount(-0.001, "EUR"), "€0.00");
+
+    // Currencies without a symbol keep their code and the decimals they need.
+    assert_eq!(Locale::En.format_amount(0.00125, "BTC"), "0.00125 BTC");
+    assert_eq!(Locale::Ru.format_amount(12.0, "CHF"), "12,00\u{a0}CHF");
+}
+
+#[tokio::test]
+async fn test_amounts_follow_the_current_locale() {
+    assert_eq!(i18n::format_amount(42.0, "EUR"), "€42.00");
+    let russian = i18n::scope(Locale::Ru, async { i18n::format_amount(42.0, "EUR") }).await;
+    assert_eq!(russian, "42,00\u{a0}€");
+}
@@ -0,0 +1,114 @@
+//! Tests for importing transactions given as JSON.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{DryRun, ImportJsonInput, TransactionFilters};
+use exaspoon_db_mcp::server::{ExaspoonDbServer, MAX_JSON_RECORDS};
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::json;
+use std::sync::Arc;
+
+mod common;
+
+#[tokio::test]
+async fn test_import_json_inserts_valid_records_and_reports_the_rest() {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    );
+    let record = |amount: serde_json::Value, direction: &str| {
+        json!({
+            "account_id": account.id,
+            "amount": amount,
+            "currency": "USD",
+            "direction": direction,
+            "occurred_at": "2024-01-05T12:00:00Z",
+            "description": "Coffee",
+        })
+    };
+    let mut no_currency = record(json!(3), "expense");
+    no_currency["currency"] = json!("");
+    let input = ImportJsonInput {
+        records: vec![
+            record(json!(12.5), "expense"),
+            record(json!("12.50"), "expense"),
+            record(json!(-4), "expense"),
+            record(json!(3000), "income"),
+            record(json!(8), "refund"),
+            no_currency,
+        ],
+    };
+
+    let planned = server
+        .import_json(Parameters(DryRun {
+            input: input.clone(),
+            dry_run: Some(true),
+        }))
+        .await
+        .unwrap();
+    let planned = planned.structured_content.unwrap();
+    assert_eq!(planned["transactions"].as_array().unwrap().len(), 2);
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 0);
+
+    let result = server
+        .import_json(Parameters(DryRun::from(input)))
+        .await
+        .unwrap();
+    let payload = result.structured_content.unwrap();
+    let transactions = payload["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0]["amount"], 12.5);
+    assert_eq!(transactions[1]["direction"], "income");
+    assert_eq!(
+        payload["rejected"],
+        json!([
+            {
+                "index": 1,
+                "field": "records[1].amount",
+                "reason": "records[1].amount must be a number",
+            },
+            {
+                "index": 2,
+                "field": "records[2].amount",
+                "reason": "records[2].amount must be a positive number",
+            },
+            {
+                "index": 4,
+                "field": "records[4].direction",
+                "reason": "records[4].direction must be one of income, expense, transfer",
+            },
+            {
+                "index": 5,
+                "field": "records[5].currency",
+                "reason": "records[5].currency is required",
+            },
+        ])
+    );
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 2);
+
+    let empty = ImportJsonInput {
+        records: Vec::new(),
+    };
+    let err = server
+        .import_json(Parameters(DryRun::from(empty)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.message, "records must contain between 1 and 5000 items");
+
+    let too_many = ImportJsonInput {
+        records: vec![record(json!(1), "expense"); MAX_JSON_RECORDS + 1],
+    };
+    let err = server
+        .import_json(Parameters(DryRun::from(too_many)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.message, "records must contain between 1 and 5000 items");
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 2);
+}
@@ -0,0 +1,323 @@
+//! Tests for plain-text accounting journals and `export_ledger`.
+
+use exaspoon_db_mcp::ledger;
+use exaspoon_db_mcp::models::{Account, Category, LedgerFormat, Transaction, TransactionDirection};
+
+mod common;
+
+fn transaction(
+    id: &str,
+    account_id: &str,
+    direction: TransactionDirection,
+    amount: f64,
+    currency: &str,
+    occurred_at: &str,
+) -> Transaction {
+    Transaction {
+        account_id: account_id.to_string(),
+        direction,
+        amount,
+        currency: currency.to_string(),
+        occurred_at: occurred_at.to_string(),
+        ..common::sample_transaction(id)
+    }
+}
+
+/// Lunch, a salary into a second account of the same name, a transfer of
+/// bitcoin and groceries in a deleted account under a deleted category,
+/// oldest first.
+fn books() -> (Vec<Account>, Vec<Category>, Vec<Transaction>) {
+    let accounts = vec![
+        Account {
+            created_at: Some("2024-01-20T08:00:00Z".to_string()),
+            ..common::sample_account("acct-1")
+        },
+        common::sample_account("acct-2"),
+    ];
+    let categories = vec![Category {
+        name: "eating  out".to_string(),
+        ..common::sample_category("cat-1")
+    }];
+    let transactions = vec![
+        Transaction {
+            description: Some("Lunch \"deli\"".to_string()),
+            category_id: Some("cat-1".to_string()),
+            ..transaction(
+                "t1",
+                "acct-1",
+                TransactionDirection::Expense,
+                12.5,
+                "USD",
+                "2024-01-15T12:30:00Z",
+            )
+        },
+        Transaction {
+            description: Some("Salary".to_string()),
+            ..transaction(
+                "t2",
+                "acct-2",
+                TransactionDirection::Income,
+                1000.0,
+                "USD",
+                "2024-01-31T09:00:00Z",
+            )
+        },
+        Transaction {
+            description: None,
+            ..transaction(
+                "t3",
+                "acct-1",
+                TransactionDirection::Transfer,
+                0.015,
+                "btc",
+                "2024-02-01T10:00:00Z",
+            )
+        },
+        Transaction {
+            description: Some("Weekly\n  groceries".to_string()),
+            category_id: Some("cat-x".to_string()),
+            ..transaction(
+                "t4",
+                "gone",
+                TransactionDirection::Expense,
+                5.0,
+                "EUR",
+                "2024-02-02T18:00:00Z",
+            )
+        },
+    ];
+    (accounts, categories, transactions)
+}
+
+#[test]
+fn test_beancount_journal_balances_every_transaction() {
+    let (accounts, categories, transactions) = books();
+    let journal = ledger::write(
+        LedgerFormat::Beancount,
+        &accounts,
+        &categories,
+        &transactions,
+    );
+    assert_eq!(
+        journal,
+        r#"1970-01-01 open Assets:Checking-Acct-2
+1970-01-01 open Expenses:Eating-Out
+2024-01-15 open Assets:Checking
+2024-01-31 open Income:Uncategorized
+2024-02-01 open Equity:Transfers
+2024-02-02 open Assets:Gone
+2024-02-02 open Expenses:Cat-X
+
+2024-01-15 * "Lunch \"deli\""
+  id: "t1"
+  Expenses:Eating-Out  12.50 USD
+  Assets:Checking  -12.50 USD
+
+2024-01-31 * "Salary"
+  id: "t2"
+  Assets:Checking-Acct-2  1000.00 USD
+  Income:Uncategorized  -1000.00 USD
+
+2024-02-01 * ""
+  id: "t3"
+  Equity:Transfers  0.015 BTC
+  Assets:Checking  -0.015 BTC
+
+2024-02-02 * "Weekly groceries"
+  id: "t4"
+  Expenses:Cat-X  5.00 EUR
+  Assets:Gone  -5.00 EUR
+"#
+    );
+}
+
+#[test]
+fn test_ledger_journal_declares_every_account() {
+    let (accounts, categories, transactions) = books();
+    let journal = ledger::write(LedgerFormat::Ledger, &accounts, &categories, &transactions);
+    assert!(journal.starts_with("account Assets:Checking-Acct-2\naccount Expenses:Eating-Out\n"));
+    assert!(journal.contains(
+        "\n2024-02-01 * (no description)\n    ; id: t3\n    Equity:Transfers  0.015 BTC\n    Assets:Checking  -0.015 BTC\n"
+    ));
+    assert!(journal.contains("\n2024-01-15 * Lunch \"deli\"\n"));
+    assert!(!journal.contains(" open "));
+}
+
+#[test]
+fn test_transfers_come_in_as_their_import_recorded() {
+    let transfer = |id: &str, raw_source: &str| Transaction {
+        raw_source: Some(raw_source.to_string()),
+        ..transaction(
+            id,
+            "acct-1",
+            TransactionDirection::Transfer,
+            100.0,
+            "USD",
+            "2024-03-01T00:00:00Z",
+        )
+    };
+    let transactions = vec![
+        transfer(
+            "ofx-in",
+            "<STMTTRN><TRNTYPE>XFER<DTPOSTED>20240301<TRNAMT>100.00<FITID>F1</STMTTRN>",
+        ),
+        transfer(
+            "ofx-out",
+            "<STMTTRN><TRNTYPE>XFER<DTPOSTED>20240301<TRNAMT>-100.00<FITID>F2</STMTTRN>",
+        ),
+        transfer("qif-in", "D03/01/2024\nT100.00\nL[Savings]"),
+        transfer("plaid-in", r#"{"transaction_id":"p1","amount":-100.0}"#),
+        transfer("plaid-out", r#"{"transaction_id":"p2","amount":100.0}"#),
+        transfer("ynab-in", r#"{"ynab_id":"y1","amount":100.0}"#),
+        transfer(
+            "gnucash-in",
+            r#"{"gnucash_split":"s1","gnucash_transaction":"g1","gnucash_amount":100.0}"#,
+        ),
+        Transaction {
+            raw_source: None,
+            ..transfer("by-hand", "")
+        },
+    ];
+    let journal = ledger::write(LedgerFormat::Beancount, &[], &[], &transactions);
+
+    let incoming = "  Assets:Acct-1  100.00 USD\n  Equity:Transfers  -100.00 USD\n";
+    let outgoing = "  Equity:Transfers  100.00 USD\n  Assets:Acct-1  -100.00 USD\n";
+    for (id, posting) in [
+        ("ofx-in", incoming),
+        ("ofx-out", outgoing),
+        ("qif-in", incoming),
+        ("plaid-in", incoming),
+        ("plaid-out", outgoing),
+        ("ynab-in", incoming),
+        ("gnucash-in", incoming),
+        ("by-hand", outgoing),
+    ] {
+        assert!(
+            journal.contains(&format!("  id: \"{id}\"\n{posting}")),
+            "{id}: {journal}"
+        );
+    }
+}
+
+#[test]
+fn test_commodities_are_valid_beancount_currencies() {
+    let transactions = [
+        "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+        "1inch",
+        "x",
+        "$",
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, currency)| {
+        transaction(
+            &format!("t{index}"),
+            "acct-1",
+            TransactionDirection::Expense,
+            1.0,
+            currency,
+            "2024-03-01T00:00:00Z",
+        )
+    })
+    .collect::<Vec<_>>();
+
+    let journal = ledger::write(LedgerFormat::Beancount, &[], &[], &transactions);
+    assert!(
+        journal.contains(" 1.00 EPJFWDD5AUFQSSQEM2QN1XZY\n"),
+        "{journal}"
+    );
+    assert!(journal.contains(" 1.00 X1INCH\n"), "{journal}");
+    assert!(journal.contains(" 1.00 XX\n"), "{journal}");
+    assert!(journal.contains(" 1.00 UNKNOWN\n"), "{journal}");
+
+    let journal = ledger::write(LedgerFormat::Ledger, &[], &[], &transactions);
+    assert!(journal.contains(" 1.00 \"X1INCH\"\n"), "{journal}");
+    assert!(journal.contains(" 1.00 XX\n"), "{journal}");
+}
+
+#[cfg(feature = "memory-backend")]
+mod tool {
+    use super::common;
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{
+        CreateTransactionInput, ExportLedgerInput, LedgerFormat, TransactionDirection,
+        TransactionFilters,
+    };
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use rmcp::handler::server::wrapper::Parameters;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_export_ledger_writes_the_filtered_transactions_oldest_first() {
+        let database = Arc::new(MemoryDatabase::new());
+        let account = database
+            .upsert_account(&common::sample_account_input())
+            .await
+            .unwrap();
+        let groceries = database
+            .upsert_category(&common::sample_category_input(), None)
+            .await
+            .unwrap();
+        let expense = |occurred_at: &str, description: &str| {
+            (
+                CreateTransactionInput {
+                    account_id: account.id.clone(),
+                    occurred_at: occurred_at.to_string(),
+                    description: Some(description.to_string()),
+                    ..common::sample_transaction_input()
+                },
+                None,
+            )
+        };
+        let inserted = database
+            .insert_transactions(&[
+                expense("2024-01-05T10:00:00Z", "Bread"),
+                expense("2024-01-20T10:00:00Z", "Cheese"),
+                (
+                    CreateTransactionInput {
+                        direction: TransactionDirection::Income,
+                        ..expense("2024-01-25T10:00:00Z", "Refund").0
+                    },
+                    None,
+                ),
+            ])
+            .await
+            .unwrap();
+        database
+            .categorize_transaction(&inserted[1].id, Some(&groceries.id))
+            .await
+            .unwrap();
+        let server = ExaspoonDbServer::new(
+            database,
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        );
+
+        let result = server
+            .export_ledger(Parameters(ExportLedgerInput {
+                format: LedgerFormat::Ledger,
+                filters: TransactionFilters {
+                    direction: Some(TransactionDirection::Expense),
+                    ..TransactionFilters::default()
+                },
+            }))
+            .await
+            .unwrap();
+        let exported = result.structured_content.unwrap();
+        assert_eq!(exported["format"], "ledger");
+        assert_eq!(exported["transactions"], 2);
+        assert!(exported.get("truncated").is_none());
+        let journal = exported["journal"].as_str().unwrap();
+        let bread = journal.find("* Bread").unwrap();
+        let cheese = journal.find("* Cheese").unwrap();
+        assert!(bread < cheese, "{journal}");
+        assert!(!journal.contains("Refund"));
+        assert!(journal.contains("    Expenses:Food  42.00 USD\n    Assets:Checking  -42.00 USD\n"));
+        assert!(!journal.contains("Income:Uncategorized"));
+        assert_eq!(
+            result.content[0].as_text().unwrap().text,
+            "Wrote a ledger journal of 2 transactions; it is in the journal field",
+            "the journal is only returned once"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Tests for the size-rotated log file.
+
+use exaspoon_db_mcp::config::{LogFileConfig, LogFormat};
+use exaspoon_db_mcp::log_file::RotatingFile;
+use exaspoon_db_mcp::log_format;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing_subscriber::layer::SubscriberExt;
+
+fn log_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("exaspoon-logs-{}", uuid::Uuid::new_v4()))
+}
+
+fn lines(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_log_file_rotates_by_size_and_keeps_the_newest_files() {
+    let dir = log_dir();
+    let config = LogFileConfig {
+        path: dir.join("nested").join("server.log"),
+        max_bytes: 200,
+        retained: 2,
+    };
+    let file = RotatingFile::open(&config).unwrap();
+    let subscriber = tracing_subscriber::registry().with(log_format::layer(LogFormat::Json, file));
+    tracing::subscriber::with_default(subscriber, || {
+        for index in 0..12 {
+            tracing::info!(index, "Event number {index}");
+        }
+    });
+
+    let rotated = |index: usize| PathBuf::from(format!("{}.{index}", config.path.display()));
+    assert!(rotated(1).exists());
+    assert!(rotated(2).exists());
+    assert!(!rotated(3).exists());
+    for path in [config.path.clone(), rotated(1), rotated(2)] {
+        let size = fs::metadata(&path).unwrap().len();
+        assert!(
+            size <= config.max_bytes,
+            "{} has {size} bytes",
+            path.display()
+        );
+    }
+
+    // Lines are never split, and the newest is in the current file.
+    let newest = lines(&config.path);
+    let last: serde_json::Value = serde_json::from_str(newest.last().unwrap()).unwrap();
+    assert_eq!(last["index"], 11);
+    let older: serde_json::Value = serde_json::from_str(&lines(&rotated(1))[0]).unwrap();
+    assert!(older["index"].as_u64().unwrap() < 11);
+
+    // A restart appends to what the last run left, rotating it when full.
+    let before = fs::read_to_string(&config.path).unwrap();
+    let file = RotatingFile::open(&config).unwrap();
+    let subscriber = tracing_subscriber::registry().with(log_format::layer(LogFormat::Text, file));
+    tracing::subscriber::with_default(subscriber, || tracing::warn!("Restarted"));
+    let after = fs::read_to_string(&config.path).unwrap();
+    assert!(after.contains("Restarted"));
+    let kept = if after.starts_with(&before) {
+        after
+    } else {
+        fs::read_to_string(rotated(1)).unwrap()
+    };
+    assert!(kept.starts_with(&before));
+
+    fs::remove_dir_all(dir).unwrap();
+}
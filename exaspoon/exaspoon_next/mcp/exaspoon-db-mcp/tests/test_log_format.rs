@@ -0,0 +1,81 @@
+//! Tests for the JSON log format.
+
+use exaspoon_db_mcp::config::LogFormat;
+use exaspoon_db_mcp::log_format::JsonFormat;
+use serde_json::Value;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::format::JsonFields;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Collects everything written to it.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Captured {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[test]
+fn test_json_lines_lift_span_fields_to_the_top_level() {
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(captured.clone())
+        .fmt_fields(JsonFields::new())
+        .event_format(JsonFormat)
+        .finish();
+    tracing::subscriber::with_default(subscriber, || {
+        let call = tracing::info_span!("tool_call", tool = "ping", correlation_id = "abc");
+        let _call = call.enter();
+        tracing::info_span!("embed", model = "small").in_scope(|| {
+            tracing::warn!(attempt = 2, "Retrying");
+        });
+        tracing::info!(duration_ms = 12u64, ok = true, "Finished ping call");
+    });
+
+    let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2, "{output}");
+
+    let retry = &lines[0];
+    assert_eq!(retry["level"], "WARN");
+    assert_eq!(retry["message"], "Retrying");
+    assert_eq!(retry["span"], "embed");
+    assert_eq!(retry["tool"], "ping");
+    assert_eq!(retry["correlation_id"], "abc");
+    assert_eq!(retry["model"], "small");
+    assert_eq!(retry["attempt"], 2);
+    assert!(retry["timestamp"].as_str().unwrap().ends_with('Z'));
+
+    let finished = &lines[1];
+    assert_eq!(finished["span"], "tool_call");
+    assert_eq!(finished["tool"], "ping");
+    assert_eq!(finished["duration_ms"], 12);
+    assert_eq!(finished["ok"], true);
+    assert!(finished.get("model").is_none());
+}
+
+#[test]
+fn test_log_format_parses() {
+    assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    assert_eq!("Text".parse::<LogFormat>().unwrap(), LogFormat::Text);
+    assert!("logfmt".parse::<LogFormat>().is_err());
+}
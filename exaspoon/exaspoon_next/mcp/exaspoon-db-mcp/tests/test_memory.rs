@@ -0,0 +1,718 @@
+//! Tests for the in-memory database backend.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    AccountType, AggregateSpendingInput, AuditEvent, BatchOperation, CategorizeInput,
+    CategorizeTransactionPromptArgs, CreateTransactionsInput, DryRun, EmbeddingIssueKind,
+    ExecuteBatchInput, FindUnusualSpendingPromptArgs, ListAccountsInput, ListAuditEventsInput,
+    ListTransactionsInput, MonthlyBudgetReviewPromptArgs, RecordKind, SearchSimilarInput,
+    SearchTextInput, SeedDemoDataInput, SpendingGroupBy, SpendingPeriod, TransactionFilters,
+};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use rmcp::model::{ErrorCode, GetPromptResult, PromptMessageContent};
+use std::sync::Arc;
+
+mod common;
+
+#[tokio::test]
+async fn test_memory_upsert_account_merges_on_name_and_type() {
+    let db = MemoryDatabase::new();
+    let mut input = common::sample_account_input();
+
+    let first = db.upsert_account(&input).await.unwrap();
+    input.currency = "EUR".to_string();
+    let second = db.upsert_account(&input).await.unwrap();
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.currency, "EUR");
+
+    input.r#type = AccountType::Onchain;
+    db.upsert_account(&input).await.unwrap();
+
+    let offchain = db
+        .list_accounts(&ListAccountsInput {
+            r#type: Some(AccountType::Offchain),
+            search: Some("check".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(offchain.len(), 1);
+    assert_eq!(db.list_accounts(&ListAccountsInput::default()).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_memory_upsert_category_reuses_id() {
+    let db = MemoryDatabase::new();
+    let input = common::sample_category_input();
+
+    let first = db.upsert_category(&input, Some(vec![1.0, 0.0])).await.unwrap();
+    let second = db.upsert_category(&input, Some(vec![0.0, 1.0])).await.unwrap();
+    assert_eq!(first.id, second.id);
+
+    let matches = db.search_similar_categories(vec![0.0, 1.0], None).await.unwrap();
+    assert_eq!(matches.len(), 1);
+    assert!((matches[0].similarity.unwrap() - 1.0).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_memory_insert_transaction_requires_existing_account() {
+    let db = MemoryDatabase::new();
+
+    let result = db
+        .insert_transaction(&common::sample_transaction_input(), None)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_memory_aggregate_spending_by_account_and_week() {
+    let db = MemoryDatabase::new();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    for (amount, occurred_at) in [
+        (10.0, "2024-01-15T08:00:00Z"),
+        (5.0, "2024-01-21T23:00:00Z"),
+        (3.0, "2024-01-22T08:00:00Z"),
+    ] {
+        input.amount = amount;
+        input.occurred_at = occurred_at.to_string();
+        db.insert_transaction(&input, None).await.unwrap();
+    }
+
+    let buckets = db
+        .aggregate_spending(&AggregateSpendingInput {
+            group_by: SpendingGroupBy::Account,
+            period: Some(SpendingPeriod::Week),
+            filters: TransactionFilters::default(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].key.as_deref(), Some(account.id.as_str()));
+    assert_eq!(
+        buckets[0].period_start.as_deref(),
+        Some("2024-01-15T00:00:00Z")
+    );
+    assert_eq!(buckets[0].total, 15.0);
+    assert_eq!(buckets[0].count, 2);
+    assert_eq!(
+        buckets[1].period_start.as_deref(),
+        Some("2024-01-22T00:00:00Z")
+    );
+
+    let filtered = db
+        .aggregate_spending(&AggregateSpendingInput {
+            group_by: SpendingGroupBy::Category,
+            period: None,
+            filters: TransactionFilters {
+                from: Some("2024-01-20T00:00:00Z".to_string()),
+                ..TransactionFilters::default()
+            },
+        })
+        .await
+        .unwrap();
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].key, None);
+    assert_eq!(filtered[0].total, 8.0);
+}
+
+#[tokio::test]
+async fn test_memory_list_accounts_and_transactions_paginate() {
+    let db = MemoryDatabase::new();
+    for name in ["Checking", "Savings", "Brokerage"] {
+        let mut input = common::sample_account_input();
+        input.name = name.to_string();
+        db.upsert_account(&input).await.unwrap();
+    }
+    let params = ListAccountsInput {
+        limit: Some(2),
+        offset: Some(1),
+        ..Default::default()
+    };
+    let accounts = db.list_accounts(&params).await.unwrap();
+    let names = accounts
+        .iter()
+        .map(|account| account.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Checking", "Savings"]);
+    assert_eq!(db.count_accounts(&params).await.unwrap(), 3);
+
+    let mut input = common::sample_transaction_input();
+    input.account_id = accounts[0].id.clone();
+    for day in 1..=3 {
+        input.occurred_at = format!("2024-03-0{day}T12:00:00Z");
+        input.amount = f64::from(day) * 10.0;
+        db.insert_transaction(&input, None).await.unwrap();
+    }
+    let filters = TransactionFilters {
+        from: Some("2024-03-02T00:00:00Z".to_string()),
+        ..TransactionFilters::default()
+    };
+    let page = db
+        .list_transactions(&ListTransactionsInput {
+            filters: filters.clone(),
+            limit: Some(1),
+            offset: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].occurred_at, "2024-03-03T12:00:00Z");
+    assert_eq!(db.count_transactions(&filters).await.unwrap(), 2);
+    let largest = db.largest_transactions(&filters, 1).await.unwrap();
+    assert_eq!(largest[0].occurred_at, "2024-03-03T12:00:00Z");
+}
+
+#[tokio::test]
+async fn test_memory_search_transactions_text() {
+    let db = MemoryDatabase::new();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    input.description = Some("Invoice 4812".to_string());
+    let invoice = db.insert_transaction(&input, None).await.unwrap();
+    input.description = Some("Groceries".to_string());
+    input.raw_source = Some("card ending 4812".to_string());
+    let groceries = db.insert_transaction(&input, None).await.unwrap();
+
+    let matches = db
+        .search_transactions_text("invoice 4812", None)
+        .await
+        .unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].transaction.id, invoice.id);
+
+    let matches = db.search_transactions_text("4812", None).await.unwrap();
+    assert_eq!(matches.len(), 2);
+    assert!(matches
+        .iter()
+        .any(|found| found.transaction.id == groceries.id));
+}
+
+#[tokio::test]
+async fn test_memory_streams_transactions_in_pages() {
+    let db = MemoryDatabase::new();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    for day in 1..=5 {
+        input.occurred_at = format!("2024-03-0{day}T12:00:00Z");
+        db.insert_transaction(&input, None).await.unwrap();
+    }
+
+    let (sender, mut pages) = tokio::sync::mpsc::channel(8);
+    let sent = db
+        .stream_transactions(&TransactionFilters::default(), 2, sender)
+        .await
+        .unwrap();
+    assert_eq!(sent, 5);
+    let mut sizes = Vec::new();
+    let mut dates = Vec::new();
+    while let Some(page) = pages.recv().await {
+        sizes.push(page.len());
+        dates.extend(page.into_iter().map(|transaction| transaction.occurred_at));
+    }
+    assert_eq!(sizes, [2, 2, 1]);
+    assert_eq!(dates.first().unwrap(), "2024-03-05T12:00:00Z");
+    assert_eq!(dates.last().unwrap(), "2024-03-01T12:00:00Z");
+
+    let (sender, pages) = tokio::sync::mpsc::channel(1);
+    drop(pages);
+    let sent = db
+        .stream_transactions(&TransactionFilters::default(), 2, sender)
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+}
+
+#[tokio::test]
+async fn test_memory_soft_delete_and_purge() {
+    let db = MemoryDatabase::new();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    let transaction = db
+        .insert_transaction(&input, Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+
+    assert!(!db
+        .soft_delete(RecordKind::Account, "acct-404")
+        .await
+        .unwrap());
+    assert!(db
+        .soft_delete(RecordKind::Transaction, &transaction.id)
+        .await
+        .unwrap());
+    assert!(db
+        .search_similar_transactions(vec![1.0, 0.0], None)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        db.count_transactions(&TransactionFilters::default())
+            .await
+            .unwrap(),
+        0
+    );
+
+    assert!(db
+        .soft_delete(RecordKind::Account, &account.id)
+        .await
+        .unwrap());
+    assert_eq!(
+        db.count_accounts(&ListAccountsInput::default())
+            .await
+            .unwrap(),
+        0
+    );
+    let revived = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    assert_eq!(revived.id, account.id);
+    assert_eq!(
+        db.count_accounts(&ListAccountsInput::default())
+            .await
+            .unwrap(),
+        1
+    );
+
+    assert_eq!(
+        db.purge_deleted(RecordKind::Account, None).await.unwrap(),
+        0
+    );
+    assert_eq!(
+        db.purge_deleted(RecordKind::Transaction, None)
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_memory_reports_and_repairs_embedding_issues() {
+    let db = MemoryDatabase::new();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut ids = Vec::new();
+    for (amount, embedding) in [
+        (1.0, None),
+        (2.0, Some(vec![1.0, 0.0])),
+        (3.0, Some(vec![1.0, 0.0, 0.0])),
+    ] {
+        let mut input = common::sample_transaction_input();
+        input.account_id = account.id.clone();
+        input.amount = amount;
+        ids.push(db.insert_transaction(&input, embedding).await.unwrap().id);
+    }
+    db.soft_delete(RecordKind::Transaction, &ids[2])
+        .await
+        .unwrap();
+    db.upsert_category(&common::sample_category_input(), Some(vec![0.0, 1.0, 0.0]))
+        .await
+        .unwrap();
+
+    let issues = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|issue| (issue.id, issue.issue))
+        .collect::<Vec<_>>();
+    let mut expected = vec![
+        (ids[0].clone(), EmbeddingIssueKind::Missing),
+        (ids[1].clone(), EmbeddingIssueKind::Dimension),
+        (ids[2].clone(), EmbeddingIssueKind::Orphaned),
+    ];
+    expected.sort_by(|left, right| left.0.cmp(&right.0));
+    assert_eq!(issues, expected);
+    assert!(db
+        .embedding_issues(RecordKind::Category, 3, None, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    let first = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 1)
+        .await
+        .unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(
+        db.embedding_issues(RecordKind::Transaction, 3, Some(&first[0].id), 10)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+
+    db.set_embedding(RecordKind::Transaction, &ids[0], Some(vec![0.0, 0.0, 1.0]))
+        .await
+        .unwrap();
+    db.set_embedding(RecordKind::Transaction, &ids[2], None)
+        .await
+        .unwrap();
+    let remaining = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].id, ids[1]);
+    assert_eq!(remaining[0].content.as_deref(), Some("Coffee"));
+    assert!(db
+        .set_embedding(RecordKind::Transaction, "txn-404", None)
+        .await
+        .is_err());
+    assert!(db
+        .set_embedding(RecordKind::Account, &account.id, None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_memory_lists_audit_events_newest_first() {
+    let db = MemoryDatabase::new();
+    let event = |tool: &str, record_id: &str, occurred_at: &str| AuditEvent {
+        tool: tool.to_string(),
+        input_hash: "0".repeat(64),
+        record_id: Some(record_id.to_string()),
+        occurred_at: occurred_at.to_string(),
+        session_id: Some("session-1".to_string()),
+        details: None,
+    };
+    db.record_audit_events(&[
+        event("create_transaction", "txn-1", "2024-01-01T00:00:00.000Z"),
+        event("delete_transaction", "txn-1", "2024-01-02T00:00:00.000Z"),
+        event("create_transaction", "txn-2", "2024-01-03T00:00:00.000Z"),
+    ])
+    .await
+    .unwrap();
+
+    let all = db
+        .list_audit_events(&ListAuditEventsInput::default())
+        .await
+        .unwrap();
+    let records: Vec<_> = all.iter().map(|e| e.record_id.as_deref()).collect();
+    assert_eq!(records, [Some("txn-2"), Some("txn-1"), Some("txn-1")]);
+
+    let for_record = db
+        .list_audit_events(&ListAuditEventsInput {
+            record_id: Some("txn-1".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let tools: Vec<_> = for_record.iter().map(|e| e.tool.as_str()).collect();
+    assert_eq!(tools, ["delete_transaction", "create_transaction"]);
+
+    let paged = db
+        .list_audit_events(&ListAuditEventsInput {
+            tool: Some("create_transaction".to_string()),
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(paged.len(), 1);
+    assert_eq!(paged[0].record_id.as_deref(), Some("txn-1"));
+}
+
+#[tokio::test]
+async fn test_memory_backend_serves_tools_end_to_end() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let server = ExaspoonDbServer::new(db.clone(), embedder);
+
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    server
+        .create_transaction(Parameters(input.into()))
+        .await
+        .expect("tool call should succeed");
+
+    let result = server
+        .search_similar_transactions(Parameters(SearchSimilarInput {
+            query: "Coffee".to_string(),
+            limit: None,
+        }))
+        .await
+        .expect("tool call should succeed");
+
+    let payload = result.structured_content.expect("structured payload");
+    assert_eq!(payload["matches"].as_array().unwrap().len(), 1);
+    assert_eq!(payload["matches"][0]["account_id"], account.id);
+    assert_eq!(payload["matches"][0]["description"], "Coffee");
+}
+
+#[tokio::test]
+async fn test_memory_execute_batch_applies_all_or_nothing() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let server = ExaspoonDbServer::new(db.clone(), embedder);
+    let category = db
+        .upsert_category(&common::sample_category_input(), None)
+        .await
+        .unwrap();
+    let mut transaction = common::sample_transaction_input();
+    transaction.account_id = "$0".to_string();
+
+    let result = server
+        .execute_batch(Parameters(
+            ExecuteBatchInput {
+                operations: vec![
+                    BatchOperation::UpsertAccount(common::sample_account_input()),
+                    BatchOperation::CreateTransactions(CreateTransactionsInput {
+                        transactions: vec![transaction.clone(), transaction.clone()],
+                    }),
+                    BatchOperation::Categorize(CategorizeInput {
+                        transaction_id: "$1.1".to_string(),
+                        category_id: category.id.clone(),
+                    }),
+                ],
+            }
+            .into(),
+        ))
+        .await
+        .expect("batch should apply");
+    let payload = result.structured_content.expect("structured payload");
+    let steps = payload["steps"].as_array().unwrap();
+    assert_eq!(steps.len(), 3);
+    assert_eq!(steps[0]["op"], "upsert_account");
+    let account_id = steps[0]["account"]["id"].clone();
+    assert_eq!(steps[1]["transactions"][0]["account_id"], account_id);
+    assert_eq!(steps[2]["transaction"]["id"], steps[1]["transactions"][1]["id"]);
+    assert_eq!(steps[2]["transaction"]["category_id"], category.id.as_str());
+
+    // The last operation fails, so the account update and the new account
+    // and transaction before it are undone.
+    let mut in_euros = common::sample_account_input();
+    in_euros.currency = "EUR".to_string();
+    let mut savings = common::sample_account_input();
+    savings.name = "Savings".to_string();
+    let err = server
+        .execute_batch(Parameters(
+            ExecuteBatchInput {
+                operations: vec![
+                    BatchOperation::UpsertAccount(in_euros),
+                    BatchOperation::UpsertAccount(savings),
+                    BatchOperation::CreateTransactions(CreateTransactionsInput {
+                        transactions: vec![transaction],
+                    }),
+                    BatchOperation::Categorize(CategorizeInput {
+                        transaction_id: "$2.0".to_string(),
+                        category_id: "cat-404".to_string(),
+                    }),
+                ],
+            }
+            .into(),
+        ))
+        .await
+        .expect_err("missing category should fail the batch");
+    assert_eq!(err.code, ErrorCode::RESOURCE_NOT_FOUND);
+    let data = err.data.expect("error data");
+    assert_eq!(data["step"], 3);
+    assert_eq!(data["rolled_back"], true);
+
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].id, account_id.as_str().unwrap());
+    assert_eq!(accounts[0].currency, "USD");
+    assert_eq!(
+        db.count_transactions(&TransactionFilters::default())
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_memory_prompts_include_live_data() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let server = ExaspoonDbServer::new(db.clone(), embedder);
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    db.upsert_category(&common::sample_category_input(), Some(vec![0.3, 0.4]))
+        .await
+        .unwrap();
+    let mut coffee = common::sample_transaction_input();
+    coffee.account_id = account.id.clone();
+    db.insert_transaction(&coffee, Some(vec![0.3, 0.4]))
+        .await
+        .unwrap();
+    let mut groceries = coffee.clone();
+    groceries.amount = 80.0;
+    groceries.occurred_at = "2023-12-20T10:00:00Z".to_string();
+    groceries.description = Some("Groceries".to_string());
+    db.insert_transaction(&groceries, None).await.unwrap();
+
+    let prompt = server
+        .categorize_transaction_prompt(Parameters(CategorizeTransactionPromptArgs {
+            line: "STARBUCKS #1234 SEATTLE".to_string(),
+        }))
+        .await
+        .expect("prompt should build");
+    let text = prompt_text(&prompt);
+    assert!(text.contains("STARBUCKS #1234 SEATTLE"), "{text}");
+    assert!(text.contains("- Food (expense, id "), "{text}");
+    assert!(text.contains("2024-01-02 expense $42.00: Coffee"), "{text}");
+
+    let prompt = server
+        .monthly_budget_review_prompt(Parameters(MonthlyBudgetReviewPromptArgs {
+            month: Some("2024-01".to_string()),
+        }))
+        .await
+        .expect("prompt should build");
+    let text = prompt_text(&prompt);
+    assert!(text.contains("Review my budget for 2024-01."), "{text}");
+    assert!(
+        text.contains("- expense: $42.00 in 1 transactions"),
+        "{text}"
+    );
+    assert!(text.contains("(previous month: $80.00)"), "{text}");
+    let err = server
+        .monthly_budget_review_prompt(Parameters(MonthlyBudgetReviewPromptArgs {
+            month: Some("January".to_string()),
+        }))
+        .await
+        .expect_err("expected validation error");
+    assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+
+    let prompt = server
+        .find_unusual_spending_prompt(Parameters(FindUnusualSpendingPromptArgs::default()))
+        .await
+        .expect("prompt should build");
+    assert!(prompt_text(&prompt).contains("in the last 30 days"));
+    let err = server
+        .find_unusual_spending_prompt(Parameters(FindUnusualSpendingPromptArgs {
+            days: Some("0".to_string()),
+        }))
+        .await
+        .expect_err("expected validation error");
+    assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+}
+
+fn prompt_text(prompt: &GetPromptResult) -> String {
+    match &prompt.messages[0].content {
+        PromptMessageContent::Text { text } => text.clone(),
+        other => panic!("unexpected prompt content {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_seed_demo_data_feeds_search_tools() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let server = ExaspoonDbServer::new(db.clone(), embedder).with_demo_seed();
+
+    let result = server
+        .seed_demo_data(Parameters(DryRun::from(SeedDemoDataInput {
+            months: Some(2),
+        })))
+        .await
+        .expect("tool call should succeed");
+    let payload = result.structured_content.expect("structured payload");
+    assert_eq!(payload["accounts"], 4);
+    assert_eq!(payload["categories"], 9);
+    let seeded = payload["transactions"].as_u64().unwrap();
+    assert!(seeded > 0);
+    assert_eq!(
+        db.count_transactions(&TransactionFilters::default())
+            .await
+            .unwrap(),
+        seeded
+    );
+
+    let result = server
+        .search_transactions_text(Parameters(SearchTextInput {
+            query: "payroll".to_string(),
+            limit: None,
+        }))
+        .await
+        .expect("tool call should succeed");
+    let payload = result.structured_content.expect("structured payload");
+    assert!(!payload["matches"].as_array().unwrap().is_empty());
+
+    // A dry run of a second seed sees the transactions already there.
+    let result = server
+        .seed_demo_data(Parameters(DryRun {
+            input: SeedDemoDataInput::default(),
+            dry_run: Some(true),
+        }))
+        .await
+        .expect("tool call should succeed");
+    let payload = result.structured_content.expect("structured payload");
+    assert_eq!(payload["dry_run"], true);
+    assert_eq!(payload["transactions"], 0);
+    assert_eq!(payload["skipped_transactions"], true);
+
+    let result = server
+        .seed_demo_data(Parameters(SeedDemoDataInput::default().into()))
+        .await
+        .expect("tool call should succeed");
+    let payload = result.structured_content.expect("structured payload");
+    assert_eq!(payload["transactions"], 0);
+    assert_eq!(payload["skipped_transactions"], true);
+    assert_eq!(
+        db.list_accounts(&ListAccountsInput::default())
+            .await
+            .unwrap()
+            .len(),
+        4
+    );
+}
+
+#[tokio::test]
+async fn test_memory_lists_live_category_names() {
+    let db = MemoryDatabase::new();
+    for name in ["Groceries", "Gym", "Rent"] {
+        let mut input = common::sample_category_input();
+        input.name = name.to_string();
+        db.upsert_category(&input, None).await.unwrap();
+    }
+    let mut input = common::sample_category_input();
+    input.name = "Gifts".to_string();
+    let gifts = db.upsert_category(&input, None).await.unwrap();
+    db.soft_delete(RecordKind::Category, &gifts.id)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        db.list_category_names(" G ", 10).await.unwrap(),
+        vec!["Groceries", "Gym"]
+    );
+    assert_eq!(
+        db.list_category_names("", 1).await.unwrap(),
+        vec!["Groceries"]
+    );
+}
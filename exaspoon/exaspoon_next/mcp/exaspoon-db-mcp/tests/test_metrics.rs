@@ -0,0 +1,47 @@
+//! Tests for the in-process request metrics.
+
+use exaspoon_db_mcp::metrics::{Metrics, LATENCY_BUCKETS_MS};
+use std::time::Duration;
+
+#[test]
+fn test_metrics_bucket_latencies_per_operation() {
+    let metrics = Metrics::new();
+    for millis in [3, 3, 8, 40, 7000] {
+        metrics.record(
+            "query transactions",
+            Duration::from_millis(millis),
+            millis < 5000,
+        );
+    }
+    metrics.record("insert into accounts", Duration::from_millis(20), true);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot[0].operation, "query transactions");
+    assert_eq!(snapshot[0].calls, 5);
+    assert_eq!(snapshot[0].errors, 1);
+    assert_eq!(snapshot[0].max_ms, 7000.0);
+    assert_eq!(snapshot[0].histogram.len(), LATENCY_BUCKETS_MS.len() + 1);
+    let counts = snapshot[0]
+        .histogram
+        .iter()
+        .map(|bucket| (bucket.le_ms, bucket.count))
+        .filter(|(_, count)| *count > 0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        counts,
+        [(Some(5), 2), (Some(10), 1), (Some(50), 1), (None, 1)]
+    );
+    assert_eq!(snapshot[0].p50_ms, Some(10));
+    assert_eq!(snapshot[0].p95_ms, None);
+    assert_eq!(snapshot[0].p99_ms, None);
+    assert_eq!(snapshot[0].error_rate, 0.2);
+
+    assert_eq!(snapshot[1].operation, "insert into accounts");
+    assert_eq!(snapshot[1].p95_ms, Some(25));
+    assert_eq!(snapshot[1].mean_ms, 20.0);
+}
+
+#[test]
+fn test_metrics_start_empty() {
+    assert!(Metrics::new().snapshot().is_empty());
+}
@@ -0,0 +1,106 @@
+//! Tests for the embedded schema migrations.
+
+use exaspoon_db_mcp::config::validate_schema;
+use exaspoon_db_mcp::migrations::{script, MIGRATIONS};
+use exaspoon_db_mcp::supabase::{
+    ACCOUNT_KEY, AUDIT_LOG_TABLE, BANK_LINKS_TABLE, BANK_LINK_KEY, CATEGORY_KEY, DELETED_AT_COLUMN,
+    TENANT_COLUMN, TENANT_RPC_PARAM, TOOL_CALLS_TABLE,
+};
+
+#[test]
+fn test_migrations_are_ordered_and_non_empty() {
+    for (index, migration) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(migration.version as usize, index + 1);
+        assert!(
+            !migration.sql.trim().is_empty(),
+            "{} is empty",
+            migration.name
+        );
+    }
+}
+
+#[test]
+fn test_migrations_define_what_the_gateway_uses() {
+    let sql = script("public");
+
+    for table in [
+        "accounts",
+        "categories",
+        "transactions",
+        AUDIT_LOG_TABLE,
+        TOOL_CALLS_TABLE,
+    ] {
+        assert!(
+            sql.contains(&format!("create table {table}")),
+            "missing {table}"
+        );
+    }
+    for function in [
+        "search_similar_transactions",
+        "search_similar_categories",
+        "aggregate_spending",
+        "search_transactions_text",
+        "embedding_issues",
+        "categorize_transaction",
+        "execute_batch",
+    ] {
+        assert!(sql.contains(&format!("create or replace function {function}")));
+    }
+    assert!(sql.contains(&format!("t.{TENANT_COLUMN} = {TENANT_RPC_PARAM}")));
+    assert!(sql.contains(&format!("c.{TENANT_COLUMN} = {TENANT_RPC_PARAM}")));
+    assert!(sql.contains("similarity float"));
+    // Keyword search needs every word, with `%` and `_` matched literally.
+    assert!(sql.contains("ilike all ("));
+    assert!(sql.contains(r"'%', '\%'), '_', '\_'"));
+    // Batches only write to the caller's own live accounts.
+    assert!(sql.contains("no live account with id"));
+    assert!(sql.contains(&format!(
+        "alter table {AUDIT_LOG_TABLE} enable row level security"
+    )));
+    for alias in ["t", "c"] {
+        assert!(sql.contains(&format!("{alias}.{DELETED_AT_COLUMN} is null")));
+    }
+}
+
+#[test]
+fn test_migrations_key_every_gateway_upsert_per_tenant() {
+    let sql = script("public");
+
+    for (table, key) in [
+        ("accounts", ACCOUNT_KEY),
+        ("categories", CATEGORY_KEY),
+        (BANK_LINKS_TABLE, BANK_LINK_KEY),
+    ] {
+        let start = sql
+            .find(&format!("create table {table} ("))
+            .unwrap_or_else(|| panic!("missing {table}"));
+        let end = start + sql[start..].find(");").unwrap();
+        // The gateway conflicts on the tenant column and the key, with or
+        // without a tenant, so untenanted rows' null must count as equal.
+        let unique = format!(
+            "unique nulls not distinct ({TENANT_COLUMN}, {})",
+            key.replace(',', ", ")
+        );
+        assert!(sql[start..end].contains(&unique), "{table} lacks {unique}");
+    }
+    // execute_batch upserts accounts within the tenant it is given.
+    assert!(sql.contains(&format!(
+        "on conflict ({TENANT_COLUMN}, {})",
+        ACCOUNT_KEY.replace(',', ", ")
+    )));
+}
+
+#[test]
+fn test_migration_script_targets_schema() {
+    let sql = script("finance");
+
+    assert!(sql.starts_with("create schema if not exists finance;"));
+    assert!(sql.contains("set search_path to finance, public, extensions;"));
+}
+
+#[test]
+fn test_validate_schema() {
+    assert_eq!(validate_schema(" finance ").unwrap(), "finance");
+    assert!(validate_schema("").is_err());
+    assert!(validate_schema("finance; drop table accounts").is_err());
+}
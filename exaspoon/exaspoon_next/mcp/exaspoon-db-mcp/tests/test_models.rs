@@ -1,8 +1,9 @@
 //! Tests for data models and serialization.
 
 use exaspoon_db_mcp::models::{
-    AccountType, CategoryKind, CreateTransactionInput, ListAccountsInput, SearchSimilarInput,
-    TransactionDirection, UpsertAccountInput, UpsertCategoryInput,
+    Account, AccountType, CategoryKind, CategoryMatch, CreateTransactionInput, ListAccountsInput,
+    SearchSimilarInput, Transaction, TransactionDirection, TransactionMatch, UpsertAccountInput,
+    UpsertCategoryInput,
 };
 use serde_json;
 
@@ -26,6 +27,7 @@ fn test_category_kind_as_ref() {
 fn test_account_type_as_ref() {
     assert_eq!(AccountType::Onchain.as_ref(), "onchain");
     assert_eq!(AccountType::Offchain.as_ref(), "offchain");
+    assert_eq!(AccountType::Exchange.as_ref(), "exchange");
 }
 
 #[test]
@@ -141,6 +143,7 @@ fn test_list_accounts_input_serialization() {
     let input = ListAccountsInput {
         r#type: Some(AccountType::Onchain),
         search: Some("test".to_string()),
+        ..Default::default()
     };
 
     let json = serde_json::to_value(&input).unwrap();
@@ -153,6 +156,7 @@ fn test_list_accounts_input_serialization_without_optional_fields() {
     let input = ListAccountsInput {
         r#type: None,
         search: None,
+        ..Default::default()
     };
 
     let json = serde_json::to_value(&input).unwrap();
@@ -243,3 +247,57 @@ fn test_upsert_account_input_deserialization() {
     assert_eq!(input.network, Some("ethereum".to_string()));
     assert_eq!(input.institution, Some("Test Bank".to_string()));
 }
+
+#[test]
+fn test_transaction_row_deserialization_ignores_extra_columns() {
+    let json_str = r#"
+    {
+        "id": "txn-1",
+        "account_id": "acct-1",
+        "amount": 12.5,
+        "currency": "EUR",
+        "direction": "expense",
+        "occurred_at": "2024-01-02T03:04:05Z",
+        "description": "Coffee",
+        "raw_source": null,
+        "category_id": null,
+        "embedding": "[0.1,0.2]",
+        "created_at": "2024-01-02T03:04:06Z"
+    }
+    "#;
+
+    let transaction: Transaction = serde_json::from_str(json_str).unwrap();
+    assert_eq!(transaction.id, "txn-1");
+    assert_eq!(transaction.amount, 12.5);
+    assert_eq!(transaction.direction, TransactionDirection::Expense);
+    assert_eq!(transaction.description, Some("Coffee".to_string()));
+    assert_eq!(transaction.raw_source, None);
+}
+
+#[test]
+fn test_account_row_round_trip() {
+    let account = common::sample_account("acct-1");
+
+    let value = serde_json::to_value(&account).unwrap();
+    assert_eq!(value["type"], "offchain");
+    assert!(value.get("network").is_none());
+
+    let decoded: Account = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded, account);
+}
+
+#[test]
+fn test_match_rows_flatten_similarity() {
+    let transaction_match = common::sample_transaction_match("txn-1", "Coffee");
+    let value = serde_json::to_value(&transaction_match).unwrap();
+    assert_eq!(value["id"], "txn-1");
+    assert_eq!(value["similarity"], 0.9);
+    let decoded: TransactionMatch = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded, transaction_match);
+
+    let json_str = r#"{ "id": "cat-1", "name": "Food", "kind": "expense", "similarity": 0.42 }"#;
+    let category_match: CategoryMatch = serde_json::from_str(json_str).unwrap();
+    assert_eq!(category_match.category.name, "Food");
+    assert_eq!(category_match.category.kind, CategoryKind::Expense);
+    assert_eq!(category_match.similarity, Some(0.42));
+}
@@ -0,0 +1,648 @@
+//! Tests for progress and log notifications, elicitation and sampling
+//! requests, protocol revision negotiation, message locales, confirmation of
+//! destructive calls, configured tool names and shutdown, driven over a raw
+//! JSON-RPC transport.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::i18n::Locale;
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use exaspoon_db_mcp::tool_names::ToolNames;
+use rmcp::ServiceExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadHalf, WriteHalf,
+};
+
+mod common;
+
+struct RawClient {
+    lines: Lines<BufReader<ReadHalf<DuplexStream>>>,
+    writer: WriteHalf<DuplexStream>,
+}
+
+impl RawClient {
+    async fn connect(server: ExaspoonDbServer) -> Self {
+        Self::connect_with(server, json!({})).await
+    }
+
+    async fn connect_with(server: ExaspoonDbServer, capabilities: Value) -> Self {
+        Self::handshake(server, "2025-06-18", capabilities).await.0
+    }
+
+    /// Connects asking for `protocol_version`, returning the client and the
+    /// server's `initialize` result.
+    async fn handshake(
+        server: ExaspoonDbServer,
+        protocol_version: &str,
+        capabilities: Value,
+    ) -> (Self, Value) {
+        let (client_end, server_end) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            if let Ok(running) = server.serve(server_end).await {
+                let _ = running.waiting().await;
+            }
+        });
+        let (reader, writer) = tokio::io::split(client_end);
+        let mut client = Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        };
+        client
+            .send(json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": protocol_version,
+                    "capabilities": capabilities,
+                    "clientInfo": { "name": "test", "version": "0" }
+                }
+            }))
+            .await;
+        let (response, _) = client.until_response(0).await;
+        client
+            .send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+        (client, response["result"].clone())
+    }
+
+    async fn send(&mut self, message: Value) {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+    }
+
+    async fn next_message(&mut self) -> Value {
+        let line = self
+            .lines
+            .next_line()
+            .await
+            .unwrap()
+            .expect("server closed the stream");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    /// Reads messages until the response to `id`, returning the response and
+    /// every notification received before it.
+    async fn until_response(&mut self, id: u64) -> (Value, Vec<Value>) {
+        let mut notifications = Vec::new();
+        loop {
+            let message = self.next_message().await;
+            if message["id"] == json!(id) && message.get("method").is_none() {
+                return (message, notifications);
+            }
+            notifications.push(message);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_batch_import_reports_progress_when_asked() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut client = RawClient::connect(ExaspoonDbServer::new(db.clone(), embedder)).await;
+
+    let transactions = (0..3)
+        .map(|index| {
+            let mut input = common::sample_transaction_input();
+            input.account_id = account.id.clone();
+            input.description = Some(format!("Coffee {index}"));
+            input
+        })
+        .collect::<Vec<_>>();
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "create_transactions",
+                "arguments": { "transactions": transactions },
+                "_meta": { "progressToken": "import-1" }
+            }
+        }))
+        .await;
+    let (response, notifications) = client.until_response(1).await;
+    assert!(response.get("error").is_none(), "{response}");
+
+    let progress = notifications
+        .iter()
+        .filter(|message| message["method"] == "notifications/progress")
+        .map(|message| &message["params"])
+        .collect::<Vec<_>>();
+    assert_eq!(progress.len(), 3);
+    for (index, params) in progress.iter().enumerate() {
+        assert_eq!(params["progressToken"], "import-1");
+        assert_eq!(params["progress"], json!((index + 1) as f64));
+        assert_eq!(params["total"], json!(3.0));
+    }
+
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "create_transactions",
+                "arguments": { "transactions": [transactions[0]] }
+            }
+        }))
+        .await;
+    let (response, notifications) = client.until_response(2).await;
+    assert!(response.get("error").is_none(), "{response}");
+    assert!(notifications.is_empty(), "{notifications:?}");
+}
+
+#[tokio::test]
+async fn test_server_events_are_logged_at_the_client_level() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let server = ExaspoonDbServer::new(db, embedder).with_demo_seed();
+    let mut client = RawClient::connect(server).await;
+    let seed = |id: u64| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "seed_demo_data", "arguments": { "months": 1 } }
+        })
+    };
+    let logs = |notifications: Vec<Value>| {
+        notifications
+            .into_iter()
+            .filter(|message| message["method"] == "notifications/message")
+            .map(|message| message["params"].clone())
+            .collect::<Vec<_>>()
+    };
+
+    client.send(seed(1)).await;
+    let (response, notifications) = client.until_response(1).await;
+    assert!(response.get("error").is_none(), "{response}");
+    let logged = logs(notifications);
+    assert_eq!(logged.len(), 1, "{logged:?}");
+    assert_eq!(logged[0]["level"], "info");
+    assert_eq!(logged[0]["logger"], "exaspoon-db-mcp");
+    assert_eq!(logged[0]["data"]["message"], "Demo data seeded");
+    assert_eq!(logged[0]["data"]["skipped_transactions"], false);
+
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "logging/setLevel",
+            "params": { "level": "notice" }
+        }))
+        .await;
+    let (response, _) = client.until_response(2).await;
+    assert!(response.get("error").is_none(), "{response}");
+
+    client.send(seed(3)).await;
+    let (response, notifications) = client.until_response(3).await;
+    assert!(response.get("error").is_none(), "{response}");
+    let logged = logs(notifications);
+    assert_eq!(logged.len(), 1, "{logged:?}");
+    assert_eq!(logged[0]["level"], "notice");
+    assert!(logged[0]["data"]["existing_transactions"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_missing_transaction_fields_are_elicited() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let server = ExaspoonDbServer::new(db.clone(), embedder);
+    let mut client = RawClient::connect_with(server, json!({ "elicitation": {} })).await;
+
+    let mut arguments = serde_json::to_value(common::sample_transaction_input()).unwrap();
+    arguments.as_object_mut().unwrap().remove("account_id");
+    arguments.as_object_mut().unwrap().remove("currency");
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "create_transaction", "arguments": arguments }
+        }))
+        .await;
+
+    let request = client.next_message().await;
+    assert_eq!(request["method"], "elicitation/create");
+    let schema = &request["params"]["requestedSchema"];
+    assert_eq!(
+        schema["properties"]["account_id"]["enum"],
+        json!([account.id])
+    );
+    assert_eq!(schema["properties"]["currency"]["maxLength"], 44);
+    assert_eq!(schema["required"], json!(["account_id", "currency"]));
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {
+                "action": "accept",
+                "content": { "account_id": account.id, "currency": "eur" }
+            }
+        }))
+        .await;
+    let (response, _) = client.until_response(1).await;
+    assert!(response.get("error").is_none(), "{response}");
+    let transaction = &response["result"]["structuredContent"]["transaction"];
+    assert_eq!(transaction["account_id"], json!(account.id));
+    assert_eq!(transaction["currency"], "EUR");
+
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "create_transaction", "arguments": arguments }
+        }))
+        .await;
+    let request = client.next_message().await;
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": { "action": "decline" }
+        }))
+        .await;
+    let (response, _) = client.until_response(2).await;
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["error"]["data"]["field"], "account_id");
+}
+
+#[tokio::test]
+async fn test_missing_transaction_fields_fail_without_elicitation() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let mut client = RawClient::connect(ExaspoonDbServer::new(db.clone(), embedder)).await;
+
+    let mut arguments = serde_json::to_value(common::sample_transaction_input()).unwrap();
+    arguments.as_object_mut().unwrap().remove("currency");
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "create_transaction", "arguments": arguments }
+        }))
+        .await;
+    let (response, notifications) = client.until_response(1).await;
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["error"]["data"]["field"], "currency");
+    assert_eq!(response["error"]["data"]["error_code"], "validation");
+    let correlation_id = response["error"]["data"]["correlation_id"].as_str().unwrap();
+    assert_eq!(correlation_id.len(), 36, "{correlation_id}");
+    assert!(notifications.is_empty(), "{notifications:?}");
+}
+
+#[tokio::test]
+async fn test_messages_follow_the_client_locale() {
+    let call = |id: u64| {
+        let mut arguments = serde_json::to_value(common::sample_transaction_input()).unwrap();
+        arguments.as_object_mut().unwrap().remove("currency");
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "create_transaction", "arguments": arguments }
+        })
+    };
+    let server = || {
+        ExaspoonDbServer::new(
+            Arc::new(MemoryDatabase::new()),
+            Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+        )
+    };
+
+    let russian = json!({ "experimental": { "i18n": { "locale": "ru-RU" } } });
+    let mut client = RawClient::connect_with(server(), russian).await;
+    client.send(call(1)).await;
+    let (response, _) = client.until_response(1).await;
+    assert_eq!(response["error"]["message"], "Поле currency обязательно");
+    assert_eq!(response["error"]["data"]["field"], "currency");
+
+    // The configured locale applies to clients that ask for none.
+    let mut client = RawClient::connect(server().with_locale(Locale::Ru)).await;
+    client.send(call(1)).await;
+    let (response, _) = client.until_response(1).await;
+    assert_eq!(response["error"]["message"], "Поле currency обязательно");
+
+    let english = json!({ "experimental": { "i18n": { "locale": "en" } } });
+    let mut client = RawClient::connect_with(server().with_locale(Locale::Ru), english).await;
+    client.send(call(1)).await;
+    let (response, _) = client.until_response(1).await;
+    assert_eq!(response["error"]["message"], "currency is required");
+}
+
+#[tokio::test]
+async fn test_calls_are_refused_once_shutting_down() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    );
+    let mut client = RawClient::connect(server.clone()).await;
+    let call = |id: u64| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "ping", "arguments": {} }
+        })
+    };
+    client.send(call(1)).await;
+    let (response, _) = client.until_response(1).await;
+    assert!(response["result"].is_object(), "{response}");
+
+    assert_eq!(server.shut_down(Duration::from_secs(1)).await, 0);
+    client.send(call(2)).await;
+    let (response, _) = client.until_response(2).await;
+    assert_eq!(response["error"]["data"]["error_code"], "upstream_unavailable");
+    assert!(response["error"]["data"]["correlation_id"].is_string(), "{response}");
+    assert_eq!(
+        response["error"]["message"],
+        "The server is shutting down; retry once it is back"
+    );
+}
+
+#[tokio::test]
+async fn test_deletes_run_only_with_a_confirmation_token() {
+    let db = Arc::new(MemoryDatabase::new());
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    let transaction = db.insert_transaction(&input, None).await.unwrap();
+    let server = ExaspoonDbServer::new(
+        db.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    )
+    .with_confirmation(Duration::from_secs(60));
+    let mut client = RawClient::connect(server).await;
+    let mut id = 0;
+    let mut delete = |arguments: Value| {
+        id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": "delete_transaction", "arguments": arguments }
+        });
+        (id, request)
+    };
+    let live = || async {
+        db.count_transactions(&Default::default()).await.unwrap()
+    };
+
+    // Ids that do not exist get an error rather than a token.
+    let (id, request) = delete(json!({ "id": "txn-missing" }));
+    client.send(request).await;
+    let (response, _) = client.until_response(id).await;
+    assert_eq!(response["error"]["data"]["error_code"], "not_found", "{response}");
+
+    let (id, request) = delete(json!({ "id": transaction.id }));
+    client.send(request).await;
+    let (response, _) = client.until_response(id).await;
+    let preview = &response["result"]["structuredContent"];
+    assert_eq!(preview["dry_run"], true, "{response}");
+    let token = preview["confirmation"]["token"].as_str().unwrap().to_string();
+    assert_eq!(preview["confirmation"]["expires_in_secs"], 60);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains(&token), "{text}");
+    assert_eq!(live().await, 1);
+
+    // A token only confirms the call it was issued for, and only once.
+    let (id, request) = delete(json!({ "id": "other", "confirmation_token": token }));
+    client.send(request).await;
+    let (response, _) = client.until_response(id).await;
+    assert_eq!(response["error"]["data"]["field"], "confirmation_token");
+    assert_eq!(response["error"]["data"]["refused"], "mismatch");
+    let (id, request) = delete(json!({ "id": transaction.id, "confirmation_token": token }));
+    client.send(request).await;
+    let (response, _) = client.until_response(id).await;
+    assert_eq!(response["error"]["data"]["refused"], "unknown");
+    assert_eq!(live().await, 1);
+
+    let (id, request) = delete(json!({ "id": transaction.id }));
+    client.send(request).await;
+    let (response, _) = client.until_response(id).await;
+    let token = &response["result"]["structuredContent"]["confirmation"]["token"];
+    let (id, request) = delete(json!({ "confirmation_token": token, "id": transaction.id }));
+    client.send(request).await;
+    let (response, _) = client.until_response(id).await;
+    let deleted = &response["result"]["structuredContent"];
+    assert_eq!(deleted["deleted"]["id"], json!(transaction.id), "{response}");
+    assert!(deleted.get("dry_run").is_none());
+    assert!(deleted.get("confirmation").is_none());
+    assert_eq!(live().await, 0);
+}
+
+#[tokio::test]
+async fn test_period_summary_is_narrated_by_the_client_model() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    db.insert_transaction(&input, None).await.unwrap();
+    let server = ExaspoonDbServer::new(db.clone(), embedder);
+    let mut client = RawClient::connect_with(server, json!({ "sampling": {} })).await;
+
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "summarize_period", "arguments": { "month": "2024-01" } }
+        }))
+        .await;
+    let request = client.next_message().await;
+    assert_eq!(request["method"], "sampling/createMessage");
+    let prompt = request["params"]["messages"][0]["content"]["text"]
+        .as_str()
+        .unwrap();
+    assert!(prompt.contains("2024-01"), "{prompt}");
+    assert!(prompt.contains("Coffee"), "{prompt}");
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {
+                "model": "test-model",
+                "role": "assistant",
+                "content": { "type": "text", "text": "A quiet month." }
+            }
+        }))
+        .await;
+    let (response, _) = client.until_response(1).await;
+    let summary = &response["result"]["structuredContent"];
+    assert_eq!(summary["month"], "2024-01");
+    assert_eq!(summary["narrative"], "A quiet month.");
+    assert_eq!(summary["model"], "test-model");
+    assert_eq!(summary["totals"][0]["key"], "expense");
+    assert_eq!(summary["notable_transactions"][0]["description"], "Coffee");
+}
+
+#[tokio::test]
+async fn test_period_summary_without_sampling_returns_the_figures() {
+    let db = Arc::new(MemoryDatabase::new());
+    let embedder = Arc::new(common::MockEmbedder::new(vec![0.3, 0.4]));
+    let mut client = RawClient::connect(ExaspoonDbServer::new(db.clone(), embedder)).await;
+
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "summarize_period", "arguments": { "month": "2024-01" } }
+        }))
+        .await;
+    let (response, notifications) = client.until_response(1).await;
+    let summary = &response["result"]["structuredContent"];
+    assert_eq!(summary["narrative"], Value::Null);
+    assert_eq!(summary["totals"], json!([]));
+    assert!(notifications.is_empty(), "{notifications:?}");
+
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "summarize_period", "arguments": { "month": "January" } }
+        }))
+        .await;
+    let (response, _) = client.until_response(2).await;
+    assert_eq!(response["error"]["data"]["field"], "month");
+}
+
+/// Lists the tools and calls `list_accounts`, returning both results.
+async fn list_and_call(client: &mut RawClient) -> (Value, Value) {
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }))
+        .await;
+    let (tools, _) = client.until_response(1).await;
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "list_accounts", "arguments": {} }
+        }))
+        .await;
+    let (call, _) = client.until_response(2).await;
+    (tools["result"].clone(), call["result"].clone())
+}
+
+#[tokio::test]
+async fn test_older_protocol_revisions_get_results_they_expect() {
+    let server = || {
+        ExaspoonDbServer::new(
+            Arc::new(MemoryDatabase::new()),
+            Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+        )
+    };
+
+    let (mut client, info) = RawClient::handshake(server(), "2025-06-18", json!({})).await;
+    assert_eq!(info["protocolVersion"], "2025-06-18");
+    let (tools, result) = list_and_call(&mut client).await;
+    let list_accounts = tools["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|tool| tool["name"] == "list_accounts")
+        .unwrap();
+    assert!(list_accounts.get("outputSchema").is_some());
+    assert!(list_accounts.get("annotations").is_some());
+    assert!(result.get("structuredContent").is_some());
+
+    let (mut client, info) = RawClient::handshake(server(), "2024-11-05", json!({})).await;
+    assert_eq!(info["protocolVersion"], "2024-11-05");
+    let (tools, result) = list_and_call(&mut client).await;
+    for tool in tools["tools"].as_array().unwrap() {
+        assert!(tool.get("outputSchema").is_none(), "{tool}");
+        assert!(tool.get("annotations").is_none(), "{tool}");
+    }
+    assert!(result.get("structuredContent").is_none(), "{result}");
+    assert_eq!(result["content"][0]["text"], "Showing 0 of 0 accounts");
+    let text = result["content"][1]["text"].as_str().unwrap();
+    assert!(serde_json::from_str::<Value>(text).unwrap()["accounts"].is_array());
+
+    let (_, info) = RawClient::handshake(server(), "2025-03-26", json!({})).await;
+    assert_eq!(info["protocolVersion"], "2025-03-26");
+}
+
+#[tokio::test]
+async fn test_tools_are_listed_and_called_by_their_configured_names() {
+    let aliases = HashMap::from([("ping".to_string(), "finance_alive".to_string())]);
+    let names = ToolNames::new("finance_".to_string(), aliases).unwrap();
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    )
+    .with_tool_names(names);
+    let mut client = RawClient::connect(server).await;
+    let call = |id: u64, name: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": {} }
+        })
+    };
+
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }))
+        .await;
+    let (response, _) = client.until_response(1).await;
+    let names = response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"finance_list_accounts".to_string()), "{names:?}");
+    assert!(names.contains(&"finance_alive".to_string()), "{names:?}");
+    assert!(!names.contains(&"finance_ping".to_string()), "{names:?}");
+    assert!(names.iter().all(|name| name.starts_with("finance_")), "{names:?}");
+
+    client.send(call(2, "finance_list_accounts")).await;
+    let (response, _) = client.until_response(2).await;
+    assert_eq!(response["result"]["content"][0]["text"], "Showing 0 of 0 accounts");
+    client.send(call(3, "finance_alive")).await;
+    let (response, _) = client.until_response(3).await;
+    assert!(response["result"].is_object(), "{response}");
+
+    // Original names are not exposed once a tool has another.
+    for (id, name) in [(4, "list_accounts"), (5, "finance_ping")] {
+        client.send(call(id, name)).await;
+        let (response, _) = client.until_response(id).await;
+        assert_eq!(response["error"]["data"]["error_code"], "not_found", "{response}");
+        assert_eq!(response["error"]["data"]["tool"], name);
+    }
+
+    client.send(call(6, "finance_describe_capabilities")).await;
+    let (response, _) = client.until_response(6).await;
+    let tools = &response["result"]["structuredContent"]["tools"];
+    assert!(tools
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|tool| tool["name"] == "finance_alive"));
+}
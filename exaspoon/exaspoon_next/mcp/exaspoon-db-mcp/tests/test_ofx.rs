@@ -0,0 +1,202 @@
+//! Tests for reading OFX and QFX statements.
+
+use exaspoon_db_mcp::models::TransactionDirection;
+use exaspoon_db_mcp::ofx;
+
+mod common;
+
+/// An OFX 1.x download: a header, then SGML without closing value tags.
+const SGML: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+ENCODING:USASCII
+
+<OFX>
+<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0<SEVERITY>INFO</STATUS></SONRS></SIGNONMSGSRSV1>
+<BANKMSGSRSV1>
+<STMTTRNRS>
+<STMTRS>
+<CURDEF>usd
+<BANKACCTFROM><BANKID>121000248<ACCTID>1234567890<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<DTSTART>20240101
+<DTEND>20240131
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240105120000.000[-5:EST]
+<TRNAMT>-42.50
+<FITID>2024010501
+<NAME>WHOLE FOODS &amp; CO
+<MEMO>Groceries
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240115
+<TRNAMT>3000.00
+<FITID>2024011501
+<NAME>PAYROLL
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>XFER
+<DTPOSTED>20240120
+<TRNAMT>-200
+<FITID>2024012001
+<MEMO>To savings
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS>
+</STMTTRNRS>
+</BANKMSGSRSV1>
+</OFX>
+";
+
+/// An OFX 2.x download, as QFX files from most banks are.
+const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX>
+  <CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+    <CURDEF>CAD</CURDEF>
+    <BANKTRANLIST>
+      <STMTTRN>
+        <TRNTYPE>DEBIT</TRNTYPE>
+        <DTPOSTED>20240302093000</DTPOSTED>
+        <TRNAMT>-5,25</TRNAMT>
+        <FITID>CC-77</FITID>
+        <NAME>Tim Hortons</NAME>
+      </STMTTRN>
+    </BANKTRANLIST>
+  </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>"#;
+
+#[test]
+fn test_parses_sgml_statements() {
+    let transactions = ofx::parse(SGML).unwrap();
+    assert_eq!(transactions.len(), 3);
+
+    let groceries = &transactions[0];
+    assert_eq!(groceries.fitid, "2024010501");
+    assert_eq!(groceries.kind, "DEBIT");
+    assert_eq!(groceries.posted_at, "2024-01-05T17:00:00Z");
+    assert_eq!(groceries.amount, -42.5);
+    assert_eq!(groceries.name.as_deref(), Some("WHOLE FOODS & CO"));
+    assert_eq!(groceries.currency.as_deref(), Some("USD"));
+    assert!(groceries
+        .raw
+        .starts_with("<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>"));
+    assert!(groceries.raw.ends_with("<MEMO>Groceries</STMTTRN>"));
+
+    let input = groceries.to_input("acct-1", "EUR");
+    assert_eq!(input.direction, TransactionDirection::Expense);
+    assert_eq!(input.amount, 42.5);
+    assert_eq!(input.currency, "USD");
+    assert_eq!(
+        input.description.as_deref(),
+        Some("WHOLE FOODS & CO - Groceries")
+    );
+
+    let salary = transactions[1].to_input("acct-1", "");
+    assert_eq!(salary.direction, TransactionDirection::Income);
+    assert_eq!(salary.occurred_at, "2024-01-15T00:00:00Z");
+    let transfer = transactions[2].to_input("acct-1", "");
+    assert_eq!(transfer.direction, TransactionDirection::Transfer);
+    assert_eq!(transfer.description.as_deref(), Some("To savings"));
+}
+
+#[test]
+fn test_parses_xml_statements() {
+    let transactions = ofx::parse(XML).unwrap();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].fitid, "CC-77");
+    assert_eq!(transactions[0].amount, -5.25);
+    assert_eq!(transactions[0].posted_at, "2024-03-02T09:30:00Z");
+    assert_eq!(transactions[0].currency.as_deref(), Some("CAD"));
+}
+
+#[test]
+fn test_fitid_is_read_back_from_the_raw_source() {
+    for transaction in ofx::parse(SGML)
+        .unwrap()
+        .iter()
+        .chain(&ofx::parse(XML).unwrap())
+    {
+        let input = transaction.to_input("acct-1", "");
+        let raw_source = input.raw_source.unwrap();
+        assert_eq!(ofx::fitid(&raw_source), Some(transaction.fitid.clone()));
+    }
+    assert_eq!(ofx::fitid("Card payment <FITID>1"), None);
+}
+
+#[test]
+fn test_rejects_malformed_statements() {
+    assert!(ofx::parse("date,amount\n2024-01-01,5").is_err());
+
+    let err = ofx::parse(&SGML.replace("<FITID>2024011501\n", "")).unwrap_err();
+    assert_eq!(format!("{err:#}"), "statement transaction 2: no FITID");
+    let err = ofx::parse(&SGML.replace("<TRNAMT>-200", "<TRNAMT>lots")).unwrap_err();
+    assert!(format!("{err:#}").contains("TRNAMT"), "{err:#}");
+    assert!(ofx::parse_date("2024-01-05").is_err());
+    assert_eq!(
+        ofx::parse_date("20240105000000[+5.5:IST]").unwrap(),
+        "2024-01-04T18:30:00Z"
+    );
+}
+
+#[cfg(feature = "memory-backend")]
+mod memory {
+    use super::{common, SGML};
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{DryRun, ImportOfxInput, TransactionFilters};
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use rmcp::handler::server::wrapper::Parameters;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_import_ofx_skips_transactions_already_imported() {
+        let database = Arc::new(MemoryDatabase::new());
+        let account = database
+            .upsert_account(&common::sample_account_input())
+            .await
+            .unwrap();
+        let server = ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        );
+        let import = |ofx: String| {
+            let input = ImportOfxInput {
+                account_id: account.id.clone(),
+                ofx,
+                currency: String::new(),
+            };
+            server.import_ofx(Parameters(DryRun::from(input)))
+        };
+
+        let first = import(SGML.to_string()).await.unwrap();
+        let first = first.structured_content.unwrap();
+        assert_eq!(first["transactions"].as_array().unwrap().len(), 3);
+        assert_eq!(first["transactions"][0]["amount"], 42.5);
+        assert_eq!(first["skipped"], serde_json::json!([]));
+
+        // A later download overlaps the first and adds one transaction.
+        let newer = SGML.replace(
+            "</BANKTRANLIST>",
+            "<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240125<TRNAMT>-9.99\
+             <FITID>2024012501<NAME>STREAMING</STMTTRN>\
+             <STMTTRN><TRNTYPE>OTHER<DTPOSTED>20240125<TRNAMT>0.00\
+             <FITID>2024012502<NAME>BALANCE</STMTTRN></BANKTRANLIST>",
+        );
+        let second = import(newer).await.unwrap();
+        let second = second.structured_content.unwrap();
+        let imported = second["transactions"].as_array().unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0]["description"], "STREAMING");
+        let skipped = second["skipped"].as_array().unwrap();
+        assert_eq!(skipped.len(), 4);
+        assert_eq!(skipped[0]["reason"], "duplicate");
+        assert_eq!(skipped[3]["fitid"], "2024012502");
+        assert_eq!(skipped[3]["reason"], "zero_amount");
+
+        let filters = TransactionFilters::default();
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 4);
+    }
+}
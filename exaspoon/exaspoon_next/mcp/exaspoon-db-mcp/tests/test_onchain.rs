@@ -0,0 +1,1119 @@
+//! Tests for syncing the transfers of onchain accounts' Ethereum addresses
+//! through Etherscan, Bitcoin addresses and xpubs through Blockbook, and
+//! Solana wallets through a Solana RPC node.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::config::{BlockbookConfig, EtherscanConfig, HttpClientConfig, SolanaConfig};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    AccountType, Chain, DryRun, ListAccountsInput, SyncOnchainInput, TransactionFilters,
+    UpsertAccountInput,
+};
+use exaspoon_db_mcp::onchain::{
+    self, bitcoin::BlockbookClient, ethereum::EtherscanClient, solana::SolanaClient,
+};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::matchers::{body_partial_json, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+const ADDRESS: &str = "0xd8da6bf26964af9d7eed9e03e53415d37aa96045";
+const OTHER: &str = "0x00000000219ab540356cbb839cbe05303d7705fa";
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+const BTC_ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+const BTC_OTHER: &str = "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh";
+const WALLET: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+const SOL_OTHER: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+
+/// A row of `txlist` with a fee of 21000 gas at 1 gwei.
+fn transaction(hash: &str, block: u64, from: &str, to: &str, value: &str, failed: bool) -> Value {
+    json!({
+        "blockNumber": block.to_string(),
+        "timeStamp": (1_709_251_200 + block % 1000).to_string(),
+        "hash": hash,
+        "from": from,
+        "to": to,
+        "value": value,
+        "gasUsed": "21000",
+        "gasPrice": "1000000000",
+        "isError": if failed { "1" } else { "0" },
+        "contractAddress": "",
+    })
+}
+
+fn token_transfer(hash: &str, block: u64, value: &str) -> Value {
+    json!({
+        "blockNumber": block.to_string(),
+        "timeStamp": (1_709_251_200 + block % 1000).to_string(),
+        "hash": hash,
+        "from": OTHER,
+        "to": ADDRESS,
+        "value": value,
+        "contractAddress": USDC,
+        "tokenSymbol": "USDC",
+        "tokenDecimal": "6",
+        "logIndex": "7",
+    })
+}
+
+fn listed(rows: Vec<Value>) -> ResponseTemplate {
+    let body = if rows.is_empty() {
+        json!({ "status": "0", "message": "No transactions found", "result": [] })
+    } else {
+        json!({ "status": "1", "message": "OK", "result": rows })
+    };
+    ResponseTemplate::new(200).set_body_json(body)
+}
+
+async fn mount(etherscan: &MockServer, action: &str, rows: Vec<Value>) {
+    Mock::given(method("GET"))
+        .and(query_param("action", action))
+        .and(query_param("address", ADDRESS))
+        .and(query_param("apikey", "key-1"))
+        .respond_with(listed(rows))
+        .with_priority(10)
+        .mount(etherscan)
+        .await;
+}
+
+fn wallet(network: Option<&str>) -> UpsertAccountInput {
+    UpsertAccountInput {
+        name: "Wallet".to_string(),
+        r#type: AccountType::Onchain,
+        currency: "ETH".to_string(),
+        network: network.map(str::to_string),
+        institution: None,
+    }
+}
+
+async fn server(etherscan: &MockServer) -> (Arc<MemoryDatabase>, ExaspoonDbServer, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&wallet(Some("ethereum")))
+        .await
+        .unwrap();
+    let client = EtherscanClient::new(
+        &EtherscanConfig {
+            api_key: "key-1".to_string(),
+            base_url: etherscan.uri(),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_etherscan(client);
+    (database, server, account.id)
+}
+
+fn sync_input(account_id: &str, address: Option<&str>) -> SyncOnchainInput {
+    SyncOnchainInput {
+        account_id: account_id.to_string(),
+        address: address.map(str::to_string),
+    }
+}
+
+#[test]
+fn test_base_units_and_addresses() {
+    assert_eq!(onchain::units("1500000000000000000", 18).unwrap(), 1.5);
+    assert_eq!(onchain::units("25500000", 6).unwrap(), 25.5);
+    assert_eq!(onchain::units("0", 18).unwrap(), 0.0);
+    assert!(onchain::units("-1", 18).is_err());
+    assert!(onchain::units("1e18", 18).is_err());
+
+    let mixed_case = "0xD8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+    assert_eq!(
+        onchain::ethereum::parse_address(mixed_case).unwrap(),
+        ADDRESS
+    );
+    assert!(onchain::ethereum::parse_address("0xd8da6bf2").is_err());
+    assert_eq!(onchain::chain_of_address(BTC_ADDRESS), Some(Chain::Bitcoin));
+    assert_eq!(onchain::chain_of_address(XPUB), Some(Chain::Bitcoin));
+    assert_eq!(
+        onchain::bitcoin::parse_address(&BTC_ADDRESS.to_ascii_uppercase()).unwrap(),
+        BTC_ADDRESS
+    );
+    assert!(onchain::bitcoin::parse_address("1BoatSLRHtKNngkdXEeobR76b53LETtpyT").is_ok());
+    assert!(onchain::bitcoin::parse_address("bc1q0").is_err());
+    assert_eq!(onchain::chain_of_address(WALLET), Some(Chain::Solana));
+    assert!(
+        onchain::solana::parse_address("9WzDXwBbmkg8ZTbN0qUxvQRAyrZzDsGYdLVL9zYtAWWM").is_err()
+    );
+    assert_eq!(onchain::solana::symbol(USDC_MINT), "USDC");
+    assert_eq!(onchain::solana::symbol(WALLET), WALLET);
+    assert!(onchain::chain_of_address("not-an-address").is_none());
+
+    assert_eq!(
+        onchain::entry_id(r#"{"chain":"ethereum","transfer":"0xabc:fee","source":{}}"#).as_deref(),
+        Some("ethereum:0xabc:fee")
+    );
+    assert_eq!(onchain::entry_id(r#"{"transaction_id":"plaid-1"}"#), None);
+}
+
+#[tokio::test]
+async fn test_sync_records_transfers_and_fees_once() {
+    let etherscan = MockServer::start().await;
+    mount(
+        &etherscan,
+        "txlist",
+        vec![
+            transaction(
+                "0xaaa",
+                19_000_000,
+                OTHER,
+                ADDRESS,
+                "1000000000000000000",
+                false,
+            ),
+            transaction(
+                "0xbbb",
+                19_000_001,
+                ADDRESS,
+                OTHER,
+                "500000000000000000",
+                false,
+            ),
+            transaction("0xccc", 19_000_002, ADDRESS, USDC, "0", true),
+        ],
+    )
+    .await;
+    // The second sync reads again from the block the first one stopped at.
+    Mock::given(method("GET"))
+        .and(query_param("action", "txlist"))
+        .and(query_param("startblock", "19000002"))
+        .respond_with(listed(vec![transaction(
+            "0xccc", 19_000_002, ADDRESS, USDC, "0", true,
+        )]))
+        .with_priority(1)
+        .mount(&etherscan)
+        .await;
+    mount(&etherscan, "txlistinternal", vec![]).await;
+    mount(
+        &etherscan,
+        "tokentx",
+        vec![token_transfer("0xddd", 19_000_001, "25500000")],
+    )
+    .await;
+    let (database, server, account_id) = server(&etherscan).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some("0xD8dA6BF26964aF9D7eEd9e03E53415D37aA96045"),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    let summary: Vec<_> = recorded
+        .iter()
+        .map(|transaction| {
+            (
+                transaction["direction"].as_str().unwrap(),
+                transaction["amount"].as_f64().unwrap(),
+                transaction["currency"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("income", 1.0, "ETH"),
+            ("expense", 0.5, "ETH"),
+            ("expense", 0.000021, "ETH"),
+            ("income", 25.5, "USDC"),
+            ("expense", 0.000021, "ETH"),
+        ]
+    );
+    assert_eq!(recorded[1]["description"], format!("ETH to {OTHER}"));
+    assert_eq!(recorded[3]["description"], format!("USDC from {OTHER}"));
+    assert_eq!(
+        synced["skipped"],
+        json!([{ "transfer_id": "0xccc", "reason": "failed" }])
+    );
+    assert_eq!(synced["complete"], true);
+    assert_eq!(synced["link"]["address"], ADDRESS);
+    assert_eq!(synced["link"]["synced_block"], 19_000_002);
+
+    // The address is kept, so later syncs need none.
+    let again = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(&account_id, None))))
+        .await
+        .unwrap();
+    let again = again.structured_content.unwrap();
+    assert!(again["transactions"].as_array().unwrap().is_empty());
+    assert_eq!(
+        again["skipped"],
+        json!([
+            { "transfer_id": "0xddd:token:7", "reason": "duplicate" },
+            { "transfer_id": "0xccc", "reason": "failed" },
+            { "transfer_id": "0xccc:fee", "reason": "duplicate" },
+        ])
+    );
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 5);
+    let metadata = database.account_metadata(&account_id).await.unwrap();
+    assert_eq!(metadata["onchain"]["chain"], "ethereum");
+    assert_eq!(metadata["onchain"]["synced_block"], 19_000_002);
+}
+
+#[tokio::test]
+async fn test_a_full_page_stops_every_list_at_its_last_block() {
+    let etherscan = MockServer::start().await;
+    let page = (0..1000)
+        .map(|n| transaction(&format!("0x{n:x}"), 100 + n, OTHER, ADDRESS, "0", false))
+        .collect();
+    mount(&etherscan, "txlist", page).await;
+    mount(&etherscan, "txlistinternal", vec![]).await;
+    mount(
+        &etherscan,
+        "tokentx",
+        vec![token_transfer("0xeee", 2000, "1000000")],
+    )
+    .await;
+    let (database, server, account_id) = server(&etherscan).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(ADDRESS),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    // The token transfer lies past the page and waits for the next sync.
+    assert!(synced["transactions"].as_array().unwrap().is_empty());
+    assert_eq!(synced["skipped"].as_array().unwrap().len(), 1000);
+    assert_eq!(synced["skipped"][0]["reason"], "zero_amount");
+    assert_eq!(synced["complete"], false);
+    assert_eq!(synced["link"]["synced_block"], 1099);
+    let metadata = database.account_metadata(&account_id).await.unwrap();
+    assert_eq!(metadata["onchain"]["synced_block"], 1099);
+}
+
+#[tokio::test]
+async fn test_a_full_page_within_one_block_reads_the_next_page() {
+    let etherscan = MockServer::start().await;
+    let page = (0..1000)
+        .map(|n| transaction(&format!("0x{n:x}"), 500, OTHER, ADDRESS, "0", false))
+        .collect();
+    mount(&etherscan, "txlist", page).await;
+    Mock::given(method("GET"))
+        .and(query_param("action", "txlist"))
+        .and(query_param("page", "2"))
+        .respond_with(listed(vec![
+            transaction("0xf00", 500, OTHER, ADDRESS, "1000000000000000000", false),
+            transaction("0xf01", 501, OTHER, ADDRESS, "2000000000000000000", false),
+        ]))
+        .with_priority(1)
+        .mount(&etherscan)
+        .await;
+    mount(&etherscan, "txlistinternal", vec![]).await;
+    mount(&etherscan, "tokentx", vec![]).await;
+    let (_, server, account_id) = server(&etherscan).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(ADDRESS),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let amounts: Vec<_> = synced["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transaction| transaction["amount"].as_f64().unwrap())
+        .collect();
+    assert_eq!(amounts, vec![1.0, 2.0]);
+    assert_eq!(synced["complete"], true);
+    assert_eq!(synced["link"]["synced_block"], 501);
+}
+
+#[tokio::test]
+async fn test_spam_tokens_are_skipped() {
+    let etherscan = MockServer::start().await;
+    mount(&etherscan, "txlist", vec![]).await;
+    mount(&etherscan, "txlistinternal", vec![]).await;
+    let mut counterfeit = token_transfer("0xbad", 600, "5000000000");
+    counterfeit["contractAddress"] = json!(OTHER);
+    counterfeit["tokenSymbol"] = json!("USDT");
+    let mut lure = token_transfer("0xbee", 601, "1000000");
+    lure["contractAddress"] = json!(OTHER);
+    lure["tokenSymbol"] = json!("Visit usdc-claim.com");
+    mount(
+        &etherscan,
+        "tokentx",
+        vec![counterfeit, lure, token_transfer("0xddd", 602, "25500000")],
+    )
+    .await;
+    let (_, server, account_id) = server(&etherscan).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(ADDRESS),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0]["currency"], "USDC");
+    assert_eq!(
+        synced["skipped"],
+        json!([
+            { "transfer_id": "0xbad:token:7", "reason": "spam" },
+            { "transfer_id": "0xbee:token:7", "reason": "spam" },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_sync_needs_an_onchain_account_with_an_address() {
+    let etherscan = MockServer::start().await;
+    let (database, server, account_id) = server(&etherscan).await;
+
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(&account_id, None))))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("address"), "{}", err.message);
+
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some("0xd8da6bf2"),
+        ))))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("Ethereum address"), "{}", err.message);
+
+    let tron = database
+        .upsert_account(&UpsertAccountInput {
+            name: "TronLink".to_string(),
+            ..wallet(Some("tron"))
+        })
+        .await
+        .unwrap();
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &tron.id,
+            Some(ADDRESS),
+        ))))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("tron"), "{}", err.message);
+
+    let checking = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &checking.id,
+            Some(ADDRESS),
+        ))))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("offchain"), "{}", err.message);
+    assert!(database
+        .account_metadata(&checking.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_etherscan_errors_carry_their_message() {
+    let etherscan = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "status": "0",
+            "message": "NOTOK",
+            "result": "Invalid API Key",
+        })))
+        .mount(&etherscan)
+        .await;
+    let (_, server, account_id) = server(&etherscan).await;
+
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(ADDRESS),
+        ))))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["etherscan"]["message"], "NOTOK");
+    assert_eq!(data["etherscan"]["result"], "Invalid API Key");
+}
+
+/// A transaction Blockbook details, with its inputs and outputs as
+/// `(address, satoshi)` and the wallet's own ones marked.
+fn bitcoin_transaction(
+    txid: &str,
+    block: i64,
+    vin: &[(&str, u64)],
+    vout: &[(&str, u64)],
+    fees: u64,
+) -> Value {
+    let side = |sides: &[(&str, u64)]| {
+        sides
+            .iter()
+            .map(|(address, value)| {
+                json!({
+                    "addresses": [address],
+                    "value": value.to_string(),
+                    "isOwn": *address != BTC_OTHER,
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    json!({
+        "txid": txid,
+        "vin": side(vin),
+        "vout": side(vout),
+        "blockHeight": block,
+        "blockTime": 1_709_251_200 + block.max(0) % 1000,
+        "fees": fees.to_string(),
+    })
+}
+
+fn bitcoin_page(page: u32, total_pages: u32, transactions: Vec<Value>) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "page": page,
+        "totalPages": total_pages,
+        "itemsOnPage": 1000,
+        "transactions": transactions,
+    }))
+}
+
+async fn bitcoin_server(blockbook: &MockServer) -> (Arc<MemoryDatabase>, ExaspoonDbServer, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&UpsertAccountInput {
+            currency: "BTC".to_string(),
+            ..wallet(Some("bitcoin"))
+        })
+        .await
+        .unwrap();
+    let client = BlockbookClient::new(
+        &BlockbookConfig {
+            base_url: format!("{}/", blockbook.uri()),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_blockbook(client);
+    (database, server, account.id)
+}
+
+#[tokio::test]
+async fn test_bitcoin_sync_records_confirmed_transactions_and_fees() {
+    let blockbook = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v2/address/{BTC_ADDRESS}")))
+        .and(query_param("details", "txs"))
+        .and(query_param("from", "0"))
+        .respond_with(bitcoin_page(
+            1,
+            1,
+            vec![
+                // Blockbook lists the newest first.
+                bitcoin_transaction(
+                    "c3",
+                    -1,
+                    &[(BTC_OTHER, 9_000)],
+                    &[(BTC_ADDRESS, 5_000)],
+                    4_000,
+                ),
+                bitcoin_transaction(
+                    "b2",
+                    800_001,
+                    &[(BTC_ADDRESS, 100_000)],
+                    &[(BTC_OTHER, 60_000), (BTC_ADDRESS, 30_000)],
+                    10_000,
+                ),
+                bitcoin_transaction(
+                    "a1",
+                    800_000,
+                    &[(BTC_OTHER, 150_000)],
+                    &[(BTC_ADDRESS, 100_000), (BTC_OTHER, 45_000)],
+                    5_000,
+                ),
+            ],
+        ))
+        .mount(&blockbook)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v2/address/{BTC_ADDRESS}")))
+        .and(query_param("from", "800001"))
+        .respond_with(bitcoin_page(1, 1, vec![]))
+        .mount(&blockbook)
+        .await;
+    let (database, server, account_id) = bitcoin_server(&blockbook).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(BTC_ADDRESS),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    let summary: Vec<_> = recorded
+        .iter()
+        .map(|transaction| {
+            (
+                transaction["direction"].as_str().unwrap(),
+                transaction["amount"].as_f64().unwrap(),
+                transaction["description"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    let from = format!("BTC from {BTC_OTHER}");
+    let to = format!("BTC to {BTC_OTHER}");
+    assert_eq!(
+        summary,
+        vec![
+            ("income", 0.001, from.as_str()),
+            ("expense", 0.0006, to.as_str()),
+            ("expense", 0.0001, "BTC network fee"),
+        ]
+    );
+    assert!(recorded
+        .iter()
+        .all(|transaction| transaction["currency"] == "BTC"));
+    assert_eq!(
+        synced["skipped"],
+        json!([
+            { "transfer_id": "c3", "reason": "pending" },
+        ])
+    );
+    assert_eq!(synced["complete"], true);
+    assert_eq!(synced["link"]["chain"], "bitcoin");
+    assert_eq!(synced["link"]["synced_block"], 800_001);
+
+    // The next sync reads on from the last confirmed block.
+    let again = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(&account_id, None))))
+        .await
+        .unwrap();
+    let again = again.structured_content.unwrap();
+    assert!(again["transactions"].as_array().unwrap().is_empty());
+    assert_eq!(again["link"]["synced_block"], 800_001);
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_bitcoin_transactions_without_a_block_time_get_their_blocks() {
+    let blockbook = MockServer::start().await;
+    let mut zero = bitcoin_transaction(
+        "d4",
+        800_002,
+        &[(BTC_OTHER, 20_000)],
+        &[(BTC_ADDRESS, 10_000)],
+        1_000,
+    );
+    zero["blockTime"] = json!(0);
+    let mut missing = bitcoin_transaction(
+        "d5",
+        800_002,
+        &[(BTC_OTHER, 20_000)],
+        &[(BTC_ADDRESS, 15_000)],
+        1_000,
+    );
+    missing.as_object_mut().unwrap().remove("blockTime");
+    let mut unconfirmed = bitcoin_transaction(
+        "d6",
+        0,
+        &[(BTC_OTHER, 20_000)],
+        &[(BTC_ADDRESS, 5_000)],
+        1_000,
+    );
+    unconfirmed["blockTime"] = json!(0);
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v2/address/{BTC_ADDRESS}")))
+        .respond_with(bitcoin_page(1, 1, vec![unconfirmed, missing, zero]))
+        .mount(&blockbook)
+        .await;
+    // Both transactions are in the block, which is asked for once.
+    Mock::given(method("GET"))
+        .and(path("/api/v2/block/800002"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "height": 800_002, "time": 1_709_337_600 })),
+        )
+        .expect(1)
+        .mount(&blockbook)
+        .await;
+    let (_, server, account_id) = bitcoin_server(&blockbook).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(BTC_ADDRESS),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded.iter().all(|transaction| transaction["occurred_at"]
+        .as_str()
+        .unwrap()
+        .starts_with("2024-03-02")));
+    assert_eq!(
+        synced["skipped"],
+        json!([{ "transfer_id": "d6", "reason": "pending" }])
+    );
+}
+
+#[tokio::test]
+async fn test_bitcoin_xpub_reads_every_page_and_its_own_moves() {
+    let blockbook = MockServer::start().await;
+    let xpub_path = format!("/api/v2/xpub/{XPUB}");
+    Mock::given(method("GET"))
+        .and(path(xpub_path.clone()))
+        .and(query_param("page", "1"))
+        .respond_with(bitcoin_page(
+            1,
+            2,
+            vec![bitcoin_transaction(
+                "e5",
+                800_010,
+                &[("bc1qownaddress0", 50_000)],
+                &[("bc1qownaddress1", 48_000)],
+                2_000,
+            )],
+        ))
+        .mount(&blockbook)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(xpub_path))
+        .and(query_param("page", "2"))
+        .respond_with(bitcoin_page(
+            2,
+            2,
+            vec![bitcoin_transaction(
+                "d4",
+                800_005,
+                &[(BTC_OTHER, 60_000)],
+                &[("bc1qownaddress0", 50_000)],
+                10_000,
+            )],
+        ))
+        .mount(&blockbook)
+        .await;
+    let (_, server, account_id) = bitcoin_server(&blockbook).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(XPUB),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    let summary: Vec<_> = recorded
+        .iter()
+        .map(|transaction| {
+            (
+                transaction["direction"].as_str().unwrap(),
+                transaction["amount"].as_f64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("income", 0.0005),
+            ("transfer", 0.00048),
+            ("expense", 0.00002)
+        ]
+    );
+    assert_eq!(synced["link"]["address"], XPUB);
+    assert_eq!(synced["link"]["synced_block"], 800_010);
+}
+
+#[tokio::test]
+async fn test_blockbook_errors_carry_their_status_and_error() {
+    let blockbook = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": "Invalid address, decoded address is of unknown format",
+        })))
+        .mount(&blockbook)
+        .await;
+    let (_, server, account_id) = bitcoin_server(&blockbook).await;
+
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(BTC_ADDRESS),
+        ))))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["blockbook"]["status_code"], 400);
+    assert_eq!(
+        data["blockbook"]["error"],
+        "Invalid address, decoded address is of unknown format"
+    );
+}
+
+fn token_balance(index: u64, mint: &str, owner: &str, amount: &str, decimals: u32) -> Value {
+    json!({
+        "accountIndex": index,
+        "mint": mint,
+        "owner": owner,
+        "uiTokenAmount": { "amount": amount, "decimals": decimals },
+    })
+}
+
+/// A `getTransaction` result in `jsonParsed` encoding, with the balances of
+/// `keys` in lamports before and after it.
+fn solana_transaction(
+    keys: &[&str],
+    balances: (&[u64], &[u64]),
+    token_balances: (Vec<Value>, Vec<Value>),
+) -> Value {
+    json!({
+        "slot": 1,
+        "blockTime": 1_709_251_200,
+        "meta": {
+            "err": null,
+            "fee": 5000,
+            "preBalances": balances.0,
+            "postBalances": balances.1,
+            "preTokenBalances": token_balances.0,
+            "postTokenBalances": token_balances.1,
+            "logMessages": ["Program log: Instruction: Transfer"],
+        },
+        "transaction": {
+            "message": {
+                "accountKeys": keys
+                    .iter()
+                    .map(|key| json!({ "pubkey": key, "signer": false, "writable": true }))
+                    .collect::<Vec<_>>(),
+            },
+            "signatures": [],
+        },
+    })
+}
+
+fn rpc_result(result: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+}
+
+async fn mount_rpc(solana: &MockServer, request: Value, result: Value) {
+    Mock::given(method("POST"))
+        .and(body_partial_json(request))
+        .respond_with(rpc_result(result))
+        .mount(solana)
+        .await;
+}
+
+/// Signatures as `getSignaturesForAddress` lists them, newest first.
+fn signatures(slots: &[(&str, u64)]) -> Value {
+    slots
+        .iter()
+        .rev()
+        .map(|(signature, slot)| {
+            json!({
+                "signature": signature,
+                "slot": slot,
+                "blockTime": 1_709_251_200 + slot % 1000,
+                "err": null,
+                "confirmationStatus": "finalized",
+            })
+        })
+        .collect()
+}
+
+async fn solana_server(solana: &MockServer) -> (Arc<MemoryDatabase>, ExaspoonDbServer, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&UpsertAccountInput {
+            currency: "SOL".to_string(),
+            ..wallet(None)
+        })
+        .await
+        .unwrap();
+    let client = SolanaClient::new(
+        &SolanaConfig {
+            rpc_url: solana.uri(),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_solana(client);
+    (database, server, account.id)
+}
+
+#[tokio::test]
+async fn test_solana_sync_records_sol_and_token_changes() {
+    let solana = MockServer::start().await;
+    mount_rpc(
+        &solana,
+        json!({ "method": "getSignaturesForAddress", "params": [WALLET] }),
+        signatures(&[
+            ("sigA", 250_000_000),
+            ("sigB", 250_000_100),
+            ("sigC", 250_000_200),
+        ]),
+    )
+    .await;
+    // Another wallet pays the fee and sends 1.5 SOL.
+    mount_rpc(
+        &solana,
+        json!({ "method": "getTransaction", "params": ["sigA"] }),
+        solana_transaction(
+            &[SOL_OTHER, WALLET, "11111111111111111111111111111111"],
+            (
+                &[10_000_000_000, 1_000_000_000, 1],
+                &[8_499_995_000, 2_500_000_000, 1],
+            ),
+            (vec![], vec![]),
+        ),
+    )
+    .await;
+    // The wallet pays the fee and sends 25.5 USDC.
+    mount_rpc(
+        &solana,
+        json!({ "method": "getTransaction", "params": ["sigB"] }),
+        solana_transaction(
+            &[
+                WALLET,
+                "WalletUsdcAccount1111111111111111111111111",
+                "OtherUsdcAccount11111111111111111111111111",
+            ],
+            (
+                &[1_000_000_000, 2_039_280, 2_039_280],
+                &[999_995_000, 2_039_280, 2_039_280],
+            ),
+            (
+                vec![
+                    token_balance(1, USDC_MINT, WALLET, "100000000", 6),
+                    token_balance(2, USDC_MINT, SOL_OTHER, "0", 6),
+                ],
+                vec![
+                    token_balance(1, USDC_MINT, WALLET, "74500000", 6),
+                    token_balance(2, USDC_MINT, SOL_OTHER, "25500000", 6),
+                ],
+            ),
+        ),
+    )
+    .await;
+    // A token the wallet had no account for yet, by its mint.
+    let mint = "MoonMint1111111111111111111111111111111111";
+    mount_rpc(
+        &solana,
+        json!({ "method": "getTransaction", "params": ["sigC"] }),
+        solana_transaction(
+            &[SOL_OTHER, "WalletMoonAccount111111111111111111111111"],
+            (&[5_000_000_000, 0], &[4_997_955_720, 2_039_280]),
+            (
+                vec![token_balance(2, mint, SOL_OTHER, "5000", 0)],
+                vec![
+                    token_balance(1, mint, WALLET, "1000", 0),
+                    token_balance(2, mint, SOL_OTHER, "4000", 0),
+                ],
+            ),
+        ),
+    )
+    .await;
+    // The next sync lists back to the newest transaction this one read.
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({
+            "method": "getSignaturesForAddress",
+            "params": [WALLET, { "until": "sigC" }],
+        })))
+        .respond_with(rpc_result(json!([])))
+        .with_priority(1)
+        .expect(1)
+        .mount(&solana)
+        .await;
+    let (database, server, account_id) = solana_server(&solana).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(WALLET),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    let summary: Vec<_> = recorded
+        .iter()
+        .map(|transaction| {
+            (
+                transaction["direction"].as_str().unwrap(),
+                transaction["amount"].as_f64().unwrap(),
+                transaction["currency"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("income", 1.5, "SOL"),
+            ("expense", 25.5, "USDC"),
+            ("expense", 0.000005, "SOL"),
+            ("income", 1000.0, mint),
+        ]
+    );
+    assert_eq!(recorded[0]["description"], format!("SOL from {SOL_OTHER}"));
+    assert_eq!(recorded[1]["description"], format!("USDC to {SOL_OTHER}"));
+    assert_eq!(synced["complete"], true);
+    assert_eq!(synced["link"]["chain"], "solana");
+    assert_eq!(synced["link"]["synced_block"], 250_000_200);
+    assert_eq!(synced["link"]["synced_signature"], "sigC");
+
+    // The chain is recorded on the account, which had no network.
+    let accounts = database
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    let account = accounts
+        .iter()
+        .find(|account| account.id == account_id)
+        .unwrap();
+    assert_eq!(account.network.as_deref(), Some("solana"));
+
+    let again = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(&account_id, None))))
+        .await
+        .unwrap();
+    let again = again.structured_content.unwrap();
+    assert!(again["transactions"].as_array().unwrap().is_empty());
+    assert!(again["skipped"].as_array().unwrap().is_empty());
+    assert_eq!(again["link"]["synced_signature"], "sigC");
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 4);
+}
+
+#[tokio::test]
+async fn test_solana_sync_reads_a_page_of_transactions_at_a_time() {
+    let solana = MockServer::start().await;
+    let listed: Vec<_> = (0..150).map(|n| (format!("sig{n}"), 1000 + n)).collect();
+    let listed: Vec<_> = listed
+        .iter()
+        .map(|(signature, slot)| (signature.as_str(), *slot))
+        .collect();
+    mount_rpc(
+        &solana,
+        json!({ "method": "getSignaturesForAddress" }),
+        signatures(&listed),
+    )
+    .await;
+    // Each transaction only pays its fee.
+    mount_rpc(
+        &solana,
+        json!({ "method": "getTransaction" }),
+        solana_transaction(&[WALLET], (&[1_000_000], &[995_000]), (vec![], vec![])),
+    )
+    .await;
+    let (_, server, account_id) = solana_server(&solana).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(WALLET),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let recorded = synced["transactions"].as_array().unwrap();
+    assert_eq!(recorded.len(), 100);
+    assert!(recorded
+        .iter()
+        .all(|transaction| transaction["description"] == "SOL network fee"));
+    assert_eq!(synced["complete"], false);
+    assert_eq!(synced["link"]["synced_block"], 1099);
+}
+
+#[tokio::test]
+async fn test_solana_transactions_without_a_block_time_ask_for_it() {
+    let solana = MockServer::start().await;
+    let mut listed = signatures(&[("sigD", 250_000_300)]);
+    listed[0]["blockTime"] = Value::Null;
+    mount_rpc(
+        &solana,
+        json!({ "method": "getSignaturesForAddress" }),
+        listed,
+    )
+    .await;
+    let mut transaction =
+        solana_transaction(&[WALLET], (&[1_000_000], &[995_000]), (vec![], vec![]));
+    transaction["blockTime"] = json!(0);
+    mount_rpc(&solana, json!({ "method": "getTransaction" }), transaction).await;
+    mount_rpc(
+        &solana,
+        json!({ "method": "getBlockTime", "params": [250_000_300] }),
+        json!(1_709_337_600),
+    )
+    .await;
+    let (_, server, account_id) = solana_server(&solana).await;
+
+    let synced = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(WALLET),
+        ))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    assert_eq!(
+        synced["transactions"][0]["occurred_at"],
+        "2024-03-02T00:00:00Z"
+    );
+}
+
+#[tokio::test]
+async fn test_solana_rpc_errors_carry_their_code() {
+    let solana = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32005, "message": "Too many requests" },
+        })))
+        .mount(&solana)
+        .await;
+    let (_, server, account_id) = solana_server(&solana).await;
+
+    let err = server
+        .sync_onchain(Parameters(DryRun::from(sync_input(
+            &account_id,
+            Some(WALLET),
+        ))))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["solana"]["code"], -32005);
+    assert_eq!(data["solana"]["message"], "Too many requests");
+}
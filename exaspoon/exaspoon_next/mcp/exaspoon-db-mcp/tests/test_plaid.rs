@@ -0,0 +1,355 @@
+//! Tests for linking accounts to banks and syncing them through Plaid.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::config::{HttpClientConfig, PlaidConfig};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    DryRun, LinkBankAccountInput, SyncBankAccountInput, TransactionDirection, TransactionFilters,
+};
+use exaspoon_db_mcp::plaid::{self, PlaidClient};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+fn plaid_transaction(id: &str, amount: f64, date: &str) -> Value {
+    json!({
+        "transaction_id": id,
+        "account_id": "plaid-checking",
+        "amount": amount,
+        "iso_currency_code": "USD",
+        "unofficial_currency_code": null,
+        "date": date,
+        "datetime": null,
+        "authorized_datetime": null,
+        "name": "CORNER CAFE 1234",
+        "merchant_name": "Corner Cafe",
+        "pending": false,
+        "personal_finance_category": { "primary": "FOOD_AND_DRINK", "detailed": "FOOD_AND_DRINK_COFFEE" },
+    })
+}
+
+fn sync_page(added: Vec<Value>, next_cursor: &str, has_more: bool) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "added": added,
+        "modified": [],
+        "removed": [],
+        "next_cursor": next_cursor,
+        "has_more": has_more,
+        "request_id": "req-1",
+    }))
+}
+
+fn client(base_url: String) -> PlaidClient {
+    PlaidClient::new(
+        &PlaidConfig {
+            client_id: "client-1".to_string(),
+            secret: "secret-1".to_string(),
+            base_url,
+            token_key: [7; 32],
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap()
+}
+
+async fn server(plaid: &MockServer) -> (Arc<MemoryDatabase>, ExaspoonDbServer, String) {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_plaid(client(plaid.uri()));
+    (database, server, account.id)
+}
+
+fn link_input(account_id: &str) -> LinkBankAccountInput {
+    LinkBankAccountInput {
+        account_id: account_id.to_string(),
+        public_token: Some("public-sandbox-1".to_string()),
+        access_token: None,
+        plaid_account_id: Some("plaid-checking".to_string()),
+    }
+}
+
+fn sync_input(account_id: &str) -> SyncBankAccountInput {
+    SyncBankAccountInput {
+        account_id: account_id.to_string(),
+        currency: String::new(),
+    }
+}
+
+#[test]
+fn test_plaid_transactions_map_to_transaction_inputs() {
+    let mut raw = plaid_transaction("tx-1", 12.5, "2024-01-05");
+    let mut transaction: plaid::PlaidTransaction = serde_json::from_value(raw.clone()).unwrap();
+    transaction.raw = raw.clone();
+    let input = transaction.to_input("acct-1", "EUR").unwrap();
+    assert_eq!(input.amount, 12.5);
+    assert_eq!(input.currency, "USD");
+    assert_eq!(input.direction, TransactionDirection::Expense);
+    assert_eq!(input.occurred_at, "2024-01-05T00:00:00Z");
+    assert_eq!(input.description.as_deref(), Some("Corner Cafe"));
+    let raw_source = input.raw_source.unwrap();
+    assert_eq!(plaid::transaction_id(&raw_source).as_deref(), Some("tx-1"));
+    assert_eq!(plaid::transaction_id("<STMTTRN><FITID>1</STMTTRN>"), None);
+
+    raw["amount"] = json!(-3000.0);
+    raw["datetime"] = json!("2024-01-15T09:30:00-05:00");
+    raw["iso_currency_code"] = Value::Null;
+    raw["merchant_name"] = Value::Null;
+    let transaction: plaid::PlaidTransaction = serde_json::from_value(raw.clone()).unwrap();
+    let input = transaction.to_input("acct-1", "EUR").unwrap();
+    assert_eq!(input.direction, TransactionDirection::Income);
+    assert_eq!(input.amount, 3000.0);
+    assert_eq!(input.currency, "EUR");
+    assert_eq!(input.occurred_at, "2024-01-15T14:30:00Z");
+    assert_eq!(input.description.as_deref(), Some("CORNER CAFE 1234"));
+
+    raw["personal_finance_category"] = json!({ "primary": "TRANSFER_IN" });
+    let transaction: plaid::PlaidTransaction = serde_json::from_value(raw).unwrap();
+    let input = transaction.to_input("acct-1", "EUR").unwrap();
+    assert_eq!(input.direction, TransactionDirection::Transfer);
+}
+
+#[tokio::test]
+async fn test_sync_records_new_transactions_once() {
+    let plaid = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/item/public_token/exchange"))
+        .and(body_partial_json(json!({
+            "client_id": "client-1",
+            "secret": "secret-1",
+            "public_token": "public-sandbox-1",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access-sandbox-1",
+            "item_id": "item-1",
+            "request_id": "req-0",
+        })))
+        .expect(1)
+        .mount(&plaid)
+        .await;
+    let mut pending = plaid_transaction("tx-pending", 7.0, "2024-01-06");
+    pending["pending"] = json!(true);
+    let mut savings = plaid_transaction("tx-savings", 100.0, "2024-01-06");
+    savings["account_id"] = json!("plaid-savings");
+    // The first sync reads two pages; the second starts at the cursor the
+    // first ended with and gets one transaction it already has.
+    Mock::given(method("POST"))
+        .and(path("/transactions/sync"))
+        .and(body_partial_json(
+            json!({ "access_token": "access-sandbox-1", "cursor": "page-2" }),
+        ))
+        .respond_with(sync_page(
+            vec![plaid_transaction("tx-2", -3000.0, "2024-01-15"), pending],
+            "cursor-1",
+            false,
+        ))
+        .mount(&plaid)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/transactions/sync"))
+        .and(body_partial_json(json!({ "cursor": "cursor-1" })))
+        .respond_with(sync_page(
+            vec![plaid_transaction("tx-1", 12.5, "2024-01-05")],
+            "cursor-2",
+            false,
+        ))
+        .mount(&plaid)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/transactions/sync"))
+        .respond_with(sync_page(
+            vec![plaid_transaction("tx-1", 12.5, "2024-01-05"), savings],
+            "page-2",
+            true,
+        ))
+        .with_priority(10)
+        .mount(&plaid)
+        .await;
+    let (database, server, account_id) = server(&plaid).await;
+
+    let linked = server
+        .link_bank_account(Parameters(DryRun::from(link_input(&account_id))))
+        .await
+        .unwrap();
+    let linked = linked.structured_content.unwrap();
+    assert_eq!(linked["item_id"], "item-1");
+    assert_eq!(linked["replaced"], false);
+    assert!(!linked.to_string().contains("access-sandbox-1"));
+    let link = database.bank_link(&account_id).await.unwrap().unwrap();
+    assert!(!link.access_token.contains("access-sandbox-1"));
+    let opened = client(plaid.uri()).open(&account_id, &link.access_token);
+    assert_eq!(opened.unwrap(), "access-sandbox-1");
+    assert_eq!(link.cursor, None);
+    assert!(!format!("{link:?}").contains("access-sandbox-1"));
+
+    let planned = server
+        .sync_bank_account(Parameters(DryRun {
+            input: sync_input(&account_id),
+            dry_run: Some(true),
+        }))
+        .await
+        .unwrap();
+    let planned = planned.structured_content.unwrap();
+    assert_eq!(planned["transactions"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        database
+            .bank_link(&account_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .cursor,
+        None
+    );
+
+    let synced = server
+        .sync_bank_account(Parameters(DryRun::from(sync_input(&account_id))))
+        .await
+        .unwrap();
+    let synced = synced.structured_content.unwrap();
+    let transactions = synced["transactions"].as_array().unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0]["amount"], 12.5);
+    assert_eq!(transactions[0]["direction"], "expense");
+    assert_eq!(transactions[1]["direction"], "income");
+    assert_eq!(
+        synced["skipped"],
+        json!([{ "transaction_id": "tx-pending", "reason": "pending" }])
+    );
+    let link = database.bank_link(&account_id).await.unwrap().unwrap();
+    assert_eq!(link.cursor.as_deref(), Some("cursor-1"));
+
+    let again = server
+        .sync_bank_account(Parameters(DryRun::from(sync_input(&account_id))))
+        .await
+        .unwrap();
+    let again = again.structured_content.unwrap();
+    assert_eq!(again["transactions"], json!([]));
+    assert_eq!(
+        again["skipped"],
+        json!([{ "transaction_id": "tx-1", "reason": "duplicate" }])
+    );
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 2);
+    let link = database.bank_link(&account_id).await.unwrap().unwrap();
+    assert_eq!(link.cursor.as_deref(), Some("cursor-2"));
+}
+
+#[test]
+fn test_access_tokens_are_sealed_to_their_account() {
+    let client = client(plaid::PLAID_SANDBOX_URL.to_string());
+    let sealed = client.seal("acct-1", "access-sandbox-1").unwrap();
+    assert!(sealed.starts_with("sealed:v1:"), "{sealed}");
+    assert_ne!(sealed, client.seal("acct-1", "access-sandbox-1").unwrap());
+    assert_eq!(client.open("acct-1", &sealed).unwrap(), "access-sandbox-1");
+    assert!(client.open("acct-2", &sealed).is_err());
+
+    let other_key = PlaidClient::new(
+        &PlaidConfig {
+            client_id: "client-1".to_string(),
+            secret: "secret-1".to_string(),
+            base_url: plaid::PLAID_SANDBOX_URL.to_string(),
+            token_key: [8; 32],
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    assert!(other_key.open("acct-1", &sealed).is_err());
+    // Tokens stored before they were sealed are read as they are.
+    assert_eq!(client.open("acct-1", "access-old").unwrap(), "access-old");
+}
+
+#[tokio::test]
+async fn test_sync_needs_a_linked_account() {
+    let plaid = MockServer::start().await;
+    let (_, server, account_id) = server(&plaid).await;
+    let err = server
+        .sync_bank_account(Parameters(DryRun::from(sync_input(&account_id))))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("link_bank_account"), "{}", err.message);
+}
+
+#[tokio::test]
+async fn test_plaid_errors_carry_their_code() {
+    let plaid = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/item/get"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error_type": "ITEM_ERROR",
+            "error_code": "ITEM_LOGIN_REQUIRED",
+            "error_message": "the login details of this item have changed",
+            "display_message": null,
+            "request_id": "req-9",
+        })))
+        .mount(&plaid)
+        .await;
+    let (database, server, account_id) = server(&plaid).await;
+    let input = LinkBankAccountInput {
+        public_token: None,
+        access_token: Some("access-sandbox-9".to_string()),
+        ..link_input(&account_id)
+    };
+
+    let err = server
+        .link_bank_account(Parameters(DryRun::from(input)))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["plaid"]["error_code"], "ITEM_LOGIN_REQUIRED");
+    assert!(database.bank_link(&account_id).await.unwrap().is_none());
+
+    let both = LinkBankAccountInput {
+        access_token: Some("access-sandbox-9".to_string()),
+        ..link_input(&account_id)
+    };
+    let err = server
+        .link_bank_account(Parameters(DryRun::from(both)))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("not both"), "{}", err.message);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_keeps_one_bank_link_per_account() {
+    use exaspoon_db_mcp::models::BankLink;
+    use exaspoon_db_mcp::sqlite::SqliteDatabase;
+
+    let database = SqliteDatabase::open_in_memory().unwrap();
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let link = BankLink {
+        account_id: account.id.clone(),
+        item_id: "item-1".to_string(),
+        access_token: "access-1".to_string(),
+        plaid_account_id: None,
+        cursor: None,
+        updated_at: None,
+    };
+    let saved = database.save_bank_link(&link).await.unwrap();
+    assert!(saved.updated_at.is_some());
+    let synced = BankLink {
+        cursor: Some("cursor-1".to_string()),
+        ..saved
+    };
+    database.save_bank_link(&synced).await.unwrap();
+
+    let stored = database.bank_link(&account.id).await.unwrap().unwrap();
+    assert_eq!(stored.item_id, "item-1");
+    assert_eq!(stored.cursor.as_deref(), Some("cursor-1"));
+    assert!(database.bank_link("elsewhere").await.unwrap().is_none());
+}
@@ -0,0 +1,55 @@
+//! Tests for parsing PostgREST error responses.
+#![cfg(feature = "supabase")]
+
+use exaspoon_db_mcp::postgrest::{PostgrestError, PostgrestErrorKind, UNIQUE_VIOLATION};
+use reqwest::StatusCode;
+
+#[test]
+fn test_postgrest_error_parses_postgres_fields() {
+    let err = PostgrestError::parse(
+        "upsert into accounts",
+        StatusCode::CONFLICT,
+        r#"{"code":"23505","message":"duplicate key value violates unique constraint \"accounts_name_type_key\"","details":"Key (name, type)=(Checking, offchain) already exists.","hint":null}"#,
+    );
+
+    assert_eq!(err.code.as_deref(), Some(UNIQUE_VIOLATION));
+    assert_eq!(err.kind(), PostgrestErrorKind::Conflict);
+    assert_eq!(err.hint, None);
+    assert_eq!(
+        err.to_string(),
+        "upsert into accounts failed (409 Conflict) [23505]: duplicate key value violates \
+         unique constraint \"accounts_name_type_key\"; Key (name, type)=(Checking, offchain) \
+         already exists."
+    );
+}
+
+#[test]
+fn test_postgrest_error_classifies_codes() {
+    let kind = |code: &str| {
+        let body = format!(r#"{{"code":"{code}","message":"rejected"}}"#);
+        PostgrestError::parse("insert into transactions", StatusCode::BAD_REQUEST, &body).kind()
+    };
+
+    assert_eq!(kind("23503"), PostgrestErrorKind::InvalidInput);
+    assert_eq!(kind("23502"), PostgrestErrorKind::InvalidInput);
+    assert_eq!(kind("23514"), PostgrestErrorKind::InvalidInput);
+    assert_eq!(kind("42501"), PostgrestErrorKind::Other);
+    assert_eq!(kind("PGRST116"), PostgrestErrorKind::Other);
+}
+
+#[test]
+fn test_postgrest_error_keeps_unstructured_bodies() {
+    let err = PostgrestError::parse(
+        "query accounts",
+        StatusCode::BAD_GATEWAY,
+        "<html>bad gateway</html>\n",
+    );
+
+    assert_eq!(err.code, None);
+    assert_eq!(err.message, "<html>bad gateway</html>");
+    assert_eq!(err.kind(), PostgrestErrorKind::Other);
+    assert_eq!(
+        err.to_string(),
+        "query accounts failed (502 Bad Gateway): <html>bad gateway</html>"
+    );
+}
@@ -0,0 +1,379 @@
+//! Tests for crypto asset prices from CoinGecko, `get_asset_price` and the
+//! valuation of crypto in the reports' base currency totals.
+#![cfg(feature = "memory-backend")]
+
+use chrono::NaiveDate;
+use exaspoon_db_mcp::config::{CoinGeckoPlan, FxConfig, HttpClientConfig, PriceConfig};
+use exaspoon_db_mcp::fx::{FxClient, UnknownCurrency};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    AggregateSpendingInput, FxProvider, GetAssetPriceInput, SpendingGroupBy, SpendingPeriod,
+    TransactionFilters,
+};
+use exaspoon_db_mcp::prices::{CoinGeckoClient, UnknownAsset};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+fn price_config(api: &MockServer, api_key: Option<&str>) -> PriceConfig {
+    PriceConfig {
+        base_url: api.uri(),
+        api_key: api_key.map(str::to_string),
+        api_plan: CoinGeckoPlan::Demo,
+        base_currency: "USD".to_string(),
+        cache_ttl: Duration::from_secs(300),
+    }
+}
+
+fn day(text: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+}
+
+/// CoinGecko's price of `coin` on a day, in US dollars.
+fn history(coin: &str, usd: f64) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": coin,
+        "symbol": "x",
+        "name": coin,
+        "market_data": { "current_price": { "usd": usd, "eur": usd * 0.8 } },
+    }))
+}
+
+fn price_input(asset: &str, vs_currency: Option<&str>, amount: Option<f64>) -> GetAssetPriceInput {
+    GetAssetPriceInput {
+        asset: asset.to_string(),
+        vs_currency: vs_currency.map(str::to_string),
+        date: None,
+        amount,
+    }
+}
+
+#[tokio::test]
+async fn test_known_symbols_are_priced_and_cached() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/simple/price"))
+        .and(query_param("ids", "bitcoin"))
+        .and(query_param("vs_currencies", "usd"))
+        .and(header("x-cg-demo-api-key", "demo-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "bitcoin": { "usd": 50000.0, "last_updated_at": 1709251200 },
+        })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    let prices = CoinGeckoClient::new(
+        &price_config(&api, Some("demo-key")),
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+
+    let price = prices.price("btc", "USD", None).await.unwrap();
+    assert_eq!(price.coin_id, "bitcoin");
+    assert_eq!(price.price, 50000.0);
+    assert_eq!(price.as_of, "2024-03-01T00:00:00Z");
+    assert_eq!(prices.price("BTC", "usd", None).await.unwrap(), price);
+}
+
+#[tokio::test]
+async fn test_other_symbols_are_searched_for() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/search"))
+        .and(query_param("query", "PEPE"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "coins": [
+                { "id": "pepe-cash", "symbol": "PEPECASH", "market_cap_rank": 900 },
+                { "id": "pepe-on-base", "symbol": "PEPE" },
+                { "id": "pepe-classic", "symbol": "PEPE", "market_cap_rank": 4000 },
+                { "id": "pepe", "symbol": "PEPE", "market_cap_rank": 30 },
+            ],
+        })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/search"))
+        .and(query_param("query", "NOPE"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "coins": [] })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/coins/pepe/history"))
+        .and(query_param("date", "01-03-2024"))
+        .respond_with(history("pepe", 0.5))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/coins/pepe/history"))
+        .and(query_param("date", "01-01-2015"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "pepe" })))
+        .mount(&api)
+        .await;
+    let prices =
+        CoinGeckoClient::new(&price_config(&api, None), &HttpClientConfig::default()).unwrap();
+
+    let on = Some(day("2024-03-01"));
+    let price = prices.price("PEPE", "USD", on).await.unwrap();
+    assert_eq!(price.coin_id, "pepe");
+    assert_eq!(price.price, 0.5);
+    assert_eq!(price.as_of, "2024-03-01");
+    assert_eq!(prices.price("pepe", "USD", on).await.unwrap(), price);
+
+    let err = prices.price("PEPE", "XYZ", on).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownCurrency>().unwrap().0, "XYZ");
+    let err = prices.price("NOPE", "USD", None).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<UnknownAsset>().unwrap().0, "NOPE");
+    // Before the coin was listed there is no price.
+    assert!(prices
+        .price("PEPE", "USD", Some(day("2015-01-01")))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_get_asset_price_values_an_amount() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/simple/price"))
+        .and(query_param("ids", "ethereum"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ethereum": { "eur": 3000.0, "last_updated_at": 1709251200 },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/simple/price"))
+        .and(query_param("ids", "solana"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+            "status": {
+                "error_code": 429,
+                "error_message": "You've exceeded the Rate Limit.",
+            },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/search"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "coins": [] })))
+        .mount(&api)
+        .await;
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    );
+    assert!(server
+        .get_asset_price(Parameters(price_input("ETH", None, None)))
+        .await
+        .is_err());
+    let server = server.with_prices(
+        CoinGeckoClient::new(&price_config(&api, None), &HttpClientConfig::default()).unwrap(),
+    );
+
+    let priced = server
+        .get_asset_price(Parameters(price_input("eth", Some("eur"), Some(0.5))))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(priced["asset"], "ETH");
+    assert_eq!(priced["coin_id"], "ethereum");
+    assert_eq!(priced["vs_currency"], "EUR");
+    assert_eq!(priced["price"], 3000.0);
+    assert_eq!(priced["value"], 1500.0);
+
+    let priced = server
+        .get_asset_price(Parameters(price_input("ETH", Some("EUR"), Some(0.000123))))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(priced["value"], 0.000123 * 3000.0);
+
+    let err = server
+        .get_asset_price(Parameters(price_input("NOPE", None, None)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "asset");
+
+    let err = server
+        .get_asset_price(Parameters(price_input("ETH", Some("euro"), None)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "vs_currency");
+
+    let err = server
+        .get_asset_price(Parameters(price_input("SOL", None, None)))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["coingecko"]["status_code"], 429);
+    assert_eq!(data["coingecko"]["error_code"], 429);
+}
+
+#[tokio::test]
+async fn test_base_currency_totals_value_crypto() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/fx/2024-01-01"))
+        .and(query_param("base", "USD"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "amount": 1.0,
+            "base": "USD",
+            "date": "2024-01-01",
+            "rates": { "EUR": 0.8 },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/coins/bitcoin/history"))
+        .and(query_param("date", "01-01-2024"))
+        .respond_with(history("bitcoin", 40000.0))
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    for (amount, currency) in [(10.0, "EUR"), (0.001, "BTC")] {
+        input.amount = amount;
+        input.currency = currency.to_string();
+        database.insert_transaction(&input, None).await.unwrap();
+    }
+    let fx = FxClient::new(
+        &FxConfig {
+            provider: FxProvider::Ecb,
+            base_url: format!("{}/fx", api.uri()),
+            api_key: None,
+            base_currency: "USD".to_string(),
+            cache_ttl: Duration::from_secs(3600),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    let server = ExaspoonDbServer::new(
+        database,
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_fx(fx)
+    .with_prices(
+        CoinGeckoClient::new(&price_config(&api, None), &HttpClientConfig::default()).unwrap(),
+    );
+
+    let report = server
+        .aggregate_spending(Parameters(AggregateSpendingInput {
+            group_by: SpendingGroupBy::Direction,
+            period: Some(SpendingPeriod::Month),
+            filters: TransactionFilters::default(),
+        }))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    let base = &report["base_totals"];
+    assert_eq!(base["buckets"][0]["total"], 52.5);
+    assert_eq!(base["buckets"][0]["count"], 2);
+    assert!(base.get("unconverted").is_none());
+}
+
+#[tokio::test]
+async fn test_pro_keys_go_in_the_pro_header() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/simple/price"))
+        .and(header("x-cg-pro-api-key", "pro-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "bitcoin": { "usd": 50000.0, "last_updated_at": 1709251200 },
+        })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    let mut config = price_config(&api, Some("pro-key"));
+    config.api_plan = CoinGeckoPlan::Pro;
+    let prices = CoinGeckoClient::new(&config, &HttpClientConfig::default()).unwrap();
+
+    assert_eq!(
+        prices.price("BTC", "USD", None).await.unwrap().price,
+        50000.0
+    );
+}
+
+#[tokio::test]
+async fn test_fiat_without_rates_is_not_priced_as_a_token() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/fx/latest"))
+        .and(query_param("base", "USD"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "amount": 1.0,
+            "base": "USD",
+            "date": "2024-01-01",
+            "rates": { "EUR": 0.8 },
+        })))
+        .mount(&api)
+        .await;
+    // A token sharing the symbol of the Nigerian naira.
+    Mock::given(method("GET"))
+        .and(path("/search"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "coins": [{ "id": "naira-token", "symbol": "NGN", "market_cap_rank": 5000 }],
+        })))
+        .expect(0)
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    for (amount, currency) in [(10.0, "EUR"), (5000.0, "NGN")] {
+        input.amount = amount;
+        input.currency = currency.to_string();
+        database.insert_transaction(&input, None).await.unwrap();
+    }
+    let fx = FxClient::new(
+        &FxConfig {
+            provider: FxProvider::Ecb,
+            base_url: format!("{}/fx", api.uri()),
+            api_key: None,
+            base_currency: "USD".to_string(),
+            cache_ttl: Duration::from_secs(3600),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap();
+    let server = ExaspoonDbServer::new(
+        database,
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_fx(fx)
+    .with_prices(
+        CoinGeckoClient::new(&price_config(&api, None), &HttpClientConfig::default()).unwrap(),
+    );
+
+    let report = server
+        .aggregate_spending(Parameters(AggregateSpendingInput {
+            group_by: SpendingGroupBy::Direction,
+            period: None,
+            filters: TransactionFilters::default(),
+        }))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    let base = &report["base_totals"];
+    assert_eq!(base["buckets"][0]["total"], 12.5);
+    assert_eq!(base["unconverted"], json!(["NGN"]));
+}
@@ -0,0 +1,277 @@
+//! Tests for reading QIF files.
+
+use exaspoon_db_mcp::models::TransactionDirection;
+use exaspoon_db_mcp::qif;
+
+/// A Quicken bank export with an account list, a transfer, a split and a
+/// record of zero amount.
+const QIF: &str = "!Option:AutoSwitch
+!Account
+NChecking
+TBank
+^
+!Clear:AutoSwitch
+!Type:Bank
+D1/ 5'24
+T-1,042.50
+PWHOLE FOODS
+MWeekly shop
+LFood:Groceries/Household
+^
+D01/15/2024
+T3000.00
+PACME PAYROLL
+LSalary
+^
+D1/20/24
+T-200.00
+PTransfer
+L[Savings]
+^
+D01/22/2024
+U-60.00
+PTHE LOCAL
+L--Split--
+SDining
+$-45.00
+SFood:Groceries
+$-15.00
+^
+D01/31/2024
+T0.00
+PBalance
+^
+";
+
+#[test]
+fn test_parses_bank_records() {
+    let records = qif::parse(QIF, false, 2).unwrap();
+    assert_eq!(records.len(), 5);
+
+    let groceries = &records[0];
+    assert_eq!(groceries.occurred_at, "2024-01-05T00:00:00Z");
+    assert_eq!(groceries.amount, -1042.5);
+    assert_eq!(groceries.category.as_deref(), Some("Food:Groceries"));
+    assert!(!groceries.transfer);
+    assert!(groceries.raw.starts_with("D1/ 5'24\nT-1,042.50\n"));
+
+    let input = groceries.to_input("acct-1", "USD");
+    assert_eq!(input.direction, TransactionDirection::Expense);
+    assert_eq!(input.amount, 1042.5);
+    assert_eq!(input.currency, "USD");
+    assert_eq!(
+        input.description.as_deref(),
+        Some("WHOLE FOODS - Weekly shop")
+    );
+
+    let salary = records[1].to_input("acct-1", "USD");
+    assert_eq!(salary.direction, TransactionDirection::Income);
+    assert_eq!(records[2].category, None);
+    assert_eq!(
+        records[2].to_input("acct-1", "USD").direction,
+        TransactionDirection::Transfer
+    );
+    assert_eq!(records[3].amount, -60.0);
+    assert_eq!(records[3].category.as_deref(), Some("Dining"));
+    assert_eq!(records[4].amount, 0.0);
+}
+
+#[test]
+fn test_reads_dates_in_either_order() {
+    assert_eq!(
+        qif::parse_date("05/01/2024", true).unwrap(),
+        "2024-01-05T00:00:00Z"
+    );
+    assert_eq!(
+        qif::parse_date("05.01.2024", false).unwrap(),
+        "2024-01-05T00:00:00Z"
+    );
+    assert_eq!(
+        qif::parse_date("2024-01-05", true).unwrap(),
+        "2024-01-05T00:00:00Z"
+    );
+    assert_eq!(
+        qif::parse_date("12/31/99", false).unwrap(),
+        "1999-12-31T00:00:00Z"
+    );
+    assert!(qif::parse_date("13/05/2024", false).is_err());
+    assert!(qif::parse_date("yesterday", false).is_err());
+}
+
+#[test]
+fn test_reads_amounts_with_either_decimal_separator() {
+    let parse = |value| qif::parse_amount(value, 2).unwrap();
+    assert_eq!(parse("-1,042.50"), Some(-1042.5));
+    assert_eq!(parse("-1.042,50"), Some(-1042.5));
+    assert_eq!(parse("12,50"), Some(12.5));
+    assert_eq!(parse("1.000"), Some(1000.0));
+    assert_eq!(parse("(45.00)"), Some(-45.0));
+    assert_eq!(parse("USD"), None);
+    assert_eq!(qif::parse_amount("1.500", 3).unwrap(), Some(1.5));
+    assert_eq!(qif::parse_amount("1,250", 3).unwrap(), Some(1.25));
+    assert_eq!(qif::parse_amount("1,234.125", 3).unwrap(), Some(1234.125));
+
+    let records = qif::parse("!Type:Bank\nD05.01.2024\nT-12,50\nPCafe\n^\n", false, 2).unwrap();
+    assert_eq!(records[0].amount, -12.5);
+}
+
+#[test]
+fn test_rejects_malformed_files() {
+    let err = qif::parse("date,amount\n2024-01-01,5", false, 2).unwrap_err();
+    assert_eq!(err.to_string(), "not a QIF document: no !Type: header");
+    let err = qif::parse("!Type:Invst\nD01/05/2024\n^\n", false, 2).unwrap_err();
+    assert!(err.to_string().contains("Invst"), "{err}");
+    let err = qif::parse("!Type:Bank\nD01/05/2024\nT-5\n", false, 2).unwrap_err();
+    assert_eq!(err.to_string(), "record 1 is not closed by ^");
+    let err = qif::parse("!Type:CCard\nD01/05/2024\nPCafe\n^\n", false, 2).unwrap_err();
+    assert_eq!(format!("{err:#}"), "record 1: no amount (T)");
+}
+
+#[cfg(feature = "memory-backend")]
+mod memory {
+    use super::QIF;
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use exaspoon_db_mcp::embedding::Embedder;
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{
+        AccountType, CategoryKind, DryRun, ImportQifInput, TransactionFilters, UpsertAccountInput,
+        UpsertCategoryInput,
+    };
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use rmcp::handler::server::wrapper::Parameters;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    /// Puts text about eating out close to `[1, 0, 0]` and everything else
+    /// on an axis of its own.
+    struct DiningEmbedder;
+
+    #[async_trait]
+    impl Embedder for DiningEmbedder {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            if text.to_lowercase().contains("dining") {
+                Ok(vec![0.9, 0.1, 0.0])
+            } else {
+                Ok(vec![0.0, 0.0, 1.0])
+            }
+        }
+    }
+
+    async fn category(database: &MemoryDatabase, name: &str, embedding: Vec<f32>) -> String {
+        let input = UpsertCategoryInput {
+            name: name.to_string(),
+            kind: Some(CategoryKind::Expense),
+            description: None,
+        };
+        let category = database.upsert_category(&input, Some(embedding));
+        category.await.unwrap().id
+    }
+
+    #[tokio::test]
+    async fn test_import_qif_files_transactions_under_mapped_categories() {
+        let database = Arc::new(MemoryDatabase::new());
+        let account = database
+            .upsert_account(&UpsertAccountInput {
+                name: "Checking".to_string(),
+                r#type: AccountType::Offchain,
+                currency: "USD".to_string(),
+                network: None,
+                institution: None,
+            })
+            .await
+            .unwrap();
+        let groceries = category(&database, "groceries", vec![0.0, 1.0, 0.0]).await;
+        let restaurants = category(&database, "Restaurants", vec![1.0, 0.0, 0.0]).await;
+        let server = ExaspoonDbServer::new(database.clone(), Arc::new(DiningEmbedder));
+        let input = ImportQifInput {
+            account_id: account.id.clone(),
+            qif: QIF.to_string(),
+            currency: "USD".to_string(),
+            day_first: false,
+            min_similarity: None,
+        };
+
+        let planned = server
+            .import_qif(Parameters(DryRun {
+                input: input.clone(),
+                dry_run: Some(true),
+            }))
+            .await
+            .unwrap();
+        let planned = planned.structured_content.unwrap();
+        assert_eq!(planned["transactions"].as_array().unwrap().len(), 4);
+        let filters = TransactionFilters::default();
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 0);
+
+        let result = server
+            .import_qif(Parameters(DryRun::from(input)))
+            .await
+            .unwrap();
+        let payload = result.structured_content.unwrap();
+        assert_eq!(payload["skipped_zero_amount"], 1);
+        let categories = payload["categories"].as_array().unwrap();
+        assert_eq!(categories.len(), 3);
+        assert_eq!(categories[0]["qif_category"], "Food:Groceries");
+        assert_eq!(categories[0]["transactions"], 1);
+        assert_eq!(categories[0]["category"]["id"], json!(groceries));
+        assert_eq!(categories[0]["matched_by"], "name");
+        assert_eq!(categories[1]["qif_category"], "Salary");
+        assert!(categories[1].get("category").is_none());
+        assert_eq!(categories[2]["qif_category"], "Dining");
+        assert_eq!(categories[2]["category"]["id"], json!(restaurants));
+        assert_eq!(categories[2]["matched_by"], "similarity");
+
+        let transactions = payload["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 4);
+        assert_eq!(transactions[0]["category_id"], json!(groceries));
+        assert!(transactions[1].get("category_id").is_none());
+        assert_eq!(transactions[2]["direction"], "transfer");
+        assert_eq!(transactions[3]["category_id"], json!(restaurants));
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_import_qif_matches_names_before_similarity() {
+        let database = Arc::new(MemoryDatabase::new());
+        let account = database
+            .upsert_account(&UpsertAccountInput {
+                name: "Checking".to_string(),
+                r#type: AccountType::Offchain,
+                currency: "USD".to_string(),
+                network: None,
+                institution: None,
+            })
+            .await
+            .unwrap();
+        // Without an embedding, no similarity search finds it.
+        let input = UpsertCategoryInput {
+            name: "Dining".to_string(),
+            kind: Some(CategoryKind::Expense),
+            description: None,
+        };
+        let dining = database.upsert_category(&input, None).await.unwrap().id;
+        category(&database, "Restaurants", vec![1.0, 0.0, 0.0]).await;
+        let server = ExaspoonDbServer::new(database.clone(), Arc::new(DiningEmbedder));
+        let input = ImportQifInput {
+            account_id: account.id,
+            qif: QIF.to_string(),
+            currency: "USD".to_string(),
+            day_first: false,
+            min_similarity: None,
+        };
+
+        let result = server
+            .import_qif(Parameters(DryRun::from(input)))
+            .await
+            .unwrap();
+        let payload = result.structured_content.unwrap();
+        let categories = payload["categories"].as_array().unwrap();
+        assert_eq!(categories[2]["qif_category"], "Dining");
+        assert_eq!(categories[2]["category"]["id"], json!(dining));
+        assert_eq!(categories[2]["matched_by"], "name");
+        let transactions = payload["transactions"].as_array().unwrap();
+        assert_eq!(transactions[3]["category_id"], json!(dining));
+    }
+}
@@ -0,0 +1,51 @@
+//! Tests for the per-session tool call budgets.
+
+use exaspoon_db_mcp::rate_limit::{RateLimitConfig, RateLimiter};
+use std::time::Duration;
+
+fn limiter(calls: u32, embedding_calls: u32, window: Duration) -> RateLimiter {
+    RateLimiter::new(RateLimitConfig {
+        calls,
+        embedding_calls,
+        window,
+    })
+}
+
+#[test]
+fn test_calls_over_budget_are_refused_until_the_window_slides() {
+    let limiter = limiter(2, 0, Duration::from_millis(30));
+    limiter.acquire(false).unwrap();
+    limiter.acquire(true).unwrap();
+
+    let limited = limiter.acquire(false).unwrap_err();
+    assert_eq!(limited.budget, "calls");
+    assert_eq!(limited.limit, 2);
+    assert!(limited.retry_in <= Duration::from_millis(30));
+    assert!(limited.to_string().contains("rate limit of 2 tool calls"));
+
+    std::thread::sleep(Duration::from_millis(40));
+    limiter.acquire(false).unwrap();
+}
+
+#[test]
+fn test_embedding_budget_is_separate_and_refusals_cost_nothing() {
+    let limiter = limiter(3, 1, Duration::from_secs(60));
+    limiter.acquire(true).unwrap();
+
+    let limited = limiter.acquire(true).unwrap_err();
+    assert_eq!(limited.budget, "embedding_calls");
+    assert!(limited.to_string().contains("embedding tool calls"));
+
+    // The refused embedding call did not use up the overall budget.
+    limiter.acquire(false).unwrap();
+    limiter.acquire(false).unwrap();
+    assert_eq!(limiter.acquire(false).unwrap_err().budget, "calls");
+}
+
+#[test]
+fn test_zero_disables_a_budget() {
+    let limiter = limiter(0, 0, Duration::from_secs(60));
+    for _ in 0..1_000 {
+        limiter.acquire(true).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+//! Tests for the Supabase Realtime listener.
+#![cfg(feature = "realtime")]
+
+use exaspoon_db_mcp::realtime::{
+    parse_message, ChangeKind, RealtimeListener, TransactionChange, TRANSACTIONS_TOPIC,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+mod common;
+
+fn listener(configure: impl FnOnce(&mut exaspoon_db_mcp::config::AppConfig)) -> RealtimeListener {
+    let mut config = common::test_config();
+    configure(&mut config);
+    RealtimeListener::from_config(&config)
+}
+
+fn change_message(kind: &str, data: Value) -> String {
+    let mut data = data;
+    data["type"] = json!(kind);
+    json!({
+        "topic": TRANSACTIONS_TOPIC,
+        "event": "postgres_changes",
+        "payload": { "ids": [1], "data": data },
+        "ref": null,
+    })
+    .to_string()
+}
+
+#[test]
+fn test_realtime_websocket_url() {
+    let url = listener(|_| {}).websocket_url().unwrap();
+    assert_eq!(
+        url,
+        "wss://test.supabase.co/realtime/v1/websocket?apikey=test-service-key&vsn=1.0.0"
+    );
+
+    let url = listener(|config| config.supabase_url = "http://127.0.0.1:54321".into())
+        .websocket_url()
+        .unwrap();
+    assert!(url.starts_with("ws://127.0.0.1:54321/realtime/v1/websocket?"));
+}
+
+#[test]
+fn test_realtime_join_filters_by_tenant_and_uses_access_token() {
+    let join = listener(|config| {
+        config.tenant_id = Some("household-1".into());
+        config.supabase_access_token = Some("user-jwt".into());
+        config.supabase_schema = "finance".into();
+    })
+    .join_message();
+
+    assert_eq!(join["event"], "phx_join");
+    assert_eq!(join["payload"]["access_token"], "user-jwt");
+    assert_eq!(
+        join["payload"]["config"]["postgres_changes"][0],
+        json!({
+            "event": "*",
+            "schema": "finance",
+            "table": "transactions",
+            "filter": "user_id=eq.household-1",
+        })
+    );
+
+    let join = listener(|_| {}).join_message();
+    assert_eq!(join["payload"]["access_token"], "test-service-key");
+    assert!(join["payload"]["config"]["postgres_changes"][0]
+        .get("filter")
+        .is_none());
+}
+
+#[test]
+fn test_realtime_parses_row_changes() {
+    let insert = change_message(
+        "INSERT",
+        json!({ "record": { "id": "txn-1" }, "old_record": {} }),
+    );
+    assert_eq!(
+        parse_message(&insert).unwrap(),
+        Some(TransactionChange {
+            kind: ChangeKind::Insert,
+            record_id: Some("txn-1".into()),
+        })
+    );
+
+    let delete = change_message("DELETE", json!({ "old_record": { "id": "txn-2" } }));
+    assert_eq!(
+        parse_message(&delete)
+            .unwrap()
+            .unwrap()
+            .record_id
+            .as_deref(),
+        Some("txn-2")
+    );
+
+    let reply = json!({
+        "topic": TRANSACTIONS_TOPIC,
+        "event": "phx_reply",
+        "payload": { "status": "ok", "response": {} },
+        "ref": "1",
+    });
+    assert_eq!(parse_message(&reply.to_string()).unwrap(), None);
+
+    let rejected = json!({
+        "topic": TRANSACTIONS_TOPIC,
+        "event": "phx_reply",
+        "payload": { "status": "error", "response": { "reason": "unauthorized" } },
+        "ref": "1",
+    });
+    assert!(parse_message(&rejected.to_string()).is_err());
+}
+
+#[tokio::test]
+async fn test_realtime_listener_forwards_changes() {
+    let socket = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (stream, _) = socket.accept().await.unwrap();
+        let mut websocket = accept_async(stream).await.unwrap();
+        let join = websocket.next().await.unwrap().unwrap();
+        let join: Value = serde_json::from_str(join.to_text().unwrap()).unwrap();
+        websocket
+            .send(Message::text(change_message(
+                "UPDATE",
+                json!({ "record": { "id": "txn-9" }, "old_record": { "id": "txn-9" } }),
+            )))
+            .await
+            .unwrap();
+        join
+    });
+
+    let mut changes = listener(|config| config.supabase_url = format!("http://{address}")).spawn();
+    let change = tokio::time::timeout(Duration::from_secs(5), changes.recv())
+        .await
+        .expect("change should arrive")
+        .expect("listener should be running");
+
+    assert_eq!(change.kind, ChangeKind::Update);
+    assert_eq!(change.record_id.as_deref(), Some("txn-9"));
+    let join = server.await.unwrap();
+    assert_eq!(join["topic"], TRANSACTIONS_TOPIC);
+}
@@ -0,0 +1,372 @@
+//! Tests for reading receipts with a vision model and recording them with
+//! `parse_receipt`.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::config::{HttpClientConfig, ReceiptConfig};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    DryRun, ParseReceiptInput, Receipt, ReceiptItem, TransactionFilters,
+};
+use exaspoon_db_mcp::receipt::{self, ReceiptReader};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+/// A 1x1 PNG.
+const PIXEL: &str =
+    "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+
+fn reader(api: &MockServer) -> ReceiptReader {
+    ReceiptReader::new(
+        &ReceiptConfig {
+            model: "gpt-4o-mini".to_string(),
+            base_url: api.uri(),
+            api_key: Some("vision-key".to_string()),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap()
+}
+
+/// A chat completion answering `content`.
+fn answer(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "chatcmpl-1",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop",
+        }],
+    }))
+}
+
+fn grocery_receipt() -> Value {
+    json!({
+        "merchant": "Corner Market",
+        "date": "2024-03-05",
+        "currency": "eur",
+        "total": 12.5,
+        "tax": 1.0,
+        "items": [
+            { "description": "Bread", "quantity": 1, "amount": 3.5 },
+            { "description": "Coffee", "quantity": 2, "amount": 8.0 },
+        ],
+    })
+}
+
+fn receipt_input(account_id: Option<&str>, split: bool) -> ParseReceiptInput {
+    ParseReceiptInput {
+        image: PIXEL.to_string(),
+        mime_type: Some("image/png".to_string()),
+        account_id: account_id.map(str::to_string),
+        split,
+    }
+}
+
+/// Direction, amount and description of each transaction in `transactions`.
+fn summary(transactions: &Value) -> Vec<(String, f64, String)> {
+    transactions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transaction| {
+            (
+                transaction["direction"].as_str().unwrap().to_string(),
+                transaction["amount"].as_f64().unwrap(),
+                transaction["description"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_reader_sends_the_image_and_checks_the_answer() {
+    let api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(header("authorization", "Bearer vision-key"))
+        .and(body_partial_json(json!({
+            "model": "gpt-4o-mini",
+            "response_format": { "type": "json_object" },
+        })))
+        .respond_with(answer(
+            "```json\n{\"merchant\": \" Corner Market \", \"date\": \"05/03/2024\", \
+             \"currency\": \"euro\", \"items\": [{\"description\": \"Bread\", \"amount\": 3.5}, \
+             {\"description\": \"Milk\", \"amount\": 1.25}, {\"description\": \"\", \"amount\": 9}]}\n```",
+        ))
+        .expect(1)
+        .mount(&api)
+        .await;
+
+    let read = reader(&api)
+        .read(&format!("data:image/png;base64,{PIXEL}"))
+        .await
+        .unwrap();
+    assert_eq!(read.merchant.as_deref(), Some("Corner Market"));
+    // Neither a date nor a currency the model could not have meant is kept.
+    assert_eq!(read.date, None);
+    assert_eq!(read.currency, None);
+    assert_eq!(read.items.len(), 2);
+    assert_eq!(read.total, Some(4.75));
+}
+
+#[test]
+fn test_images_are_sent_as_urls() {
+    assert_eq!(
+        receipt::image_url("https://example.com/r.jpg", None).unwrap(),
+        "https://example.com/r.jpg"
+    );
+    assert!(receipt::image_url("http://example.com/r.jpg", None).is_err());
+    assert_eq!(
+        receipt::image_url(PIXEL, Some("image/png")).unwrap(),
+        format!("data:image/png;base64,{PIXEL}")
+    );
+    assert!(receipt::image_url(PIXEL, None)
+        .unwrap()
+        .starts_with("data:image/jpeg;base64,"));
+    let data_url = format!("data:image/png;base64,{PIXEL}");
+    assert_eq!(receipt::image_url(&data_url, None).unwrap(), data_url);
+
+    assert!(receipt::image_url("not base64!", None).is_err());
+    assert!(receipt::image_url(PIXEL, Some("application/pdf")).is_err());
+    assert!(receipt::image_url("data:image/png,raw", None).is_err());
+}
+
+#[test]
+fn test_split_receipts_keep_the_total() {
+    let receipt = Receipt {
+        merchant: Some("Corner Market".to_string()),
+        date: Some("2024-03-05".to_string()),
+        currency: None,
+        total: Some(12.5),
+        tax: Some(1.0),
+        items: vec![
+            ReceiptItem {
+                description: "Bread".to_string(),
+                quantity: None,
+                amount: 3.5,
+            },
+            ReceiptItem {
+                description: "Coffee".to_string(),
+                quantity: Some(2.0),
+                amount: 8.0,
+            },
+        ],
+    };
+
+    let whole = receipt::transactions(&receipt, "acc-1", "USD", false).unwrap();
+    assert_eq!(whole.len(), 1);
+    assert_eq!(whole[0].amount, 12.5);
+    assert_eq!(whole[0].currency, "USD");
+    assert_eq!(whole[0].occurred_at, "2024-03-05T12:00:00Z");
+
+    let split = receipt::transactions(&receipt, "acc-1", "USD", true).unwrap();
+    let amounts: Vec<_> = split.iter().map(|expense| expense.amount).collect();
+    assert_eq!(amounts, vec![3.5, 8.0, 1.0]);
+    // Every line carries the purchase's id, whichever way it was recorded.
+    let id = receipt::purchase_id(&receipt, "USD");
+    for expense in whole.iter().chain(&split) {
+        let raw_source = expense.raw_source.as_deref().unwrap();
+        assert_eq!(
+            receipt::receipt_id(raw_source).as_deref(),
+            Some(id.as_str())
+        );
+    }
+    let raw_source: Value = serde_json::from_str(split[0].raw_source.as_deref().unwrap()).unwrap();
+    assert_eq!(raw_source["receipt"]["lines"], 3);
+    let mut other = receipt.clone();
+    other.total = Some(12.49);
+    assert_ne!(receipt::purchase_id(&other, "USD"), id);
+    assert_eq!(
+        split[2].description.as_deref(),
+        Some("Corner Market: tax and other charges")
+    );
+
+    // A discount line leaves nothing to split the total by.
+    let mut discounted = receipt.clone();
+    discounted.items.push(ReceiptItem {
+        description: "Loyalty discount".to_string(),
+        quantity: None,
+        amount: -2.0,
+    });
+    assert_eq!(
+        receipt::transactions(&discounted, "acc-1", "USD", true)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let unreadable = Receipt::default();
+    assert!(receipt::transactions(&unreadable, "acc-1", "USD", true).is_err());
+}
+
+#[tokio::test]
+async fn test_parse_receipt_records_the_purchase_split_by_item() {
+    let api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(answer(&grocery_receipt().to_string()))
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    );
+    assert!(server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(None, false))))
+        .await
+        .is_err());
+    let server = server.with_receipts(reader(&api));
+
+    let read = server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(None, false))))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(read["model"], "gpt-4o-mini");
+    assert_eq!(read["receipt"]["currency"], "EUR");
+    assert_eq!(read["receipt"]["items"][1]["quantity"], 2.0);
+    assert!(read.get("transactions").is_none());
+
+    let planned = server
+        .parse_receipt(Parameters(DryRun {
+            input: receipt_input(Some(&account.id), true),
+            dry_run: Some(true),
+        }))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(planned["transactions"].as_array().unwrap().len(), 3);
+    let filters = TransactionFilters::default();
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 0);
+
+    let recorded = server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(
+            Some(&account.id),
+            true,
+        ))))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    let transactions = &recorded["transactions"];
+    assert_eq!(
+        summary(transactions),
+        vec![
+            (
+                "expense".to_string(),
+                3.5,
+                "Corner Market: Bread".to_string()
+            ),
+            (
+                "expense".to_string(),
+                8.0,
+                "Corner Market: Coffee".to_string()
+            ),
+            (
+                "expense".to_string(),
+                1.0,
+                "Corner Market: tax and other charges".to_string()
+            ),
+        ]
+    );
+    assert_eq!(transactions[0]["currency"], "EUR");
+    assert_eq!(transactions[0]["account_id"], account.id);
+    let raw_source: Value =
+        serde_json::from_str(transactions[1]["raw_source"].as_str().unwrap()).unwrap();
+    assert_eq!(raw_source["receipt"]["total"], 12.5);
+    assert_eq!(raw_source["line"], 1);
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 3);
+    assert!(recorded.get("already_recorded").is_none());
+
+    // A retry reads the same purchase and records nothing.
+    let retried = server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(
+            Some(&account.id),
+            false,
+        ))))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(retried["already_recorded"], true);
+    assert!(retried.get("transactions").is_none());
+    assert_eq!(database.count_transactions(&filters).await.unwrap(), 3);
+
+    let mut other_account = common::sample_account_input();
+    other_account.name = "Cash".to_string();
+    let other_account = database.upsert_account(&other_account).await.unwrap();
+    let whole = server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(
+            Some(&other_account.id),
+            false,
+        ))))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(
+        summary(&whole["transactions"]),
+        vec![("expense".to_string(), 12.5, "Corner Market".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_parse_receipt_errors_name_their_cause() {
+    let api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "error": {
+                "message": "The model `gpt-5-vision` does not exist.",
+                "type": "invalid_request_error",
+                "code": "model_not_found",
+            },
+        })))
+        .mount(&api)
+        .await;
+    let database = Arc::new(MemoryDatabase::new());
+    let server = ExaspoonDbServer::new(
+        database.clone(),
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+    .with_receipts(reader(&api));
+
+    let mut input = receipt_input(None, false);
+    input.image = "not an image".to_string();
+    let err = server
+        .parse_receipt(Parameters(DryRun::from(input)))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "image");
+
+    let err = server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(
+            Some("missing"),
+            false,
+        ))))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "account_id");
+
+    let err = server
+        .parse_receipt(Parameters(DryRun::from(receipt_input(None, false))))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["vision"]["status_code"], 404);
+    assert_eq!(data["vision"]["code"], "model_not_found");
+}
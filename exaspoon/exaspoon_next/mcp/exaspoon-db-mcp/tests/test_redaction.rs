@@ -0,0 +1,103 @@
+//! Tests for PII redaction applied before embedding.
+
+use exaspoon_db_mcp::embedding::{Embedder, RedactingEmbedder};
+use exaspoon_db_mcp::redaction::Redactor;
+use std::sync::Arc;
+
+mod common;
+
+#[test]
+fn test_redactor_masks_builtin_patterns() {
+    let redactor = Redactor::new(&[]).unwrap();
+
+    assert_eq!(
+        redactor.redact("Refund to jane.doe@example.com"),
+        "Refund to [EMAIL]"
+    );
+    assert_eq!(
+        redactor.redact("Transfer to DE89 3704 0044 0532 0130 00"),
+        "Transfer to [IBAN]"
+    );
+    assert_eq!(
+        redactor.redact("Paid with 4111 1111 1111 1111"),
+        "Paid with [CARD]"
+    );
+    assert_eq!(
+        redactor.redact("Call +1 415-555-0132 for support"),
+        "Call [PHONE] for support"
+    );
+}
+
+#[test]
+fn test_redactor_masks_phone_numbers_in_their_usual_shapes() {
+    let redactor = Redactor::new(&[]).unwrap();
+
+    for phone in [
+        "+14155550132",
+        "+44 20 7946 0958",
+        "+7 (495) 123-45-67",
+        "(415) 555-0132",
+        "415.555.0132",
+        "415 555 0132",
+    ] {
+        assert_eq!(
+            redactor.redact(&format!("Call {phone} today")),
+            "Call [PHONE] today",
+            "{phone}"
+        );
+    }
+}
+
+#[test]
+fn test_redactor_keeps_plain_digit_runs() {
+    let redactor = Redactor::new(&[]).unwrap();
+
+    for text in [
+        "Order 4155550132",
+        "Account 12345678",
+        "Reference 123456789 paid",
+        "Paid 1234.56 on 2024-01-15",
+        "Transfer 1 500 000 RUB",
+    ] {
+        assert_eq!(redactor.redact(text), text);
+    }
+}
+
+#[test]
+fn test_redactor_keeps_numbers_failing_luhn_check() {
+    let redactor = Redactor::new(&[]).unwrap();
+
+    assert_eq!(
+        redactor.redact("Invoice 1234 5678 9012 3456"),
+        "Invoice 1234 5678 9012 3456"
+    );
+}
+
+#[test]
+fn test_redactor_applies_custom_patterns() {
+    let redactor = Redactor::new(&[r"ACCT-\d{6}".to_string()]).unwrap();
+
+    assert_eq!(redactor.redact("Loan ACCT-123456"), "Loan [REDACTED]");
+}
+
+#[test]
+fn test_redactor_rejects_invalid_pattern() {
+    let result = Redactor::new(&["(".to_string()]);
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_redacting_embedder_sends_redacted_text() {
+    let inner = common::MockEmbedder::new(vec![0.1]);
+    let embedder = RedactingEmbedder::new(Arc::new(inner.clone()), Redactor::new(&[]).unwrap());
+
+    embedder.embed("Coffee, receipt to bob@example.com").await.unwrap();
+    embedder.embed_query("payments to bob@example.com").await.unwrap();
+    embedder.maybe_embed(None).await.unwrap();
+
+    assert_eq!(
+        inner.calls(),
+        vec!["Coffee, receipt to [EMAIL]", "payments to [EMAIL]"]
+    );
+}
@@ -0,0 +1,98 @@
+//! Tests for the retry policy used by the Supabase gateway.
+
+use anyhow::anyhow;
+use exaspoon_db_mcp::retry::{is_transient_status, Failure, RetryPolicy};
+use reqwest::StatusCode;
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn instant_policy(max_retries: u32) -> RetryPolicy {
+    RetryPolicy::new(max_retries, Duration::ZERO)
+}
+
+#[tokio::test]
+async fn test_retry_recovers_from_transient_failures() {
+    let attempts = AtomicU32::new(0);
+
+    let result = instant_policy(3)
+        .run("test", || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(Failure::Transient(anyhow!("503")))
+            } else {
+                Ok("done")
+            }
+        })
+        .await;
+
+    assert_eq!(result.unwrap(), "done");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_gives_up_after_budget() {
+    let attempts = AtomicU32::new(0);
+
+    let result: anyhow::Result<()> = instant_policy(2)
+        .run("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Failure::Transient(anyhow!("bad gateway")))
+        })
+        .await;
+
+    assert_eq!(result.unwrap_err().to_string(), "bad gateway");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retry_does_not_repeat_permanent_failures() {
+    let attempts = AtomicU32::new(0);
+
+    let result: anyhow::Result<()> = instant_policy(5)
+        .run("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Failure::Permanent(anyhow!("invalid input")))
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_retry_delay_grows_exponentially_and_is_capped() {
+    let policy = RetryPolicy::new(10, Duration::from_millis(100));
+
+    assert_eq!(policy.delay(0), Duration::from_millis(100));
+    assert_eq!(policy.delay(1), Duration::from_millis(200));
+    assert_eq!(policy.delay(3), Duration::from_millis(800));
+    assert_eq!(policy.delay(30), Duration::from_secs(10));
+}
+
+#[test]
+fn test_failure_classification() {
+    assert!(is_transient_status(StatusCode::BAD_GATEWAY));
+    assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+    assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+    assert!(!is_transient_status(StatusCode::BAD_REQUEST));
+    assert!(!is_transient_status(StatusCode::INTERNAL_SERVER_ERROR));
+
+    assert!(matches!(
+        Failure::from_message(anyhow!("x"), "query failed (503 Service Unavailable)"),
+        Failure::Transient(_)
+    ));
+    let timed_out = io::Error::new(io::ErrorKind::TimedOut, "operation timed out");
+    assert!(matches!(
+        Failure::from_message(anyhow!(timed_out).context("query failed"), "query failed"),
+        Failure::Transient(_)
+    ));
+    assert!(matches!(
+        Failure::from_message(anyhow!("x"), "relation does not exist"),
+        Failure::Permanent(_)
+    ));
+    // Numbers and words that merely look like a transient failure do not count.
+    assert!(matches!(
+        Failure::from_message(anyhow!("x"), "account 503 has no connection timeout set"),
+        Failure::Permanent(_)
+    ));
+}
@@ -0,0 +1,617 @@
+//! Tests for exporting reports to Google Sheets and Notion with
+//! `export_to_sheet`.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::config::{GoogleSheetsConfig, HttpClientConfig, NotionConfig};
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::models::{
+    CreateTransactionInput, DryRun, ExportToSheetInput, ListAuditEventsInput, SheetDestination,
+    SheetReport, TransactionDirection, UpsertCategoryInput,
+};
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::sheets::{google::GoogleSheetsClient, notion::NotionClient};
+use exaspoon_db_mcp::supabase::Database;
+use rmcp::handler::server::wrapper::Parameters;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::matchers::{body_partial_json, body_string_contains, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+fn google(api: &MockServer) -> GoogleSheetsClient {
+    GoogleSheetsClient::new(
+        &GoogleSheetsConfig {
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            spreadsheet_id: Some("sheet-1".to_string()),
+            base_url: api.uri(),
+            token_url: format!("{}/token", api.uri()),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap()
+}
+
+fn notion(api: &MockServer) -> NotionClient {
+    NotionClient::new(
+        &NotionConfig {
+            token: "secret_notion".to_string(),
+            database_id: Some("db-1".to_string()),
+            base_url: api.uri(),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap()
+}
+
+async fn mount_token(api: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .and(body_string_contains("refresh_token=refresh-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "access-token",
+            "expires_in": 3599,
+            "token_type": "Bearer",
+        })))
+        .expect(1)
+        .mount(api)
+        .await;
+}
+
+/// A server over a Checking account with groceries in January, and rent and
+/// a salary in February.
+async fn server_with_transactions() -> ExaspoonDbServer {
+    server_over(database_with_transactions().await)
+}
+
+fn server_over(database: Arc<MemoryDatabase>) -> ExaspoonDbServer {
+    ExaspoonDbServer::new(
+        database,
+        Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+    )
+}
+
+/// The database of [`server_with_transactions`].
+async fn database_with_transactions() -> Arc<MemoryDatabase> {
+    let database = Arc::new(MemoryDatabase::new());
+    let account = database
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let groceries = database
+        .upsert_category(
+            &UpsertCategoryInput {
+                name: "Groceries".to_string(),
+                ..common::sample_category_input()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    let transaction = |amount, direction, occurred_at: &str, description: &str| {
+        (
+            CreateTransactionInput {
+                account_id: account.id.clone(),
+                amount,
+                direction,
+                occurred_at: occurred_at.to_string(),
+                description: Some(description.to_string()),
+                ..common::sample_transaction_input()
+            },
+            None,
+        )
+    };
+    let inserted = database
+        .insert_transactions(&[
+            transaction(
+                12.5,
+                TransactionDirection::Expense,
+                "2024-01-15T10:00:00Z",
+                "Bread",
+            ),
+            transaction(
+                900.0,
+                TransactionDirection::Expense,
+                "2024-02-01T09:00:00Z",
+                "Rent",
+            ),
+            transaction(
+                3000.0,
+                TransactionDirection::Income,
+                "2024-02-25T09:00:00Z",
+                "Salary",
+            ),
+        ])
+        .await
+        .unwrap();
+    database
+        .categorize_transaction(&inserted[0].id, Some(&groceries.id))
+        .await
+        .unwrap();
+    database
+}
+
+#[tokio::test]
+async fn test_export_to_google_sheets_replaces_the_tab() {
+    let api = MockServer::start().await;
+    mount_token(&api).await;
+    Mock::given(method("GET"))
+        .and(path("/spreadsheets/sheet-1"))
+        .and(header("authorization", "Bearer access-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sheets": [{ "properties": { "sheetId": 0, "title": "Sheet1" } }],
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/spreadsheets/sheet-1:batchUpdate"))
+        .and(body_partial_json(json!({
+            "requests": [{ "addSheet": { "properties": { "title": "Transactions" } } }],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "spreadsheetId": "sheet-1",
+            "replies": [{ "addSheet": { "properties": { "sheetId": 7, "title": "Transactions" } } }],
+        })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/spreadsheets/sheet-1:batchUpdate"))
+        .and(body_partial_json(json!({
+            "requests": [{ "updateCells": { "fields": "userEnteredValue" } }],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "spreadsheetId": "sheet-1",
+            "replies": [{}],
+        })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    let server = server_with_transactions().await;
+    assert!(server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput::default())))
+        .await
+        .is_err());
+    let server = server.with_google_sheets(google(&api));
+
+    let exported = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput::default())))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(exported["destination"], "google_sheets");
+    assert_eq!(exported["target"], "sheet-1");
+    assert_eq!(exported["sheet"], "Transactions");
+    assert_eq!(exported["rows"], 3);
+    assert_eq!(
+        exported["url"],
+        "https://docs.google.com/spreadsheets/d/sheet-1/edit#gid=7"
+    );
+
+    // The tab is replaced by one request, so a failed export leaves it as
+    // it was.
+    let requests = api.received_requests().await.unwrap();
+    let update = requests
+        .iter()
+        .filter_map(|request| request.body_json::<Value>().ok())
+        .find(|body| body["requests"][0].get("updateCells").is_some())
+        .unwrap();
+    let update = &update["requests"][0]["updateCells"];
+    assert_eq!(
+        update["range"],
+        json!({ "sheetId": 7 }),
+        "the cells past the rows are cleared"
+    );
+    let values: Vec<Vec<Value>> = update["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            row["values"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|cell| cell["userEnteredValue"].clone())
+                .collect()
+        })
+        .collect();
+    assert_eq!(values.len(), 4);
+    assert_eq!(values[0][0], json!({ "stringValue": "Description" }));
+    assert_eq!(values[1][0], json!({ "stringValue": "Salary" }));
+    assert_eq!(values[2][2], json!({ "numberValue": -900.0 }));
+    assert_eq!(
+        values[3][..7],
+        [
+            json!({ "stringValue": "Bread" }),
+            json!({ "stringValue": "2024-01-15" }),
+            json!({ "numberValue": -12.5 }),
+            json!({ "stringValue": "USD" }),
+            json!({ "stringValue": "expense" }),
+            json!({ "stringValue": "Checking" }),
+            json!({ "stringValue": "Groceries" }),
+        ],
+        "text is never parsed as a formula"
+    );
+}
+
+#[tokio::test]
+async fn test_export_to_notion_adds_a_page_per_row() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/databases/db-1"))
+        .and(header("authorization", "Bearer secret_notion"))
+        .and(header("notion-version", "2022-06-28"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "object": "database",
+            "url": "https://www.notion.so/db1",
+            "properties": {
+                "Name": { "id": "title", "type": "title" },
+                "Total": { "id": "a", "type": "number" },
+            },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/databases/db-1"))
+        .and(body_partial_json(json!({
+            "properties": {
+                "Direction": { "select": {} },
+                "Transactions": { "number": {} },
+                "Export key": { "rich_text": {} },
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "database" })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/pages"))
+        .and(body_partial_json(
+            json!({ "parent": { "database_id": "db-1" } }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "page" })))
+        .expect(3)
+        .mount(&api)
+        .await;
+    let server = server_with_transactions().await.with_notion(notion(&api));
+
+    let exported = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            report: SheetReport::MonthlySummary,
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(exported["destination"], "notion");
+    assert_eq!(exported["report"], "monthly_summary");
+    assert_eq!(exported["rows"], 3);
+    assert_eq!(exported["url"], "https://www.notion.so/db1");
+    assert!(exported.get("sheet").is_none());
+
+    let pages: Vec<Value> = api
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/pages")
+        .map(|request| request.body_json::<Value>().unwrap()["properties"].clone())
+        .collect();
+    // Newest month first, the month in the title property.
+    assert_eq!(pages[0]["Name"]["title"][0]["text"]["content"], "2024-02");
+    assert_eq!(pages[0]["Direction"]["select"]["name"], "expense");
+    assert_eq!(pages[0]["Total"]["number"], 900.0);
+    assert_eq!(pages[1]["Direction"]["select"]["name"], "income");
+    assert_eq!(pages[2]["Name"]["title"][0]["text"]["content"], "2024-01");
+    assert_eq!(pages[2]["Category"]["select"]["name"], "Groceries");
+    assert_eq!(
+        pages[2]["Export key"]["rich_text"][0]["text"]["content"],
+        "2024-01/expense/Groceries/USD"
+    );
+}
+
+/// A database an earlier export of the monthly summary keyed, with the page
+/// of January's groceries.
+async fn mount_keyed_database(api: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/databases/db-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "object": "database",
+            "properties": {
+                "Month": { "id": "title", "type": "title" },
+                "Direction": { "id": "b", "type": "select" },
+                "Category": { "id": "c", "type": "select" },
+                "Currency": { "id": "d", "type": "select" },
+                "Total": { "id": "e", "type": "number" },
+                "Transactions": { "id": "f", "type": "number" },
+                "Export key": { "id": "g", "type": "rich_text" },
+            },
+        })))
+        .mount(api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/databases/db-1/query"))
+        .and(body_partial_json(json!({
+            "filter": { "property": "Export key", "rich_text": { "is_not_empty": true } },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "object": "list",
+            "results": [{
+                "object": "page",
+                "id": "page-groceries",
+                "properties": {
+                    "Export key": {
+                        "type": "rich_text",
+                        "rich_text": [{ "plain_text": "2024-01/expense/Groceries/USD" }],
+                    },
+                },
+            }],
+            "has_more": false,
+            "next_cursor": null,
+        })))
+        .expect(1)
+        .mount(api)
+        .await;
+}
+
+#[tokio::test]
+async fn test_export_to_notion_again_updates_the_exported_pages() {
+    let api = MockServer::start().await;
+    mount_keyed_database(&api).await;
+    Mock::given(method("PATCH"))
+        .and(path("/pages/page-groceries"))
+        .and(body_partial_json(json!({
+            "properties": { "Total": { "number": 12.5 } },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "page" })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/pages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "page" })))
+        .expect(2)
+        .mount(&api)
+        .await;
+    let server = server_with_transactions().await.with_notion(notion(&api));
+
+    let exported = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            report: SheetReport::MonthlySummary,
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(exported["rows"], 3);
+    assert!(
+        !api.received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .any(|request| request.method.as_str() == "PATCH"
+                && request.url.path() == "/databases/db-1"),
+        "the database already has every property"
+    );
+}
+
+#[tokio::test]
+async fn test_export_to_notion_waits_out_rate_limits() {
+    let api = MockServer::start().await;
+    mount_keyed_database(&api).await;
+    Mock::given(method("PATCH"))
+        .and(path("/pages/page-groceries"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "0")
+                .set_body_json(json!({
+                    "object": "error",
+                    "status": 429,
+                    "code": "rate_limited",
+                    "message": "You have been rate limited.",
+                })),
+        )
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/pages/page-groceries"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "page" })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/pages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "page" })))
+        .expect(2)
+        .mount(&api)
+        .await;
+    let server = server_with_transactions().await.with_notion(notion(&api));
+
+    let exported = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            report: SheetReport::MonthlySummary,
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(exported["rows"], 3);
+}
+
+#[tokio::test]
+async fn test_export_to_notion_stopped_partway_reports_the_pages_written() {
+    let api = MockServer::start().await;
+    mount_keyed_database(&api).await;
+    Mock::given(method("POST"))
+        .and(path("/pages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "object": "page" })))
+        .up_to_n_times(2)
+        .mount(&api)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/pages/page-groceries"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "object": "error",
+            "status": 400,
+            "code": "validation_error",
+            "message": "Total is expected to be number.",
+        })))
+        .mount(&api)
+        .await;
+    let database = database_with_transactions().await;
+    let server = server_over(database.clone()).with_notion(notion(&api));
+
+    let err = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            report: SheetReport::MonthlySummary,
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["pages_written"], 2);
+    assert_eq!(data["notion"]["code"], "validation_error");
+    let events = database
+        .list_audit_events(&ListAuditEventsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1, "the pages written are audited");
+    assert_eq!(events[0].tool, "export_to_sheet");
+}
+
+#[tokio::test]
+async fn test_export_to_notion_escapes_the_database_id() {
+    let api = MockServer::start().await;
+    let server = server_with_transactions().await.with_notion(notion(&api));
+
+    let _ = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            target: Some("../pages/page-1".to_string()),
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap_err();
+    let requests = api.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].url.path(), "/databases/..%2Fpages%2Fpage-1");
+}
+
+#[tokio::test]
+async fn test_export_to_sheet_dry_run_and_arguments() {
+    let google_api = MockServer::start().await;
+    let notion_api = MockServer::start().await;
+    let server = server_with_transactions()
+        .await
+        .with_google_sheets(google(&google_api))
+        .with_notion(notion(&notion_api));
+
+    let err = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput::default())))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "destination");
+
+    let planned = server
+        .export_to_sheet(Parameters(DryRun {
+            input: ExportToSheetInput {
+                destination: Some(SheetDestination::GoogleSheets),
+                report: SheetReport::MonthlySummary,
+                filters: exaspoon_db_mcp::models::TransactionFilters {
+                    direction: Some(TransactionDirection::Expense),
+                    ..Default::default()
+                },
+                target: Some("sheet-2".to_string()),
+                sheet: None,
+            },
+            dry_run: Some(true),
+        }))
+        .await
+        .unwrap()
+        .structured_content
+        .unwrap();
+    assert_eq!(planned["dry_run"], true);
+    assert_eq!(planned["target"], "sheet-2");
+    assert_eq!(planned["sheet"], "Monthly summary");
+    assert_eq!(planned["rows"], 2);
+    assert!(google_api.received_requests().await.unwrap().is_empty());
+
+    let unconfigured = server_with_transactions().await.with_notion(
+        NotionClient::new(
+            &NotionConfig {
+                token: "secret_notion".to_string(),
+                database_id: None,
+                base_url: notion_api.uri(),
+            },
+            &HttpClientConfig::default(),
+        )
+        .unwrap(),
+    );
+    let err = unconfigured
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput::default())))
+        .await
+        .unwrap_err();
+    assert_eq!(err.data.unwrap()["field"], "target");
+}
+
+#[tokio::test]
+async fn test_export_to_sheet_errors_carry_the_api_error() {
+    let google_api = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": "invalid_grant",
+            "error_description": "Token has been expired or revoked.",
+        })))
+        .mount(&google_api)
+        .await;
+    let notion_api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/databases/db-1"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "object": "error",
+            "status": 404,
+            "code": "object_not_found",
+            "message": "Could not find database with ID: db-1.",
+        })))
+        .mount(&notion_api)
+        .await;
+    let server = server_with_transactions()
+        .await
+        .with_google_sheets(google(&google_api))
+        .with_notion(notion(&notion_api));
+
+    let err = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            destination: Some(SheetDestination::GoogleSheets),
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["google"]["status_code"], 400);
+    assert_eq!(data["google"]["status"], "invalid_grant");
+    assert_eq!(
+        data["google"]["message"],
+        "Token has been expired or revoked."
+    );
+
+    let err = server
+        .export_to_sheet(Parameters(DryRun::from(ExportToSheetInput {
+            destination: Some(SheetDestination::Notion),
+            ..ExportToSheetInput::default()
+        })))
+        .await
+        .unwrap_err();
+    let data = err.data.unwrap();
+    assert_eq!(data["notion"]["status_code"], 404);
+    assert_eq!(data["notion"]["code"], "object_not_found");
+}
@@ -0,0 +1,39 @@
+//! Tests for draining tool calls on shutdown.
+
+use exaspoon_db_mcp::shutdown::Drain;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_drain_waits_for_running_calls() {
+    let drain = Arc::new(Drain::default());
+    let (started, running) = tokio::sync::oneshot::channel();
+    let call = tokio::spawn({
+        let drain = drain.clone();
+        async move {
+            let _call = drain.enter().expect("admitted before the drain");
+            started.send(()).unwrap();
+            tokio::time::sleep(Duration::from_millis(40)).await;
+        }
+    });
+    running.await.unwrap();
+    assert_eq!(drain.in_flight(), 1);
+
+    let start_time = Instant::now();
+    assert_eq!(drain.drain(Duration::from_secs(1)).await, 0);
+    assert!(
+        start_time.elapsed() >= Duration::from_millis(20),
+        "waits for the call"
+    );
+    call.await.unwrap();
+    assert!(drain.enter().is_none(), "no calls are admitted after it");
+}
+
+#[tokio::test]
+async fn test_drain_gives_up_at_the_deadline() {
+    let drain = Drain::default();
+    let _stuck = drain.enter().unwrap();
+    let _other = drain.enter().unwrap();
+    assert_eq!(drain.drain(Duration::from_millis(20)).await, 2);
+    assert!(drain.is_draining());
+}
@@ -0,0 +1,172 @@
+//! Tests for the TCP and Unix domain socket transports, driven with raw
+//! newline-delimited JSON-RPC.
+#![cfg(feature = "memory-backend")]
+
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::socket;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, Lines,
+};
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+struct LineClient<S> {
+    lines: Lines<BufReader<ReadHalf<S>>>,
+    writer: WriteHalf<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite> LineClient<S> {
+    async fn connect(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        let mut client = Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        };
+        client.send(common::initialize_request(0)).await;
+        let initialized = client.until_response(0).await;
+        assert_eq!(
+            initialized["result"]["protocolVersion"],
+            common::PROTOCOL_VERSION
+        );
+        client.send(common::initialized_notification()).await;
+        client
+    }
+
+    async fn send(&mut self, message: Value) {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+    }
+
+    async fn until_response(&mut self, id: u64) -> Value {
+        loop {
+            let line = self
+                .lines
+                .next_line()
+                .await
+                .unwrap()
+                .expect("stream closed");
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if message["id"] == json!(id) && message.get("method").is_none() {
+                return message;
+            }
+        }
+    }
+
+    async fn call(&mut self, id: u64, name: &str, arguments: Value) -> Value {
+        self.send(common::tool_call(id, name, arguments)).await;
+        self.until_response(id).await
+    }
+}
+
+fn server() -> ExaspoonDbServer {
+    ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    )
+}
+
+#[tokio::test]
+async fn test_tcp_clients_get_sessions_of_their_own() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(socket::serve_tcp(server(), listener, stop.clone()));
+
+    let mut first = LineClient::connect(TcpStream::connect(address).await.unwrap()).await;
+    let mut second = LineClient::connect(TcpStream::connect(address).await.unwrap()).await;
+
+    let pong = first.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+    let set = first
+        .call(2, "set_session_defaults", json!({ "currency": "EUR" }))
+        .await;
+    assert_eq!(
+        set["result"]["structuredContent"]["defaults"]["currency"],
+        "EUR"
+    );
+    let other = second.call(1, "set_session_defaults", json!({})).await;
+    assert_eq!(other["result"]["structuredContent"]["defaults"], json!({}));
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_sessions_beyond_the_limit_are_refused_until_one_ends() {
+    let server = server().with_max_sessions(1);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(socket::serve_tcp(server.clone(), listener, stop.clone()));
+
+    let mut first = LineClient::connect(TcpStream::connect(address).await.unwrap()).await;
+    let pong = first.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    // The second connection is closed without a session.
+    let mut refused = TcpStream::connect(address).await.unwrap();
+    let mut buffer = [0; 1];
+    assert_eq!(refused.read(&mut buffer).await.unwrap(), 0);
+
+    drop(first);
+    let mut open = 1;
+    for _ in 0..100 {
+        let metrics = server.server_metrics().await.unwrap();
+        open = metrics.structured_content.unwrap()["sessions"]
+            .as_u64()
+            .unwrap();
+        if open == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(open, 0);
+
+    let mut third = LineClient::connect(TcpStream::connect(address).await.unwrap()).await;
+    let pong = third.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_serves_clients_and_is_removed_on_stop() {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixStream;
+
+    let path = std::env::temp_dir().join(format!("exaspoon-{}.sock", uuid::Uuid::new_v4()));
+    // A socket left behind by an earlier run is replaced.
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+    let listener = socket::bind_unix(&path).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // A socket a running server listens on is not taken over.
+    assert!(socket::bind_unix(&path).is_err());
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(socket::serve_unix(server(), listener, stop.clone()));
+
+    let mut client = LineClient::connect(UnixStream::connect(&path).await.unwrap()).await;
+    let pong = client.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+    assert!(!path.exists());
+
+    // Anything but a socket is left alone.
+    std::fs::write(&path, "keep me").unwrap();
+    assert!(socket::bind_unix(&path).is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+    std::fs::remove_file(&path).unwrap();
+}
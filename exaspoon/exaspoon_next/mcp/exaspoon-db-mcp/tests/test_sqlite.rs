@@ -0,0 +1,855 @@
+//! Tests for the SQLite offline backend.
+#![cfg(feature = "sqlite")]
+
+use exaspoon_db_mcp::batch::{BatchFailed, BatchStep, MissingRow};
+use exaspoon_db_mcp::models::{
+    AccountType, AggregateSpendingInput, AuditEvent, BatchStepResult, CategorizeInput,
+    CategoryKind, EmbeddingIssueKind, ListAccountsInput, ListAuditEventsInput,
+    ListTransactionsInput, RecordKind, SpendingGroupBy, SpendingPeriod, TransactionDirection,
+    TransactionFilters, UpsertCategoryInput,
+};
+use exaspoon_db_mcp::sqlite::SqliteDatabase;
+use exaspoon_db_mcp::supabase::Database;
+
+mod common;
+
+#[tokio::test]
+async fn test_sqlite_upsert_account_merges_on_name_and_type() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let mut input = common::sample_account_input();
+
+    let first = db.upsert_account(&input).await.unwrap();
+    input.institution = Some("Other Bank".to_string());
+    let second = db.upsert_account(&input).await.unwrap();
+
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.institution, Some("Other Bank".to_string()));
+
+    input.r#type = AccountType::Onchain;
+    let third = db.upsert_account(&input).await.unwrap();
+    assert_ne!(third.id, first.id);
+}
+
+#[tokio::test]
+async fn test_sqlite_list_accounts_filters_by_type_and_search() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let mut input = common::sample_account_input();
+    db.upsert_account(&input).await.unwrap();
+    input.name = "Savings".to_string();
+    db.upsert_account(&input).await.unwrap();
+    input.name = "Wallet".to_string();
+    input.r#type = AccountType::Onchain;
+    db.upsert_account(&input).await.unwrap();
+
+    let offchain = db
+        .list_accounts(&ListAccountsInput {
+            r#type: Some(AccountType::Offchain),
+            search: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(offchain.len(), 2);
+
+    let matches = db
+        .list_accounts(&ListAccountsInput {
+            r#type: None,
+            search: Some("  sav ".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].name, "Savings");
+}
+
+#[tokio::test]
+async fn test_sqlite_insert_and_search_transactions() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    let mut coffee = common::sample_transaction_input();
+    coffee.account_id = account.id.clone();
+    let inserted = db
+        .insert_transaction(&coffee, Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+    assert_eq!(inserted.account_id, account.id);
+    assert_eq!(inserted.amount, 42.0);
+    assert_eq!(inserted.description, Some("Coffee".to_string()));
+
+    let mut rent = coffee.clone();
+    rent.description = Some("Rent".to_string());
+    db.insert_transaction(&rent, Some(vec![0.0, 1.0])).await.unwrap();
+    let mut unembedded = coffee.clone();
+    unembedded.description = None;
+    db.insert_transaction(&unembedded, None).await.unwrap();
+
+    let matches = db
+        .search_similar_transactions(vec![0.9, 0.1], Some(5))
+        .await
+        .unwrap();
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].transaction.id, inserted.id);
+    assert!(matches[0].similarity.unwrap() > matches[1].similarity.unwrap());
+
+    let limited = db
+        .search_similar_transactions(vec![0.9, 0.1], Some(1))
+        .await
+        .unwrap();
+    assert_eq!(limited.len(), 1);
+}
+
+#[tokio::test]
+async fn test_sqlite_search_transactions_text_requires_every_word() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    input.description = Some("Payment for Invoice 4812".to_string());
+    let invoice = db.insert_transaction(&input, None).await.unwrap();
+    input.description = Some("Invoice 4813".to_string());
+    input.raw_source = Some("ref 4812-b".to_string());
+    input.occurred_at = "2024-01-03T00:00:00Z".to_string();
+    let referenced = db.insert_transaction(&input, None).await.unwrap();
+    input.description = Some("Invoice 7000".to_string());
+    input.raw_source = None;
+    db.insert_transaction(&input, None).await.unwrap();
+
+    let matches = db
+        .search_transactions_text("invoice 4812", None)
+        .await
+        .unwrap();
+    let ids = matches
+        .iter()
+        .map(|found| found.transaction.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [referenced.id.as_str(), invoice.id.as_str()]);
+    assert!(matches.iter().all(|found| found.rank.is_none()));
+
+    let limited = db
+        .search_transactions_text("INVOICE", Some(1))
+        .await
+        .unwrap();
+    assert_eq!(limited.len(), 1);
+
+    db.soft_delete(RecordKind::Transaction, &invoice.id)
+        .await
+        .unwrap();
+    let matches = db
+        .search_transactions_text("payment 4812", None)
+        .await
+        .unwrap();
+    assert!(matches.is_empty());
+}
+
+#[tokio::test]
+async fn test_sqlite_insert_transaction_requires_existing_account() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+
+    let result = db
+        .insert_transaction(&common::sample_transaction_input(), None)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_sqlite_insert_transactions_is_atomic() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut valid = common::sample_transaction_input();
+    valid.account_id = account.id.clone();
+
+    let inserted = db
+        .insert_transactions(&[(valid.clone(), Some(vec![1.0, 0.0])), (valid.clone(), None)])
+        .await
+        .unwrap();
+    assert_eq!(inserted.len(), 2);
+    assert_ne!(inserted[0].id, inserted[1].id);
+
+    let mut orphan = valid.clone();
+    orphan.account_id = "missing".to_string();
+    let result = db
+        .insert_transactions(&[(valid, Some(vec![0.0, 1.0])), (orphan, None)])
+        .await;
+    assert!(result.is_err());
+
+    let matches = db
+        .search_similar_transactions(vec![0.0, 1.0], Some(25))
+        .await
+        .unwrap();
+    assert_eq!(
+        matches.len(),
+        1,
+        "the failed batch must not leave rows behind"
+    );
+}
+
+#[tokio::test]
+async fn test_sqlite_execute_batch_is_atomic() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let category = db
+        .upsert_category(&common::sample_category_input(), None)
+        .await
+        .unwrap();
+    let mut transaction = common::sample_transaction_input();
+    transaction.account_id = "$0".to_string();
+
+    let steps = db
+        .execute_batch(&[
+            BatchStep::UpsertAccount(common::sample_account_input()),
+            BatchStep::CreateTransactions(vec![(transaction.clone(), Some(vec![1.0, 0.0]))]),
+            BatchStep::Categorize(CategorizeInput {
+                transaction_id: "$1".to_string(),
+                category_id: category.id.clone(),
+            }),
+        ])
+        .await
+        .unwrap();
+    let BatchStepResult::UpsertAccount { account } = &steps[0] else {
+        panic!("unexpected first step {:?}", steps[0]);
+    };
+    let BatchStepResult::Categorize { transaction: filed } = &steps[2] else {
+        panic!("unexpected last step {:?}", steps[2]);
+    };
+    assert_eq!(filed.account_id, account.id);
+    assert_eq!(filed.category_id.as_deref(), Some(category.id.as_str()));
+
+    let mut savings = common::sample_account_input();
+    savings.name = "Savings".to_string();
+    let err = db
+        .execute_batch(&[
+            BatchStep::UpsertAccount(savings),
+            BatchStep::CreateTransactions(vec![(transaction, None)]),
+            BatchStep::Categorize(CategorizeInput {
+                transaction_id: "missing".to_string(),
+                category_id: category.id.clone(),
+            }),
+        ])
+        .await
+        .unwrap_err();
+    let failed = err.downcast_ref::<BatchFailed>().expect("a failed batch");
+    assert_eq!(failed.step, 2);
+    assert!(failed.rolled_back);
+    assert!(failed.source.downcast_ref::<MissingRow>().is_some());
+
+    assert_eq!(
+        db.count_accounts(&ListAccountsInput::default()).await.unwrap(),
+        1,
+        "the failed batch must not leave rows behind"
+    );
+    assert_eq!(
+        db.count_transactions(&TransactionFilters::default())
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_sqlite_aggregate_spending_by_direction_and_month() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    let mut rows = Vec::new();
+    for (amount, direction, occurred_at, currency) in [
+        (
+            10.0,
+            TransactionDirection::Expense,
+            "2024-01-05T10:00:00Z",
+            "USD",
+        ),
+        (
+            15.0,
+            TransactionDirection::Expense,
+            "2024-01-20T10:00:00Z",
+            "USD",
+        ),
+        (
+            7.0,
+            TransactionDirection::Expense,
+            "2024-01-21T10:00:00Z",
+            "EUR",
+        ),
+        (
+            99.0,
+            TransactionDirection::Income,
+            "2024-01-25T10:00:00Z",
+            "USD",
+        ),
+        (
+            20.0,
+            TransactionDirection::Expense,
+            "2024-02-02T10:00:00Z",
+            "USD",
+        ),
+    ] {
+        let mut row = input.clone();
+        row.amount = amount;
+        row.direction = direction;
+        row.occurred_at = occurred_at.to_string();
+        row.currency = currency.to_string();
+        rows.push((row, None));
+    }
+    db.insert_transactions(&rows).await.unwrap();
+
+    let buckets = db
+        .aggregate_spending(&AggregateSpendingInput {
+            group_by: SpendingGroupBy::Direction,
+            period: Some(SpendingPeriod::Month),
+            filters: TransactionFilters {
+                direction: Some(TransactionDirection::Expense),
+                to: Some("2024-02-01T00:00:00Z".to_string()),
+                ..TransactionFilters::default()
+            },
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].key.as_deref(), Some("expense"));
+    assert_eq!(
+        buckets[0].period_start.as_deref(),
+        Some("2024-01-01T00:00:00Z")
+    );
+    assert_eq!(buckets[0].currency, "USD");
+    assert_eq!(buckets[0].total, 25.0);
+    assert_eq!(buckets[0].count, 2);
+    assert_eq!(buckets[1].currency, "EUR");
+
+    let weekly = db
+        .aggregate_spending(&AggregateSpendingInput {
+            group_by: SpendingGroupBy::Account,
+            period: Some(SpendingPeriod::Week),
+            filters: TransactionFilters {
+                from: Some("2024-01-20T00:00:00Z".to_string()),
+                to: Some("2024-01-22T00:00:00Z".to_string()),
+                ..TransactionFilters::default()
+            },
+        })
+        .await
+        .unwrap();
+    assert_eq!(weekly.len(), 2);
+    assert!(weekly
+        .iter()
+        .all(|bucket| bucket.period_start.as_deref() == Some("2024-01-15T00:00:00Z")));
+    assert_eq!(weekly[0].key.as_deref(), Some(account.id.as_str()));
+}
+
+#[tokio::test]
+async fn test_sqlite_list_transactions_paginates_with_count() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    let mut rows = Vec::new();
+    for (day, direction, amount) in [
+        (1, TransactionDirection::Expense, 40.0),
+        (2, TransactionDirection::Income, 90.0),
+        (3, TransactionDirection::Expense, 5.0),
+        (4, TransactionDirection::Expense, 25.0),
+    ] {
+        let mut row = input.clone();
+        row.direction = direction;
+        row.amount = amount;
+        row.occurred_at = format!("2024-03-0{day}T12:00:00Z");
+        rows.push((row, None));
+    }
+    db.insert_transactions(&rows).await.unwrap();
+
+    let filters = TransactionFilters {
+        direction: Some(TransactionDirection::Expense),
+        ..TransactionFilters::default()
+    };
+    let page = db
+        .list_transactions(&ListTransactionsInput {
+            filters: filters.clone(),
+            limit: Some(2),
+            offset: Some(1),
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].occurred_at, "2024-03-03T12:00:00Z");
+    assert_eq!(page[1].occurred_at, "2024-03-01T12:00:00Z");
+    assert_eq!(db.count_transactions(&filters).await.unwrap(), 3);
+    let largest = db.largest_transactions(&filters, 2).await.unwrap();
+    let amounts = largest.iter().map(|row| row.amount).collect::<Vec<_>>();
+    assert_eq!(amounts, [40.0, 25.0]);
+    assert_eq!(
+        db.count_transactions(&TransactionFilters::default())
+            .await
+            .unwrap(),
+        4
+    );
+
+    let params = ListAccountsInput {
+        limit: Some(1),
+        offset: Some(1),
+        ..Default::default()
+    };
+    assert!(db.list_accounts(&params).await.unwrap().is_empty());
+    assert_eq!(db.count_accounts(&params).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_sqlite_soft_delete_hides_rows_until_purged() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let category = db
+        .upsert_category(&common::sample_category_input(), Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    let transaction = db
+        .insert_transaction(&input, Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+
+    assert!(db
+        .soft_delete(RecordKind::Transaction, &transaction.id)
+        .await
+        .unwrap());
+    assert!(!db
+        .soft_delete(RecordKind::Transaction, &transaction.id)
+        .await
+        .unwrap());
+    assert_eq!(
+        db.count_transactions(&TransactionFilters::default())
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(db
+        .search_similar_transactions(vec![1.0, 0.0], None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert!(db
+        .soft_delete(RecordKind::Category, &category.id)
+        .await
+        .unwrap());
+    assert!(db
+        .search_similar_categories(vec![1.0, 0.0], None)
+        .await
+        .unwrap()
+        .is_empty());
+    let revived = db
+        .upsert_category(&common::sample_category_input(), Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+    assert_eq!(revived.id, category.id);
+    assert_eq!(
+        db.search_similar_categories(vec![1.0, 0.0], None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    assert_eq!(
+        db.purge_deleted(RecordKind::Transaction, Some("2000-01-01T00:00:00Z"))
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        db.purge_deleted(RecordKind::Transaction, None)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(!db
+        .soft_delete(RecordKind::Transaction, &transaction.id)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_sqlite_reports_and_repairs_embedding_issues() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let mut ids = Vec::new();
+    for (amount, embedding) in [
+        (1.0, None),
+        (2.0, Some(vec![1.0, 0.0])),
+        (3.0, Some(vec![1.0, 0.0, 0.0])),
+    ] {
+        let mut input = common::sample_transaction_input();
+        input.account_id = account.id.clone();
+        input.amount = amount;
+        ids.push(db.insert_transaction(&input, embedding).await.unwrap().id);
+    }
+    db.soft_delete(RecordKind::Transaction, &ids[2])
+        .await
+        .unwrap();
+    db.upsert_category(&common::sample_category_input(), Some(vec![0.0, 1.0, 0.0]))
+        .await
+        .unwrap();
+
+    let issues = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|issue| (issue.id, issue.issue))
+        .collect::<Vec<_>>();
+    let mut expected = vec![
+        (ids[0].clone(), EmbeddingIssueKind::Missing),
+        (ids[1].clone(), EmbeddingIssueKind::Dimension),
+        (ids[2].clone(), EmbeddingIssueKind::Orphaned),
+    ];
+    expected.sort_by(|left, right| left.0.cmp(&right.0));
+    assert_eq!(issues, expected);
+    assert!(db
+        .embedding_issues(RecordKind::Category, 3, None, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    let first = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 1)
+        .await
+        .unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(
+        db.embedding_issues(RecordKind::Transaction, 3, Some(&first[0].id), 10)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+
+    db.set_embedding(RecordKind::Transaction, &ids[0], Some(vec![0.0, 0.0, 1.0]))
+        .await
+        .unwrap();
+    db.set_embedding(RecordKind::Transaction, &ids[2], None)
+        .await
+        .unwrap();
+    let remaining = db
+        .embedding_issues(RecordKind::Transaction, 3, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].id, ids[1]);
+    assert_eq!(remaining[0].content.as_deref(), Some("Coffee"));
+    assert!(db
+        .set_embedding(RecordKind::Transaction, "txn-404", None)
+        .await
+        .is_err());
+    assert!(db
+        .set_embedding(RecordKind::Account, &account.id, None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_sqlite_purging_category_uncategorizes_transactions() {
+    let path = std::env::temp_dir().join(format!("exaspoon-purge-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let db = SqliteDatabase::open(&path).unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let category = db
+        .upsert_category(&common::sample_category_input(), None)
+        .await
+        .unwrap();
+    let mut input = common::sample_transaction_input();
+    input.account_id = account.id.clone();
+    db.insert_transaction(&input, None).await.unwrap();
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute("update transactions set category_id = ?1", [&category.id])
+            .unwrap();
+    }
+
+    db.soft_delete(RecordKind::Category, &category.id)
+        .await
+        .unwrap();
+    assert_eq!(
+        db.purge_deleted(RecordKind::Category, None).await.unwrap(),
+        1
+    );
+
+    let transactions = db
+        .list_transactions(&ListTransactionsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].category_id, None);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_sqlite_delete_record_removes_live_and_deleted_rows() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+    let category = db
+        .upsert_category(&common::sample_category_input(), None)
+        .await
+        .unwrap();
+    db.soft_delete(RecordKind::Category, &category.id)
+        .await
+        .unwrap();
+
+    assert!(db
+        .delete_record(RecordKind::Category, &category.id)
+        .await
+        .unwrap());
+    assert!(db
+        .delete_record(RecordKind::Account, &account.id)
+        .await
+        .unwrap());
+    assert!(!db
+        .delete_record(RecordKind::Account, &account.id)
+        .await
+        .unwrap());
+    assert_eq!(
+        db.purge_deleted(RecordKind::Category, None).await.unwrap(),
+        0
+    );
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    assert!(accounts.is_empty());
+}
+
+#[tokio::test]
+async fn test_sqlite_sets_the_network_of_live_accounts() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let account = db
+        .upsert_account(&common::sample_account_input())
+        .await
+        .unwrap();
+
+    db.set_account_network(&account.id, "solana").await.unwrap();
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(accounts[0].network.as_deref(), Some("solana"));
+    assert_eq!(accounts[0].name, account.name);
+
+    db.soft_delete(RecordKind::Account, &account.id)
+        .await
+        .unwrap();
+    assert!(db
+        .set_account_network(&account.id, "ethereum")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_sqlite_upsert_and_search_categories() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let food = db
+        .upsert_category(&common::sample_category_input(), Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+    let salary = UpsertCategoryInput {
+        name: "Salary".to_string(),
+        kind: Some(CategoryKind::Income),
+        description: None,
+    };
+    let salary = db.upsert_category(&salary, Some(vec![0.0, 1.0])).await.unwrap();
+    assert_eq!(salary.description, Some("Salary".to_string()));
+
+    let updated = db
+        .upsert_category(&common::sample_category_input(), Some(vec![1.0, 0.0]))
+        .await
+        .unwrap();
+    assert_eq!(updated.id, food.id);
+
+    let matches = db
+        .search_similar_categories(vec![0.0, 1.0], None)
+        .await
+        .unwrap();
+    assert_eq!(matches[0].category.name, "Salary");
+    assert_eq!(matches[0].category.kind, CategoryKind::Income);
+}
+
+#[tokio::test]
+async fn test_sqlite_search_skips_mismatched_dimensions() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    db.upsert_category(&common::sample_category_input(), Some(vec![1.0, 0.0, 0.0]))
+        .await
+        .unwrap();
+
+    let matches = db
+        .search_similar_categories(vec![1.0, 0.0], None)
+        .await
+        .unwrap();
+
+    assert!(matches.is_empty());
+}
+
+#[tokio::test]
+async fn test_sqlite_records_and_filters_audit_events() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let event = |tool: &str, record_id: Option<&str>| AuditEvent {
+        tool: tool.to_string(),
+        input_hash: "0".repeat(64),
+        record_id: record_id.map(str::to_string),
+        occurred_at: "2024-01-01T00:00:00.000Z".to_string(),
+        session_id: Some("session-1".to_string()),
+        details: None,
+    };
+    db.record_audit_events(&[
+        event("upsert_account", Some("acct-1")),
+        event("purge_deleted", None),
+        event("delete_account", Some("acct-1")),
+    ])
+    .await
+    .unwrap();
+
+    let all = db
+        .list_audit_events(&ListAuditEventsInput::default())
+        .await
+        .unwrap();
+    let tools: Vec<_> = all.iter().map(|e| e.tool.as_str()).collect();
+    assert_eq!(tools, ["delete_account", "purge_deleted", "upsert_account"]);
+    assert_eq!(all[1].record_id, None);
+    assert_eq!(all[0].session_id.as_deref(), Some("session-1"));
+
+    let for_record = db
+        .list_audit_events(&ListAuditEventsInput {
+            record_id: Some("acct-1".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(for_record.len(), 1);
+    assert_eq!(for_record[0].tool, "delete_account");
+}
+
+#[tokio::test]
+async fn test_sqlite_round_trips_audit_event_details() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    let details = serde_json::json!({ "arguments": { "limit": 5 }, "duration_ms": 3 });
+    db.record_audit_events(&[AuditEvent {
+        tool: "list_accounts".to_string(),
+        input_hash: "0".repeat(64),
+        record_id: None,
+        occurred_at: "2024-01-01T00:00:00.000Z".to_string(),
+        session_id: None,
+        details: Some(details.clone()),
+    }])
+    .await
+    .unwrap();
+
+    let events = db
+        .list_audit_events(&ListAuditEventsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(events[0].details, Some(details));
+}
+
+#[tokio::test]
+async fn test_sqlite_lists_live_category_names() {
+    let db = SqliteDatabase::open_in_memory().unwrap();
+    for name in ["Groceries", "Gym", "Rent"] {
+        let mut input = common::sample_category_input();
+        input.name = name.to_string();
+        db.upsert_category(&input, None).await.unwrap();
+    }
+    let mut input = common::sample_category_input();
+    input.name = "Gifts".to_string();
+    let gifts = db.upsert_category(&input, None).await.unwrap();
+    db.soft_delete(RecordKind::Category, &gifts.id)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        db.list_category_names(" G ", 10).await.unwrap(),
+        vec!["Groceries", "Gym"]
+    );
+    assert_eq!(
+        db.list_category_names("", 1).await.unwrap(),
+        vec!["Groceries"]
+    );
+}
+
+#[tokio::test]
+async fn test_sqlite_opens_files_from_before_exchange_accounts() {
+    let path = std::env::temp_dir().join(format!("exaspoon-exchange-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "create table accounts (
+               id text primary key, name text not null,
+               type text not null check (type in ('onchain','offchain')),
+               currency text not null, network text, institution text, metadata text,
+               created_at text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+               deleted_at text, unique (name, type)
+             );
+             create table transactions (
+               id text primary key, account_id text not null references accounts(id) on delete cascade,
+               amount real not null, currency text not null, direction text not null,
+               occurred_at text not null, description text, raw_source text, category_id text,
+               embedding blob,
+               created_at text not null default (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+               deleted_at text
+             );
+             insert into accounts (id, name, type, currency) values ('acct-1', 'Checking', 'offchain', 'USD');
+             insert into transactions (id, account_id, amount, currency, direction, occurred_at)
+               values ('txn-1', 'acct-1', 12.5, 'USD', 'expense', '2024-03-01T00:00:00Z');",
+        )
+        .unwrap();
+    }
+
+    let db = SqliteDatabase::open(&path).unwrap();
+    let mut input = common::sample_account_input();
+    input.name = "Binance".to_string();
+    input.r#type = AccountType::Exchange;
+    let exchange = db.upsert_account(&input).await.unwrap();
+    assert_eq!(exchange.r#type, AccountType::Exchange);
+
+    let transactions = db
+        .list_transactions(&ListTransactionsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].account_id, "acct-1");
+    // Opening again leaves the rebuilt table alone.
+    drop(db);
+    let db = SqliteDatabase::open(&path).unwrap();
+    let accounts = db
+        .list_accounts(&ListAccountsInput::default())
+        .await
+        .unwrap();
+    assert_eq!(accounts.len(), 2);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,216 @@
+//! Tests for reading MT940 and CAMT.053 bank statements.
+
+use exaspoon_db_mcp::models::{StatementFormat, TransactionDirection};
+use exaspoon_db_mcp::statement;
+
+mod common;
+
+/// A German bank's MT940 export in a SWIFT envelope, with `?NN` and
+/// `/KEY/` information and a statement line booked in the next year.
+const MT940: &str = "{1:F01DEUTDEFFAXXX0000000000}{2:O940}{4:
+:20:STARTUMSE
+:25:37040044/0532013000
+:28C:00001/001
+:60F:C231229EUR1000,00
+:61:2312290102DR42,50NMSCNONREF//8327000090031789
+Card payment
+:86:106?00KARTENZAHLUNG?20REWE SAGT DANKE 4481?21BERLIN
+?32REWE Markt GmbH
+:61:240115C3000,NTRFNONREF
+:86:/EREF/SALARY-2024-01/REMI/USTD//Salary January
+/NAME/ACME GmbH/
+:61:240120RC15,00NCHGREF-77
+:86:Fee refund
+reversed
+:62F:C240131EUR3942,50
+-}";
+
+/// A CAMT.053 statement with a booked debit, the reversal of an earlier
+/// debit and a pending entry.
+const CAMT053: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <GrpHdr><MsgId>MSG-1</MsgId></GrpHdr>
+    <Stmt>
+      <Id>STMT-1</Id>
+      <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+      <!-- booked card payment -->
+      <Ntry>
+        <Amt Ccy="EUR">42.50</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-01-05</Dt></BookgDt>
+        <AcctSvcrRef>REF-1</AcctSvcrRef>
+        <NtryDtls><TxDtls>
+          <RltdPties><Cdtr><Nm>Caf&#233; &amp; Bar</Nm></Cdtr></RltdPties>
+          <RmtInf><Ustrd>Lunch</Ustrd><Ustrd>team</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="USD">10.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <RvslInd>true</RvslInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><DtTm>2024-01-06T10:30:00+01:00</DtTm></BookgDt>
+        <AddtlNtryInf>Returned transfer</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">5.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>PDNG</Sts>
+        <BookgDt><Dt>2024-01-07</Dt></BookgDt>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+#[test]
+fn test_detects_the_format() {
+    assert_eq!(statement::detect(MT940).unwrap(), StatementFormat::Mt940);
+    assert_eq!(
+        statement::detect(CAMT053).unwrap(),
+        StatementFormat::Camt053
+    );
+    assert!(statement::detect("date;amount\n2024-01-05;12").is_err());
+}
+
+#[test]
+fn test_parses_mt940_statement_lines() {
+    let entries = statement::parse(MT940, StatementFormat::Mt940).unwrap();
+    assert_eq!(entries.len(), 3);
+
+    let card = &entries[0];
+    assert_eq!(card.booked_at, "2024-01-02T00:00:00Z");
+    assert_eq!(card.amount, -42.5);
+    assert_eq!(card.currency.as_deref(), Some("EUR"));
+    assert_eq!(card.reference.as_deref(), Some("8327000090031789"));
+    assert_eq!(card.counterparty.as_deref(), Some("REWE Markt GmbH"));
+    assert_eq!(card.details.as_deref(), Some("REWE SAGT DANKE 4481BERLIN"));
+    assert!(card.raw.starts_with(":61:2312290102DR42,50NMSC"));
+    assert!(card.raw.contains("\n:86:106?00KARTENZAHLUNG"));
+
+    let salary = &entries[1];
+    assert_eq!(salary.amount, 3000.0);
+    assert_eq!(salary.reference, None);
+    assert_eq!(salary.counterparty.as_deref(), Some("ACME GmbH"));
+    assert_eq!(salary.details.as_deref(), Some("Salary January"));
+    let input = salary.to_input("acct-1", "");
+    assert_eq!(input.direction, TransactionDirection::Income);
+    assert_eq!(
+        input.description.as_deref(),
+        Some("ACME GmbH - Salary January")
+    );
+
+    let refund = &entries[2];
+    assert_eq!(refund.amount, -15.0);
+    assert_eq!(refund.reference.as_deref(), Some("REF-77"));
+    assert_eq!(refund.details.as_deref(), Some("Fee refund reversed"));
+}
+
+#[test]
+fn test_parses_camt053_entries() {
+    let entries = statement::parse(CAMT053, StatementFormat::Camt053).unwrap();
+    assert_eq!(entries.len(), 3);
+
+    let lunch = &entries[0];
+    assert_eq!(lunch.amount, -42.5);
+    assert_eq!(lunch.booked_at, "2024-01-05T00:00:00Z");
+    assert_eq!(lunch.reference.as_deref(), Some("REF-1"));
+    assert_eq!(lunch.counterparty.as_deref(), Some("Café & Bar"));
+    assert_eq!(lunch.details.as_deref(), Some("Lunch team"));
+    assert!(!lunch.pending);
+    assert!(lunch.raw.starts_with("<Ntry><Amt Ccy=\"EUR\">42.50</Amt>"));
+    assert!(lunch.raw.ends_with("</Ntry>"));
+
+    let returned = &entries[1];
+    assert_eq!(returned.amount, 10.0);
+    assert_eq!(returned.currency.as_deref(), Some("USD"));
+    assert_eq!(returned.booked_at, "2024-01-06T09:30:00Z");
+    assert_eq!(returned.details.as_deref(), Some("Returned transfer"));
+    assert!(entries[2].pending);
+}
+
+#[test]
+fn test_rejects_malformed_statements() {
+    let broken = MT940.replace("240115C3000,", "240115X3000,");
+    let err = statement::parse(&broken, StatementFormat::Mt940).unwrap_err();
+    assert!(
+        format!("{err:#}").starts_with("statement line 2:"),
+        "{err:#}"
+    );
+
+    let broken = CAMT053.replace(
+        "</Sts>\n        <BookgDt><Dt>2024-01-07",
+        "\n        <BookgDt><Dt>2024-01-07",
+    );
+    assert!(statement::parse(&broken, StatementFormat::Camt053).is_err());
+    let broken = CAMT053.replace("<CdtDbtInd>CRDT</CdtDbtInd>", "");
+    let err = statement::parse(&broken, StatementFormat::Camt053).unwrap_err();
+    assert!(format!("{err:#}").starts_with("entry 2:"), "{err:#}");
+}
+
+#[cfg(feature = "memory-backend")]
+mod memory {
+    use super::{common, CAMT053, MT940};
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{DryRun, ImportBankStatementInput, TransactionFilters};
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use rmcp::handler::server::wrapper::Parameters;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_import_bank_statement_skips_entries_imported_before() {
+        let database = Arc::new(MemoryDatabase::new());
+        let account = database
+            .upsert_account(&common::sample_account_input())
+            .await
+            .unwrap();
+        let server = ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        );
+        let import = |statement: &str| {
+            let input = ImportBankStatementInput {
+                account_id: account.id.clone(),
+                statement: statement.to_string(),
+                format: None,
+                currency: String::new(),
+            };
+            server.import_bank_statement(Parameters(DryRun::from(input)))
+        };
+
+        let first = import(CAMT053).await.unwrap();
+        let first = first.structured_content.unwrap();
+        assert_eq!(first["format"], "camt053");
+        assert_eq!(first["transactions"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            first["transactions"][0]["description"],
+            "Café & Bar - Lunch team"
+        );
+        assert_eq!(first["skipped"][0]["reason"], "pending");
+
+        // The pending entry has been booked by the next download.
+        let booked = CAMT053.replace("<Sts>PDNG</Sts>", "<Sts>BOOK</Sts>");
+        let second = import(&booked).await.unwrap();
+        let second = second.structured_content.unwrap();
+        let imported = second["transactions"].as_array().unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0]["amount"], 5.0);
+        let skipped = second["skipped"].as_array().unwrap();
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0]["reason"], "duplicate");
+        assert_eq!(skipped[0]["reference"], "REF-1");
+
+        let mt940 = import(MT940).await.unwrap();
+        let mt940 = mt940.structured_content.unwrap();
+        assert_eq!(mt940["format"], "mt940");
+        assert_eq!(mt940["transactions"].as_array().unwrap().len(), 3);
+        let again = import(MT940).await.unwrap();
+        let again = again.structured_content.unwrap();
+        assert_eq!(again["transactions"], serde_json::json!([]));
+
+        let filters = TransactionFilters::default();
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 6);
+    }
+}
@@ -0,0 +1,129 @@
+//! Tests for the watched stdio transport.
+
+use exaspoon_db_mcp::stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+#[tokio::test]
+async fn test_closed_stdin_is_held_back_until_the_server_stops() {
+    let (mut client, input) = tokio::io::duplex(64);
+    let (mut input, _output, monitor) = stdio::monitor(input, tokio::io::sink());
+
+    client.write_all(b"{}\n").await.unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(input.read(&mut buf).await.unwrap(), 3);
+
+    drop(client);
+    let read = tokio::time::timeout(Duration::from_millis(50), input.read(&mut buf)).await;
+    assert!(read.is_err(), "end of input reached the service: {read:?}");
+    assert_eq!(monitor.closed().await, "stdin closed");
+}
+
+#[tokio::test]
+async fn test_writes_to_a_closed_stdout_are_dropped() {
+    let (client, output) = tokio::io::duplex(64);
+    let (_input, mut output, monitor) = stdio::monitor(tokio::io::empty(), output);
+
+    drop(client);
+    output.write_all(b"{}\n").await.unwrap();
+    output.flush().await.unwrap();
+    assert_eq!(monitor.closed().await, "stdout closed");
+}
+
+#[tokio::test]
+async fn test_idle_resolves_once_the_client_stops_writing() {
+    let (mut client, input) = tokio::io::duplex(64);
+    let (mut input, _output, monitor) = stdio::monitor(input, tokio::io::sink());
+    let timeout = Duration::from_millis(200);
+
+    let reader = tokio::spawn(async move {
+        let mut buf = [0; 8];
+        while input.read(&mut buf).await.is_ok() {}
+    });
+    // Writing keeps the client from counting as idle.
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.write_all(b"{}\n").await.unwrap();
+    }
+    let idle = tokio::time::timeout(Duration::from_millis(100), monitor.idle(Some(timeout)));
+    assert!(idle.await.is_err());
+    tokio::time::timeout(timeout * 2, monitor.idle(Some(timeout)))
+        .await
+        .unwrap();
+
+    let never = tokio::time::timeout(Duration::from_millis(50), monitor.idle(None));
+    assert!(never.await.is_err());
+    reader.abort();
+}
+
+const TOOL_CALL: &str = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call"}"#;
+
+/// Sends `message` from the client and has the server read it.
+async fn send<R: AsyncRead + Unpin>(client: &mut DuplexStream, input: &mut R, message: &str) {
+    let line = format!("{message}\n");
+    client.write_all(line.as_bytes()).await.unwrap();
+    let mut read = vec![0; line.len()];
+    input.read_exact(&mut read).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_requests_count_until_answered_or_cancelled() {
+    let (mut client, input) = tokio::io::duplex(1024);
+    let (mut input, mut output, monitor) = stdio::monitor(input, tokio::io::sink());
+
+    send(&mut client, &mut input, TOOL_CALL).await;
+    send(
+        &mut client,
+        &mut input,
+        r#"{"jsonrpc":"2.0","id":"two","method":"ping"}"#,
+    )
+    .await;
+    let initialized = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    send(&mut client, &mut input, initialized).await;
+    assert_eq!(monitor.unanswered(), 2);
+    let answered = tokio::time::timeout(Duration::from_millis(50), monitor.answered());
+    assert!(answered.await.is_err());
+
+    // Requests of the server's own answer nothing, and a response counts
+    // once all of it is written.
+    output
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"elicitation/create\"}\n")
+        .await
+        .unwrap();
+    output
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,")
+        .await
+        .unwrap();
+    assert_eq!(monitor.unanswered(), 2);
+    output.write_all(b"\"result\":{}}\n").await.unwrap();
+    assert_eq!(monitor.unanswered(), 1);
+
+    let cancelled =
+        r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":"two"}}"#;
+    send(&mut client, &mut input, cancelled).await;
+    assert_eq!(monitor.unanswered(), 0);
+    tokio::time::timeout(Duration::from_millis(50), monitor.answered())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_running_requests_keep_the_client_from_idling() {
+    let (mut client, input) = tokio::io::duplex(1024);
+    let (mut input, mut output, monitor) = stdio::monitor(input, tokio::io::sink());
+    let timeout = Duration::from_millis(100);
+
+    send(&mut client, &mut input, TOOL_CALL).await;
+    let idle = tokio::time::timeout(timeout * 3, monitor.idle(Some(timeout)));
+    assert!(idle.await.is_err());
+
+    output
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{}}\n")
+        .await
+        .unwrap();
+    let answered = std::time::Instant::now();
+    tokio::time::timeout(timeout * 3, monitor.idle(Some(timeout)))
+        .await
+        .unwrap();
+    assert!(answered.elapsed() >= timeout * 9 / 10);
+}
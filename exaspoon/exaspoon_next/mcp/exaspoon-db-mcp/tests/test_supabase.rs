@@ -1,12 +1,8 @@
 //! Tests for database operations.
 
 use exaspoon_db_mcp::embedding::Embedder;
-use exaspoon_db_mcp::models::{
-    AccountType, CategoryKind, CreateTransactionInput, ListAccountsInput, SearchSimilarInput,
-    TransactionDirection, UpsertAccountInput, UpsertCategoryInput,
-};
+use exaspoon_db_mcp::models::{AccountType, CategoryKind};
 use exaspoon_db_mcp::supabase::Database;
-use serde_json::json;
 
 mod common;
 
@@ -22,7 +18,7 @@ async fn test_mock_database_insert_transaction() {
     )
     .await
     .unwrap();
-    assert_eq!(result, json!({ "id": "txn-default" }));
+    assert_eq!(result.id, "txn-default");
 
     let inserted = db.inserted_transactions();
     assert_eq!(inserted.len(), 1);
@@ -42,7 +38,8 @@ async fn test_mock_database_upsert_category() {
     )
     .await
     .unwrap();
-    assert_eq!(result, json!({ "id": "cat-default" }));
+    assert_eq!(result.id, "cat-default");
+    assert_eq!(result.kind, CategoryKind::Expense);
 
     let upserted = db.upserted_categories();
     assert_eq!(upserted.len(), 1);
@@ -62,7 +59,8 @@ async fn test_mock_database_upsert_account() {
     )
     .await
     .unwrap();
-    assert_eq!(result, json!({ "id": "acct-default" }));
+    assert_eq!(result.id, "acct-default");
+    assert_eq!(result.r#type, AccountType::Offchain);
 
     let upserted = db.upserted_accounts();
     assert_eq!(upserted.len(), 1);
@@ -79,7 +77,14 @@ async fn test_mock_database_list_accounts() {
     let params = exaspoon_db_mcp::models::ListAccountsInput {
         r#type: Some(AccountType::Offchain),
         search: Some("Test".to_string()),
+        ..Default::default()
     };
+    db.configure(|state| {
+        state.accounts = vec![
+            common::sample_named_account("acct-1", "Test Account 1"),
+            common::sample_named_account("acct-2", "Test Account 2"),
+        ];
+    });
 
     let result = db.list_accounts(
         &params
@@ -87,8 +92,8 @@ async fn test_mock_database_list_accounts() {
     .await
     .unwrap();
     assert_eq!(result, vec![
-        json!({ "id": "acct-1", "name": "Test Account 1" }),
-        json!({ "id": "acct-2", "name": "Test Account 2" }),
+        common::sample_named_account("acct-1", "Test Account 1"),
+        common::sample_named_account("acct-2", "Test Account 2"),
     ]);
 
     let list_params = db.account_list_params();
@@ -106,8 +111,8 @@ async fn test_mock_database_search_similar_transactions() {
     // Configure mock database to return specific matches
     db.configure(|state| {
         state.transaction_matches = vec![
-            json!({ "id": "txn-1", "description": "Coffee shop" }),
-            json!({ "id": "txn-2", "description": "Cafe" }),
+            common::sample_transaction_match("txn-1", "Coffee shop"),
+            common::sample_transaction_match("txn-2", "Cafe"),
         ];
     });
 
@@ -117,8 +122,8 @@ async fn test_mock_database_search_similar_transactions() {
     .await
     .unwrap();
     assert_eq!(result, vec![
-        json!({ "id": "txn-1", "description": "Coffee shop" }),
-        json!({ "id": "txn-2", "description": "Cafe" }),
+        common::sample_transaction_match("txn-1", "Coffee shop"),
+        common::sample_transaction_match("txn-2", "Cafe"),
     ]);
 
     let search_limits = db.transaction_search_limits();
@@ -134,8 +139,8 @@ async fn test_mock_database_search_similar_categories() {
     // Configure mock database to return specific matches
     db.configure(|state| {
         state.category_matches = vec![
-            json!({ "id": "cat-1", "name": "Food" }),
-            json!({ "id": "cat-2", "name": "Dining" }),
+            common::sample_category_match("cat-1", "Food"),
+            common::sample_category_match("cat-2", "Dining"),
         ];
     });
 
@@ -145,8 +150,8 @@ async fn test_mock_database_search_similar_categories() {
     .await
     .unwrap();
     assert_eq!(result, vec![
-        json!({ "id": "cat-1", "name": "Food" }),
-        json!({ "id": "cat-2", "name": "Dining" }),
+        common::sample_category_match("cat-1", "Food"),
+        common::sample_category_match("cat-2", "Dining"),
         ]);
 }
 
@@ -195,20 +200,20 @@ async fn test_mock_database_configure_custom_state() {
 
     // Configure custom state
     db.configure(|state| {
-        state.transaction_response = json!({ "id": "custom-txn" });
-        state.category_response = json!({ "id": "custom-cat" });
-        state.account_response = json!({ "id": "custom-acct" });
+        state.transaction_response = common::sample_transaction("custom-txn");
+        state.category_response = common::sample_category("custom-cat");
+        state.account_response = common::sample_account("custom-acct");
         state.accounts = vec![
-            json!({ "id": "acct-1", "name": "Custom Account 1" }),
-            json!({ "id": "acct-2", "name": "Custom Account 2" }),
+            common::sample_named_account("acct-1", "Custom Account 1"),
+            common::sample_named_account("acct-2", "Custom Account 2"),
         ];
         state.transaction_matches = vec![
-            json!({ "id": "txn-1", "description": "Custom Transaction" }),
-            json!({ "id": "txn-2", "description": "Custom Transaction" }),
+            common::sample_transaction_match("txn-1", "Custom Transaction"),
+            common::sample_transaction_match("txn-2", "Custom Transaction"),
         ];
         state.category_matches = vec![
-            json!({ "id": "cat-1", "name": "Custom Category" }),
-            json!({ "id": "cat-2", "name": "Custom Category" }),
+            common::sample_category_match("cat-1", "Custom Category"),
+            common::sample_category_match("cat-2", "Custom Category"),
         ];
     });
 
@@ -219,7 +224,7 @@ async fn test_mock_database_configure_custom_state() {
     )
     .await
     .unwrap();
-    assert_eq!(txn_result, json!({ "id": "custom-txn" }));
+    assert_eq!(txn_result.id, "custom-txn");
 
     let cat_input = common::sample_category_input();
     let cat_result = db.upsert_category(
@@ -227,7 +232,7 @@ async fn test_mock_database_configure_custom_state() {
     )
     .await
     .unwrap();
-    assert_eq!(cat_result, json!({ "id": "custom-cat" }));
+    assert_eq!(cat_result.id, "custom-cat");
 
     let acct_input = common::sample_account_input();
     let acct_result = db.upsert_account(
@@ -235,7 +240,7 @@ async fn test_mock_database_configure_custom_state() {
     )
     .await
     .unwrap();
-    assert_eq!(acct_result, json!({ "id": "custom-acct" }));
+    assert_eq!(acct_result.id, "custom-acct");
 
     let list_result = db.list_accounts(
         &exaspoon_db_mcp::models::ListAccountsInput::default()
@@ -243,8 +248,8 @@ async fn test_mock_database_configure_custom_state() {
     .await
     .unwrap();
     assert_eq!(list_result, vec![
-        json!({ "id": "acct-1", "name": "Custom Account" }),
-        json!({ "id": "acct-2", "name": "Custom Account 2" }),
+        common::sample_named_account("acct-1", "Custom Account 1"),
+        common::sample_named_account("acct-2", "Custom Account 2"),
         ]);
 
     let search_result = db.search_similar_transactions(
@@ -253,6 +258,7 @@ async fn test_mock_database_configure_custom_state() {
     .await
     .unwrap();
     assert_eq!(search_result, vec![
-        json!({ "id": "txn-1", "description": "Custom Transaction" })
+        common::sample_transaction_match("txn-1", "Custom Transaction"),
+        common::sample_transaction_match("txn-2", "Custom Transaction"),
     ]);
 }
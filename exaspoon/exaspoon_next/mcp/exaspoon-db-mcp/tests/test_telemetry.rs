@@ -0,0 +1,48 @@
+//! Tests for exporting spans over OTLP.
+#![cfg(feature = "otel")]
+
+use exaspoon_db_mcp::config::TracingConfig;
+use exaspoon_db_mcp::telemetry::OtlpExporter;
+use std::collections::HashMap;
+use tracing_subscriber::layer::SubscriberExt;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn test_export_is_off_without_an_endpoint() {
+    let exporter = OtlpExporter::from_config(&TracingConfig::default()).unwrap();
+    assert!(exporter.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_spans_are_posted_to_the_collector_with_its_headers() {
+    let collector = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/traces"))
+        .and(header("x-api-key", "secret"))
+        .and(header("content-type", "application/x-protobuf"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1..)
+        .mount(&collector)
+        .await;
+
+    let config = TracingConfig {
+        otlp_endpoint: Some(format!("{}/v1/traces", collector.uri())),
+        otlp_headers: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+        service_name: "exaspoon-test".to_string(),
+        ..Default::default()
+    };
+    // Building, recording and flushing block on the exporter's own thread.
+    tokio::task::spawn_blocking(move || {
+        let exporter = OtlpExporter::from_config(&config).unwrap().unwrap();
+        let subscriber = tracing_subscriber::registry().with(exporter.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("tool_call", tool = "ping").in_scope(|| {});
+        });
+        drop(exporter);
+    })
+    .await
+    .unwrap();
+
+    collector.verify().await;
+}
@@ -0,0 +1,192 @@
+//! Tests for the checks every tool's arguments go through.
+
+use chrono::NaiveDateTime;
+use exaspoon_db_mcp::models::CreateTransactionInput;
+use exaspoon_db_mcp::validation::{
+    conform, currency_code, parse_timezone, parse_utc_offset, validate, Invalid, Mismatch, Rule,
+    MAX_DESCRIPTION_CHARS,
+};
+use rmcp::handler::server::tool::cached_schema_for_type;
+use serde_json::{json, Value};
+
+fn check(arguments: Value) -> Result<(), Invalid> {
+    validate(arguments.as_object().unwrap())
+}
+
+fn invalid(field: &str, rule: Rule) -> Result<(), Invalid> {
+    Err(Invalid {
+        field: field.to_string(),
+        rule,
+    })
+}
+
+#[test]
+fn test_well_formed_arguments_pass() {
+    let transaction = json!({
+        "account_id": "acct-1",
+        "amount": 42.5,
+        "currency": "USD",
+        "direction": "expense",
+        "occurred_at": "2024-01-02T03:04:05+02:00",
+        "description": "Coffee",
+    });
+    assert_eq!(check(transaction.clone()), Ok(()));
+    assert_eq!(check(json!({ "transactions": [transaction] })), Ok(()));
+    assert_eq!(check(json!({ "query": "coffee", "limit": 1000 })), Ok(()));
+    // Left to the handler: a missing currency is asked of the user, and an
+    // absent or null limit takes the tool's default.
+    assert_eq!(check(json!({ "currency": "", "limit": null })), Ok(()));
+}
+
+#[test]
+fn test_each_rule_names_the_offending_field() {
+    assert_eq!(
+        check(json!({ "occurred_at": "2024-01-02" })),
+        invalid("occurred_at", Rule::Rfc3339)
+    );
+    assert_eq!(check(json!({ "amount": 0 })), invalid("amount", Rule::Positive));
+    assert_eq!(check(json!({ "amount": "12" })), invalid("amount", Rule::Positive));
+    assert_eq!(check(json!({ "currency": "usd" })), invalid("currency", Rule::Currency));
+    assert_eq!(check(json!({ "currency": "E" })), invalid("currency", Rule::Currency));
+    assert_eq!(check(json!({ "currency": "US DOLLAR" })), invalid("currency", Rule::Currency));
+    assert_eq!(
+        check(json!({ "description": "x".repeat(MAX_DESCRIPTION_CHARS + 1) })),
+        invalid("description", Rule::MaxChars(MAX_DESCRIPTION_CHARS))
+    );
+    assert_eq!(check(json!({ "limit": 0 })), invalid("limit", Rule::Range(1, 1000)));
+    assert_eq!(check(json!({ "limit": 5000 })), invalid("limit", Rule::Range(1, 1000)));
+}
+
+#[test]
+fn test_asset_codes_pass_as_currencies() {
+    for currency in [
+        "USDT",
+        "USDC.E",
+        "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+        "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+    ] {
+        assert_eq!(check(json!({ "currency": currency })), Ok(()), "{currency}");
+    }
+    assert_eq!(currency_code(" usdt "), Some("USDT".to_string()));
+    assert_eq!(
+        currency_code("0xA0b86991c6218b36c1d19d4a2e9eB0cE3606eB48"),
+        Some("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string())
+    );
+    assert_eq!(currency_code("not a code"), None);
+}
+
+#[test]
+fn test_nested_arguments_are_checked_by_path() {
+    let ok = json!({ "amount": 1, "currency": "USD", "occurred_at": "2024-01-02T03:04:05Z" });
+    let bad = json!({ "amount": 1, "currency": "USD", "occurred_at": "yesterday" });
+    assert_eq!(
+        check(json!({ "transactions": [ok, bad] })),
+        invalid("transactions[1].occurred_at", Rule::Rfc3339)
+    );
+    // Unicode text is measured in characters, not bytes.
+    let description = "é".repeat(MAX_DESCRIPTION_CHARS);
+    assert_eq!(check(json!({ "category": { "description": description } })), Ok(()));
+}
+
+#[test]
+fn test_timezones_are_utc_offsets_or_iana_zones() {
+    for timezone in ["UTC", "z", "+03:00", "-0530", "+14:00", "Europe/Moscow", ""] {
+        assert_eq!(check(json!({ "timezone": timezone })), Ok(()), "{timezone}");
+    }
+    for timezone in ["Europe/Atlantis", "+3", "+15:00", "03:00"] {
+        assert_eq!(
+            check(json!({ "timezone": timezone })),
+            invalid("timezone", Rule::Timezone),
+            "{timezone}"
+        );
+    }
+    let offset = parse_utc_offset("-05:30").unwrap();
+    assert_eq!(offset.local_minus_utc(), -(5 * 3600 + 30 * 60));
+}
+
+#[test]
+fn test_iana_timezones_follow_daylight_saving_time() {
+    let berlin = parse_timezone("Europe/Berlin").unwrap();
+    let local = |text: &str| {
+        let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M").unwrap();
+        berlin.localize(naive).unwrap().to_rfc3339()
+    };
+    assert_eq!(local("2024-01-15T09:30"), "2024-01-15T09:30:00+01:00");
+    assert_eq!(local("2024-07-15T09:30"), "2024-07-15T09:30:00+02:00");
+    // Skipped when clocks go forward, and repeated when they go back.
+    assert_eq!(local("2024-03-31T02:30"), "2024-03-31T03:30:00+02:00");
+    assert_eq!(local("2024-10-27T02:30"), "2024-10-27T02:30:00+02:00");
+}
+
+#[test]
+fn test_free_form_rpc_params_are_not_checked() {
+    let arguments = json!({
+        "function": "monthly_budget",
+        "params": { "amount": -50, "currency": "usd" },
+    });
+    assert_eq!(check(arguments), Ok(()));
+}
+
+#[test]
+fn test_free_form_import_records_are_left_to_the_tool() {
+    let arguments = json!({ "records": [{ "amount": -3, "currency": "usd" }] });
+    assert_eq!(check(arguments), Ok(()));
+}
+
+#[test]
+fn test_values_are_checked_against_the_transaction_schema() {
+    let schema = cached_schema_for_type::<CreateTransactionInput>();
+    let mismatch = |field: &str, message: &str| {
+        Err(Mismatch {
+            field: field.to_string(),
+            message: message.to_string(),
+        })
+    };
+    let transaction = json!({
+        "amount": 12.5,
+        "direction": "expense",
+        "occurred_at": "2024-01-02T03:04:05Z",
+        "description": null,
+    });
+    assert_eq!(conform(&schema, "records[0]", &transaction), Ok(()));
+
+    assert_eq!(
+        conform(&schema, "records[1]", &json!(["not", "an", "object"])),
+        mismatch("records[1]", "records[1] must be an object")
+    );
+    assert_eq!(
+        conform(
+            &schema,
+            "records[2]",
+            &json!({ "amount": 1, "direction": "expense" })
+        ),
+        mismatch(
+            "records[2].occurred_at",
+            "records[2].occurred_at is required"
+        )
+    );
+    let mut wrong = transaction.clone();
+    wrong["amount"] = json!("12.50");
+    assert_eq!(
+        conform(&schema, "records[3]", &wrong),
+        mismatch("records[3].amount", "records[3].amount must be a number")
+    );
+    wrong = transaction.clone();
+    wrong["direction"] = json!("refund");
+    assert_eq!(
+        conform(&schema, "records[4]", &wrong),
+        mismatch(
+            "records[4].direction",
+            "records[4].direction must be one of income, expense, transfer"
+        )
+    );
+    wrong = transaction;
+    wrong["raw_source"] = json!(42);
+    assert_eq!(
+        conform(&schema, "records[5]", &wrong),
+        mismatch(
+            "records[5].raw_source",
+            "records[5].raw_source must be a string or null"
+        )
+    );
+}
@@ -0,0 +1,141 @@
+//! Tests for the WebSocket transport, driven with a plain WebSocket client.
+#![cfg(all(feature = "websocket", feature = "memory-backend"))]
+
+use exaspoon_db_mcp::memory::MemoryDatabase;
+use exaspoon_db_mcp::origin::AllowedHosts;
+use exaspoon_db_mcp::server::ExaspoonDbServer;
+use exaspoon_db_mcp::websocket;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Error, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+struct WsClient {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl WsClient {
+    async fn connect(url: &str) -> Self {
+        let (socket, _) = connect_async(url).await.unwrap();
+        let mut client = Self { socket };
+        client.send(common::initialize_request(0)).await;
+        let initialized = client.until_response(0).await;
+        assert_eq!(
+            initialized["result"]["protocolVersion"],
+            common::PROTOCOL_VERSION
+        );
+        client.send(common::initialized_notification()).await;
+        client
+    }
+
+    async fn send(&mut self, message: Value) {
+        self.socket
+            .send(Message::text(message.to_string()))
+            .await
+            .unwrap();
+    }
+
+    async fn until_response(&mut self, id: u64) -> Value {
+        loop {
+            let frame = self.socket.next().await.unwrap().unwrap();
+            let Message::Text(text) = frame else {
+                continue;
+            };
+            let message: Value = serde_json::from_str(&text).unwrap();
+            if message["id"] == json!(id) && message.get("method").is_none() {
+                return message;
+            }
+        }
+    }
+
+    async fn call(&mut self, id: u64, name: &str, arguments: Value) -> Value {
+        self.send(common::tool_call(id, name, arguments)).await;
+        self.until_response(id).await
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_clients_get_sessions_of_their_own() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(websocket::serve(
+        server,
+        listener,
+        AllowedHosts::default(),
+        stop.clone(),
+    ));
+
+    let mut first = WsClient::connect(&url).await;
+    let mut second = WsClient::connect(&url).await;
+
+    let pong = first.call(1, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    // Defaults one client sets do not leak into another's session.
+    let set = first
+        .call(2, "set_session_defaults", json!({ "currency": "EUR" }))
+        .await;
+    assert_eq!(
+        set["result"]["structuredContent"]["defaults"]["currency"],
+        "EUR"
+    );
+    let other = second.call(1, "set_session_defaults", json!({})).await;
+    assert_eq!(other["result"]["structuredContent"]["defaults"], json!({}));
+
+    // A malformed frame is skipped without closing the session.
+    first.socket.send(Message::text("not json")).await.unwrap();
+    let pong = first.call(3, "ping", json!({})).await;
+    assert!(pong["result"].is_object(), "{pong}");
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_websocket_refuses_foreign_origins() {
+    let server = ExaspoonDbServer::new(
+        Arc::new(MemoryDatabase::new()),
+        Arc::new(common::MockEmbedder::new(vec![0.3, 0.4])),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let stop = CancellationToken::new();
+    let serving = tokio::spawn(websocket::serve(
+        server,
+        listener,
+        AllowedHosts::default(),
+        stop.clone(),
+    ));
+
+    // A page on another site the user happens to have open.
+    let mut request = url.as_str().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("origin", "https://evil.example".parse().unwrap());
+    match connect_async(request).await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 403),
+        other => panic!("handshake from a foreign origin was not refused: {other:?}"),
+    }
+
+    let mut request = url.as_str().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("origin", "http://localhost:3000".parse().unwrap());
+    connect_async(request).await.unwrap();
+
+    stop.cancel();
+    serving.await.unwrap().unwrap();
+}
@@ -0,0 +1,593 @@
+//! Tests for reading YNAB budgets, `import_ynab` and `export_ynab`.
+
+use exaspoon_db_mcp::config::{HttpClientConfig, YnabConfig};
+use exaspoon_db_mcp::models::TransactionDirection;
+use exaspoon_db_mcp::ynab::{self, Interrupted, YnabClient, YnabError};
+use serde_json::json;
+use wiremock::matchers::{body_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+/// A register export with a paycheck, a transfer to savings, a purchase split
+/// across two categories and a refund, YNAB's BOM and quoting included.
+const REGISTER: &str = "\u{feff}\"Account\",\"Flag\",\"Date\",\"Payee\",\"Category Group/Category\",\"Category Group\",\"Category\",\"Memo\",\"Outflow\",\"Inflow\",\"Cleared\"\r
+\"Checking\",\"\",\"01/15/2024\",\"Grocer, Inc\",\"Everyday: Groceries\",\"Everyday\",\"Groceries\",\"Split (1/2) Bread\",$12.50,$0.00,\"Cleared\"\r
+\"Checking\",\"\",\"01/15/2024\",\"Grocer, Inc\",\"Everyday: Household\",\"Everyday\",\"Household\",\"Split (2/2) \"\"Soap\"\"\",$4.00,$0.00,\"Cleared\"\r
+\"Checking\",\"\",\"01/01/2024\",\"Employer\",\"Inflow: Ready to Assign\",\"Inflow\",\"Ready to Assign\",\"\",$0.00,\"$1,500.00\",\"Cleared\"\r
+\"Checking\",\"\",\"01/20/2024\",\"Transfer : Savings\",\"\",\"\",\"\",\"\",$200.00,$0.00,\"Cleared\"\r
+\"Savings\",\"\",\"01/20/2024\",\"Transfer : Checking\",\"\",\"\",\"\",\"\",$0.00,$200.00,\"Cleared\"\r
+\"Checking\",\"\",\"01/25/2024\",\"Grocer, Inc\",\"Everyday: Groceries\",\"Everyday\",\"Groceries\",\"Refund\",$0.00,$3.25,\"Cleared\"\r
+";
+
+/// The plan export of the same budget, with a category nothing was spent on.
+const PLAN: &str = "\"Month\",\"Category Group/Category\",\"Category Group\",\"Category\",\"Assigned\",\"Activity\",\"Available\"
+\"Jan 2024\",\"Inflow: Ready to Assign\",\"Inflow\",\"Ready to Assign\",$0.00,$0.00,$0.00
+\"Jan 2024\",\"Everyday: Groceries\",\"Everyday\",\"Groceries\",$300.00,-$9.25,$290.75
+\"Jan 2024\",\"Bills: Rent\",\"Bills\",\"Rent\",$900.00,$0.00,$900.00
+";
+
+fn client(api: &MockServer) -> YnabClient {
+    YnabClient::new(
+        &YnabConfig {
+            token: "ynab-token".to_string(),
+            budget_id: Some("budget-1".to_string()),
+            base_url: api.uri(),
+        },
+        &HttpClientConfig::default(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_register_export_is_read_oldest_first() {
+    let budget = ynab::parse_export(REGISTER, None, false, 2).unwrap();
+    let transactions = budget.transactions;
+    assert_eq!(transactions.len(), 6);
+
+    let paycheck = &transactions[0];
+    assert_eq!(paycheck.date, "2024-01-01T00:00:00Z");
+    assert_eq!(paycheck.amount, 1500.0);
+    assert_eq!(paycheck.category, None);
+    assert_eq!(paycheck.direction(), TransactionDirection::Income);
+
+    let bread = &transactions[1];
+    assert_eq!(bread.account, "Checking");
+    assert_eq!(bread.payee.as_deref(), Some("Grocer, Inc"));
+    assert_eq!(bread.category.as_deref(), Some("Groceries"));
+    assert_eq!(bread.memo.as_deref(), Some("Bread"));
+    assert_eq!(bread.amount, -12.5);
+    assert_eq!(bread.direction(), TransactionDirection::Expense);
+    assert_eq!(transactions[2].memo.as_deref(), Some("\"Soap\""));
+    assert_eq!(transactions[2].category.as_deref(), Some("Household"));
+
+    let to_savings = &transactions[3];
+    assert!(to_savings.transfer);
+    assert_eq!(to_savings.category, None);
+    assert_eq!(to_savings.direction(), TransactionDirection::Transfer);
+    assert_eq!(transactions[4].account, "Savings");
+    assert_eq!(transactions[4].amount, 200.0);
+    assert_eq!(transactions[5].amount, 3.25);
+
+    let names: Vec<_> = budget
+        .categories
+        .iter()
+        .map(|category| (category.group.as_str(), category.name.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        [("Everyday", "Groceries"), ("Everyday", "Household")]
+    );
+}
+
+#[test]
+fn test_register_ids_are_stable_and_tell_identical_rows_apart() {
+    let first = ynab::parse_export(REGISTER, None, false, 2).unwrap();
+    let again = ynab::parse_export(REGISTER, None, false, 2).unwrap();
+    let ids = |budget: &ynab::Budget| {
+        budget
+            .transactions
+            .iter()
+            .map(|transaction| transaction.id.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&first), ids(&again));
+    assert!(ids(&first)[0].starts_with("register-"));
+
+    let twice = format!(
+        "{REGISTER}\"Checking\",\"\",\"01/25/2024\",\"Grocer, Inc\",\"Everyday: Groceries\",\"Everyday\",\"Groceries\",\"Refund\",$0.00,$3.25,\"Cleared\"\r\n"
+    );
+    let twice = ynab::parse_export(&twice, None, false, 2).unwrap();
+    let refunds: Vec<_> = twice
+        .transactions
+        .iter()
+        .filter(|transaction| transaction.memo.as_deref() == Some("Refund"))
+        .map(|transaction| transaction.id.as_str())
+        .collect();
+    assert_eq!(refunds.len(), 2);
+    assert_ne!(refunds[0], refunds[1]);
+    assert_eq!(refunds[0], ids(&first)[5]);
+
+    // Filing the refund elsewhere or editing its memo in YNAB keeps its id.
+    let edited = REGISTER.replace(
+        "\"Everyday: Groceries\",\"Everyday\",\"Groceries\",\"Refund\"",
+        "\"Everyday: Household\",\"Everyday\",\"Household\",\"Returned soap\"",
+    );
+    let edited = ynab::parse_export(&edited, None, false, 2).unwrap();
+    assert_eq!(
+        edited.transactions[5].memo.as_deref(),
+        Some("Returned soap")
+    );
+    assert_eq!(ids(&edited), ids(&first));
+}
+
+#[test]
+fn test_plan_export_adds_unused_categories() {
+    let budget = ynab::parse_export(REGISTER, Some(PLAN), false, 2).unwrap();
+    let names: Vec<_> = budget
+        .categories
+        .iter()
+        .map(|category| category.name.as_str())
+        .collect();
+    assert_eq!(names, ["Groceries", "Rent", "Household"]);
+    assert!(budget
+        .categories
+        .iter()
+        .all(|category| category.id.is_none()));
+}
+
+#[test]
+fn test_register_amounts_and_dates_in_other_formats() {
+    let register = "Account,Date,Payee,Category Group,Category,Memo,Outflow,Inflow
+Girokonto,05.02.2024,Bäckerei,Alltag,Lebensmittel,,\"1.234,56 €\",
+Girokonto,06/02/2024,Bäckerei,Alltag,Lebensmittel,,\"2,50 €\",
+Girokonto,07.02.2024,Möbelhaus,Wohnen,Einrichtung,,\"1.234 €\",
+Girokonto,08.02.2024,Kiosk,Alltag,Lebensmittel,,\"3.5 €\",
+";
+    let budget = ynab::parse_export(register, None, true, 2).unwrap();
+    assert_eq!(budget.transactions[0].date, "2024-02-05T00:00:00Z");
+    assert_eq!(budget.transactions[0].amount, -1234.56);
+    assert_eq!(budget.transactions[1].date, "2024-02-06T00:00:00Z");
+    assert_eq!(budget.transactions[1].amount, -2.5);
+    assert_eq!(budget.transactions[2].amount, -1234.0);
+    assert_eq!(budget.transactions[3].amount, -3.5);
+
+    let register = "Account,Date,Payee,Category Group,Category,Memo,Outflow,Inflow
+Checking,2024-02-05,Grocer,Everyday,Groceries,,1.500 KWD,
+Checking,2024-02-06,Grocer,Everyday,Groceries,,\"1,234.125 KWD\",
+";
+    let budget = ynab::parse_export(register, None, false, 3).unwrap();
+    assert_eq!(budget.transactions[0].amount, -1.5);
+    assert_eq!(budget.transactions[1].amount, -1234.125);
+
+    let err = ynab::parse_export(
+        "Account,Date,Outflow\nChecking,2024-01-01,1\n",
+        None,
+        false,
+        2,
+    )
+    .unwrap_err();
+    assert_eq!(format!("{err:#}"), "register: no Inflow column");
+    let err = ynab::parse_export(
+        "Account,Date,Outflow,Inflow\nChecking,someday,1,0\n",
+        None,
+        false,
+        2,
+    )
+    .unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "register row 1: \"someday\" is not a date"
+    );
+}
+
+#[test]
+fn test_transaction_id_is_read_back_from_the_raw_source() {
+    let budget = ynab::parse_export(REGISTER, None, false, 2).unwrap();
+    let bread = &budget.transactions[1];
+    let input = bread.to_input("acct-1", "USD");
+    assert_eq!(input.amount, 12.5);
+    assert_eq!(input.direction, TransactionDirection::Expense);
+    assert_eq!(input.description.as_deref(), Some("Grocer, Inc - Bread"));
+    assert_eq!(
+        ynab::transaction_id(input.raw_source.as_deref().unwrap()).as_deref(),
+        Some(bread.id.as_str())
+    );
+    assert_eq!(ynab::transaction_id("plain text"), None);
+    assert_eq!(ynab::transaction_id("{\"fitid\":\"1\"}"), None);
+}
+
+#[tokio::test]
+async fn test_budget_is_read_through_the_api() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/budgets/budget-1/categories"))
+        .and(header("authorization", "Bearer ynab-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {
+            "category_groups": [
+                { "name": "Internal Master Category", "hidden": false, "deleted": false, "categories": [
+                    { "id": "rta", "name": "Inflow: Ready to Assign", "hidden": false, "deleted": false },
+                ] },
+                { "name": "Everyday", "hidden": false, "deleted": false, "categories": [
+                    { "id": "c-groceries", "name": "Groceries", "hidden": false, "deleted": false },
+                    { "id": "c-old", "name": "Old", "hidden": false, "deleted": true },
+                    { "id": "c-household", "name": "Household", "hidden": false, "deleted": false },
+                ] },
+            ],
+            "server_knowledge": 10,
+        } })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/budgets/budget-1/transactions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {
+            "transactions": [
+                {
+                    "id": "t-split", "date": "2024-01-15", "amount": -16500, "memo": "Weekly shop",
+                    "account_name": "Checking", "payee_name": "Grocer",
+                    "category_name": "Split (Multiple Categories)...", "transfer_account_id": null,
+                    "deleted": false,
+                    "subtransactions": [
+                        { "id": "s-1", "amount": -12500, "memo": null, "payee_name": null,
+                          "category_name": "Groceries", "transfer_account_id": null, "deleted": false },
+                        { "id": "s-2", "amount": -4000, "memo": "Soap", "payee_name": null,
+                          "category_name": "Household", "transfer_account_id": null, "deleted": false },
+                    ],
+                },
+                {
+                    "id": "t-pay", "date": "2024-01-01", "amount": 1500000, "memo": null,
+                    "account_name": "Checking", "payee_name": "Employer",
+                    "category_name": "Inflow: Ready to Assign", "transfer_account_id": null,
+                    "deleted": false, "subtransactions": [],
+                },
+                {
+                    "id": "t-gone", "date": "2024-01-02", "amount": -1000, "memo": null,
+                    "account_name": "Checking", "payee_name": "Cafe", "category_name": "Groceries",
+                    "transfer_account_id": null, "deleted": true, "subtransactions": [],
+                },
+                {
+                    "id": "t-move", "date": "2024-01-20", "amount": -200000, "memo": null,
+                    "account_name": "Checking", "payee_name": "Transfer : Savings",
+                    "category_name": null, "transfer_account_id": "a-savings",
+                    "deleted": false, "subtransactions": [],
+                },
+            ],
+            "server_knowledge": 10,
+        } })))
+        .mount(&api)
+        .await;
+
+    let budget = client(&api).budget("budget-1").await.unwrap();
+    let names: Vec<_> = budget
+        .categories
+        .iter()
+        .map(|category| (category.id.as_deref().unwrap(), category.name.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        [("c-groceries", "Groceries"), ("c-household", "Household")]
+    );
+
+    let transactions = &budget.transactions;
+    let ids: Vec<_> = transactions
+        .iter()
+        .map(|transaction| transaction.id.as_str())
+        .collect();
+    assert_eq!(ids, ["t-pay", "s-1", "s-2", "t-move"]);
+    assert_eq!(transactions[0].amount, 1500.0);
+    assert_eq!(transactions[0].category, None);
+    assert_eq!(transactions[1].payee.as_deref(), Some("Grocer"));
+    assert_eq!(transactions[1].memo.as_deref(), Some("Weekly shop"));
+    assert_eq!(transactions[1].category.as_deref(), Some("Groceries"));
+    assert_eq!(transactions[1].amount, -12.5);
+    assert_eq!(transactions[2].memo.as_deref(), Some("Soap"));
+    assert_eq!(transactions[2].date, "2024-01-15T00:00:00Z");
+    assert!(transactions[3].transfer);
+}
+
+#[tokio::test]
+async fn test_add_categories_adds_the_group_once_and_errors_carry_the_api_error() {
+    let api = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/budgets/budget-1/categories"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {
+            "category_groups": [
+                { "id": "g-everyday", "name": "Everyday", "hidden": false, "deleted": false,
+                  "categories": [] },
+            ],
+        } })))
+        .mount(&api)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/budgets/budget-1/category_groups"))
+        .and(body_json(
+            json!({ "category_group": { "name": "Exaspoon" } }),
+        ))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "data": {
+            "category_group": { "id": "g-exaspoon", "name": "Exaspoon" },
+        } })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    for (name, group, id, times) in [
+        ("Travel", "g-exaspoon", "c-travel", 1),
+        ("Gifts", "g-everyday", "c-gifts", 2),
+    ] {
+        Mock::given(method("POST"))
+            .and(path("/budgets/budget-1/categories"))
+            .and(body_json(json!({
+                "category": { "name": name, "category_group_id": group },
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "data": {
+                "category": { "id": id, "name": name, "hidden": false, "deleted": false },
+            } })))
+            .expect(times)
+            .mount(&api)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(path("/budgets/budget-1/categories"))
+        .and(body_json(json!({
+            "category": { "name": "Rent", "category_group_id": "g-everyday" },
+        })))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": { "id": "400", "name": "bad_request", "detail": "Bad request" },
+        })))
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/budgets/x%2F..%2F..%2Fuser/settings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {
+            "settings": { "currency_format": { "iso_code": "usd" } },
+        } })))
+        .expect(1)
+        .mount(&api)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/budgets/missing/settings"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "error": { "id": "404.2", "name": "resource_not_found", "detail": "Resource not found" },
+        })))
+        .mount(&api)
+        .await;
+    let client = client(&api);
+    assert_eq!(client.budget_id(), "budget-1");
+
+    let added = client
+        .add_categories("budget-1", "Exaspoon", &["Travel".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].id.as_deref(), Some("c-travel"));
+    assert_eq!(added[0].group, "Exaspoon");
+    // A group of that name in another case is reused.
+    let added = client
+        .add_categories("budget-1", "everyday", &["Gifts".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(added[0].id.as_deref(), Some("c-gifts"));
+
+    let err = client
+        .add_categories(
+            "budget-1",
+            "Everyday",
+            &["Gifts".to_string(), "Rent".to_string()],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<Interrupted>().unwrap().categories, 1);
+    assert_eq!(err.downcast_ref::<YnabError>().unwrap().status_code, 400);
+
+    // Ids are escaped rather than read as more of the path.
+    assert_eq!(client.currency("x/../../user").await.unwrap(), "USD");
+
+    let err = client.currency("missing").await.unwrap_err();
+    let rejected = err.downcast_ref::<YnabError>().unwrap();
+    assert_eq!(rejected.status_code, 404);
+    assert_eq!(rejected.id, "404.2");
+    assert_eq!(rejected.name, "resource_not_found");
+}
+
+#[cfg(feature = "memory-backend")]
+mod tool {
+    use super::{client, common, PLAN, REGISTER};
+    use exaspoon_db_mcp::memory::MemoryDatabase;
+    use exaspoon_db_mcp::models::{
+        CategoryKind, DryRun, ExportYnabInput, ImportYnabInput, ListAuditEventsInput,
+        TransactionFilters, UpsertCategoryInput,
+    };
+    use exaspoon_db_mcp::server::ExaspoonDbServer;
+    use exaspoon_db_mcp::supabase::Database;
+    use rmcp::handler::server::wrapper::Parameters;
+    use serde_json::json;
+    use std::sync::Arc;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_import_ynab_creates_accounts_and_categories_and_skips_imported_transactions() {
+        let database = Arc::new(MemoryDatabase::new());
+        let server = ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        );
+        let input = ImportYnabInput {
+            register: Some(REGISTER.to_string()),
+            plan: Some(PLAN.to_string()),
+            currency: "USD".to_string(),
+            ..Default::default()
+        };
+        let filters = TransactionFilters::default();
+
+        let err = server
+            .import_ynab(Parameters(DryRun::from(ImportYnabInput {
+                currency: String::new(),
+                ..input.clone()
+            })))
+            .await
+            .unwrap_err();
+        assert_eq!(err.data.unwrap()["field"], "currency");
+
+        let result = server
+            .import_ynab(Parameters(DryRun::from(input.clone())))
+            .await
+            .unwrap();
+        let payload = result.structured_content.unwrap();
+        assert_eq!(payload["source"], "export");
+        let accounts = payload["accounts"].as_array().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0]["name"], "Checking");
+        assert_eq!(accounts[0]["created"], true);
+        assert_eq!(accounts[0]["transactions"], 5);
+        assert_eq!(accounts[1]["name"], "Savings");
+        assert_eq!(accounts[1]["transactions"], 1);
+        let categories = payload["categories"].as_array().unwrap();
+        let names: Vec<_> = categories
+            .iter()
+            .map(|category| &category["name"])
+            .collect();
+        assert_eq!(
+            names,
+            [&json!("Groceries"), &json!("Rent"), &json!("Household")]
+        );
+        assert_eq!(categories[0]["transactions"], 2);
+        assert_eq!(categories[1]["transactions"], 0);
+
+        let transactions = payload["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 6);
+        assert_eq!(transactions[0]["direction"], "income");
+        assert_eq!(transactions[0]["category_id"], json!(null));
+        assert_eq!(transactions[1]["category_id"], categories[0]["id"]);
+        assert_eq!(transactions[1]["amount"], 12.5);
+        assert_eq!(transactions[1]["description"], "Grocer, Inc - Bread");
+        assert_eq!(transactions[3]["direction"], "transfer");
+        assert_eq!(transactions[4]["account_id"], accounts[1]["id"]);
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 6);
+        assert_eq!(database.list_categories().await.unwrap().len(), 3);
+
+        let again = server
+            .import_ynab(Parameters(DryRun::from(input)))
+            .await
+            .unwrap();
+        let again = again.structured_content.unwrap();
+        assert!(again["transactions"].as_array().unwrap().is_empty());
+        let skipped = again["skipped"].as_array().unwrap();
+        assert_eq!(skipped.len(), 6);
+        assert!(skipped
+            .iter()
+            .all(|skipped| skipped["reason"] == "duplicate"));
+        assert_eq!(again["accounts"][0]["created"], false);
+        assert_eq!(again["categories"][0]["created"], false);
+        assert_eq!(database.count_transactions(&filters).await.unwrap(), 6);
+
+        let err = server
+            .import_ynab(Parameters(DryRun::from(ImportYnabInput::default())))
+            .await
+            .unwrap_err();
+        assert!(err.data.unwrap()["details"]
+            .as_str()
+            .unwrap()
+            .contains("YNAB_TOKEN"));
+    }
+
+    #[tokio::test]
+    async fn test_export_ynab_adds_the_missing_expense_categories_to_a_group() {
+        let api = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/budgets/budget-1/categories"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": {
+                "category_groups": [
+                    { "id": "g-everyday", "name": "Everyday", "hidden": false, "deleted": false,
+                      "categories": [
+                        { "id": "c-groceries", "name": "groceries", "hidden": false },
+                    ] },
+                ],
+            } })))
+            .mount(&api)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/budgets/budget-1/category_groups"))
+            .and(body_json(
+                json!({ "category_group": { "name": "Exaspoon" } }),
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "data": {
+                "category_group": { "id": "g-exaspoon", "name": "Exaspoon" },
+            } })))
+            .expect(1)
+            .mount(&api)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/budgets/budget-1/categories"))
+            .and(body_json(json!({
+                "category": { "name": "Travel", "category_group_id": "g-exaspoon" },
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "data": {
+                "category": { "id": "c-travel", "name": "Travel" },
+            } })))
+            .expect(1)
+            .mount(&api)
+            .await;
+
+        let database = Arc::new(MemoryDatabase::new());
+        // Income categories have no place in YNAB's plan.
+        for (name, kind) in [
+            ("Groceries", CategoryKind::Expense),
+            ("Travel", CategoryKind::Expense),
+            ("Salary", CategoryKind::Income),
+        ] {
+            let input = UpsertCategoryInput {
+                name: name.to_string(),
+                kind: Some(kind),
+                ..common::sample_category_input()
+            };
+            database.upsert_category(&input, None).await.unwrap();
+        }
+        let server = ExaspoonDbServer::new(
+            database.clone(),
+            Arc::new(common::MockEmbedder::new(vec![0.1, 0.2])),
+        )
+        .with_ynab(client(&api));
+
+        let planned = server
+            .export_ynab(Parameters(DryRun {
+                input: ExportYnabInput::default(),
+                dry_run: Some(true),
+            }))
+            .await
+            .unwrap()
+            .structured_content
+            .unwrap();
+        assert_eq!(planned["dry_run"], true);
+        assert_eq!(planned["budget_id"], "budget-1");
+        assert_eq!(planned["group"], "Exaspoon");
+        assert_eq!(planned["categories"][1]["status"], "added");
+        assert_eq!(planned["categories"][1]["ynab_category_id"], json!(null));
+        assert!(api
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .all(|request| request.method.as_str() == "GET"));
+
+        let payload = server
+            .export_ynab(Parameters(DryRun::from(ExportYnabInput::default())))
+            .await
+            .unwrap()
+            .structured_content
+            .unwrap();
+        let categories = payload["categories"].as_array().unwrap();
+        assert_eq!(categories.len(), 2);
+        assert_eq!(categories[0]["category"], "Groceries");
+        assert_eq!(categories[0]["ynab_category_id"], "c-groceries");
+        assert_eq!(categories[0]["status"], "existing");
+        assert_eq!(categories[1]["category"], "Travel");
+        assert_eq!(categories[1]["ynab_category_id"], "c-travel");
+        assert_eq!(categories[1]["status"], "added");
+        let events = database
+            .list_audit_events(&ListAuditEventsInput::default())
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tool, "export_ynab");
+    }
+}